@@ -31,5 +31,8 @@ fn main() -> io::Result<()> {
         println!("cargo:rerun-if-changed={}", path.display());
     }
     output_file.write_all(b"]));\n")?;
+
+    // Rebuild if a theme is added or removed.
+    println!("cargo:rerun-if-changed=themes");
     Ok(())
 }
@@ -0,0 +1,368 @@
+//! A web view that lets the audience follow the presentation from their browsers.
+
+use crate::poll::{OpenPoll, PollRegistry};
+use serde::Serialize;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_CONNECTIONS: usize = 256;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>presenterm</title>
+<style>
+  body { margin: 0; font-family: monospace; }
+  .slide { display: flow-root; min-height: 100vh; padding: 2em; box-sizing: border-box; white-space: pre-wrap; }
+  .line { min-height: 1.2em; }
+  #polls { position: fixed; bottom: 0; left: 0; right: 0; background: #eee; color: #222; }
+  .poll { padding: 1em; }
+  .poll button { margin: 0.25em; font-family: monospace; }
+</style>
+</head>
+<body>
+<div id="slide"></div>
+<div id="polls"></div>
+<script>
+  const renderPolls = (polls) => {
+    const container = document.getElementById("polls");
+    container.replaceChildren();
+    for (const poll of polls) {
+      const key = `voted:${poll.question}`;
+      const element = document.createElement("div");
+      element.className = "poll";
+      element.append(poll.question);
+      poll.options.forEach((option, index) => {
+        const button = document.createElement("button");
+        button.textContent = option;
+        button.disabled = localStorage.getItem(key) !== null;
+        button.onclick = () => {
+          fetch(`/polls/${poll.id}/${index}`, { method: "POST" });
+          localStorage.setItem(key, option);
+          element.querySelectorAll("button").forEach((button) => button.disabled = true);
+        };
+        element.append(button);
+      });
+      container.append(element);
+    }
+  };
+  const source = new EventSource("/events");
+  source.onmessage = (event) => {
+    const data = JSON.parse(event.data);
+    document.getElementById("slide").innerHTML = data.html;
+    document.title = `presenterm (${data.current_slide}/${data.total_slides})`;
+    renderPolls(data.polls);
+  };
+</script>
+</body>
+</html>
+"#;
+
+/// The slide currently being shown to the audience.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct AudienceSlide {
+    /// The slide rendered as HTML.
+    pub(crate) html: String,
+
+    /// The current slide number, 1-index based.
+    pub(crate) current_slide: usize,
+
+    /// The total number of slides.
+    pub(crate) total_slides: usize,
+
+    /// The polls in this slide that are accepting votes.
+    pub(crate) polls: Vec<OpenPoll>,
+}
+
+#[derive(Default)]
+struct SharedState {
+    slide: Mutex<(u64, AudienceSlide)>,
+    changed: Condvar,
+    connections: AtomicUsize,
+}
+
+// Counts a connection as open for as long as it's alive.
+struct ConnectionGuard<'a>(&'a AtomicUsize);
+
+impl<'a> ConnectionGuard<'a> {
+    fn acquire(connections: &'a AtomicUsize) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| (count < MAX_CONNECTIONS).then_some(count + 1))
+            .ok()
+            .map(|_| Self(connections))
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// An HTTP server that serves a read only mirror of the current slide.
+///
+/// Browsers connected to it follow along as the presenter navigates via server sent events, and
+/// can vote on any open polls.
+pub(crate) struct AudienceServer {
+    state: Arc<SharedState>,
+}
+
+impl AudienceServer {
+    /// Start serving on the given address.
+    pub(crate) fn start(address: SocketAddr, polls: PollRegistry) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let state = Arc::new(SharedState::default());
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = server_state.clone();
+                let polls = polls.clone();
+                // Every browser following along holds a connection open, so there's a thread per connection, but
+                // only up to a point: anything past it is dropped right away.
+                thread::spawn(move || {
+                    let Some(_guard) = ConnectionGuard::acquire(&state.connections) else {
+                        return Ok(());
+                    };
+                    Self::handle_connection(stream, &state, &polls)
+                });
+            }
+        });
+        Ok(Self { state })
+    }
+
+    /// Update the slide being shown.
+    ///
+    /// Connected browsers are only notified if the slide actually changed.
+    pub(crate) fn update(&self, slide: AudienceSlide) {
+        let mut current = self.state.slide.lock().expect("lock poisoned");
+        if current.1 != slide {
+            *current = (current.0 + 1, slide);
+            self.state.changed.notify_all();
+        }
+    }
+
+    fn handle_connection(stream: TcpStream, state: &SharedState, polls: &PollRegistry) -> io::Result<()> {
+        // Clients that never finish sending their request, or that stop reading what we send them, shouldn't
+        // hold on to their connection forever.
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // The only headers we care about are the ones that tell us whether a request comes from our own page.
+        let (mut host, mut origin) = (None, None);
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            if let Some((name, value)) = header.split_once(':') {
+                let value = Some(value.trim().to_string());
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "host" => host = value,
+                    "origin" => origin = value,
+                    _ => (),
+                };
+            }
+            header.clear();
+        }
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return Self::respond(stream, "400 Bad Request", "text/plain", "");
+        };
+        match (method, path) {
+            ("GET", "/") => Self::respond(stream, "200 OK", "text/html; charset=utf-8", PAGE),
+            ("GET", "/events") => Self::stream_events(stream, state),
+            // Browsers always say where a POST comes from, so this keeps other pages from voting on behalf of
+            // whoever has them open.
+            ("POST", _) if !Self::same_origin(host.as_deref(), origin.as_deref()) => {
+                Self::respond(stream, "403 Forbidden", "text/plain", "")
+            }
+            ("POST", path) if path.starts_with("/polls/") => {
+                let voter = stream.peer_addr()?.ip();
+                match Self::parse_vote(path) {
+                    Some((id, option)) if polls.vote(id, option, voter) => {
+                        Self::respond(stream, "200 OK", "text/plain", "")
+                    }
+                    _ => Self::respond(stream, "404 Not Found", "text/plain", ""),
+                }
+            }
+            _ => Self::respond(stream, "404 Not Found", "text/plain", ""),
+        }
+    }
+
+    fn same_origin(host: Option<&str>, origin: Option<&str>) -> bool {
+        match origin {
+            Some(origin) => {
+                let origin = origin.strip_prefix("http://").unwrap_or(origin);
+                host == Some(origin)
+            }
+            // Requests that don't come from a browser don't say where they come from.
+            None => true,
+        }
+    }
+
+    fn parse_vote(path: &str) -> Option<(usize, usize)> {
+        let (id, option) = path.strip_prefix("/polls/")?.split_once('/')?;
+        Some((id.parse().ok()?, option.parse().ok()?))
+    }
+
+    fn respond(mut stream: TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+        let length = body.len();
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {length}\r\n\
+            Connection: close\r\n\r\n{body}"
+        )?;
+        stream.flush()
+    }
+
+    fn stream_events(mut stream: TcpStream, state: &SharedState) -> io::Result<()> {
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n")?;
+        stream.flush()?;
+
+        let mut last_version = None;
+        loop {
+            // Never write while holding the lock so a slow browser can't block the presentation.
+            let data = {
+                let mut slide = state.slide.lock().expect("lock poisoned");
+                if last_version == Some(slide.0) {
+                    slide = state.changed.wait_timeout(slide, KEEP_ALIVE_INTERVAL).expect("lock poisoned").0;
+                }
+                if last_version == Some(slide.0) {
+                    None
+                } else {
+                    last_version = Some(slide.0);
+                    Some(serde_json::to_string(&slide.1).expect("serialization failed"))
+                }
+            };
+            match data {
+                Some(data) => write!(stream, "data: {data}\n\n")?,
+                // Send a comment every now and then so we notice when the browser goes away.
+                None => write!(stream, ": keep-alive\n\n")?,
+            };
+            stream.flush()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poll::{Poll, PollDefinition};
+    use rstest::rstest;
+    use std::io::Read;
+
+    fn request(address: SocketAddr, method: &str, path: &str) -> String {
+        request_with_headers(address, method, path, "")
+    }
+
+    fn request_with_headers(address: SocketAddr, method: &str, path: &str, headers: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "{method} {path} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn start_with_polls(polls: PollRegistry) -> (AudienceServer, SocketAddr) {
+        // Find a free port to bind to.
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        (AudienceServer::start(address, polls).unwrap(), address)
+    }
+
+    fn start() -> (AudienceServer, SocketAddr) {
+        start_with_polls(PollRegistry::default())
+    }
+
+    #[test]
+    fn page() {
+        let (_server, address) = start();
+        let response = request(address, "GET", "/");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("EventSource"));
+    }
+
+    #[test]
+    fn not_found() {
+        let (_server, address) = start();
+        let response = request(address, "GET", "/potato");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[test]
+    fn events() {
+        let (server, address) = start();
+        server.update(AudienceSlide { html: "<b>hi</b>".into(), current_slide: 1, total_slides: 2, polls: vec![] });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while !line.starts_with("data:") {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        assert_eq!(line, "data: {\"html\":\"<b>hi</b>\",\"current_slide\":1,\"total_slides\":2,\"polls\":[]}\n");
+    }
+
+    #[test]
+    fn vote() {
+        let polls = PollRegistry::default();
+        let poll = Poll::new(PollDefinition { question: "?".into(), options: vec!["a".into()] }, 0);
+        poll.open();
+        polls.replace(vec![poll.clone()]);
+
+        let (_server, address) = start_with_polls(polls);
+        assert!(request(address, "POST", "/polls/0/1").starts_with("HTTP/1.1 404 Not Found"));
+        assert!(request(address, "POST", "/polls/foo").starts_with("HTTP/1.1 404 Not Found"));
+        assert!(request(address, "POST", "/polls/0/0").starts_with("HTTP/1.1 200 OK"));
+        // Voting again from the same address doesn't count.
+        assert!(request(address, "POST", "/polls/0/0").starts_with("HTTP/1.1 404 Not Found"));
+        assert_eq!(poll.votes(), &[1]);
+    }
+
+    #[rstest]
+    #[case::same_origin("Origin: http://localhost\r\n", "HTTP/1.1 200 OK")]
+    #[case::cross_origin("Origin: http://example.com\r\n", "HTTP/1.1 403 Forbidden")]
+    fn vote_origin(#[case] headers: &str, #[case] expected: &str) {
+        let polls = PollRegistry::default();
+        let poll = Poll::new(PollDefinition { question: "?".into(), options: vec!["a".into()] }, 0);
+        poll.open();
+        polls.replace(vec![poll.clone()]);
+
+        let (_server, address) = start_with_polls(polls);
+        let response = request_with_headers(address, "POST", "/polls/0/0", headers);
+        assert!(response.starts_with(expected), "{response}");
+    }
+
+    #[test]
+    fn incomplete_request_times_out() {
+        let (_server, address) = start();
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\n").unwrap();
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT * 2)).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).expect("connection not closed");
+        assert_eq!(response, "");
+    }
+
+    #[test]
+    fn connection_limit() {
+        let connections = AtomicUsize::new(0);
+        let guards: Vec<_> = (0..MAX_CONNECTIONS).map(|_| ConnectionGuard::acquire(&connections).unwrap()).collect();
+        assert!(ConnectionGuard::acquire(&connections).is_none());
+        drop(guards);
+        assert!(ConnectionGuard::acquire(&connections).is_some());
+    }
+}
@@ -1,40 +1,157 @@
 use crate::{
-    execute::{CodeExecuter, ExecutionHandle, ExecutionState, ProcessStatus},
+    config::{ExecutionConfig, PlaceholderConfig, UnknownCommandPolicy},
+    execute::{CodeExecuter, ExecutionHandle, ExecutionState, ProcessStatus, SessionManager},
     markdown::{
         elements::{
-            Code, CodeLanguage, Highlight, HighlightGroup, ListItem, ListItemType, MarkdownElement, ParagraphElement,
-            SourcePosition, StyledText, Table, TableRow, Text,
+            Code, CodeAlignment, CodeAttributes, CodeLanguage, Highlight, HighlightGroup, ImageAttributes, ImageFit,
+            ListItem, ListItemBlock, ListItemType, MarkdownElement, ParagraphElement, SourcePosition, StyledText, Table,
+            TableRow, Text,
         },
+        parse::MarkdownParser,
+        template::Template,
         text::{WeightedLine, WeightedText},
     },
+    options::{ExternalOptions, PresentationOptions},
+    placeholders::PlaceholderRegistry,
+    poll::{Poll, PollDefinition, PollOperation, PollRegistry},
     presentation::{
-        AsRenderOperations, ChunkMutator, MarginProperties, PreformattedLine, Presentation, PresentationMetadata,
-        PresentationThemeMetadata, RenderOnDemand, RenderOnDemandState, RenderOperation, Slide, SlideChunk,
+        AsRenderOperations, ChunkMutator, CodeBlock, ExportProfile, ImageFloat, MarginProperties, MinimumSize,
+        PreformattedLine, Presentation, PresentationMetadata, PresentationThemeMetadata, RenderOnDemand,
+        RenderOnDemandState, RenderOperation, Slide, SlideChunk, SlideId, SlideMetadata, SlideSound, SlideWarning,
+        SocialLinks,
     },
     render::{
-        highlighting::{CodeHighlighter, LanguageHighlighter, StyledTokens},
+        ansi::{self, AnsiStyle},
+        highlighting::{CodeHighlighter, InvalidCodeTheme, LanguageHighlighter, StyledTokens},
+        media::Image,
         properties::WindowSize,
     },
-    resource::{LoadImageError, Resources},
-    style::{Colors, TextStyle},
-    theme::{Alignment, AuthorPositioning, ElementType, FooterStyle, LoadThemeError, Margin, PresentationTheme},
+    resource::{DiagramKind, FetchImageError, LoadImageError, Resources},
+    schema,
+    style::{Color, Colors, TextStyle},
+    theme::{
+        Alignment, AuthorPositioning, ElementType, ExecutionOutputBlockStyle, FooterStyle, LoadThemeError, Margin,
+        PresentationTheme, SocialLayout, TextTransform,
+    },
+    transcript::{ExecutionTranscript, TranscriptEntry},
 };
+use comrak::Arena;
 use itertools::Itertools;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use std::{borrow::Cow, cell::RefCell, fmt::Display, iter, mem, path::PathBuf, rc::Rc, str::FromStr};
+use serde_with::DeserializeFromStr;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    iter, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use strum::{EnumVariantNames, VariantNames};
 use syntect::highlighting::Style;
 use unicode_width::UnicodeWidthStr;
 
+// The text images within a line are drawn over, which can't be split by word wrapping.
+const NON_BREAKING_SPACE: &str = "\u{a0}";
+
+// The line displayed in place of the lines a code block leaves out.
+const FOLD_MARKER: &str = "…";
+
 // TODO: move to a theme config.
 static DEFAULT_BOTTOM_SLIDE_MARGIN: u16 = 3;
 
+// The language of the code blocks that hold the output executable code blocks show in dry run mode.
+const CANNED_OUTPUT_LANGUAGE: &str = "output";
+const STDIN_LANGUAGE: &str = "stdin";
+const EXPECTED_OUTPUT_LANGUAGE: &str = "expected_output";
+
+// The rows taken by the blank line and separator above the lines in a block of output.
+const OUTPUT_HEADER_ROWS: usize = 3;
+
+// The theme used instead of the presentation's own one when high contrast is enabled.
+const HIGH_CONTRAST_THEME: &str = "high-contrast";
+
 pub(crate) struct PresentationBuilderOptions {
     pub(crate) allow_mutations: bool,
+    pub(crate) use_export_profile: bool,
+    pub(crate) polls: PollRegistry,
+
+    /// The presentation level options set outside of the presentation, e.g. its profile.
+    pub(crate) presentation_options: ExternalOptions,
+
+    /// Whether to keep going after an error in a slide and report every error found at the end.
+    pub(crate) accumulate_errors: bool,
+
+    /// What to do with comments that contain unknown commands.
+    pub(crate) unknown_commands: UnknownCommandPolicy,
+
+    /// What to do with `command_output` commands.
+    pub(crate) command_output: CommandOutputPolicy,
+
+    /// The only slide `command_output` commands are run in, if they aren't run in every slide.
+    ///
+    /// This is used when refreshing a slide, as every other slide is thrown away.
+    pub(crate) command_output_slide: Option<usize>,
+
+    /// The placeholders that can be used on top of the built in ones.
+    pub(crate) placeholders: PlaceholderConfig,
+
+    /// The commands used to compile code blocks before executing them.
+    pub(crate) execution: ExecutionConfig,
+
+    /// The transcript every run of an executable code block is written into, if any.
+    pub(crate) execution_transcript: Option<ExecutionTranscript>,
+
+    /// The shell sessions executable code blocks run in, which outlive reloads.
+    pub(crate) sessions: SessionManager,
+}
+
+/// What to do with the commands whose output is inserted into a presentation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum CommandOutputPolicy {
+    /// Fail to build the presentation, as running commands has to be explicitly allowed.
+    #[default]
+    Deny,
+
+    /// Run the commands and insert their output.
+    Run,
+
+    /// Don't run the commands and don't insert anything in their place.
+    Skip,
+
+    /// Run the commands while presenting, once running them is confirmed, and insert their output then.
+    Confirm,
+}
+
+impl CommandOutputPolicy {
+    /// Get the policy to use depending on whether running commands is allowed.
+    pub(crate) fn from_allowed(allowed: bool) -> Self {
+        if allowed { Self::Run } else { Self::Deny }
+    }
 }
 
 impl Default for PresentationBuilderOptions {
     fn default() -> Self {
-        Self { allow_mutations: true }
+        Self {
+            allow_mutations: true,
+            use_export_profile: false,
+            polls: Default::default(),
+            presentation_options: Default::default(),
+            accumulate_errors: false,
+            unknown_commands: UnknownCommandPolicy::Error,
+            command_output: CommandOutputPolicy::Deny,
+            command_output_slide: None,
+            placeholders: Default::default(),
+            execution: ExecutionConfig::default(),
+            execution_transcript: None,
+            sessions: Default::default(),
+        }
     }
 }
 
@@ -52,6 +169,27 @@ pub(crate) struct PresentationBuilder<'a> {
     resources: &'a mut Resources,
     slide_state: SlideState,
     footer_context: Rc<RefCell<FooterContext>>,
+    polls: Vec<Poll>,
+    export_profile: Option<ExportProfile>,
+    conditions: Vec<bool>,
+    errors: Vec<SlideBuildError>,
+    warnings: Vec<SlideWarning>,
+    slide_ids: HashSet<SlideId>,
+    link_references: bool,
+    max_slide_height: Option<u16>,
+    minimum_size: Option<MinimumSize>,
+    agenda: bool,
+    default_containers: BTreeMap<String, String>,
+    sections: Rc<RefCell<Vec<String>>>,
+    section_color: Option<Color>,
+    includes: Vec<PathBuf>,
+    code_executions: Vec<Rc<RunCodeOperation>>,
+    // The index of the last top level item in the last list, which `continue_list` picks up from.
+    last_list_index: Option<usize>,
+    placeholders: PlaceholderRegistry,
+    // The options in the front matter, and those resolved against the ones set outside of the presentation.
+    front_matter_options: PresentationOptions,
+    presentation_options: PresentationOptions,
     options: PresentationBuilderOptions,
 }
 
@@ -63,6 +201,9 @@ impl<'a> PresentationBuilder<'a> {
         resources: &'a mut Resources,
         options: PresentationBuilderOptions,
     ) -> Self {
+        let placeholders = PlaceholderRegistry::new(resources.base_path(), &options.placeholders);
+        let footer_context = FooterContext { placeholders: placeholders.clone(), ..Default::default() };
+        let presentation_options = options.presentation_options.resolve(&Default::default());
         Self {
             slide_chunks: Vec::new(),
             chunk_operations: Vec::new(),
@@ -72,38 +213,190 @@ impl<'a> PresentationBuilder<'a> {
             theme: Cow::Borrowed(default_theme),
             resources,
             slide_state: Default::default(),
-            footer_context: Default::default(),
+            footer_context: Rc::new(RefCell::new(footer_context)),
+            polls: Vec::new(),
+            export_profile: None,
+            conditions: Vec::new(),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            slide_ids: HashSet::new(),
+            link_references: false,
+            max_slide_height: None,
+            minimum_size: None,
+            agenda: false,
+            default_containers: BTreeMap::new(),
+            sections: Default::default(),
+            section_color: None,
+            includes: Vec::new(),
+            code_executions: Vec::new(),
+            last_list_index: None,
+            placeholders,
+            front_matter_options: Default::default(),
+            presentation_options,
             options,
         }
     }
 
     /// Build a presentation.
     pub(crate) fn build(mut self, elements: Vec<MarkdownElement>) -> Result<Presentation, BuildError> {
-        if let Some(MarkdownElement::FrontMatter(contents)) = elements.first() {
-            self.process_front_matter(contents)?;
-        }
+        let elements = Self::expand_templates(elements, &self.placeholders)?;
+        // Resources are reused when reloading so don't keep the root from a previous build around.
+        self.resources.set_root(None);
+        self.resources.clear_included_files();
+        match elements.first() {
+            Some(MarkdownElement::FrontMatter(contents)) => self.process_front_matter(contents)?,
+            // The options set outside of the presentation can still change its theme.
+            _ => self.set_theme(&Default::default())?,
+        };
         self.set_code_theme()?;
 
         if self.chunk_operations.is_empty() {
             self.push_slide_prelude();
         }
-        for element in elements {
-            self.slide_state.ignore_element_line_break = false;
-            self.process_element(element)?;
-            self.validate_last_operation()?;
-            if !self.slide_state.ignore_element_line_break {
-                self.push_line_break();
-            }
+        self.push_elements(elements)?;
+        if !self.conditions.is_empty() {
+            self.record_error(BuildError::UnterminatedCondition)?;
+        }
+        if let Err(e) = self.link_code_dependencies() {
+            self.record_error(e)?;
+        }
+        if !self.errors.is_empty() {
+            return Err(BuildError::Multiple(self.errors));
         }
         if !self.chunk_operations.is_empty() || !self.slide_chunks.is_empty() {
             self.terminate_slide();
         }
         self.footer_context.borrow_mut().total_slides = self.slides.len();
+        self.options.polls.replace(self.polls);
 
-        let presentation = Presentation::new(self.slides);
+        let presentation = Presentation::new(self.slides)
+            .with_export_profile(self.export_profile)
+            .with_minimum_size(self.minimum_size)
+            .with_warnings(self.warnings)
+            .with_options(self.front_matter_options);
         Ok(presentation)
     }
 
+    fn push_elements(&mut self, elements: Vec<MarkdownElement>) -> Result<(), BuildError> {
+        for element in elements {
+            // Comments are always processed as they can contain the commands that end a conditional block.
+            if !self.content_enabled() && !matches!(element, MarkdownElement::Comment { .. }) {
+                continue;
+            }
+            self.slide_state.ignore_element_line_break = false;
+            let spacing = self.element_spacing(&element);
+            if let Err(error) = self.process_element(element).and_then(|_| self.validate_last_operation()) {
+                self.record_error(error)?;
+            }
+            if !self.slide_state.ignore_element_line_break {
+                for _ in 0..spacing {
+                    self.push_line_break();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn element_spacing(&self, element: &MarkdownElement) -> u8 {
+        let spacing = &self.theme.spacing;
+        let spacing = match element {
+            MarkdownElement::Paragraph(_) => spacing.paragraph,
+            MarkdownElement::Heading { .. } => spacing.heading,
+            MarkdownElement::Code(_) => spacing.code,
+            _ => None,
+        };
+        spacing.unwrap_or(1)
+    }
+
+    // The number of blank lines after the last element in this chunk, if it has one.
+    fn trailing_blank_lines(&self) -> Option<usize> {
+        let line_breaks = self
+            .chunk_operations
+            .iter()
+            .rev()
+            .take_while(|operation| matches!(operation, RenderOperation::RenderLineBreak))
+            .count();
+        match self.chunk_operations.iter().rev().nth(line_breaks)? {
+            // There's nothing to separate from at the top of a slide or a column.
+            RenderOperation::ApplyMargin(_) | RenderOperation::EnterColumn { .. } => None,
+            // Lines of code already end in a line break.
+            RenderOperation::RenderDynamic(_) => Some(line_breaks),
+            _ => Some(line_breaks.saturating_sub(1)),
+        }
+    }
+
+    fn record_error(&mut self, error: BuildError) -> Result<(), BuildError> {
+        // Errors in included files point at lines in them so say which file they're in.
+        let error = match self.includes.last() {
+            Some(path) => BuildError::Included { path: path.clone(), error: Box::new(error) },
+            None => error,
+        };
+        if !self.options.accumulate_errors {
+            return Err(error);
+        }
+        self.errors.push(SlideBuildError { slide: self.slides.len() + 1, error });
+        Ok(())
+    }
+
+    /// Collect every template definition and replace every use of one with its contents.
+    ///
+    /// Templates need to be defined before they're used, and they can use any template defined
+    /// before them.
+    fn expand_templates(
+        elements: Vec<MarkdownElement>,
+        placeholders: &PlaceholderRegistry,
+    ) -> Result<Vec<MarkdownElement>, BuildError> {
+        let mut templates: HashMap<String, Template> = HashMap::new();
+        let mut output = Vec::new();
+        let mut definition: Option<(String, Vec<MarkdownElement>)> = None;
+        for element in elements {
+            let command = match &element {
+                MarkdownElement::Comment { comment, .. } if !Self::should_ignore_comment(comment) => {
+                    // Any parse errors are reported when the comment is processed later on.
+                    match Self::parse_commands(comment) {
+                        Ok(mut commands) if commands.len() == 1 => commands.pop(),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match command {
+                Some(CommentCommand::Template(name)) => {
+                    if definition.is_some() {
+                        return Err(BuildError::InvalidTemplate("templates can't be defined inside templates".into()));
+                    }
+                    definition = Some((name, Vec::new()));
+                }
+                Some(CommentCommand::EndTemplate) => {
+                    let Some((name, elements)) = definition.take() else {
+                        return Err(BuildError::InvalidTemplate("found `end_template` without a `template`".into()));
+                    };
+                    templates.insert(name, Template::new(elements));
+                }
+                Some(CommentCommand::UseTemplate(TemplateInstance { name, values })) => {
+                    let template = templates
+                        .get(&name)
+                        .ok_or_else(|| BuildError::InvalidTemplate(format!("template '{name}' is not defined")))?;
+                    let elements = template
+                        .instantiate(&values, placeholders)
+                        .map_err(|e| BuildError::InvalidTemplate(format!("template '{name}': {e}")))?;
+                    match &mut definition {
+                        Some((_, body)) => body.extend(elements),
+                        None => output.extend(elements),
+                    };
+                }
+                _ => match &mut definition {
+                    Some((_, body)) => body.push(element),
+                    None => output.push(element),
+                },
+            };
+        }
+        if let Some((name, _)) = definition {
+            return Err(BuildError::InvalidTemplate(format!("template '{name}' is missing its `end_template`")));
+        }
+        Ok(output)
+    }
+
     fn validate_last_operation(&mut self) -> Result<(), BuildError> {
         if !self.slide_state.needs_enter_column {
             return Ok(());
@@ -133,21 +426,27 @@ impl<'a> PresentationBuilder<'a> {
     }
 
     fn process_element(&mut self, element: MarkdownElement) -> Result<(), BuildError> {
-        let should_clear_last = !matches!(element, MarkdownElement::List(_) | MarkdownElement::Comment { .. });
+        // Code blocks set the last element themselves.
+        let should_clear_last =
+            !matches!(element, MarkdownElement::List(_) | MarkdownElement::Comment { .. } | MarkdownElement::Code(_));
         match element {
             // This one is processed before everything else as it affects how the rest of the
             // elements is rendered.
             MarkdownElement::FrontMatter(_) => self.slide_state.ignore_element_line_break = true,
             MarkdownElement::SetexHeading { text } => self.push_slide_title(text),
+            MarkdownElement::Heading { level: 1, text } if self.agenda => {
+                self.push_agenda_slide(text.to_plain_text());
+                self.push_heading(1, text);
+            }
             MarkdownElement::Heading { level, text } => self.push_heading(level, text),
             MarkdownElement::Paragraph(elements) => self.push_paragraph(elements)?,
-            MarkdownElement::List(elements) => self.push_list(elements),
-            MarkdownElement::Code(code) => self.push_code(code),
-            MarkdownElement::Table(table) => self.push_table(table),
+            MarkdownElement::List(elements) => self.push_list(elements)?,
+            MarkdownElement::Code(code) => self.process_code(*code)?,
+            MarkdownElement::Table(table) => self.push_table(table)?,
             MarkdownElement::ThematicBreak => self.push_separator(),
             MarkdownElement::Comment { comment, source_position } => self.process_comment(comment, source_position)?,
             MarkdownElement::BlockQuote(lines) => self.push_block_quote(lines),
-            MarkdownElement::Image { path, .. } => self.push_image(path)?,
+            MarkdownElement::Image { path, attributes, .. } => self.push_image(path, attributes)?,
         };
         if should_clear_last {
             self.slide_state.last_element = Default::default();
@@ -155,16 +454,112 @@ impl<'a> PresentationBuilder<'a> {
         Ok(())
     }
 
+    fn process_code(&mut self, code: Code) -> Result<(), BuildError> {
+        let last_element = mem::take(&mut self.slide_state.last_element);
+        let mut code = self.load_snippet(code)?;
+        // An output block right after an executable one is what it shows when running in dry run mode,
+        // a stdin block is what's written into its stdin, and an expected output block is what it's
+        // checked against when validating. These can follow each other in any order. Output blocks are
+        // only taken in dry run mode, otherwise they're displayed like any other code block.
+        if let LastElement::Execution(operation) = last_element {
+            let consumed = match &code.language {
+                CodeLanguage::Unknown(name) if name == CANNED_OUTPUT_LANGUAGE && self.dry_run_execution() => {
+                    operation.set_canned_output(code.contents.lines().map(ToString::to_string).collect());
+                    true
+                }
+                CodeLanguage::Unknown(name) if name == STDIN_LANGUAGE => {
+                    operation.set_stdin(code.contents.clone());
+                    true
+                }
+                CodeLanguage::Unknown(name) if name == EXPECTED_OUTPUT_LANGUAGE => {
+                    operation.set_expected_output(code.contents.lines().map(ToString::to_string).collect());
+                    true
+                }
+                _ => false,
+            };
+            if consumed {
+                self.slide_state.ignore_element_line_break = true;
+                self.slide_state.last_element = LastElement::Execution(operation);
+                return Ok(());
+            }
+        }
+        // Code that runs somewhere else, or that keeps state in this machine, doesn't use the default container.
+        let attributes = &mut code.attributes;
+        let runs_locally = attributes.container.is_none() && attributes.host.is_none() && attributes.session.is_none();
+        if attributes.execute && runs_locally {
+            attributes.container = self.default_containers.get(code.language.name()).cloned();
+        }
+        if code.attributes.execute && !CodeExecuter::supports(&code.language, &self.options.execution) {
+            let line = code.source_position.start.line;
+            return Err(BuildError::ExecutionNotSupported { language: code.language.name().into(), line });
+        }
+        if let Some(host) =
+            code.attributes.host.as_ref().filter(|host| !self.options.execution.hosts.contains_key(*host))
+        {
+            return Err(BuildError::UnknownHost(host.clone()));
+        }
+        let stdin = match &code.attributes.stdin {
+            Some(path) => {
+                let input = self.resources.included_file(path);
+                Some(input.map_err(|e| BuildError::InvalidStdin(path.clone(), e.to_string()))?)
+            }
+            None => None,
+        };
+        if code.attributes.render {
+            self.push_diagram(&code);
+        } else if code.attributes.replace_with_output {
+            self.push_code_output(&code)?;
+        } else {
+            self.push_code(code);
+        }
+        if let (Some(input), LastElement::Execution(operation)) = (stdin, &self.slide_state.last_element) {
+            operation.set_stdin(input);
+        }
+        Ok(())
+    }
+
     fn process_front_matter(&mut self, contents: &str) -> Result<(), BuildError> {
-        let metadata: PresentationMetadata =
+        let mut metadata: PresentationMetadata =
             serde_yaml::from_str(contents).map_err(|e| BuildError::InvalidMetadata(e.to_string()))?;
 
+        // The options decide how everything after the front matter is built, e.g. the profile.
+        self.presentation_options = self.options.presentation_options.resolve(&metadata.options);
+        self.front_matter_options = mem::take(&mut metadata.options);
         self.footer_context.borrow_mut().author = metadata.author.clone().unwrap_or_default();
-        self.set_theme(&metadata.theme)?;
-        if metadata.title.is_some() || metadata.sub_title.is_some() || metadata.author.is_some() {
-            self.push_slide_prelude();
-            self.push_intro_slide(metadata);
+        if let Some(profile) = &metadata.export {
+            if profile.columns == 0 || profile.rows == 0 {
+                return Err(BuildError::InvalidMetadata("export dimensions can't be zero".into()));
+            }
+            if let (true, Some(theme)) = (self.options.use_export_profile, &profile.theme) {
+                metadata.theme.name = Some(theme.clone());
+                metadata.theme.path = None;
+            }
         }
+        self.export_profile = metadata.export.clone();
+        self.link_references = metadata.link_references;
+        self.max_slide_height = metadata.max_slide_height;
+        self.minimum_size = metadata.minimum_size.clone();
+        self.agenda = metadata.agenda;
+        self.default_containers = metadata.containers.clone();
+        self.resources.set_root(metadata.resources_root.clone());
+        self.set_theme(&metadata.theme)?;
+        self.footer_context.borrow_mut().social = self.social_links(&metadata.social).join("  ");
+        match (metadata.intro_slide, &metadata.intro_slide_template) {
+            (Some(false), Some(_)) => {
+                return Err(BuildError::InvalidMetadata("cannot disable intro slide and set its template".into()));
+            }
+            (Some(false), None) => (),
+            (_, Some(path)) => {
+                let path = path.clone();
+                self.push_slide_prelude();
+                self.push_intro_slide_template(&path, &metadata)?;
+            }
+            (_, None) if metadata.title.is_some() || metadata.sub_title.is_some() || metadata.author.is_some() => {
+                self.push_slide_prelude();
+                self.push_intro_slide(metadata);
+            }
+            _ => (),
+        };
         Ok(())
     }
 
@@ -178,7 +573,14 @@ impl<'a> PresentationBuilder<'a> {
             self.theme = Cow::Owned(theme);
         }
         if let Some(theme_path) = &metadata.path {
-            let theme = self.resources.theme(theme_path)?;
+            let mut theme = self.resources.theme(theme_path)?;
+            // Code theme files in a theme file are relative to the theme file rather than to the presentation.
+            let code_theme = theme.code.theme_name.as_mut().filter(|name| CodeHighlighter::is_theme_file(name));
+            if let Some(code_theme) = code_theme {
+                let theme_path = self.resources.resolve(theme_path);
+                let directory = theme_path.parent().unwrap_or(&theme_path);
+                *code_theme = directory.join(&*code_theme).to_string_lossy().into_owned();
+            }
             self.theme = Cow::Owned(theme);
         }
         if let Some(overrides) = &metadata.overrides {
@@ -187,17 +589,86 @@ impl<'a> PresentationBuilder<'a> {
                 .map_err(|e| BuildError::InvalidMetadata(format!("invalid theme: {e}")))?;
             self.theme = Cow::Owned(theme);
         }
+        // High contrast is for whoever is watching so it wins over whatever theme the presentation uses.
+        if self.presentation_options.high_contrast == Some(true) {
+            let theme = PresentationTheme::from_name(HIGH_CONTRAST_THEME).expect("high contrast theme missing");
+            self.theme = Cow::Owned(theme);
+        }
         Ok(())
     }
 
     fn set_code_theme(&mut self) -> Result<(), BuildError> {
         if let Some(theme) = &self.theme.code.theme_name {
-            let highlighter = CodeHighlighter::new(theme).map_err(|_| BuildError::InvalidCodeTheme)?;
-            self.highlighter = highlighter;
+            // Theme files are relative to the presentation, like any other file it uses, unless they come from a
+            // theme file, in which case they were already made relative to it.
+            let theme = if CodeHighlighter::is_theme_file(theme) {
+                self.resources.resolve(theme).to_string_lossy().into_owned()
+            } else {
+                theme.clone()
+            };
+            self.highlighter = self.highlighter.with_theme(&theme)?;
+        }
+        Ok(())
+    }
+
+    fn push_included_file(&mut self, path: &Path) -> Result<(), BuildError> {
+        let resolved_path = self.resources.resolve(path);
+        if self.includes.contains(&resolved_path) {
+            return Err(BuildError::IncludeCycle(path.into()));
         }
+        let error = |e: &dyn Display| BuildError::InvalidInclude(path.into(), e.to_string());
+        let contents = self.resources.included_file(path).map_err(|e| error(&e))?;
+        let arena = Arena::new();
+        let elements = MarkdownParser::new(&arena).parse(&contents).map_err(|e| error(&e))?;
+        let elements = Self::expand_templates(elements, &self.placeholders).map_err(|e| error(&e))?;
+        // The presentation's own front matter is the only one that matters.
+        let elements = elements.into_iter().filter(|element| !matches!(element, MarkdownElement::FrontMatter(_)));
+        self.includes.push(resolved_path);
+        let result = self.push_elements(elements.collect());
+        self.includes.pop();
+        result
+    }
+
+    fn push_intro_slide_template(&mut self, path: &Path, metadata: &PresentationMetadata) -> Result<(), BuildError> {
+        let error = |e: &dyn Display| BuildError::InvalidIntroSlideTemplate(path.into(), e.to_string());
+        let contents = self.resources.markdown(path).map_err(|e| error(&e))?;
+        let arena = Arena::new();
+        let elements = MarkdownParser::new(&arena).parse(&contents).map_err(|e| error(&e))?;
+        // The template can use the presentation's metadata via placeholders like `{{title}}`.
+        let mut values: HashMap<_, _> =
+            [("title", &metadata.title), ("sub_title", &metadata.sub_title), ("author", &metadata.author)]
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.clone().unwrap_or_default()))
+                .collect();
+        values.insert("social".into(), self.social_links(&metadata.social).join("  "));
+        let elements = Template::new(elements).instantiate(&values, &self.placeholders).map_err(|e| error(&e))?;
+        self.slide_state.title = metadata.title.clone();
+        self.push_elements(elements)?;
+        self.terminate_slide();
         Ok(())
     }
 
+    fn social_links(&self, links: &SocialLinks) -> Vec<String> {
+        let icons = self.theme.intro_slide.social.icons;
+        links.iter().map(|(network, value)| format!("{}{value}", icons.label(network))).collect()
+    }
+
+    fn push_intro_social(&mut self, links: Vec<String>) {
+        if links.is_empty() {
+            return;
+        }
+        let style = TextStyle::default().colors(self.theme.intro_slide.social.colors.clone());
+        let lines = match self.theme.intro_slide.social.layout {
+            SocialLayout::Inline => vec![links.join("  ")],
+            SocialLayout::Stacked => links,
+        };
+        self.push_line_break();
+        for line in lines {
+            self.push_text(Text::from(StyledText::new(line, style.clone())), ElementType::PresentationSocial);
+            self.push_line_break();
+        }
+    }
+
     fn push_intro_slide(&mut self, metadata: PresentationMetadata) {
         let styles = &self.theme.intro_slide;
         let title = StyledText::new(
@@ -212,6 +683,8 @@ impl<'a> PresentationBuilder<'a> {
             .author
             .as_ref()
             .map(|text| StyledText::new(text.clone(), TextStyle::default().colors(styles.author.colors.clone())));
+        let social = self.social_links(&metadata.social);
+        self.slide_state.title = Some(title.text.clone());
         self.chunk_operations.push(RenderOperation::JumpToVerticalCenter);
         self.push_text(Text::from(title), ElementType::PresentationTitle);
         self.push_line_break();
@@ -219,6 +692,11 @@ impl<'a> PresentationBuilder<'a> {
             self.push_text(Text::from(text), ElementType::PresentationSubTitle);
             self.push_line_break();
         }
+        // Social links go right below the author unless that's at the bottom of the page.
+        let author_at_bottom = matches!(self.theme.intro_slide.author.positioning, AuthorPositioning::PageBottom);
+        if author.is_none() || author_at_bottom {
+            self.push_intro_social(social.clone());
+        }
         if let Some(text) = author {
             match self.theme.intro_slide.author.positioning {
                 AuthorPositioning::BelowTitle => {
@@ -231,6 +709,10 @@ impl<'a> PresentationBuilder<'a> {
                 }
             };
             self.push_text(Text::from(text), ElementType::PresentationAuthor);
+            if !author_at_bottom {
+                self.push_line_break();
+                self.push_intro_social(social);
+            }
         }
         self.terminate_slide();
     }
@@ -239,11 +721,34 @@ impl<'a> PresentationBuilder<'a> {
         if Self::should_ignore_comment(&comment) {
             return Ok(());
         }
-        let comment = match comment.parse::<CommentCommand>() {
-            Ok(comment) => comment,
-            Err(error) => return Err(BuildError::CommandParse { line: source_position.start.line + 1, error }),
+        let line = source_position.start.line;
+        let commands = match (Self::parse_commands(&comment), self.options.unknown_commands) {
+            (Ok(commands), _) => commands,
+            (Err(error @ CommandParseError::UnknownCommand(_)), UnknownCommandPolicy::Warn) => {
+                let message = format!("line {line}: {error}");
+                self.warnings.push(SlideWarning { slide: self.slides.len() + 1, message });
+                Vec::new()
+            }
+            (Err(CommandParseError::UnknownCommand(_)), UnknownCommandPolicy::Ignore) => Vec::new(),
+            (Err(error), _) => return Err(BuildError::CommandParse { line, error }),
         };
-        match comment {
+        for command in commands {
+            self.process_command(command)?;
+        }
+        // Don't push line breaks for any comments.
+        self.slide_state.ignore_element_line_break = true;
+        Ok(())
+    }
+
+    fn content_enabled(&self) -> bool {
+        self.conditions.iter().all(|enabled| *enabled)
+    }
+
+    fn process_command(&mut self, command: CommentCommand) -> Result<(), BuildError> {
+        if !self.content_enabled() && !matches!(command, CommentCommand::If(_) | CommentCommand::EndIf) {
+            return Ok(());
+        }
+        match command {
             CommentCommand::Pause => self.process_pause(),
             CommentCommand::EndSlide => self.terminate_slide(),
             CommentCommand::InitColumnLayout(columns) => {
@@ -252,6 +757,20 @@ impl<'a> PresentationBuilder<'a> {
                 self.chunk_operations.push(RenderOperation::InitColumnLayout { columns });
                 self.slide_state.needs_enter_column = true;
             }
+            CommentCommand::SlideId(id) => self.slide_state.id = Some(id.into()),
+            CommentCommand::ImageFloat(float) => self.slide_state.image_float = Some(float),
+            CommentCommand::SectionColor(color) => self.section_color = Some(color),
+            CommentCommand::ImageAnimation(animate) => self.slide_state.disable_image_animation = !animate,
+            CommentCommand::ContinueList => self.slide_state.continue_list = true,
+            CommentCommand::SlideLayout(name) => self.apply_slide_layout(&name)?,
+            CommentCommand::CommandOutput(command) => self.push_command_output(&command)?,
+            CommentCommand::SpeakerNote(note) => self.slide_state.notes.push(note),
+            CommentCommand::Include(path) => self.push_included_file(&path)?,
+            CommentCommand::Sound(path) => {
+                let sound = SlideSound { chunk: self.slide_chunks.len(), path: self.resources.resolve(path) };
+                self.slide_state.sounds.push(sound);
+            }
+            CommentCommand::RefreshEvery(interval) => self.slide_state.refresh_every = Some(interval.0),
             CommentCommand::ResetLayout => {
                 self.slide_state.layout = LayoutState::Default;
                 self.chunk_operations.extend([RenderOperation::ExitLayout, RenderOperation::RenderLineBreak]);
@@ -270,22 +789,49 @@ impl<'a> PresentationBuilder<'a> {
                 self.slide_state.layout = LayoutState::InColumn { column, columns_count };
                 self.chunk_operations.push(RenderOperation::EnterColumn { column });
             }
+            CommentCommand::Poll(definition) => self.push_poll(definition)?,
+            CommentCommand::If(condition) => {
+                let enabled = condition.evaluate(self.presentation_options.profile.as_deref());
+                self.conditions.push(enabled);
+            }
+            CommentCommand::EndIf => {
+                self.conditions.pop().ok_or(BuildError::UnmatchedEndIf)?;
+            }
+            // These are all handled before any element is processed as long as they're on their own.
+            CommentCommand::Template(_) | CommentCommand::EndTemplate | CommentCommand::UseTemplate(_) => {
+                let message = "template commands can't be combined with other commands";
+                return Err(BuildError::InvalidTemplate(message.into()));
+            }
         };
-        // Don't push line breaks for any comments.
-        self.slide_state.ignore_element_line_break = true;
         Ok(())
     }
 
-    fn should_ignore_comment(comment: &str) -> bool {
-        // Ignore any multi line comment; those are assumed to be user comments
+    /// Whether a comment is meant for humans rather than containing commands.
+    pub(crate) fn should_ignore_comment(comment: &str) -> bool {
+        // Ignore any multi line comment unless every line in it contains commands; those are
+        // assumed to be user comments
         if comment.contains('\n') {
-            return true;
+            return Self::parse_commands(comment).is_err();
         }
         // Ignore vim-like code folding tags
         let comment = comment.trim();
         comment == "{{{" || comment == "}}}"
     }
 
+    /// Split a comment into the YAML for each of its lines, each of which can contain one command or
+    /// a list of them.
+    pub(crate) fn comment_lines(comment: &str) -> impl Iterator<Item = &str> {
+        comment.lines().map(str::trim).filter(|line| !line.is_empty())
+    }
+
+    pub(crate) fn parse_commands(comment: &str) -> Result<Vec<CommentCommand>, CommandParseError> {
+        let mut commands = Vec::new();
+        for line in Self::comment_lines(comment) {
+            commands.extend(line.parse::<CommentCommands>()?.0);
+        }
+        Ok(commands)
+    }
+
     fn validate_column_layout(columns: &[u8]) -> Result<(), BuildError> {
         if columns.is_empty() {
             Err(BuildError::InvalidLayout("need at least one column"))
@@ -296,6 +842,53 @@ impl<'a> PresentationBuilder<'a> {
         }
     }
 
+    fn dry_run_execution(&self) -> bool {
+        self.presentation_options.dry_run_exec.unwrap_or_default()
+    }
+
+    // Commands that run while building, like `command_output` ones, only run in the slide being
+    // refreshed, if any, as every other slide is thrown away.
+    fn command_output_policy(&self) -> CommandOutputPolicy {
+        match self.options.command_output_slide {
+            Some(slide) if slide != self.slides.len() => CommandOutputPolicy::Skip,
+            _ => self.options.command_output,
+        }
+    }
+
+    fn push_command_output(&mut self, command: &str) -> Result<(), BuildError> {
+        let mut code = Code {
+            contents: String::new(),
+            language: CodeLanguage::Unknown(String::new()),
+            language_token: String::new(),
+            attributes: CodeAttributes {
+                highlight_groups: vec![HighlightGroup::new(vec![Highlight::All])],
+                ..Default::default()
+            },
+            source_position: Default::default(),
+        };
+        let output = match self.command_output_policy() {
+            CommandOutputPolicy::Deny => return Err(BuildError::CommandOutputNotAllowed),
+            CommandOutputPolicy::Skip => return Ok(()),
+            CommandOutputPolicy::Confirm => {
+                let directory = self.resources.base_path().to_path_buf();
+                let timeout_ms = self.options.execution.timeout_ms;
+                let source = DeferredOutputSource::Command { command: command.into(), directory, timeout_ms };
+                self.push_deferred_output(source, &code, command.into());
+                self.push_line_break();
+                return Ok(());
+            }
+            CommandOutputPolicy::Run => {
+                CodeExecuter::run_command(command, self.resources.base_path(), self.options.execution.timeout_ms)
+                    .map_err(|e| BuildError::CommandOutput(command.into(), e.to_string()))?
+            }
+        };
+        code.contents = output.trim_end().into();
+        self.push_code(code);
+        // The comment doesn't push a line break so do it here, like for any other code block.
+        self.push_line_break();
+        Ok(())
+    }
+
     fn process_pause(&mut self) {
         self.slide_state.last_chunk_ended_in_list = matches!(self.slide_state.last_element, LastElement::List { .. });
 
@@ -305,6 +898,8 @@ impl<'a> PresentationBuilder<'a> {
     }
 
     fn push_slide_title(&mut self, mut text: Text) {
+        self.slide_state.title = Some(text.to_plain_text());
+        self.slide_state.title_text = Some(text.clone());
         let style = self.theme.slide_title.clone();
         text.apply_style(&TextStyle::default().bold().colors(style.colors.clone()));
 
@@ -322,6 +917,39 @@ impl<'a> PresentationBuilder<'a> {
         }
         self.push_line_break();
         self.slide_state.ignore_element_line_break = true;
+        self.slide_state.content_start = Some(self.chunk_operations.len());
+    }
+
+    // Every top level heading starts a section, and its agenda goes right before the slide the heading is in,
+    // which is the one being built.
+    fn push_agenda_slide(&mut self, section: String) {
+        let current_section = {
+            let mut sections = self.sections.borrow_mut();
+            sections.push(section);
+            sections.len() - 1
+        };
+        let operations = mem::take(&mut self.chunk_operations);
+        let mutators = mem::take(&mut self.chunk_mutators);
+        let chunks = mem::take(&mut self.slide_chunks);
+        let state = mem::take(&mut self.slide_state);
+
+        self.push_slide_prelude();
+        self.push_slide_title(Text::from("Agenda"));
+        let style = TextStyle::default().colors(self.theme.default_style.colors.clone());
+        let list = AgendaList {
+            sections: self.sections.clone(),
+            current_section,
+            alignment: self.theme.alignment(&ElementType::List),
+            style,
+            current_style: TextStyle::default().bold().colors(self.theme.headings.h1.colors.clone()),
+        };
+        self.chunk_operations.push(RenderOperation::RenderDynamic(Rc::new(list)));
+        self.terminate_slide();
+
+        self.chunk_operations = operations;
+        self.chunk_mutators = mutators;
+        self.slide_chunks = chunks;
+        self.slide_state = state;
     }
 
     fn push_heading(&mut self, level: u8, mut text: Text) {
@@ -334,22 +962,63 @@ impl<'a> PresentationBuilder<'a> {
             6 => (ElementType::Heading6, &self.theme.headings.h6),
             other => panic!("unexpected heading level {other}"),
         };
+        if self.slide_state.title.is_none() {
+            self.slide_state.title = Some(text.to_plain_text());
+        }
+        if let Some(transform) = &style.text_transform {
+            Self::transform_text(&mut text, transform);
+        }
         if let Some(prefix) = &style.prefix {
             let mut prefix = prefix.clone();
             prefix.push(' ');
             text.chunks.insert(0, StyledText::from(prefix));
         }
+        let width = text.width();
+        // The border goes in front of the underline too so it looks like a single bar.
+        let border = style.border.map(|border| format!("{border} ")).unwrap_or_default();
+        let underline =
+            style.underline.map(|underline| Text::from(format!("{border}{}", underline.to_string().repeat(width))));
+        if !border.is_empty() {
+            text.chunks.insert(0, StyledText::from(border));
+        }
         let text_style = TextStyle::default().bold().colors(style.colors.clone());
         text.apply_style(&text_style);
 
-        self.push_text(text, element_type);
+        self.push_text(text, element_type.clone());
         self.push_line_break();
+        if let Some(mut underline) = underline {
+            underline.apply_style(&text_style);
+            self.push_text(underline, element_type);
+            self.push_line_break();
+        }
+    }
+
+    fn transform_text(text: &mut Text, transform: &TextTransform) {
+        let mut word_start = true;
+        for chunk in &mut text.chunks {
+            chunk.text = match transform {
+                TextTransform::Uppercase => chunk.text.to_uppercase(),
+                TextTransform::Title => {
+                    let mut output = String::new();
+                    for c in chunk.text.chars() {
+                        if word_start {
+                            output.extend(c.to_uppercase());
+                        } else {
+                            output.push(c);
+                        }
+                        word_start = c.is_whitespace();
+                    }
+                    output
+                }
+            };
+        }
     }
 
     fn push_paragraph(&mut self, elements: Vec<ParagraphElement>) -> Result<(), BuildError> {
         for element in elements {
             match element {
-                ParagraphElement::Text(text) => {
+                ParagraphElement::Text(mut text) => {
+                    self.load_inline_images(&mut text)?;
                     self.push_text(text, ElementType::Paragraph);
                     self.push_line_break();
                 }
@@ -365,14 +1034,131 @@ impl<'a> PresentationBuilder<'a> {
         self.chunk_operations.extend([RenderSeparator::default().into(), RenderOperation::RenderLineBreak]);
     }
 
-    fn push_image(&mut self, path: PathBuf) -> Result<(), BuildError> {
-        let image = self.resources.image(&path)?;
-        self.chunk_operations.push(RenderOperation::RenderImage(image));
-        self.chunk_operations.push(RenderOperation::SetColors(self.theme.default_style.colors.clone()));
+    fn push_image(&mut self, path: PathBuf, attributes: ImageAttributes) -> Result<(), BuildError> {
+        let image = self.resources.image(&path).map_err(Self::image_error)?;
+        self.push_loaded_image(image.with_attributes(attributes));
+        Ok(())
+    }
+
+    // Diagrams that can't be rendered show why instead, the same way code that fails to run does.
+    fn push_diagram(&mut self, code: &Code) {
+        let kind = match code.language {
+            CodeLanguage::Mermaid => DiagramKind::Mermaid,
+            CodeLanguage::Dot => DiagramKind::Graphviz,
+            CodeLanguage::D2 => DiagramKind::D2,
+            CodeLanguage::Latex => DiagramKind::Latex,
+            CodeLanguage::Typst => DiagramKind::Typst,
+            ref language => panic!("language {language:?} can't be rendered"),
+        };
+        match self.resources.diagram(kind, &code.contents, self.theme.default_style.colors.foreground) {
+            Ok(image) => self.push_loaded_image(image),
+            Err(e) => self.push_error_output(&e),
+        };
+    }
+
+    fn push_error_output(&mut self, error: &dyn Display) {
+        let output = OutputBlock {
+            heading: " [error] ".into(),
+            lines: error.to_string().lines().map(ToString::to_string).collect(),
+            default_colors: self.theme.default_style.colors.clone(),
+            block_colors: self.theme.execution_output.colors.clone(),
+            max_rows: None,
+            scroll: 0,
+        };
+        self.chunk_operations.push(RenderOperation::RenderDynamic(Rc::new(output)));
+    }
+
+    // This code runs while building, just like `command_output` commands, so it needs the same permission.
+    fn push_code_output(&mut self, code: &Code) -> Result<(), BuildError> {
+        let output = match (self.dry_run_execution(), self.command_output_policy()) {
+            (true, _) => CodeExecuter::dry_run(code, &self.options.execution),
+            (false, CommandOutputPolicy::Deny) => return Err(BuildError::CommandOutputNotAllowed),
+            (false, CommandOutputPolicy::Skip) => return Ok(()),
+            (false, CommandOutputPolicy::Confirm) => {
+                let execution = self.options.execution.clone().into();
+                let transcript = self.options.execution_transcript.clone();
+                let source = DeferredOutputSource::Code { code: code.clone().into(), execution, transcript };
+                self.push_deferred_output(source, code, code.visible_contents());
+                return Ok(());
+            }
+            (false, CommandOutputPolicy::Run) => {
+                let transcript = self.options.execution_transcript.as_ref();
+                self.resources.code_output(code, &self.options.execution, transcript)
+            }
+        };
+        let lines = match output {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.push_error_output(&e);
+                return Ok(());
+            }
+        };
+        let alignment = self.code_alignment(code);
+        let operations = Self::output_operations(&lines, &alignment, &self.theme.default_style.colors);
+        self.chunk_operations.extend(operations);
         Ok(())
     }
 
-    fn push_list(&mut self, list: Vec<ListItem>) {
+    // The output that replaces code is laid out like the code would've been, as a single block.
+    fn output_operations(lines: &[String], alignment: &Alignment, default_colors: &Colors) -> Vec<RenderOperation> {
+        let mut style = AnsiStyle::default();
+        let lines: Vec<Vec<_>> = lines.iter().map(|line| style.parse(line)).collect();
+        let width = |pieces: &[(String, TextStyle)]| pieces.iter().map(|(text, _)| text.width()).sum::<usize>();
+        let block_length = lines.iter().map(|pieces| width(pieces)).max().unwrap_or(0);
+        let mut operations = Vec::new();
+        for pieces in lines {
+            let chars: Vec<_> = pieces.iter().flat_map(|(text, style)| text.chars().map(move |c| (c, style))).collect();
+            let text = ansi::styled_text(&chars, default_colors);
+            let unformatted_length = width(&pieces);
+            let line = PreformattedLine { text, unformatted_length, block_length, alignment: alignment.clone() };
+            operations.extend([RenderOperation::RenderPreformattedLine(line), RenderOperation::RenderLineBreak]);
+        }
+        operations
+    }
+
+    // Output that's only produced once running what produces it is confirmed is listed along with the slide's
+    // code blocks, so it can be confirmed and run like they are.
+    fn push_deferred_output(&mut self, source: DeferredOutputSource, code: &Code, contents: String) {
+        let commands = source.commands();
+        let operation = Rc::new(DeferredOutputOperation::new(
+            source,
+            self.code_alignment(code),
+            self.theme.default_style.colors.clone(),
+            self.theme.execution_output.colors.clone(),
+        ));
+        self.chunk_operations.push(RenderOperation::RenderOnDemand(operation.clone()));
+        let chunk = self.slide_chunks.len();
+        let line = code.source_position.start.line;
+        let execution = Some(operation as Rc<dyn RenderOnDemand>);
+        let file = self.includes.last().cloned();
+        let block = CodeBlock { chunk, contents, execution, commands, line, file, replaced_with_output: true };
+        self.slide_state.code_blocks.push(block);
+    }
+
+    fn image_error(error: LoadImageError) -> BuildError {
+        match error {
+            LoadImageError::Fetch(e) => BuildError::FetchImage(e),
+            e => e.into(),
+        }
+    }
+
+    fn push_loaded_image(&mut self, image: Image) {
+        let image = match mem::take(&mut self.slide_state.disable_image_animation) {
+            true => image.without_animation(),
+            false => image,
+        };
+        match self.slide_state.image_float.take() {
+            Some(float) => {
+                self.chunk_operations.push(RenderOperation::RenderFloatingImage { image, float });
+                // Whatever comes next starts right next to the top of the image.
+                self.slide_state.ignore_element_line_break = true;
+            }
+            None => self.chunk_operations.push(RenderOperation::RenderImage(image)),
+        };
+        self.chunk_operations.push(RenderOperation::SetColors(self.theme.default_style.colors.clone()));
+    }
+
+    fn push_list(&mut self, list: Vec<ListItem>) -> Result<(), BuildError> {
         let last_chunk_operation = self.slide_chunks.last().and_then(|chunk| chunk.iter_operations().last());
         // If the last chunk ended in a list, pop the newline so we get them all next to each
         // other.
@@ -382,19 +1168,23 @@ impl<'a> PresentationBuilder<'a> {
         {
             self.slide_chunks.last_mut().unwrap().pop_last();
         }
-        // If this chunk just starts (because there was a pause), pick up from the last index.
+        // If this chunk just starts (because there was a pause), pick up from the last index. The same
+        // happens when a list is explicitly continued, which also works across slides.
+        let continuing = mem::take(&mut self.slide_state.continue_list);
         let start_index = match self.slide_state.last_element {
+            _ if continuing => self.last_list_index.map_or(0, |index| index + 1),
             LastElement::List { last_index } if self.chunk_operations.is_empty() => last_index + 1,
             _ => 0,
         };
 
         let iter = ListIterator::new(list, start_index);
         for item in iter {
-            self.push_list_item(item.index, item.item);
+            self.push_list_item(item.index, item.item)?;
         }
+        Ok(())
     }
 
-    fn push_list_item(&mut self, index: usize, item: ListItem) {
+    fn push_list_item(&mut self, index: usize, item: ListItem) -> Result<(), BuildError> {
         let padding_length = (item.depth as usize + 1) * 3;
         let mut prefix: String = " ".repeat(padding_length);
         match item.item_type {
@@ -422,14 +1212,48 @@ impl<'a> PresentationBuilder<'a> {
         let text = item.contents;
         self.push_aligned_text(text, Alignment::Left { margin: Margin::Fixed(prefix_length) });
         self.push_line_break();
+
+        self.slide_state.list_item_margin = Some(prefix_length);
+        let result = self.push_list_item_blocks(item.blocks, prefix_length);
+        self.slide_state.list_item_margin = None;
+        result?;
         if item.depth == 0 {
             self.slide_state.last_element = LastElement::List { last_index: index };
+            self.last_list_index = Some(index);
+        }
+        Ok(())
+    }
+
+    // The rest of a list item's blocks line up with its text, with an empty line before each one.
+    fn push_list_item_blocks(&mut self, blocks: Vec<ListItemBlock>, margin: u16) -> Result<(), BuildError> {
+        for block in blocks {
+            self.push_line_break();
+            match block {
+                ListItemBlock::Paragraph(text) => {
+                    self.push_aligned_text(text, Alignment::Left { margin: Margin::Fixed(margin) });
+                    self.push_line_break();
+                }
+                ListItemBlock::Code(code) => {
+                    self.process_code(*code)?;
+                    // Output and stdin blocks aren't displayed so they don't take the empty line before them either.
+                    match mem::take(&mut self.slide_state.ignore_element_line_break) {
+                        true => _ = self.chunk_operations.pop(),
+                        false => self.push_line_break(),
+                    };
+                }
+                ListItemBlock::BlockQuote(lines) => self.push_block_quote(lines),
+            };
         }
+        Ok(())
     }
 
     fn push_block_quote(&mut self, lines: Vec<String>) {
         let prefix = self.theme.block_quote.prefix.clone().unwrap_or_default();
         let block_length = lines.iter().map(|line| line.width() + prefix.width()).max().unwrap_or(0);
+        let alignment = match self.slide_state.list_item_margin {
+            Some(margin) => Alignment::Left { margin: Margin::Fixed(margin) },
+            None => self.theme.alignment(&ElementType::BlockQuote),
+        };
 
         self.chunk_operations.push(RenderOperation::SetColors(self.theme.block_quote.colors.clone()));
         for mut line in lines {
@@ -440,7 +1264,7 @@ impl<'a> PresentationBuilder<'a> {
                 text: line,
                 unformatted_length: line_length,
                 block_length,
-                alignment: self.theme.alignment(&ElementType::BlockQuote).clone(),
+                alignment: alignment.clone(),
             }));
             self.push_line_break();
         }
@@ -458,7 +1282,21 @@ impl<'a> PresentationBuilder<'a> {
             if chunk.style.is_code() {
                 chunk.style.colors = self.theme.inline_code.colors.clone();
             }
-            texts.push(chunk.into());
+            if self.link_references && chunk.style.is_link() {
+                self.slide_state.links.push(chunk.text.clone());
+            }
+            // Images were already loaded when their text was given room for them.
+            let image = chunk.image.as_ref().and_then(|image| {
+                let attributes =
+                    ImageAttributes { fit: image.attributes.fit.or(Some(ImageFit::Contain)), ..image.attributes };
+                let loaded = self.resources.image(&image.path).ok()?;
+                Some(loaded.without_animation().with_attributes(attributes))
+            });
+            let text = WeightedText::from(chunk);
+            texts.push(match image {
+                Some(image) => text.with_image(image),
+                None => text,
+            });
         }
         if !texts.is_empty() {
             self.chunk_operations
@@ -466,11 +1304,53 @@ impl<'a> PresentationBuilder<'a> {
         }
     }
 
+    // Images within text take up as many columns as they need to keep their aspect ratio in a single row, which is
+    // about twice as tall as a column is wide.
+    fn load_inline_images(&mut self, text: &mut Text) -> Result<(), BuildError> {
+        for chunk in &mut text.chunks {
+            let Some(image) = &chunk.image else {
+                continue;
+            };
+            let (width, height) = self.resources.image(&image.path).map_err(Self::image_error)?.size();
+            let columns = image
+                .attributes
+                .columns
+                .unwrap_or_else(|| (width as f64 / height.max(1) as f64 * 2.0).round().max(1.0) as u16);
+            chunk.text = NON_BREAKING_SPACE.repeat(columns as usize);
+        }
+        Ok(())
+    }
+
     fn push_line_break(&mut self) {
         self.chunk_operations.push(RenderOperation::RenderLineBreak);
     }
 
+    // Code blocks that come from a file get their contents replaced with the lines they include.
+    fn load_snippet(&mut self, mut code: Code) -> Result<Code, BuildError> {
+        let Some(snippet) = &code.attributes.snippet else {
+            return Ok(code);
+        };
+        let error = |e: &dyn Display| BuildError::InvalidSnippet(snippet.path.clone(), e.to_string());
+        let contents = self.resources.included_file(&snippet.path).map_err(|e| error(&e))?;
+        let lines: Vec<_> = contents.lines().collect();
+        let first = snippet.first_line.unwrap_or(1);
+        let last = snippet.last_line.unwrap_or(lines.len()).min(lines.len());
+        if first > lines.len() {
+            return Err(error(&format!("file only has {} lines", lines.len())));
+        }
+        let mut contents = lines[first - 1..last].join("\n");
+        contents.push('\n');
+        code.contents = contents;
+        Ok(code)
+    }
+
     fn push_code(&mut self, code: Code) {
+        // The element before this one already added its own spacing so this only tops it up.
+        if let (Some(spacing), Some(blank_lines)) = (self.theme.spacing.code, self.trailing_blank_lines()) {
+            for _ in blank_lines..usize::from(spacing) {
+                self.push_line_break();
+            }
+        }
         let (lines, context) = self.highlight_lines(&code);
         for line in lines {
             self.chunk_operations.push(RenderOperation::RenderDynamic(Rc::new(line)));
@@ -478,16 +1358,36 @@ impl<'a> PresentationBuilder<'a> {
         if self.options.allow_mutations && context.borrow().groups.len() > 1 {
             self.chunk_mutators.push(Box::new(HighlightMutator { context }));
         }
-        if code.attributes.execute {
-            self.push_code_execution(code);
+        let chunk = self.slide_chunks.len();
+        let contents = code.visible_contents();
+        let line = code.source_position.start.line;
+        let commands = match code.attributes.execute {
+            true => Self::execution_commands(&code, &self.options.execution),
+            false => Vec::new(),
+        };
+        let execution = code.attributes.execute.then(|| self.push_code_execution(code));
+        let file = self.includes.last().cloned();
+        let block = CodeBlock { chunk, contents, execution, commands, line, file, replaced_with_output: false };
+        self.slide_state.code_blocks.push(block);
+    }
+
+    // What running a piece of code does, which is shown when asking for confirmation before running it.
+    fn execution_commands(code: &Code, config: &ExecutionConfig) -> Vec<String> {
+        let mut commands = CodeExecuter::dry_run(code, config).unwrap_or_default();
+        if let Some(session) = &code.attributes.session {
+            commands.insert(0, format!("# in session {session}"));
+        }
+        if !code.attributes.needs.is_empty() {
+            commands.insert(0, format!("# after running {}", code.attributes.needs.join(", ")));
         }
+        commands
     }
 
     fn highlight_lines(&self, code: &Code) -> (Vec<HighlightedLine>, Rc<RefCell<HighlightContext>>) {
         let lines = CodePreparer { theme: &self.theme }.prepare(code);
-        let block_length = lines.iter().map(|line| line.width()).max().unwrap_or(0);
-        let mut empty_highlighter = self.highlighter.language_highlighter(&CodeLanguage::Unknown);
-        let mut code_highlighter = self.highlighter.language_highlighter(&code.language);
+        let block_length = lines.iter().filter(|line| !line.elided).map(|line| line.width()).max().unwrap_or(0);
+        let mut empty_highlighter = self.highlighter.language_highlighter(&CodeLanguage::Unknown(String::new()));
+        let mut code_highlighter = self.highlighter.code_highlighter(&code.language, &code.language_token);
         let padding_style = {
             let mut highlighter = self.highlighter.language_highlighter(&CodeLanguage::Rust);
             highlighter.style_line("//").first().expect("no styles").style
@@ -500,11 +1400,16 @@ impl<'a> PresentationBuilder<'a> {
             groups,
             current: 0,
             block_length,
-            alignment: self.theme.alignment(&ElementType::Code),
+            alignment: self.code_alignment(code),
         }));
 
         let mut output = Vec::new();
         for line in lines.into_iter() {
+            if line.elided {
+                // Elided lines still go through the highlighter so the state it carries across lines is kept.
+                code_highlighter.highlight_line(&line.code);
+                continue;
+            }
             let highlighted = line.highlight(&padding_style, &mut code_highlighter);
             let not_highlighted = line.highlight(&padding_style, &mut empty_highlighter);
             let width = line.width();
@@ -515,27 +1420,239 @@ impl<'a> PresentationBuilder<'a> {
         (output, context)
     }
 
-    fn push_code_execution(&mut self, code: Code) {
-        let operation = RunCodeOperation::new(
-            code,
-            self.theme.default_style.colors.clone(),
-            self.theme.execution_output.colors.clone(),
-        );
-        let operation = RenderOperation::RenderOnDemand(Rc::new(operation));
-        self.chunk_operations.push(operation);
-    }
+    fn code_alignment(&self, code: &Code) -> Alignment {
+        if let Some(margin) = self.slide_state.list_item_margin {
+            return Alignment::Left { margin: Margin::Fixed(margin) };
+        }
+        let alignment = self.theme.alignment(&ElementType::Code);
+        let margin = match &alignment {
+            Alignment::Left { margin } | Alignment::Right { margin } => margin.clone(),
+            Alignment::Center { minimum_margin, .. } => minimum_margin.clone(),
+        };
+        match code.attributes.alignment {
+            None => alignment,
+            Some(CodeAlignment::Left) => Alignment::Left { margin },
+            Some(CodeAlignment::Center) => {
+                let minimum_size = match alignment {
+                    Alignment::Center { minimum_size, .. } => minimum_size,
+                    _ => 0,
+                };
+                Alignment::Center { minimum_margin: margin, minimum_size }
+            }
+        }
+    }
+
+    fn push_code_execution(&mut self, code: Code) -> Rc<dyn RenderOnDemand> {
+        let operation = Rc::new(RunCodeOperation::new(
+            code,
+            self.options.execution.clone(),
+            self.theme.default_style.colors.clone(),
+            self.theme.execution_output.clone(),
+            self.dry_run_execution(),
+            self.options.execution_transcript.clone(),
+            self.options.sessions.clone(),
+        ));
+        self.code_executions.push(operation.clone());
+        self.slide_state.last_element = LastElement::Execution(operation.clone());
+        self.chunk_operations.push(RenderOperation::RenderOnDemand(operation.clone()));
+        operation
+    }
+
+    // Code blocks can need blocks that show up later in the presentation so these can only be
+    // linked once every slide is built.
+    fn link_code_dependencies(&self) -> Result<(), BuildError> {
+        let mut blocks = HashMap::new();
+        for operation in &self.code_executions {
+            if let Some(id) = operation.id() {
+                if blocks.insert(id, operation).is_some() {
+                    return Err(BuildError::CodeDependencies(format!("more than one code block has id '{id}'")));
+                }
+            }
+        }
+        for operation in &self.code_executions {
+            for id in &operation.code.attributes.needs {
+                if !blocks.contains_key(id.as_str()) {
+                    return Err(BuildError::CodeDependencies(format!("no executable code block has id '{id}'")));
+                }
+            }
+        }
+        let mut checked = HashSet::new();
+        for id in blocks.keys() {
+            Self::check_dependency_cycle(id, &blocks, &mut Vec::new(), &mut checked)?;
+        }
+        for operation in &self.code_executions {
+            let dependencies = operation.code.attributes.needs.iter().map(|id| blocks[id.as_str()].clone()).collect();
+            operation.set_dependencies(dependencies);
+        }
+        Ok(())
+    }
+
+    fn check_dependency_cycle<'b>(
+        id: &'b str,
+        blocks: &HashMap<&'b str, &'b Rc<RunCodeOperation>>,
+        path: &mut Vec<&'b str>,
+        checked: &mut HashSet<&'b str>,
+    ) -> Result<(), BuildError> {
+        // Blocks that were checked already aren't part of any cycle, and walking through them again for
+        // every block that needs them would take exponential time in long chains of blocks.
+        if checked.contains(id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|other| *other == id) {
+            let cycle = path[start..].iter().chain([&id]).join(" -> ");
+            return Err(BuildError::CodeDependencies(format!("code blocks need each other: {cycle}")));
+        }
+        path.push(id);
+        for dependency in &blocks[id].code.attributes.needs {
+            Self::check_dependency_cycle(dependency, blocks, path, checked)?;
+        }
+        path.pop();
+        checked.insert(id);
+        Ok(())
+    }
+
+    fn apply_slide_layout(&mut self, name: &str) -> Result<(), BuildError> {
+        let Some(layout) = self.theme.slide_layouts.get(name) else {
+            return Err(BuildError::InvalidSlideLayout(format!("theme has no layout named '{name}'")));
+        };
+        if !self.slide_chunks.is_empty() {
+            return Err(BuildError::InvalidSlideLayout("`slide_layout` can't be used after a pause".into()));
+        }
+        if let Some(split) = layout.split_background.clone() {
+            // Paint it right after the screen is cleared so it's behind everything else.
+            let clear_index = self.chunk_operations.iter().position(|op| matches!(op, RenderOperation::ClearScreen));
+            let index = clear_index.map_or(0, |index| index + 1);
+            self.chunk_operations.insert(index, RenderOperation::RenderSplitBackground(split));
+        }
+        Ok(())
+    }
+
+    fn push_poll(&mut self, definition: PollDefinition) -> Result<(), BuildError> {
+        if definition.options.is_empty() {
+            return Err(BuildError::InvalidPoll("poll has no options"));
+        }
+        let poll = Poll::new(definition, self.slides.len());
+        self.polls.push(poll.clone());
+        let operation = RenderOperation::RenderOnDemand(Rc::new(PollOperation::new(poll)));
+        self.chunk_operations.push(operation);
+        Ok(())
+    }
 
     fn terminate_slide(&mut self) {
+        let overflow = self.split_overflow();
+        let title = self.slide_state.title_text.take();
         let footer = self.generate_footer();
+        self.push_link_references();
 
         let operations = mem::take(&mut self.chunk_operations);
         let mutators = mem::take(&mut self.chunk_mutators);
         self.slide_chunks.push(SlideChunk::new(operations, mutators));
 
         let chunks = mem::take(&mut self.slide_chunks);
-        self.slides.push(Slide::new(chunks, footer));
+        let id = self.next_slide_id();
+        let notes = mem::take(&mut self.slide_state.notes);
+        let refresh_every = self.slide_state.refresh_every;
+        let code_blocks = mem::take(&mut self.slide_state.code_blocks);
+        let sounds = mem::take(&mut self.slide_state.sounds);
+        let metadata =
+            SlideMetadata { title: self.slide_state.title.take(), id, notes, refresh_every, code_blocks, sounds };
+        self.slides.push(Slide::new(chunks, footer).with_metadata(metadata));
+        self.footer_context.borrow_mut().section_colors.push(self.section_color);
         self.push_slide_prelude();
         self.slide_state = Default::default();
+
+        if let Some(overflow) = overflow {
+            if let Some(title) = title {
+                let mut continued_title = title.clone();
+                continued_title.chunks.push(StyledText::from(" (cont.)"));
+                self.push_slide_title(continued_title);
+                // Keep the original one so slides split more than once don't get more than one suffix.
+                self.slide_state.title_text = Some(title);
+            }
+            self.chunk_operations.extend(overflow);
+            self.terminate_slide();
+        }
+    }
+
+    // Takes the operations that don't fit in the current slide given the maximum slide height.
+    //
+    // Heights are estimated by counting lines so wrapped text and images aren't taken into account.
+    // Slides that pause or use layouts are never split.
+    fn split_overflow(&mut self) -> Option<Vec<RenderOperation>> {
+        let max_height = usize::from(self.max_slide_height?);
+        if !self.slide_chunks.is_empty() || !self.chunk_mutators.is_empty() {
+            return None;
+        }
+        let content_start = match self.slide_state.content_start {
+            Some(start) => start,
+            // Skip the prelude, which ends in the margin and a line break.
+            None => self.chunk_operations.iter().position(|op| matches!(op, RenderOperation::ApplyMargin(_)))? + 2,
+        };
+        let (header, body) = self.chunk_operations.split_at(content_start.min(self.chunk_operations.len()));
+        let positioned = body.iter().any(|operation| {
+            use RenderOperation::*;
+            matches!(operation, InitColumnLayout { .. } | JumpToBottomRow { .. } | JumpToVerticalCenter)
+        });
+        if positioned {
+            return None;
+        }
+        let mut rows = header.iter().filter(|operation| Self::is_row_end(operation)).count();
+        let mut split_at = None;
+        for (index, _) in body.iter().enumerate().filter(|(_, operation)| Self::is_row_end(operation)) {
+            rows += 1;
+            if rows > max_height {
+                break;
+            }
+            split_at = Some(content_start + index + 1);
+        }
+        if rows <= max_height {
+            return None;
+        }
+        let overflow = self.chunk_operations.split_off(split_at?);
+        // Don't start the next slide with blank lines.
+        let overflow: Vec<_> = overflow
+            .into_iter()
+            .skip_while(|operation| matches!(operation, RenderOperation::RenderLineBreak))
+            .collect();
+        if overflow.is_empty() { None } else { Some(overflow) }
+    }
+
+    fn is_row_end(operation: &RenderOperation) -> bool {
+        // Dynamic operations are mostly lines of code, which end in a line break of their own.
+        matches!(operation, RenderOperation::RenderLineBreak | RenderOperation::RenderDynamic(_))
+    }
+
+    // The references are printed right above the footer, one per line, in the order they first
+    // showed up in the slide.
+    fn push_link_references(&mut self) {
+        let links = mem::take(&mut self.slide_state.links);
+        if links.is_empty() {
+            return;
+        }
+        // Links used more than once are listed the first time they show up.
+        let links: Vec<_> = links.into_iter().unique().collect();
+        let bottom_row = u16::try_from(links.len() - 1).unwrap_or(u16::MAX);
+        self.chunk_operations
+            .extend([RenderOperation::ExitLayout, RenderOperation::JumpToBottomRow { index: bottom_row }]);
+        for (index, link) in links.into_iter().enumerate() {
+            let text = Text::from(format!("[{}] {link}", index + 1));
+            self.push_text(text, ElementType::Paragraph);
+            self.push_line_break();
+        }
+    }
+
+    // Slides that end up with the same id get a numeric suffix, like `intro`, `intro-2`, etc.
+    fn next_slide_id(&mut self) -> Option<SlideId> {
+        let id = self.slide_state.id.take().or_else(|| SlideId::from_title(self.slide_state.title.as_ref()?))?;
+        let mut unique_id = id.clone();
+        for suffix in 2.. {
+            if !self.slide_ids.contains(&unique_id) {
+                break;
+            }
+            unique_id = id.with_suffix(suffix);
+        }
+        self.slide_ids.insert(unique_id.clone());
+        Some(unique_id)
     }
 
     fn generate_footer(&mut self) -> Vec<RenderOperation> {
@@ -553,7 +1670,12 @@ impl<'a> PresentationBuilder<'a> {
         ]
     }
 
-    fn push_table(&mut self, table: Table) {
+    fn push_table(&mut self, mut table: Table) -> Result<(), BuildError> {
+        for row in iter::once(&mut table.header).chain(&mut table.rows) {
+            for text in &mut row.0 {
+                self.load_inline_images(text)?;
+            }
+        }
         let widths: Vec<_> = (0..table.columns())
             .map(|column| table.iter_column(column).map(|text| text.width()).max().unwrap_or(0))
             .collect();
@@ -572,7 +1694,7 @@ impl<'a> PresentationBuilder<'a> {
                     margin += 1;
                 }
             }
-            contents.extend(iter::repeat("─").take(*width + margin));
+            contents.push_str(&"─".repeat(*width + margin));
             separator.chunks.push(StyledText::from(contents));
         }
 
@@ -584,6 +1706,7 @@ impl<'a> PresentationBuilder<'a> {
             self.push_text(flattened_row, ElementType::Table);
             self.push_line_break();
         }
+        Ok(())
     }
 
     fn prepare_table_row(row: TableRow, widths: &[usize]) -> Text {
@@ -593,7 +1716,7 @@ impl<'a> PresentationBuilder<'a> {
                 flattened_row.chunks.push(StyledText::from(" │ "));
             }
             let text_length = text.width();
-            flattened_row.chunks.extend(text.chunks.into_iter());
+            flattened_row.chunks.extend(text.chunks);
 
             let cell_width = widths[column];
             if text_length < cell_width {
@@ -625,17 +1748,31 @@ impl<'a> CodePreparer<'a> {
     }
 
     fn push_lines(&self, code: &Code, horizontal_padding: u8, lines: &mut Vec<CodeLine>) {
-        if code.contents.is_empty() {
+        let total_lines = code.visible_lines().count();
+        if total_lines == 0 {
             return;
         }
 
         let padding = " ".repeat(horizontal_padding as usize);
-        let total_lines_width = code.contents.lines().count().ilog10();
-        for (index, line) in code.contents.lines().enumerate() {
+        let total_lines_width = total_lines.ilog10();
+        let mut folded = false;
+        for (index, line) in code.visible_lines().enumerate() {
             let mut line = line.to_string();
             let mut prefix = padding.clone();
+            let line_number = index + 1;
+            let shown = code.attributes.shown_lines.as_ref().map(|group| group.contains(line_number as u16));
+            let elided = !shown.unwrap_or(true);
+            if elided && code.attributes.fold && !folded {
+                let mut prefix = padding.clone();
+                if code.attributes.line_numbers {
+                    prefix.push_str(&" ".repeat(total_lines_width as usize + 2));
+                }
+                let code = format!("{FOLD_MARKER}\n");
+                let suffix = padding.clone();
+                lines.push(CodeLine { prefix, code, suffix, line_number: None, elided: false, fold_marker: true });
+            }
+            folded = elided;
             if code.attributes.line_numbers {
-                let line_number = index + 1;
                 let line_number_width = line_number.ilog10();
                 // Suffix this with padding to make all numbers pad to the right
                 let number_padding = total_lines_width - line_number_width;
@@ -644,8 +1781,9 @@ impl<'a> CodePreparer<'a> {
                 prefix.push(' ');
             }
             line.push('\n');
-            let line_number = Some(index as u16 + 1);
-            lines.push(CodeLine { prefix, code: line, suffix: padding.clone(), line_number });
+            let line_number = Some(line_number as u16);
+            let suffix = padding.clone();
+            lines.push(CodeLine { prefix, code: line, suffix, line_number, elided, fold_marker: false });
         }
     }
 }
@@ -655,11 +1793,14 @@ struct CodeLine {
     code: String,
     suffix: String,
     line_number: Option<u16>,
+    elided: bool,
+    fold_marker: bool,
 }
 
 impl CodeLine {
     fn empty() -> Self {
-        Self { prefix: String::new(), code: "\n".into(), suffix: String::new(), line_number: None }
+        let (prefix, suffix) = (String::new(), String::new());
+        Self { prefix, code: "\n".into(), suffix, line_number: None, elided: false, fold_marker: false }
     }
 
     fn width(&self) -> usize {
@@ -667,6 +1808,12 @@ impl CodeLine {
     }
 
     fn highlight(&self, padding_style: &Style, code_highlighter: &mut LanguageHighlighter) -> String {
+        if self.fold_marker {
+            // The marker isn't code, so it's styled like the padding and it's kept away from the highlighter, which
+            // carries state from one line to the next.
+            let line = format!("{}{}{}", self.prefix, self.code.trim_end_matches('\n'), self.suffix);
+            return StyledTokens { style: *padding_style, tokens: &line }.apply_style();
+        }
         let mut output = StyledTokens { style: *padding_style, tokens: &self.prefix }.apply_style();
         output.push_str(&code_highlighter.highlight_line(&self.code));
         // Remove newline
@@ -684,6 +1831,28 @@ struct HighlightContext {
     alignment: Alignment,
 }
 
+impl HighlightContext {
+    /// Get the alignment to use within the column the block is being rendered in.
+    ///
+    /// Margins are dropped if keeping them would mean the block no longer fits in the column so narrow
+    /// columns don't cut code off just to leave some empty space around it.
+    fn alignment(&self, dimensions: &WindowSize) -> Alignment {
+        let block_length = u16::try_from(self.block_length).unwrap_or(u16::MAX);
+        let fits = |margin: &Margin| {
+            let margin = margin.as_characters(dimensions.columns);
+            margin.saturating_mul(2).saturating_add(block_length) <= dimensions.columns
+        };
+        match &self.alignment {
+            Alignment::Left { margin } if !fits(margin) => Alignment::Left { margin: Margin::Fixed(0) },
+            Alignment::Right { margin } if !fits(margin) => Alignment::Right { margin: Margin::Fixed(0) },
+            Alignment::Center { minimum_margin, minimum_size } if !fits(minimum_margin) => {
+                Alignment::Center { minimum_margin: Margin::Fixed(0), minimum_size: *minimum_size }
+            }
+            alignment => alignment.clone(),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct HighlightedLine {
     highlighted: String,
@@ -694,7 +1863,7 @@ struct HighlightedLine {
 }
 
 impl AsRenderOperations for HighlightedLine {
-    fn as_render_operations(&self, _: &WindowSize) -> Vec<RenderOperation> {
+    fn as_render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
         let context = self.context.borrow();
         let group = &context.groups[context.current];
         let needs_highlight = self.line_number.map(|number| group.contains(number)).unwrap_or_default();
@@ -708,7 +1877,7 @@ impl AsRenderOperations for HighlightedLine {
                 text,
                 unformatted_length: self.width,
                 block_length: context.block_length,
-                alignment: context.alignment.clone(),
+                alignment: context.alignment(dimensions),
             }),
             RenderOperation::RenderLineBreak,
         ]
@@ -767,6 +1936,20 @@ struct SlideState {
     last_chunk_ended_in_list: bool,
     last_element: LastElement,
     layout: LayoutState,
+    title: Option<String>,
+    id: Option<SlideId>,
+    image_float: Option<ImageFloat>,
+    disable_image_animation: bool,
+    continue_list: bool,
+    // The margin of the list item whose blocks are being pushed, which they line up with.
+    list_item_margin: Option<u16>,
+    links: Vec<String>,
+    title_text: Option<Text>,
+    content_start: Option<usize>,
+    notes: Vec<String>,
+    refresh_every: Option<Duration>,
+    code_blocks: Vec<CodeBlock>,
+    sounds: Vec<SlideSound>,
 }
 
 #[derive(Debug, Default)]
@@ -789,12 +1972,17 @@ enum LastElement {
     List {
         last_index: usize,
     },
+    Execution(Rc<RunCodeOperation>),
 }
 
 #[derive(Debug, Default)]
 struct FooterContext {
     total_slides: usize,
+    // The color of the section every slide is in, if it has one.
+    section_colors: Vec<Option<Color>>,
     author: String,
+    social: String,
+    placeholders: PlaceholderRegistry,
 }
 
 #[derive(Debug)]
@@ -812,15 +2000,58 @@ impl FooterGenerator {
         colors: Colors,
         alignment: Alignment,
     ) -> RenderOperation {
-        let contents = template
-            .replace("{current_slide}", current_slide)
-            .replace("{total_slides}", &context.total_slides.to_string())
-            .replace("{author}", &context.author);
+        let values: HashMap<_, _> = [
+            ("current_slide", current_slide.to_string()),
+            ("total_slides", context.total_slides.to_string()),
+            ("author", context.author.clone()),
+            ("social", context.social.clone()),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        let contents = context.placeholders.render(template, &values);
         let text = WeightedText::from(StyledText::new(contents, TextStyle::default().colors(colors)));
         RenderOperation::RenderText { line: vec![text].into(), alignment }
     }
 }
 
+/// The sections listed in an agenda slide, with the one that comes next highlighted.
+///
+/// Every section is only known once the whole presentation is built so the list is shared by all
+/// agenda slides.
+#[derive(Debug)]
+struct AgendaList {
+    sections: Rc<RefCell<Vec<String>>>,
+    current_section: usize,
+    alignment: Alignment,
+    style: TextStyle,
+    current_style: TextStyle,
+}
+
+impl AsRenderOperations for AgendaList {
+    fn as_render_operations(&self, _: &WindowSize) -> Vec<RenderOperation> {
+        let mut operations = Vec::new();
+        for (index, section) in self.sections.borrow().iter().enumerate() {
+            let (marker, style) = match index == self.current_section {
+                true => ("▶ ", &self.current_style),
+                false => ("  ", &self.style),
+            };
+            let text = StyledText::new(format!("{marker}{section}"), style.clone());
+            let line = WeightedLine::from(vec![WeightedText::from(text)]);
+            operations.extend([
+                RenderOperation::RenderText { line, alignment: self.alignment.clone() },
+                RenderOperation::RenderLineBreak,
+                RenderOperation::RenderLineBreak,
+            ]);
+        }
+        operations
+    }
+
+    fn diffable_content(&self) -> Option<&str> {
+        None
+    }
+}
+
 impl AsRenderOperations for FooterGenerator {
     fn as_render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
         let context = self.context.borrow();
@@ -853,8 +2084,26 @@ impl AsRenderOperations for FooterGenerator {
                 let total_columns = dimensions.columns as usize / character.width();
                 let progress_ratio = (self.current_slide + 1) as f64 / context.total_slides as f64;
                 let columns_ratio = (total_columns as f64 * progress_ratio).ceil();
-                let bar = character.repeat(columns_ratio as usize);
-                let bar = vec![WeightedText::from(StyledText::new(bar, TextStyle::default().colors(colors.clone())))];
+                // Every column stands for a slide, and is painted in the color of the section the slide is in.
+                let mut segments: Vec<(Colors, usize)> = Vec::new();
+                for column in 0..columns_ratio as usize {
+                    let slide = column * context.total_slides / total_columns;
+                    let mut colors = colors.clone();
+                    if let Some(Some(color)) = context.section_colors.get(slide) {
+                        colors.foreground = Some(*color);
+                    }
+                    match segments.last_mut() {
+                        Some((last_colors, length)) if *last_colors == colors => *length += 1,
+                        _ => segments.push((colors, 1)),
+                    };
+                }
+                let bar: Vec<_> = segments
+                    .into_iter()
+                    .map(|(colors, length)| {
+                        let style = TextStyle::default().colors(colors);
+                        WeightedText::from(StyledText::new(character.repeat(length), style))
+                    })
+                    .collect();
                 vec![
                     RenderOperation::JumpToBottomRow { index: 0 },
                     RenderOperation::RenderText {
@@ -878,14 +2127,17 @@ pub enum BuildError {
     #[error("loading image: {0}")]
     LoadImage(#[from] LoadImageError),
 
+    #[error("fetching image: {0}")]
+    FetchImage(FetchImageError),
+
     #[error("invalid presentation metadata: {0}")]
     InvalidMetadata(String),
 
     #[error("invalid theme: {0}")]
     InvalidTheme(#[from] LoadThemeError),
 
-    #[error("invalid code highlighter theme")]
-    InvalidCodeTheme,
+    #[error("invalid code highlighter theme: {0}")]
+    InvalidCodeTheme(#[from] InvalidCodeTheme),
 
     #[error("invalid layout: {0}")]
     InvalidLayout(&'static str),
@@ -902,21 +2154,257 @@ pub enum BuildError {
     #[error("need to enter layout column explicitly using `column` command")]
     NotInsideColumn,
 
+    #[error("invalid poll: {0}")]
+    InvalidPoll(&'static str),
+
     #[error("error parsing command at line {line}: {error}")]
     CommandParse { line: usize, error: CommandParseError },
+
+    #[error("found `endif` without a matching `if`")]
+    UnmatchedEndIf,
+
+    #[error("`if` block is missing its `endif`")]
+    UnterminatedCondition,
+
+    #[error("invalid template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("invalid slide layout: {0}")]
+    InvalidSlideLayout(String),
+
+    #[error("invalid intro slide template {0:?}: {1}")]
+    InvalidIntroSlideTemplate(PathBuf, String),
+
+    #[error("including code from {0:?}: {1}")]
+    InvalidSnippet(PathBuf, String),
+
+    #[error("code block at line {line}: language '{language}' does not support execution")]
+    ExecutionNotSupported { language: String, line: usize },
+
+    #[error("reading stdin from {0:?}: {1}")]
+    InvalidStdin(PathBuf, String),
+
+    #[error("host '{0}' is not configured")]
+    UnknownHost(String),
+
+    #[error("invalid code block dependencies: {0}")]
+    CodeDependencies(String),
+
+    #[error("including {0:?}: {1}")]
+    InvalidInclude(PathBuf, String),
+
+    #[error("{0:?} ends up including itself")]
+    IncludeCycle(PathBuf),
+
+    #[error("in {path:?}: {error}")]
+    Included { path: PathBuf, error: Box<BuildError> },
+
+    #[error("running commands isn't allowed, use --allow-command-output to allow it")]
+    CommandOutputNotAllowed,
+
+    #[error("running command '{0}': {1}")]
+    CommandOutput(String, String),
+
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Multiple(Vec<SlideBuildError>),
+}
+
+/// An error found when building a specific slide.
+#[derive(thiserror::Error, Debug)]
+#[error("slide {slide}: {error}")]
+pub struct SlideBuildError {
+    /// The slide the error was found in, starting at 1.
+    pub slide: usize,
+
+    /// The error itself.
+    pub error: BuildError,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+/// A command.
+// The names in `strum` attributes need to match the ones in `serde` attributes, they're used to tell
+// unknown commands apart from known ones used the wrong way.
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema, EnumVariantNames)]
 #[serde(rename_all = "snake_case")]
-enum CommentCommand {
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum CommentCommand {
+    /// Pause before showing the content after this.
     Pause,
+
+    /// End the current slide.
     EndSlide,
+
+    /// Split the slide into columns, using the width of each of them in units.
     #[serde(rename = "column_layout")]
+    #[strum(serialize = "column_layout")]
     InitColumnLayout(Vec<u8>),
+
+    /// Enter a column.
     Column(usize),
+
+    /// Leave the column layout.
     ResetLayout,
+
+    /// A poll the audience can vote on.
+    Poll(PollDefinition),
+
+    /// Only include the content until the matching `endif` if a condition is met, e.g.
+    /// `profile == "workshop"` or `profile != "workshop"`.
+    If(#[schemars(with = "String")] Condition),
+
+    /// End the content included by an `if`.
+    #[serde(rename = "endif")]
+    #[strum(serialize = "endif")]
+    EndIf,
+
+    /// Define a template with the content until the matching `end_template`.
+    Template(String),
+
+    /// End the content of a template.
+    EndTemplate,
+
+    /// Include the contents of a template, optionally followed by its values, e.g. `name with: {a: b}`.
+    UseTemplate(#[schemars(with = "String")] TemplateInstance),
+
+    /// Set the id of the current slide, which is otherwise derived from its title.
+    SlideId(String),
+
+    /// Place the next image on one side of the slide and let the content after it flow next to it,
+    /// e.g. `right 40%`.
+    ImageFloat(#[schemars(with = "String")] ImageFloat),
+
+    /// Set whether the next image plays its animation, if it's an animated GIF.
+    ImageAnimation(bool),
+
+    /// Continue the list in the previous slide rather than starting a new one.
+    ContinueList,
+
+    /// Set the color the progress bar uses for the current slide and every slide after it, e.g. `ff0000`.
+    SectionColor(Color),
+
+    /// Use one of the layouts defined in the theme for the current slide.
+    SlideLayout(String),
+
+    /// Run a shell command when the presentation is loaded and insert its output.
+    CommandOutput(String),
+
+    /// Add a note for the presenter to the current slide, shown in the presenter console.
+    SpeakerNote(String),
+
+    /// Build the current slide again periodically while it's displayed, running its commands
+    /// again, e.g. every `30s`, `5m` or `500ms`.
+    RefreshEvery(#[schemars(with = "String")] RefreshInterval),
+
+    /// Play a sound when the content after this is shown, if a sound player is configured.
+    Sound(PathBuf),
+
+    /// Include the contents of another markdown file, relative to the presentation.
+    Include(PathBuf),
+}
+
+impl CommentCommand {
+    /// Get the JSON schema for the commands that can be used in comments.
+    pub(crate) fn json_schema() -> serde_json::Value {
+        schema::schema_for::<Self>("presenterm comment command")
+    }
+}
+
+/// A use of a template, e.g. `comparison with: {left: "A", right: "B"}`.
+#[derive(Debug, Clone, PartialEq, DeserializeFromStr)]
+pub(crate) struct TemplateInstance {
+    name: String,
+    values: HashMap<String, String>,
+}
+
+impl FromStr for TemplateInstance {
+    type Err = serde_yaml::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (name, values) = match input.split_once(" with:") {
+            Some((name, values)) => (name, serde_yaml::from_str(values)?),
+            None => (input, HashMap::new()),
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(serde::de::Error::custom(format!("invalid template name '{name}'")));
+        }
+        Ok(Self { name: name.into(), values })
+    }
+}
+
+/// A condition that decides whether a block of content is included, e.g. `profile == "workshop"`.
+#[derive(Debug, Clone, PartialEq, DeserializeFromStr)]
+pub(crate) enum Condition {
+    ProfileEquals(String),
+    ProfileNotEquals(String),
+}
+
+impl Condition {
+    fn evaluate(&self, profile: Option<&str>) -> bool {
+        match self {
+            Self::ProfileEquals(expected) => profile == Some(expected),
+            Self::ProfileNotEquals(expected) => profile != Some(expected),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ParseConditionError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (variable, operator, value) = match (input.split_once("=="), input.split_once("!=")) {
+            (Some((variable, value)), None) => (variable, "==", value),
+            (None, Some((variable, value))) => (variable, "!=", value),
+            _ => return Err(ParseConditionError(input.into())),
+        };
+        if variable.trim() != "profile" {
+            return Err(ParseConditionError(input.into()));
+        }
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+        if value.is_empty() || value.contains(char::is_whitespace) {
+            return Err(ParseConditionError(input.into()));
+        }
+        match operator {
+            "==" => Ok(Self::ProfileEquals(value.into())),
+            _ => Ok(Self::ProfileNotEquals(value.into())),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("invalid condition '{0}', expected `profile == \"<name>\"` or `profile != \"<name>\"`")]
+pub(crate) struct ParseConditionError(String);
+
+/// How often a slide is refreshed, e.g. `30s`.
+#[derive(Debug, Clone, PartialEq, DeserializeFromStr)]
+pub(crate) struct RefreshInterval(Duration);
+
+impl FromStr for RefreshInterval {
+    type Err = ParseRefreshIntervalError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || ParseRefreshIntervalError(input.into());
+        let input = input.trim();
+        let split = input.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+        let (amount, unit) = input.split_at(split);
+        let amount: u64 = amount.parse().map_err(|_| error())?;
+        let interval = match unit {
+            "ms" => Duration::from_millis(amount),
+            "s" => Duration::from_secs(amount),
+            "m" => Duration::from_secs(amount.checked_mul(60).ok_or_else(error)?),
+            _ => return Err(error()),
+        };
+        if interval.is_zero() {
+            return Err(error());
+        }
+        Ok(Self(interval))
+    }
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("invalid refresh interval '{0}', expected an amount and a unit like `30s`, `5m` or `500ms`")]
+pub(crate) struct ParseRefreshIntervalError(String);
+
 impl FromStr for CommentCommand {
     type Err = CommandParseError;
 
@@ -924,86 +2412,159 @@ impl FromStr for CommentCommand {
         #[derive(Deserialize)]
         struct CommandWrapper(#[serde(with = "serde_yaml::with::singleton_map")] CommentCommand);
 
+        // The values in a template use aren't valid YAML when put together with its name.
+        if let Some(instance) = s.trim().strip_prefix("use_template:") {
+            return Ok(Self::UseTemplate(instance.parse()?));
+        }
+        Self::check_name(&serde_yaml::from_str(s)?)?;
         let wrapper = serde_yaml::from_str::<CommandWrapper>(s)?;
         Ok(wrapper.0)
     }
 }
 
-#[derive(thiserror::Error, Debug)]
-pub struct CommandParseError(#[from] serde_yaml::Error);
+impl CommentCommand {
+    // Every command is either its name alone or a map from its name to its value.
+    fn check_name(command: &serde_yaml::Value) -> Result<(), CommandParseError> {
+        let name = match command {
+            serde_yaml::Value::String(name) => name.as_str(),
+            serde_yaml::Value::Mapping(mapping) => match mapping.keys().next().and_then(serde_yaml::Value::as_str) {
+                Some(name) => name,
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        match Self::VARIANTS.contains(&name) {
+            true => Ok(()),
+            false => Err(CommandParseError::UnknownCommand(name.into())),
+        }
+    }
+}
+
+/// A list of commands in a single line of a comment, e.g. `[pause, column: 1]`.
+struct CommentCommands(Vec<CommentCommand>);
+
+impl FromStr for CommentCommands {
+    type Err = CommandParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.trim_start().starts_with('[') {
+            return Ok(Self(vec![s.parse()?]));
+        }
+        #[derive(Deserialize)]
+        struct CommandWrapper(#[serde(with = "serde_yaml::with::singleton_map")] CommentCommand);
 
-impl Display for CommandParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let inner = self.0.to_string();
-        // Remove the trailing "at line X, ..." that comes from serde_yaml. This otherwise claims
-        // we're always in line 1 because the yaml is parsed in isolation out of the HTML comment.
-        let inner = inner.split(" at line").next().unwrap();
-        write!(f, "{inner}")
+        for command in serde_yaml::from_str::<Vec<serde_yaml::Value>>(s)? {
+            CommentCommand::check_name(&command)?;
+        }
+        let wrappers = serde_yaml::from_str::<Vec<CommandWrapper>>(s)?;
+        Ok(Self(wrappers.into_iter().map(|wrapper| wrapper.0).collect()))
     }
 }
 
-#[derive(Debug)]
-struct RunCodeOperationInner {
-    handle: Option<ExecutionHandle>,
-    output_lines: Vec<String>,
-    state: RenderOnDemandState,
+#[derive(thiserror::Error, Debug)]
+pub enum CommandParseError {
+    /// The command doesn't exist, as opposed to a known command used the wrong way.
+    #[error("unknown command: {0}")]
+    UnknownCommand(String),
+
+    #[error("{}", trim_yaml_location(.0))]
+    Invalid(#[from] serde_yaml::Error),
 }
 
-#[derive(Debug)]
-pub(crate) struct RunCodeOperation {
-    code: Code,
-    default_colors: Colors,
-    block_colors: Colors,
-    inner: Rc<RefCell<RunCodeOperationInner>>,
+// Remove the trailing "at line X, ..." that comes from serde_yaml. This otherwise claims we're always in
+// line 1 because the yaml is parsed in isolation out of the HTML comment.
+fn trim_yaml_location(error: &serde_yaml::Error) -> String {
+    let error = error.to_string();
+    error.split(" at line").next().unwrap_or_default().to_string()
 }
 
-impl RunCodeOperation {
-    fn new(code: Code, default_colors: Colors, block_colors: Colors) -> Self {
-        let inner =
-            RunCodeOperationInner { handle: None, output_lines: Vec::new(), state: RenderOnDemandState::default() };
-        Self { code, default_colors, block_colors, inner: Rc::new(RefCell::new(inner)) }
+type DeferredOutput = Result<Vec<String>, String>;
+
+// Programs color their output using escape sequences, which aren't part of its text.
+fn uncolored_lines(lines: &[String]) -> Vec<String> {
+    let mut style = AnsiStyle::default();
+    lines.iter().map(|line| style.parse(line).into_iter().map(|(text, _)| text).collect()).collect()
+}
+
+/// What produces the output that replaces it, see [DeferredOutputOperation].
+#[derive(Clone, Debug)]
+enum DeferredOutputSource {
+    /// A code block that uses `+exec_replace`, whose runs are appended to the transcript if there's one.
+    Code { code: Box<Code>, execution: Box<ExecutionConfig>, transcript: Option<ExecutionTranscript> },
+
+    /// A `command_output` command, which runs in the given directory and is killed after the given timeout.
+    Command { command: String, directory: PathBuf, timeout_ms: Option<u64> },
+}
+
+impl DeferredOutputSource {
+    fn commands(&self) -> Vec<String> {
+        match self {
+            Self::Code { code, execution, .. } => PresentationBuilder::execution_commands(code, execution),
+            Self::Command { command, .. } => vec![format!("$ {command}")],
+        }
     }
 
-    fn render_line(&self, line: String) -> RenderOperation {
-        let line_len = line.len();
-        RenderOperation::RenderPreformattedLine(PreformattedLine {
-            text: line,
-            unformatted_length: line_len,
-            block_length: line_len,
-            alignment: Default::default(),
-        })
+    fn run(&self) -> DeferredOutput {
+        match self {
+            Self::Code { code, execution, transcript } => {
+                CodeExecuter::execute_sync(code, execution, transcript.as_ref()).map_err(|e| e.to_string())
+            }
+            Self::Command { command, directory, timeout_ms } => {
+                match CodeExecuter::run_command(command, directory, *timeout_ms) {
+                    Ok(output) => Ok(output.trim_end().lines().map(ToString::to_string).collect()),
+                    Err(e) => Err(BuildError::CommandOutput(command.clone(), e.to_string()).to_string()),
+                }
+            }
+        }
     }
 }
 
-impl AsRenderOperations for RunCodeOperation {
+/// Output that replaces what produced it, which only runs while presenting once running it is confirmed rather
+/// than when the presentation is built.
+#[derive(Debug)]
+struct DeferredOutputOperation {
+    source: DeferredOutputSource,
+    alignment: Alignment,
+    default_colors: Colors,
+    error_colors: Colors,
+    state: RefCell<RenderOnDemandState>,
+    // Set by the thread the source runs in once it finishes.
+    output: Arc<Mutex<Option<DeferredOutput>>>,
+}
+
+impl DeferredOutputOperation {
+    fn new(source: DeferredOutputSource, alignment: Alignment, default_colors: Colors, error_colors: Colors) -> Self {
+        Self {
+            source,
+            alignment,
+            default_colors,
+            error_colors,
+            state: Default::default(),
+            output: Default::default(),
+        }
+    }
+}
+
+impl AsRenderOperations for DeferredOutputOperation {
     fn as_render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
-        let inner = self.inner.borrow();
-        if matches!(inner.state, RenderOnDemandState::NotStarted) {
+        if !matches!(*self.state.borrow(), RenderOnDemandState::Rendered) {
             return Vec::new();
         }
-        let state = match inner.state {
-            RenderOnDemandState::Rendered => "done",
-            _ => "running",
-        };
-        let heading = format!(" [{state}] ");
-        let separator = RenderSeparator::new(heading);
-        let mut operations = vec![
-            RenderOperation::RenderLineBreak,
-            RenderOperation::RenderDynamic(Rc::new(separator)),
-            RenderOperation::RenderLineBreak,
-            RenderOperation::RenderLineBreak,
-            RenderOperation::SetColors(self.block_colors.clone()),
-        ];
-
-        for line in &inner.output_lines {
-            let chunks = line.chars().chunks(dimensions.columns as usize);
-            for chunk in &chunks {
-                operations.push(self.render_line(chunk.collect()));
-                operations.push(RenderOperation::RenderLineBreak);
+        match self.output.lock().unwrap().as_ref() {
+            Some(Ok(lines)) => PresentationBuilder::output_operations(lines, &self.alignment, &self.default_colors),
+            Some(Err(e)) => {
+                let output = OutputBlock {
+                    heading: " [error] ".into(),
+                    lines: e.lines().map(ToString::to_string).collect(),
+                    default_colors: self.default_colors.clone(),
+                    block_colors: self.error_colors.clone(),
+                    max_rows: None,
+                    scroll: 0,
+                };
+                output.as_render_operations(dimensions)
             }
+            None => Vec::new(),
         }
-        operations.push(RenderOperation::SetColors(self.default_colors.clone()));
-        operations
     }
 
     fn diffable_content(&self) -> Option<&str> {
@@ -1011,41 +2572,507 @@ impl AsRenderOperations for RunCodeOperation {
     }
 }
 
-impl RenderOnDemand for RunCodeOperation {
+impl RenderOnDemand for DeferredOutputOperation {
     fn poll_state(&self) -> RenderOnDemandState {
-        let mut inner = self.inner.borrow_mut();
-        if let Some(handle) = inner.handle.as_mut() {
-            let state = handle.state();
-            let ExecutionState { output, status } = state;
-            if status.is_finished() {
-                inner.handle.take();
-                inner.state = RenderOnDemandState::Rendered;
-            }
-            inner.output_lines = output;
-            if matches!(status, ProcessStatus::Failure) {
-                inner.output_lines.push("[finished with error]".to_string());
-            }
+        let mut state = self.state.borrow_mut();
+        if matches!(*state, RenderOnDemandState::Rendering) && self.output.lock().unwrap().is_some() {
+            *state = RenderOnDemandState::Rendered;
         }
-        inner.state.clone()
+        state.clone()
     }
 
-    fn start_render(&self) -> bool {
-        let mut inner = self.inner.borrow_mut();
-        if !matches!(inner.state, RenderOnDemandState::NotStarted) {
+    fn failed(&self) -> bool {
+        matches!(self.output.lock().unwrap().as_ref(), Some(Err(_)))
+    }
+
+    fn output(&self) -> Vec<String> {
+        match self.output.lock().unwrap().as_ref() {
+            Some(Ok(lines)) => uncolored_lines(lines),
+            Some(Err(e)) => e.lines().map(ToString::to_string).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn restart_render(&self) -> bool {
+        {
+            let mut state = self.state.borrow_mut();
+            if matches!(*state, RenderOnDemandState::Rendering) {
+                return false;
+            }
+            *state = RenderOnDemandState::NotStarted;
+        }
+        self.start_render()
+    }
+
+    fn start_render(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        if !matches!(*state, RenderOnDemandState::NotStarted) {
             return false;
         }
-        match CodeExecuter::execute(&self.code) {
+        *state = RenderOnDemandState::Rendering;
+        *self.output.lock().unwrap() = None;
+        let source = self.source.clone();
+        let output = self.output.clone();
+        thread::spawn(move || {
+            let result = source.run();
+            *output.lock().unwrap() = Some(result);
+        });
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+struct RunCodeOperationInner {
+    handle: Option<ExecutionHandle>,
+    output_lines: Vec<String>,
+    state: RenderOnDemandState,
+    failed: bool,
+    // The dependencies that still have to run before this block does, in the order they run in.
+    pending: VecDeque<Rc<RunCodeOperation>>,
+    total_dependencies: usize,
+    // How many rows the output is scrolled up from its bottom, when it's capped.
+    scroll: usize,
+    started_at: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct RunCodeOperation {
+    code: Code,
+    execution: ExecutionConfig,
+    default_colors: Colors,
+    style: ExecutionOutputBlockStyle,
+    dry_run: bool,
+    transcript: Option<ExecutionTranscript>,
+    sessions: SessionManager,
+    // The furthest the output can be scrolled up, as of the last time it was rendered.
+    max_scroll: Cell<usize>,
+    canned_output: RefCell<Vec<String>>,
+    stdin: RefCell<Option<String>>,
+    expected_output: RefCell<Option<Vec<String>>>,
+    dependencies: RefCell<Vec<Rc<RunCodeOperation>>>,
+    inner: Rc<RefCell<RunCodeOperationInner>>,
+}
+
+impl RunCodeOperation {
+    fn new(
+        code: Code,
+        execution: ExecutionConfig,
+        default_colors: Colors,
+        style: ExecutionOutputBlockStyle,
+        dry_run: bool,
+        transcript: Option<ExecutionTranscript>,
+        sessions: SessionManager,
+    ) -> Self {
+        Self {
+            code,
+            execution,
+            default_colors,
+            style,
+            dry_run,
+            transcript,
+            sessions,
+            max_scroll: Default::default(),
+            canned_output: Default::default(),
+            stdin: Default::default(),
+            expected_output: Default::default(),
+            dependencies: Default::default(),
+            inner: Default::default(),
+        }
+    }
+
+    fn set_canned_output(&self, lines: Vec<String>) {
+        *self.canned_output.borrow_mut() = lines;
+    }
+
+    fn set_stdin(&self, input: String) {
+        *self.stdin.borrow_mut() = Some(input);
+    }
+
+    fn set_expected_output(&self, lines: Vec<String>) {
+        *self.expected_output.borrow_mut() = Some(lines);
+    }
+
+    // Trailing whitespace and empty lines at the end are ignored when comparing output.
+    fn comparable_lines(lines: impl Iterator<Item = String>) -> Vec<String> {
+        let mut lines: Vec<_> = lines.map(|line| line.trim_end().to_string()).collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.code.attributes.id.as_deref()
+    }
+
+    fn set_dependencies(&self, dependencies: Vec<Rc<RunCodeOperation>>) {
+        *self.dependencies.borrow_mut() = dependencies;
+    }
+
+    // Every block this one needs, directly or through other blocks, in the order they need to run in.
+    fn ordered_dependencies(&self) -> Vec<Rc<RunCodeOperation>> {
+        fn visit(dependencies: &[Rc<RunCodeOperation>], ordered: &mut Vec<Rc<RunCodeOperation>>) {
+            for dependency in dependencies {
+                if ordered.iter().any(|other| Rc::ptr_eq(other, dependency)) {
+                    continue;
+                }
+                visit(&dependency.dependencies.borrow(), ordered);
+                ordered.push(dependency.clone());
+            }
+        }
+        let mut ordered = Vec::new();
+        visit(&self.dependencies.borrow(), &mut ordered);
+        ordered
+    }
+
+    // The output of every block this one needs that ran, in order and under its id, followed by its own.
+    fn needed_output(&self, inner: &RunCodeOperationInner) -> Vec<String> {
+        let mut lines = Vec::new();
+        for dependency in self.ordered_dependencies() {
+            let dependency_inner = dependency.inner.borrow();
+            if matches!(dependency_inner.state, RenderOnDemandState::NotStarted) {
+                continue;
+            }
+            lines.push(format!("[{}]", dependency.id().unwrap_or_default()));
+            lines.extend(dependency_inner.output_lines.iter().cloned());
+        }
+        if inner.pending.is_empty() {
+            lines.extend(inner.output_lines.iter().cloned());
+        }
+        lines
+    }
+
+    fn succeeded(&self) -> bool {
+        let inner = self.inner.borrow();
+        matches!(inner.state, RenderOnDemandState::Rendered) && !inner.failed
+    }
+
+    // Runs this block's code alone, without looking at its dependencies.
+    fn run(&self, inner: &mut RunCodeOperationInner) {
+        if self.dry_run {
+            match CodeExecuter::dry_run(&self.code, &self.execution) {
+                Ok(lines) => {
+                    inner.output_lines = lines.into_iter().chain(self.canned_output.borrow().clone()).collect();
+                }
+                Err(e) => {
+                    inner.output_lines = vec![e.to_string()];
+                    inner.failed = true;
+                }
+            };
+            inner.state = RenderOnDemandState::Rendered;
+            return;
+        }
+        let stdin = self.stdin.borrow().clone();
+        let handle = match &self.code.attributes.session {
+            Some(name) => self.sessions.execute(name, &self.code, &self.execution, stdin),
+            None => CodeExecuter::execute_with_stdin(&self.code, &self.execution, stdin),
+        };
+        match handle {
             Ok(handle) => {
                 inner.handle = Some(handle);
+                inner.started_at = Some(Instant::now());
                 inner.state = RenderOnDemandState::Rendering;
-                true
             }
             Err(e) => {
                 inner.output_lines = vec![e.to_string()];
+                inner.failed = true;
+                inner.state = RenderOnDemandState::Rendered;
+            }
+        }
+    }
+
+    fn record_transcript(&self, inner: &mut RunCodeOperationInner, status: &ProcessStatus, exit_code: Option<i32>) {
+        let Some(transcript) = &self.transcript else {
+            return;
+        };
+        let commands = PresentationBuilder::execution_commands(&self.code, &self.execution);
+        let source = TranscriptEntry::source(&self.code);
+        let entry = TranscriptEntry {
+            commands: &commands,
+            source: &source,
+            output: &inner.output_lines,
+            status,
+            exit_code,
+            duration: inner.started_at.take().map(|started_at| started_at.elapsed()).unwrap_or_default(),
+        };
+        // There's nowhere else to show this in the middle of a presentation.
+        if let Err(e) = transcript.record(&entry) {
+            inner.output_lines.push(format!("[failed to write transcript: {e}]"));
+        }
+    }
+
+    // A dependency that's already running, e.g. because it was run in its own slide, is waited for instead.
+    fn run_dependency(dependency: &RunCodeOperation) {
+        let mut inner = dependency.inner.borrow_mut();
+        if matches!(inner.state, RenderOnDemandState::Rendering) {
+            return;
+        }
+        inner.output_lines.clear();
+        inner.failed = false;
+        dependency.run(&mut inner);
+    }
+}
+
+/// A block of output lines under a separator, like the one code blocks write into when they run.
+#[derive(Debug)]
+struct OutputBlock {
+    heading: String,
+    lines: Vec<String>,
+    default_colors: Colors,
+    block_colors: Colors,
+    // The most rows shown at once, if the output is capped.
+    max_rows: Option<usize>,
+    // How many rows the visible ones are scrolled up from the bottom by, when it's capped.
+    scroll: usize,
+}
+
+impl OutputBlock {
+    // Also returns how far up the output can be scrolled.
+    fn render(&self, dimensions: &WindowSize) -> (Vec<RenderOperation>, usize) {
+        // Programs color their output using escape sequences so those are turned into styled text.
+        let mut style = AnsiStyle::default();
+        let mut rows = Vec::new();
+        for line in &self.lines {
+            let pieces = style.parse(line);
+            let chars: Vec<_> = pieces.iter().flat_map(|(text, style)| text.chars().map(move |c| (c, style))).collect();
+            for chunk in chars.chunks(usize::from(dimensions.columns).max(1)) {
+                rows.push(self.render_line(chunk));
+            }
+        }
+        // Capped output shows the rows at the bottom by default, where new ones show up.
+        let total_rows = rows.len();
+        let (rows, heading, max_scroll) = match self.max_rows {
+            Some(max_rows) if total_rows > max_rows => {
+                let max_scroll = total_rows - max_rows;
+                let end = total_rows - self.scroll.min(max_scroll);
+                let start = end - max_rows;
+                let heading = format!("{}[{}-{end}/{total_rows}] ", self.heading, start + 1);
+                (rows.drain(start..end).collect(), heading, max_scroll)
+            }
+            _ => (rows, self.heading.clone(), 0),
+        };
+
+        let separator = RenderSeparator::new(heading);
+        let mut operations = vec![
+            RenderOperation::RenderLineBreak,
+            RenderOperation::RenderDynamic(Rc::new(separator)),
+            RenderOperation::RenderLineBreak,
+            RenderOperation::RenderLineBreak,
+            RenderOperation::SetColors(self.block_colors.clone()),
+        ];
+        for row in rows {
+            operations.extend([row, RenderOperation::RenderLineBreak]);
+        }
+        operations.push(RenderOperation::SetColors(self.default_colors.clone()));
+        (operations, max_scroll)
+    }
+
+    fn render_line(&self, chars: &[(char, &TextStyle)]) -> RenderOperation {
+        RenderOperation::RenderPreformattedLine(PreformattedLine {
+            text: ansi::styled_text(chars, &self.block_colors),
+            unformatted_length: chars.len(),
+            block_length: chars.len(),
+            alignment: Default::default(),
+        })
+    }
+}
+
+impl AsRenderOperations for OutputBlock {
+    fn as_render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
+        self.render(dimensions).0
+    }
+
+    fn diffable_content(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl AsRenderOperations for RunCodeOperation {
+    fn as_render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
+        let inner = self.inner.borrow();
+        if matches!(inner.state, RenderOnDemandState::NotStarted) {
+            return Vec::new();
+        }
+        // Dependencies show their own output here while they run.
+        let (heading, mut lines) = match inner.pending.front() {
+            Some(dependency) => {
+                let current = inner.total_dependencies - inner.pending.len() + 1;
+                let name = dependency.id().unwrap_or_default();
+                let heading = format!(" [running {name} {current}/{}] ", inner.total_dependencies);
+                (heading, dependency.inner.borrow().output_lines.clone())
+            }
+            None => {
+                let state = match inner.state {
+                    RenderOnDemandState::Rendered if self.dry_run => "dry run",
+                    RenderOnDemandState::Rendered => "done",
+                    _ => "running",
+                };
+                (format!(" [{state}] "), inner.output_lines.clone())
+            }
+        };
+        if self.code.attributes.shows_needed_output {
+            lines = self.needed_output(&inner);
+        }
+        // Output never goes past the bottom of the slide, even if it's allowed to be longer.
+        let fitting_rows = usize::from(dimensions.rows).saturating_sub(OUTPUT_HEADER_ROWS).max(1);
+        let max_rows = match self.code.attributes.output_lines.or(self.style.max_lines) {
+            Some(lines) => usize::from(lines).min(fitting_rows),
+            None => fitting_rows,
+        };
+        let output = OutputBlock {
+            heading,
+            lines,
+            default_colors: self.default_colors.clone(),
+            block_colors: self.style.colors.clone(),
+            max_rows: Some(max_rows),
+            scroll: inner.scroll,
+        };
+        let (operations, max_scroll) = output.render(dimensions);
+        self.max_scroll.set(max_scroll);
+        operations
+    }
+
+    fn diffable_content(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl RenderOnDemand for RunCodeOperation {
+    fn poll_state(&self) -> RenderOnDemandState {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(dependency) = inner.pending.front().cloned() {
+            if !matches!(dependency.poll_state(), RenderOnDemandState::Rendered) {
+                return inner.state.clone();
+            }
+            if !dependency.succeeded() {
+                let name = dependency.id().unwrap_or_default();
+                inner.pending.clear();
+                inner.output_lines = vec![format!("[dependency {name} finished with error]")];
+                inner.failed = true;
+                inner.state = RenderOnDemandState::Rendered;
+                return inner.state.clone();
+            }
+            inner.pending.pop_front();
+            match inner.pending.front() {
+                Some(next) => Self::run_dependency(next),
+                None => self.run(&mut inner),
+            };
+        }
+        if let Some(handle) = inner.handle.as_mut() {
+            let state = handle.state();
+            let ExecutionState { output, status, exit_code } = state;
+            inner.output_lines = output;
+            if status.is_finished() {
+                inner.handle.take();
+                inner.state = RenderOnDemandState::Rendered;
+                self.record_transcript(&mut inner, &status, exit_code);
+            }
+            let suffix = match status {
+                ProcessStatus::Failure => Some("[finished with error]"),
+                ProcessStatus::TimedOut => Some("[timed out]"),
+                ProcessStatus::Cancelled => Some("[cancelled]"),
+                ProcessStatus::Running | ProcessStatus::Success => None,
+            };
+            if let Some(suffix) = suffix {
+                inner.output_lines.push(suffix.to_string());
+                inner.failed = true;
+            }
+        }
+        inner.state.clone()
+    }
+
+    fn failed(&self) -> bool {
+        self.inner.borrow().failed
+    }
+
+    fn has_expected_output(&self) -> bool {
+        self.expected_output.borrow().is_some()
+    }
+
+    fn output_mismatch(&self) -> Option<String> {
+        let expected = self.expected_output.borrow();
+        let expected = Self::comparable_lines(expected.as_ref()?.iter().cloned());
+        let output = Self::comparable_lines(self.output().into_iter());
+        let describe = |line: Option<&String>| line.map(|line| format!("{line:?}")).unwrap_or_else(|| "nothing".into());
+        let mut differences = Vec::new();
+        for index in 0..output.len().max(expected.len()) {
+            let (expected, actual) = (expected.get(index), output.get(index));
+            if expected != actual {
+                let (line, expected, actual) = (index + 1, describe(expected), describe(actual));
+                differences.push(format!("line {line}: expected {expected}, got {actual}"));
+            }
+        }
+        (!differences.is_empty()).then(|| differences.join("; "))
+    }
+
+    fn output(&self) -> Vec<String> {
+        uncolored_lines(&self.inner.borrow().output_lines)
+    }
+
+    fn restart_render(&self) -> bool {
+        {
+            let mut inner = self.inner.borrow_mut();
+            if matches!(inner.state, RenderOnDemandState::Rendering) {
+                return false;
+            }
+            inner.output_lines.clear();
+            inner.scroll = 0;
+            inner.state = RenderOnDemandState::NotStarted;
+        }
+        self.start_render()
+    }
+
+    fn scroll(&self, rows: isize) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        // The offset counts rows from the bottom so scrolling up makes it larger.
+        let scroll = inner.scroll.saturating_add_signed(-rows).min(self.max_scroll.get());
+        let moved = scroll != inner.scroll;
+        inner.scroll = scroll;
+        moved
+    }
+
+    fn cancel_render(&self) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        if !matches!(inner.state, RenderOnDemandState::Rendering) {
+            return false;
+        }
+        match inner.pending.front().cloned() {
+            // The dependency that's running stops and this block never runs.
+            Some(dependency) => {
+                dependency.cancel_render();
+                inner.pending.clear();
+                inner.output_lines = vec!["[cancelled]".to_string()];
+                inner.failed = true;
                 inner.state = RenderOnDemandState::Rendered;
-                true
             }
+            None => {
+                if let Some(handle) = &inner.handle {
+                    handle.cancel();
+                }
+            }
+        };
+        true
+    }
+
+    fn start_render(&self) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        if !matches!(inner.state, RenderOnDemandState::NotStarted) {
+            return false;
         }
+        inner.failed = false;
+        // Dependencies that already ran successfully aren't run again.
+        let pending: VecDeque<_> =
+            self.ordered_dependencies().into_iter().filter(|dependency| !dependency.succeeded()).collect();
+        match pending.front() {
+            Some(dependency) => {
+                Self::run_dependency(dependency);
+                inner.state = RenderOnDemandState::Rendering;
+            }
+            None => self.run(&mut inner),
+        };
+        inner.total_dependencies = pending.len();
+        inner.pending = pending;
+        true
     }
 }
 
@@ -1139,8 +3166,14 @@ struct IndexedListItem {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::markdown::elements::{CodeAttributes, CodeLanguage};
+    use crate::{
+        clock::SystemClock,
+        config::ExecutorConfig,
+        markdown::elements::{CodeAttributes, CodeLanguage, CodeSnippet, InlineImage},
+        presentation::FloatSide,
+    };
     use rstest::rstest;
+    use std::fs;
 
     fn build_presentation(elements: Vec<MarkdownElement>) -> Presentation {
         try_build_presentation(elements).expect("build failed")
@@ -1159,6 +3192,10 @@ mod test {
         MarkdownElement::Comment { comment: "pause".into(), source_position: Default::default() }
     }
 
+    fn build_slide_layout() -> MarkdownElement {
+        MarkdownElement::Comment { comment: "slide_layout: title".into(), source_position: Default::default() }
+    }
+
     fn build_end_slide() -> MarkdownElement {
         MarkdownElement::Comment { comment: "end_slide".into(), source_position: Default::default() }
     }
@@ -1175,20 +3212,23 @@ mod test {
         use RenderOperation::*;
         match operation {
             ClearScreen
+            | RenderSplitBackground(_)
             | SetColors(_)
             | JumpToVerticalCenter
             | JumpToBottomRow { .. }
             | InitColumnLayout { .. }
             | EnterColumn { .. }
-            | ExitLayout { .. }
+            | ExitLayout
             | ApplyMargin(_)
             | PopMargin => false,
             RenderText { .. }
             | RenderLineBreak
             | RenderImage(_)
+            | RenderFloatingImage { .. }
             | RenderPreformattedLine(_)
             | RenderDynamic(_)
-            | RenderOnDemand(_) => true,
+            | RenderOnDemand(_)
+            | RenderCustom(_) => true,
         }
     }
 
@@ -1214,10 +3254,21 @@ mod test {
     }
 
     fn extract_slide_text_lines(slide: Slide) -> Vec<String> {
-        let operations: Vec<_> = slide.into_operations().into_iter().filter(|op| is_visible(op)).collect();
+        let operations: Vec<_> = slide.into_operations().into_iter().filter(is_visible).collect();
         extract_text_lines(&operations)
     }
 
+    // The text in every text operation, one string per operation.
+    fn rendered_texts<'a>(operations: impl IntoIterator<Item = &'a RenderOperation>) -> Vec<String> {
+        operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderText { line, .. } => Some(line.iter_texts().map(|t| &t.text.text).join("")),
+                _ => None,
+            })
+            .collect()
+    }
+
     #[test]
     fn prelude_appears_once() {
         let elements = vec![
@@ -1227,7 +3278,7 @@ mod test {
             MarkdownElement::Heading { text: Text::from("bye"), level: 1 },
         ];
         let presentation = build_presentation(elements);
-        for (index, slide) in presentation.iter_slides().into_iter().enumerate() {
+        for (index, slide) in presentation.iter_slides().enumerate() {
             let clear_screen_count =
                 slide.iter_operations().filter(|op| matches!(op, RenderOperation::ClearScreen)).count();
             let set_colors_count =
@@ -1260,54 +3311,800 @@ mod test {
     }
 
     #[test]
-    fn table() {
-        let elements = vec![MarkdownElement::Table(Table {
-            header: TableRow(vec![Text::from("key"), Text::from("value"), Text::from("other")]),
-            rows: vec![TableRow(vec![Text::from("potato"), Text::from("bar"), Text::from("yes")])],
-        })];
-        let slides = build_presentation(elements).into_slides();
-        let lines = extract_slide_text_lines(slides.into_iter().next().unwrap());
-        let expected_lines = &["key    │ value │ other", "───────┼───────┼──────", "potato │ bar   │ yes  "];
-        assert_eq!(lines, expected_lines);
+    fn slide_titles() {
+        let elements = vec![
+            MarkdownElement::FrontMatter("title: hello".to_string()),
+            MarkdownElement::SetexHeading { text: Text::from("my title") },
+            MarkdownElement::Heading { text: Text::from("heading"), level: 1 },
+            build_end_slide(),
+            MarkdownElement::Heading { text: Text::from("first"), level: 2 },
+            MarkdownElement::Heading { text: Text::from("second"), level: 1 },
+            build_end_slide(),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+        ];
+        let presentation = build_presentation(elements);
+        let titles: Vec<_> = presentation.iter_slides().map(|slide| slide.metadata().title.clone()).collect();
+        let expected = &[Some("hello".into()), Some("my title".into()), Some("first".into()), None];
+        assert_eq!(titles, expected);
     }
 
     #[test]
-    fn layout_without_init() {
-        let elements = vec![build_column(0)];
-        let result = try_build_presentation(elements);
-        assert!(result.is_err());
+    fn progress_bar_sections() {
+        let red = Color::new(255, 0, 0);
+        let context = FooterContext {
+            total_slides: 4,
+            section_colors: vec![None, Some(red), Some(red), None],
+            ..Default::default()
+        };
+        let generator = FooterGenerator {
+            current_slide: 2,
+            context: Rc::new(RefCell::new(context)),
+            style: FooterStyle::ProgressBar { character: Some('#'), colors: Default::default() },
+        };
+        let operations = generator.as_render_operations(&WindowSize::from((8, 24)));
+        let Some(RenderOperation::RenderText { line, .. }) = operations.last() else {
+            panic!("no progress bar: {operations:?}");
+        };
+        let segments: Vec<_> =
+            line.iter_texts().map(|text| (text.text.text.as_str(), text.text.style.colors.foreground)).collect();
+        assert_eq!(segments, &[("##", None), ("####", Some(red))]);
     }
 
     #[test]
-    fn already_in_column() {
+    fn agenda() {
         let elements = vec![
-            MarkdownElement::Comment { comment: "column_layout: [1]".into(), source_position: Default::default() },
-            MarkdownElement::Comment { comment: "column: 0".into(), source_position: Default::default() },
-            MarkdownElement::Comment { comment: "column: 0".into(), source_position: Default::default() },
+            MarkdownElement::FrontMatter("agenda: true".to_string()),
+            MarkdownElement::Heading { text: Text::from("one"), level: 1 },
+            build_end_slide(),
+            MarkdownElement::Heading { text: Text::from("nested"), level: 2 },
+            build_end_slide(),
+            MarkdownElement::Heading { text: Text::from("two"), level: 1 },
         ];
-        let result = try_build_presentation(elements);
-        assert!(result.is_err());
+        let presentation = build_presentation(elements);
+        let titles: Vec<_> = presentation.iter_slides().map(|slide| slide.metadata().title.clone()).collect();
+        let expected = ["Agenda", "one", "nested", "Agenda", "two"].map(|title| Some(title.to_string()));
+        assert_eq!(titles, expected);
+
+        let dimensions = WindowSize::from((80, 24));
+        let sections: Vec<Vec<String>> = [0, 3]
+            .into_iter()
+            .map(|index| {
+                let slide = presentation.iter_slides().nth(index).unwrap();
+                let operations: Vec<_> = slide
+                    .iter_chunk_operations()
+                    .filter_map(|operation| match operation {
+                        RenderOperation::RenderDynamic(operation) => Some(operation.as_render_operations(&dimensions)),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect();
+                rendered_texts(&operations)
+            })
+            .collect();
+        assert_eq!(sections, &[["▶ one", "  two"], ["  one", "▶ two"]]);
     }
 
     #[test]
-    fn column_index_overflow() {
+    fn slide_ids() {
         let elements = vec![
-            MarkdownElement::Comment { comment: "column_layout: [1]".into(), source_position: Default::default() },
-            MarkdownElement::Comment { comment: "column: 1".into(), source_position: Default::default() },
+            MarkdownElement::SetexHeading { text: Text::from("Intro") },
+            build_end_slide(),
+            MarkdownElement::SetexHeading { text: Text::from("Intro") },
+            build_end_slide(),
+            MarkdownElement::Comment { comment: "slide_id: custom".into(), source_position: Default::default() },
+            MarkdownElement::SetexHeading { text: Text::from("Ignored") },
+            build_end_slide(),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
         ];
-        let result = try_build_presentation(elements);
-        assert!(result.is_err());
-    }
-
-    #[rstest]
-    #[case::empty("column_layout: []")]
-    #[case::zero("column_layout: [0]")]
-    #[case::one_is_zero("column_layout: [1, 0]")]
-    fn invalid_layouts(#[case] definition: &str) {
-        let elements =
-            vec![MarkdownElement::Comment { comment: definition.into(), source_position: Default::default() }];
-        let result = try_build_presentation(elements);
-        assert!(result.is_err());
+        let presentation = build_presentation(elements);
+        let ids: Vec<_> =
+            presentation.iter_slides().map(|slide| slide.metadata().id.as_ref().map(ToString::to_string)).collect();
+        let expected = &[Some("intro".into()), Some("intro-2".into()), Some("custom".into()), None];
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn speaker_notes() {
+        let elements = vec![
+            MarkdownElement::Comment { comment: "speaker_note: say hi".into(), source_position: Default::default() },
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+            MarkdownElement::Comment { comment: "speaker_note: wave".into(), source_position: Default::default() },
+            build_end_slide(),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("bye".into())]),
+        ];
+        let presentation = build_presentation(elements);
+        let notes: Vec<_> = presentation.iter_slides().map(|slide| slide.metadata().notes.clone()).collect();
+        let expected: &[Vec<String>] = &[vec!["say hi".into(), "wave".into()], vec![]];
+        assert_eq!(notes, expected);
+    }
+
+    #[test]
+    fn code_blocks() {
+        let code = |contents: &str, line| {
+            let language = CodeLanguage::Unknown(String::new());
+            MarkdownElement::Code(Box::new(Code {
+                contents: contents.into(),
+                language,
+                language_token: String::new(),
+                attributes: Default::default(),
+                source_position: SourcePosition::default().offset_lines(line),
+            }))
+        };
+        let elements = vec![code("first", 3), build_pause(), code("second", 7)];
+        let mut presentation = build_presentation(elements);
+        let slide = presentation.current_slide();
+        assert_eq!(slide.code_block(None), Some((1, "first")));
+        assert_eq!(slide.code_block(Some(2)), Some((2, "second")));
+        assert_eq!(slide.code_block(Some(0)), None);
+        assert_eq!(slide.code_block(Some(3)), None);
+        assert_eq!(slide.code_block_line(None), Some((1, 3)));
+        assert_eq!(slide.code_block_line(Some(2)), Some((2, 7)));
+        assert_eq!(slide.visible_code_blocks().count(), 1);
+
+        presentation.jump_next_slide();
+        assert_eq!(presentation.current_slide().code_block(None), Some((2, "second")));
+        assert_eq!(presentation.current_slide().visible_code_blocks().count(), 2);
+    }
+
+    #[rstest]
+    #[case::wide(100, Margin::Fixed(10))]
+    #[case::narrow(30, Margin::Fixed(0))]
+    fn code_alignment_in_column(#[case] columns: u16, #[case] expected_margin: Margin) {
+        let context = HighlightContext {
+            groups: Vec::new(),
+            current: 0,
+            block_length: 25,
+            alignment: Alignment::Center { minimum_margin: Margin::Fixed(10), minimum_size: 0 },
+        };
+        let dimensions = WindowSize { rows: 10, columns, height: 0, width: 0, has_pixels: false };
+        let expected = Alignment::Center { minimum_margin: expected_margin, minimum_size: 0 };
+        assert_eq!(context.alignment(&dimensions), expected);
+    }
+
+    #[rstest]
+    #[case::valid("10s", true)]
+    #[case::zero("0s", false)]
+    #[case::unknown_unit("10h", false)]
+    #[case::no_amount("s", false)]
+    #[case::overflow("999999999999999999m", false)]
+    fn refresh_every(#[case] interval: &str, #[case] valid: bool) {
+        let elements = vec![
+            MarkdownElement::Comment {
+                comment: format!("refresh_every: {interval}"),
+                source_position: Default::default(),
+            },
+            build_end_slide(),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("static".into())]),
+        ];
+        let result = try_build_presentation(elements);
+        assert_eq!(result.is_ok(), valid);
+        if let Ok(presentation) = result {
+            let intervals: Vec<_> = presentation.iter_slides().map(|slide| slide.metadata().refresh_every).collect();
+            assert_eq!(intervals, &[Some(Duration::from_secs(10)), None]);
+        }
+    }
+
+    fn build_layout_front_matter() -> MarkdownElement {
+        let front_matter = r#"
+theme:
+  override:
+    slide_layouts:
+      title:
+        split_background: { region: left, percent: 40, color: "ff0000" }
+"#;
+        MarkdownElement::FrontMatter(front_matter.into())
+    }
+
+    #[test]
+    fn slide_layout() {
+        let elements = vec![
+            build_layout_front_matter(),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+            build_slide_layout(),
+        ];
+        let slide = build_presentation(elements).into_slides().into_iter().next().unwrap();
+        let operations = slide.into_operations();
+        let clear_index = operations.iter().position(|op| matches!(op, RenderOperation::ClearScreen)).unwrap();
+        let RenderOperation::RenderSplitBackground(split) = &operations[clear_index + 1] else {
+            panic!("background not painted after clearing screen");
+        };
+        assert_eq!(split.percent, 40);
+    }
+
+    #[rstest]
+    #[case::unknown(vec![build_slide_layout()])]
+    #[case::after_pause(vec![build_layout_front_matter(), build_pause(), build_slide_layout()])]
+    fn invalid_slide_layout(#[case] elements: Vec<MarkdownElement>) {
+        let result = try_build_presentation(elements);
+        assert!(matches!(result, Err(BuildError::InvalidSlideLayout(_))), "{:?}", result.err());
+    }
+
+    #[rstest]
+    #[case::default(None, false)]
+    #[case::root(Some("assets"), true)]
+    fn resources_root(#[case] root: Option<&str>, #[case] loads: bool) {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        fs::create_dir(directory.path().join("assets")).expect("failed to create directory");
+        fs::copy("examples/doge.png", directory.path().join("assets/doge.png")).expect("failed to copy image");
+        let front_matter = match root {
+            Some(root) => format!("resources_root: {root}"),
+            None => "title: hi".into(),
+        };
+        let elements = vec![
+            MarkdownElement::FrontMatter(front_matter),
+            MarkdownElement::Image {
+                path: "doge.png".into(),
+                attributes: Default::default(),
+                source_position: Default::default(),
+            },
+        ];
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new(directory.path());
+        let options = PresentationBuilderOptions::default();
+        let result = PresentationBuilder::new(highlighter, &theme, &mut resources, options).build(elements);
+        assert_eq!(result.is_ok(), loads);
+    }
+
+    #[rstest]
+    #[case::aspect_ratio(None, 4)]
+    #[case::columns(Some(3), 3)]
+    fn inline_image(#[case] columns: Option<u16>, #[case] expected_columns: usize) {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        image::RgbaImage::new(20, 10).save(directory.path().join("badge.png")).expect("failed to write image");
+        let image = InlineImage { path: "badge.png".into(), attributes: ImageAttributes { fit: None, columns } };
+        let text = Text { chunks: vec![StyledText::from("hi "), StyledText::image("badge", image)] };
+        let elements = vec![MarkdownElement::Paragraph(vec![ParagraphElement::Text(text)])];
+
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new(directory.path());
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, Default::default());
+        let presentation = builder.build(elements).expect("build failed");
+        let lines = extract_slide_text_lines(presentation.into_slides().into_iter().next().unwrap());
+        let expected = format!("hi {}", NON_BREAKING_SPACE.repeat(expected_columns));
+        assert_eq!(lines, &[expected]);
+    }
+
+    #[rstest]
+    #[case::whole_file(None, None, Some("one\ntwo\nthree\n"))]
+    #[case::range(Some(2), Some(3), Some("two\nthree\n"))]
+    #[case::past_the_end(Some(2), Some(10), Some("two\nthree\n"))]
+    #[case::out_of_bounds(Some(4), None, None)]
+    fn snippet(#[case] first_line: Option<usize>, #[case] last_line: Option<usize>, #[case] expected: Option<&str>) {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        fs::write(directory.path().join("snippet.rs"), "one\ntwo\nthree\n").expect("failed to write snippet");
+        let snippet = CodeSnippet { path: "snippet.rs".into(), first_line, last_line };
+        let attributes = CodeAttributes { snippet: Some(snippet), ..Default::default() };
+        let code = Code {
+            contents: String::new(),
+            language: CodeLanguage::Rust,
+            language_token: "rust".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new(directory.path());
+        let mut builder =
+            PresentationBuilder::new(highlighter, &theme, &mut resources, PresentationBuilderOptions::default());
+        let contents = builder.load_snippet(code).ok().map(|code| code.contents);
+        assert_eq!(contents.as_deref(), expected);
+        let included: Vec<_> = resources.included_files().collect();
+        assert_eq!(included, &[directory.path().join("snippet.rs")]);
+    }
+
+    #[test]
+    fn configured_executor() {
+        let attributes = CodeAttributes { execute: true, ..Default::default() };
+        let code = Code {
+            contents: "puts 1".into(),
+            language: CodeLanguage::Unknown("ruby".into()),
+            language_token: "ruby".into(),
+            attributes,
+            source_position: SourcePosition::default().offset_lines(4),
+        };
+        let build = |options: PresentationBuilderOptions| {
+            let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+            let theme = PresentationTheme::default();
+            let mut resources = Resources::new("/tmp");
+            let elements = vec![MarkdownElement::Code(code.clone().into())];
+            PresentationBuilder::new(highlighter, &theme, &mut resources, options).build(elements)
+        };
+        let result = build(Default::default());
+        let Err(error @ BuildError::ExecutionNotSupported { .. }) = result else { panic!("build succeeded") };
+        assert_eq!(error.to_string(), "code block at line 4: language 'ruby' does not support execution");
+
+        let executor = ExecutorConfig { command: "ruby {file}".into(), extension: None };
+        let execution = ExecutionConfig { executors: [("ruby".into(), executor)].into(), ..Default::default() };
+        let presentation = build(PresentationBuilderOptions { execution, ..Default::default() }).expect("build failed");
+        assert!(presentation.iter_slides().next().unwrap().metadata().code_blocks[0].execution.is_some());
+    }
+
+    #[test]
+    fn unknown_host() {
+        let mut code = executable_code("hostname", None, &[]);
+        code.attributes.host = Some("demo".into());
+        let result = try_build_presentation(vec![MarkdownElement::Code(code.into())]);
+        assert!(matches!(result, Err(BuildError::UnknownHost(host)) if host == "demo"));
+    }
+
+    #[test]
+    fn code_block_commands() {
+        let mut second = executable_code("ls\npwd", None, &["setup"]);
+        second.attributes.container = Some("alpine".into());
+        let elements = vec![
+            MarkdownElement::Code(executable_code("touch a", Some("setup"), &[]).into()),
+            MarkdownElement::Code(second.into()),
+        ];
+        let presentation = build_presentation(elements);
+        let code_blocks = &presentation.iter_slides().next().unwrap().metadata().code_blocks;
+        let commands: Vec<_> = code_blocks.iter().map(|block| block.commands.clone()).collect();
+        let expected = vec![
+            vec!["$ touch a".to_string()],
+            vec!["# after running setup".into(), "# in container alpine".into(), "$ ls".into(), "$ pwd".into()],
+        ];
+        assert_eq!(commands, expected);
+    }
+
+    #[test]
+    fn default_containers() {
+        let mut own_container = executable_code("ls", None, &[]);
+        own_container.attributes.container = Some("alpine".into());
+        let mut session = executable_code("ls", None, &[]);
+        session.attributes.session = Some("demo".into());
+        let elements = vec![
+            MarkdownElement::FrontMatter("containers:\n  sh: debian".into()),
+            MarkdownElement::Code(executable_code("ls", None, &[]).into()),
+            MarkdownElement::Code(own_container.into()),
+            MarkdownElement::Code(session.into()),
+        ];
+        let presentation = build_presentation(elements);
+        let code_blocks = &presentation.iter_slides().next().unwrap().metadata().code_blocks;
+        let containers: Vec<_> = code_blocks.iter().map(|block| block.commands[0].as_str()).collect();
+        assert_eq!(containers, &["# in container debian", "# in container alpine", "# in session demo"]);
+    }
+
+    fn build_with_includes(files: &[(&str, &str)], elements: Vec<MarkdownElement>) -> Result<Presentation, BuildError> {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        for (name, contents) in files {
+            fs::write(directory.path().join(name), contents).expect("failed to write file");
+        }
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new(directory.path());
+        let options = PresentationBuilderOptions::default();
+        PresentationBuilder::new(highlighter, &theme, &mut resources, options).build(elements)
+    }
+
+    fn build_include(path: &str) -> MarkdownElement {
+        MarkdownElement::Comment { comment: format!("include: {path}"), source_position: Default::default() }
+    }
+
+    #[test]
+    fn include() {
+        let intro = "---\ntitle: ignored\n---\n\nIntro\n===\n\n<!-- include: outro.md -->";
+        let files = [("intro.md", intro), ("outro.md", "Bye")];
+        let elements = vec![build_include("intro.md"), build_end_slide(), build_include("outro.md")];
+        let presentation = build_with_includes(&files, elements).expect("build failed");
+        assert_eq!(presentation.iter_slides().count(), 2);
+        assert_eq!(presentation.current_slide().metadata().title.as_deref(), Some("Intro"));
+    }
+
+    #[test]
+    fn included_code_block_file() {
+        let files = [("code.md", "```rust\nfn main() {}\n```")];
+        let elements = vec![build_include("code.md")];
+        let presentation = build_with_includes(&files, elements).expect("build failed");
+        let file = presentation.current_slide().code_block_file(None).expect("no file");
+        assert!(file.ends_with("code.md"), "{file:?}");
+        assert_eq!(presentation.current_slide().code_block_line(None), Some((1, 1)));
+    }
+
+    #[rstest]
+    #[case::cycle(
+        &[("a.md", "<!-- include: b.md -->"), ("b.md", "<!-- include: a.md -->")],
+        "ends up including itself"
+    )]
+    #[case::missing(&[], "including \"a.md\"")]
+    #[case::invalid_command(&[("a.md", "hi\n\n<!-- potato -->")], "a.md\": error parsing command at line")]
+    fn invalid_include(#[case] files: &[(&str, &str)], #[case] expected: &str) {
+        let result = build_with_includes(files, vec![build_include("a.md")]);
+        let message = result.err().expect("build succeeded").to_string();
+        assert!(message.contains(expected), "{message}");
+    }
+
+    #[test]
+    fn intro_slide_template() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let path = directory.path().join("intro.md");
+        fs::write(&path, "# Welcome to {{title}}\n\nby **{{author}}**{{sub_title}}").expect("failed to write template");
+        let front_matter = format!("title: potato\nauthor: bob\nintro_slide_template: {}", path.display());
+        let elements = vec![
+            MarkdownElement::FrontMatter(front_matter),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+        ];
+        let presentation = build_presentation(elements);
+        let slides = presentation.into_slides();
+        assert_eq!(slides.len(), 2);
+        assert_eq!(slides[0].metadata().title.as_deref(), Some("potato"));
+        let texts = rendered_texts(slides[0].iter_operations());
+        assert!(texts.contains(&"by bob".to_string()), "{texts:?}");
+    }
+
+    #[rstest]
+    #[case::below_title("below_title")]
+    #[case::page_bottom("page_bottom")]
+    fn intro_slide_social(#[case] positioning: &str) {
+        let front_matter = format!(
+            r#"
+title: potato
+author: bob
+social: {{ github: bob, email: bob@example.com }}
+theme:
+  override:
+    intro_slide:
+      author: {{ positioning: {positioning} }}
+"#
+        );
+        let elements = vec![MarkdownElement::FrontMatter(front_matter)];
+        let slide = build_presentation(elements).into_slides().into_iter().next().unwrap();
+        let texts = rendered_texts(slide.iter_operations());
+        assert!(texts.contains(&"github: bob  email: bob@example.com".to_string()), "{texts:?}");
+    }
+
+    #[test]
+    fn sounds() {
+        let sound = |path: &str| MarkdownElement::Comment {
+            comment: format!("sound: {path}"),
+            source_position: Default::default(),
+        };
+        let elements = vec![sound("intro.wav"), build_pause(), sound("/tmp/ding.wav")];
+        let presentation = build_presentation(elements);
+        let expected = &[
+            SlideSound { chunk: 0, path: PathBuf::from("/tmp/intro.wav") },
+            SlideSound { chunk: 1, path: PathBuf::from("/tmp/ding.wav") },
+        ];
+        assert_eq!(presentation.current_slide().metadata().sounds, expected);
+    }
+
+    #[test]
+    fn minimum_size() {
+        let elements = vec![MarkdownElement::FrontMatter("minimum_size: { columns: 100, rows: 30 }".into())];
+        let presentation = build_presentation(elements);
+        let expected = MinimumSize { columns: 100, rows: 30 };
+        assert_eq!(presentation.minimum_size(), Some(&expected));
+        assert!(expected.fits(100, 40));
+        assert!(!expected.fits(99, 40));
+        assert!(!expected.fits(120, 29));
+    }
+
+    #[rstest]
+    #[case::front_matter(None, &["0", "talk"])]
+    #[case::cli(Some("workshop"), &["0"])]
+    fn front_matter_options(#[case] cli_profile: Option<&str>, #[case] expected: &[&str]) {
+        let elements = vec![
+            MarkdownElement::FrontMatter("options:\n  profile: talk\n  auto_advance: 5".into()),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("0".into())]),
+            MarkdownElement::Comment { comment: "if: profile == talk".into(), source_position: Default::default() },
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("talk".into())]),
+            MarkdownElement::Comment { comment: "endif".into(), source_position: Default::default() },
+        ];
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let cli = PresentationOptions { profile: cli_profile.map(Into::into), ..Default::default() };
+        let options = PresentationBuilderOptions {
+            presentation_options: ExternalOptions { cli, ..Default::default() },
+            ..Default::default()
+        };
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, options);
+        let presentation = builder.build(elements).expect("build failed");
+        // The presentation keeps the front matter's own options so they can be resolved again later on.
+        assert_eq!(presentation.options().profile.as_deref(), Some("talk"));
+        assert_eq!(presentation.options().auto_advance, std::num::NonZeroU64::new(5));
+        assert_eq!(paragraph_texts(presentation), expected);
+    }
+
+    #[rstest]
+    #[case::unknown_option("options:\n  potato: true")]
+    #[case::invalid_value("options:\n  image_protocol: smoke_signals")]
+    #[case::zero_auto_advance("options:\n  auto_advance: 0")]
+    fn invalid_front_matter_options(#[case] front_matter: &str) {
+        let elements = vec![MarkdownElement::FrontMatter(front_matter.into())];
+        let result = try_build_presentation(elements);
+        assert!(matches!(result, Err(BuildError::InvalidMetadata(_))), "{:?}", result.err());
+    }
+
+    #[test]
+    fn high_contrast() {
+        let elements = vec![
+            MarkdownElement::FrontMatter("theme:\n  name: tokyonight-storm\noptions:\n  high_contrast: true".into()),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+        ];
+        let slide = build_presentation(elements).into_slides().into_iter().next().unwrap();
+        let colors = slide
+            .iter_operations()
+            .find_map(|operation| match operation {
+                RenderOperation::SetColors(colors) => Some(colors.clone()),
+                _ => None,
+            })
+            .expect("no colors");
+        assert_eq!(colors.background, Some(Color::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn code_theme_file_relative_to_theme() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let themes = directory.path().join("themes");
+        fs::create_dir(&themes).expect("failed to create themes directory");
+        fs::write(themes.join("theme.yaml"), "code:\n  theme_name: code.tmTheme\n").expect("failed to write theme");
+        let code_theme = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#123456</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>
+"#;
+        fs::write(themes.join("code.tmTheme"), code_theme).expect("failed to write code theme");
+
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new(directory.path());
+        let elements = vec![MarkdownElement::FrontMatter("theme:\n  path: themes/theme.yaml".into())];
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, Default::default());
+        let result = builder.build(elements);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[rstest]
+    #[case::fits(20, &["Things"])]
+    #[case::split(8, &["Things", "Things (cont.)"])]
+    #[case::split_many(5, &["Things", "Things (cont.)", "Things (cont.)", "Things (cont.)", "Things (cont.)"])]
+    fn max_slide_height(#[case] max_height: u16, #[case] expected_titles: &[&str]) {
+        let item = |index: usize| ListItem {
+            depth: 0,
+            contents: Text::from(index.to_string()),
+            item_type: ListItemType::Unordered,
+            blocks: vec![],
+        };
+        let elements = vec![
+            MarkdownElement::FrontMatter(format!("max_slide_height: {max_height}")),
+            MarkdownElement::SetexHeading { text: Text::from("Things") },
+            MarkdownElement::List((0..10).map(item).collect()),
+        ];
+        let presentation = build_presentation(elements);
+        let titles: Vec<_> = presentation.iter_slides().map(|slide| slide.metadata().title.clone().unwrap()).collect();
+        assert_eq!(titles, expected_titles);
+
+        let mut items = rendered_texts(presentation.iter_slides().flat_map(|slide| slide.iter_operations()));
+        items.retain(|text| text.chars().all(|c| c.is_ascii_digit()));
+        let expected_items: Vec<_> = (0..10).map(|index| index.to_string()).collect();
+        assert_eq!(items, expected_items);
+    }
+
+    #[rstest]
+    #[case::deny(CommandOutputPolicy::Deny, None)]
+    #[case::run(CommandOutputPolicy::Run, Some(2))]
+    #[case::skip(CommandOutputPolicy::Skip, Some(0))]
+    fn command_output(#[case] policy: CommandOutputPolicy, #[case] expected_lines: Option<usize>) {
+        let elements = vec![MarkdownElement::Comment {
+            comment: "command_output: \"echo a; echo b\"".into(),
+            source_position: Default::default(),
+        }];
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions { command_output: policy, ..Default::default() };
+        let result = PresentationBuilder::new(highlighter, &theme, &mut resources, options).build(elements);
+        // Every line of code is a dynamic operation, as is the footer.
+        let lines = result.ok().map(|presentation| {
+            let slide = presentation.into_slides().into_iter().next().unwrap();
+            slide.into_operations().iter().filter(|op| matches!(op, RenderOperation::RenderDynamic(_))).count() - 1
+        });
+        assert_eq!(lines, expected_lines);
+    }
+
+    #[test]
+    fn command_output_in_one_slide() {
+        let command = || MarkdownElement::Comment {
+            comment: "command_output: \"echo hi\"".into(),
+            source_position: Default::default(),
+        };
+        let elements = vec![command(), build_end_slide(), command()];
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions {
+            command_output: CommandOutputPolicy::Run,
+            command_output_slide: Some(1),
+            ..Default::default()
+        };
+        let presentation = PresentationBuilder::new(highlighter, &theme, &mut resources, options)
+            .build(elements)
+            .expect("build failed");
+        let has_output: Vec<_> = presentation
+            .iter_slides()
+            .map(|slide| {
+                slide.iter_operations().any(|operation| match operation {
+                    RenderOperation::RenderDynamic(operation) => {
+                        rendered_output(operation.as_ref(), 30, 80).iter().any(|line| line.contains("hi"))
+                    }
+                    _ => false,
+                })
+            })
+            .collect();
+        assert_eq!(has_output, &[false, true]);
+    }
+
+    #[rstest]
+    #[case::error(UnknownCommandPolicy::Error, None)]
+    #[case::warn(UnknownCommandPolicy::Warn, Some(1))]
+    #[case::ignore(UnknownCommandPolicy::Ignore, Some(0))]
+    fn unknown_commands(#[case] policy: UnknownCommandPolicy, #[case] expected_warnings: Option<usize>) {
+        let elements = vec![
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+            MarkdownElement::Comment { comment: "end_slid".into(), source_position: Default::default() },
+        ];
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions { unknown_commands: policy, ..Default::default() };
+        let result = PresentationBuilder::new(highlighter, &theme, &mut resources, options).build(elements);
+        let warnings = result.ok().map(|presentation| presentation.current_slide_warnings().count());
+        assert_eq!(warnings, expected_warnings);
+    }
+
+    #[rstest]
+    #[case::uppercase("{ text_transform: uppercase }", &["HELLO WORLD"])]
+    #[case::title("{ text_transform: title }", &["Hello World"])]
+    #[case::underline("{ underline: '─', prefix: '#' }", &["# hello world", "─────────────"])]
+    #[case::border("{ border: '▌', underline: '=' }", &["▌ hello world", "▌ ==========="])]
+    fn heading_decorations(#[case] style: &str, #[case] expected: &[&str]) {
+        let front_matter = format!("theme:\n  override:\n    headings:\n      h2: {style}");
+        let elements = vec![
+            MarkdownElement::FrontMatter(front_matter),
+            MarkdownElement::Heading { level: 2, text: Text::from("hello world") },
+        ];
+        let slide = build_presentation(elements).into_slides().into_iter().next().unwrap();
+        assert_eq!(rendered_texts(slide.iter_operations()), expected);
+        assert_eq!(slide.metadata().title.as_deref(), Some("hello world"));
+    }
+
+    #[rstest]
+    #[case::default("{}", "TBBCBTBBC")]
+    #[case::dense("{ paragraph: 0, code: 0 }", "TBCTBC")]
+    #[case::airy_code("{ paragraph: 0, code: 2 }", "TBBBCBBTBC")]
+    #[case::airy_paragraph("{ paragraph: 3 }", "TBBBBCBTBBBBC")]
+    fn element_spacing(#[case] spacing: &str, #[case] expected: &str) {
+        let front_matter = format!("theme:\n  override:\n    spacing: {spacing}");
+        let code = Code {
+            contents: "hi".into(),
+            language: CodeLanguage::Unknown(String::new()),
+            language_token: String::new(),
+            attributes: Default::default(),
+            source_position: Default::default(),
+        };
+        let elements = vec![
+            MarkdownElement::FrontMatter(front_matter),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("a".into())]),
+            MarkdownElement::Code(code.into()),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("b".into())]),
+        ];
+        let slide = build_presentation(elements).into_slides().into_iter().next().unwrap();
+        let mut layout = String::new();
+        // Skip the line break at the top of the slide and collapse every code line into one. Code lines end in a
+        // line break of their own, and the footer shows up as the last one.
+        let operations =
+            slide.iter_operations().skip_while(|operation| !matches!(operation, RenderOperation::RenderText { .. }));
+        for operation in operations {
+            let symbol = match operation {
+                RenderOperation::RenderText { .. } => 'T',
+                RenderOperation::RenderLineBreak => 'B',
+                RenderOperation::RenderDynamic(_) => 'C',
+                _ => continue,
+            };
+            if !(symbol == 'C' && layout.ends_with('C')) {
+                layout.push(symbol);
+            }
+        }
+        assert_eq!(layout, expected);
+    }
+
+    #[rstest]
+    #[case::enabled(true, &["[1] https://a.com", "[2] https://b.com"])]
+    #[case::disabled(false, &[])]
+    fn link_references(#[case] enabled: bool, #[case] expected: &[&str]) {
+        let link = |url: &str| StyledText::new(url, TextStyle::default().link());
+        let text = Text { chunks: vec![link("https://a.com"), StyledText::from(" and "), link("https://b.com")] };
+        let elements = vec![
+            MarkdownElement::FrontMatter(format!("link_references: {enabled}")),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text(text)]),
+            build_pause(),
+            MarkdownElement::List(vec![ListItem {
+                depth: 0,
+                contents: Text { chunks: vec![link("https://b.com"), StyledText::from(" "), link("https://a.com")] },
+                item_type: ListItemType::Unordered,
+                blocks: vec![],
+            }]),
+        ];
+        let slide = build_presentation(elements).into_slides().into_iter().next().unwrap();
+        let mut references = rendered_texts(slide.iter_chunk_operations());
+        references.retain(|text| text.starts_with('['));
+        assert_eq!(references, expected);
+    }
+
+    #[rstest]
+    #[case::disabled("title: potato\nintro_slide: false", 1)]
+    #[case::enabled("title: potato\nintro_slide: true", 2)]
+    fn intro_slide_toggle(#[case] front_matter: &str, #[case] expected_slides: usize) {
+        let elements = vec![
+            MarkdownElement::FrontMatter(front_matter.into()),
+            MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]),
+        ];
+        let presentation = build_presentation(elements);
+        assert_eq!(presentation.iter_slides().count(), expected_slides);
+    }
+
+    #[test]
+    fn table() {
+        let elements = vec![MarkdownElement::Table(Table {
+            header: TableRow(vec![Text::from("key"), Text::from("value"), Text::from("other")]),
+            rows: vec![TableRow(vec![Text::from("potato"), Text::from("bar"), Text::from("yes")])],
+        })];
+        let slides = build_presentation(elements).into_slides();
+        let lines = extract_slide_text_lines(slides.into_iter().next().unwrap());
+        let expected_lines = &["key    │ value │ other", "───────┼───────┼──────", "potato │ bar   │ yes  "];
+        assert_eq!(lines, expected_lines);
+    }
+
+    #[test]
+    fn layout_without_init() {
+        let elements = vec![build_column(0)];
+        let result = try_build_presentation(elements);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn already_in_column() {
+        let elements = vec![
+            MarkdownElement::Comment { comment: "column_layout: [1]".into(), source_position: Default::default() },
+            MarkdownElement::Comment { comment: "column: 0".into(), source_position: Default::default() },
+            MarkdownElement::Comment { comment: "column: 0".into(), source_position: Default::default() },
+        ];
+        let result = try_build_presentation(elements);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn column_index_overflow() {
+        let elements = vec![
+            MarkdownElement::Comment { comment: "column_layout: [1]".into(), source_position: Default::default() },
+            MarkdownElement::Comment { comment: "column: 1".into(), source_position: Default::default() },
+        ];
+        let result = try_build_presentation(elements);
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case::empty("column_layout: []")]
+    #[case::zero("column_layout: [0]")]
+    #[case::one_is_zero("column_layout: [1, 0]")]
+    fn invalid_layouts(#[case] definition: &str) {
+        let elements =
+            vec![MarkdownElement::Comment { comment: definition.into(), source_position: Default::default() }];
+        let result = try_build_presentation(elements);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -1327,11 +4124,125 @@ mod test {
     #[case::column_layout("column_layout: [1, 2]", CommentCommand::InitColumnLayout(vec![1, 2]))]
     #[case::column("column: 1", CommentCommand::Column(1))]
     #[case::reset_layout("reset_layout", CommentCommand::ResetLayout)]
+    #[case::if_equals(r#"if: profile == "workshop""#, CommentCommand::If(Condition::ProfileEquals("workshop".into())))]
+    #[case::if_not_equals("if: profile != talk", CommentCommand::If(Condition::ProfileNotEquals("talk".into())))]
+    #[case::endif("endif", CommentCommand::EndIf)]
+    #[case::template("template: comparison", CommentCommand::Template("comparison".into()))]
+    #[case::slide_id("slide_id: intro", CommentCommand::SlideId("intro".into()))]
+    #[case::slide_layout("slide_layout: title", CommentCommand::SlideLayout("title".into()))]
+    #[case::refresh_seconds(
+        "refresh_every: 30s",
+        CommentCommand::RefreshEvery(RefreshInterval(Duration::from_secs(30)))
+    )]
+    #[case::refresh_minutes(
+        "refresh_every: 2m",
+        CommentCommand::RefreshEvery(RefreshInterval(Duration::from_secs(120)))
+    )]
+    #[case::refresh_millis(
+        "refresh_every: 500ms",
+        CommentCommand::RefreshEvery(RefreshInterval(Duration::from_millis(500)))
+    )]
+    #[case::image_float(
+        "image_float: right 40%",
+        CommentCommand::ImageFloat(ImageFloat { side: FloatSide::Right, width: 40 })
+    )]
+    #[case::image_animation("image_animation: false", CommentCommand::ImageAnimation(false))]
+    #[case::continue_list("continue_list", CommentCommand::ContinueList)]
+    #[case::sound("sound: ding.wav", CommentCommand::Sound("ding.wav".into()))]
+    #[case::include("include: intro.md", CommentCommand::Include("intro.md".into()))]
+    #[case::section_color("section_color: ff0000", CommentCommand::SectionColor(Color::new(255, 0, 0)))]
+    #[case::use_template(
+        r#"use_template: comparison with: {left: "A", right: B}"#,
+        CommentCommand::UseTemplate(TemplateInstance {
+            name: "comparison".into(),
+            values: HashMap::from([("left".into(), "A".into()), ("right".into(), "B".into())]),
+        })
+    )]
+    #[case::use_template_without_values(
+        "use_template: title",
+        CommentCommand::UseTemplate(TemplateInstance { name: "title".into(), values: HashMap::new() })
+    )]
+    #[case::poll(
+        "poll: { question: why?, options: [a, b] }",
+        CommentCommand::Poll(PollDefinition { question: "why?".into(), options: vec!["a".into(), "b".into()] })
+    )]
     fn command_formatting(#[case] input: &str, #[case] expected: CommentCommand) {
         let parsed: CommentCommand = input.parse().expect("deserialization failed");
         assert_eq!(parsed, expected);
     }
 
+    #[rstest]
+    #[case::pause("pause", true)]
+    #[case::column_layout("column_layout: [1, 2]", true)]
+    #[case::column("column: 1", true)]
+    #[case::poll("poll: { question: why?, options: [a, b] }", true)]
+    #[case::condition(r#"if: profile == "workshop""#, true)]
+    #[case::template("template: comparison", true)]
+    #[case::unknown("potato", false)]
+    #[case::negative_column("column: -1", false)]
+    #[case::invalid_layout("column_layout: [1, potato]", false)]
+    #[case::poll_without_question("poll: { options: [a, b] }", false)]
+    #[case::refresh_without_unit("refresh_every: 30", false)]
+    fn command_schema(#[case] input: &str, #[case] valid: bool) {
+        let value: serde_json::Value = serde_yaml::from_str(input).expect("invalid yaml");
+        let result = schema::validate(&CommentCommand::json_schema(), &value);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(input.parse::<CommentCommand>().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::unknown("end_slid", true)]
+    #[case::unknown_in_list("[pause, end_slid]", true)]
+    #[case::unknown_with_value("colum: 1", true)]
+    #[case::invalid_value("column: potato", false)]
+    #[case::invalid_value_in_list("[pause, column: potato]", false)]
+    fn unknown_command_errors(#[case] input: &str, #[case] unknown: bool) {
+        let error = PresentationBuilder::parse_commands(input).expect_err("parsing succeeded");
+        assert_eq!(matches!(error, CommandParseError::UnknownCommand(_)), unknown, "{error}");
+    }
+
+    #[test]
+    fn command_names_match_schema() {
+        let schema = CommentCommand::json_schema();
+        let mut names: Vec<_> = schema["oneOf"]
+            .as_array()
+            .expect("no alternatives")
+            .iter()
+            .flat_map(|alternative| -> Vec<_> {
+                match alternative["properties"].as_object() {
+                    Some(properties) => properties.keys().map(String::as_str).collect(),
+                    None => alternative["const"].as_str().into_iter().collect(),
+                }
+            })
+            .collect();
+        names.sort();
+        let mut expected = CommentCommand::VARIANTS.to_vec();
+        expected.sort();
+        assert_eq!(names, expected);
+    }
+
+    #[rstest]
+    #[case::valid("export: { columns: 80, rows: 24 }", true)]
+    #[case::zero_columns("export: { columns: 0, rows: 24 }", false)]
+    #[case::unknown_field("export: { columns: 80, rows: 24, potato: 1 }", false)]
+    fn export_profile(#[case] front_matter: &str, #[case] valid: bool) {
+        let elements = vec![MarkdownElement::FrontMatter(front_matter.into())];
+        let result = try_build_presentation(elements);
+        assert_eq!(result.is_ok(), valid);
+        if let Ok(presentation) = result {
+            let profile = presentation.export_profile().expect("no profile");
+            assert_eq!((profile.columns, profile.rows), (80, 24));
+        }
+    }
+
+    #[test]
+    fn poll_without_options() {
+        let comment = "poll: { question: why?, options: [] }";
+        let elements = vec![MarkdownElement::Comment { comment: comment.into(), source_position: Default::default() }];
+        let result = try_build_presentation(elements);
+        assert!(matches!(result, Err(BuildError::InvalidPoll(_))));
+    }
+
     #[test]
     fn end_slide_inside_layout() {
         let elements = vec![build_column_layout(1), build_end_slide()];
@@ -1357,13 +4268,13 @@ mod test {
     fn iterate_list() {
         let iter = ListIterator::new(
             vec![
-                ListItem { depth: 0, contents: "0".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 0, contents: "1".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 1, contents: "00".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 1, contents: "01".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 1, contents: "02".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 2, contents: "001".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 0, contents: "2".into(), item_type: ListItemType::Unordered },
+                ListItem { depth: 0, contents: "0".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 0, contents: "1".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 1, contents: "00".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 1, contents: "01".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 1, contents: "02".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 2, contents: "001".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 0, contents: "2".into(), item_type: ListItemType::Unordered, blocks: vec![] },
             ],
             0,
         );
@@ -1376,8 +4287,8 @@ mod test {
     fn iterate_list_starting_from_other() {
         let list = ListIterator::new(
             vec![
-                ListItem { depth: 0, contents: "0".into(), item_type: ListItemType::Unordered },
-                ListItem { depth: 0, contents: "1".into(), item_type: ListItemType::Unordered },
+                ListItem { depth: 0, contents: "0".into(), item_type: ListItemType::Unordered, blocks: vec![] },
+                ListItem { depth: 0, contents: "1".into(), item_type: ListItemType::Unordered, blocks: vec![] },
             ],
             3,
         );
@@ -1386,19 +4297,78 @@ mod test {
         assert_eq!(indexes, expected_indexes);
     }
 
+    #[test]
+    fn list_item_blocks() {
+        let attributes = CodeAttributes {
+            highlight_groups: vec![HighlightGroup::new(vec![Highlight::All])],
+            ..Default::default()
+        };
+        let code = Code {
+            contents: "echo hi\n".into(),
+            language: CodeLanguage::Shell("bash".into()),
+            language_token: "bash".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+        let blocks = vec![
+            ListItemBlock::Paragraph("more".into()),
+            ListItemBlock::Code(code.into()),
+            ListItemBlock::BlockQuote(vec!["quoted".into()]),
+        ];
+        let item = ListItem { depth: 0, contents: "one".into(), item_type: ListItemType::Unordered, blocks };
+        let slide =
+            build_presentation(vec![MarkdownElement::List(vec![item])]).into_slides().into_iter().next().unwrap();
+        let operations: Vec<_> = slide.into_operations().into_iter().filter(is_visible).collect();
+
+        // Every block lines up with the item's text.
+        let alignments: Vec<_> = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderText { line, alignment } => {
+                    let text: String = line.iter_texts().map(|text| text.text.text.clone()).collect();
+                    (text != "   •").then_some((text, alignment.clone()))
+                }
+                RenderOperation::RenderPreformattedLine(line) => Some((line.text.clone(), line.alignment.clone())),
+                RenderOperation::RenderDynamic(operation) => {
+                    let operations = operation.as_render_operations(&WindowSize::from((80, 24)));
+                    operations.into_iter().find_map(|operation| match operation {
+                        RenderOperation::RenderPreformattedLine(line) => Some(("code".into(), line.alignment)),
+                        _ => None,
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        let alignment = Alignment::Left { margin: Margin::Fixed(6) };
+        let expected: Vec<_> =
+            ["one", "more", "code", "quoted"].into_iter().map(|text| (text.to_string(), alignment.clone())).collect();
+        assert_eq!(alignments, expected);
+    }
+
     #[test]
     fn ordered_list_with_pauses() {
         let elements = vec![
             MarkdownElement::List(vec![
-                ListItem { depth: 0, contents: "one".into(), item_type: ListItemType::OrderedPeriod },
-                ListItem { depth: 1, contents: "one_one".into(), item_type: ListItemType::OrderedPeriod },
-                ListItem { depth: 1, contents: "one_two".into(), item_type: ListItemType::OrderedPeriod },
+                ListItem { depth: 0, contents: "one".into(), item_type: ListItemType::OrderedPeriod, blocks: vec![] },
+                ListItem {
+                    depth: 1,
+                    contents: "one_one".into(),
+                    item_type: ListItemType::OrderedPeriod,
+                    blocks: vec![],
+                },
+                ListItem {
+                    depth: 1,
+                    contents: "one_two".into(),
+                    item_type: ListItemType::OrderedPeriod,
+                    blocks: vec![],
+                },
             ]),
             build_pause(),
             MarkdownElement::List(vec![ListItem {
                 depth: 0,
                 contents: "two".into(),
                 item_type: ListItemType::OrderedPeriod,
+                blocks: vec![],
             }]),
         ];
         let slides = build_presentation(elements).into_slides();
@@ -1407,6 +4377,30 @@ mod test {
         assert_eq!(lines, expected_lines);
     }
 
+    #[rstest]
+    #[case::continued(true, &["   3. three", "   4. four"])]
+    #[case::not_continued(false, &["   1. three", "   2. four"])]
+    fn ordered_list_across_slides(#[case] continued: bool, #[case] expected_lines: &[&str]) {
+        let list = |items: &[&str]| {
+            let items = items.iter().map(|item| ListItem {
+                depth: 0,
+                contents: (*item).into(),
+                item_type: ListItemType::OrderedPeriod,
+                blocks: vec![],
+            });
+            MarkdownElement::List(items.collect())
+        };
+        let mut elements = vec![list(&["one", "two"]), build_end_slide()];
+        if continued {
+            let comment = "continue_list".into();
+            elements.push(MarkdownElement::Comment { comment, source_position: Default::default() });
+        }
+        elements.push(list(&["three", "four"]));
+        let slides = build_presentation(elements).into_slides();
+        let lines = extract_slide_text_lines(slides.into_iter().nth(1).unwrap());
+        assert_eq!(lines, expected_lines);
+    }
+
     #[test]
     fn pause_after_list() {
         let elements = vec![
@@ -1414,6 +4408,7 @@ mod test {
                 depth: 0,
                 contents: "one".into(),
                 item_type: ListItemType::OrderedPeriod,
+                blocks: vec![],
             }]),
             build_pause(),
             MarkdownElement::Heading { level: 1, text: "hi".into() },
@@ -1421,6 +4416,7 @@ mod test {
                 depth: 0,
                 contents: "two".into(),
                 item_type: ListItemType::OrderedPeriod,
+                blocks: vec![],
             }]),
         ];
         let slides = build_presentation(elements).into_slides();
@@ -1431,8 +4427,140 @@ mod test {
         assert!(matches!(last_operation, RenderOperation::RenderLineBreak), "last operation is {last_operation:?}");
     }
 
+    #[rstest]
+    #[case::single("pause", 1)]
+    #[case::list("[pause, column: 1]", 2)]
+    #[case::lines("pause\n column: 1 \n\n[end_slide, reset_layout]", 4)]
+    fn command_batches(#[case] comment: &str, #[case] expected: usize) {
+        assert!(!PresentationBuilder::should_ignore_comment(comment));
+        let commands = PresentationBuilder::parse_commands(comment).expect("parse failed");
+        assert_eq!(commands.len(), expected);
+    }
+
+    #[test]
+    fn batched_layout() {
+        let comments = ["column_layout: [1, 1]\ncolumn: 0", "[column: 1, pause]"];
+        let mut elements: Vec<_> = comments
+            .into_iter()
+            .map(|comment| MarkdownElement::Comment { comment: comment.into(), source_position: Default::default() })
+            .collect();
+        elements.insert(1, MarkdownElement::Paragraph(vec![ParagraphElement::Text("hi".into())]));
+        elements.push(MarkdownElement::Paragraph(vec![ParagraphElement::Text("bye".into())]));
+        let slides = build_presentation(elements).into_slides();
+        assert_eq!(slides[0].iter_chunks().count(), 2);
+    }
+
+    fn build_with_profile(comments: &[&str], profile: Option<&str>) -> Result<Presentation, BuildError> {
+        let mut elements = Vec::new();
+        for (index, comment) in comments.iter().enumerate() {
+            elements.extend([
+                MarkdownElement::Comment { comment: comment.to_string(), source_position: Default::default() },
+                MarkdownElement::Paragraph(vec![ParagraphElement::Text(index.to_string().into())]),
+            ]);
+        }
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let cli = PresentationOptions { profile: profile.map(Into::into), ..Default::default() };
+        let options = PresentationBuilderOptions {
+            presentation_options: ExternalOptions { cli, ..Default::default() },
+            ..Default::default()
+        };
+        PresentationBuilder::new(highlighter, &theme, &mut resources, options).build(elements)
+    }
+
+    fn paragraph_texts(presentation: Presentation) -> Vec<String> {
+        let mut texts = Vec::new();
+        for slide in presentation.iter_slides() {
+            for operation in slide.iter_chunk_operations() {
+                if let RenderOperation::RenderText { line, .. } = operation {
+                    texts.extend(line.iter_texts().map(|text| text.text.text.clone()));
+                }
+            }
+        }
+        texts
+    }
+
+    #[rstest]
+    #[case::no_profile(None, &["0", "2", "4"])]
+    #[case::talk(Some("talk"), &["0", "4"])]
+    #[case::workshop(Some("workshop"), &["0", "1", "2", "4"])]
+    fn conditional_content(#[case] profile: Option<&str>, #[case] expected: &[&str]) {
+        let comments = [
+            "pause",
+            r#"if: profile == "workshop""#,
+            "[endif, if: profile != talk]",
+            // Nested conditions are never met if the outer one isn't.
+            "endif\nif: profile == talk\nif: profile != talk",
+            "[endif, endif]",
+        ];
+        let presentation = build_with_profile(&comments, profile).expect("build failed");
+        let texts = paragraph_texts(presentation);
+        let texts: Vec<_> = texts.iter().filter(|text| text.chars().all(|c| c.is_ascii_digit())).collect();
+        assert_eq!(texts, expected);
+    }
+
+    #[test]
+    fn templates() {
+        let comments = [
+            "template: title",
+            "end_template",
+            "template: comparison",
+            "use_template: title with: {text: nested}",
+            "end_template",
+            "use_template: comparison with: {text: first}",
+            r#"use_template: comparison with: {text: "second"}"#,
+        ];
+        let elements = comments
+            .into_iter()
+            .flat_map(|comment| {
+                [
+                    MarkdownElement::Comment { comment: comment.into(), source_position: Default::default() },
+                    MarkdownElement::Paragraph(vec![ParagraphElement::Text("{{text}}".into())]),
+                ]
+            })
+            .collect();
+        let presentation = try_build_presentation(elements).expect("build failed");
+        let texts = paragraph_texts(presentation);
+        let texts: Vec<_> = texts.iter().filter(|text| !text.trim().is_empty()).collect();
+        // Every paragraph has a placeholder, so only the ones in instantiated templates are filled in.
+        let expected = &[
+            "{{text}}", "{{text}}", "first", "nested", "first", "{{text}}", "second", "nested", "second", "{{text}}",
+        ];
+        assert_eq!(texts, expected);
+    }
+
+    #[rstest]
+    #[case::undefined(&["use_template: potato"])]
+    #[case::missing_value(&["template: a", "column: {{column}}", "end_template", "use_template: a"])]
+    #[case::unterminated(&["template: a"])]
+    #[case::unmatched_end(&["end_template"])]
+    #[case::nested_definition(&["template: a", "template: b"])]
+    #[case::combined(&["[template: a, pause]", "end_template"])]
+    fn invalid_templates(#[case] comments: &[&str]) {
+        let result = build_with_profile(comments, None);
+        assert!(matches!(result, Err(BuildError::InvalidTemplate(_))), "{:?}", result.err());
+    }
+
+    #[rstest]
+    #[case::unmatched_endif(&["endif"])]
+    #[case::unterminated(&["if: profile == workshop"])]
+    #[case::invalid_condition(&["if: slide == 3"])]
+    fn invalid_conditions(#[case] comments: &[&str]) {
+        assert!(build_with_profile(comments, None).is_err());
+    }
+
+    #[test]
+    fn invalid_command_in_list() {
+        let comment = "[pause, potato]";
+        let elements = vec![MarkdownElement::Comment { comment: comment.into(), source_position: Default::default() }];
+        let result = try_build_presentation(elements);
+        assert!(matches!(result, Err(BuildError::CommandParse { .. })));
+    }
+
     #[rstest]
     #[case::multiline("hello\nworld")]
+    #[case::partially_commands("pause\nthis is not a command")]
     #[case::many_open_braces("{{{")]
     #[case::many_close_braces("}}}")]
     fn ignore_comments(#[case] comment: &str) {
@@ -1445,8 +4573,10 @@ mod test {
         let input_lines = "hi\n".repeat(total_lines);
         let code = Code {
             contents: input_lines,
-            language: CodeLanguage::Unknown,
+            language: CodeLanguage::Unknown(String::new()),
+            language_token: String::new(),
             attributes: CodeAttributes { line_numbers: true, ..Default::default() },
+            source_position: Default::default(),
         };
         let lines = CodePreparer { theme: &Default::default() }.prepare(&code);
         assert_eq!(lines.len(), total_lines);
@@ -1463,4 +4593,477 @@ mod test {
             assert_eq!(&line.prefix, &format!("{line_number} "));
         }
     }
+
+    #[rstest]
+    #[case::executable(true, &["fn main() {\n", "}\n"])]
+    #[case::not_executable(false, &["# use std::io;\n", "#\n", "fn main() {\n", "}\n"])]
+    fn hidden_lines(#[case] execute: bool, #[case] expected: &[&str]) {
+        let code = Code {
+            contents: "# use std::io;\n#\nfn main() {\n}\n".into(),
+            language: CodeLanguage::Rust,
+            language_token: "rust".into(),
+            attributes: CodeAttributes { execute, line_numbers: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        let lines = CodePreparer { theme: &Default::default() }.prepare(&code);
+        let lines: Vec<_> = lines.iter().map(|line| line.code.as_str()).collect();
+        assert_eq!(lines, expected);
+        if execute {
+            assert_eq!(code.executable_contents(), "use std::io;\n\nfn main() {\n}\n");
+        }
+    }
+
+    #[test]
+    fn hidden_lines_keep_doc_comments() {
+        let code = Code {
+            contents: "#~ #include <cstdio>\n/// Says hi.\nvoid hi() {}\n".into(),
+            language: CodeLanguage::Cpp,
+            language_token: "cpp".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        let lines = CodePreparer { theme: &Default::default() }.prepare(&code);
+        let lines: Vec<_> = lines.iter().map(|line| line.code.as_str()).collect();
+        assert_eq!(lines, &["/// Says hi.\n", "void hi() {}\n"]);
+        assert_eq!(code.executable_contents(), "#include <cstdio>\n/// Says hi.\nvoid hi() {}\n");
+    }
+
+    #[rstest]
+    #[case::no_fold(false, &["1 a\n", "2 b\n", "4 d\n"])]
+    #[case::fold(true, &["1 a\n", "2 b\n", "  …\n", "4 d\n"])]
+    fn shown_lines(#[case] fold: bool, #[case] expected: &[&str]) {
+        let code = Code {
+            contents: "a\nb\nc\nd\n".into(),
+            language: CodeLanguage::Unknown(String::new()),
+            language_token: String::new(),
+            attributes: CodeAttributes {
+                line_numbers: true,
+                shown_lines: Some(HighlightGroup::new(vec![Highlight::Range(1..3), Highlight::Single(4)])),
+                fold,
+                ..Default::default()
+            },
+            source_position: Default::default(),
+        };
+        let lines = CodePreparer { theme: &Default::default() }.prepare(&code);
+        let lines: Vec<_> = lines
+            .iter()
+            .filter(|line| !line.elided)
+            .map(|line| format!("{}{}", line.prefix, line.code))
+            .collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn fold_marker_not_highlighted() {
+        let code = Code {
+            contents: "/* a\nb\n*/\n".into(),
+            language: CodeLanguage::Rust,
+            language_token: "rust".into(),
+            attributes: CodeAttributes {
+                shown_lines: Some(HighlightGroup::new(vec![Highlight::Single(1), Highlight::Single(3)])),
+                fold: true,
+                ..Default::default()
+            },
+            source_position: Default::default(),
+        };
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, Default::default());
+        let (lines, _) = builder.highlight_lines(&code);
+        let marker = lines.iter().find(|line| line.not_highlighted.contains(FOLD_MARKER)).expect("no fold marker");
+        // The marker is in the middle of a comment but it's not styled like one.
+        assert_eq!(marker.highlighted, marker.not_highlighted);
+    }
+
+    fn executable_code(contents: &str, id: Option<&str>, needs: &[&str]) -> Code {
+        let id = id.map(String::from);
+        let needs = needs.iter().map(|id| id.to_string()).collect();
+        let attributes = CodeAttributes { execute: true, id, needs, ..Default::default() };
+        Code {
+            contents: contents.into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes,
+            source_position: Default::default(),
+        }
+    }
+
+    fn code_operation(code: Code) -> RunCodeOperation {
+        RunCodeOperation::new(
+            code,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            false,
+            None,
+            Default::default(),
+        )
+    }
+
+    fn wait_rendered(operation: &dyn RenderOnDemand) {
+        while !matches!(operation.poll_state(), RenderOnDemandState::Rendered) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn run_to_completion(operation: &RunCodeOperation) -> Vec<String> {
+        operation.start_render();
+        wait_rendered(operation);
+        operation.inner.borrow().output_lines.clone()
+    }
+
+    // The preformatted lines an operation renders in a screen of the given size.
+    fn rendered_output(operation: &dyn AsRenderOperations, rows: u16, columns: u16) -> Vec<String> {
+        let dimensions = WindowSize { rows, columns, height: 0, width: 0, has_pixels: false };
+        operation
+            .as_render_operations(&dimensions)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderPreformattedLine(line) => Some(line.text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::success("echo setup", &["first"], "setup")]
+    #[case::failure("exit 1", &["[dependency setup finished with error]"], "[finished with error]")]
+    fn code_dependencies(#[case] setup: &str, #[case] expected: &[&str], #[case] setup_last_line: &str) {
+        let operation = |code| Rc::new(code_operation(code));
+        let setup = operation(executable_code(setup, Some("setup"), &[]));
+        let first = operation(executable_code("echo first", None, &["setup"]));
+        first.set_dependencies(vec![setup.clone()]);
+        assert_eq!(run_to_completion(&first), expected);
+        assert_eq!(setup.inner.borrow().output_lines.last().map(String::as_str), Some(setup_last_line));
+    }
+
+    #[rstest]
+    #[case::success("echo built", &["[build]", "built", "ran"])]
+    #[case::failure(
+        "echo oops; exit 1",
+        &["[build]", "oops", "[finished with error]", "[dependency build finished with error]"]
+    )]
+    fn required_code_output(#[case] build: &str, #[case] expected: &[&str]) {
+        let build = Rc::new(code_operation(executable_code(build, Some("build"), &[])));
+        let mut code = executable_code("echo ran", None, &["build"]);
+        code.attributes.shows_needed_output = true;
+        let operation = code_operation(code);
+        operation.set_dependencies(vec![build]);
+        run_to_completion(&operation);
+        assert_eq!(rendered_output(&operation, 20, 40), expected);
+    }
+
+    #[rstest]
+    #[case::attribute(Some(3), 20)]
+    #[case::screen(None, 6)]
+    #[case::attribute_larger_than_screen(Some(5), 6)]
+    fn capped_code_output(#[case] output_lines: Option<u16>, #[case] rows: u16) {
+        let mut code = executable_code("seq 1 10", None, &[]);
+        code.attributes.output_lines = output_lines;
+        let operation = code_operation(code);
+        run_to_completion(&operation);
+        let visible_lines = || rendered_output(&operation, rows, 20);
+        assert_eq!(visible_lines(), &["8", "9", "10"]);
+        assert!(!operation.scroll(1));
+
+        assert!(operation.scroll(-2));
+        assert_eq!(visible_lines(), &["6", "7", "8"]);
+        assert!(operation.scroll(-100));
+        assert_eq!(visible_lines(), &["1", "2", "3"]);
+        assert!(!operation.scroll(-1));
+        assert!(operation.scroll(100));
+        assert_eq!(visible_lines(), &["8", "9", "10"]);
+    }
+
+    #[rstest]
+    #[case::running("sleep 30", &[], &["[cancelled]"])]
+    #[case::dependency("echo done", &["setup"], &["[cancelled]"])]
+    fn cancel_code_execution(#[case] contents: &str, #[case] needs: &[&str], #[case] expected: &[&str]) {
+        let operation = |code| Rc::new(code_operation(code));
+        let setup = operation(executable_code("sleep 30", Some("setup"), &[]));
+        let code = operation(executable_code(contents, None, needs));
+        if !needs.is_empty() {
+            code.set_dependencies(vec![setup.clone()]);
+        }
+        assert!(code.start_render());
+        assert!(code.cancel_render());
+        assert_eq!(run_to_completion(&code), expected);
+        assert!(!code.cancel_render());
+    }
+
+    #[rstest]
+    #[case::duplicate_id(vec![(Some("a"), vec![]), (Some("a"), vec![])])]
+    #[case::unknown_id(vec![(None, vec!["a"])])]
+    #[case::cycle(vec![(Some("a"), vec!["b"]), (Some("b"), vec!["c"]), (Some("c"), vec!["a"])])]
+    #[case::itself(vec![(Some("a"), vec!["a"])])]
+    fn invalid_code_dependencies(#[case] blocks: Vec<(Option<&str>, Vec<&str>)>) {
+        let elements = blocks
+            .into_iter()
+            .map(|(id, needs)| MarkdownElement::Code(executable_code("true", id, &needs).into()))
+            .collect();
+        let result = try_build_presentation(elements);
+        assert!(matches!(result, Err(BuildError::CodeDependencies(_))));
+    }
+
+    #[rstest]
+    #[case::deny(CommandOutputPolicy::Deny, None)]
+    #[case::run(CommandOutputPolicy::Run, Some(1))]
+    #[case::skip(CommandOutputPolicy::Skip, Some(0))]
+    #[case::confirm(CommandOutputPolicy::Confirm, Some(0))]
+    fn replace_with_output_policy(#[case] policy: CommandOutputPolicy, #[case] expected_lines: Option<usize>) {
+        let mut code = executable_code("echo hello", None, &[]);
+        code.attributes.replace_with_output = true;
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions { command_output: policy, ..Default::default() };
+        let result = PresentationBuilder::new(highlighter, &theme, &mut resources, options)
+            .build(vec![MarkdownElement::Code(code.into())]);
+        let lines = result.ok().map(|presentation| {
+            let slide = presentation.into_slides().into_iter().next().unwrap();
+            slide.into_operations().iter().filter(|op| matches!(op, RenderOperation::RenderPreformattedLine(_))).count()
+        });
+        assert_eq!(lines, expected_lines);
+    }
+
+    #[test]
+    fn replace_with_output() {
+        let mut code = executable_code("echo hello; echo '  world'", None, &[]);
+        code.attributes.replace_with_output = true;
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions { command_output: CommandOutputPolicy::Run, ..Default::default() };
+        let presentation = PresentationBuilder::new(highlighter, &theme, &mut resources, options)
+            .build(vec![MarkdownElement::Code(code.into())])
+            .expect("build failed");
+        let lines: Vec<_> = presentation
+            .iter_slides()
+            .next()
+            .unwrap()
+            .iter_operations()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderPreformattedLine(line) => Some((line.text.as_str(), line.block_length)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines, &[("hello", 7), ("  world", 7)]);
+    }
+
+    #[rstest]
+    #[case::code(true)]
+    #[case::command(false)]
+    fn confirmed_output(#[case] code_block: bool) {
+        let element = match code_block {
+            true => {
+                let mut code = executable_code("echo hello", None, &[]);
+                code.attributes.replace_with_output = true;
+                MarkdownElement::Code(code.into())
+            }
+            false => MarkdownElement::Comment {
+                comment: "command_output: \"echo hello\"".into(),
+                source_position: Default::default(),
+            },
+        };
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions { command_output: CommandOutputPolicy::Confirm, ..Default::default() };
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, options);
+        let presentation = builder.build(vec![element]).expect("build failed");
+        let code_blocks = &presentation.iter_slides().next().unwrap().metadata().code_blocks;
+        assert_eq!(code_blocks.len(), 1);
+        assert!(code_blocks[0].replaced_with_output);
+        assert_eq!(code_blocks[0].commands, &["$ echo hello"]);
+
+        // Nothing runs until it's started, once running it is confirmed.
+        let execution = code_blocks[0].execution.as_ref().expect("not executable");
+        assert!(rendered_output(execution.as_ref(), 30, 80).is_empty());
+        assert!(execution.start_render());
+        wait_rendered(execution.as_ref());
+        assert_eq!(rendered_output(execution.as_ref(), 30, 80), &["hello"]);
+    }
+
+    #[rstest]
+    #[case::canned_output(true, &["$ echo hi", "hello"])]
+    #[case::no_canned_output(false, &["$ echo hi"])]
+    fn dry_run_execution(#[case] canned_output: bool, #[case] expected: &[&str]) {
+        let mut elements = vec![MarkdownElement::Code(executable_code("echo hi", None, &[]).into())];
+        if canned_output {
+            let language = CodeLanguage::Unknown("output".into());
+            let code = Code {
+                contents: "hello\n".into(),
+                language,
+                language_token: "output".into(),
+                attributes: Default::default(),
+                source_position: Default::default(),
+            };
+            elements.push(MarkdownElement::Code(code.into()));
+        }
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let cli = PresentationOptions { dry_run_exec: Some(true), ..Default::default() };
+        let options = PresentationBuilderOptions {
+            presentation_options: ExternalOptions { cli, ..Default::default() },
+            ..Default::default()
+        };
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, options);
+        let presentation = builder.build(elements).expect("build failed");
+
+        // The output block isn't displayed.
+        let slide = presentation.iter_slides().next().unwrap();
+        assert_eq!(slide.metadata().code_blocks.len(), 1);
+        let execution = slide.metadata().code_blocks[0].execution.clone().expect("not executable");
+        assert!(execution.start_render());
+        assert!(matches!(execution.poll_state(), RenderOnDemandState::Rendered));
+        assert_eq!(rendered_output(execution.as_ref(), 10, 20), expected);
+    }
+
+    #[test]
+    fn output_block_outside_dry_run() {
+        let output = Code {
+            contents: "hello\n".into(),
+            language: CodeLanguage::Unknown("output".into()),
+            language_token: "output".into(),
+            attributes: Default::default(),
+            source_position: Default::default(),
+        };
+        let elements = vec![
+            MarkdownElement::Code(executable_code("echo hi", None, &[]).into()),
+            MarkdownElement::Code(output.into()),
+        ];
+        let presentation = build_presentation(elements);
+        let slide = presentation.iter_slides().next().unwrap();
+        assert_eq!(slide.metadata().code_blocks.len(), 2);
+    }
+
+    #[test]
+    fn execution_transcript() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let path = directory.path().join("transcript.log");
+        let code = executable_code("echo hi\nexit 3", None, &[]);
+        let transcript = ExecutionTranscript::new(path.clone(), Arc::new(SystemClock));
+        let operation = RunCodeOperation { transcript: Some(transcript), ..code_operation(code) };
+        run_to_completion(&operation);
+        run_to_completion(&code_operation(executable_code("echo untracked", None, &[])));
+
+        let contents = fs::read_to_string(path).expect("failed to read transcript");
+        let lines: Vec<_> = contents.lines().skip(1).collect();
+        let expected = [
+            "command:",
+            "  $ echo hi",
+            "  $ exit 3",
+            "status: failure (exit code 3)",
+            "duration",
+            "output:",
+            "  hi",
+            "",
+        ];
+        assert_eq!(lines.len(), expected.len(), "{contents}");
+        for (line, expected) in lines.iter().zip(expected) {
+            assert!(line.starts_with(expected), "{line} doesn't start with {expected}");
+        }
+    }
+
+    #[test]
+    fn replaced_code_transcript() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let path = directory.path().join("transcript.log");
+        let mut code = executable_code("echo hello", None, &[]);
+        code.attributes.replace_with_output = true;
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new("/tmp");
+        let options = PresentationBuilderOptions {
+            command_output: CommandOutputPolicy::Run,
+            execution_transcript: Some(ExecutionTranscript::new(path.clone(), Arc::new(SystemClock))),
+            ..Default::default()
+        };
+        PresentationBuilder::new(highlighter, &theme, &mut resources, options)
+            .build(vec![MarkdownElement::Code(code.into())])
+            .expect("build failed");
+
+        let contents = fs::read_to_string(path).expect("failed to read transcript");
+        let lines: Vec<_> = contents.lines().skip(1).collect();
+        assert_eq!(&lines[..2], &["command:", "  $ echo hello"], "{contents}");
+        assert!(contents.contains("output:\n  hello\n"), "{contents}");
+    }
+
+    #[rstest]
+    #[case::block(false)]
+    #[case::file(true)]
+    fn code_stdin(#[case] from_file: bool) {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        fs::write(directory.path().join("input.txt"), "hello\n").unwrap();
+        let mut code = executable_code("read line; echo \"got $line\"", None, &[]);
+        let mut elements = vec![];
+        if from_file {
+            code.attributes.stdin = Some("input.txt".into());
+            elements.push(MarkdownElement::Code(code.into()));
+        } else {
+            let language = CodeLanguage::Unknown("stdin".into());
+            let input = Code {
+                contents: "hello\n".into(),
+                language,
+                language_token: "stdin".into(),
+                attributes: Default::default(),
+                source_position: Default::default(),
+            };
+            elements.extend([MarkdownElement::Code(code.into()), MarkdownElement::Code(input.into())]);
+        }
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let theme = PresentationTheme::default();
+        let mut resources = Resources::new(directory.path());
+        let builder = PresentationBuilder::new(highlighter, &theme, &mut resources, Default::default());
+        let presentation = builder.build(elements).expect("build failed");
+
+        // The stdin block isn't displayed.
+        let slide = presentation.iter_slides().next().unwrap();
+        assert_eq!(slide.metadata().code_blocks.len(), 1);
+        let execution = slide.metadata().code_blocks[0].execution.clone().expect("not executable");
+        assert!(execution.start_render());
+        wait_rendered(execution.as_ref());
+        assert_eq!(rendered_output(execution.as_ref(), 10, 20), &["got hello"]);
+    }
+
+    #[test]
+    fn missing_stdin_file() {
+        let mut code = executable_code("cat", None, &[]);
+        code.attributes.stdin = Some("potato.txt".into());
+        let result = try_build_presentation(vec![MarkdownElement::Code(code.into())]);
+        assert!(matches!(result, Err(BuildError::InvalidStdin(..))));
+    }
+
+    #[test]
+    fn dependencies_in_later_slides() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let path = directory.path().join("greeting").display().to_string();
+        let elements = vec![
+            MarkdownElement::Code(executable_code(&format!("cat {path}"), None, &["setup"]).into()),
+            build_end_slide(),
+            MarkdownElement::Code(executable_code(&format!("echo hi > {path}"), Some("setup"), &[]).into()),
+        ];
+        let presentation = build_presentation(elements);
+        let slide = presentation.iter_slides().next().unwrap();
+        let execution = slide.metadata().code_blocks[0].execution.clone().expect("not executable");
+        assert!(execution.start_render());
+        wait_rendered(execution.as_ref());
+        assert_eq!(rendered_output(execution.as_ref(), 10, 20), &["hi"]);
+    }
+
+    #[test]
+    fn long_dependency_chain() {
+        // Every block needs the two before it, which takes forever to check unless blocks are only checked once.
+        let elements = (0..64)
+            .map(|index| {
+                let id = index.to_string();
+                let needs: Vec<_> = (index.max(2) - 2..index).map(|other| other.to_string()).collect();
+                let needs: Vec<_> = needs.iter().map(String::as_str).collect();
+                MarkdownElement::Code(executable_code("true", Some(&id), &needs).into())
+            })
+            .collect();
+        build_presentation(elements);
+    }
 }
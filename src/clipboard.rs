@@ -0,0 +1,83 @@
+//! Copies text into the system clipboard.
+//!
+//! This uses the tools that come with every OS where there's one, but those don't work over SSH
+//! or in terminals that aren't on a desktop. In those cases the terminal is asked to copy the text
+//! using an OSC 52 sequence instead.
+
+use crate::encoding::encode_base64;
+use std::{
+    env,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// The system clipboard.
+pub(crate) struct Clipboard;
+
+impl Clipboard {
+    /// Copy text using the first native tool that works, returning whether any of them did.
+    pub(crate) fn copy_native(text: &str) -> bool {
+        let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+        let x11 = env::var_os("DISPLAY").is_some();
+        Self::native_commands(env::consts::OS, wayland, x11).iter().any(|command| Self::run(command, text))
+    }
+
+    /// Get the OSC 52 sequence that asks the terminal to copy some text.
+    pub(crate) fn osc52_sequence(text: &str) -> String {
+        format!("\x1b]52;c;{}\x07", encode_base64(text.as_bytes()))
+    }
+
+    fn native_commands(os: &str, wayland: bool, x11: bool) -> Vec<&'static [&'static str]> {
+        match os {
+            "macos" => vec![&["pbcopy"]],
+            "windows" => vec![&["clip"]],
+            _ => {
+                let mut commands: Vec<&[&str]> = Vec::new();
+                if wayland {
+                    commands.push(&["wl-copy"]);
+                }
+                if x11 {
+                    commands.extend([&["xclip", "-selection", "clipboard"][..], &["xsel", "--clipboard", "--input"]]);
+                }
+                commands
+            }
+        }
+    }
+
+    fn run(command: &[&str], text: &str) -> bool {
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            return false;
+        };
+        // Dropping stdin closes it so the tool knows it's got all of the text.
+        let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        let succeeded = child.wait().is_ok_and(|status| status.success());
+        written && succeeded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn osc52() {
+        assert_eq!(Clipboard::osc52_sequence("hi"), "\x1b]52;c;aGk=\x07");
+    }
+
+    #[rstest]
+    #[case::macos("macos", false, false, &["pbcopy"])]
+    #[case::wayland_and_x11("linux", true, true, &["wl-copy", "xclip", "xsel"])]
+    #[case::x11("freebsd", false, true, &["xclip", "xsel"])]
+    #[case::no_desktop("linux", false, false, &[])]
+    fn native_commands(#[case] os: &str, #[case] wayland: bool, #[case] x11: bool, #[case] expected: &[&str]) {
+        let programs: Vec<_> = Clipboard::native_commands(os, wayland, x11).iter().map(|command| command[0]).collect();
+        assert_eq!(programs, expected);
+    }
+}
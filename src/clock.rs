@@ -0,0 +1,91 @@
+//! The current time, which is taken from a [Clock] so whatever uses it can be given a fixed one.
+
+use std::{
+    fmt::{self, Debug, Display},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A source of the current time.
+pub(crate) trait Clock: Debug + Send + Sync {
+    /// Get the current time.
+    fn now(&self) -> UtcTime;
+}
+
+/// The system's clock.
+#[derive(Debug)]
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> UtcTime {
+        UtcTime(SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default())
+    }
+}
+
+/// A point in time in UTC, in seconds since the epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct UtcTime(pub(crate) u64);
+
+impl UtcTime {
+    /// Format the date, like `2024-05-12`.
+    pub(crate) fn date(&self) -> String {
+        let (year, month, day) = Self::civil_date((self.0 / 86400) as i64);
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+
+    /// Format the time of day, like `14:03`.
+    pub(crate) fn time(&self) -> String {
+        let seconds = self.0 % 86400;
+        format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+    }
+
+    // Converts a number of days since the epoch into a date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn civil_date(days: i64) -> (i64, u32, u32) {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        (year, month, day)
+    }
+}
+
+/// Formats this time as an RFC 3339 timestamp, like `2024-05-12T14:03:27Z`.
+impl Display for UtcTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}T{}:{:02}Z", self.date(), self.time(), self.0 % 60)
+    }
+}
+
+/// A clock that's stuck at a point in time.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct FixedClock(pub(crate) UtcTime);
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> UtcTime {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::epoch(0, "1970-01-01", "00:00", "1970-01-01T00:00:00Z")]
+    #[case::leap_day(951825600, "2000-02-29", "12:00", "2000-02-29T12:00:00Z")]
+    #[case::end_of_year(1704067199, "2023-12-31", "23:59", "2023-12-31T23:59:59Z")]
+    fn formatting(#[case] seconds: u64, #[case] date: &str, #[case] time: &str, #[case] timestamp: &str) {
+        let utc = UtcTime(seconds);
+        assert_eq!(utc.date(), date);
+        assert_eq!(utc.time(), time);
+        assert_eq!(utc.to_string(), timestamp);
+    }
+}
@@ -0,0 +1,636 @@
+//! Compilation of presentations into files that can be played without building them again.
+//!
+//! A compiled presentation contains every step of every slide already turned into render
+//! operations, including highlighted code and embedded images. Anything that depends on the screen
+//! size, like footers, is laid out using the export profile's dimensions if there's one, or the
+//! terminal's size at compilation time otherwise.
+//!
+//! Nothing is evaluated when playing a compiled presentation: executable code blocks are frozen in
+//! the state they're in before running them, and the layout doesn't adapt to the terminal's size.
+//!
+//! The file is encoded using bincode: the format version, followed by the slides.
+
+use crate::{
+    builder::{BuildError, CommandOutputPolicy, PresentationBuilder, PresentationBuilderOptions},
+    config::{ExecutionConfig, PlaceholderConfig},
+    markdown::{
+        elements::{ImageAttributes, StyledText},
+        parse::ParseError,
+        text::{WeightedLine, WeightedText},
+    },
+    options::ExternalOptions,
+    presentation::{
+        AsRenderOperations, ChunkMutator, ImageFloat, MarginProperties, PreformattedLine, Presentation,
+        RenderOperation, Slide, SlideChunk, SlideMetadata,
+    },
+    render::{
+        canvas::Canvas,
+        layout::WindowRect,
+        media::{Image, InvalidImage},
+        properties::WindowSize,
+    },
+    style::Colors,
+    theme::{Alignment, Margin, SplitBackground},
+    CodeHighlighter, MarkdownParser, PresentationTheme, Resources,
+};
+use bincode::Options;
+use image::ImageError;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+/// The version of the compiled presentation format.
+///
+/// This needs to be bumped every time the format changes in an incompatible way.
+const FORMAT_VERSION: u32 = 2;
+
+/// The dimensions used if there's no export profile and the terminal size can't be found.
+const DEFAULT_DIMENSIONS: (u16, u16) = (100, 30);
+
+/// Compiles presentations.
+pub struct PresentationCompiler<'a> {
+    parser: MarkdownParser<'a>,
+    default_theme: &'a PresentationTheme,
+    default_highlighter: CodeHighlighter,
+    resources: Resources,
+    options: ExternalOptions,
+    command_output: CommandOutputPolicy,
+    placeholders: PlaceholderConfig,
+    execution: ExecutionConfig,
+}
+
+impl<'a> PresentationCompiler<'a> {
+    /// Construct a new compiler.
+    pub fn new(
+        parser: MarkdownParser<'a>,
+        default_theme: &'a PresentationTheme,
+        default_highlighter: CodeHighlighter,
+        resources: Resources,
+    ) -> Self {
+        Self {
+            parser,
+            default_theme,
+            default_highlighter,
+            resources,
+            options: Default::default(),
+            command_output: Default::default(),
+            placeholders: Default::default(),
+            execution: Default::default(),
+        }
+    }
+
+    /// Set the presentation options given via command line parameters and the config file.
+    pub fn with_options(mut self, options: ExternalOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set whether to run the commands in `command_output` comments.
+    ///
+    /// Their output is stored in the compiled presentation so they're never run when playing it.
+    pub fn with_command_output(mut self, allowed: bool) -> Self {
+        self.command_output = CommandOutputPolicy::from_allowed(allowed);
+        self
+    }
+
+    /// Set the placeholders that can be used on top of the built in ones.
+    pub fn with_placeholders(mut self, placeholders: PlaceholderConfig) -> Self {
+        self.placeholders = placeholders;
+        self
+    }
+
+    /// Set the configuration used to run code blocks while compiling, like the ones that are replaced
+    /// with their output.
+    pub fn with_execution(mut self, config: ExecutionConfig) -> Self {
+        self.execution = config;
+        self
+    }
+
+    /// Compile the presentation in the given path and write it into the output path.
+    pub fn compile(&mut self, presentation_path: &Path, output_path: &Path) -> Result<(), CompileError> {
+        let content = self.resources.presentation(presentation_path).map_err(CompileError::ReadPresentation)?;
+        let compiled = self.compile_contents(&content)?;
+        let file = File::create(output_path).map_err(CompileError::WriteOutput)?;
+        let mut writer = BufWriter::new(file);
+        compiled.write(&mut writer).map_err(CompileError::WriteOutput)?;
+        writer.flush().map_err(CompileError::WriteOutput)
+    }
+
+    fn compile_contents(&mut self, content: &str) -> Result<CompiledPresentation, CompileError> {
+        let elements = self.parser.parse(content)?;
+        let options = PresentationBuilderOptions {
+            use_export_profile: true,
+            presentation_options: self.options.clone(),
+            command_output: self.command_output,
+            placeholders: self.placeholders.clone(),
+            execution: self.execution.clone(),
+            ..Default::default()
+        };
+        let mut presentation = PresentationBuilder::new(
+            self.default_highlighter.clone(),
+            self.default_theme,
+            &mut self.resources,
+            options,
+        )
+        .build(elements)?;
+        let dimensions = match presentation.export_profile() {
+            Some(profile) => WindowSize::from((profile.columns, profile.rows)),
+            None => WindowSize::current().unwrap_or_else(|_| WindowSize::from(DEFAULT_DIMENSIONS)),
+        };
+        CompiledPresentation::new(&mut presentation, dimensions)
+    }
+}
+
+/// A presentation that's already been built.
+#[derive(Debug)]
+pub(crate) struct CompiledPresentation {
+    slides: Vec<CompiledSlide>,
+}
+
+impl CompiledPresentation {
+    fn new(presentation: &mut Presentation, dimensions: WindowSize) -> Result<Self, CompileError> {
+        let mut slides = Vec::new();
+        let total_slides = presentation.iter_slides().count();
+        for index in 0..total_slides {
+            presentation.jump_slide(index);
+            let mut steps = Vec::new();
+            loop {
+                let slide = presentation.current_slide();
+                let mut freezer = OperationFreezer::new(dimensions.clone());
+                for operation in slide.iter_operations() {
+                    freezer.freeze(operation)?;
+                }
+                steps.push(freezer.operations);
+                if presentation.is_current_slide_last_step() {
+                    break;
+                }
+                presentation.jump_next_slide();
+            }
+            let SlideMetadata { title, notes, .. } = presentation.current_slide().metadata().clone();
+            slides.push(CompiledSlide { title, notes, steps });
+        }
+        Ok(Self { slides })
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let encode = |error: bincode::Error| io::Error::other(error);
+        bincode::serialize_into(&mut *writer, &FORMAT_VERSION).map_err(encode)?;
+        bincode::serialize_into(writer, &self.slides).map_err(encode)
+    }
+
+    /// Load a compiled presentation from the given path.
+    pub(crate) fn load(path: &Path) -> Result<Self, LoadCompiledError> {
+        let file = File::open(path).map_err(LoadCompiledError::Io)?;
+        // Nothing in the file can take more bytes than the file has, so corrupted lengths are caught
+        // before they're allocated.
+        let size = file.metadata().map_err(LoadCompiledError::Io)?.len();
+        let options = bincode::DefaultOptions::new().with_fixint_encoding().allow_trailing_bytes().with_limit(size);
+        let mut reader = BufReader::new(file);
+        // The version is read on its own first so older files fail with a useful error rather than
+        // whatever decoding them as the current format runs into.
+        let version: u32 = options.deserialize_from(&mut reader)?;
+        if version != FORMAT_VERSION {
+            return Err(LoadCompiledError::UnsupportedVersion(version));
+        }
+        let slides = options.deserialize_from(reader)?;
+        Ok(Self { slides })
+    }
+
+    /// Turn this into a presentation that can be displayed.
+    pub(crate) fn into_presentation(self) -> Result<Presentation, LoadCompiledError> {
+        let mut slides = Vec::new();
+        for CompiledSlide { title, notes, steps } in self.slides {
+            let steps = steps
+                .into_iter()
+                .map(|step| step.into_iter().map(CompiledOperation::into_render_operation).collect())
+                .collect::<Result<Vec<_>, _>>()?;
+            let current = Rc::new(Cell::new(0));
+            let mutator = StepMutator { current: current.clone(), total: steps.len() };
+            let operation = RenderOperation::RenderDynamic(Rc::new(CompiledSteps { steps, current }));
+            let chunk = SlideChunk::new(vec![operation], vec![Box::new(mutator)]);
+            let metadata = SlideMetadata { title, notes, ..Default::default() };
+            slides.push(Slide::new(vec![chunk], vec![]).with_metadata(metadata));
+        }
+        Ok(Presentation::new(slides))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CompiledSlide {
+    title: Option<String>,
+    notes: Vec<String>,
+    steps: Vec<Vec<CompiledOperation>>,
+}
+
+/// The subset of render operations that don't depend on anything that's only known when presenting.
+#[derive(Debug, Deserialize, Serialize)]
+enum CompiledOperation {
+    ClearScreen,
+    RenderSplitBackground(SplitBackground),
+    SetColors(Colors),
+    JumpToVerticalCenter,
+    JumpToBottomRow { index: u16 },
+    RenderText { text: Vec<StyledText>, alignment: CompiledAlignment },
+    RenderLineBreak,
+    RenderImage { png: Vec<u8>, attributes: ImageAttributes },
+    RenderFloatingImage { png: Vec<u8>, float: ImageFloat, attributes: ImageAttributes },
+    RenderPreformattedLine {
+        text: String,
+        unformatted_length: usize,
+        block_length: usize,
+        alignment: CompiledAlignment,
+    },
+    InitColumnLayout { columns: Vec<u8> },
+    EnterColumn { column: usize },
+    ExitLayout,
+    ApplyMargin(MarginProperties),
+    PopMargin,
+}
+
+impl CompiledOperation {
+    fn into_render_operation(self) -> Result<RenderOperation, LoadCompiledError> {
+        use CompiledOperation::*;
+        let operation = match self {
+            ClearScreen => RenderOperation::ClearScreen,
+            RenderSplitBackground(split) => RenderOperation::RenderSplitBackground(split),
+            SetColors(colors) => RenderOperation::SetColors(colors),
+            JumpToVerticalCenter => RenderOperation::JumpToVerticalCenter,
+            JumpToBottomRow { index } => RenderOperation::JumpToBottomRow { index },
+            RenderText { text, alignment } => {
+                let line = WeightedLine::from(text.into_iter().map(WeightedText::from).collect::<Vec<_>>());
+                RenderOperation::RenderText { line, alignment: alignment.into() }
+            }
+            RenderLineBreak => RenderOperation::RenderLineBreak,
+            RenderImage { png, attributes } => {
+                RenderOperation::RenderImage(Image::new(&png)?.with_attributes(attributes))
+            }
+            RenderFloatingImage { png, float, attributes } => {
+                let image = Image::new(&png)?.with_attributes(attributes);
+                RenderOperation::RenderFloatingImage { image, float }
+            }
+            RenderPreformattedLine { text, unformatted_length, block_length, alignment } => {
+                let line = PreformattedLine { text, unformatted_length, block_length, alignment: alignment.into() };
+                RenderOperation::RenderPreformattedLine(line)
+            }
+            InitColumnLayout { columns } => RenderOperation::InitColumnLayout { columns },
+            EnterColumn { column } => RenderOperation::EnterColumn { column },
+            ExitLayout => RenderOperation::ExitLayout,
+            ApplyMargin(properties) => RenderOperation::ApplyMargin(properties),
+            PopMargin => RenderOperation::PopMargin,
+        };
+        Ok(operation)
+    }
+}
+
+/// An [Alignment].
+///
+/// Themes tag alignments using a field within them, which bincode can't decode, so they're encoded
+/// using this instead.
+#[derive(Debug, Deserialize, Serialize)]
+enum CompiledAlignment {
+    Left { margin: Margin },
+    Right { margin: Margin },
+    Center { minimum_margin: Margin, minimum_size: u16 },
+}
+
+impl From<Alignment> for CompiledAlignment {
+    fn from(alignment: Alignment) -> Self {
+        match alignment {
+            Alignment::Left { margin } => Self::Left { margin },
+            Alignment::Right { margin } => Self::Right { margin },
+            Alignment::Center { minimum_margin, minimum_size } => Self::Center { minimum_margin, minimum_size },
+        }
+    }
+}
+
+impl From<CompiledAlignment> for Alignment {
+    fn from(alignment: CompiledAlignment) -> Self {
+        match alignment {
+            CompiledAlignment::Left { margin } => Self::Left { margin },
+            CompiledAlignment::Right { margin } => Self::Right { margin },
+            CompiledAlignment::Center { minimum_margin, minimum_size } => Self::Center { minimum_margin, minimum_size },
+        }
+    }
+}
+
+/// Turns render operations into compiled ones.
+///
+/// Operations generated at render time are generated here instead, so this keeps track of the
+/// area they'd be rendered into the same way the render engine does.
+struct OperationFreezer {
+    rects: Vec<WindowRect>,
+    columns: Option<Vec<u16>>,
+    in_column: bool,
+    operations: Vec<CompiledOperation>,
+}
+
+impl OperationFreezer {
+    fn new(dimensions: WindowSize) -> Self {
+        Self { rects: vec![WindowRect::new(dimensions)], columns: None, in_column: false, operations: Vec::new() }
+    }
+
+    fn current_rect(&self) -> &WindowRect {
+        self.rects.last().expect("no rects")
+    }
+
+    fn freeze(&mut self, operation: &RenderOperation) -> Result<(), CompileError> {
+        let compiled = match operation {
+            RenderOperation::ClearScreen => CompiledOperation::ClearScreen,
+            RenderOperation::RenderSplitBackground(split) => CompiledOperation::RenderSplitBackground(split.clone()),
+            RenderOperation::SetColors(colors) => CompiledOperation::SetColors(colors.clone()),
+            RenderOperation::JumpToVerticalCenter => CompiledOperation::JumpToVerticalCenter,
+            RenderOperation::JumpToBottomRow { index } => CompiledOperation::JumpToBottomRow { index: *index },
+            RenderOperation::RenderText { line, alignment } => Self::freeze_text(line, alignment.clone()),
+            RenderOperation::RenderLineBreak => CompiledOperation::RenderLineBreak,
+            RenderOperation::RenderImage(image) => {
+                CompiledOperation::RenderImage { png: image.to_png()?, attributes: image.attributes() }
+            }
+            // How far down the content next to a floating image goes depends on the image's height
+            // on screen so anything generated next to it is generated using the whole width.
+            RenderOperation::RenderFloatingImage { image, float } => {
+                let (png, attributes) = (image.to_png()?, image.attributes());
+                CompiledOperation::RenderFloatingImage { png, float: float.clone(), attributes }
+            }
+            RenderOperation::RenderPreformattedLine(line) => {
+                let PreformattedLine { text, unformatted_length, block_length, alignment } = line.clone();
+                let alignment = alignment.into();
+                CompiledOperation::RenderPreformattedLine { text, unformatted_length, block_length, alignment }
+            }
+            RenderOperation::RenderDynamic(generator) => {
+                return self.freeze_all(generator.as_render_operations(&self.current_rect().dimensions));
+            }
+            RenderOperation::RenderOnDemand(generator) => {
+                return self.freeze_all(generator.as_render_operations(&self.current_rect().dimensions));
+            }
+            RenderOperation::RenderCustom(operation) => {
+                let columns = self.current_rect().dimensions.columns;
+                let mut canvas = Canvas::new(columns, operation.measure(columns));
+                operation.paint(&mut canvas);
+                let alignment = Alignment::Left { margin: Margin::Fixed(0) };
+                for line in canvas.into_lines() {
+                    self.operations.push(Self::freeze_text(&line, alignment.clone()));
+                    self.operations.push(CompiledOperation::RenderLineBreak);
+                }
+                return Ok(());
+            }
+            RenderOperation::InitColumnLayout { columns } => {
+                self.exit_layout();
+                self.columns = Some(columns.iter().copied().map(u16::from).collect());
+                CompiledOperation::InitColumnLayout { columns: columns.clone() }
+            }
+            RenderOperation::EnterColumn { column } => {
+                if self.in_column {
+                    self.rects.pop();
+                    self.in_column = false;
+                }
+                let rect = self.columns.as_ref().and_then(|columns| self.current_rect().column(columns, *column));
+                if let Some(rect) = rect {
+                    self.rects.push(rect);
+                    self.in_column = true;
+                }
+                CompiledOperation::EnterColumn { column: *column }
+            }
+            RenderOperation::ExitLayout => {
+                self.exit_layout();
+                CompiledOperation::ExitLayout
+            }
+            RenderOperation::ApplyMargin(properties) => {
+                let current = self.current_rect();
+                let margin = properties.horizontal_margin.as_characters(current.dimensions.columns);
+                let rect = current.apply_margin(margin).shrink_rows(properties.bottom_slide_margin);
+                self.rects.push(rect);
+                CompiledOperation::ApplyMargin(properties.clone())
+            }
+            RenderOperation::PopMargin => {
+                if self.rects.len() > 1 {
+                    self.rects.pop();
+                }
+                CompiledOperation::PopMargin
+            }
+        };
+        self.operations.push(compiled);
+        Ok(())
+    }
+
+    fn freeze_all(&mut self, operations: Vec<RenderOperation>) -> Result<(), CompileError> {
+        operations.iter().try_for_each(|operation| self.freeze(operation))
+    }
+
+    fn freeze_text(line: &WeightedLine, alignment: Alignment) -> CompiledOperation {
+        let text = line.iter_texts().map(|text| text.text.clone()).collect();
+        CompiledOperation::RenderText { text, alignment: alignment.into() }
+    }
+
+    fn exit_layout(&mut self) {
+        if self.in_column {
+            self.rects.pop();
+            self.in_column = false;
+        }
+        self.columns = None;
+    }
+}
+
+/// Renders the operations for the current step of a compiled slide.
+#[derive(Debug)]
+struct CompiledSteps {
+    steps: Vec<Vec<RenderOperation>>,
+    current: Rc<Cell<usize>>,
+}
+
+impl AsRenderOperations for CompiledSteps {
+    fn as_render_operations(&self, _dimensions: &WindowSize) -> Vec<RenderOperation> {
+        self.steps.get(self.current.get()).cloned().unwrap_or_default()
+    }
+
+    fn diffable_content(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Moves through the steps of a compiled slide.
+#[derive(Debug)]
+struct StepMutator {
+    current: Rc<Cell<usize>>,
+    total: usize,
+}
+
+impl ChunkMutator for StepMutator {
+    fn mutate_next(&self) -> bool {
+        let next = self.current.get() + 1;
+        if next < self.total {
+            self.current.set(next);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn mutate_previous(&self) -> bool {
+        match self.current.get() {
+            0 => false,
+            current => {
+                self.current.set(current - 1);
+                true
+            }
+        }
+    }
+
+    fn reset_mutations(&self) {
+        self.current.set(0);
+    }
+
+    fn apply_all_mutations(&self) {
+        self.current.set(self.total.saturating_sub(1));
+    }
+
+    fn mutations(&self) -> (usize, usize) {
+        (self.current.get(), self.total)
+    }
+}
+
+/// An error compiling a presentation.
+#[derive(thiserror::Error, Debug)]
+pub enum CompileError {
+    #[error("failed to read presentation: {0}")]
+    ReadPresentation(io::Error),
+
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    #[error(transparent)]
+    Build(#[from] BuildError),
+
+    #[error("failed to encode image: {0}")]
+    EncodeImage(#[from] ImageError),
+
+    #[error("failed to write compiled presentation: {0}")]
+    WriteOutput(io::Error),
+}
+
+/// An error loading a compiled presentation.
+#[derive(thiserror::Error, Debug)]
+pub enum LoadCompiledError {
+    #[error("io: {0}")]
+    Io(io::Error),
+
+    #[error("corrupted compiled presentation: {0}")]
+    Corrupted(#[from] bincode::Error),
+
+    #[error("compiled presentation uses unsupported version {0}, compile it again")]
+    UnsupportedVersion(u32),
+
+    #[error("invalid image in compiled presentation: {0}")]
+    InvalidImage(#[from] InvalidImage),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::options::PresentationOptions;
+    use comrak::Arena;
+
+    fn compile(content: &str) -> CompiledPresentation {
+        let arena = Arena::new();
+        let parser = MarkdownParser::new(&arena);
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples");
+        let mut compiler = PresentationCompiler::new(parser, &theme, highlighter, resources);
+        compiler.compile_contents(content).expect("compilation failed")
+    }
+
+    fn step_operations(presentation: &Presentation) -> Vec<RenderOperation> {
+        let dimensions = WindowSize::from(DEFAULT_DIMENSIONS);
+        match presentation.current_slide().iter_operations().next().unwrap() {
+            RenderOperation::RenderDynamic(steps) => steps.as_render_operations(&dimensions),
+            _ => panic!("not a dynamic operation"),
+        }
+    }
+
+    fn step_texts(presentation: &Presentation) -> Vec<String> {
+        step_operations(presentation)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderText { line, .. } => {
+                    Some(line.iter_texts().map(|text| text.text.text.clone()).collect::<String>())
+                }
+                _ => None,
+            })
+            .filter(|text| !text.trim().is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn steps() {
+        let content = r"
+# First
+
+hi
+<!-- pause -->
+mom
+
+<!-- end_slide -->
+
+![](doge.png)
+";
+        let compiled = compile(content);
+        let directory = tempfile::TempDir::new().unwrap();
+        let path = directory.path().join("presentation.prsm");
+        compiled.write(&mut File::create(&path).unwrap()).expect("writing failed");
+        let compiled = CompiledPresentation::load(&path).expect("loading failed");
+        assert_eq!(compiled.slides.iter().map(|slide| slide.steps.len()).collect::<Vec<_>>(), &[2, 1]);
+        assert_eq!(compiled.slides[0].title.as_deref(), Some("First"));
+
+        let mut presentation = compiled.into_presentation().expect("loading failed");
+        // The footer is part of every step.
+        assert_eq!(step_texts(&presentation), &["First", "hi", "1 / 2"]);
+        presentation.jump_next_slide();
+        assert_eq!(step_texts(&presentation), &["First", "hi", "mom", "1 / 2"]);
+        presentation.jump_next_slide();
+        let operations = step_operations(&presentation);
+        assert!(operations.iter().any(|operation| matches!(operation, RenderOperation::RenderImage(_))));
+        presentation.jump_previous_slide();
+        assert_eq!(step_texts(&presentation), &["First", "hi", "mom", "1 / 2"]);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let path = directory.path().join("presentation.prsm");
+        std::fs::write(&path, bincode::serialize(&1000u32).unwrap()).unwrap();
+        let result = CompiledPresentation::load(&path);
+        assert!(matches!(result, Err(LoadCompiledError::UnsupportedVersion(1000))));
+    }
+
+    #[test]
+    fn corrupted_length() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let path = directory.path().join("presentation.prsm");
+        let mut contents = bincode::serialize(&FORMAT_VERSION).unwrap();
+        // A slide count that would take way more memory than there is.
+        contents.extend(bincode::serialize(&u64::MAX).unwrap());
+        std::fs::write(&path, contents).unwrap();
+        let result = CompiledPresentation::load(&path);
+        assert!(matches!(result, Err(LoadCompiledError::Corrupted(_))), "{result:?}");
+    }
+
+    #[test]
+    fn presentation_options() {
+        let content = "<!-- if: profile == \"workshop\" -->\nhello\n<!-- endif -->\n";
+        let arena = Arena::new();
+        let parser = MarkdownParser::new(&arena);
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples");
+        let cli = PresentationOptions { profile: Some("workshop".into()), ..Default::default() };
+        let mut compiler = PresentationCompiler::new(parser, &theme, highlighter, resources)
+            .with_options(ExternalOptions { cli, ..Default::default() });
+        let presentation = compiler.compile_contents(content).expect("compilation failed").into_presentation().unwrap();
+        assert_eq!(step_texts(&presentation)[0], "hello");
+    }
+}
@@ -0,0 +1,584 @@
+//! User configuration.
+
+use crate::options::PresentationOptions;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The user configuration.
+///
+/// This is loaded from `$XDG_CONFIG_HOME/presenterm/config.yaml`, falling back to
+/// `$HOME/.config/presenterm/config.yaml`, unless a specific path is provided.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The hooks to run when presentation events happen.
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+
+    /// The template used to set the terminal's window title while presenting.
+    ///
+    /// This can contain `{title}`, `{current_slide}`, and `{total_slides}` placeholders.
+    #[serde(default)]
+    pub window_title: Option<String>,
+
+    /// Ignore repeated next/previous slide commands that happen within this many milliseconds.
+    ///
+    /// This protects against clickers that fire twice on a single press.
+    #[serde(default)]
+    pub navigation_debounce_ms: Option<u64>,
+
+    /// What to do with comments that look like commands but aren't any known one.
+    #[serde(default)]
+    pub unknown_commands: UnknownCommandPolicy,
+
+    /// The placeholders that can be used in footers and templates on top of the built in ones.
+    #[serde(default)]
+    pub placeholders: PlaceholderConfig,
+
+    /// How the presentation's file is watched for changes in development mode.
+    #[serde(default)]
+    pub watch: WatchConfig,
+
+    /// What to show in terminal multiplexers, like tmux, while presenting inside them.
+    #[serde(default)]
+    pub multiplexer: MultiplexerConfig,
+
+    /// The commands used to render math blocks into images.
+    #[serde(default)]
+    pub math: MathConfig,
+
+    /// How the sounds set via the `sound` command are played.
+    #[serde(default)]
+    pub sound: SoundConfig,
+
+    /// The commands used to compile code blocks before executing them.
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+
+    /// The presentation options to use when neither parameters nor the presentation's front matter set them.
+    #[serde(default)]
+    pub options: PresentationOptions,
+
+    /// The area around the edges of the terminal that's left empty while presenting.
+    #[serde(default)]
+    pub safe_area: SafeAreaConfig,
+}
+
+impl Config {
+    /// Load the configuration from the given path.
+    pub fn load(path: &Path) -> Result<Self, ConfigLoadError> {
+        let contents = fs::read_to_string(path)?;
+        let config: Self = serde_yaml::from_str(&contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load the configuration from the default path, if it exists.
+    pub fn load_default() -> Result<Self, ConfigLoadError> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Get the default path for the configuration file.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("presenterm").join("config.yaml"))
+    }
+
+    fn validate(&self) -> Result<(), ConfigLoadError> {
+        for hook in &self.hooks {
+            match &hook.action {
+                HookAction::Command(command) if command.is_empty() => {
+                    return Err(ConfigLoadError::Invalid("hook command can't be empty".into()));
+                }
+                HookAction::Url(url) if !url.starts_with("http://") => {
+                    return Err(ConfigLoadError::Invalid(format!("hook url '{url}' must use http://")));
+                }
+                _ => (),
+            };
+        }
+        for (name, command) in &self.placeholders.commands {
+            if command.trim().is_empty() {
+                return Err(ConfigLoadError::Invalid(format!("placeholder '{name}' command can't be empty")));
+            }
+        }
+        if let Some(player) = &self.sound.player {
+            if player.is_empty() {
+                return Err(ConfigLoadError::Invalid("sound player can't be empty".into()));
+            }
+            if player.iter().any(|arg| arg.trim().is_empty()) {
+                return Err(ConfigLoadError::Invalid("sound player arguments can't be empty".into()));
+            }
+        }
+        for (name, command) in [("latex", &self.math.latex), ("typst", &self.math.typst)] {
+            if command.as_ref().is_some_and(|command| command.trim().is_empty()) {
+                return Err(ConfigLoadError::Invalid(format!("{name} math command can't be empty")));
+            }
+        }
+        let ExecutionConfig { rust, c, cpp, go, executors, container_runtime, hosts, timeout_ms, .. } = &self.execution;
+        if container_runtime.as_ref().is_some_and(|runtime| runtime.trim().is_empty()) {
+            return Err(ConfigLoadError::Invalid("container runtime can't be empty".into()));
+        }
+        if *timeout_ms == Some(0) {
+            return Err(ConfigLoadError::Invalid("execution timeout can't be 0".into()));
+        }
+        for (name, command) in [("rust", rust), ("c", c), ("cpp", cpp), ("go", go)] {
+            if command.as_ref().is_some_and(|command| command.trim().is_empty()) {
+                return Err(ConfigLoadError::Invalid(format!("{name} compile command can't be empty")));
+            }
+        }
+        for (name, host) in hosts {
+            if host.address.trim().is_empty() {
+                return Err(ConfigLoadError::Invalid(format!("{name} host address can't be empty")));
+            }
+        }
+        for (name, executor) in executors {
+            if executor.command.trim().is_empty() {
+                return Err(ConfigLoadError::Invalid(format!("{name} executor command can't be empty")));
+            }
+        }
+        self.safe_area.validate(crossterm::terminal::size().ok())?;
+        Ok(())
+    }
+}
+
+/// What to do with a comment that contains an unknown command, e.g. a typo like `end_slid`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownCommandPolicy {
+    /// Fail to load the presentation.
+    #[default]
+    Error,
+
+    /// Load the presentation and display a warning in the slide the comment is in.
+    Warn,
+
+    /// Treat the comment as a regular one.
+    Ignore,
+}
+
+/// The placeholders that can be used in footers and templates on top of the built in ones.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaceholderConfig {
+    /// Custom placeholders, by name, and the commands whose output is their value.
+    #[serde(default)]
+    pub commands: BTreeMap<String, String>,
+
+    /// The environment variables that can be used as `{env:NAME}` placeholders.
+    ///
+    /// No environment variable can be used unless it's listed here, as presentations could
+    /// otherwise display secrets that happen to be in the presenter's environment.
+    #[serde(default)]
+    pub environment: Vec<String>,
+}
+
+/// How the presentation's file is watched for changes.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchConfig {
+    /// How changes are detected.
+    #[serde(default)]
+    pub strategy: WatchStrategy,
+
+    /// Check the file at most once every this many milliseconds.
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+}
+
+/// How changes to a file are detected.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchStrategy {
+    /// Compare the file's modification time, size, and identity, following symlinks.
+    #[default]
+    Metadata,
+
+    /// Compare the file's contents.
+    ///
+    /// This works on filesystems that don't keep accurate modification times, like some network mounts.
+    Contents,
+}
+
+/// What to show in terminal multiplexers while presenting.
+///
+/// Templates can contain `{title}`, `{notes}`, `{current_slide}`, and `{total_slides}` placeholders.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MultiplexerConfig {
+    /// The template used to set tmux's right status line when presenting inside tmux.
+    #[serde(default)]
+    pub tmux_status: Option<String>,
+
+    /// The template used to name the pane the presentation is in when presenting inside zellij.
+    #[serde(default)]
+    pub zellij_pane: Option<String>,
+}
+
+/// The area around the edges of the terminal that's left empty while presenting.
+///
+/// This is meant for projectors and video captures that crop the edges of the screen, so nothing
+/// in a slide is ever drawn in the part that's cut off.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SafeAreaConfig {
+    /// The number of rows left empty at the top and at the bottom.
+    #[serde(default)]
+    pub rows: u16,
+
+    /// The number of columns left empty on the left and on the right.
+    #[serde(default)]
+    pub columns: u16,
+
+    /// Whether to draw faint marks in the corners of the safe area when not presenting.
+    #[serde(default)]
+    pub crop_marks: bool,
+}
+
+impl SafeAreaConfig {
+    // The safe area has to leave some of the window, given as its columns and rows if it's known, to draw in.
+    fn validate(&self, window: Option<(u16, u16)>) -> Result<(), ConfigLoadError> {
+        let (rows, columns) = (self.rows.checked_mul(2), self.columns.checked_mul(2));
+        let fits = match (rows, columns, window) {
+            (Some(rows), Some(columns), Some((window_columns, window_rows))) => {
+                rows < window_rows && columns < window_columns
+            }
+            (Some(_), Some(_), None) => true,
+            _ => false,
+        };
+        if !fits {
+            let Self { rows, columns, .. } = self;
+            let message = format!("safe area of {rows} rows and {columns} columns is larger than the window");
+            return Err(ConfigLoadError::Invalid(message));
+        }
+        Ok(())
+    }
+}
+
+/// The commands used to render math blocks into images.
+///
+/// Commands run in a directory that contains the formula in a file named `formula.tex` or
+/// `formula.typ` and are expected to write the image into `formula.png`. The built in commands,
+/// which use `latex` and `dvipng` for LaTeX and `typst` for typst, are used if these aren't set.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MathConfig {
+    /// The command used to render LaTeX formulas.
+    #[serde(default)]
+    pub latex: Option<String>,
+
+    /// The command used to render typst formulas.
+    #[serde(default)]
+    pub typst: Option<String>,
+}
+
+/// The commands used to compile code blocks in compiled languages.
+///
+/// Commands run in a directory that contains the code in a file named `main.rs`, `main.c`,
+/// `main.cpp`, or `main.go`, and are expected to write an executable named `main` that's run
+/// afterwards. The built in commands, which use `rustc`, `cc`, `c++`, and `go`, are used if these
+/// aren't set.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutionConfig {
+    /// The command used to compile rust code.
+    #[serde(default)]
+    pub rust: Option<String>,
+
+    /// The command used to compile C code.
+    #[serde(default)]
+    pub c: Option<String>,
+
+    /// The command used to compile C++ code.
+    #[serde(default)]
+    pub cpp: Option<String>,
+
+    /// The command used to compile go code.
+    #[serde(default)]
+    pub go: Option<String>,
+
+    /// How to execute code in other languages, by language name.
+    ///
+    /// These take precedence over the built in ways of executing code, and make code in languages
+    /// _presenterm_ doesn't know about executable.
+    #[serde(default)]
+    pub executors: BTreeMap<String, ExecutorConfig>,
+
+    /// The program used to run code blocks that use `+container`, `docker` if this isn't set.
+    ///
+    /// Anything with a command line compatible with docker's, like `podman`, can be used.
+    #[serde(default)]
+    pub container_runtime: Option<String>,
+
+    /// The machines code blocks that use `+host` run in over SSH, by name.
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostConfig>,
+
+    /// Stop code that's been running for this many milliseconds.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+
+    /// Show the commands code blocks run and ask for confirmation before running them.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// A machine code runs in over SSH.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct HostConfig {
+    /// The address passed to `ssh`, like `user@demo-box`.
+    pub address: String,
+
+    /// Any extra arguments passed to `ssh`, like `["-p", "2222"]`.
+    #[serde(default)]
+    pub ssh_args: Vec<String>,
+}
+
+/// How to execute code in a language.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutorConfig {
+    /// The command that executes the code.
+    ///
+    /// This runs in a directory that contains the code and can contain a `{file}` placeholder
+    /// with the name of the file the code is in.
+    pub command: String,
+
+    /// The extension of the file the code is in, if it needs one.
+    #[serde(default)]
+    pub extension: Option<String>,
+}
+
+/// How sounds are played.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SoundConfig {
+    /// The command used to play a sound.
+    ///
+    /// The first element is the program and the rest are its arguments. Arguments can contain a
+    /// `{path}` placeholder. Sounds are never played unless this is set.
+    #[serde(default)]
+    pub player: Option<Vec<String>>,
+}
+
+/// A hook that's triggered when a presentation event happens.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    /// The event that triggers this hook.
+    pub event: HookEvent,
+
+    /// What to do when the event happens.
+    #[serde(flatten)]
+    pub action: HookAction,
+}
+
+/// The events that can trigger a hook.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// Any event.
+    Any,
+
+    /// The presentation started.
+    PresentationStarted,
+
+    /// The presentation ended.
+    PresentationEnded,
+
+    /// The current slide changed.
+    SlideChanged,
+
+    /// A piece of code finished executing.
+    ExecutionFinished,
+}
+
+/// The action a hook performs.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookAction {
+    /// Run a command.
+    ///
+    /// The first element is the program and the rest are its arguments. Arguments can contain
+    /// `{event}`, `{current_slide}`, and `{total_slides}` placeholders.
+    Command(Vec<String>),
+
+    /// Send a POST request with the event serialized as JSON to a URL.
+    Url(String),
+}
+
+/// An error loading the configuration.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigLoadError {
+    #[error("io: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid configuration: {0}")]
+    Parse(#[from] serde_yaml::Error),
+
+    #[error("invalid configuration: {0}")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::presenter::BoundaryCue;
+
+    fn parse(contents: &str) -> Result<Config, ConfigLoadError> {
+        let config: Config = serde_yaml::from_str(contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn hooks() {
+        let config = parse(
+            r#"
+hooks:
+  - event: slide_changed
+    command: ["obs-cmd", "scene", "{current_slide}"]
+  - event: any
+    url: http://localhost:8080/events
+"#,
+        )
+        .expect("invalid config");
+        assert_eq!(config.hooks.len(), 2);
+        assert_eq!(config.hooks[0].event, HookEvent::SlideChanged);
+        assert!(matches!(&config.hooks[0].action, HookAction::Command(args) if args.len() == 3));
+        assert!(matches!(&config.hooks[1].action, HookAction::Url(url) if url == "http://localhost:8080/events"));
+    }
+
+    #[test]
+    fn window_title() {
+        let config = parse("window_title: '{title} ({current_slide}/{total_slides})'").expect("invalid config");
+        assert_eq!(config.window_title.as_deref(), Some("{title} ({current_slide}/{total_slides})"));
+    }
+
+    #[test]
+    fn multiplexer() {
+        let config = parse("multiplexer: { tmux_status: '{title}' }").expect("invalid config");
+        let expected = MultiplexerConfig { tmux_status: Some("{title}".into()), zellij_pane: None };
+        assert_eq!(config.multiplexer, expected);
+    }
+
+    #[test]
+    fn safe_area() {
+        let config = parse("safe_area: { rows: 1, columns: 3, crop_marks: true }").expect("invalid config");
+        let expected = SafeAreaConfig { rows: 1, columns: 3, crop_marks: true };
+        assert_eq!(config.safe_area, expected);
+    }
+
+    #[test]
+    fn safe_area_larger_than_window() {
+        let safe_area = SafeAreaConfig { rows: 5, columns: 10, crop_marks: false };
+        assert!(safe_area.validate(Some((21, 11))).is_ok());
+        assert!(safe_area.validate(None).is_ok());
+        assert!(safe_area.validate(Some((20, 11))).is_err());
+        assert!(safe_area.validate(Some((21, 10))).is_err());
+
+        let safe_area = SafeAreaConfig { rows: u16::MAX, columns: 1, crop_marks: false };
+        assert!(safe_area.validate(None).is_err());
+    }
+
+    #[test]
+    fn math() {
+        let config = parse("math: { typst: 'typst compile formula.typ formula.png' }").expect("invalid config");
+        let expected = MathConfig { latex: None, typst: Some("typst compile formula.typ formula.png".into()) };
+        assert_eq!(config.math, expected);
+        assert!(parse("math: { latex: ' ' }").is_err());
+    }
+
+    #[test]
+    fn execution() {
+        let config = parse("execution: { rust: 'rustc -O -o main main.rs' }").expect("invalid config");
+        let expected = ExecutionConfig { rust: Some("rustc -O -o main main.rs".into()), ..Default::default() };
+        assert_eq!(config.execution, expected);
+        assert!(parse("execution: { go: '' }").is_err());
+        assert!(parse("execution: { container_runtime: '' }").is_err());
+        assert!(parse("execution: { timeout_ms: 0 }").is_err());
+    }
+
+    #[test]
+    fn executors() {
+        let config = parse("execution: { executors: { ts: { command: 'deno run {file}', extension: ts } } }")
+            .expect("invalid config");
+        let expected = ExecutorConfig { command: "deno run {file}".into(), extension: Some("ts".into()) };
+        assert_eq!(config.execution.executors["ts"], expected);
+        assert!(parse("execution: { executors: { python: { command: ' ' } } }").is_err());
+    }
+
+    #[test]
+    fn hosts() {
+        let config = parse("execution: { hosts: { demo: { address: 'me@demo-box', ssh_args: ['-p', '2222'] } } }")
+            .expect("invalid config");
+        let expected = HostConfig { address: "me@demo-box".into(), ssh_args: vec!["-p".into(), "2222".into()] };
+        assert_eq!(config.execution.hosts["demo"], expected);
+        assert!(parse("execution: { hosts: { demo: { address: '' } } }").is_err());
+    }
+
+    #[test]
+    fn options() {
+        let config = parse("options: { boundary_cue: notification, dry_run_exec: true }").expect("invalid config");
+        let expected = PresentationOptions {
+            boundary_cue: Some(BoundaryCue::Notification),
+            dry_run_exec: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(config.options, expected);
+        assert!(parse("options: { boundary_cue: siren }").is_err());
+    }
+
+    #[test]
+    fn sound() {
+        let config = parse("sound: { player: ['paplay', '{path}'] }").expect("invalid config");
+        assert_eq!(config.sound.player, Some(vec!["paplay".into(), "{path}".into()]));
+        assert!(parse("sound: { player: [] }").is_err());
+        assert!(parse("sound: { player: ['', '{path}'] }").is_err());
+        assert!(parse("sound: { player: ['paplay', ' '] }").is_err());
+    }
+
+    #[test]
+    fn navigation_debounce() {
+        let config = parse("navigation_debounce_ms: 150").expect("invalid config");
+        assert_eq!(config.navigation_debounce_ms, Some(150));
+    }
+
+    #[test]
+    fn placeholders() {
+        let contents = "placeholders: { commands: { weather: \"curl -s 'wttr.in?format=3'\" }, environment: [USER] }";
+        let config = parse(contents).expect("invalid config");
+        assert_eq!(config.placeholders.commands["weather"], "curl -s 'wttr.in?format=3'");
+        assert_eq!(config.placeholders.environment, &["USER"]);
+        assert!(parse("placeholders: { commands: { weather: ' ' } }").is_err());
+    }
+
+    #[test]
+    fn watch() {
+        let config = parse("watch: { strategy: contents, interval_ms: 1000 }").expect("invalid config");
+        assert_eq!(config.watch.strategy, WatchStrategy::Contents);
+        assert_eq!(config.watch.interval_ms, Some(1000));
+    }
+
+    #[test]
+    fn hook_https_url() {
+        let result = parse("hooks: [{event: any, url: 'https://example.com'}]");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn hook_empty_command() {
+        let result = parse("hooks: [{event: any, command: []}]");
+        assert!(result.is_err());
+    }
+}
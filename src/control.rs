@@ -0,0 +1,336 @@
+//! Keeps several presenterm instances in sync over a control socket.
+//!
+//! The instance acting as the presenter listens on the socket and relays every navigation command
+//! it applies, one JSON object per line. Audience instances connect to it and apply the same
+//! commands.
+//!
+//! The same socket also answers plain HTTP `GET` requests describing the presentation, so other
+//! programs like stream overlays can show where the presenter is at.
+
+use crate::input::source::Command;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(250);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A slide, as described to programs asking the control server about the presentation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct SlideSummary {
+    /// The slide number, 1-index based.
+    pub(crate) slide: usize,
+
+    /// The slide's title, if any.
+    pub(crate) title: Option<String>,
+
+    /// The speaker notes in this slide.
+    pub(crate) notes: Vec<String>,
+
+    /// The number of chunks in this slide.
+    pub(crate) total_chunks: usize,
+}
+
+#[derive(Default)]
+struct ControlState {
+    clients: Vec<Sender<String>>,
+    slide: usize,
+    chunk: usize,
+    slides: Vec<SlideSummary>,
+}
+
+/// The listening end of the control socket.
+pub(crate) struct ControlServer {
+    state: Arc<Mutex<ControlState>>,
+}
+
+impl ControlServer {
+    /// Start listening on the given address.
+    pub(crate) fn bind(address: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let state = Arc::new(Mutex::new(ControlState::default()));
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = server_state.clone();
+                thread::spawn(move || Self::accept(stream, &state));
+            }
+        });
+        Ok(Self { state })
+    }
+
+    fn accept(mut stream: TcpStream, state: &Mutex<ControlState>) -> io::Result<()> {
+        // HTTP clients talk first while instances only ever listen, so wait a bit to find out which
+        // one this is.
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut method = [0; 4];
+        let is_http = matches!(stream.peek(&mut method), Ok(4) if &method == b"GET ");
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        if is_http {
+            // HTTP clients that never finish sending their request shouldn't hold on to their connection forever.
+            stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+            return Self::serve(stream, state);
+        }
+        stream.set_read_timeout(None)?;
+        let (sender, receiver) = mpsc::channel();
+        {
+            let mut state = state.lock().expect("lock poisoned");
+            // Bring the new client up to date before relaying anything else to it.
+            let command = Command::JumpPosition { slide: state.slide, chunk: state.chunk };
+            sender.send(Self::serialize(&command)).expect("receiver dropped");
+            state.clients.push(sender);
+        }
+        // Writes happen in this client's own thread so a slow client can't hold up the rest. Once a
+        // write fails or times out the receiver is dropped, which gets the client removed from the list
+        // the next time something is broadcast.
+        for line in receiver {
+            stream.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn serve(stream: TcpStream, state: &Mutex<ControlState>) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Consume the headers, we don't care about them.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let body = {
+            let state = state.lock().expect("lock poisoned");
+            match path {
+                "/current" => Some(Self::current(&state).to_string()),
+                "/slides" => Some(serde_json::to_string(&state.slides).expect("serialization failed")),
+                _ => None,
+            }
+        };
+        match body {
+            Some(body) => Self::respond(stream, "200 OK", &body),
+            None => Self::respond(stream, "404 Not Found", ""),
+        }
+    }
+
+    fn current(state: &ControlState) -> serde_json::Value {
+        let slide = state.slides.get(state.slide).cloned().unwrap_or_default();
+        json!({
+            "current_slide": state.slide + 1,
+            "total_slides": state.slides.len(),
+            "current_chunk": state.chunk + 1,
+            "total_chunks": slide.total_chunks,
+            "title": slide.title,
+            "notes": slide.notes,
+        })
+    }
+
+    fn respond(mut stream: TcpStream, status: &str, body: &str) -> io::Result<()> {
+        let length = body.len();
+        // There's no CORS header on purpose: any web page open in the presenter's browser could otherwise
+        // read their speaker notes.
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {length}\r\n\
+            Connection: close\r\n\r\n{body}"
+        )?;
+        stream.flush()
+    }
+
+    /// Set the position the presentation is at.
+    ///
+    /// Positions are relayed as absolute jumps rather than the commands that led to them so clients
+    /// that missed or skipped a command don't drift away from the presenter.
+    pub(crate) fn set_position(&self, slide: usize, chunk: usize) {
+        let mut state = self.state.lock().expect("lock poisoned");
+        if (state.slide, state.chunk) == (slide, chunk) {
+            return;
+        }
+        state.slide = slide;
+        state.chunk = chunk;
+        Self::send_all(&mut state, &Command::JumpPosition { slide, chunk });
+    }
+
+    /// Set the slides in the presentation, as described over HTTP.
+    pub(crate) fn set_slides(&self, slides: Vec<SlideSummary>) {
+        self.state.lock().expect("lock poisoned").slides = slides;
+    }
+
+    /// Relay a command to every connected client.
+    pub(crate) fn broadcast(&self, command: &Command) {
+        let mut state = self.state.lock().expect("lock poisoned");
+        Self::send_all(&mut state, command);
+    }
+
+    fn send_all(state: &mut ControlState, command: &Command) {
+        let line = Self::serialize(command);
+        // Clients whose writer thread is gone can't be written to anymore so they're dropped.
+        state.clients.retain(|client| client.send(line.clone()).is_ok());
+    }
+
+    fn serialize(command: &Command) -> String {
+        let mut line = serde_json::to_string(command).expect("serialization failed");
+        line.push('\n');
+        line
+    }
+}
+
+/// The connecting end of the control socket.
+pub(crate) struct ControlClient;
+
+impl ControlClient {
+    /// Connect to a control server in the background.
+    ///
+    /// The connection is retried until it succeeds, and whenever it's lost, so the presenter
+    /// instance can be started or restarted at any time.
+    pub(crate) fn connect(address: SocketAddr) -> Receiver<Command> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || loop {
+            if let Ok(stream) = TcpStream::connect(address) {
+                if Self::relay(stream, &sender).is_err() {
+                    // The receiving end is gone, there's nothing else to do.
+                    return;
+                }
+            }
+            thread::sleep(RECONNECT_INTERVAL);
+        });
+        receiver
+    }
+
+    fn relay(stream: TcpStream, sender: &Sender<Command>) -> Result<(), mpsc::SendError<Command>> {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            // Ignore anything we don't understand.
+            if let Ok(command) = serde_json::from_str(&line) {
+                sender.send(command)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check whether a command should be relayed to other instances.
+///
+/// Only commands that change what's displayed are relayed; anything else, like reloads, is up to
+/// each instance. Navigation isn't relayed either as the resulting position is sent instead.
+pub(crate) fn is_relayed(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::RenderWidgets
+            | Command::RunConfirmedCode { .. }
+            | Command::CancelExecution
+            | Command::ScrollOutputUp
+            | Command::ScrollOutputDown
+            | Command::Exit
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+    use std::io::Read;
+
+    #[test]
+    fn relay() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let server = ControlServer::bind(address).unwrap();
+        server.set_position(2, 1);
+
+        let receiver = ControlClient::connect(address);
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Command::JumpPosition { slide: 2, chunk: 1 });
+
+        server.broadcast(&Command::RenderWidgets);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Command::RenderWidgets);
+
+        server.set_position(3, 0);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Command::JumpPosition { slide: 3, chunk: 0 });
+
+        // Setting the same position again doesn't relay anything.
+        server.set_position(3, 0);
+        server.broadcast(&Command::Exit);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Command::Exit);
+    }
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn http() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let server = ControlServer::bind(address).unwrap();
+        let slides = vec![
+            SlideSummary { slide: 1, title: Some("intro".into()), notes: vec![], total_chunks: 1 },
+            SlideSummary { slide: 2, title: None, notes: vec!["breathe".into()], total_chunks: 3 },
+        ];
+        server.set_slides(slides);
+        server.set_position(1, 2);
+
+        let current = get(address, "/current");
+        assert!(current.starts_with("HTTP/1.1 200 OK"), "{current}");
+        let body = |response: &str| -> serde_json::Value {
+            serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap()
+        };
+        let expected = json!({
+            "current_slide": 2,
+            "total_slides": 2,
+            "current_chunk": 3,
+            "total_chunks": 3,
+            "title": null,
+            "notes": ["breathe"],
+        });
+        assert_eq!(body(&current), expected);
+
+        let slides = get(address, "/slides");
+        assert!(slides.starts_with("HTTP/1.1 200 OK"), "{slides}");
+        let expected = json!([
+            {"slide": 1, "title": "intro", "notes": [], "total_chunks": 1},
+            {"slide": 2, "title": null, "notes": ["breathe"], "total_chunks": 3},
+        ]);
+        assert_eq!(body(&slides), expected);
+        assert!(get(address, "/potato").starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[test]
+    fn incomplete_http_request_times_out() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let _server = ControlServer::bind(address).unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET /current HTTP/1.1\r\n").unwrap();
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT * 2)).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).expect("connection not closed");
+        assert_eq!(response, "");
+    }
+
+    #[rstest]
+    #[case::next(Command::JumpNextSlide, false)]
+    #[case::undo(Command::UndoNavigation, false)]
+    #[case::render(Command::RenderWidgets, true)]
+    #[case::run_confirmed(Command::RunConfirmedCode { blocks: Some(vec![1]) }, true)]
+    #[case::exit(Command::Exit, true)]
+    #[case::reload(Command::Reload, false)]
+    #[case::redraw(Command::Redraw, false)]
+    fn relayed(#[case] command: Command, #[case] expected: bool) {
+        assert_eq!(is_relayed(&command), expected);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::presentation::{Presentation, RenderOperation, SlideChunk};
+use crate::presentation::{Presentation, RenderOperation, Slide, SlideChunk};
 use std::{any::Any, cmp::Ordering, fmt::Debug, mem};
 
 /// Allow diffing presentations.
@@ -6,10 +6,15 @@ pub(crate) struct PresentationDiffer;
 
 impl PresentationDiffer {
     /// Find the first modification between two presentations.
+    ///
+    /// Slides are matched by their id when they have one and by their position otherwise, so adding
+    /// or removing a slide doesn't make every slide after it look modified.
     pub(crate) fn find_first_modification(original: &Presentation, updated: &Presentation) -> Option<Modification> {
-        let original_slides = original.iter_slides();
-        let updated_slides = updated.iter_slides();
-        for (slide_index, (original, updated)) in original_slides.zip(updated_slides).enumerate() {
+        for (slide_index, updated) in updated.iter_slides().enumerate() {
+            // If there was no such slide before, this is a new one so let's scroll to it.
+            let Some(original) = Self::counterpart(updated, slide_index, original) else {
+                return Some(Modification { slide_index, chunk_index: 0 });
+            };
             for (chunk_index, (original, updated)) in original.iter_chunks().zip(updated.iter_chunks()).enumerate() {
                 if original.is_content_different(updated) {
                     return Some(Modification { slide_index, chunk_index });
@@ -25,17 +30,35 @@ impl PresentationDiffer {
                 }
             }
         }
-        let total_original = original.iter_slides().count();
+        // At this point the only possible change is that some slides were removed.
+        let mapping = Self::slide_mapping(original, updated);
+        let removed = mapping.iter().position(Option::is_none)?;
         let total_updated = updated.iter_slides().count();
-        match total_original.cmp(&total_updated) {
-            // If they have the same number of slides there's no difference.
-            Ordering::Equal => None,
-            // If the original had fewer, let's scroll to the first new one.
-            Ordering::Less => Some(Modification { slide_index: total_original, chunk_index: 0 }),
-            // If the original had more, let's scroll to the last one.
-            Ordering::Greater => {
-                Some(Modification { slide_index: total_updated.saturating_sub(1), chunk_index: usize::MAX })
-            }
+        match mapping[..removed].iter().flatten().count() {
+            // If the removed slide was followed by others, let's scroll to the one that took its place.
+            slide_index if slide_index < total_updated => Some(Modification { slide_index, chunk_index: 0 }),
+            // Otherwise let's scroll to the last one.
+            _ => Some(Modification { slide_index: total_updated.saturating_sub(1), chunk_index: usize::MAX }),
+        }
+    }
+
+    /// Get the index every slide in the original presentation has in the updated one.
+    ///
+    /// Slides that don't exist in the updated presentation are mapped to `None`.
+    pub(crate) fn slide_mapping(original: &Presentation, updated: &Presentation) -> Vec<Option<usize>> {
+        let slides = original.iter_slides().enumerate();
+        slides.map(|(index, slide)| Self::counterpart_index(slide, index, updated)).collect()
+    }
+
+    fn counterpart<'a>(slide: &Slide, index: usize, other: &'a Presentation) -> Option<&'a Slide> {
+        let index = Self::counterpart_index(slide, index, other)?;
+        other.iter_slides().nth(index)
+    }
+
+    fn counterpart_index(slide: &Slide, index: usize, other: &Presentation) -> Option<usize> {
+        match &slide.metadata().id {
+            Some(id) => other.find_slide(id),
+            None => (index < other.iter_slides().count()).then_some(index),
         }
     }
 }
@@ -72,6 +95,10 @@ impl ContentDiff for RenderOperation {
                 false
             }
             (RenderImage(original), RenderImage(updated)) if original != updated => true,
+            (
+                RenderFloatingImage { image: original_image, float: original_float },
+                RenderFloatingImage { image: updated_image, float: updated_float },
+            ) if original_image != updated_image || original_float != updated_float => true,
             (RenderPreformattedLine(original), RenderPreformattedLine(updated)) if original != updated => true,
             (InitColumnLayout { columns: original }, InitColumnLayout { columns: updated }) if original != updated => {
                 true
@@ -81,6 +108,9 @@ impl ContentDiff for RenderOperation {
             (RenderDynamic(original), RenderDynamic(updated)) => {
                 original.diffable_content() != updated.diffable_content()
             }
+            (RenderCustom(original), RenderCustom(updated)) => {
+                original.diffable_content() != updated.diffable_content()
+            }
             _ => false,
         }
     }
@@ -108,7 +138,7 @@ where
 mod test {
     use super::*;
     use crate::{
-        presentation::{AsRenderOperations, PreformattedLine, Slide},
+        presentation::{AsRenderOperations, PreformattedLine, SlideId, SlideMetadata},
         render::properties::WindowSize,
         style::{Color, Colors},
         theme::{Alignment, Margin},
@@ -294,4 +324,62 @@ mod test {
             Some(Modification { slide_index: 1, chunk_index: 1 })
         );
     }
+
+    fn identified_slide(id: &str, operation: RenderOperation) -> Slide {
+        let metadata = SlideMetadata { id: Some(SlideId::from(id.to_string())), ..Default::default() };
+        Slide::from(vec![operation]).with_metadata(metadata)
+    }
+
+    #[test]
+    fn identified_slide_inserted() {
+        let lhs = Presentation::new(vec![
+            identified_slide("a", RenderOperation::ClearScreen),
+            identified_slide("b", RenderOperation::JumpToVerticalCenter),
+        ]);
+        let rhs = Presentation::new(vec![
+            identified_slide("a", RenderOperation::ClearScreen),
+            identified_slide("new", RenderOperation::ClearScreen),
+            identified_slide("b", RenderOperation::JumpToVerticalCenter),
+        ]);
+
+        assert_eq!(
+            PresentationDiffer::find_first_modification(&lhs, &rhs),
+            Some(Modification { slide_index: 1, chunk_index: 0 })
+        );
+        assert_eq!(PresentationDiffer::slide_mapping(&lhs, &rhs), &[Some(0), Some(2)]);
+    }
+
+    #[test]
+    fn identified_slide_removed() {
+        let lhs = Presentation::new(vec![
+            identified_slide("a", RenderOperation::ClearScreen),
+            identified_slide("b", RenderOperation::ClearScreen),
+            identified_slide("c", RenderOperation::JumpToVerticalCenter),
+        ]);
+        let rhs = Presentation::new(vec![
+            identified_slide("a", RenderOperation::ClearScreen),
+            identified_slide("c", RenderOperation::JumpToVerticalCenter),
+        ]);
+
+        assert_eq!(
+            PresentationDiffer::find_first_modification(&lhs, &rhs),
+            Some(Modification { slide_index: 1, chunk_index: 0 })
+        );
+        assert_eq!(PresentationDiffer::slide_mapping(&lhs, &rhs), &[Some(0), None, Some(1)]);
+    }
+
+    #[test]
+    fn identified_slides_reordered() {
+        let lhs = Presentation::new(vec![
+            identified_slide("a", RenderOperation::ClearScreen),
+            identified_slide("b", RenderOperation::JumpToVerticalCenter),
+        ]);
+        let rhs = Presentation::new(vec![
+            identified_slide("b", RenderOperation::JumpToVerticalCenter),
+            identified_slide("a", RenderOperation::ClearScreen),
+        ]);
+
+        assert_eq!(PresentationDiffer::find_first_modification(&lhs, &rhs), None);
+        assert_eq!(PresentationDiffer::slide_mapping(&lhs, &rhs), &[Some(1), Some(0)]);
+    }
 }
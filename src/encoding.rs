@@ -0,0 +1,35 @@
+//! Encodings that are simple enough not to pull in a dependency for.
+
+/// Encode some bytes as base64, using the standard alphabet and padding.
+pub(crate) fn encode_base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let value = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                output.push(ALPHABET[(value >> (18 - index * 6) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty("", "")]
+    #[case::two_padding("f", "Zg==")]
+    #[case::one_padding("fo", "Zm8=")]
+    #[case::no_padding("foo", "Zm9v")]
+    #[case::longer("fn main() {}", "Zm4gbWFpbigpIHt9")]
+    fn base64(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(encode_base64(input.as_bytes()), expected);
+    }
+}
@@ -0,0 +1,93 @@
+//! Presentation events.
+
+use serde::Serialize;
+
+/// An event that happens while presenting.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum PresentationEvent {
+    /// The presentation started.
+    PresentationStarted { total_slides: usize },
+
+    /// The presentation ended.
+    PresentationEnded,
+
+    /// The current slide changed.
+    ///
+    /// Slide numbers are 1-index based.
+    SlideChanged { current_slide: usize, total_slides: usize },
+
+    /// All pieces of code being executed in the current slide finished running.
+    ExecutionFinished { current_slide: usize, total_slides: usize },
+}
+
+impl PresentationEvent {
+    /// Get this event's name.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::PresentationStarted { .. } => "presentation_started",
+            Self::PresentationEnded => "presentation_ended",
+            Self::SlideChanged { .. } => "slide_changed",
+            Self::ExecutionFinished { .. } => "execution_finished",
+        }
+    }
+}
+
+/// A listener for presentation events.
+pub(crate) trait EventListener {
+    /// Handle an event.
+    ///
+    /// Listeners are invoked from the presentation loop so they shouldn't block.
+    fn on_event(&mut self, event: &PresentationEvent);
+}
+
+/// Dispatches presentation events to every subscribed listener.
+#[derive(Default)]
+pub(crate) struct EventBus {
+    listeners: Vec<Box<dyn EventListener>>,
+}
+
+impl EventBus {
+    /// Subscribe a listener to all future events.
+    pub(crate) fn subscribe(&mut self, listener: Box<dyn EventListener>) {
+        self.listeners.push(listener);
+    }
+
+    /// Publish an event to all listeners.
+    pub(crate) fn publish(&mut self, event: PresentationEvent) {
+        for listener in &mut self.listeners {
+            listener.on_event(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Recorder(Rc<RefCell<Vec<PresentationEvent>>>);
+
+    impl EventListener for Recorder {
+        fn on_event(&mut self, event: &PresentationEvent) {
+            self.0.borrow_mut().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn publish() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = EventBus::default();
+        bus.subscribe(Box::new(Recorder(events.clone())));
+        bus.subscribe(Box::new(Recorder(events.clone())));
+        bus.publish(PresentationEvent::PresentationEnded);
+        assert_eq!(*events.borrow(), &[PresentationEvent::PresentationEnded, PresentationEvent::PresentationEnded]);
+    }
+
+    #[test]
+    fn serialize() {
+        let event = PresentationEvent::SlideChanged { current_slide: 2, total_slides: 5 };
+        let serialized = serde_json::to_string(&event).unwrap();
+        assert_eq!(serialized, r#"{"event":"slide_changed","current_slide":2,"total_slides":5}"#);
+    }
+}
@@ -1,52 +1,381 @@
 //! Code execution.
 
-use crate::markdown::elements::{Code, CodeLanguage};
+use crate::{
+    config::{ExecutionConfig, ExecutorConfig, HostConfig},
+    markdown::{
+        code::CodeBlockParser,
+        elements::{Code, CodeLanguage},
+    },
+    transcript::{ExecutionTranscript, TranscriptEntry},
+};
 use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
     io::{self, BufRead, BufReader, Write},
+    path::Path,
     process::{self, ChildStdout, Stdio},
-    sync::{Arc, Mutex},
+    rc::Rc,
+    sync::{mpsc, Arc, Mutex},
     thread::{self},
+    time::{Duration, Instant},
 };
-use tempfile::NamedTempFile;
+use tempfile::{NamedTempFile, TempDir};
+
+const RUST_COMMAND: &str = "rustc --edition 2021 -o main main.rs";
+const C_COMMAND: &str = "cc -o main main.c";
+const CPP_COMMAND: &str = "c++ -o main main.cpp";
+const GO_COMMAND: &str = "go build -o main main.go";
+const DEFAULT_CONTAINER_RUNTIME: &str = "docker";
+
+/// How long commands can run for unless they're given a timeout of their own.
+const DEFAULT_COMMAND_TIMEOUT_MS: u64 = 60_000;
+
+// Where the directory the code is in is mounted in containers.
+const CONTAINER_DIRECTORY: &str = "/presenterm";
+
+// Ends the heredoc the code is written into a file with in remote hosts.
+const HEREDOC_DELIMITER: &str = "PRESENTERM_SNIPPET_END";
+
+// Where the directories code runs in are created in remote hosts, relative to the home directory.
+const REMOTE_DIRECTORY: &str = ".cache/presenterm";
 
 /// Allows executing code.
 pub(crate) struct CodeExecuter;
 
 impl CodeExecuter {
     /// Execute a piece of code.
-    pub(crate) fn execute(code: &Code) -> Result<ExecutionHandle, CodeExecuteError> {
-        if !code.language.supports_execution() {
+    ///
+    /// Code in compiled languages is compiled using the commands in the given configuration first.
+    pub(crate) fn execute(code: &Code, config: &ExecutionConfig) -> Result<ExecutionHandle, CodeExecuteError> {
+        Self::execute_with_stdin(code, config, None)
+    }
+
+    /// Execute a piece of code, writing the given input into its stdin.
+    pub(crate) fn execute_with_stdin(
+        code: &Code,
+        config: &ExecutionConfig,
+        stdin: Option<String>,
+    ) -> Result<ExecutionHandle, CodeExecuteError> {
+        if !Self::supports(&code.language, config) {
             return Err(CodeExecuteError::UnsupportedExecution);
         }
         if !code.attributes.execute {
             return Err(CodeExecuteError::NotExecutableCode);
         }
-        match &code.language {
-            CodeLanguage::Shell(interpreter) => Self::execute_shell(interpreter, &code.contents),
-            _ => Err(CodeExecuteError::UnsupportedExecution),
+        let backend = Backend::new(code, config)?;
+        let contents = code.executable_contents();
+        if let Some(executor) = Self::executor(&code.language, config) {
+            let file_name = Self::snippet_file_name(executor);
+            let script = executor.command.replace("{file}", &file_name);
+            return Self::execute_in_directory(&backend, &file_name, &script, &contents, config, stdin);
         }
+        match (&code.language, &backend) {
+            (CodeLanguage::Shell(interpreter), Backend::Host) => {
+                return Self::execute_shell(interpreter, &contents, config, stdin);
+            }
+            // Containers can only see the directory the code is in so shell code runs in it too.
+            (CodeLanguage::Shell(interpreter), _) => {
+                let script = format!("exec {interpreter} snippet");
+                return Self::execute_in_directory(&backend, "snippet", &script, &contents, config, stdin);
+            }
+            _ => (),
+        };
+        let (file_name, command) =
+            Self::compile_command(&code.language, config).ok_or(CodeExecuteError::UnsupportedExecution)?;
+        // Compilation errors are sent to stdout so they show up in the output like the program's do.
+        let script = format!("({command}) 2>&1 && exec ./main");
+        Self::execute_in_directory(&backend, file_name, &script, &contents, config, stdin)
+    }
+
+    /// Describe the commands that would run when executing a piece of code, without running them.
+    ///
+    /// Shell code is described by its own commands and code in every other language by the command
+    /// that compiles or runs it.
+    pub(crate) fn dry_run(code: &Code, config: &ExecutionConfig) -> Result<Vec<String>, CodeExecuteError> {
+        if !Self::supports(&code.language, config) {
+            return Err(CodeExecuteError::UnsupportedExecution);
+        }
+        if !code.attributes.execute {
+            return Err(CodeExecuteError::NotExecutableCode);
+        }
+        // Code that runs somewhere else says where first.
+        let mut lines = Vec::new();
+        if let Some(host) = &code.attributes.host {
+            lines.push(format!("# in host {host}"));
+        }
+        if let Some(image) = &code.attributes.container {
+            lines.push(format!("# in container {image}"));
+        }
+        if let Some(executor) = Self::executor(&code.language, config) {
+            let command = executor.command.replace("{file}", &Self::snippet_file_name(executor));
+            lines.push(format!("$ {command}"));
+        } else if let CodeLanguage::Shell(_) = &code.language {
+            lines.extend(code.executable_contents().lines().map(|line| format!("$ {line}")));
+        } else {
+            let (_, command) =
+                Self::compile_command(&code.language, config).ok_or(CodeExecuteError::UnsupportedExecution)?;
+            lines.push(format!("$ {command} && ./main"));
+        }
+        Ok(lines)
+    }
+
+    /// Execute a piece of code and wait for it to finish, returning the lines it wrote into stdout.
+    ///
+    /// The run is appended to the given transcript, if any.
+    pub(crate) fn execute_sync(
+        code: &Code,
+        config: &ExecutionConfig,
+        transcript: Option<&ExecutionTranscript>,
+    ) -> Result<Vec<String>, CodeExecuteError> {
+        let started_at = Instant::now();
+        let ExecutionState { mut output, status, exit_code } = Self::execute(code, config)?.wait();
+        if let Some(transcript) = transcript {
+            let commands = Self::dry_run(code, config).unwrap_or_default();
+            let source = TranscriptEntry::source(code);
+            let entry = TranscriptEntry {
+                commands: &commands,
+                source: &source,
+                output: &output,
+                status: &status,
+                exit_code,
+                duration: started_at.elapsed(),
+            };
+            if let Err(e) = transcript.record(&entry) {
+                output.push(format!("[failed to write transcript: {e}]"));
+            }
+        }
+        match status {
+            ProcessStatus::Failure | ProcessStatus::Cancelled => {
+                Err(CodeExecuteError::ExecutionFailed(output.join("\n")))
+            }
+            ProcessStatus::TimedOut => Err(CodeExecuteError::TimedOut),
+            ProcessStatus::Running | ProcessStatus::Success => Ok(output),
+        }
+    }
+
+    /// Check whether code in a language can be executed, either by default or via a configured executor.
+    pub(crate) fn supports(language: &CodeLanguage, config: &ExecutionConfig) -> bool {
+        language.supports_execution() || Self::executor(language, config).is_some()
+    }
+
+    // Executors can be configured using any of the names code blocks can use for a language, e.g. `ts`.
+    fn executor<'a>(language: &CodeLanguage, config: &'a ExecutionConfig) -> Option<&'a ExecutorConfig> {
+        config
+            .executors
+            .iter()
+            .find(|(name, _)| &CodeBlockParser::parse_language_name(name) == language)
+            .map(|(_, executor)| executor)
     }
 
-    fn execute_shell(interpreter: &str, code: &str) -> Result<ExecutionHandle, CodeExecuteError> {
+    /// Run a shell command in the given directory and wait for it to finish, returning its output.
+    ///
+    /// The command, along with anything it started, is killed if it's still running after the given number of
+    /// milliseconds, or after a minute if none is given.
+    pub(crate) fn run_command(
+        command: &str,
+        directory: &Path,
+        timeout_ms: Option<u64>,
+    ) -> Result<String, CodeExecuteError> {
+        let mut shell = process::Command::new("/usr/bin/env");
+        shell.args(["sh", "-c", command]).current_dir(directory);
+        // Like for code, the command runs in its own group so whatever it starts gets killed along with it.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+        let process_handle = shell
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(CodeExecuteError::SpawnProcess)?;
+
+        let state: Arc<Mutex<ExecutionState>> = Default::default();
+        let killer = ProcessKiller { process_id: process_handle.id(), stop_command: None, state: state.clone() };
+        let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_COMMAND_TIMEOUT_MS));
+        let (finished_sender, finished_receiver) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = finished_receiver.recv_timeout(timeout) {
+                killer.kill(ProcessStatus::TimedOut);
+            }
+        });
+        let output = process_handle.wait_with_output().map_err(CodeExecuteError::SpawnProcess);
+        {
+            let mut state = state.lock().unwrap();
+            if matches!(state.status, ProcessStatus::TimedOut) {
+                return Err(CodeExecuteError::TimedOut);
+            }
+            // The process is gone so the killer must not touch its group anymore.
+            state.status = ProcessStatus::Success;
+        }
+        drop(finished_sender);
+        let output = output?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(CodeExecuteError::CommandFailed(output.status.to_string(), error));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn execute_shell(
+        interpreter: &str,
+        code: &str,
+        config: &ExecutionConfig,
+        stdin: Option<String>,
+    ) -> Result<ExecutionHandle, CodeExecuteError> {
         let mut output_file = NamedTempFile::new().map_err(CodeExecuteError::TempFile)?;
         output_file.write_all(code.as_bytes()).map_err(CodeExecuteError::TempFile)?;
         output_file.flush().map_err(CodeExecuteError::TempFile)?;
-        let process_handle = process::Command::new("/usr/bin/env")
-            .arg(interpreter)
-            .arg(output_file.path())
-            .stdin(Stdio::null())
+        let mut command = process::Command::new("/usr/bin/env");
+        command.arg(interpreter).arg(output_file.path());
+        Self::spawn(command, ScratchFiles::File(output_file), config, stdin, None)
+    }
+
+    // The file code in a compiled language is written into and the command that compiles it into `main`.
+    fn compile_command<'a>(language: &CodeLanguage, config: &'a ExecutionConfig) -> Option<(&'static str, &'a str)> {
+        let (file_name, command, default_command) = match language {
+            CodeLanguage::Rust => ("main.rs", &config.rust, RUST_COMMAND),
+            CodeLanguage::C => ("main.c", &config.c, C_COMMAND),
+            CodeLanguage::Cpp => ("main.cpp", &config.cpp, CPP_COMMAND),
+            CodeLanguage::Go => ("main.go", &config.go, GO_COMMAND),
+            _ => return None,
+        };
+        Some((file_name, command.as_deref().unwrap_or(default_command)))
+    }
+
+    // Writes the code into a file in a temporary directory and runs a script in that directory.
+    fn execute_in_directory(
+        backend: &Backend,
+        file_name: &str,
+        script: &str,
+        code: &str,
+        config: &ExecutionConfig,
+        stdin: Option<String>,
+    ) -> Result<ExecutionHandle, CodeExecuteError> {
+        let directory = TempDir::new().map_err(CodeExecuteError::TempFile)?;
+        fs::write(directory.path().join(file_name), code).map_err(CodeExecuteError::TempFile)?;
+        let command = backend.command(script, directory.path(), file_name, code);
+        let stop_command = backend.stop_command(directory.path());
+        Self::spawn(command, ScratchFiles::Directory(directory), config, stdin, stop_command)
+    }
+
+    fn snippet_file_name(executor: &ExecutorConfig) -> String {
+        match &executor.extension {
+            Some(extension) => format!("snippet.{extension}"),
+            None => "snippet".into(),
+        }
+    }
+
+    fn spawn(
+        mut command: process::Command,
+        files: ScratchFiles,
+        config: &ExecutionConfig,
+        stdin: Option<String>,
+        stop_command: Option<Vec<String>>,
+    ) -> Result<ExecutionHandle, CodeExecuteError> {
+        // Code often starts processes of its own so it runs in its own group, which is what gets killed.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let stdin_mode = if stdin.is_some() { Stdio::piped() } else { Stdio::null() };
+        let mut process_handle = command
+            .stdin(stdin_mode)
             .stdout(Stdio::piped())
             .stderr(Stdio::null())
             .spawn()
             .map_err(CodeExecuteError::SpawnProcess)?;
+        if let (Some(input), Some(mut pipe)) = (stdin, process_handle.stdin.take()) {
+            // Code may not read all of it before writing its output so this can't block the caller.
+            // The pipe is closed once it's all written, which is how the code knows there's no more.
+            thread::spawn(move || {
+                let _ = pipe.write_all(input.as_bytes());
+            });
+        }
 
         let state: Arc<Mutex<ExecutionState>> = Default::default();
-        let reader_handle = ProcessReader::spawn(process_handle, state.clone(), output_file);
-        let handle = ExecutionHandle { state, reader_handle };
+        let killer = ProcessKiller { process_id: process_handle.id(), stop_command, state: state.clone() };
+        let (finished_sender, finished_receiver) = mpsc::channel();
+        let reader_handle = ProcessReader::spawn(process_handle, state.clone(), files, finished_sender);
+        if let Some(timeout) = config.timeout_ms {
+            let killer = killer.clone();
+            let timeout = Duration::from_millis(timeout);
+            // The reader drops its end of the channel when the process finishes, which wakes this up early.
+            thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = finished_receiver.recv_timeout(timeout) {
+                    killer.kill(ProcessStatus::TimedOut);
+                }
+            });
+        }
+        let handle = ExecutionHandle { state, reader_handle, killer };
         Ok(handle)
     }
 }
 
+/// Keeps the shell sessions code blocks run in, by shell and name.
+///
+/// Every code block runs in a process of its own, so sessions keep the exported environment variables
+/// and the working directory the code in them leaves behind and restores them before running more
+/// code in them. Variables code assigns are exported automatically so they're kept too, but nothing
+/// else is, like functions, aliases, or shell options.
+///
+/// The environment is saved in the syntax of the shell that ran the code, so code in different shells
+/// never shares a session even if it uses the same name.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SessionManager {
+    sessions: Rc<RefCell<HashMap<SessionKey, Rc<TempDir>>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct SessionKey {
+    interpreter: String,
+    name: String,
+}
+
+impl SessionManager {
+    /// Execute shell code in the session with the given name, starting it if it's the first code to run in it.
+    pub(crate) fn execute(
+        &self,
+        name: &str,
+        code: &Code,
+        config: &ExecutionConfig,
+        stdin: Option<String>,
+    ) -> Result<ExecutionHandle, CodeExecuteError> {
+        let CodeLanguage::Shell(interpreter) = &code.language else {
+            return Err(CodeExecuteError::UnsupportedExecution);
+        };
+        if !code.attributes.execute {
+            return Err(CodeExecuteError::NotExecutableCode);
+        }
+        let directory = self.directory(interpreter, name)?;
+        let script = Self::script(&directory.path().to_string_lossy(), &code.executable_contents());
+        CodeExecuter::execute_shell(interpreter, &script, config, stdin)
+    }
+
+    fn directory(&self, interpreter: &str, name: &str) -> Result<Rc<TempDir>, CodeExecuteError> {
+        let mut sessions = self.sessions.borrow_mut();
+        let key = SessionKey { interpreter: interpreter.into(), name: name.into() };
+        if let Some(directory) = sessions.get(&key) {
+            return Ok(directory.clone());
+        }
+        let directory = Rc::new(TempDir::new().map_err(CodeExecuteError::TempFile)?);
+        sessions.insert(key, directory.clone());
+        Ok(directory)
+    }
+
+    // Wraps code so it starts off where the previous code in the session left and saves where it leaves.
+    fn script(directory: &str, code: &str) -> String {
+        let environment = shell_quote(&format!("{directory}/environment"));
+        let cwd = shell_quote(&format!("{directory}/cwd"));
+        // This runs on exit so code that calls `exit` is saved too, but code that's killed isn't.
+        let save = shell_quote(&format!("export -p > {environment}; pwd > {cwd}"));
+        format!(
+            "[ -f {environment} ] && . {environment}\n\
+            [ -f {cwd} ] && cd \"$(cat {cwd})\"\n\
+            trap {save} EXIT\n\
+            set -a\n\
+            {code}"
+        )
+    }
+}
+
 /// An error during the execution of some code.
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum CodeExecuteError {
@@ -61,14 +390,26 @@ pub(crate) enum CodeExecuteError {
 
     #[error("error spawning process: {0}")]
     SpawnProcess(io::Error),
+
+    #[error("command failed with {0}: {1}")]
+    CommandFailed(String, String),
+
+    #[error("code finished with error: {0}")]
+    ExecutionFailed(String),
+
+    #[error("code took too long to run")]
+    TimedOut,
+
+    #[error("host '{0}' is not configured")]
+    UnknownHost(String),
 }
 
 /// A handle for the execution of a piece of code.
 #[derive(Debug)]
 pub(crate) struct ExecutionHandle {
     state: Arc<Mutex<ExecutionState>>,
-    #[allow(dead_code)]
     reader_handle: thread::JoinHandle<()>,
+    killer: ProcessKiller,
 }
 
 impl ExecutionHandle {
@@ -76,6 +417,181 @@ impl ExecutionHandle {
     pub(crate) fn state(&self) -> ExecutionState {
         self.state.lock().unwrap().clone()
     }
+
+    /// Kill the process if it's still running.
+    pub(crate) fn cancel(&self) {
+        self.killer.kill(ProcessStatus::Cancelled);
+    }
+
+    /// Wait for the process to finish and get its final state.
+    pub(crate) fn wait(self) -> ExecutionState {
+        // The reader only finishes once the process does, after the final state is set.
+        let _ = self.reader_handle.join();
+        self.state.lock().unwrap().clone()
+    }
+}
+
+/// Where code runs.
+#[derive(Debug, PartialEq)]
+enum Backend<'a> {
+    /// Directly in this machine.
+    Host,
+
+    /// In a throwaway container, so whatever the code needs doesn't have to be installed in this machine.
+    Container { runtime: &'a str, image: &'a str },
+
+    /// In another machine over SSH.
+    Remote(&'a HostConfig),
+}
+
+impl<'a> Backend<'a> {
+    fn new(code: &'a Code, config: &'a ExecutionConfig) -> Result<Self, CodeExecuteError> {
+        if let Some(host) = &code.attributes.host {
+            let host = config.hosts.get(host).ok_or_else(|| CodeExecuteError::UnknownHost(host.clone()))?;
+            return Ok(Self::Remote(host));
+        }
+        let backend = match &code.attributes.container {
+            Some(image) => {
+                let runtime = config.container_runtime.as_deref().unwrap_or(DEFAULT_CONTAINER_RUNTIME);
+                Self::Container { runtime, image }
+            }
+            None => Self::Host,
+        };
+        Ok(backend)
+    }
+
+    /// Build the command that runs a shell script in a directory that contains a file with the code.
+    fn command(&self, script: &str, directory: &Path, file_name: &str, code: &str) -> process::Command {
+        let mut command = process::Command::new("/usr/bin/env");
+        match self {
+            Self::Host => {
+                command.current_dir(directory);
+            }
+            Self::Container { runtime, image } => {
+                let volume = format!("{}:{CONTAINER_DIRECTORY}", directory.display());
+                let name = Self::run_name(directory);
+                // Interactive containers get the input written into the code's stdin, if any.
+                command.args([runtime, "run", "--rm", "--interactive", "--name", &name, "--volume", &volume]);
+                // The directory belongs to whoever is presenting, so running the code as them means the
+                // files it writes there don't end up belonging to root.
+                #[cfg(unix)]
+                if let Ok(metadata) = fs::metadata(directory) {
+                    use std::os::unix::fs::MetadataExt;
+                    command.args(["--user", &format!("{}:{}", metadata.uid(), metadata.gid())]);
+                }
+                command.args(["--workdir", CONTAINER_DIRECTORY, image]);
+            }
+            Self::Remote(host) => {
+                // Batch mode makes ssh fail rather than prompting for passwords in the middle of the presentation.
+                command.args(["ssh", "-o", "BatchMode=yes"]).args(&host.ssh_args).arg(&host.address);
+                let script = Self::remote_script(script, directory, file_name, code);
+                command.arg(format!("sh -c {}", shell_quote(&script)));
+                return command;
+            }
+        };
+        command.args(["sh", "-c", script]);
+        command
+    }
+
+    /// Build the command that stops code running in a directory, if killing the process that runs it isn't enough.
+    fn stop_command(&self, directory: &Path) -> Option<Vec<String>> {
+        match self {
+            // Killing the runtime's client leaves the container running.
+            Self::Container { runtime, .. } => {
+                Some(vec![runtime.to_string(), "kill".into(), Self::run_name(directory)])
+            }
+            // Killing ssh leaves the code running in the remote host so its process group is killed there.
+            Self::Remote(host) => {
+                let script = format!(
+                    "{}\n\
+                    kill -KILL \"-$(cat \"$directory.pgid\")\"\n\
+                    rm -rf \"$directory\" \"$directory.pgid\"\n",
+                    Self::remote_directory(directory)
+                );
+                let mut command: Vec<String> = vec!["ssh".into(), "-o".into(), "BatchMode=yes".into()];
+                command.extend(host.ssh_args.iter().cloned());
+                command.extend([host.address.clone(), format!("sh -c {}", shell_quote(&script))]);
+                Some(command)
+            }
+            Self::Host => None,
+        }
+    }
+
+    // Every directory is a new one so it makes for a unique name for the container or remote directory code
+    // runs in.
+    fn run_name(directory: &Path) -> String {
+        let name = directory.file_name().unwrap_or_default().to_string_lossy();
+        format!("presenterm-{}", name.trim_start_matches('.'))
+    }
+
+    // Set the `directory` variable to where the code in the given directory runs in remote hosts.
+    fn remote_directory(directory: &Path) -> String {
+        format!("directory=\"$HOME/{REMOTE_DIRECTORY}/\"{}", shell_quote(&Self::run_name(directory)))
+    }
+
+    // The remote host can't see this machine's directory so the code is written into one in it first. The
+    // process group the code runs in is written next to it so it can be killed from another connection.
+    fn remote_script(script: &str, directory: &Path, file_name: &str, code: &str) -> String {
+        let code = code.trim_end_matches('\n');
+        let script = shell_quote(script);
+        let file_name = shell_quote(file_name);
+        format!(
+            "{}\n\
+            mkdir -p \"$directory\" && cd \"$directory\" || exit 1\n\
+            ps -o pgid= -p $$ | tr -d ' ' > \"$directory.pgid\"\n\
+            cat > {file_name} <<'{HEREDOC_DELIMITER}'\n{code}\n{HEREDOC_DELIMITER}\n\
+            sh -c {script}\n\
+            status=$?\n\
+            cd / && rm -rf \"$directory\" \"$directory.pgid\"\n\
+            exit $status\n",
+            Self::remote_directory(directory)
+        )
+    }
+}
+
+// Quote a string so a POSIX shell sees it as a single word.
+fn shell_quote(input: &str) -> String {
+    format!("'{}'", input.replace('\'', r"'\''"))
+}
+
+/// Kills a running process along with anything it started.
+#[derive(Clone, Debug)]
+struct ProcessKiller {
+    process_id: u32,
+    stop_command: Option<Vec<String>>,
+    state: Arc<Mutex<ExecutionState>>,
+}
+
+impl ProcessKiller {
+    /// Kill the process, which finishes with the given status, unless it already finished.
+    fn kill(&self, status: ProcessStatus) {
+        let mut state = self.state.lock().unwrap();
+        if state.status.is_finished() {
+            return;
+        }
+        state.status = status;
+        // The process is the leader of its own group so this kills every process in it. This can only
+        // fail if the group is gone already, which is fine.
+        #[cfg(unix)]
+        unsafe {
+            libc::killpg(self.process_id as libc::pid_t, libc::SIGKILL);
+        }
+        if let Some(stop_command) = &self.stop_command {
+            let _ = process::Command::new("/usr/bin/env")
+                .args(stop_command)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+/// The files a process needs, which are deleted once they're dropped.
+#[allow(dead_code)]
+enum ScratchFiles {
+    File(NamedTempFile),
+    Directory(TempDir),
 }
 
 /// Consumes the output of a process and stores it in a shared state.
@@ -83,16 +599,19 @@ struct ProcessReader {
     handle: process::Child,
     state: Arc<Mutex<ExecutionState>>,
     #[allow(dead_code)]
-    file_handle: NamedTempFile,
+    files: ScratchFiles,
+    #[allow(dead_code)]
+    finished: mpsc::Sender<()>,
 }
 
 impl ProcessReader {
     fn spawn(
         handle: process::Child,
         state: Arc<Mutex<ExecutionState>>,
-        file_handle: NamedTempFile,
+        files: ScratchFiles,
+        finished: mpsc::Sender<()>,
     ) -> thread::JoinHandle<()> {
-        let reader = Self { handle, state, file_handle };
+        let reader = Self { handle, state, files, finished };
         thread::spawn(|| reader.run())
     }
 
@@ -100,15 +619,18 @@ impl ProcessReader {
         let stdout = self.handle.stdout.take().expect("no stdout");
         let stdout = BufReader::new(stdout);
         let _ = Self::process_output(self.state.clone(), stdout);
-        let success = match self.handle.try_wait() {
-            Ok(Some(code)) => code.success(),
-            _ => false,
-        };
-        let status = match success {
+        // The process can close its stdout a bit before it exits so wait for it rather than guessing.
+        let exit_status = self.handle.wait().ok();
+        let status = match exit_status.is_some_and(|status| status.success()) {
             true => ProcessStatus::Success,
             false => ProcessStatus::Failure,
         };
-        self.state.lock().unwrap().status = status;
+        let mut state = self.state.lock().unwrap();
+        state.exit_code = exit_status.and_then(|status| status.code());
+        // Processes that were killed keep the reason why.
+        if !state.status.is_finished() {
+            state.status = status;
+        }
     }
 
     fn process_output(state: Arc<Mutex<ExecutionState>>, stdout: BufReader<ChildStdout>) -> io::Result<()> {
@@ -126,6 +648,9 @@ impl ProcessReader {
 pub(crate) struct ExecutionState {
     pub(crate) output: Vec<String>,
     pub(crate) status: ProcessStatus,
+
+    /// The code the process exited with, which processes killed by a signal don't have.
+    pub(crate) exit_code: Option<i32>,
 }
 
 /// The status of a process.
@@ -135,12 +660,14 @@ pub(crate) enum ProcessStatus {
     Running,
     Success,
     Failure,
+    TimedOut,
+    Cancelled,
 }
 
 impl ProcessStatus {
     /// Check whether the underlying process is finished.
     pub(crate) fn is_finished(&self) -> bool {
-        matches!(self, ProcessStatus::Success | ProcessStatus::Failure)
+        !matches!(self, ProcessStatus::Running)
     }
 }
 
@@ -148,6 +675,7 @@ impl ProcessStatus {
 mod test {
     use super::*;
     use crate::markdown::elements::CodeAttributes;
+    use rstest::rstest;
 
     #[test]
     fn shell_code_execution() {
@@ -158,29 +686,390 @@ echo 'bye'"
         let code = Code {
             contents,
             language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
             attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
         };
-        let handle = CodeExecuter::execute(&code).expect("execution failed");
-        let state = loop {
-            let state = handle.state();
-            if state.status.is_finished() {
-                break state;
-            }
-        };
+        let state = run(&code, &Default::default());
 
         let expected_lines = vec!["hello world", "bye"];
         assert_eq!(state.output, expected_lines);
     }
 
+    fn run(code: &Code, config: &ExecutionConfig) -> ExecutionState {
+        CodeExecuter::execute(code, config).expect("execution failed").wait()
+    }
+
+    fn c_code(contents: &str) -> Code {
+        Code {
+            contents: contents.into(),
+            language: CodeLanguage::C,
+            language_token: "c".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        }
+    }
+
+    #[test]
+    fn compiled_code_execution() {
+        let code = c_code("#include <stdio.h>\nint main() { puts(\"hello world\"); return 0; }\n");
+        let config = ExecutionConfig { c: Some("echo compiling; cc -o main main.c".into()), ..Default::default() };
+        let state = run(&code, &config);
+        assert_eq!(state.output, &["compiling", "hello world"]);
+    }
+
+    #[test]
+    fn compilation_errors() {
+        let code = c_code("int main() { return potato; }\n");
+        let state = run(&code, &Default::default());
+        assert!(state.output.iter().any(|line| line.contains("potato")), "{:?}", state.output);
+    }
+
+    #[test]
+    fn hidden_lines() {
+        let code = Code {
+            contents: "#~ echo setup\necho hi\n".into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        assert_eq!(run(&code, &Default::default()).output, &["setup", "hi"]);
+    }
+
+    #[test]
+    fn execute_sync() {
+        let code = |contents: &str| Code {
+            contents: contents.into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes: CodeAttributes { execute: true, replace_with_output: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        let output = CodeExecuter::execute_sync(&code("echo hi; echo bye"), &Default::default(), None).expect("failed");
+        assert_eq!(output, &["hi", "bye"]);
+
+        let error = CodeExecuter::execute_sync(&code("echo oops; exit 1"), &Default::default(), None).unwrap_err();
+        assert!(matches!(&error, CodeExecuteError::ExecutionFailed(output) if output == "oops"), "{error}");
+    }
+
+    #[test]
+    fn stdin() {
+        let code = Code {
+            contents: "while read line; do echo \"> $line\"; done".into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        let handle = CodeExecuter::execute_with_stdin(&code, &Default::default(), Some("hello\nbye\n".into()))
+            .expect("execution failed");
+        assert_eq!(handle.wait().output, &["> hello", "> bye"]);
+    }
+
+    #[rstest]
+    #[case::sh("sh")]
+    #[case::bash("bash")]
+    fn sessions(#[case] interpreter: &str) {
+        let sessions = SessionManager::default();
+        let run_in = |interpreter: &str, session: &str, contents: &str| {
+            let code = Code {
+                contents: contents.into(),
+                language: CodeLanguage::Shell(interpreter.into()),
+                language_token: interpreter.into(),
+                attributes: CodeAttributes { execute: true, ..Default::default() },
+                source_position: Default::default(),
+            };
+            let handle = sessions.execute(session, &code, &Default::default(), None).expect("execution failed");
+            handle.wait().output
+        };
+        let run = |session: &str, contents: &str| run_in(interpreter, session, contents);
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let directory = directory.path().to_string_lossy();
+        // Code that exits early still leaves its state behind.
+        assert!(run("demo", &format!("NAME=bob\ncd {directory}\nexit 0\necho unreachable")).is_empty());
+        assert_eq!(run("demo", "echo \"$NAME\"\npwd"), &["bob", directory.as_ref()]);
+        assert_eq!(run("other", "echo \"[$NAME]\""), &["[]"]);
+        // Every shell saves the environment its own way so they don't share sessions.
+        let other_interpreter = if interpreter == "sh" { "bash" } else { "sh" };
+        assert_eq!(run_in(other_interpreter, "demo", "echo \"[$NAME]\""), &["[]"]);
+    }
+
+    #[test]
+    fn timeout() {
+        let code = Code {
+            contents: "echo started; sleep 30 & wait".into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        let config = ExecutionConfig { timeout_ms: Some(100), ..Default::default() };
+        let state = run(&code, &config);
+        assert!(matches!(state.status, ProcessStatus::TimedOut), "{:?}", state.status);
+        assert_eq!(state.output, &["started"]);
+
+        let error = CodeExecuter::execute_sync(&code, &config, None).unwrap_err();
+        assert!(matches!(error, CodeExecuteError::TimedOut), "{error}");
+    }
+
+    #[test]
+    fn cancel() {
+        let code = Code {
+            contents: "sleep 30".into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        let handle = CodeExecuter::execute(&code, &Default::default()).expect("execution failed");
+        handle.cancel();
+        assert!(matches!(handle.state().status, ProcessStatus::Cancelled));
+        assert!(matches!(handle.wait().status, ProcessStatus::Cancelled));
+    }
+
+    #[rstest]
+    #[case::shell(CodeLanguage::Shell("bash".into()), Default::default(), &["$ cd /tmp", "$ ls"])]
+    #[case::compiled(CodeLanguage::Go, Default::default(), &["$ go build -o main main.go && ./main"])]
+    #[case::executor(
+        CodeLanguage::Unknown("ruby".into()),
+        ExecutionConfig {
+            executors: [("ruby".into(), ExecutorConfig { command: "ruby {file}".into(), extension: Some("rb".into()) })]
+                .into(),
+            ..Default::default()
+        },
+        &["$ ruby snippet.rb"]
+    )]
+    fn dry_run(#[case] language: CodeLanguage, #[case] config: ExecutionConfig, #[case] expected: &[&str]) {
+        let attributes = CodeAttributes { execute: true, ..Default::default() };
+        let code = Code {
+            contents: "#~ cd /tmp\nls\n".into(),
+            language_token: language.name().into(),
+            language,
+            attributes,
+            source_position: Default::default(),
+        };
+        assert_eq!(CodeExecuter::dry_run(&code, &config).expect("dry run failed"), expected);
+    }
+
+    #[rstest]
+    #[case::container(Some("alpine"), None, &["# in container alpine", "$ ls"])]
+    #[case::host(None, Some("demo"), &["# in host demo", "$ ls"])]
+    fn dry_run_elsewhere(#[case] container: Option<&str>, #[case] host: Option<&str>, #[case] expected: &[&str]) {
+        let attributes = CodeAttributes {
+            execute: true,
+            container: container.map(String::from),
+            host: host.map(String::from),
+            ..Default::default()
+        };
+        let code = Code {
+            contents: "ls\n".into(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+        let host = HostConfig { address: "me@demo-box".into(), ssh_args: vec![] };
+        let config = ExecutionConfig { hosts: [("demo".into(), host)].into(), ..Default::default() };
+        assert_eq!(CodeExecuter::dry_run(&code, &config).expect("dry run failed"), expected);
+    }
+
+    #[rstest]
+    #[case::host(None, None, vec!["sh", "-c", "ls"])]
+    #[case::docker(
+        Some("python:3.12"),
+        None,
+        ["docker", "run", "--rm", "--interactive", "--name", "presenterm-code", "--volume", "/tmp/code:/presenterm"]
+            .into_iter()
+            .chain(["--workdir", "/presenterm", "python:3.12", "sh", "-c", "ls"])
+            .collect::<Vec<_>>()
+    )]
+    #[case::podman(
+        Some("alpine"),
+        Some("podman"),
+        ["podman", "run", "--rm", "--interactive", "--name", "presenterm-code", "--volume", "/tmp/code:/presenterm"]
+            .into_iter()
+            .chain(["--workdir", "/presenterm", "alpine", "sh", "-c", "ls"])
+            .collect::<Vec<_>>()
+    )]
+    fn backends(#[case] container: Option<&str>, #[case] runtime: Option<&str>, #[case] expected: Vec<&str>) {
+        let attributes = CodeAttributes { execute: true, container: container.map(String::from), ..Default::default() };
+        let code = Code {
+            contents: String::new(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+        let config = ExecutionConfig { container_runtime: runtime.map(String::from), ..Default::default() };
+        let command = Backend::new(&code, &config).unwrap().command("ls", Path::new("/tmp/code"), "snippet", "");
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_str().unwrap()).collect();
+        assert_eq!(args, expected);
+        let directory = command.get_current_dir();
+        assert_eq!(directory, container.is_none().then_some(Path::new("/tmp/code")));
+    }
+
+    #[test]
+    fn container_backend() {
+        let attributes = CodeAttributes { execute: true, container: Some("alpine".into()), ..Default::default() };
+        let code = Code {
+            contents: String::new(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+        let config = ExecutionConfig::default();
+        let backend = Backend::new(&code, &config).unwrap();
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let file_name = directory.path().file_name().unwrap().to_str().unwrap();
+        let name = format!("presenterm-{}", file_name.trim_start_matches('.'));
+        assert_eq!(backend.stop_command(directory.path()), Some(vec!["docker".into(), "kill".into(), name]));
+
+        // The code runs as whoever owns the directory it's in.
+        let command = backend.command("ls", directory.path(), "snippet", "");
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_str().unwrap()).collect();
+        let user = args.iter().position(|arg| *arg == "--user").map(|index| args[index + 1]).expect("no user");
+        let output = CodeExecuter::run_command("echo $(id -u):$(id -g)", Path::new("/"), None).expect("id failed");
+        assert_eq!(user, output.trim());
+        assert_eq!(Backend::Host.stop_command(directory.path()), None);
+    }
+
+    #[test]
+    fn remote_backend() {
+        let attributes = CodeAttributes { execute: true, host: Some("demo".into()), ..Default::default() };
+        let code = Code {
+            contents: String::new(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+        assert!(matches!(Backend::new(&code, &Default::default()), Err(CodeExecuteError::UnknownHost(_))));
+
+        let host = HostConfig { address: "me@demo-box".into(), ssh_args: vec!["-p".into(), "2222".into()] };
+        let config = ExecutionConfig { hosts: [("demo".into(), host)].into(), ..Default::default() };
+        let backend = Backend::new(&code, &config).unwrap();
+        let contents = "echo 'it'\\''s' here\ncat snippet\n";
+        let command = backend.command("sh snippet", Path::new("/tmp/code"), "snippet", contents);
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_str().unwrap()).collect();
+        assert_eq!(&args[..6], &["ssh", "-o", "BatchMode=yes", "-p", "2222", "me@demo-box"]);
+        assert_eq!(args.len(), 7);
+
+        // Run what the remote host would locally to make sure it makes it there intact.
+        let home = TempDir::new().unwrap();
+        let output = process::Command::new("sh").args(["-c", args[6]]).env("HOME", home.path()).output().unwrap();
+        assert!(output.status.success(), "remote script failed");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("it's here\n{contents}"));
+        assert_eq!(fs::read_dir(home.path().join(REMOTE_DIRECTORY)).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remote_backend_stop() {
+        use std::os::unix::process::CommandExt;
+
+        let attributes = CodeAttributes { execute: true, host: Some("demo".into()), ..Default::default() };
+        let code = Code {
+            contents: String::new(),
+            language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
+            attributes,
+            source_position: Default::default(),
+        };
+        let host = HostConfig { address: "me@demo-box".into(), ssh_args: vec![] };
+        let config = ExecutionConfig { hosts: [("demo".into(), host)].into(), ..Default::default() };
+        let backend = Backend::new(&code, &config).unwrap();
+        let directory = Path::new("/tmp/code");
+        let command = backend.command("sleep 30", directory, "snippet", "");
+        let stop_command = backend.stop_command(directory).expect("no stop command");
+        assert_eq!(&stop_command[..4], &["ssh", "-o", "BatchMode=yes", "me@demo-box"]);
+
+        // The remote session is its own process group, like the one sshd starts.
+        let home = TempDir::new().unwrap();
+        let script = command.get_args().last().unwrap();
+        let mut remote = process::Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .env("HOME", home.path())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let process_group = home.path().join(REMOTE_DIRECTORY).join("presenterm-code.pgid");
+        while fs::read_to_string(&process_group).map(|id| id.trim().is_empty()).unwrap_or(true) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let status = process::Command::new("sh").args(["-c", &stop_command[4]]).env("HOME", home.path()).status();
+        assert!(status.unwrap().success(), "stop script failed");
+        assert!(!remote.wait().unwrap().success());
+        assert_eq!(fs::read_dir(home.path().join(REMOTE_DIRECTORY)).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn run_command() {
+        let output = CodeExecuter::run_command("echo hello; echo bye", Path::new("/"), None).expect("command failed");
+        assert_eq!(output, "hello\nbye\n");
+
+        let error =
+            CodeExecuter::run_command("echo oops >&2; exit 3", Path::new("/"), None).expect_err("command succeeded");
+        assert!(matches!(&error, CodeExecuteError::CommandFailed(_, message) if message == "oops"), "{error}");
+    }
+
+    #[test]
+    fn run_command_timeout() {
+        let started = Instant::now();
+        // The background process keeps the output open so this only finishes once its whole group is killed.
+        let error =
+            CodeExecuter::run_command("sleep 30 & wait", Path::new("/"), Some(100)).expect_err("command finished");
+        assert!(matches!(error, CodeExecuteError::TimedOut), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn executor() {
+        let code = Code {
+            contents: "hello\n".into(),
+            language: CodeLanguage::Unknown("potato".into()),
+            language_token: "potato".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        assert!(CodeExecuter::execute(&code, &Default::default()).is_err());
+
+        let executor = ExecutorConfig { command: "echo {file}; cat {file}".into(), extension: Some("txt".into()) };
+        let config = ExecutionConfig { executors: [("potato".into(), executor)].into(), ..Default::default() };
+        assert!(CodeExecuter::supports(&code.language, &config));
+        assert_eq!(run(&code, &config).output, &["snippet.txt", "hello"]);
+    }
+
+    #[test]
+    fn executor_alias() {
+        let code = Code {
+            contents: "console.log('hi')\n".into(),
+            language: CodeLanguage::TypeScript,
+            language_token: "typescript".into(),
+            attributes: CodeAttributes { execute: true, ..Default::default() },
+            source_position: Default::default(),
+        };
+        assert!(!CodeExecuter::supports(&code.language, &Default::default()));
+
+        let executor = ExecutorConfig { command: "echo {file}".into(), extension: Some("ts".into()) };
+        let config = ExecutionConfig { executors: [("ts".into(), executor)].into(), ..Default::default() };
+        assert!(CodeExecuter::supports(&code.language, &config));
+        assert_eq!(run(&code, &config).output, &["snippet.ts"]);
+    }
+
     #[test]
     fn non_executable_code_cant_be_executed() {
         let contents = String::new();
         let code = Code {
             contents,
             language: CodeLanguage::Shell("sh".into()),
+            language_token: "sh".into(),
             attributes: CodeAttributes { execute: false, ..Default::default() },
+            source_position: Default::default(),
         };
-        let result = CodeExecuter::execute(&code);
+        let result = CodeExecuter::execute(&code, &Default::default());
         assert!(result.is_err());
     }
 }
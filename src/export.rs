@@ -1,25 +1,55 @@
 use crate::{
-    builder::{BuildError, PresentationBuilder, PresentationBuilderOptions},
+    builder::{BuildError, CommandOutputPolicy, PresentationBuilder, PresentationBuilderOptions},
+    config::{ExecutionConfig, PlaceholderConfig},
     markdown::{elements::MarkdownElement, parse::ParseError},
-    presentation::Presentation,
+    options::ExternalOptions,
+    presentation::{ExportProfile, Presentation, RenderOnDemandState},
+    render::{draw::RenderError, html::HtmlRenderer, properties::WindowSize, raster::SlideRasterizer},
+    validate::DEFAULT_SNIPPET_TIMEOUT_MS,
     CodeHighlighter, MarkdownParser, PresentationTheme, Resources,
 };
+use image::ImageError;
 use serde::Serialize;
 use std::{
     env, fs,
     io::{self, Write},
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    thread::sleep,
+    time::Duration,
 };
 
 const COMMAND: &str = "presenterm-export";
 
-/// Allows exporting presentations into PDF.
+/// The environment variable the `--profile` parameter is forwarded to the PDF exporter through.
+pub const PROFILE_ENV: &str = "PRESENTERM_PROFILE";
+
+/// The environment variable the `--rev` parameter is forwarded to the PDF exporter through.
+pub const REVISION_ENV: &str = "PRESENTERM_REV";
+
+/// The environment variable the `--allow-command-output` parameter is forwarded to the PDF exporter through.
+pub const COMMAND_OUTPUT_ENV: &str = "PRESENTERM_ALLOW_COMMAND_OUTPUT";
+
+/// The dimensions used when exporting images or HTML if the presentation doesn't have an export profile.
+const DEFAULT_IMAGE_DIMENSIONS: (u16, u16) = (100, 30);
+
+/// The name of the file HTML exports are written into.
+const HTML_FILE_NAME: &str = "index.html";
+
+/// How often a running code block is checked for completion when checking its expected output.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Allows exporting presentations into PDF, images, and HTML.
 pub struct Exporter<'a> {
     parser: MarkdownParser<'a>,
     default_theme: &'a PresentationTheme,
     default_highlighter: CodeHighlighter,
     resources: Resources,
+    options: ExternalOptions,
+    command_output: CommandOutputPolicy,
+    placeholders: PlaceholderConfig,
+    execution: ExecutionConfig,
+    check_expected_output: bool,
 }
 
 impl<'a> Exporter<'a> {
@@ -30,7 +60,51 @@ impl<'a> Exporter<'a> {
         default_highlighter: CodeHighlighter,
         resources: Resources,
     ) -> Self {
-        Self { parser, default_theme, default_highlighter, resources }
+        Self {
+            parser,
+            default_theme,
+            default_highlighter,
+            resources,
+            options: Default::default(),
+            command_output: Default::default(),
+            placeholders: Default::default(),
+            execution: Default::default(),
+            check_expected_output: false,
+        }
+    }
+
+    /// Set the presentation options given via command line parameters and the config file.
+    pub fn with_options(mut self, options: ExternalOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set whether to run the commands in `command_output` comments.
+    pub fn with_command_output(mut self, allowed: bool) -> Self {
+        self.command_output = CommandOutputPolicy::from_allowed(allowed);
+        self
+    }
+
+    /// Set the placeholders that can be used on top of the built in ones.
+    pub fn with_placeholders(mut self, placeholders: PlaceholderConfig) -> Self {
+        self.placeholders = placeholders;
+        self
+    }
+
+    /// Set the configuration used to run code blocks while building the presentation, like the ones
+    /// that are replaced with their output.
+    pub fn with_execution(mut self, config: ExecutionConfig) -> Self {
+        self.execution = config;
+        self
+    }
+
+    /// Set whether to run the code blocks followed by an `expected_output` block, and fail the export if
+    /// any of them doesn't produce it, before exporting.
+    ///
+    /// Code blocks are stopped after a minute unless a timeout is configured for them.
+    pub fn with_expected_output_check(mut self, enabled: bool) -> Self {
+        self.check_expected_output = enabled;
+        self
     }
 
     /// Export the given presentation into PDF.
@@ -38,13 +112,85 @@ impl<'a> Exporter<'a> {
     /// This uses a separate `presenterm-export` tool.
     pub fn export_pdf(&mut self, presentation_path: &Path) -> Result<(), ExportError> {
         let metadata = self.generate_metadata(presentation_path)?;
-        Self::execute_exporter(metadata).map_err(ExportError::InvokeExporter)?;
+        self.execute_exporter(metadata).map_err(ExportError::InvokeExporter)?;
         Ok(())
     }
 
+    /// Export every slide in the given presentation into a PNG image in the given directory.
+    ///
+    /// Images are named after the slide they contain, e.g. `slide-01.png`. If `every_chunk` is set,
+    /// one image is generated for every step in each slide instead, e.g. `slide-01-02.png`.
+    pub fn export_images(
+        &mut self,
+        presentation_path: &Path,
+        output_directory: &Path,
+        every_chunk: bool,
+    ) -> Result<(), ExportError> {
+        let content = self.resources.presentation(presentation_path).map_err(ExportError::ReadPresentation)?;
+        let elements = self.parser.parse(&content)?;
+        let mut presentation = self.build_presentation(elements)?;
+        self.check_expected_outputs(&presentation)?;
+        let (columns, rows) = match presentation.export_profile() {
+            Some(profile) => (profile.columns, profile.rows),
+            None => DEFAULT_IMAGE_DIMENSIONS,
+        };
+        let rasterizer = SlideRasterizer::new(columns, rows);
+        fs::create_dir_all(output_directory).map_err(ExportError::CreateDirectory)?;
+        loop {
+            let slide = presentation.current_slide_index() + 1;
+            let chunk = presentation.current_chunk() + 1;
+            let file_name = match every_chunk {
+                true => format!("slide-{slide:02}-{chunk:02}.png"),
+                false => format!("slide-{slide:02}.png"),
+            };
+            if every_chunk || presentation.is_current_slide_last_step() {
+                let image = rasterizer.rasterize(presentation.current_slide())?;
+                image.save(output_directory.join(file_name))?;
+            }
+            if !presentation.jump_next_slide() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Export the given presentation into a standalone HTML file in the given directory.
+    ///
+    /// Every slide is rendered as it looks once all of its steps are shown, one after the other,
+    /// into an `index.html` file.
+    pub fn export_html(&mut self, presentation_path: &Path, output_directory: &Path) -> Result<(), ExportError> {
+        let content = self.resources.presentation(presentation_path).map_err(ExportError::ReadPresentation)?;
+        let title = presentation_path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+        let html = self.build_html(&content, &title)?;
+        fs::create_dir_all(output_directory).map_err(ExportError::CreateDirectory)?;
+        fs::write(output_directory.join(HTML_FILE_NAME), html).map_err(ExportError::WriteHtml)?;
+        Ok(())
+    }
+
+    fn build_html(&mut self, content: &str, title: &str) -> Result<String, ExportError> {
+        let elements = self.parser.parse(content)?;
+        let mut presentation = self.build_presentation(elements)?;
+        self.check_expected_outputs(&presentation)?;
+        let (columns, rows) = match presentation.export_profile() {
+            Some(profile) => (profile.columns, profile.rows),
+            None => DEFAULT_IMAGE_DIMENSIONS,
+        };
+        let dimensions = WindowSize { rows, columns, width: 0, height: 0, has_pixels: false };
+        let mut slides = Vec::new();
+        loop {
+            if presentation.is_current_slide_last_step() {
+                slides.push(HtmlRenderer::new(dimensions.clone()).render_slide(presentation.current_slide()));
+            }
+            if !presentation.jump_next_slide() {
+                break;
+            }
+        }
+        Ok(HtmlRenderer::render_document(title, &slides))
+    }
+
     /// Generate the metadata for the given presentation.
     pub fn generate_metadata(&mut self, presentation_path: &Path) -> Result<ExportMetadata, ExportError> {
-        let content = fs::read_to_string(presentation_path).map_err(ExportError::ReadPresentation)?;
+        let content = self.resources.presentation(presentation_path).map_err(ExportError::ReadPresentation)?;
         let metadata = self.extract_metadata(&content, presentation_path)?;
         Ok(metadata)
     }
@@ -52,9 +198,62 @@ impl<'a> Exporter<'a> {
     /// Extract the metadata necessary to make an export.
     fn extract_metadata(&mut self, content: &str, path: &Path) -> Result<ExportMetadata, ExportError> {
         let elements = self.parser.parse(content)?;
-        let base_path = path.parent().expect("no parent").canonicalize().expect("canonicalize");
-        let images = Self::build_image_metadata(&elements, &base_path);
-        let options = PresentationBuilderOptions { allow_mutations: false };
+        let presentation = self.build_presentation(elements.clone())?;
+        self.check_expected_outputs(&presentation)?;
+        // Images are relative to the resources root, which is only known once the presentation is built.
+        let base_path = self.resources.resolve("").canonicalize().map_err(ExportError::ReadPresentation)?;
+        let images = self.build_image_metadata(&elements, &base_path);
+        let profile = presentation.export_profile().cloned();
+        let commands = Self::build_capture_commands(presentation);
+        let presentation_path = path.canonicalize().map_err(ExportError::ReadPresentation)?;
+        let metadata = ExportMetadata { commands, presentation_path, images, profile };
+        Ok(metadata)
+    }
+
+    // Code blocks followed by an `expected_output` block are run before exporting so what they show is still accurate.
+    fn check_expected_outputs(&self, presentation: &Presentation) -> Result<(), ExportError> {
+        if !self.check_expected_output {
+            return Ok(());
+        }
+        let mut mismatches = Vec::new();
+        for (slide_index, slide) in presentation.iter_slides().enumerate() {
+            for code_block in &slide.metadata().code_blocks {
+                let Some(execution) = code_block.execution.as_ref().filter(|execution| execution.has_expected_output())
+                else {
+                    continue;
+                };
+                // Blocks that already ran as another one's dependency don't start again.
+                execution.start_render();
+                while !matches!(execution.poll_state(), RenderOnDemandState::Rendered) {
+                    sleep(POLL_INTERVAL);
+                }
+                if let Some(differences) = execution.output_mismatch() {
+                    let (line, slide) = (code_block.line, slide_index + 1);
+                    mismatches.push(format!("code block at line {line} in slide {slide}: {differences}"));
+                }
+            }
+        }
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(ExportError::UnexpectedOutput(mismatches.join("\n"))),
+        }
+    }
+
+    fn build_presentation(&mut self, elements: Vec<MarkdownElement>) -> Result<Presentation, ExportError> {
+        let mut execution = self.execution.clone();
+        // Nobody's around to stop code that never finishes while its output is checked.
+        if self.check_expected_output {
+            execution.timeout_ms = execution.timeout_ms.or(Some(DEFAULT_SNIPPET_TIMEOUT_MS));
+        }
+        let options = PresentationBuilderOptions {
+            allow_mutations: false,
+            use_export_profile: true,
+            presentation_options: self.options.clone(),
+            command_output: self.command_output,
+            placeholders: self.placeholders.clone(),
+            execution,
+            ..Default::default()
+        };
         let presentation = PresentationBuilder::new(
             self.default_highlighter.clone(),
             self.default_theme,
@@ -62,16 +261,20 @@ impl<'a> Exporter<'a> {
             options,
         )
         .build(elements)?;
-        let commands = Self::build_capture_commands(presentation);
-        let presentation_path = path.canonicalize().map_err(ExportError::ReadPresentation)?;
-        let metadata = ExportMetadata { commands, presentation_path, images };
-        Ok(metadata)
+        Ok(presentation)
     }
 
-    fn execute_exporter(metadata: ExportMetadata) -> io::Result<()> {
+    fn execute_exporter(&self, metadata: ExportMetadata) -> io::Result<()> {
         let presenterm_path = env::current_exe()?;
-        let mut command =
-            Command::new(COMMAND).arg("--presenterm-path").arg(presenterm_path).stdin(Stdio::piped()).spawn()?;
+        let mut command = Command::new(COMMAND);
+        command.arg("--presenterm-path").arg(presenterm_path).stdin(Stdio::piped());
+        // The exporter runs presenterm again to capture it, which has to build the same presentation
+        // we generated the metadata for. It inherits our environment so the options are passed along
+        // that way.
+        for (name, value) in self.forwarded_options() {
+            command.env(name, value);
+        }
+        let mut command = command.spawn()?;
         let mut stdin = command.stdin.take().expect("no stdin");
         let metadata = serde_json::to_vec(&metadata).expect("serialization failed");
         stdin.write_all(&metadata)?;
@@ -86,6 +289,20 @@ impl<'a> Exporter<'a> {
         Ok(())
     }
 
+    fn forwarded_options(&self) -> Vec<(&'static str, String)> {
+        let mut options = Vec::new();
+        if let Some(profile) = &self.options.cli.profile {
+            options.push((PROFILE_ENV, profile.clone()));
+        }
+        if let Some(revision) = self.resources.git_revision() {
+            options.push((REVISION_ENV, revision.to_string()));
+        }
+        if self.command_output == CommandOutputPolicy::Run {
+            options.push((COMMAND_OUTPUT_ENV, "true".to_string()));
+        }
+        options
+    }
+
     fn build_capture_commands(mut presentation: Presentation) -> Vec<CaptureCommand> {
         let mut commands = Vec::new();
         let slide_chunks: Vec<_> = presentation.iter_slides().map(|slide| slide.iter_chunks().count()).collect();
@@ -104,11 +321,12 @@ impl<'a> Exporter<'a> {
         commands
     }
 
-    fn build_image_metadata(elements: &[MarkdownElement], base_path: &Path) -> Vec<ImageMetadata> {
+    fn build_image_metadata(&self, elements: &[MarkdownElement], base_path: &Path) -> Vec<ImageMetadata> {
         let mut positions = Vec::new();
         for element in elements {
-            if let MarkdownElement::Image { path, source_position } = element {
-                let full_path = base_path.join(path);
+            if let MarkdownElement::Image { path, source_position, .. } = element {
+                // Remote images were downloaded when building the presentation.
+                let full_path = self.resources.remote_image_path(path).unwrap_or_else(|| base_path.join(path));
                 let meta = ImageMetadata {
                     content_path: path.into(),
                     full_path,
@@ -135,6 +353,21 @@ pub enum ExportError {
 
     #[error("failed to invoke presenterm-export (is it installed?): {0}")]
     InvokeExporter(io::Error),
+
+    #[error("failed to render slide: {0}")]
+    RenderSlide(#[from] RenderError),
+
+    #[error("failed to create output directory: {0}")]
+    CreateDirectory(io::Error),
+
+    #[error("failed to write image: {0}")]
+    WriteImage(#[from] ImageError),
+
+    #[error("failed to write html: {0}")]
+    WriteHtml(io::Error),
+
+    #[error("code didn't produce its expected output:\n{0}")]
+    UnexpectedOutput(String),
 }
 
 /// The metadata necessary to export a presentation.
@@ -143,6 +376,9 @@ pub struct ExportMetadata {
     presentation_path: PathBuf,
     images: Vec<ImageMetadata>,
     commands: Vec<CaptureCommand>,
+
+    /// The profile the exporter should use, e.g. to size the terminal it captures.
+    profile: Option<ExportProfile>,
 }
 
 /// Metadata about an image.
@@ -166,7 +402,9 @@ enum CaptureCommand {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{options::PresentationOptions, presentation::ExportFont};
     use comrak::Arena;
+    use rstest::rstest;
 
     fn extract_metadata(content: &str, path: &str) -> ExportMetadata {
         let arena = Arena::new();
@@ -178,6 +416,24 @@ mod test {
         exporter.extract_metadata(content, Path::new(path)).expect("metadata extraction failed")
     }
 
+    #[test]
+    fn forwarded_options() {
+        let arena = Arena::new();
+        let parser = MarkdownParser::new(&arena);
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples").with_git_revision("v1");
+        let exporter = Exporter::new(parser, &theme, highlighter, resources)
+            .with_options(ExternalOptions {
+                cli: PresentationOptions { profile: Some("workshop".into()), ..Default::default() },
+                ..Default::default()
+            })
+            .with_command_output(true);
+        let expected =
+            [(PROFILE_ENV, "workshop".to_string()), (REVISION_ENV, "v1".into()), (COMMAND_OUTPUT_ENV, "true".into())];
+        assert_eq!(exporter.forwarded_options(), expected);
+    }
+
     #[test]
     fn metadata() {
         let presentation = r"
@@ -226,5 +482,78 @@ mom
             WaitForChange,
         ];
         assert_eq!(meta.commands, expected_commands);
+        assert_eq!(meta.profile, None);
+    }
+
+    #[test]
+    fn html() {
+        let presentation = r"
+# First
+
+<!-- end_slide -->
+
+hi
+<!-- pause -->
+mom
+        ";
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let mut exporter = Exporter::new(MarkdownParser::new(&arena), &theme, highlighter, Resources::new("examples"));
+        let html = exporter.build_html(presentation, "demo").expect("export failed");
+        assert!(html.contains("<title>demo</title>"));
+        // Slides are only exported once all of their steps are visible.
+        assert_eq!(html.matches("<div class=\"slide\"").count(), 2);
+        assert!(html.contains("First"));
+        assert!(html.contains("mom"));
+    }
+
+    #[rstest]
+    #[case::matches("hi", None)]
+    #[case::differs("bye", Some("code block at line 2 in slide 1: line 1: expected \"bye\", got \"hi\""))]
+    fn expected_output(#[case] block: &str, #[case] expected: Option<&str>) {
+        let presentation = format!("\n```bash +exec\necho hi\n```\n\n```expected_output\n{block}\n```\n");
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let mut exporter = Exporter::new(MarkdownParser::new(&arena), &theme, highlighter, Resources::new("examples"))
+            .with_expected_output_check(true);
+        let elements = exporter.parser.parse(&presentation).expect("parse failed");
+        let presentation = exporter.build_presentation(elements).expect("build failed");
+        let result = exporter.check_expected_outputs(&presentation);
+        match (result, expected) {
+            (Ok(()), None) => (),
+            (Err(ExportError::UnexpectedOutput(mismatches)), Some(expected)) => assert_eq!(mismatches, expected),
+            (result, _) => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn expected_output_not_checked_by_default() {
+        let presentation = "\n```bash +exec\necho hi\n```\n\n```expected_output\nbye\n```\n";
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let mut exporter = Exporter::new(MarkdownParser::new(&arena), &theme, highlighter, Resources::new("examples"));
+        assert!(exporter.build_html(presentation, "demo").is_ok());
+    }
+
+    #[test]
+    fn profile() {
+        let presentation = r"---
+export:
+  columns: 100
+  rows: 30
+  font:
+    family: Hack
+    size: 12
+---
+
+hi
+        ";
+        let meta = extract_metadata(presentation, "examples/demo.md");
+        let font = ExportFont { family: Some("Hack".into()), size: Some(12) };
+        let expected = ExportProfile { columns: 100, rows: 30, font, theme: None };
+        assert_eq!(meta.profile, Some(expected));
     }
 }
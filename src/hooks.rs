@@ -0,0 +1,188 @@
+//! Hooks that run external actions when presentation events happen.
+
+use crate::{
+    config::{HookAction, HookConfig, HookEvent},
+    events::{EventListener, PresentationEvent},
+};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    process::{self, Stdio},
+    thread,
+    time::Duration,
+};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Runs the configured hooks for every event.
+///
+/// Hooks run in the background so a slow hook never blocks the presentation.
+pub(crate) struct HookRunner {
+    hooks: Vec<HookConfig>,
+}
+
+impl HookRunner {
+    /// Construct a new hook runner.
+    pub(crate) fn new(hooks: Vec<HookConfig>) -> Self {
+        Self { hooks }
+    }
+
+    fn matches(hook: &HookConfig, event: &PresentationEvent) -> bool {
+        matches!(
+            (&hook.event, event),
+            (HookEvent::Any, _)
+                | (HookEvent::PresentationStarted, PresentationEvent::PresentationStarted { .. })
+                | (HookEvent::PresentationEnded, PresentationEvent::PresentationEnded)
+                | (HookEvent::SlideChanged, PresentationEvent::SlideChanged { .. })
+                | (HookEvent::ExecutionFinished, PresentationEvent::ExecutionFinished { .. })
+        )
+    }
+
+    fn render_template(template: &str, event: &PresentationEvent) -> String {
+        let (current_slide, total_slides) = match event {
+            PresentationEvent::PresentationStarted { total_slides } => (String::new(), total_slides.to_string()),
+            PresentationEvent::PresentationEnded => (String::new(), String::new()),
+            PresentationEvent::SlideChanged { current_slide, total_slides }
+            | PresentationEvent::ExecutionFinished { current_slide, total_slides } => {
+                (current_slide.to_string(), total_slides.to_string())
+            }
+        };
+        template
+            .replace("{event}", event.name())
+            .replace("{current_slide}", &current_slide)
+            .replace("{total_slides}", &total_slides)
+    }
+
+    fn run_command(command: &[String], event: &PresentationEvent) {
+        let mut args = command.iter().map(|arg| Self::render_template(arg, event));
+        let Some(program) = args.next() else {
+            return;
+        };
+        let child = process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = child {
+            // Reap the process in the background so we don't leave zombies around.
+            thread::spawn(move || child.wait());
+        }
+    }
+
+    fn post_event(url: &str, event: &PresentationEvent) {
+        let url = url.to_string();
+        let body = serde_json::to_string(event).expect("serialization failed");
+        thread::spawn(move || HttpPost::send(&url, &body));
+    }
+}
+
+impl EventListener for HookRunner {
+    fn on_event(&mut self, event: &PresentationEvent) {
+        for hook in &self.hooks {
+            if !Self::matches(hook, event) {
+                continue;
+            }
+            match &hook.action {
+                HookAction::Command(command) => Self::run_command(command, event),
+                HookAction::Url(url) => Self::post_event(url, event),
+            };
+        }
+    }
+}
+
+/// A minimal HTTP POST implementation.
+///
+/// This only supports plain `http://` URLs, which is all that's needed to talk to local tools.
+struct HttpPost;
+
+impl HttpPost {
+    fn send(url: &str, body: &str) -> io::Result<()> {
+        let (host, path) = Self::split_url(url).ok_or_else(|| io::Error::other("invalid url"))?;
+        let address = if host.contains(':') { host.to_string() } else { format!("{host}:80") };
+        let mut stream = Self::connect(&address)?;
+        stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+        let length = body.len();
+        write!(
+            stream,
+            "POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\n\
+            Content-Length: {length}\r\nConnection: close\r\n\r\n{body}"
+        )?;
+        stream.flush()?;
+        // We don't care about the response but give the server a chance to process the request.
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        Ok(())
+    }
+
+    // Hosts can resolve to several addresses, which are tried in order until one of them accepts the connection.
+    fn connect(address: &str) -> io::Result<TcpStream> {
+        let mut last_error = io::Error::other("host has no addresses");
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, HTTP_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn split_url(url: &str) -> Option<(&str, &str)> {
+        let url = url.strip_prefix("http://")?;
+        match url.find('/') {
+            Some(index) => Some((&url[..index], &url[index..])),
+            None => Some((url, "/")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn template() {
+        let event = PresentationEvent::SlideChanged { current_slide: 3, total_slides: 10 };
+        let rendered = HookRunner::render_template("{event}: {current_slide}/{total_slides}", &event);
+        assert_eq!(rendered, "slide_changed: 3/10");
+    }
+
+    #[rstest]
+    #[case::any(HookEvent::Any, true)]
+    #[case::same(HookEvent::SlideChanged, true)]
+    #[case::different(HookEvent::PresentationEnded, false)]
+    fn matching(#[case] hook_event: HookEvent, #[case] expected: bool) {
+        let hook = HookConfig { event: hook_event, action: HookAction::Url("http://localhost".into()) };
+        let event = PresentationEvent::SlideChanged { current_slide: 1, total_slides: 1 };
+        assert_eq!(HookRunner::matches(&hook, &event), expected);
+    }
+
+    #[rstest]
+    #[case::no_path("http://localhost:8080", Some(("localhost:8080", "/")))]
+    #[case::path("http://localhost/foo/bar", Some(("localhost", "/foo/bar")))]
+    #[case::no_scheme("localhost/foo", None)]
+    fn split_url(#[case] url: &str, #[case] expected: Option<(&str, &str)>) {
+        assert_eq!(HttpPost::split_url(url), expected);
+    }
+
+    #[test]
+    fn post() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            while !request.ends_with("{}") {
+                let mut buffer = [0; 1024];
+                let length = stream.read(&mut buffer).unwrap();
+                request.push_str(std::str::from_utf8(&buffer[..length]).unwrap());
+            }
+            request
+        });
+        HttpPost::send(&url, "{}").expect("request failed");
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"), "{request}");
+    }
+}
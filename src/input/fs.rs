@@ -1,32 +1,255 @@
-use std::{fs, io, path::PathBuf, time::SystemTime};
+use crate::config::WatchStrategy;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant, SystemTime},
+};
 
 /// Watchers the presentation's file.
 ///
 /// This uses polling rather than something fancier like `inotify`. The latter turned out to make
-/// code too complex for little added gain. This instead keeps the last modified time for the given
-/// path and uses that to determine if it's changed.
+/// code too complex for little added gain, and doesn't work on network mounts anyway. This instead
+/// keeps a fingerprint of the file and uses that to determine if it's changed.
+///
+/// Editors that save by writing a temporary file and renaming it over the original one briefly
+/// leave no file behind, so a missing file is never considered a change: the watcher simply waits
+/// for it to show up again.
 pub(crate) struct PresentationFileWatcher {
     path: PathBuf,
-    last_modification: SystemTime,
+    strategy: WatchStrategy,
+    interval: Duration,
+    last_check: Option<Instant>,
+    last_fingerprint: Option<Fingerprint>,
 }
 
 impl PresentationFileWatcher {
     /// Create a watcher over the given file path.
     pub(crate) fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self::with_strategy(path, WatchStrategy::default(), Duration::ZERO)
+    }
+
+    /// Create a watcher over the given file path that checks it at most once every `interval`.
+    pub(crate) fn with_strategy<P: Into<PathBuf>>(path: P, strategy: WatchStrategy, interval: Duration) -> Self {
         let path = path.into();
-        let last_modification = fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
-        Self { path, last_modification }
+        let last_fingerprint = Fingerprint::new(&path, strategy);
+        Self { path, strategy, interval, last_check: None, last_fingerprint }
     }
 
     /// Checker whether this file has modifications.
-    pub(crate) fn has_modifications(&mut self) -> io::Result<bool> {
-        let metadata = fs::metadata(&self.path)?;
-        let modified_time = metadata.modified()?;
-        if modified_time > self.last_modification {
-            self.last_modification = modified_time;
-            Ok(true)
-        } else {
-            Ok(false)
+    pub(crate) fn has_modifications(&mut self, now: Instant) -> bool {
+        if let Some(last_check) = self.last_check {
+            if now.saturating_duration_since(last_check) < self.interval {
+                return false;
+            }
+        }
+        self.last_check = Some(now);
+        let Some(fingerprint) = Fingerprint::new(&self.path, self.strategy) else {
+            return false;
+        };
+        if self.last_fingerprint.as_ref() == Some(&fingerprint) {
+            return false;
+        }
+        self.last_fingerprint = Some(fingerprint);
+        true
+    }
+}
+
+/// What a file looked like the last time it was checked.
+#[derive(Debug, PartialEq)]
+enum Fingerprint {
+    Metadata {
+        // Symlinks can be pointed somewhere else without the file they used to point to changing.
+        target: PathBuf,
+        // Renaming a file over another one changes its inode even if its modification time is the same.
+        inode: u64,
+        modified: SystemTime,
+        length: u64,
+    },
+    Contents(u64),
+}
+
+impl Fingerprint {
+    fn new(path: &Path, strategy: WatchStrategy) -> Option<Self> {
+        match strategy {
+            WatchStrategy::Metadata => {
+                let target = fs::canonicalize(path).ok()?;
+                let metadata = fs::metadata(&target).ok()?;
+                let modified = metadata.modified().ok()?;
+                Some(Self::Metadata { target, inode: inode(&metadata), modified, length: metadata.len() })
+            }
+            WatchStrategy::Contents => {
+                let contents = fs::read(path).ok()?;
+                let mut hasher = DefaultHasher::new();
+                contents.hash(&mut hasher);
+                Some(Self::Contents(hasher.finish()))
+            }
+        }
+    }
+}
+
+/// Watches the commit a git revision points to.
+///
+/// Presentations read from a git revision don't change when their files in the working tree do, only
+/// when the revision, e.g. a branch, is moved to a different commit.
+pub(crate) struct GitRevisionWatcher {
+    directory: PathBuf,
+    revision: String,
+    interval: Duration,
+    last_check: Option<Instant>,
+    last_commit: Option<String>,
+}
+
+impl GitRevisionWatcher {
+    /// Create a watcher over a revision in the repository the given directory is in.
+    pub(crate) fn new<P: Into<PathBuf>>(directory: P, revision: String, interval: Duration) -> Self {
+        let directory = directory.into();
+        let last_commit = resolve_commit(&directory, &revision);
+        Self { directory, revision, interval, last_check: None, last_commit }
+    }
+
+    /// Checks whether the revision points to a different commit than it used to.
+    pub(crate) fn has_modifications(&mut self, now: Instant) -> bool {
+        if let Some(last_check) = self.last_check {
+            if now.saturating_duration_since(last_check) < self.interval {
+                return false;
+            }
+        }
+        self.last_check = Some(now);
+        // Like missing files, a revision that can't be resolved for now is not a change.
+        let Some(commit) = resolve_commit(&self.directory, &self.revision) else {
+            return false;
+        };
+        if self.last_commit.as_ref() == Some(&commit) {
+            return false;
         }
+        self.last_commit = Some(commit);
+        true
+    }
+}
+
+fn resolve_commit(directory: &Path, revision: &str) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{revision}^{{commit}}"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    fn watch(directory: &TempDir, strategy: WatchStrategy) -> (PathBuf, PresentationFileWatcher) {
+        let path = directory.path().join("presentation.md");
+        fs::write(&path, "# hi").unwrap();
+        let watcher = PresentationFileWatcher::with_strategy(&path, strategy, Duration::ZERO);
+        (path, watcher)
+    }
+
+    #[rstest]
+    #[case::metadata(WatchStrategy::Metadata)]
+    #[case::contents(WatchStrategy::Contents)]
+    fn atomic_rename(#[case] strategy: WatchStrategy) {
+        let directory = TempDir::new().unwrap();
+        let (path, mut watcher) = watch(&directory, strategy);
+        assert!(!watcher.has_modifications(Instant::now()));
+
+        // The file is gone while the editor is halfway through saving.
+        let temporary_path = directory.path().join(".presentation.md.tmp");
+        fs::write(&temporary_path, "# bye").unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.has_modifications(Instant::now()));
+
+        fs::rename(&temporary_path, &path).unwrap();
+        assert!(watcher.has_modifications(Instant::now()));
+        assert!(!watcher.has_modifications(Instant::now()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_retargeted() {
+        let directory = TempDir::new().unwrap();
+        let (path, _) = watch(&directory, WatchStrategy::Metadata);
+        let other_path = directory.path().join("other.md");
+        fs::copy(&path, &other_path).unwrap();
+        let link = directory.path().join("link.md");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        let mut watcher = PresentationFileWatcher::new(&link);
+        assert!(!watcher.has_modifications(Instant::now()));
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink(&other_path, &link).unwrap();
+        assert!(watcher.has_modifications(Instant::now()));
+    }
+
+    #[test]
+    fn contents_only_change_on_different_contents() {
+        let directory = TempDir::new().unwrap();
+        let (path, mut watcher) = watch(&directory, WatchStrategy::Contents);
+        fs::write(&path, "# hi").unwrap();
+        assert!(!watcher.has_modifications(Instant::now()));
+        fs::write(&path, "# ho").unwrap();
+        assert!(watcher.has_modifications(Instant::now()));
+    }
+
+    #[test]
+    fn git_revision_moved() {
+        let directory = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").arg("-C").arg(directory.path()).args(args).status().unwrap();
+            assert!(status.success());
+        };
+        let path = directory.path().join("presentation.md");
+        fs::write(&path, "# hi").unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["-c", "user.name=a", "-c", "user.email=a@a", "commit", "-q", "-m", "first"]);
+
+        let mut watcher = GitRevisionWatcher::new(directory.path(), "HEAD".into(), Duration::ZERO);
+        // Changes in the working tree aren't part of the revision.
+        fs::write(&path, "# ho").unwrap();
+        assert!(!watcher.has_modifications(Instant::now()));
+
+        git(&["-c", "user.name=a", "-c", "user.email=a@a", "commit", "-q", "-am", "second"]);
+        assert!(watcher.has_modifications(Instant::now()));
+        assert!(!watcher.has_modifications(Instant::now()));
+    }
+
+    #[test]
+    fn interval() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("presentation.md");
+        let interval = Duration::from_secs(1);
+        let mut watcher = PresentationFileWatcher::with_strategy(&path, WatchStrategy::Contents, interval);
+        let start = Instant::now();
+        assert!(!watcher.has_modifications(start));
+
+        fs::write(&path, "# hi").unwrap();
+        assert!(!watcher.has_modifications(start + Duration::from_millis(500)));
+        assert!(watcher.has_modifications(start + Duration::from_secs(1)));
     }
 }
@@ -1,35 +1,157 @@
-use super::{fs::PresentationFileWatcher, user::UserInput};
-use std::{io, path::PathBuf, time::Duration};
+use super::{
+    fs::{GitRevisionWatcher, PresentationFileWatcher},
+    user::UserInput,
+};
+use crate::config::{WatchConfig, WatchStrategy};
+use serde::{Deserialize, Serialize};
+use strum::EnumVariantNames;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
 
 /// The source of commands.
 ///
 /// This expects user commands as well as watches over the presentation file to reload if it that
 /// happens.
 pub struct CommandSource {
+    presentation_path: PathBuf,
     watcher: PresentationFileWatcher,
+    watch_strategy: WatchStrategy,
+    watch_interval: Duration,
+    included_watchers: Vec<PresentationFileWatcher>,
+    revision_watcher: Option<GitRevisionWatcher>,
     user_input: UserInput,
+    remote: Option<Receiver<Command>>,
+    debouncer: Option<CommandDebouncer>,
 }
 
 impl CommandSource {
     /// Create a new command source over the given presentation path.
     pub fn new<P: Into<PathBuf>>(presentation_path: P) -> Self {
-        let watcher = PresentationFileWatcher::new(presentation_path);
-        Self { watcher, user_input: UserInput::default() }
+        let presentation_path = presentation_path.into();
+        let watcher = PresentationFileWatcher::new(&presentation_path);
+        Self {
+            presentation_path,
+            watcher,
+            watch_strategy: WatchStrategy::default(),
+            watch_interval: Duration::ZERO,
+            included_watchers: Vec::new(),
+            revision_watcher: None,
+            user_input: UserInput::default(),
+            remote: None,
+            debouncer: None,
+        }
+    }
+
+    /// Set how the presentation's file is watched for changes.
+    pub fn with_watch_config(mut self, config: &WatchConfig) -> Self {
+        let interval = Duration::from_millis(config.interval_ms.unwrap_or_default());
+        self.watcher = PresentationFileWatcher::with_strategy(&self.presentation_path, config.strategy, interval);
+        self.watch_strategy = config.strategy;
+        self.watch_interval = interval;
+        self
+    }
+
+    /// Watch the commit a git revision points to rather than the files in the working tree.
+    ///
+    /// Running git is more expensive than looking at a file so this is checked at most once a second.
+    pub fn with_git_revision<S: Into<String>>(mut self, revision: S) -> Self {
+        let directory = match self.presentation_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let interval = self.watch_interval.max(Duration::from_secs(1));
+        self.revision_watcher = Some(GitRevisionWatcher::new(directory, revision.into(), interval));
+        self
+    }
+
+    /// Also reload the presentation when any of these files, which are included in it, change.
+    ///
+    /// This replaces the files watched by a previous call.
+    pub(crate) fn watch_included_files<'a>(&mut self, paths: impl Iterator<Item = &'a Path>) {
+        self.included_watchers = paths
+            .map(|path| PresentationFileWatcher::with_strategy(path, self.watch_strategy, self.watch_interval))
+            .collect();
+    }
+
+    /// Ignore repeated next/previous slide commands coming from the user within this window.
+    pub fn with_navigation_debounce(mut self, window: Duration) -> Self {
+        self.debouncer = Some(CommandDebouncer::new(window));
+        self
+    }
+
+    /// Also receive commands from a remote source, like another presenterm instance.
+    pub(crate) fn attach_remote(&mut self, remote: Receiver<Command>) {
+        self.remote = Some(remote);
     }
 
     /// Try to get the next command.
     ///
-    /// This attempts to get a command and returns `Ok(None)` on timeout.
-    pub(crate) fn try_next_command(&mut self) -> io::Result<Option<Command>> {
-        if let Some(command) = self.user_input.poll_next_command(Duration::from_millis(250))? {
+    /// This waits for a command for at most the given timeout and returns `Ok(None)` if none shows up.
+    pub(crate) fn try_next_command(&mut self, timeout: Duration) -> io::Result<Option<Command>> {
+        if let Some(remote) = &self.remote {
+            match remote.try_recv() {
+                Ok(command) => return Ok(Some(command)),
+                Err(TryRecvError::Disconnected) => self.remote = None,
+                Err(TryRecvError::Empty) => (),
+            };
+        }
+        // Replayed commands come in back to back so they're never debounced.
+        if let Some(command) = self.user_input.next_replayed_command() {
             return Ok(Some(command));
+        }
+        if let Some(command) = self.user_input.poll_next_command(timeout)? {
+            let accepted = match &mut self.debouncer {
+                Some(debouncer) => debouncer.accept(&command, Instant::now()),
+                None => true,
+            };
+            return Ok(accepted.then_some(command));
         };
-        if self.watcher.has_modifications()? { Ok(Some(Command::Reload)) } else { Ok(None) }
+        let now = Instant::now();
+        if let Some(watcher) = &mut self.revision_watcher {
+            return Ok(watcher.has_modifications(now).then_some(Command::Reload));
+        }
+        // Check every file so they all forget about this change, even if one of them already changed.
+        let modified = self
+            .included_watchers
+            .iter_mut()
+            .fold(self.watcher.has_modifications(now), |modified, watcher| watcher.has_modifications(now) || modified);
+        if modified { Ok(Some(Command::Reload)) } else { Ok(None) }
+    }
+}
+
+/// Drops next/previous slide commands that repeat the last one too quickly.
+struct CommandDebouncer {
+    window: Duration,
+    last: Option<(Command, Instant)>,
+}
+
+impl CommandDebouncer {
+    fn new(window: Duration) -> Self {
+        Self { window, last: None }
+    }
+
+    fn accept(&mut self, command: &Command, now: Instant) -> bool {
+        if !matches!(command, Command::JumpNextSlide | Command::JumpPreviousSlide) {
+            return true;
+        }
+        if let Some((last_command, last_time)) = &self.last {
+            if last_command == command && now.saturating_duration_since(*last_time) < self.window {
+                return false;
+            }
+        }
+        self.last = Some((command.clone(), now));
+        true
     }
 }
 
 /// A command.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumVariantNames)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
 pub(crate) enum Command {
     /// Redraw the presentation.
     ///
@@ -51,9 +173,53 @@ pub(crate) enum Command {
     /// Jump to one particular slide.
     JumpSlide(u32),
 
+    /// Jump to a particular chunk within a particular slide.
+    ///
+    /// Both indexes are zero based.
+    JumpPosition { slide: usize, chunk: usize },
+
+    /// Go back to where the presentation was before the last navigation.
+    UndoNavigation,
+
     /// Render any widgets in the currently visible slide.
     RenderWidgets,
 
+    /// Run code in the currently visible slide that running was already confirmed for.
+    ///
+    /// The numbers are those of the code blocks to run, which are 1 based. When there's none, every widget in the
+    /// slide is rendered like [Command::RenderWidgets] does.
+    RunConfirmedCode { blocks: Option<Vec<usize>> },
+
+    /// Scroll the output of a code block in the currently visible slide up by one line.
+    ScrollOutputUp,
+
+    /// Scroll the output of a code block in the currently visible slide down by one line.
+    ScrollOutputDown,
+
+    /// Stop any code that's running in the currently visible slide.
+    CancelExecution,
+
+    /// Open or close the overview showing every slide in the presentation.
+    ToggleOverview,
+
+    /// Open or close the picker for the code blocks in the current slide.
+    ToggleCodePicker,
+
+    /// Show or hide the markers in the places slides pause at.
+    ///
+    /// This is only available in development mode.
+    TogglePauseMarkers,
+
+    /// Copy a code block in the current slide into the clipboard.
+    ///
+    /// The index is 1 based. When there's none, the last code block among the visible chunks is copied.
+    CopyCode { index: Option<usize> },
+
+    /// Open a code block in the current slide in an editor, at the line it starts at.
+    ///
+    /// The index is 1 based. When there's none, the last code block among the visible chunks is opened.
+    EditCode { index: Option<usize> },
+
     /// Exit the presentation.
     Exit,
 
@@ -64,4 +230,27 @@ pub(crate) enum Command {
     ///
     /// Like [Command::Reload] but also reloads any external resources like images and themes.
     HardReload,
+
+    /// Reload the presentation even in presentation mode.
+    ///
+    /// This needs to be sent twice in a row, the first one only asks for confirmation.
+    ForceReload,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn debounce() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut debouncer = CommandDebouncer::new(Duration::from_millis(100));
+        assert!(debouncer.accept(&Command::JumpNextSlide, at(0)));
+        assert!(!debouncer.accept(&Command::JumpNextSlide, at(50)));
+        assert!(debouncer.accept(&Command::Redraw, at(60)));
+        assert!(debouncer.accept(&Command::JumpPreviousSlide, at(70)));
+        assert!(debouncer.accept(&Command::JumpNextSlide, at(80)));
+        assert!(debouncer.accept(&Command::JumpNextSlide, at(180)));
+    }
 }
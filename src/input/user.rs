@@ -1,14 +1,21 @@
 use super::source::Command;
 use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
-use std::{io, mem, time::Duration};
+use std::{collections::VecDeque, io, mem, time::Duration};
 
 /// A user input handler.
 #[derive(Default)]
 pub(crate) struct UserInput {
     state: InputState,
+    locked: bool,
+    keyboard_macro: KeyboardMacro,
 }
 
 impl UserInput {
+    /// Get the next command of the keyboard macro being replayed, if any.
+    pub(crate) fn next_replayed_command(&mut self) -> Option<Command> {
+        self.keyboard_macro.replaying.pop_front()
+    }
+
     /// Polls for the next input command coming from the keyboard.
     pub(crate) fn poll_next_command(&mut self, timeout: Duration) -> io::Result<Option<Command>> {
         if poll(timeout)? { self.next_command() } else { Ok(None) }
@@ -18,7 +25,7 @@ impl UserInput {
     pub(crate) fn next_command(&mut self) -> io::Result<Option<Command>> {
         let current_state = mem::take(&mut self.state);
         let (command, next_state) = match read()? {
-            Event::Key(event) => Self::apply_key_event(event, current_state),
+            Event::Key(event) => self.apply_key_event_with_lock(event, current_state),
             Event::Resize(..) => (Some(Command::Redraw), current_state),
             _ => (None, current_state),
         };
@@ -26,6 +33,42 @@ impl UserInput {
         Ok(command)
     }
 
+    // While locked every key is ignored except for the ones to unlock and exit, so the keyboard can
+    // be handed over without risking accidental slide changes.
+    fn apply_key_event_with_lock(&mut self, event: KeyEvent, state: InputState) -> (Option<Command>, InputState) {
+        let is_control = event.modifiers == KeyModifiers::CONTROL;
+        match event.code {
+            KeyCode::Char('l') if is_control => {
+                self.locked = !self.locked;
+                (None, InputState::Empty)
+            }
+            KeyCode::Char('c') if is_control && self.locked => (Some(Command::Exit), InputState::Empty),
+            _ if self.locked => (None, InputState::Empty),
+            _ => self.apply_key_event_with_macro(event, state),
+        }
+    }
+
+    // `q` starts and stops recording the commands that follow, which `@` then replays.
+    fn apply_key_event_with_macro(&mut self, event: KeyEvent, state: InputState) -> (Option<Command>, InputState) {
+        match event.code {
+            KeyCode::Char('q') => {
+                self.keyboard_macro.toggle_recording();
+                (None, InputState::Empty)
+            }
+            KeyCode::Char('@') => {
+                self.keyboard_macro.replay();
+                (None, InputState::Empty)
+            }
+            _ => {
+                let (command, state) = Self::apply_key_event(event, state);
+                if let Some(command) = &command {
+                    self.keyboard_macro.record(command);
+                }
+                (command, state)
+            }
+        }
+    }
+
     fn apply_key_event(event: KeyEvent, state: InputState) -> (Option<Command>, InputState) {
         match event.code {
             KeyCode::Char('h') | KeyCode::Char('k') | KeyCode::Left | KeyCode::PageUp | KeyCode::Up => {
@@ -41,6 +84,17 @@ impl UserInput {
             KeyCode::Char('e') if event.modifiers == KeyModifiers::CONTROL => {
                 (Some(Command::RenderWidgets), InputState::Empty)
             }
+            KeyCode::Char('x') if event.modifiers == KeyModifiers::CONTROL => {
+                (Some(Command::CancelExecution), InputState::Empty)
+            }
+            KeyCode::Char('K') => (Some(Command::ScrollOutputUp), InputState::Empty),
+            KeyCode::Char('J') => (Some(Command::ScrollOutputDown), InputState::Empty),
+            KeyCode::Char('o') => (Some(Command::ToggleOverview), InputState::Empty),
+            KeyCode::Char('c') => (Some(Command::ToggleCodePicker), InputState::Empty),
+            KeyCode::Char('p') => (Some(Command::TogglePauseMarkers), InputState::Empty),
+            KeyCode::Char('u') => (Some(Command::UndoNavigation), InputState::Empty),
+            KeyCode::Char('y') => Self::apply_y(state),
+            KeyCode::Char('e') => Self::apply_e(state),
             KeyCode::Char('G') => Self::apply_uppercase_g(state),
             KeyCode::Char('g') => Self::apply_lowercase_g(state),
             KeyCode::Char(number) if number.is_ascii_digit() => {
@@ -50,6 +104,9 @@ impl UserInput {
             KeyCode::Char('r') if event.modifiers == KeyModifiers::CONTROL => {
                 (Some(Command::HardReload), InputState::Empty)
             }
+            KeyCode::Char('f') if event.modifiers == KeyModifiers::CONTROL => {
+                (Some(Command::ForceReload), InputState::Empty)
+            }
             _ => (None, InputState::Empty),
         }
     }
@@ -70,6 +127,22 @@ impl UserInput {
         }
     }
 
+    fn apply_y(state: InputState) -> (Option<Command>, InputState) {
+        let index = match state {
+            InputState::PendingNumber(number) => Some(number as usize),
+            _ => None,
+        };
+        (Some(Command::CopyCode { index }), InputState::Empty)
+    }
+
+    fn apply_e(state: InputState) -> (Option<Command>, InputState) {
+        let index = match state {
+            InputState::PendingNumber(number) => Some(number as usize),
+            _ => None,
+        };
+        (Some(Command::EditCode { index }), InputState::Empty)
+    }
+
     fn apply_number(number: u32, state: InputState) -> InputState {
         let maybe_next = match state {
             InputState::PendingNumber(current) => current.checked_mul(10).and_then(|n| n.checked_add(number)),
@@ -87,6 +160,37 @@ impl UserInput {
     }
 }
 
+/// A sequence of commands that can be replayed with a single key.
+#[derive(Default)]
+struct KeyboardMacro {
+    commands: Vec<Command>,
+    recording: Option<Vec<Command>>,
+    replaying: VecDeque<Command>,
+}
+
+impl KeyboardMacro {
+    fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(commands) => self.commands = commands,
+            None => self.recording = Some(Vec::new()),
+        };
+    }
+
+    fn record(&mut self, command: &Command) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(command.clone());
+        }
+    }
+
+    // Replaying while recording another macro makes it part of that one.
+    fn replay(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            recording.extend(self.commands.iter().cloned());
+        }
+        self.replaying.extend(self.commands.iter().cloned());
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Eq)]
 enum InputState {
     #[default]
@@ -99,6 +203,7 @@ enum InputState {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::iter;
 
     #[test]
     fn lowercase_g() {
@@ -111,6 +216,44 @@ mod test {
         assert_eq!(state, InputState::Empty);
     }
 
+    #[test]
+    fn lock() {
+        let mut input = UserInput::default();
+        let lock = KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL);
+        let exit = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        let (command, state) = input.apply_key_event_with_lock(lock, InputState::Empty);
+        assert!(command.is_none());
+
+        let (command, state) = input.apply_key_event_with_lock(KeyCode::Char('1').into(), state);
+        assert!(command.is_none());
+        let (command, state) = input.apply_key_event_with_lock(KeyCode::Char('G').into(), state);
+        assert!(command.is_none());
+        let (command, state) = input.apply_key_event_with_lock(exit, state);
+        assert_eq!(command, Some(Command::Exit));
+
+        let (_, state) = input.apply_key_event_with_lock(lock, state);
+        let (command, _) = input.apply_key_event_with_lock(KeyCode::Right.into(), state);
+        assert_eq!(command, Some(Command::JumpNextSlide));
+    }
+
+    #[test]
+    fn keyboard_macro() {
+        let mut input = UserInput::default();
+        let run = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL);
+        let keys = [KeyCode::Char('q').into(), run, KeyCode::Right.into(), run, KeyCode::Char('q').into()];
+        let mut state = InputState::Empty;
+        for key in keys {
+            (_, state) = input.apply_key_event_with_lock(key, state);
+        }
+        assert_eq!(input.next_replayed_command(), None);
+
+        let (command, _) = input.apply_key_event_with_lock(KeyCode::Char('@').into(), state);
+        assert_eq!(command, None);
+        let replayed: Vec<_> = iter::from_fn(|| input.next_replayed_command()).collect();
+        let expected = [Command::RenderWidgets, Command::JumpNextSlide, Command::RenderWidgets];
+        assert_eq!(replayed, expected);
+    }
+
     #[test]
     fn uppercase_g() {
         let state = InputState::Empty;
@@ -119,6 +262,35 @@ mod test {
         assert_eq!(state, InputState::Empty);
     }
 
+    #[test]
+    fn copy_code() {
+        let (command, _) = UserInput::apply_key_event(KeyCode::Char('y').into(), InputState::Empty);
+        assert_eq!(command, Some(Command::CopyCode { index: None }));
+
+        let (_, state) = UserInput::apply_key_event(KeyCode::Char('2').into(), InputState::Empty);
+        let (command, state) = UserInput::apply_key_event(KeyCode::Char('y').into(), state);
+        assert_eq!(command, Some(Command::CopyCode { index: Some(2) }));
+        assert_eq!(state, InputState::Empty);
+    }
+
+    #[test]
+    fn edit_code() {
+        let (command, _) = UserInput::apply_key_event(KeyCode::Char('e').into(), InputState::Empty);
+        assert_eq!(command, Some(Command::EditCode { index: None }));
+
+        let (_, state) = UserInput::apply_key_event(KeyCode::Char('3').into(), InputState::Empty);
+        let (command, state) = UserInput::apply_key_event(KeyCode::Char('e').into(), state);
+        assert_eq!(command, Some(Command::EditCode { index: Some(3) }));
+        assert_eq!(state, InputState::Empty);
+    }
+
+    #[test]
+    fn code_picker() {
+        let (command, state) = UserInput::apply_key_event(KeyCode::Char('c').into(), InputState::Empty);
+        assert_eq!(command, Some(Command::ToggleCodePicker));
+        assert_eq!(state, InputState::Empty);
+    }
+
     #[test]
     fn jump_number() {
         let state = InputState::Empty;
@@ -2,25 +2,53 @@
 //!
 //! This is not meant to be used as a crate!
 
+pub(crate) mod audience;
 pub(crate) mod builder;
+pub(crate) mod clipboard;
+pub(crate) mod clock;
+pub(crate) mod compile;
+pub(crate) mod config;
+pub(crate) mod control;
 pub(crate) mod diff;
+pub(crate) mod encoding;
+pub(crate) mod events;
 pub(crate) mod execute;
 pub(crate) mod export;
+pub(crate) mod hooks;
 pub(crate) mod input;
 pub(crate) mod markdown;
+pub(crate) mod multiplexer;
+pub(crate) mod options;
+pub(crate) mod placeholders;
+pub(crate) mod poll;
 pub(crate) mod presentation;
 pub(crate) mod presenter;
 pub(crate) mod render;
 pub(crate) mod resource;
+pub(crate) mod rpc;
+pub(crate) mod schema;
+pub(crate) mod snapshot;
+pub(crate) mod sound;
 pub(crate) mod style;
 pub(crate) mod theme;
+pub(crate) mod timing;
+pub(crate) mod transcript;
+pub(crate) mod validate;
 
 pub use crate::{
-    export::{ExportError, Exporter},
+    compile::{CompileError, PresentationCompiler},
+    config::{
+        Config, ExecutionConfig, ExecutorConfig, HostConfig, MathConfig, MultiplexerConfig, SoundConfig,
+        UnknownCommandPolicy, WatchConfig, WatchStrategy,
+    },
+    export::{ExportError, Exporter, COMMAND_OUTPUT_ENV, PROFILE_ENV, REVISION_ENV},
     input::source::CommandSource,
     markdown::parse::MarkdownParser,
-    presenter::{PresentMode, Presenter},
+    options::{ExternalOptions, PresentationOptions},
+    presenter::{BoundaryCue, ControlChannel, ImageProtocol, PresentMode, Presenter, PresenterOptions, PresenterRole},
     render::highlighting::CodeHighlighter,
     resource::Resources,
+    snapshot::{SnapshotChange, SnapshotError, SnapshotRecorder},
     theme::PresentationTheme,
+    validate::{IssueSeverity, PresentationValidator, ValidateError, ValidationIssue, ValidationReport},
 };
@@ -1,22 +1,59 @@
-use clap::{error::ErrorKind, CommandFactory, Parser};
+use clap::{error::ErrorKind, ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
 use comrak::Arena;
 use presenterm::{
-    CodeHighlighter, CommandSource, Exporter, MarkdownParser, PresentMode, PresentationTheme, Presenter, Resources,
+    BoundaryCue, CodeHighlighter, CommandSource, Config, ControlChannel, Exporter, ExternalOptions, ImageProtocol,
+    MarkdownParser, PresentMode, PresentationCompiler, PresentationOptions, PresentationTheme, PresentationValidator,
+    Presenter, PresenterOptions, PresenterRole, Resources, SnapshotRecorder, UnknownCommandPolicy, ValidationReport,
+    COMMAND_OUTPUT_ENV, PROFILE_ENV, REVISION_ENV,
 };
-use std::path::{Path, PathBuf};
+use std::{
+    env,
+    net::SocketAddr,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The directory next to the presentation its snapshots are stored in.
+const SNAPSHOTS_DIRECTORY: &str = ".presenterm-snapshots";
 
 /// Run slideshows from your terminal.
 #[derive(Parser)]
 #[command()]
 #[command(author, version, about = create_splash(), long_about = create_splash(), arg_required_else_help = true)]
+#[command(subcommand_negates_reqs = true)]
+#[command(group(
+    ArgGroup::new("validation").multiple(true).args(["validate", "validate_snippets", "lint", "check_links"])
+))]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+
     /// The path to the markdown file that contains the presentation.
-    path: PathBuf,
+    #[clap(required_unless_present_any = ["theme_schema", "front_matter_schema", "comment_command_schema"])]
+    path: Option<PathBuf>,
 
     /// Export the presentation as a PDF rather than displaying it.
     #[clap(short, long)]
     export_pdf: bool,
 
+    /// Export every slide in the presentation as a PNG image into the given directory.
+    #[clap(long, value_name = "DIRECTORY")]
+    export_images: Option<PathBuf>,
+
+    /// When exporting images, generate one for every step in each slide rather than one per slide.
+    #[clap(long, requires = "export_images")]
+    export_every_chunk: bool,
+
+    /// Export the presentation as a standalone HTML file into the given directory.
+    #[clap(long, value_name = "DIRECTORY")]
+    export_html: Option<PathBuf>,
+
+    /// When exporting, run the code blocks followed by an `expected_output` block first and fail if
+    /// any of them doesn't produce it.
+    #[clap(long)]
+    check_expected_output: bool,
+
     /// Generate the PDF metadata without generating the PDF itself.
     #[clap(long, hide = true)]
     generate_pdf_metadata: bool,
@@ -32,6 +69,187 @@ struct Cli {
     /// The theme to use.
     #[clap(short, long, default_value = "dark")]
     theme: String,
+
+    /// Print the JSON schema for theme files and exit.
+    #[clap(long)]
+    theme_schema: bool,
+
+    /// Print the JSON schema for the presentation's front matter and exit.
+    #[clap(long)]
+    front_matter_schema: bool,
+
+    /// Print the JSON schema for the commands that can be used in comments and exit.
+    #[clap(long)]
+    comment_command_schema: bool,
+
+    /// Check the presentation's front matter and comment commands without presenting it.
+    #[clap(long)]
+    validate: bool,
+
+    /// Run every executable code block in the presentation and report the ones that fail, without presenting it.
+    #[clap(long)]
+    validate_snippets: bool,
+
+    /// Check the presentation for likely mistakes, like comments with unknown commands, without presenting it.
+    ///
+    /// Unlike `--validate`, this reports unknown commands whatever the configuration says to do with them.
+    #[clap(long)]
+    lint: bool,
+
+    /// Check that every link in the presentation points to an existing file or a URL that can be fetched.
+    #[clap(long)]
+    check_links: bool,
+
+    /// The format to report the problems found when validating in.
+    #[clap(long, value_enum, default_value_t = ValidationFormat::Text, requires = "validation")]
+    validation_format: ValidationFormat,
+
+    /// The profile to present with, which selects the content in `if` blocks that check for it.
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Write the time spent on every slide to this path when exiting the presentation.
+    ///
+    /// The report is written as JSON if the path ends in `.json` and as CSV otherwise.
+    #[clap(long, value_name = "PATH")]
+    timing_report: Option<PathBuf>,
+
+    /// Load the presentation and the files it uses from this git revision rather than the working tree.
+    #[clap(long, value_name = "REVISION")]
+    rev: Option<String>,
+
+    /// The path to the configuration file.
+    #[clap(long)]
+    config_file: Option<PathBuf>,
+
+    /// Run the commands in `command_output` comments when loading the presentation.
+    #[clap(long)]
+    allow_command_output: bool,
+
+    /// Show the commands executable code blocks would run, and their `output` blocks, instead of running them.
+    #[clap(long)]
+    dry_run_exec: bool,
+
+    /// Append the commands, output, exit code, and duration of every code block run to this path.
+    #[clap(long, value_name = "PATH")]
+    exec_transcript: Option<PathBuf>,
+
+    /// Emit a cue when reaching the last step of a slide or the final slide.
+    #[clap(long, value_enum)]
+    boundary_cue: Option<BoundaryCue>,
+
+    /// The way to draw images, which is detected from the terminal by default.
+    #[clap(long, value_enum)]
+    image_protocol: Option<ImageProtocol>,
+
+    /// Move into the next step automatically after this many seconds.
+    #[clap(long, value_name = "SECONDS")]
+    auto_advance: Option<NonZeroU64>,
+
+    /// Use the high contrast theme instead of the presentation's own one.
+    #[clap(long)]
+    high_contrast: bool,
+
+    /// Serve a read only web view of the current slide on the given address, e.g. `0.0.0.0:8000`.
+    #[clap(long)]
+    audience_view: Option<SocketAddr>,
+
+    /// The role to play when presenting over two terminals: one for the audience, one for the presenter.
+    #[clap(long, value_enum)]
+    role: Option<PresenterRole>,
+
+    /// Let other programs control the presentation, e.g. via JSON-RPC commands in stdin.
+    #[clap(long, value_enum, conflicts_with = "role")]
+    control: Option<ControlChannel>,
+
+    /// The address of the control socket used to keep the audience and presenter roles in sync.
+    ///
+    /// The presenter's socket also answers HTTP requests about the presentation, speaker notes included, without any
+    /// authentication, so it should only be reachable by trusted clients.
+    #[clap(long, default_value = "127.0.0.1:7171")]
+    control_address: SocketAddr,
+
+    /// Minimize the amount of data written to the terminal, for presenting over slow SSH links.
+    ///
+    /// By default this is enabled automatically when running inside an SSH session.
+    #[clap(long, value_enum, num_args = 0..=1, default_value_t = SshFriendly::Auto, default_missing_value = "always")]
+    ssh_friendly: SshFriendly,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Compile a presentation into a file that can be played without building it again.
+    ///
+    /// Compiled presentations load instantly, which is useful on slow machines and to make sure a
+    /// presentation looks exactly the same everywhere.
+    Compile {
+        /// The path to the markdown file that contains the presentation.
+        path: PathBuf,
+
+        /// The path to write the compiled presentation to.
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+
+    /// Play a compiled presentation.
+    Play {
+        /// The path to the compiled presentation.
+        path: PathBuf,
+    },
+
+    /// Compare how every slide looks against the snapshots taken last, and report the ones that changed.
+    ///
+    /// Snapshots are stored as text files, along with the styles used in every row, in a directory
+    /// named after the presentation inside a `.presenterm-snapshots` directory next to it.
+    Snapshot {
+        /// The path to the markdown file that contains the presentation.
+        path: PathBuf,
+
+        /// Take new snapshots, replacing the existing ones, rather than comparing against them.
+        #[clap(long)]
+        update: bool,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum ValidationFormat {
+    /// One line per problem, written to stderr.
+    Text,
+
+    /// A JSON report of every problem, written to stdout.
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum SshFriendly {
+    /// Enable it if running inside an SSH session.
+    Auto,
+
+    /// Always enable it.
+    Always,
+
+    /// Never enable it.
+    Never,
+}
+
+impl SshFriendly {
+    fn is_enabled(&self) -> bool {
+        match self {
+            Self::Auto => ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"].iter().any(|name| env::var_os(name).is_some()),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+impl Cli {
+    // The PDF exporter runs presenterm in export mode to capture it and passes along the options the presentation was
+    // built with through the environment.
+    fn apply_forwarded_options(&mut self) {
+        self.profile = self.profile.take().or_else(|| env::var(PROFILE_ENV).ok());
+        self.rev = self.rev.take().or_else(|| env::var(REVISION_ENV).ok());
+        self.allow_command_output |= env::var_os(COMMAND_OUTPUT_ENV).is_some();
+    }
 }
 
 fn create_splash() -> String {
@@ -50,6 +268,25 @@ fn create_splash() -> String {
 }
 
 fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = match (cli.theme_schema, cli.front_matter_schema, cli.comment_command_schema) {
+        (true, _, _) => Some(PresentationTheme::json_schema()),
+        (_, true, _) => Some(PresentationValidator::front_matter_schema()),
+        (_, _, true) => Some(PresentationValidator::comment_command_schema()),
+        _ => None,
+    };
+    if let Some(schema) = schema {
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+    let path = match (&cli.command, cli.path) {
+        (Some(CliCommand::Compile { path, .. } | CliCommand::Play { path } | CliCommand::Snapshot { path, .. }), _) => {
+            path.clone()
+        }
+        (None, Some(path)) => path,
+        (None, None) => {
+            Cli::command().error(ErrorKind::MissingRequiredArgument, "no presentation path given").exit();
+        }
+    };
     let Some(default_theme) = PresentationTheme::from_name(&cli.theme) else {
         let mut cmd = Cli::command();
         let valid_themes = PresentationTheme::theme_names().collect::<Vec<_>>().join(", ");
@@ -62,29 +299,150 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
         (false, true) => PresentMode::Export,
         (false, false) => PresentMode::Development,
     };
+    let config = match &cli.config_file {
+        Some(path) => Config::load(path)?,
+        None => Config::load_default()?,
+    };
     let arena = Arena::new();
     let parser = MarkdownParser::new(&arena);
-    let default_highlighter = CodeHighlighter::new("base16-ocean.dark")?;
-    let resources_path = cli.path.parent().unwrap_or(Path::new("/"));
-    let resources = Resources::new(resources_path);
-    if cli.export_pdf || cli.generate_pdf_metadata {
-        let mut exporter = Exporter::new(parser, &default_theme, default_highlighter, resources);
-        if cli.export_pdf {
-            exporter.export_pdf(&cli.path)?;
+    let cli_options = PresentationOptions {
+        boundary_cue: cli.boundary_cue,
+        profile: cli.profile,
+        dry_run_exec: cli.dry_run_exec.then_some(true),
+        present: cli.present.then_some(true),
+        image_protocol: cli.image_protocol,
+        auto_advance: cli.auto_advance,
+        high_contrast: cli.high_contrast.then_some(true),
+    };
+    let presentation_options = ExternalOptions { cli: cli_options, config: config.options };
+    let mut default_highlighter = CodeHighlighter::new("base16-ocean.dark")?;
+    // Custom syntax definitions live in a `syntaxes` directory next to the config file.
+    let config_path = cli.config_file.clone().or_else(Config::default_path);
+    let syntaxes_path = config_path.as_deref().and_then(Path::parent).map(|parent| parent.join("syntaxes"));
+    if let Some(syntaxes_path) = syntaxes_path.filter(|path| path.is_dir()) {
+        default_highlighter = default_highlighter.with_syntaxes_from(&syntaxes_path)?;
+    }
+    let resources_path = path.parent().unwrap_or(Path::new("/"));
+    let mut resources = Resources::new(resources_path).with_math_config(config.math.clone());
+    if let Some(revision) = &cli.rev {
+        resources = resources.with_git_revision(revision.as_str());
+    }
+    if cli.validate || cli.validate_snippets || cli.lint || cli.check_links {
+        // Linting reports unknown commands whatever the configuration says to do with them.
+        let unknown_commands = if cli.lint { UnknownCommandPolicy::Warn } else { config.unknown_commands };
+        let mut validator = PresentationValidator::new(parser, &default_theme, default_highlighter, resources)
+            .with_unknown_commands(unknown_commands)
+            .with_execution(config.execution.clone());
+        let mut issues = Vec::new();
+        if cli.validate || cli.lint {
+            issues.extend(validator.validate(&path)?);
+        }
+        if cli.validate_snippets {
+            issues.extend(validator.validate_snippets(&path)?);
+        }
+        if cli.check_links {
+            issues.extend(validator.check_links(&path)?);
+        }
+        match cli.validation_format {
+            ValidationFormat::Text => {
+                for issue in &issues {
+                    eprintln!("{}: {issue}", path.display());
+                }
+            }
+            ValidationFormat::Json => println!("{}", ValidationReport::new(&path, &issues).to_json()),
+        };
+        return match issues.len() {
+            0 => Ok(()),
+            count => Err(format!("found {count} issue(s)").into()),
+        };
+    }
+    if let Some(CliCommand::Snapshot { update, .. }) = &cli.command {
+        // Presentations in the same directory each get their own snapshots.
+        let directory = resources_path.join(SNAPSHOTS_DIRECTORY).join(path.file_stem().unwrap_or_default());
+        let mut recorder = SnapshotRecorder::new(parser, &default_theme, default_highlighter, resources)
+            .with_options(presentation_options.clone())
+            .with_command_output(cli.allow_command_output)
+            .with_execution(config.execution.clone());
+        if *update {
+            recorder.update(&path, &directory)?;
+            return Ok(());
+        }
+        let changes = recorder.compare(&path, &directory)?;
+        for change in &changes {
+            println!("{change}");
+        }
+        return match changes.len() {
+            0 => Ok(()),
+            count => Err(format!("{count} slide(s) changed since the last snapshot").into()),
+        };
+    }
+    if let Some(CliCommand::Compile { output, .. }) = &cli.command {
+        let mut compiler = PresentationCompiler::new(parser, &default_theme, default_highlighter, resources)
+            .with_options(presentation_options.clone())
+            .with_command_output(cli.allow_command_output)
+            .with_placeholders(config.placeholders.clone())
+            .with_execution(config.execution.clone());
+        compiler.compile(&path, output)?;
+    } else if cli.export_pdf || cli.generate_pdf_metadata || cli.export_images.is_some() || cli.export_html.is_some() {
+        let mut exporter = Exporter::new(parser, &default_theme, default_highlighter, resources)
+            .with_options(presentation_options.clone())
+            .with_command_output(cli.allow_command_output)
+            .with_placeholders(config.placeholders)
+            .with_execution(config.execution.clone())
+            .with_expected_output_check(cli.check_expected_output);
+        if let Some(directory) = &cli.export_images {
+            exporter.export_images(&path, directory, cli.export_every_chunk)?;
+        } else if let Some(directory) = &cli.export_html {
+            exporter.export_html(&path, directory)?;
+        } else if cli.export_pdf {
+            exporter.export_pdf(&path)?;
         } else {
-            let meta = exporter.generate_metadata(&cli.path)?;
+            let meta = exporter.generate_metadata(&path)?;
             println!("{}", serde_json::to_string_pretty(&meta)?);
         }
     } else {
-        let commands = CommandSource::new(&cli.path);
-        let presenter = Presenter::new(&default_theme, default_highlighter, commands, parser, resources, mode);
-        presenter.present(&cli.path)?;
+        let mut commands = CommandSource::new(&path).with_watch_config(&config.watch);
+        if let Some(revision) = &cli.rev {
+            commands = commands.with_git_revision(revision.as_str());
+        }
+        if let Some(millis) = config.navigation_debounce_ms {
+            commands = commands.with_navigation_debounce(Duration::from_millis(millis));
+        }
+        let options = PresenterOptions {
+            mode,
+            hooks: config.hooks,
+            window_title: config.window_title,
+            audience_view: cli.audience_view,
+            role: cli.role,
+            control_address: cli.control_address,
+            low_bandwidth: cli.ssh_friendly.is_enabled(),
+            timing_report: cli.timing_report,
+            unknown_commands: config.unknown_commands,
+            allow_command_output: cli.allow_command_output,
+            placeholders: config.placeholders,
+            control: cli.control,
+            multiplexer: config.multiplexer,
+            sound: config.sound,
+            execution: config.execution,
+            presentation_options,
+            execution_transcript: cli.exec_transcript,
+            safe_area: config.safe_area,
+        };
+        let presenter = Presenter::new(&default_theme, default_highlighter, commands, parser, resources, options);
+        if matches!(cli.command, Some(CliCommand::Play { .. })) {
+            presenter.play(&path)?;
+        } else {
+            presenter.present(&path)?;
+        }
     }
     Ok(())
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    if cli.export {
+        cli.apply_forwarded_options();
+    }
     if let Err(e) = run(cli) {
         eprintln!("{e}");
         std::process::exit(1);
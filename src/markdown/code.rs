@@ -1,5 +1,8 @@
-use super::elements::{Code, CodeAttributes, CodeLanguage, Highlight, HighlightGroup};
+use super::elements::{
+    Code, CodeAlignment, CodeAttributes, CodeLanguage, CodeSnippet, Highlight, HighlightGroup, SourcePosition,
+};
 use comrak::nodes::NodeCodeBlock;
+use std::path::PathBuf;
 use strum::EnumDiscriminants;
 
 pub(crate) type ParseResult<T> = Result<T, CodeBlockParseError>;
@@ -7,21 +10,35 @@ pub(crate) type ParseResult<T> = Result<T, CodeBlockParseError>;
 pub(crate) struct CodeBlockParser;
 
 impl CodeBlockParser {
-    pub(crate) fn parse(code_block: &NodeCodeBlock) -> ParseResult<Code> {
+    pub(crate) fn parse(code_block: &NodeCodeBlock, source_position: SourcePosition) -> ParseResult<Code> {
         let (language, attributes) = Self::parse_block_info(&code_block.info)?;
-        let code = Code { contents: code_block.literal.clone(), language, attributes };
+        let language_token = Self::next_identifier(&code_block.info).into();
+        let code = Code { contents: code_block.literal.clone(), language, language_token, attributes, source_position };
         Ok(code)
     }
 
     fn parse_block_info(input: &str) -> ParseResult<(CodeLanguage, CodeAttributes)> {
         let (language, input) = Self::parse_language(input);
         let attributes = Self::parse_attributes(input)?;
-        if attributes.execute && !language.supports_execution() {
-            return Err(CodeBlockParseError::ExecutionNotSupported(language));
+        if attributes.render && !language.supports_rendering() {
+            return Err(CodeBlockParseError::RenderingNotSupported(language));
         }
+        match (&attributes.session, &language) {
+            // Sessions are saved and restored using POSIX shell syntax, which fish doesn't understand.
+            (Some(_), CodeLanguage::Shell(interpreter)) if interpreter == "fish" => {
+                return Err(CodeBlockParseError::SessionNotSupported(language));
+            }
+            (Some(_), CodeLanguage::Shell(_)) | (None, _) => (),
+            (Some(_), _) => return Err(CodeBlockParseError::SessionNotSupported(language)),
+        };
         Ok((language, attributes))
     }
 
+    /// Get the language code blocks that use the given name, or any of its aliases, are in.
+    pub(crate) fn parse_language_name(name: &str) -> CodeLanguage {
+        Self::parse_language(name).0
+    }
+
     fn parse_language(input: &str) -> (CodeLanguage, &str) {
         let token = Self::next_identifier(input);
         use CodeLanguage::*;
@@ -37,7 +54,9 @@ impl CodeBlockParser {
             "cpp" | "c++" => Cpp,
             "css" => Css,
             "d" => DLang,
+            "d2" => D2,
             "docker" => Docker,
+            "dot" | "graphviz" => Dot,
             "dotenv" => Dotenv,
             "elixir" => Elixir,
             "elm" => Elm,
@@ -53,6 +72,7 @@ impl CodeBlockParser {
             "lua" => Lua,
             "make" => Makefile,
             "markdown" => Markdown,
+            "mermaid" => Mermaid,
             "ocaml" => OCaml,
             "perl" => Perl,
             "php" => Php,
@@ -69,11 +89,12 @@ impl CodeBlockParser {
             "swift" => Swift,
             "terraform" => Terraform,
             "typescript" | "ts" => TypeScript,
+            "typst" => Typst,
             "xml" => Xml,
             "yaml" => Yaml,
             "vue" => Vue,
             "zig" => Zig,
-            _ => Unknown,
+            name => Unknown(name.into()),
         };
         let rest = &input[token.len()..];
         (language, rest)
@@ -89,8 +110,27 @@ impl CodeBlockParser {
             }
             match attribute {
                 Attribute::LineNumbers => attributes.line_numbers = true,
-                Attribute::Exec => attributes.execute = true,
+                Attribute::Exec { replace } => {
+                    attributes.execute = true;
+                    attributes.replace_with_output = replace;
+                }
+                Attribute::Render => attributes.render = true,
+                Attribute::Alignment(alignment) => attributes.alignment = Some(alignment),
+                Attribute::File(snippet) => attributes.snippet = Some(snippet),
                 Attribute::HighlightedLines(lines) => attributes.highlight_groups = lines,
+                Attribute::Id(id) => attributes.id = Some(id),
+                Attribute::Needs(ids) => attributes.needs = ids,
+                Attribute::Requires(ids) => {
+                    attributes.needs = ids;
+                    attributes.shows_needed_output = true;
+                }
+                Attribute::Container(image) => attributes.container = Some(image),
+                Attribute::Host(host) => attributes.host = Some(host),
+                Attribute::Stdin(path) => attributes.stdin = Some(path),
+                Attribute::OutputLines(lines) => attributes.output_lines = Some(lines),
+                Attribute::Session(name) => attributes.session = Some(name),
+                Attribute::Lines(lines) => attributes.shown_lines = Some(lines),
+                Attribute::Fold => attributes.fold = true,
             };
             processed_attributes.push(discriminant);
             input = rest;
@@ -98,9 +138,58 @@ impl CodeBlockParser {
         if attributes.highlight_groups.is_empty() {
             attributes.highlight_groups.push(HighlightGroup::new(vec![Highlight::All]));
         }
+        if processed_attributes.contains(&AttributeDiscriminants::Needs)
+            && processed_attributes.contains(&AttributeDiscriminants::Requires)
+        {
+            return Err(CodeBlockParseError::IncompatibleAttributes("+requires", "+needs"));
+        }
+        if !attributes.needs.is_empty() {
+            let attribute = if attributes.shows_needed_output { "+requires" } else { "+needs" };
+            Self::validate_interactive_execution(&attributes, attribute)?;
+        }
+        if attributes.container.is_some() && !attributes.execute {
+            return Err(CodeBlockParseError::RequiresExec("+container"));
+        }
+        if attributes.host.is_some() && !attributes.execute {
+            return Err(CodeBlockParseError::RequiresExec("+host"));
+        }
+        if attributes.stdin.is_some() {
+            Self::validate_interactive_execution(&attributes, "+stdin")?;
+        }
+        if attributes.output_lines.is_some() {
+            Self::validate_interactive_execution(&attributes, "+output_lines")?;
+        }
+        if attributes.session.is_some() {
+            Self::validate_interactive_execution(&attributes, "+session")?;
+        }
+        if attributes.fold && attributes.shown_lines.is_none() {
+            return Err(CodeBlockParseError::RequiresAttribute("+fold", "+lines"));
+        }
+        if attributes.host.is_some() && attributes.container.is_some() {
+            return Err(CodeBlockParseError::IncompatibleAttributes("+host", "+container"));
+        }
+        // Sessions keep their state in this machine so their code has to run in it too.
+        if attributes.session.is_some() && attributes.container.is_some() {
+            return Err(CodeBlockParseError::IncompatibleAttributes("+session", "+container"));
+        }
+        if attributes.session.is_some() && attributes.host.is_some() {
+            return Err(CodeBlockParseError::IncompatibleAttributes("+session", "+host"));
+        }
         Ok(attributes)
     }
 
+    // Some attributes only make sense for code that runs while presenting, which isn't the case for
+    // +exec_replace since its output replaces it when the presentation is loaded.
+    fn validate_interactive_execution(attributes: &CodeAttributes, attribute: &'static str) -> ParseResult<()> {
+        if !attributes.execute {
+            Err(CodeBlockParseError::RequiresExec(attribute))
+        } else if attributes.replace_with_output {
+            Err(CodeBlockParseError::ReplacedExecution(attribute))
+        } else {
+            Ok(())
+        }
+    }
+
     fn parse_attribute(input: &str) -> ParseResult<(Option<Attribute>, &str)> {
         let input = Self::skip_whitespace(input);
         let (attribute, input) = match input.chars().next() {
@@ -108,7 +197,48 @@ impl CodeBlockParser {
                 let token = Self::next_identifier(&input[1..]);
                 let attribute = match token {
                     "line_numbers" => Attribute::LineNumbers,
-                    "exec" => Attribute::Exec,
+                    "exec" => Attribute::Exec { replace: false },
+                    "exec_replace" => Attribute::Exec { replace: true },
+                    "render" => Attribute::Render,
+                    "left" => Attribute::Alignment(CodeAlignment::Left),
+                    "center" => Attribute::Alignment(CodeAlignment::Center),
+                    "fold" => Attribute::Fold,
+                    token if token.starts_with("id=") => Attribute::Id(Self::parse_id(&token["id=".len()..])?),
+                    token if token.starts_with("needs=") => {
+                        let ids = token["needs=".len()..].split(',').map(Self::parse_id);
+                        Attribute::Needs(ids.collect::<Result<_, _>>()?)
+                    }
+                    token if token.starts_with("requires=") => {
+                        let ids = token["requires=".len()..].split(',').map(Self::parse_id);
+                        Attribute::Requires(ids.collect::<Result<_, _>>()?)
+                    }
+                    token if token.starts_with("container=") && token.len() > "container=".len() => {
+                        Attribute::Container(token["container=".len()..].into())
+                    }
+                    token if token.starts_with("host=") && token.len() > "host=".len() => {
+                        Attribute::Host(token["host=".len()..].into())
+                    }
+                    token if token.starts_with("session=") => {
+                        Attribute::Session(Self::parse_id(&token["session=".len()..])?)
+                    }
+                    token if token.starts_with("output_lines=") => {
+                        match token["output_lines=".len()..].parse::<u16>() {
+                            Ok(lines) if lines > 0 => Attribute::OutputLines(lines),
+                            _ => return Err(CodeBlockParseError::InvalidToken(token.into())),
+                        }
+                    }
+                    token if token.starts_with("lines=") => {
+                        let group = Self::parse_highlight_group(&token["lines=".len()..])
+                            .map_err(|_| CodeBlockParseError::InvalidToken(token.into()))?;
+                        Attribute::Lines(group)
+                    }
+                    token if token.starts_with("stdin=") => {
+                        let path = token["stdin=".len()..].trim_matches('"');
+                        if path.is_empty() {
+                            return Err(CodeBlockParseError::InvalidToken(token.into()));
+                        }
+                        Attribute::Stdin(path.into())
+                    }
                     _ => return Err(CodeBlockParseError::InvalidToken(Self::next_identifier(input).into())),
                 };
                 (Some(attribute), &input[token.len() + 1..])
@@ -117,6 +247,11 @@ impl CodeBlockParser {
                 let (lines, input) = Self::parse_highlight_groups(&input[1..])?;
                 (Some(Attribute::HighlightedLines(lines)), input)
             }
+            Some(_) if input.starts_with("file=") => {
+                let token = Self::next_identifier(input);
+                let snippet = Self::parse_snippet(&token["file=".len()..])?;
+                (Some(Attribute::File(snippet)), &input[token.len()..])
+            }
             Some(_) => return Err(CodeBlockParseError::InvalidToken(Self::next_identifier(input).into())),
             None => (None, input),
         };
@@ -174,6 +309,43 @@ impl CodeBlockParser {
             .map_err(|_| CodeBlockParseError::InvalidHighlightedLines(format!("not a number: '{input}'")))
     }
 
+    // Parses `path` or `path[first..last]`, where either end of the range can be left out.
+    fn parse_snippet(input: &str) -> ParseResult<CodeSnippet> {
+        let error = |reason: &str| CodeBlockParseError::InvalidSnippet(format!("{input}: {reason}"));
+        let (path, range) = match input.strip_suffix(']').and_then(|input| input.rsplit_once('[')) {
+            Some((path, range)) => (path, Some(range)),
+            None => (input, None),
+        };
+        if path.is_empty() {
+            return Err(error("no path"));
+        }
+        let (first_line, last_line) = match range {
+            Some(range) => {
+                let (first, last) = range.split_once("..").ok_or_else(|| error("expected a range like '10..20'"))?;
+                let parse = |line: &str| match line {
+                    "" => Ok(None),
+                    line => line.parse::<usize>().map(Some).map_err(|_| error("invalid line number")),
+                };
+                (parse(first)?, parse(last)?)
+            }
+            None => (None, None),
+        };
+        match (first_line, last_line) {
+            (Some(0), _) | (_, Some(0)) => return Err(error("lines start at 1")),
+            (Some(first), Some(last)) if first > last => return Err(error("the range is empty")),
+            _ => (),
+        };
+        Ok(CodeSnippet { path: path.into(), first_line, last_line })
+    }
+
+    fn parse_id(input: &str) -> ParseResult<String> {
+        let valid = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+        if input.is_empty() || !input.chars().all(valid) {
+            return Err(CodeBlockParseError::InvalidId(format!("'{input}' is not a valid id")));
+        }
+        Ok(input.into())
+    }
+
     fn skip_whitespace(input: &str) -> &str {
         input.trim_start_matches(' ')
     }
@@ -194,24 +366,59 @@ pub(crate) enum CodeBlockParseError {
     #[error("invalid highlighted lines: {0}")]
     InvalidHighlightedLines(String),
 
+    #[error("invalid file: {0}")]
+    InvalidSnippet(String),
+
+    #[error("invalid code block id: {0}")]
+    InvalidId(String),
+
+    #[error("{0} can only be used in code blocks that use +exec")]
+    RequiresExec(&'static str),
+
+    #[error("{0} can't be used in code blocks that use +exec_replace")]
+    ReplacedExecution(&'static str),
+
+    #[error("{0} can only be used along with {1}")]
+    RequiresAttribute(&'static str, &'static str),
+
+    #[error("{0} can't be used along with {1}")]
+    IncompatibleAttributes(&'static str, &'static str),
+
     #[error("duplicate attribute: {0}")]
     DuplicateAttribute(&'static str),
 
-    #[error("language {0:?} does not support execution")]
-    ExecutionNotSupported(CodeLanguage),
+    #[error("language {0:?} does not support rendering")]
+    RenderingNotSupported(CodeLanguage),
+
+    #[error("language {0:?} does not support sessions, only sh, bash and zsh code does")]
+    SessionNotSupported(CodeLanguage),
 }
 
 #[derive(EnumDiscriminants)]
 enum Attribute {
     LineNumbers,
-    Exec,
+    Exec { replace: bool },
+    Render,
+    Alignment(CodeAlignment),
+    File(CodeSnippet),
     HighlightedLines(Vec<HighlightGroup>),
+    Id(String),
+    Needs(Vec<String>),
+    Requires(Vec<String>),
+    Container(String),
+    Host(String),
+    Stdin(PathBuf),
+    OutputLines(u16),
+    Session(String),
+    Lines(HighlightGroup),
+    Fold,
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use rstest::rstest;
+    use std::path::Path;
     use Highlight::*;
 
     fn parse_language(input: &str) -> CodeLanguage {
@@ -226,7 +433,7 @@ mod test {
 
     #[test]
     fn unknown_language() {
-        assert_eq!(parse_language("potato"), CodeLanguage::Unknown);
+        assert_eq!(parse_language("potato"), CodeLanguage::Unknown("potato".into()));
     }
 
     #[test]
@@ -248,6 +455,167 @@ mod test {
         assert!(attributes.line_numbers);
     }
 
+    #[test]
+    fn exec_replace() {
+        let attributes = parse_attributes("bash +exec_replace");
+        assert!(attributes.execute);
+        assert!(attributes.replace_with_output);
+        assert!(!parse_attributes("bash +exec").replace_with_output);
+        CodeBlockParser::parse_block_info("bash +exec +exec_replace").unwrap_err();
+        CodeBlockParser::parse_block_info("bash +exec_replace +needs=setup").unwrap_err();
+    }
+
+    #[test]
+    fn render() {
+        let attributes = parse_attributes("mermaid +render");
+        assert!(attributes.render);
+        assert!(!attributes.execute);
+        assert_eq!(parse_language("graphviz +render"), CodeLanguage::Dot);
+        assert_eq!(parse_language("typst +render"), CodeLanguage::Typst);
+        assert_eq!(parse_language("d2 +render"), CodeLanguage::D2);
+        CodeBlockParser::parse_block_info("bash +render").unwrap_err();
+    }
+
+    #[rstest]
+    #[case::none("rust", None)]
+    #[case::left("rust +left", Some(CodeAlignment::Left))]
+    #[case::center("bash +exec +center", Some(CodeAlignment::Center))]
+    fn alignment(#[case] input: &str, #[case] expected: Option<CodeAlignment>) {
+        assert_eq!(parse_attributes(input).alignment, expected);
+    }
+
+    #[rstest]
+    #[case::whole_file("rust file=src/main.rs", "src/main.rs", None, None)]
+    #[case::range("rust file=src/main.rs[10..40] +line_numbers", "src/main.rs", Some(10), Some(40))]
+    #[case::open_start("rust file=main.rs[..3]", "main.rs", None, Some(3))]
+    #[case::open_end("bash +exec file=a[b].sh[3..]", "a[b].sh", Some(3), None)]
+    fn snippet(
+        #[case] input: &str,
+        #[case] path: &str,
+        #[case] first_line: Option<usize>,
+        #[case] last_line: Option<usize>,
+    ) {
+        let expected = CodeSnippet { path: path.into(), first_line, last_line };
+        assert_eq!(parse_attributes(input).snippet, Some(expected));
+    }
+
+    #[rstest]
+    #[case::no_path("rust file=")]
+    #[case::not_a_range("rust file=main.rs[10]")]
+    #[case::zero("rust file=main.rs[0..3]")]
+    #[case::empty_range("rust file=main.rs[4..3]")]
+    fn invalid_snippet(#[case] input: &str) {
+        CodeBlockParser::parse_block_info(input).unwrap_err();
+    }
+
+    #[test]
+    fn dependencies() {
+        let attributes = parse_attributes("bash +exec +id=run +needs=setup,build_2");
+        assert_eq!(attributes.id.as_deref(), Some("run"));
+        assert_eq!(attributes.needs, &["setup", "build_2"]);
+        assert_eq!(parse_attributes("bash +id=setup").id.as_deref(), Some("setup"));
+        assert!(!attributes.shows_needed_output);
+
+        let attributes = parse_attributes("bash +exec +requires=build");
+        assert_eq!(attributes.needs, &["build"]);
+        assert!(attributes.shows_needed_output);
+    }
+
+    #[rstest]
+    #[case::empty_id("bash +exec +id=")]
+    #[case::invalid_id("bash +exec +id=a.b")]
+    #[case::empty_dependency("bash +exec +needs=a,")]
+    #[case::not_executable("bash +needs=setup")]
+    #[case::requires_not_executable("bash +requires=setup")]
+    #[case::requires_and_needs("bash +exec +requires=setup +needs=build")]
+    fn invalid_dependencies(#[case] input: &str) {
+        CodeBlockParser::parse_block_info(input).unwrap_err();
+    }
+
+    #[test]
+    fn container() {
+        let attributes = parse_attributes("python +exec +container=ghcr.io/some/python:3.12");
+        assert_eq!(attributes.container.as_deref(), Some("ghcr.io/some/python:3.12"));
+        CodeBlockParser::parse_block_info("python +exec +container=").unwrap_err();
+        CodeBlockParser::parse_block_info("python +container=python:3.12").unwrap_err();
+    }
+
+    #[test]
+    fn host() {
+        let attributes = parse_attributes("bash +exec +host=demo-box");
+        assert_eq!(attributes.host.as_deref(), Some("demo-box"));
+        CodeBlockParser::parse_block_info("bash +exec +host=").unwrap_err();
+        CodeBlockParser::parse_block_info("bash +host=demo-box").unwrap_err();
+        CodeBlockParser::parse_block_info("bash +exec +host=demo-box +container=alpine").unwrap_err();
+    }
+
+    #[rstest]
+    #[case::plain("bash +exec +stdin=data.txt")]
+    #[case::quoted("bash +exec +stdin=\"data.txt\"")]
+    fn stdin(#[case] input: &str) {
+        let attributes = parse_attributes(input);
+        assert_eq!(attributes.stdin.as_deref(), Some(Path::new("data.txt")));
+    }
+
+    #[rstest]
+    #[case::empty("bash +exec +stdin=")]
+    #[case::not_executable("bash +stdin=data.txt")]
+    #[case::replaced("bash +exec_replace +stdin=data.txt")]
+    fn invalid_stdin(#[case] input: &str) {
+        CodeBlockParser::parse_block_info(input).unwrap_err();
+    }
+
+    #[test]
+    fn replaced_stdin() {
+        let error = CodeBlockParser::parse_block_info("bash +exec_replace +stdin=data.txt").unwrap_err();
+        assert!(matches!(error, CodeBlockParseError::ReplacedExecution("+stdin")), "{error}");
+    }
+
+    #[test]
+    fn shown_lines() {
+        let attributes = parse_attributes("bash +lines=1-3,5 +fold");
+        let expected = HighlightGroup::new(vec![Range(1..4), Single(5)]);
+        assert_eq!(attributes.shown_lines, Some(expected));
+        assert!(attributes.fold);
+    }
+
+    #[rstest]
+    #[case::not_a_range("bash +lines=a-b")]
+    #[case::empty("bash +lines=")]
+    #[case::fold_without_lines("bash +fold")]
+    fn invalid_shown_lines(#[case] input: &str) {
+        CodeBlockParser::parse_block_info(input).unwrap_err();
+    }
+
+    #[rstest]
+    #[case::valid("bash +exec +output_lines=10", Some(10))]
+    #[case::zero("bash +exec +output_lines=0", None)]
+    #[case::not_a_number("bash +exec +output_lines=ten", None)]
+    #[case::not_executable("bash +output_lines=10", None)]
+    fn output_lines(#[case] input: &str, #[case] expected: Option<u16>) {
+        let result = CodeBlockParser::parse_block_info(input);
+        assert_eq!(result.ok().and_then(|(_, attributes)| attributes.output_lines), expected);
+    }
+
+    #[rstest]
+    #[case::valid("bash +exec +session=demo", Some("demo"))]
+    #[case::not_executable("bash +session=demo", None)]
+    #[case::replaced("bash +exec_replace +session=demo", None)]
+    #[case::invalid_name("bash +exec +session=a.b", None)]
+    #[case::not_shell("python +exec +session=demo", None)]
+    #[case::fish("fish +exec +session=demo", None)]
+    #[case::container("bash +exec +session=demo +container=alpine", None)]
+    #[case::host("bash +exec +session=demo +host=box", None)]
+    fn session(#[case] input: &str, #[case] expected: Option<&str>) {
+        let result = CodeBlockParser::parse_block_info(input);
+        assert_eq!(result.ok().and_then(|(_, attributes)| attributes.session).as_deref(), expected);
+    }
+
+    #[test]
+    fn conflicting_alignments() {
+        CodeBlockParser::parse_block_info("rust +left +center").unwrap_err();
+    }
+
     #[test]
     fn invalid_attributes() {
         CodeBlockParser::parse_block_info("bash +potato").unwrap_err();
@@ -1,6 +1,7 @@
 use crate::style::TextStyle;
+use serde::{Deserialize, Serialize};
 use std::{iter, ops::Range, path::PathBuf};
-use strum::EnumIter;
+use strum::{EnumIter, IntoStaticStr};
 use unicode_width::UnicodeWidthStr;
 
 /// A markdown element.
@@ -22,7 +23,7 @@ pub(crate) enum MarkdownElement {
     Paragraph(Vec<ParagraphElement>),
 
     /// An image.
-    Image { path: PathBuf, source_position: SourcePosition },
+    Image { path: PathBuf, attributes: ImageAttributes, source_position: SourcePosition },
 
     /// A list.
     ///
@@ -30,7 +31,7 @@ pub(crate) enum MarkdownElement {
     List(Vec<ListItem>),
 
     /// A block of code.
-    Code(Code),
+    Code(Box<Code>),
 
     /// A table.
     Table(Table),
@@ -45,7 +46,7 @@ pub(crate) enum MarkdownElement {
     BlockQuote(Vec<String>),
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub(crate) struct SourcePosition {
     pub(crate) start: LineColumn,
 }
@@ -64,7 +65,7 @@ impl From<comrak::nodes::Sourcepos> for SourcePosition {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub(crate) struct LineColumn {
     pub(crate) line: usize,
     pub(crate) column: usize,
@@ -93,7 +94,7 @@ pub(crate) enum ParagraphElement {
 /// A piece of styled text.
 ///
 /// Text is represented as a series of chunks, each with their own formatting.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub(crate) struct Text {
     /// The chunks that make up this text.
     pub(crate) chunks: Vec<StyledText>,
@@ -105,6 +106,11 @@ impl Text {
         self.chunks.iter().map(|text| text.text.width()).sum()
     }
 
+    /// Get the contents of this text without any styling.
+    pub(crate) fn to_plain_text(&self) -> String {
+        self.chunks.iter().map(|text| text.text.as_str()).collect()
+    }
+
     /// Applies the given style to this text.
     pub(crate) fn apply_style(&mut self, style: &TextStyle) {
         for text in &mut self.chunks {
@@ -122,31 +128,50 @@ impl<T: Into<StyledText>> From<T> for Text {
 /// A styled piece of text.
 ///
 /// This is the most granular text representation: a `String` and a style.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub(crate) struct StyledText {
     pub(crate) text: String,
     pub(crate) style: TextStyle,
+
+    /// The image drawn over this text, if this stands for an image in the middle of some text.
+    #[serde(skip)]
+    pub(crate) image: Option<InlineImage>,
 }
 
 impl StyledText {
     /// Construct a new styled text.
     pub(crate) fn new<S: Into<String>>(text: S, style: TextStyle) -> Self {
-        Self { text: text.into(), style }
+        Self { text: text.into(), style, image: None }
+    }
+
+    /// Construct a text that stands for an image, using the image's alt text until it's loaded.
+    pub(crate) fn image<S: Into<String>>(alt: S, image: InlineImage) -> Self {
+        Self { text: alt.into(), style: TextStyle::default(), image: Some(image) }
     }
 }
 
 impl From<String> for StyledText {
     fn from(text: String) -> Self {
-        Self { text, style: TextStyle::default() }
+        Self::new(text, TextStyle::default())
     }
 }
 
 impl From<&str> for StyledText {
     fn from(text: &str) -> Self {
-        Self { text: text.into(), style: TextStyle::default() }
+        Self::new(text, TextStyle::default())
     }
 }
 
+/// An image shown within a line of text, like a badge or an icon.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct InlineImage {
+    /// The path to the image.
+    pub(crate) path: PathBuf,
+
+    /// The attributes set in the image's title.
+    pub(crate) attributes: ImageAttributes,
+}
+
 /// A list item.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct ListItem {
@@ -160,6 +185,22 @@ pub(crate) struct ListItem {
 
     /// The type of list item.
     pub(crate) item_type: ListItemType,
+
+    /// The blocks after this item's text, like more paragraphs or code, which are shown under it.
+    pub(crate) blocks: Vec<ListItemBlock>,
+}
+
+/// A block in a list item other than the text it starts with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ListItemBlock {
+    /// A paragraph.
+    Paragraph(Text),
+
+    /// A code block.
+    Code(Box<Code>),
+
+    /// A block quote.
+    BlockQuote(Vec<String>),
 }
 
 /// The type of a list item.
@@ -184,12 +225,61 @@ pub(crate) struct Code {
     /// The programming language this code is written in.
     pub(crate) language: CodeLanguage,
 
+    /// The token the language was written as in the code block, like `js` for javascript.
+    pub(crate) language_token: String,
+
     /// The attributes used for this code.
     pub(crate) attributes: CodeAttributes,
+
+    /// Where this code starts in the presentation's file.
+    pub(crate) source_position: SourcePosition,
+}
+
+impl Code {
+    /// Get the lines in this code that are displayed.
+    ///
+    /// Executable code can contain lines that are run but not displayed, like setup code, by
+    /// prefixing them with the language's hidden line prefix.
+    pub(crate) fn visible_lines(&self) -> impl Iterator<Item = &str> {
+        self.contents.lines().filter(|line| self.hidden_line(line).is_none())
+    }
+
+    /// Get the code that's displayed.
+    pub(crate) fn visible_contents(&self) -> String {
+        let mut contents = self.visible_lines().collect::<Vec<_>>().join("\n");
+        if self.contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents
+    }
+
+    /// Get the code that's run when this code is executed, which includes hidden lines.
+    pub(crate) fn executable_contents(&self) -> String {
+        let mut contents = String::new();
+        for line in self.contents.lines() {
+            contents.push_str(self.hidden_line(line).unwrap_or(line));
+            contents.push('\n');
+        }
+        contents
+    }
+
+    // Get the contents of a line if it's hidden.
+    fn hidden_line<'a>(&self, line: &'a str) -> Option<&'a str> {
+        if !self.attributes.execute {
+            return None;
+        }
+        let prefix = self.language.hidden_line_prefix();
+        match line.strip_prefix(prefix) {
+            Some(line) => Some(line),
+            None if line == prefix.trim_end() => Some(""),
+            None => None,
+        }
+    }
 }
 
 /// The language of a piece of code.
-#[derive(Clone, Debug, PartialEq, Eq, EnumIter)]
+#[derive(Clone, Debug, PartialEq, Eq, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
 pub(crate) enum CodeLanguage {
     Ada,
     Asp,
@@ -203,8 +293,10 @@ pub(crate) enum CodeLanguage {
     Clojure,
     Cpp,
     Css,
+    D2,
     DLang,
     Docker,
+    Dot,
     Dotenv,
     Elixir,
     Elm,
@@ -220,6 +312,7 @@ pub(crate) enum CodeLanguage {
     Lua,
     Makefile,
     Markdown,
+    Mermaid,
     OCaml,
     Perl,
     Php,
@@ -235,7 +328,8 @@ pub(crate) enum CodeLanguage {
     Svelte,
     Terraform,
     TypeScript,
-    Unknown,
+    Typst,
+    Unknown(String),
     Xml,
     Yaml,
     Vue,
@@ -243,8 +337,34 @@ pub(crate) enum CodeLanguage {
 }
 
 impl CodeLanguage {
+    /// The name of this language.
+    ///
+    /// This is the name used in code blocks for languages we don't know about and the interpreter
+    /// for shells.
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::Shell(name) | Self::Unknown(name) => name,
+            language => language.into(),
+        }
+    }
+
+    /// The prefix that hides lines in executable code in this language.
+    ///
+    /// This is the one rustdoc uses for rust, and one that isn't valid code, nor a comment people
+    /// write, in any other language. Anything that's a comment, like `/// `, would hide doc comments.
+    pub(crate) fn hidden_line_prefix(&self) -> &'static str {
+        match self {
+            Self::Rust => "# ",
+            _ => "#~ ",
+        }
+    }
+
     pub(crate) fn supports_execution(&self) -> bool {
-        matches!(self, Self::Shell(_))
+        matches!(self, Self::Shell(_) | Self::Rust | Self::C | Self::Cpp | Self::Go)
+    }
+
+    pub(crate) fn supports_rendering(&self) -> bool {
+        matches!(self, Self::Mermaid | Self::Dot | Self::D2 | Self::Latex | Self::Typst)
     }
 }
 
@@ -254,11 +374,97 @@ pub(crate) struct CodeAttributes {
     /// Whether the code block is marked as executable.
     pub(crate) execute: bool,
 
+    /// Whether an executable code block is run while building the presentation and replaced by its output.
+    pub(crate) replace_with_output: bool,
+
+    /// Whether the code block is rendered into an image rather than displayed as code.
+    pub(crate) render: bool,
+
     /// Whether the code block should show line numbers.
     pub(crate) line_numbers: bool,
 
+    /// The alignment to use for this code block rather than the theme's one.
+    pub(crate) alignment: Option<CodeAlignment>,
+
+    /// The file this code block's contents are read from, if any.
+    pub(crate) snippet: Option<CodeSnippet>,
+
     /// The groups of lines to highlight.
     pub(crate) highlight_groups: Vec<HighlightGroup>,
+
+    /// The name other code blocks use to refer to this one.
+    pub(crate) id: Option<String>,
+
+    /// The ids of the code blocks that need to run before this one does.
+    pub(crate) needs: Vec<String>,
+
+    /// Whether the output of the code blocks this one needs is shown before its own.
+    pub(crate) shows_needed_output: bool,
+
+    /// The container image executable code runs in, if it doesn't run directly in this machine.
+    pub(crate) container: Option<String>,
+
+    /// The name of the configured host executable code runs in over SSH, if any.
+    pub(crate) host: Option<String>,
+
+    /// The file whose contents are written into executable code's stdin, relative to the presentation.
+    pub(crate) stdin: Option<PathBuf>,
+
+    /// The most lines of output shown at once when this code runs, rather than the theme's.
+    pub(crate) output_lines: Option<u16>,
+
+    /// The name of the shell session executable code runs in, which keeps its state across code blocks.
+    pub(crate) session: Option<String>,
+
+    /// The lines that are displayed, if only some of them are.
+    pub(crate) shown_lines: Option<HighlightGroup>,
+
+    /// Whether a marker is displayed in place of the lines that aren't shown.
+    pub(crate) fold: bool,
+}
+
+/// A file, or a range of lines in it, used as a code block's contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CodeSnippet {
+    /// The path to the file, relative to the presentation.
+    pub(crate) path: PathBuf,
+
+    /// The first line to include, starting at 1.
+    pub(crate) first_line: Option<usize>,
+
+    /// The last line to include.
+    pub(crate) last_line: Option<usize>,
+}
+
+/// The alignment of a code block, within the column it's in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CodeAlignment {
+    Left,
+    Center,
+}
+
+/// Attributes for images, set via the image's title.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct ImageAttributes {
+    /// How the image is scaled to the space it's drawn in, if at all.
+    pub(crate) fit: Option<ImageFit>,
+
+    /// The number of columns the image can take up at most.
+    pub(crate) columns: Option<u16>,
+}
+
+/// How an image is scaled to the space it's drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ImageFit {
+    /// Scale the image up or down so it's as large as it can be while still being fully visible.
+    Contain,
+
+    /// Scale the image so it covers all of the space, cropping whatever doesn't fit.
+    Cover,
+
+    /// Stretch the image so it takes up all of the space.
+    Fill,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -1,4 +1,5 @@
 pub(crate) mod code;
 pub(crate) mod elements;
 pub(crate) mod parse;
+pub(crate) mod template;
 pub(crate) mod text;
@@ -2,7 +2,10 @@ use super::{code::CodeBlockParseError, elements::SourcePosition};
 use crate::{
     markdown::{
         code::CodeBlockParser,
-        elements::{ListItem, ListItemType, MarkdownElement, ParagraphElement, StyledText, Table, TableRow, Text},
+        elements::{
+            Code, ImageAttributes, ImageFit, InlineImage, ListItem, ListItemBlock, ListItemType, MarkdownElement,
+            ParagraphElement, StyledText, Table, TableRow, Text,
+        },
     },
     style::TextStyle,
 };
@@ -56,8 +59,9 @@ impl<'a> MarkdownParser<'a> {
         for node in node.children() {
             let mut parsed_elements =
                 Self::parse_node(node).map_err(|e| ParseError::new(e.kind, e.sourcepos.offset_lines(lines_offset)))?;
-            if let Some(MarkdownElement::FrontMatter(contents)) = parsed_elements.first() {
-                lines_offset += contents.lines().count() + 2;
+            // The front matter includes its delimiters and the blank lines after it.
+            if let NodeValue::FrontMatter(contents) = &node.data.borrow().value {
+                lines_offset += contents.lines().count();
             }
             // comrak ignores the lines in the front matter so we need to offset this ourselves.
             Self::adjust_source_positions(parsed_elements.iter_mut(), lines_offset);
@@ -74,12 +78,21 @@ impl<'a> MarkdownParser<'a> {
                 | MarkdownElement::Heading { .. }
                 | MarkdownElement::Paragraph(_)
                 | MarkdownElement::Image { .. }
-                | MarkdownElement::List(_)
-                | MarkdownElement::Code(_)
                 | MarkdownElement::Table(_)
                 | MarkdownElement::ThematicBreak
                 | MarkdownElement::BlockQuote(_) => continue,
                 MarkdownElement::Comment { source_position, .. } => source_position,
+                MarkdownElement::Code(code) => &mut code.source_position,
+                MarkdownElement::List(items) => {
+                    let codes = items.iter_mut().flat_map(|item| &mut item.blocks).filter_map(|block| match block {
+                        ListItemBlock::Code(code) => Some(code),
+                        _ => None,
+                    });
+                    for code in codes {
+                        code.source_position = code.source_position.offset_lines(lines_offset);
+                    }
+                    continue;
+                }
             };
             *position = position.offset_lines(lines_offset);
         }
@@ -97,10 +110,12 @@ impl<'a> MarkdownParser<'a> {
                 MarkdownElement::List(items)
             }
             NodeValue::Table(_) => Self::parse_table(node)?,
-            NodeValue::CodeBlock(block) => Self::parse_code_block(block, data.sourcepos)?,
+            NodeValue::CodeBlock(block) => {
+                MarkdownElement::Code(Box::new(Self::parse_code_block(block, data.sourcepos)?))
+            }
             NodeValue::ThematicBreak => MarkdownElement::ThematicBreak,
             NodeValue::HtmlBlock(block) => Self::parse_html_block(block, data.sourcepos)?,
-            NodeValue::BlockQuote => Self::parse_block_quote(node)?,
+            NodeValue::BlockQuote => MarkdownElement::BlockQuote(Self::parse_block_quote(node)?),
             other => return Err(ParseErrorKind::UnsupportedElement(other.identifier()).with_sourcepos(data.sourcepos)),
         };
         Ok(vec![element])
@@ -127,7 +142,7 @@ impl<'a> MarkdownParser<'a> {
         Ok(MarkdownElement::Comment { comment: block.into(), source_position: sourcepos.into() })
     }
 
-    fn parse_block_quote(node: &'a AstNode<'a>) -> ParseResult<MarkdownElement> {
+    fn parse_block_quote(node: &'a AstNode<'a>) -> ParseResult<Vec<String>> {
         let mut buffer = BufWriter::new(Vec::new());
         let mut options = ParserOptions::default().0;
         options.render.list_style = ListStyleType::Star;
@@ -143,16 +158,15 @@ impl<'a> MarkdownParser<'a> {
             };
             lines.push(line.to_string());
         }
-        Ok(MarkdownElement::BlockQuote(lines))
+        Ok(lines)
     }
 
-    fn parse_code_block(block: &NodeCodeBlock, sourcepos: Sourcepos) -> ParseResult<MarkdownElement> {
+    fn parse_code_block(block: &NodeCodeBlock, sourcepos: Sourcepos) -> ParseResult<Code> {
         if !block.fenced {
             return Err(ParseErrorKind::UnfencedCodeBlock.with_sourcepos(sourcepos));
         }
-        let code =
-            CodeBlockParser::parse(block).map_err(|e| ParseErrorKind::InvalidCodeBlock(e).with_sourcepos(sourcepos))?;
-        Ok(MarkdownElement::Code(code))
+        CodeBlockParser::parse(block, sourcepos.into())
+            .map_err(|e| ParseErrorKind::InvalidCodeBlock(e).with_sourcepos(sourcepos))
     }
 
     fn parse_heading(heading: &NodeHeading, node: &'a AstNode<'a>) -> ParseResult<MarkdownElement> {
@@ -166,18 +180,19 @@ impl<'a> MarkdownParser<'a> {
 
     fn parse_paragraph(node: &'a AstNode<'a>) -> ParseResult<Vec<MarkdownElement>> {
         let mut elements = Vec::new();
-        let inlines = InlinesParser::default().parse(node)?;
+        let inlines = InlinesParser::new(InlineImages::WithinText).parse(node)?;
         let mut paragraph_elements = Vec::new();
         for inline in inlines {
             match inline {
                 Inline::Text(text) => paragraph_elements.push(ParagraphElement::Text(text)),
                 Inline::LineBreak => paragraph_elements.push(ParagraphElement::LineBreak),
-                Inline::Image(path) => {
+                Inline::Image { path, attributes } => {
                     if !paragraph_elements.is_empty() {
                         elements.push(MarkdownElement::Paragraph(mem::take(&mut paragraph_elements)));
                     }
                     elements.push(MarkdownElement::Image {
                         path: path.into(),
+                        attributes,
                         source_position: node.data.borrow().sourcepos.into(),
                     });
                 }
@@ -190,7 +205,11 @@ impl<'a> MarkdownParser<'a> {
     }
 
     fn parse_text(node: &'a AstNode<'a>) -> ParseResult<Text> {
-        let inlines = InlinesParser::default().parse(node)?;
+        Self::parse_text_with_images(node, InlineImages::None)
+    }
+
+    fn parse_text_with_images(node: &'a AstNode<'a>, images: InlineImages) -> ParseResult<Text> {
+        let inlines = InlinesParser::new(images).parse(node)?;
         let mut chunks = Vec::new();
         for inline in inlines {
             match inline {
@@ -230,16 +249,25 @@ impl<'a> MarkdownParser<'a> {
             (ListType::Ordered, ListDelimType::Paren) => ListItemType::OrderedParens,
             (ListType::Ordered, ListDelimType::Period) => ListItemType::OrderedPeriod,
         };
-        let mut elements = Vec::new();
+        let mut contents = None;
+        let mut blocks = Vec::new();
+        let mut nested = Vec::new();
         for node in root.children() {
             let data = node.data.borrow();
-            match &data.value {
-                NodeValue::Paragraph => {
-                    let contents = Self::parse_text(node)?;
-                    elements.push(ListItem { contents, depth, item_type: item_type.clone() });
+            // Blocks after a nested list are still part of this item so they're shown along with its other ones.
+            let block = match &data.value {
+                NodeValue::Paragraph if contents.is_none() && blocks.is_empty() => {
+                    contents = Some(Self::parse_text(node)?);
+                    continue;
                 }
+                NodeValue::Paragraph => ListItemBlock::Paragraph(Self::parse_text(node)?),
+                NodeValue::CodeBlock(block) => {
+                    ListItemBlock::Code(Self::parse_code_block(block, data.sourcepos)?.into())
+                }
+                NodeValue::BlockQuote => ListItemBlock::BlockQuote(Self::parse_block_quote(node)?),
                 NodeValue::List(_) => {
-                    elements.extend(Self::parse_list(node, depth + 1)?);
+                    nested.extend(Self::parse_list(node, depth + 1)?);
+                    continue;
                 }
                 other => {
                     return Err(ParseErrorKind::UnsupportedStructure {
@@ -248,8 +276,14 @@ impl<'a> MarkdownParser<'a> {
                     }
                     .with_sourcepos(data.sourcepos));
                 }
-            }
+            };
+            blocks.push(block);
+        }
+        let mut elements = Vec::new();
+        if contents.is_some() || !blocks.is_empty() {
+            elements.push(ListItem { depth, contents: contents.unwrap_or_default(), item_type, blocks });
         }
+        elements.extend(nested);
         Ok(elements)
     }
 
@@ -286,20 +320,37 @@ impl<'a> MarkdownParser<'a> {
                 }
                 .with_sourcepos(data.sourcepos));
             };
-            let text = Self::parse_text(node)?;
+            let text = Self::parse_text_with_images(node, InlineImages::All)?;
             cells.push(text);
         }
         Ok(TableRow(cells))
     }
 }
 
-#[derive(Default)]
+/// Which images are shown within the text around them instead of on their own.
+#[derive(Clone, Copy)]
+enum InlineImages {
+    /// None of them.
+    None,
+
+    /// The ones that have text before or after them in the same line.
+    WithinText,
+
+    /// All of them.
+    All,
+}
+
 struct InlinesParser {
     inlines: Vec<Inline>,
     pending_text: Vec<StyledText>,
+    images: InlineImages,
 }
 
 impl InlinesParser {
+    fn new(images: InlineImages) -> Self {
+        Self { inlines: Vec::new(), pending_text: Vec::new(), images }
+    }
+
     fn parse<'a>(mut self, node: &'a AstNode<'a>) -> ParseResult<Vec<Inline>> {
         self.process_children(node, TextStyle::default())?;
         self.store_pending_text();
@@ -334,8 +385,15 @@ impl InlinesParser {
                 self.inlines.push(Inline::LineBreak);
             }
             NodeValue::Image(link) => {
-                self.store_pending_text();
-                self.inlines.push(Inline::Image(link.url.clone()));
+                let attributes = Self::parse_image_attributes(&link.title)
+                    .map_err(|e| ParseErrorKind::InvalidImageAttributes(e).with_sourcepos(data.sourcepos))?;
+                if self.is_inline_image(node) {
+                    let image = InlineImage { path: link.url.clone().into(), attributes };
+                    self.pending_text.push(StyledText::image(Self::alt_text(node), image));
+                } else {
+                    self.store_pending_text();
+                    self.inlines.push(Inline::Image { path: link.url.clone(), attributes });
+                }
             }
             other => {
                 return Err(ParseErrorKind::UnsupportedStructure { container: "text", element: other.identifier() }
@@ -345,6 +403,54 @@ impl InlinesParser {
         Ok(())
     }
 
+    fn is_inline_image<'a>(&self, node: &'a AstNode<'a>) -> bool {
+        let is_text = |node: Option<&'a AstNode<'a>>| {
+            node.is_some_and(|node| !matches!(node.data.borrow().value, NodeValue::SoftBreak | NodeValue::LineBreak))
+        };
+        match self.images {
+            InlineImages::None => false,
+            InlineImages::WithinText => is_text(node.previous_sibling()) || is_text(node.next_sibling()),
+            InlineImages::All => true,
+        }
+    }
+
+    fn alt_text<'a>(node: &'a AstNode<'a>) -> String {
+        node.descendants()
+            .filter_map(|node| match &node.data.borrow().value {
+                NodeValue::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Attributes go in the title, e.g. `![](image.png "+fit=cover +columns=40")`.
+    fn parse_image_attributes(title: &str) -> Result<ImageAttributes, String> {
+        let mut attributes = ImageAttributes::default();
+        for attribute in title.split_whitespace() {
+            let (name, value) = attribute
+                .strip_prefix('+')
+                .and_then(|attribute| attribute.split_once('='))
+                .ok_or_else(|| format!("expected '+name=value', found '{attribute}'"))?;
+            match name {
+                "fit" => {
+                    let fit = match value {
+                        "contain" => ImageFit::Contain,
+                        "cover" => ImageFit::Cover,
+                        "fill" => ImageFit::Fill,
+                        _ => return Err(format!("unknown fit '{value}'")),
+                    };
+                    attributes.fit = Some(fit);
+                }
+                "columns" => match value.parse() {
+                    Ok(columns) if columns > 0 => attributes.columns = Some(columns),
+                    _ => return Err(format!("invalid number of columns '{value}'")),
+                },
+                _ => return Err(format!("unknown attribute '{name}'")),
+            };
+        }
+        Ok(attributes)
+    }
+
     fn process_children<'a>(&mut self, node: &'a AstNode<'a>, style: TextStyle) -> ParseResult<()> {
         for node in node.children() {
             self.process_node(node, style.clone())?;
@@ -355,7 +461,7 @@ impl InlinesParser {
 
 enum Inline {
     Text(Text),
-    Image(String),
+    Image { path: String, attributes: ImageAttributes },
     LineBreak,
 }
 
@@ -363,7 +469,7 @@ impl Inline {
     fn kind(&self) -> &'static str {
         match self {
             Self::Text(_) => "text",
-            Self::Image(_) => "image",
+            Self::Image { .. } => "image",
             Self::LineBreak => "line break",
         }
     }
@@ -406,6 +512,9 @@ pub(crate) enum ParseErrorKind {
     /// A code block contains invalid attributes.
     InvalidCodeBlock(CodeBlockParseError),
 
+    /// An image contains invalid attributes.
+    InvalidImageAttributes(String),
+
     /// An internal parsing error.
     Internal(String),
 }
@@ -419,6 +528,7 @@ impl Display for ParseErrorKind {
             }
             Self::UnfencedCodeBlock => write!(f, "only fenced code blocks are supported"),
             Self::InvalidCodeBlock(error) => write!(f, "invalid code block: {error}"),
+            Self::InvalidImageAttributes(error) => write!(f, "invalid image attributes: {error}"),
             Self::Internal(message) => write!(f, "internal error: {message}"),
         }
     }
@@ -476,6 +586,7 @@ impl Identifier for NodeValue {
 mod test {
     use super::*;
     use crate::markdown::elements::CodeLanguage;
+    use rstest::rstest;
     use std::path::Path;
 
     fn parse_single(input: &str) -> MarkdownElement {
@@ -526,6 +637,20 @@ boop
         assert_eq!(elements, expected_elements);
     }
 
+    #[test]
+    fn table_image() {
+        let parsed = parse_single(
+            r"
+| Name | Status |
+| ------ | ------ |
+| Potato | ![ok](ok.png) |
+",
+        );
+        let MarkdownElement::Table(Table { rows, .. }) = parsed else { panic!("not a table: {parsed:?}") };
+        let image = InlineImage { path: "ok.png".into(), attributes: Default::default() };
+        assert_eq!(rows[0].0[1], Text::from(StyledText::image("ok", image)));
+    }
+
     #[test]
     fn link() {
         let parsed = parse_single("my [website](https://example.com)");
@@ -544,14 +669,49 @@ boop
         assert_eq!(path, Path::new("potato.png"));
     }
 
+    #[rstest]
+    #[case::none("", ImageAttributes::default())]
+    #[case::fit(r#""+fit=cover""#, ImageAttributes { fit: Some(ImageFit::Cover), columns: None })]
+    #[case::both(r#""+fit=contain +columns=40""#, ImageAttributes { fit: Some(ImageFit::Contain), columns: Some(40) })]
+    fn image_attributes(#[case] title: &str, #[case] expected: ImageAttributes) {
+        let parsed = parse_single(&format!("![](potato.png {title})"));
+        let MarkdownElement::Image { attributes, .. } = parsed else { panic!("not an image: {parsed:?}") };
+        assert_eq!(attributes, expected);
+    }
+
+    #[rstest]
+    #[case::no_plus("fit=cover")]
+    #[case::unknown_fit("+fit=potato")]
+    #[case::zero_columns("+columns=0")]
+    #[case::unknown_attribute("+width=3")]
+    fn invalid_image_attributes(#[case] title: &str) {
+        let arena = Arena::new();
+        let result = MarkdownParser::new(&arena).parse(&format!("![](potato.png \"{title}\")"));
+        assert!(result.is_err());
+    }
+
     #[test]
     fn image_within_text() {
+        let parsed = parse_single(r#"built with ![rust](rust.png "+columns=2") today"#);
+        let MarkdownElement::Paragraph(elements) = parsed else { panic!("not a paragraph: {parsed:?}") };
+        let attributes = ImageAttributes { fit: None, columns: Some(2) };
+        let image = InlineImage { path: "rust.png".into(), attributes };
+        let expected_chunks =
+            vec![StyledText::from("built with "), StyledText::image("rust", image), StyledText::from(" today")];
+        let expected_elements = &[ParagraphElement::Text(Text { chunks: expected_chunks })];
+        assert_eq!(elements, expected_elements);
+    }
+
+    #[test]
+    fn image_after_text_line() {
         let parsed = parse_all(
             r"
-picture of potato: ![](potato.png)
+picture of potato:
+![](potato.png)
 ",
         );
         assert_eq!(parsed.len(), 2);
+        assert!(matches!(parsed[1], MarkdownElement::Image { .. }), "not an image: {:?}", parsed[1]);
     }
 
     #[test]
@@ -598,6 +758,37 @@ Title
         assert_eq!(next().depth, 0);
     }
 
+    #[test]
+    fn list_item_blocks() {
+        let parsed = parse_single(
+            r"
+* One
+
+  more about one
+
+  ```bash
+  echo hi
+  ```
+
+  > quoted
+  * Sub
+* Two",
+        );
+        let MarkdownElement::List(items) = parsed else { panic!("not a list: {parsed:?}") };
+        let contents: Vec<_> = items.iter().map(|item| (item.depth, item.contents.to_plain_text())).collect();
+        assert_eq!(contents, &[(0, "One".into()), (1, "Sub".into()), (0, "Two".into())]);
+
+        let [ListItemBlock::Paragraph(text), ListItemBlock::Code(code), ListItemBlock::BlockQuote(lines)] =
+            items[0].blocks.as_slice()
+        else {
+            panic!("unexpected blocks: {:?}", items[0].blocks)
+        };
+        assert_eq!(text.to_plain_text(), "more about one");
+        assert_eq!(code.contents, "echo hi\n");
+        assert_eq!(lines, &["quoted"]);
+        assert!(items[1].blocks.is_empty());
+    }
+
     #[test]
     fn line_breaks() {
         let parsed = parse_all(
@@ -753,7 +944,7 @@ mom
         let Err(e) = result else {
             panic!("parsing didn't fail");
         };
-        assert_eq!(e.sourcepos.start.line, 5);
+        assert_eq!(e.sourcepos.start.line, 6);
         assert_eq!(e.sourcepos.start.column, 3);
     }
 
@@ -769,7 +960,7 @@ mom
 ",
         );
         let MarkdownElement::Comment { source_position, .. } = &parsed[1] else { panic!("not a comment") };
-        assert_eq!(source_position.start.line, 5);
+        assert_eq!(source_position.start.line, 6);
         assert_eq!(source_position.start.column, 1);
     }
 }
@@ -0,0 +1,199 @@
+use super::elements::{ListItem, ListItemBlock, MarkdownElement, ParagraphElement, Table, Text};
+use crate::placeholders::PlaceholderRegistry;
+use std::{collections::HashMap, path::PathBuf};
+
+/// A reusable piece of a presentation.
+///
+/// Templates can contain placeholders like `{{title}}` anywhere there's text, which are replaced
+/// with the values given when instantiating them or, if there's none, the ones provided by a
+/// [PlaceholderRegistry].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Template {
+    elements: Vec<MarkdownElement>,
+}
+
+impl Template {
+    /// Construct a template out of the elements in it.
+    pub(crate) fn new(elements: Vec<MarkdownElement>) -> Self {
+        Self { elements }
+    }
+
+    /// Get a copy of the elements in this template with every placeholder replaced by its value.
+    pub(crate) fn instantiate(
+        &self,
+        values: &HashMap<String, String>,
+        placeholders: &PlaceholderRegistry,
+    ) -> Result<Vec<MarkdownElement>, TemplateError> {
+        let filler = PlaceholderFiller { values, placeholders };
+        self.elements.iter().cloned().map(|element| filler.fill_element(element)).collect()
+    }
+}
+
+struct PlaceholderFiller<'a> {
+    values: &'a HashMap<String, String>,
+    placeholders: &'a PlaceholderRegistry,
+}
+
+impl<'a> PlaceholderFiller<'a> {
+    fn fill_element(&self, element: MarkdownElement) -> Result<MarkdownElement, TemplateError> {
+        use MarkdownElement::*;
+        let element = match element {
+            SetexHeading { text } => SetexHeading { text: self.fill_text(text)? },
+            Heading { level, text } => Heading { level, text: self.fill_text(text)? },
+            Paragraph(elements) => {
+                let elements = elements
+                    .into_iter()
+                    .map(|element| match element {
+                        ParagraphElement::Text(text) => Ok(ParagraphElement::Text(self.fill_text(text)?)),
+                        ParagraphElement::LineBreak => Ok(ParagraphElement::LineBreak),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Paragraph(elements)
+            }
+            Image { path, attributes, source_position } => {
+                let path = PathBuf::from(self.fill(&path.to_string_lossy())?);
+                Image { path, attributes, source_position }
+            }
+            List(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| {
+                        let contents = self.fill_text(item.contents)?;
+                        let blocks = item.blocks.into_iter().map(|block| self.fill_list_item_block(block));
+                        Ok(ListItem { contents, blocks: blocks.collect::<Result<_, _>>()?, ..item })
+                    })
+                    .collect::<Result<_, _>>()?;
+                List(items)
+            }
+            Code(mut code) => {
+                code.contents = self.fill(&code.contents)?;
+                Code(code)
+            }
+            Table(table) => Table(self.fill_table(table)?),
+            Comment { comment, source_position } => Comment { comment: self.fill(&comment)?, source_position },
+            BlockQuote(lines) => BlockQuote(lines.iter().map(|line| self.fill(line)).collect::<Result<_, _>>()?),
+            element @ (FrontMatter(_) | ThematicBreak) => element,
+        };
+        Ok(element)
+    }
+
+    fn fill_text(&self, mut text: Text) -> Result<Text, TemplateError> {
+        for chunk in &mut text.chunks {
+            chunk.text = self.fill(&chunk.text)?;
+        }
+        Ok(text)
+    }
+
+    fn fill_list_item_block(&self, block: ListItemBlock) -> Result<ListItemBlock, TemplateError> {
+        let block = match block {
+            ListItemBlock::Paragraph(text) => ListItemBlock::Paragraph(self.fill_text(text)?),
+            ListItemBlock::Code(mut code) => {
+                code.contents = self.fill(&code.contents)?;
+                ListItemBlock::Code(code)
+            }
+            ListItemBlock::BlockQuote(lines) => {
+                ListItemBlock::BlockQuote(lines.iter().map(|line| self.fill(line)).collect::<Result<_, _>>()?)
+            }
+        };
+        Ok(block)
+    }
+
+    fn fill_table(&self, mut table: Table) -> Result<Table, TemplateError> {
+        for row in std::iter::once(&mut table.header).chain(&mut table.rows) {
+            for cell in &mut row.0 {
+                *cell = self.fill_text(cell.clone())?;
+            }
+        }
+        Ok(table)
+    }
+
+    fn fill(&self, mut text: &str) -> Result<String, TemplateError> {
+        let mut output = String::new();
+        while let Some((before, rest)) = text.split_once("{{") {
+            // Anything that doesn't look like a placeholder is kept as is.
+            let Some((name, after)) = rest.split_once("}}") else {
+                break;
+            };
+            let name = name.trim();
+            let value = self
+                .values
+                .get(name)
+                .cloned()
+                .or_else(|| self.placeholders.value(name))
+                .ok_or_else(|| TemplateError::MissingValue(name.into()))?;
+            output.push_str(before);
+            output.push_str(&value);
+            text = after;
+        }
+        output.push_str(text);
+        Ok(output)
+    }
+}
+
+/// An error instantiating a template.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub(crate) enum TemplateError {
+    #[error("no value for placeholder '{0}'")]
+    MissingValue(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::PlaceholderConfig,
+        markdown::elements::{ListItemType, StyledText, TableRow},
+        style::TextStyle,
+    };
+    use rstest::rstest;
+    use std::path::Path;
+
+    fn values() -> HashMap<String, String> {
+        HashMap::from([("left".into(), "A".into()), ("right".into(), "B".into())])
+    }
+
+    #[rstest]
+    #[case::none("hello", "hello")]
+    #[case::one("{{left}}", "A")]
+    #[case::many("{{left}} vs {{ right }}!", "A vs B!")]
+    #[case::unterminated("{{left", "{{left")]
+    #[case::provided("{{left}} {{env:PATH}}", &format!("A {}", std::env::var("PATH").unwrap()))]
+    fn fill(#[case] input: &str, #[case] expected: &str) {
+        let values = values();
+        let config = PlaceholderConfig { environment: vec!["PATH".into()], ..Default::default() };
+        let placeholders = PlaceholderRegistry::new(Path::new("/tmp"), &config);
+        let filler = PlaceholderFiller { values: &values, placeholders: &placeholders };
+        assert_eq!(filler.fill(input), Ok(expected.into()));
+    }
+
+    #[test]
+    fn instantiate() {
+        let chunks = vec![StyledText::from("{{left}} "), StyledText::new("{{right}}", TextStyle::default().bold())];
+        let text = Text { chunks };
+        let template = Template::new(vec![
+            MarkdownElement::Heading { level: 1, text },
+            MarkdownElement::List(vec![ListItem {
+                depth: 0,
+                contents: Text::from("{{right}}"),
+                item_type: ListItemType::Unordered,
+                blocks: vec![],
+            }]),
+            MarkdownElement::Table(Table { header: TableRow(vec![Text::from("{{left}}")]), rows: vec![] }),
+        ]);
+        let elements = template.instantiate(&values(), &Default::default()).expect("instantiation failed");
+        let MarkdownElement::Heading { text, .. } = &elements[0] else { panic!("not a heading") };
+        assert_eq!(text.to_plain_text(), "A B");
+        assert!(text.chunks[1].style.is_bold());
+        let MarkdownElement::List(items) = &elements[1] else { panic!("not a list") };
+        assert_eq!(items[0].contents.to_plain_text(), "B");
+        let MarkdownElement::Table(table) = &elements[2] else { panic!("not a table") };
+        assert_eq!(table.header.0[0].to_plain_text(), "A");
+    }
+
+    #[test]
+    fn missing_value() {
+        let template = Template::new(vec![MarkdownElement::BlockQuote(vec!["{{potato}}".into()])]);
+        let result = template.instantiate(&values(), &Default::default());
+        assert_eq!(result.unwrap_err(), TemplateError::MissingValue("potato".into()));
+    }
+}
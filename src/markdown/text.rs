@@ -1,16 +1,16 @@
 use super::elements::StyledText;
-use crate::style::TextStyle;
+use crate::{render::media::Image, style::TextStyle};
 use unicode_width::UnicodeWidthChar;
 
 /// A weighted line of text.
 ///
 /// The weight of a character is its given by its width in unicode.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub(crate) struct WeightedLine(Vec<WeightedText>);
 
 impl WeightedLine {
     /// Split this line into chunks of at most `max_length` width.
-    pub(crate) fn split(&self, max_length: usize) -> SplitTextIter {
+    pub(crate) fn split(&self, max_length: usize) -> SplitTextIter<'_> {
         SplitTextIter::new(&self.0, max_length)
     }
 
@@ -20,7 +20,6 @@ impl WeightedLine {
     }
 
     /// Get an iterator to the underlying text chunks.
-    #[cfg(test)]
     pub(crate) fn iter_texts(&self) -> impl Iterator<Item = &WeightedText> {
         self.0.iter()
     }
@@ -46,15 +45,26 @@ struct CharAccumulator {
 }
 
 /// A piece of weighted text.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) struct WeightedText {
     pub(crate) text: StyledText,
     accumulators: Vec<CharAccumulator>,
+    image: Option<Image>,
 }
 
 impl WeightedText {
-    fn to_ref(&self) -> WeightedTextRef {
-        WeightedTextRef { text: &self.text.text, accumulators: &self.accumulators, style: self.text.style.clone() }
+    /// Draw the given image over this text, which should be as wide as the image is meant to be.
+    pub(crate) fn with_image(self, image: Image) -> Self {
+        Self { image: Some(image), ..self }
+    }
+
+    fn to_ref(&self) -> WeightedTextRef<'_> {
+        WeightedTextRef {
+            text: &self.text.text,
+            accumulators: &self.accumulators,
+            style: self.text.style.clone(),
+            image: self.image.as_ref(),
+        }
     }
 
     fn width(&self) -> usize {
@@ -73,7 +83,7 @@ impl From<StyledText> for WeightedText {
             bytes += c.len_utf8();
         }
         accumulators.push(CharAccumulator { width, bytes });
-        Self { text, accumulators }
+        Self { text, accumulators, image: None }
     }
 }
 
@@ -99,6 +109,18 @@ impl<'a> Iterator for SplitTextIter<'a> {
         let mut elements = Vec::new();
         let mut remaining = self.max_length as i64;
         while let Some(current) = self.current.take() {
+            // Images can't be split so they're moved into the next line if they don't fit in this one, and cut
+            // down to the line's width if they don't fit in any.
+            if current.image.is_some() && current.width() as i64 > remaining {
+                if !elements.is_empty() {
+                    self.current = Some(current);
+                    break;
+                }
+                elements.push(current.make_ref(0, current.bytes_until(self.max_length)));
+                self.texts = &self.texts[1..];
+                self.current = self.texts.first().map(WeightedText::to_ref);
+                break;
+            }
             let (head, rest) = current.word_split_at_length(remaining as usize);
             // Prevent splitting a word partially. We do allow this on the first chunk as otherwise
             // a word longer than `max_length` would never be split.
@@ -130,6 +152,7 @@ pub(crate) struct WeightedTextRef<'a> {
     text: &'a str,
     accumulators: &'a [CharAccumulator],
     style: TextStyle,
+    image: Option<&'a Image>,
 }
 
 impl<'a> WeightedTextRef<'a> {
@@ -138,6 +161,11 @@ impl<'a> WeightedTextRef<'a> {
         (self.text, self.style)
     }
 
+    /// Get the image drawn over this text, if any.
+    pub(crate) fn image(&self) -> Option<&'a Image> {
+        self.image
+    }
+
     // Attempts to split this at a word boundary.
     //
     // This will try to consume as many words as possible up to the given maximum length, and
@@ -165,17 +193,22 @@ impl<'a> WeightedTextRef<'a> {
         let leading_char_count = self.text[0..from].chars().count();
         let output_char_count = text.chars().count();
         let character_lengths = &self.accumulators[leading_char_count..leading_char_count + output_char_count + 1];
-        WeightedTextRef { text, accumulators: character_lengths, style: self.style.clone() }
+        WeightedTextRef { text, accumulators: character_lengths, style: self.style.clone(), image: self.image }
     }
 
     fn trim_start(self) -> Self {
+        // The text images are drawn over is made up of spaces that are part of the image.
+        if self.image.is_some() {
+            return self;
+        }
         let text = self.text.trim_start();
         let trimmed = self.text.chars().count() - text.chars().count();
         let accumulators = &self.accumulators[trimmed..];
-        Self { text, accumulators, style: self.style }
+        Self { text, accumulators, style: self.style, image: self.image }
     }
 
-    fn width(&self) -> usize {
+    /// The width of this piece of text.
+    pub(crate) fn width(&self) -> usize {
         let last_width = self.accumulators.last().map(|a| a.width).unwrap_or(0);
         let first_width = self.accumulators.first().map(|a| a.width).unwrap_or(0);
         last_width - first_width
@@ -269,6 +302,23 @@ mod test {
         assert_eq!(rest.text, " string");
     }
 
+    #[test]
+    fn split_images() {
+        let image = Image::new(&std::fs::read("examples/doge.png").unwrap()).expect("invalid image");
+        let text = WeightedLine(vec![
+            WeightedText::from(StyledText::from("hi ")),
+            WeightedText::from(StyledText::from("\u{a0}".repeat(6))).with_image(image),
+        ]);
+        let widths = |max_length| -> Vec<Vec<_>> {
+            let lines = text.split(max_length);
+            lines.map(|line| line.iter().map(|text| (text.width(), text.image().is_some())).collect()).collect()
+        };
+        // Images are never split, even if they're made up of spaces.
+        assert_eq!(widths(8), &[vec![(3, false)], vec![(6, true)]]);
+        // Images wider than the line are cut down to fit in it.
+        assert_eq!(widths(4), &[vec![(3, false)], vec![(4, true)]]);
+    }
+
     #[test]
     fn split_at_full_length() {
         let text = WeightedLine(vec![WeightedText::from(StyledText::from("hello world"))]);
@@ -0,0 +1,191 @@
+//! Shows what's being presented in the terminal multiplexer the presentation runs in.
+//!
+//! This talks to tmux and zellij through their own command line tools, which send commands to
+//! the server the presentation runs under.
+
+use crate::{config::MultiplexerConfig, presentation::Presentation};
+use std::{
+    env,
+    process::{self, Stdio},
+    thread,
+};
+
+/// A terminal multiplexer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Multiplexer {
+    Tmux,
+    Zellij,
+}
+
+impl Multiplexer {
+    /// Check whether we're running inside this multiplexer.
+    fn is_active(&self) -> bool {
+        let variable = match self {
+            Self::Tmux => "TMUX",
+            Self::Zellij => "ZELLIJ",
+        };
+        env::var_os(variable).is_some()
+    }
+
+    /// The command that shows some text in this multiplexer.
+    fn show_command(&self, text: &str) -> Vec<String> {
+        match self {
+            // `#` starts a format in tmux's status line so it needs to be escaped.
+            Self::Tmux => vec!["tmux".into(), "set-option".into(), "status-right".into(), text.replace('#', "##")],
+            Self::Zellij => vec!["zellij".into(), "action".into(), "rename-pane".into(), text.into()],
+        }
+    }
+
+    /// The command that prints whatever [Multiplexer::show_command] is about to replace, if it
+    /// can't be undone otherwise.
+    fn save_command(&self) -> Option<Vec<String>> {
+        match self {
+            // This only prints the session's own value, which is empty if it uses the global one.
+            Self::Tmux => Some(vec!["tmux".into(), "show-options".into(), "-qv".into(), "status-right".into()]),
+            Self::Zellij => None,
+        }
+    }
+
+    /// The command that undoes whatever [Multiplexer::show_command] did, given the output of
+    /// [Multiplexer::save_command].
+    fn restore_command(&self, saved: Option<&str>) -> Vec<String> {
+        let command: &[&str] = match (self, saved) {
+            (Self::Tmux, Some(saved)) if !saved.is_empty() => &["tmux", "set-option", "status-right", saved],
+            (Self::Tmux, _) => &["tmux", "set-option", "-u", "status-right"],
+            (Self::Zellij, _) => &["zellij", "action", "undo-rename-pane"],
+        };
+        command.iter().map(|arg| arg.to_string()).collect()
+    }
+}
+
+/// A multiplexer along with the template used to render what's shown in it.
+struct Target {
+    multiplexer: Multiplexer,
+    template: String,
+    shown: Option<String>,
+    saved: Option<String>,
+}
+
+/// Keeps the multiplexers the presentation runs in up to date with the current slide.
+pub(crate) struct MultiplexerStatus {
+    targets: Vec<Target>,
+}
+
+impl MultiplexerStatus {
+    /// Construct a status for the multiplexers we're running in that have a template configured.
+    pub(crate) fn new(config: &MultiplexerConfig) -> Self {
+        let templates = [(Multiplexer::Tmux, &config.tmux_status), (Multiplexer::Zellij, &config.zellij_pane)];
+        let targets = templates
+            .into_iter()
+            .filter(|(multiplexer, _)| multiplexer.is_active())
+            .filter_map(|(multiplexer, template)| {
+                let template = template.clone()?;
+                let saved = multiplexer.save_command().and_then(|command| Self::output(&command));
+                Some(Target { multiplexer, template, shown: None, saved })
+            })
+            .collect();
+        Self { targets }
+    }
+
+    /// Show the current slide in every multiplexer, if it changed since the last time.
+    pub(crate) fn update(&mut self, presentation: &Presentation) {
+        for target in &mut self.targets {
+            let text = Self::render(&target.template, presentation);
+            if target.shown.as_ref() != Some(&text) {
+                Self::spawn(target.multiplexer.show_command(&text));
+                target.shown = Some(text);
+            }
+        }
+    }
+
+    fn render(template: &str, presentation: &Presentation) -> String {
+        let current_slide = presentation.current_slide_index() + 1;
+        let total_slides = presentation.iter_slides().count();
+        let metadata = presentation.current_slide().metadata();
+        let title = metadata.title.as_deref().unwrap_or_default();
+        // Status lines are a single line long.
+        let notes = metadata.notes.iter().flat_map(|note| note.lines()).collect::<Vec<_>>().join(" | ");
+        template
+            .replace("{title}", title)
+            .replace("{notes}", &notes)
+            .replace("{current_slide}", &current_slide.to_string())
+            .replace("{total_slides}", &total_slides.to_string())
+    }
+
+    fn command(command: &[String]) -> process::Command {
+        let mut process = process::Command::new(&command[0]);
+        process.args(&command[1..]).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        process
+    }
+
+    fn output(command: &[String]) -> Option<String> {
+        let output = Self::command(command).stdout(Stdio::piped()).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let output = String::from_utf8(output.stdout).ok()?;
+        Some(output.trim_end_matches('\n').to_string())
+    }
+
+    fn spawn(command: Vec<String>) {
+        if let Ok(mut child) = Self::command(&command).spawn() {
+            // Reap the process in the background so we don't leave zombies around.
+            thread::spawn(move || child.wait());
+        }
+    }
+}
+
+impl Drop for MultiplexerStatus {
+    fn drop(&mut self) {
+        for target in &self.targets {
+            if target.shown.is_some() {
+                // Wait for these as the process is likely about to exit.
+                let _ = Self::command(&target.multiplexer.restore_command(target.saved.as_deref())).status();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::presentation::{Slide, SlideChunk, SlideMetadata};
+    use rstest::rstest;
+
+    fn presentation() -> Presentation {
+        let metadata = |title: &str, notes: &[&str]| SlideMetadata {
+            title: Some(title.into()),
+            notes: notes.iter().map(|note| note.to_string()).collect(),
+            ..Default::default()
+        };
+        let slides = vec![
+            Slide::new(vec![SlideChunk::default()], vec![]).with_metadata(metadata("Intro", &[])),
+            Slide::new(vec![SlideChunk::default()], vec![]).with_metadata(metadata("Demo", &["slow down", "a\nb"])),
+        ];
+        let mut presentation = Presentation::new(slides);
+        presentation.jump_next_slide();
+        presentation
+    }
+
+    #[rstest]
+    #[case::position("{title} {current_slide}/{total_slides}", "Demo 2/2")]
+    #[case::notes("{notes}", "slow down | a | b")]
+    fn render(#[case] template: &str, #[case] expected: &str) {
+        assert_eq!(MultiplexerStatus::render(template, &presentation()), expected);
+    }
+
+    #[test]
+    fn tmux_escaping() {
+        let command = Multiplexer::Tmux.show_command("#1 slide");
+        assert_eq!(command, &["tmux", "set-option", "status-right", "##1 slide"]);
+    }
+
+    #[rstest]
+    #[case::tmux_global(Multiplexer::Tmux, None, &["tmux", "set-option", "-u", "status-right"])]
+    #[case::tmux_empty(Multiplexer::Tmux, Some(""), &["tmux", "set-option", "-u", "status-right"])]
+    #[case::tmux_session(Multiplexer::Tmux, Some("#H %H:%M"), &["tmux", "set-option", "status-right", "#H %H:%M"])]
+    #[case::zellij(Multiplexer::Zellij, None, &["zellij", "action", "undo-rename-pane"])]
+    fn restore(#[case] multiplexer: Multiplexer, #[case] saved: Option<&str>, #[case] expected: &[&str]) {
+        assert_eq!(multiplexer.restore_command(saved), expected);
+    }
+}
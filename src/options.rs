@@ -0,0 +1,110 @@
+//! The presentation level options that can be set in more than one place.
+//!
+//! These can be set via command line parameters, in a presentation's front matter, and in the
+//! config file, in that order of precedence.
+
+use crate::presenter::{BoundaryCue, ImageProtocol};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::num::NonZeroU64;
+
+/// Presentation level options, where every one that isn't set is taken from somewhere else.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PresentationOptions {
+    /// The cue to emit when reaching the last step of a slide or the final slide.
+    #[serde(default)]
+    pub boundary_cue: Option<BoundaryCue>,
+
+    /// The active profile, which decides which conditional blocks of content are included.
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// Whether executable code blocks show what they would run instead of running.
+    #[serde(default)]
+    pub dry_run_exec: Option<bool>,
+
+    /// Whether to use presentation mode, where the presentation isn't reloaded when it changes.
+    #[serde(default)]
+    pub present: Option<bool>,
+
+    /// The way images are drawn.
+    #[serde(default)]
+    pub image_protocol: Option<ImageProtocol>,
+
+    /// The number of seconds to wait on every step before moving into the next one.
+    #[serde(default)]
+    pub auto_advance: Option<NonZeroU64>,
+
+    /// Whether to use the high contrast theme instead of the presentation's own one.
+    #[serde(default)]
+    pub high_contrast: Option<bool>,
+}
+
+impl PresentationOptions {
+    fn or(self, other: Self) -> Self {
+        Self {
+            boundary_cue: self.boundary_cue.or(other.boundary_cue),
+            profile: self.profile.or(other.profile),
+            dry_run_exec: self.dry_run_exec.or(other.dry_run_exec),
+            present: self.present.or(other.present),
+            image_protocol: self.image_protocol.or(other.image_protocol),
+            auto_advance: self.auto_advance.or(other.auto_advance),
+            high_contrast: self.high_contrast.or(other.high_contrast),
+        }
+    }
+}
+
+/// The options set outside of a presentation, which the ones in its front matter are resolved against.
+#[derive(Clone, Debug, Default)]
+pub struct ExternalOptions {
+    /// The options set via command line parameters, which take precedence over the front matter.
+    pub cli: PresentationOptions,
+
+    /// The options set in the config file, which the front matter takes precedence over.
+    pub config: PresentationOptions,
+}
+
+impl ExternalOptions {
+    /// Resolve the options to use for a presentation given the ones in its front matter.
+    ///
+    /// Every option is taken from the command line if it's set there, then from the front matter,
+    /// and then from the config file.
+    pub(crate) fn resolve(&self, front_matter: &PresentationOptions) -> PresentationOptions {
+        self.cli.clone().or(front_matter.clone()).or(self.config.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let cli = PresentationOptions { profile: Some("cli".into()), ..Default::default() };
+        let front_matter = PresentationOptions {
+            profile: Some("front matter".into()),
+            boundary_cue: Some(BoundaryCue::Bell),
+            present: Some(false),
+            ..Default::default()
+        };
+        let config = PresentationOptions {
+            profile: Some("config".into()),
+            boundary_cue: Some(BoundaryCue::Notification),
+            dry_run_exec: Some(true),
+            present: Some(true),
+            high_contrast: Some(true),
+            ..Default::default()
+        };
+        let options = ExternalOptions { cli, config }.resolve(&front_matter);
+        let expected = PresentationOptions {
+            profile: Some("cli".into()),
+            boundary_cue: Some(BoundaryCue::Bell),
+            dry_run_exec: Some(true),
+            present: Some(false),
+            high_contrast: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(options, expected);
+    }
+}
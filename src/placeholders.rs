@@ -0,0 +1,280 @@
+//! Placeholders whose values come from the environment, like `{date}` or `{git_branch}`.
+//!
+//! These can be used in footers and in templates. Every source of values is a
+//! [PlaceholderProvider], and users can register their own ones that run commands via the
+//! configuration file.
+
+use crate::{
+    clock::{Clock, SystemClock},
+    config::PlaceholderConfig,
+    execute::CodeExecuter,
+};
+use std::{
+    cell::OnceCell,
+    collections::HashMap,
+    env,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
+/// A source of placeholder values.
+pub(crate) trait PlaceholderProvider: Debug {
+    /// Get the value of a placeholder, if this provider knows about it.
+    fn value(&self, name: &str) -> Option<String>;
+}
+
+impl PlaceholderProvider for HashMap<String, String> {
+    fn value(&self, name: &str) -> Option<String> {
+        self.get(name).cloned()
+    }
+}
+
+/// The providers available when building a presentation.
+///
+/// Providers are asked for a value in the order they were registered in, and the first one that
+/// has one wins.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PlaceholderRegistry {
+    providers: Vec<Rc<dyn PlaceholderProvider>>,
+}
+
+impl PlaceholderRegistry {
+    /// Construct a registry with the built in providers and the ones set in the configuration.
+    ///
+    /// Commands, as well as `git`, run in the given directory.
+    pub(crate) fn new(directory: &Path, config: &PlaceholderConfig) -> Self {
+        let mut registry = Self::default();
+        registry.register(Rc::new(DateTimeProvider { clock: Arc::new(SystemClock) }));
+        registry.register(Rc::new(EnvironmentProvider { allowed: config.environment.clone() }));
+        registry.register(Rc::new(HostnameProvider));
+        registry.register(Rc::new(GitProvider { directory: directory.into(), ..Default::default() }));
+        for (name, command) in &config.commands {
+            let (name, command) = (name.clone(), command.clone());
+            let provider = CommandProvider { name, command, directory: directory.into(), ..Default::default() };
+            registry.register(Rc::new(provider));
+        }
+        registry
+    }
+
+    /// Add a provider, which is asked for values after every other one.
+    pub(crate) fn register(&mut self, provider: Rc<dyn PlaceholderProvider>) {
+        self.providers.push(provider);
+    }
+
+    /// Get the value of a placeholder.
+    pub(crate) fn value(&self, name: &str) -> Option<String> {
+        self.providers.iter().find_map(|provider| provider.value(name))
+    }
+
+    /// Replace every `{name}` placeholder in a template.
+    ///
+    /// The given values take precedence over the ones provided by this registry, and placeholders
+    /// nobody knows about are kept as is.
+    pub(crate) fn render(&self, template: &str, values: &dyn PlaceholderProvider) -> String {
+        let mut output = String::new();
+        let mut text = template;
+        while let Some((before, rest)) = text.split_once('{') {
+            output.push_str(before);
+            let value = rest.split_once('}').and_then(|(name, after)| {
+                let value = values.value(name).or_else(|| self.value(name))?;
+                Some((value, after))
+            });
+            match value {
+                Some((value, after)) => {
+                    output.push_str(&value);
+                    text = after;
+                }
+                None => {
+                    output.push('{');
+                    text = rest;
+                }
+            };
+        }
+        output.push_str(text);
+        output
+    }
+}
+
+/// Provides `{date}` and `{time}`, in UTC.
+#[derive(Debug)]
+struct DateTimeProvider {
+    clock: Arc<dyn Clock>,
+}
+
+impl PlaceholderProvider for DateTimeProvider {
+    fn value(&self, name: &str) -> Option<String> {
+        let now = self.clock.now();
+        match name {
+            "date" => Some(now.date()),
+            "time" => Some(now.time()),
+            _ => None,
+        }
+    }
+}
+
+/// Provides `{env:NAME}` for the environment variables that are allowed to be used.
+#[derive(Debug)]
+struct EnvironmentProvider {
+    allowed: Vec<String>,
+}
+
+impl PlaceholderProvider for EnvironmentProvider {
+    fn value(&self, name: &str) -> Option<String> {
+        let name = name.strip_prefix("env:")?;
+        if !self.allowed.iter().any(|allowed| allowed == name) {
+            return None;
+        }
+        env::var(name).ok()
+    }
+}
+
+/// Provides `{hostname}`.
+#[derive(Debug)]
+struct HostnameProvider;
+
+impl PlaceholderProvider for HostnameProvider {
+    fn value(&self, name: &str) -> Option<String> {
+        if name != "hostname" {
+            return None;
+        }
+        ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .into_iter()
+            .find_map(|path| fs::read_to_string(path).ok())
+            .or_else(|| env::var("HOSTNAME").ok())
+            .map(|hostname| hostname.trim().to_string())
+    }
+}
+
+/// Provides values describing the git repository a directory is in.
+///
+/// These are `{git_branch}`, the branch checked out, `{git_sha}`, the abbreviated hash of the
+/// commit checked out, and `{git_dirty}`, which is `-dirty` if there are uncommitted changes to
+/// tracked files and empty otherwise, like `git describe --dirty` does.
+#[derive(Debug, Default)]
+struct GitProvider {
+    directory: PathBuf,
+    // Footers are rendered over and over again so only ask git once.
+    branch: OnceCell<Option<String>>,
+    sha: OnceCell<Option<String>>,
+    dirty: OnceCell<Option<String>>,
+}
+
+impl GitProvider {
+    fn run(&self, command: &str) -> Option<String> {
+        let output = CodeExecuter::run_command(command, &self.directory, None).ok()?;
+        Some(output.trim().to_string())
+    }
+}
+
+impl PlaceholderProvider for GitProvider {
+    fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "git_branch" => self.branch.get_or_init(|| self.run("git rev-parse --abbrev-ref HEAD")).clone(),
+            "git_sha" => self.sha.get_or_init(|| self.run("git rev-parse --short HEAD")).clone(),
+            "git_dirty" => self
+                .dirty
+                .get_or_init(|| {
+                    let status = self.run("git status --porcelain --untracked-files=no")?;
+                    Some(if status.is_empty() { String::new() } else { "-dirty".into() })
+                })
+                .clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Provides a placeholder whose value is the output of a command set in the configuration.
+#[derive(Debug, Default)]
+struct CommandProvider {
+    name: String,
+    command: String,
+    directory: PathBuf,
+    output: OnceCell<Option<String>>,
+}
+
+impl PlaceholderProvider for CommandProvider {
+    fn value(&self, name: &str) -> Option<String> {
+        if name != self.name {
+            return None;
+        }
+        self.output
+            .get_or_init(|| {
+                let output = CodeExecuter::run_command(&self.command, &self.directory, None).ok()?;
+                Some(output.trim().to_string())
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{FixedClock, UtcTime};
+    use rstest::rstest;
+
+    fn registry() -> PlaceholderRegistry {
+        let config = PlaceholderConfig {
+            commands: [("greeting".to_string(), "echo hello".to_string())].into(),
+            environment: vec!["PATH".into()],
+        };
+        PlaceholderRegistry::new(Path::new("/tmp"), &config)
+    }
+
+    #[rstest]
+    #[case::plain("hi", "hi")]
+    #[case::value("{current_slide}/3", "2/3")]
+    #[case::command("{greeting} {current_slide}", "hello 2")]
+    #[case::unknown("{potato}", "{potato}")]
+    #[case::unterminated("{greeting", "{greeting")]
+    #[case::braces_around("{{greeting}}", "{hello}")]
+    fn render(#[case] template: &str, #[case] expected: &str) {
+        let values = HashMap::from([("current_slide".to_string(), "2".to_string())]);
+        assert_eq!(registry().render(template, &values), expected);
+    }
+
+    #[test]
+    fn environment() {
+        let path = env::var("PATH").expect("no PATH");
+        assert_eq!(registry().value("env:PATH"), Some(path));
+        assert_eq!(registry().value("PATH"), None);
+
+        // Only the variables that are allowed can be used.
+        assert!(env::var("HOME").is_ok());
+        assert_eq!(registry().value("env:HOME"), None);
+    }
+
+    #[test]
+    fn git() {
+        let directory = tempfile::TempDir::new().expect("failed to create directory");
+        let provider = || GitProvider { directory: directory.path().into(), ..Default::default() };
+        assert_eq!(provider().value("git_sha"), None);
+
+        let git = |command: &str| CodeExecuter::run_command(command, directory.path(), None).expect("git failed");
+        fs::write(directory.path().join("slides.md"), "# hi").unwrap();
+        git("git init -q && git add slides.md");
+        git("git -c user.name=test -c user.email=test@example.com commit -q -m initial");
+        let sha = git("git rev-parse --short HEAD");
+        assert_eq!(provider().value("git_sha").as_deref(), Some(sha.trim()));
+        assert_eq!(provider().value("git_dirty").as_deref(), Some(""));
+
+        // Files git doesn't know about, like build outputs, don't count as changes.
+        fs::write(directory.path().join("slides.pdf"), "").unwrap();
+        assert_eq!(provider().value("git_dirty").as_deref(), Some(""));
+
+        fs::write(directory.path().join("slides.md"), "# bye").unwrap();
+        assert_eq!(provider().value("git_dirty").as_deref(), Some("-dirty"));
+    }
+
+    #[rstest]
+    #[case::epoch(0, "1970-01-01", "00:00")]
+    #[case::leap_day(951825600, "2000-02-29", "12:00")]
+    #[case::end_of_year(1704067199, "2023-12-31", "23:59")]
+    fn date_time(#[case] seconds: u64, #[case] date: &str, #[case] time: &str) {
+        let provider = DateTimeProvider { clock: Arc::new(FixedClock(UtcTime(seconds))) };
+        assert_eq!(provider.value("date").as_deref(), Some(date));
+        assert_eq!(provider.value("time").as_deref(), Some(time));
+    }
+}
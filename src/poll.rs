@@ -0,0 +1,337 @@
+//! Audience polls.
+
+use crate::{
+    markdown::{
+        elements::StyledText,
+        text::{WeightedLine, WeightedText},
+    },
+    presentation::{AsRenderOperations, RenderOnDemand, RenderOnDemandState, RenderOperation},
+    render::{
+        chart::{Bar, BarChart},
+        properties::WindowSize,
+    },
+    style::TextStyle,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+/// The definition of a poll, as written in a presentation.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PollDefinition {
+    /// The question being asked.
+    pub(crate) question: String,
+
+    /// The options the audience can vote for.
+    pub(crate) options: Vec<String>,
+}
+
+#[derive(Debug)]
+struct PollState {
+    definition: PollDefinition,
+    slide: usize,
+    votes: Vec<u64>,
+    voters: HashSet<IpAddr>,
+    status: PollStatus,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PollStatus {
+    NotOpened,
+    Open,
+    Closed,
+}
+
+/// A poll the audience can vote on.
+///
+/// This is cheap to clone and every clone refers to the same poll.
+#[derive(Clone, Debug)]
+pub(crate) struct Poll(Arc<Mutex<PollState>>);
+
+impl Poll {
+    /// Construct a new, closed, poll that lives in the given slide.
+    pub(crate) fn new(definition: PollDefinition, slide: usize) -> Self {
+        let votes = vec![0; definition.options.len()];
+        let voters = HashSet::new();
+        Self(Arc::new(Mutex::new(PollState { definition, slide, votes, voters, status: PollStatus::NotOpened })))
+    }
+
+    /// Open this poll so it starts accepting votes.
+    ///
+    /// Polls that were already closed aren't opened again.
+    pub(crate) fn open(&self) {
+        let mut state = self.0.lock().expect("lock poisoned");
+        if state.status == PollStatus::NotOpened {
+            state.status = PollStatus::Open;
+        }
+    }
+
+    /// Close this poll so it stops accepting votes.
+    ///
+    /// Returns false if the poll wasn't open.
+    pub(crate) fn close(&self) -> bool {
+        let mut state = self.0.lock().expect("lock poisoned");
+        if state.status != PollStatus::Open {
+            return false;
+        }
+        state.status = PollStatus::Closed;
+        true
+    }
+
+    fn status(&self) -> PollStatus {
+        self.0.lock().expect("lock poisoned").status
+    }
+
+    /// Vote for an option.
+    ///
+    /// Each voter, as identified by their address, only gets to vote once. Returns false if the poll
+    /// isn't open, the option doesn't exist, or the voter already voted.
+    pub(crate) fn vote(&self, option: usize, voter: IpAddr) -> bool {
+        let mut state = self.0.lock().expect("lock poisoned");
+        if state.status != PollStatus::Open || option >= state.votes.len() || !state.voters.insert(voter) {
+            return false;
+        }
+        state.votes[option] += 1;
+        true
+    }
+
+    /// Get the votes for every option.
+    pub(crate) fn votes(&self) -> Vec<u64> {
+        self.0.lock().expect("lock poisoned").votes.clone()
+    }
+
+    fn definition(&self) -> PollDefinition {
+        self.0.lock().expect("lock poisoned").definition.clone()
+    }
+}
+
+/// A poll that's currently accepting votes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct OpenPoll {
+    /// The poll's identifier.
+    pub(crate) id: usize,
+
+    /// The question being asked.
+    pub(crate) question: String,
+
+    /// The options the audience can vote for.
+    pub(crate) options: Vec<String>,
+}
+
+/// Keeps track of all the polls in a presentation.
+///
+/// This is cheap to clone and every clone refers to the same set of polls.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PollRegistry {
+    polls: Arc<Mutex<Vec<Poll>>>,
+}
+
+impl PollRegistry {
+    /// Replace the registered polls.
+    ///
+    /// Polls that existed before and that haven't changed keep their votes so reloading a
+    /// presentation doesn't lose them.
+    pub(crate) fn replace(&self, polls: Vec<Poll>) {
+        let mut current = self.polls.lock().expect("lock poisoned");
+        for poll in &polls {
+            let definition = poll.definition();
+            let existing = current
+                .iter()
+                .find(|existing| !Arc::ptr_eq(&existing.0, &poll.0) && existing.definition() == definition);
+            if let Some(existing) = existing {
+                let (votes, voters, status) = {
+                    let existing = existing.0.lock().expect("lock poisoned");
+                    (existing.votes.clone(), existing.voters.clone(), existing.status)
+                };
+                let mut state = poll.0.lock().expect("lock poisoned");
+                state.votes = votes;
+                state.voters = voters;
+                state.status = status;
+            }
+        }
+        *current = polls;
+    }
+
+    /// Vote for an option in a poll.
+    ///
+    /// Returns false if the poll doesn't exist or the vote isn't accepted, see [Poll::vote].
+    pub(crate) fn vote(&self, id: usize, option: usize, voter: IpAddr) -> bool {
+        let polls = self.polls.lock().expect("lock poisoned");
+        polls.get(id).map(|poll| poll.vote(option, voter)).unwrap_or(false)
+    }
+
+    /// Get the polls in a slide that are accepting votes.
+    pub(crate) fn open_polls(&self, slide: usize) -> Vec<OpenPoll> {
+        let polls = self.polls.lock().expect("lock poisoned");
+        let mut output = Vec::new();
+        for (id, poll) in polls.iter().enumerate() {
+            let state = poll.0.lock().expect("lock poisoned");
+            if state.status == PollStatus::Open && state.slide == slide {
+                let PollDefinition { question, options } = state.definition.clone();
+                output.push(OpenPoll { id, question, options });
+            }
+        }
+        output
+    }
+}
+
+/// Renders a poll as a bar chart of its votes.
+///
+/// The poll is opened when the slide's widgets are rendered and the chart is updated live from
+/// then on, until the poll is closed by stopping them.
+#[derive(Debug)]
+pub(crate) struct PollOperation {
+    poll: Poll,
+}
+
+impl PollOperation {
+    /// Construct a new poll operation.
+    pub(crate) fn new(poll: Poll) -> Self {
+        Self { poll }
+    }
+
+    fn render_line(text: WeightedLine) -> [RenderOperation; 2] {
+        [RenderOperation::RenderText { line: text, alignment: Default::default() }, RenderOperation::RenderLineBreak]
+    }
+}
+
+impl AsRenderOperations for PollOperation {
+    fn as_render_operations(&self, _dimensions: &WindowSize) -> Vec<RenderOperation> {
+        let PollDefinition { question, options } = self.poll.definition();
+        let votes = self.poll.votes();
+        let total_votes: u64 = votes.iter().sum();
+        let bars = options.into_iter().zip(votes).map(|(label, value)| Bar { label, value }).collect();
+
+        let question = WeightedText::from(StyledText::new(question, TextStyle::default().bold()));
+        let mut operations = Vec::new();
+        operations.extend(Self::render_line(vec![question].into()));
+        operations.extend([
+            RenderOperation::RenderLineBreak,
+            RenderOperation::RenderCustom(Rc::new(BarChart::new(bars))),
+            RenderOperation::RenderLineBreak,
+        ]);
+        let status = match self.poll.status() {
+            PollStatus::NotOpened => "poll not open yet".to_string(),
+            PollStatus::Open => format!("{total_votes} votes"),
+            PollStatus::Closed => format!("poll closed, {total_votes} votes"),
+        };
+        let status = WeightedText::from(StyledText::new(status, TextStyle::default().italics()));
+        operations.extend(Self::render_line(vec![status].into()));
+        operations
+    }
+
+    fn diffable_content(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl RenderOnDemand for PollOperation {
+    fn start_render(&self) -> bool {
+        if self.poll.status() != PollStatus::NotOpened {
+            return false;
+        }
+        self.poll.open();
+        true
+    }
+
+    fn poll_state(&self) -> RenderOnDemandState {
+        // Votes can come in at any time so an open poll is only done rendering once it's closed.
+        match self.poll.status() {
+            PollStatus::NotOpened => RenderOnDemandState::NotStarted,
+            PollStatus::Open => RenderOnDemandState::Rendering,
+            PollStatus::Closed => RenderOnDemandState::Rendered,
+        }
+    }
+
+    fn cancel_render(&self) -> bool {
+        self.poll.close()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const VOTER: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn definition(question: &str) -> PollDefinition {
+        PollDefinition { question: question.into(), options: vec!["a".into(), "b".into()] }
+    }
+
+    #[test]
+    fn vote_closed_poll() {
+        let poll = Poll::new(definition("question"), 0);
+        assert!(!poll.vote(0, VOTER));
+        poll.open();
+        assert!(!poll.vote(2, VOTER));
+        assert!(poll.vote(0, VOTER));
+        assert_eq!(poll.votes(), &[1, 0]);
+    }
+
+    #[test]
+    fn vote_once() {
+        let poll = Poll::new(definition("question"), 0);
+        poll.open();
+        assert!(poll.vote(0, VOTER));
+        assert!(!poll.vote(1, VOTER));
+        assert!(poll.vote(1, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(poll.votes(), &[1, 1]);
+    }
+
+    #[test]
+    fn registry_vote() {
+        let registry = PollRegistry::default();
+        let poll = Poll::new(definition("question"), 3);
+        registry.replace(vec![poll.clone()]);
+        assert!(!registry.vote(0, 1, VOTER));
+        assert!(registry.open_polls(3).is_empty());
+
+        poll.open();
+        assert!(registry.vote(0, 1, VOTER));
+        assert!(!registry.vote(1, 0, VOTER));
+        let expected = OpenPoll { id: 0, question: "question".into(), options: vec!["a".into(), "b".into()] };
+        assert_eq!(registry.open_polls(3), &[expected]);
+        assert!(registry.open_polls(0).is_empty());
+    }
+
+    #[test]
+    fn replace_keeps_votes() {
+        let registry = PollRegistry::default();
+        let poll = Poll::new(definition("question"), 0);
+        poll.open();
+        poll.vote(1, VOTER);
+        registry.replace(vec![poll]);
+
+        let same = Poll::new(definition("question"), 0);
+        let different = Poll::new(definition("other"), 0);
+        registry.replace(vec![same.clone(), different.clone()]);
+        assert_eq!(same.status(), PollStatus::Open);
+        assert_eq!(same.votes(), &[0, 1]);
+        assert!(!same.vote(0, VOTER));
+        assert_eq!(different.status(), PollStatus::NotOpened);
+        assert_eq!(different.votes(), &[0, 0]);
+    }
+
+    #[test]
+    fn start_render() {
+        let operation = PollOperation::new(Poll::new(definition("question"), 0));
+        assert!(matches!(operation.poll_state(), RenderOnDemandState::NotStarted));
+        assert!(operation.start_render());
+        assert!(!operation.start_render());
+        assert!(matches!(operation.poll_state(), RenderOnDemandState::Rendering));
+
+        assert!(operation.cancel_render());
+        assert!(matches!(operation.poll_state(), RenderOnDemandState::Rendered));
+        assert!(!operation.poll.vote(0, VOTER));
+        // Closed polls aren't opened again.
+        assert!(!operation.start_render());
+        assert!(!operation.cancel_render());
+    }
+}
@@ -1,22 +1,90 @@
 use crate::{
     markdown::text::WeightedLine,
-    render::{media::Image, properties::WindowSize},
+    options::PresentationOptions,
+    render::{canvas::Canvas, media::Image, properties::WindowSize},
+    schema,
     style::Colors,
-    theme::{Alignment, Margin, PresentationTheme},
+    theme::{Alignment, Margin, PresentationTheme, SplitBackground},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{self, Debug},
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+    time::{Duration, Instant},
 };
-use serde::Deserialize;
-use std::{fmt::Debug, rc::Rc};
 
 /// A presentation.
 pub(crate) struct Presentation {
     slides: Vec<Slide>,
     current_slide_index: usize,
+    export_profile: Option<ExportProfile>,
+    minimum_size: Option<MinimumSize>,
+    warnings: Vec<SlideWarning>,
+    options: PresentationOptions,
 }
 
 impl Presentation {
     /// Construct a new presentation.
     pub(crate) fn new(slides: Vec<Slide>) -> Self {
-        Self { slides, current_slide_index: 0 }
+        Self {
+            slides,
+            current_slide_index: 0,
+            export_profile: None,
+            minimum_size: None,
+            warnings: Vec::new(),
+            options: Default::default(),
+        }
+    }
+
+    /// Set the options in this presentation's front matter.
+    pub(crate) fn with_options(mut self, options: PresentationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Get the options in this presentation's front matter, which still have to be resolved against the ones set
+    /// anywhere else.
+    pub(crate) fn options(&self) -> &PresentationOptions {
+        &self.options
+    }
+
+    /// Set the problems found when building this presentation that didn't stop it from being built.
+    pub(crate) fn with_warnings(mut self, warnings: Vec<SlideWarning>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Get the warnings for the current slide.
+    pub(crate) fn current_slide_warnings(&self) -> impl Iterator<Item = &SlideWarning> {
+        let slide = self.current_slide_index + 1;
+        self.warnings.iter().filter(move |warning| warning.slide == slide)
+    }
+
+    /// Set the profile to use when exporting this presentation.
+    pub(crate) fn with_export_profile(mut self, profile: Option<ExportProfile>) -> Self {
+        self.export_profile = profile;
+        self
+    }
+
+    /// Get the profile to use when exporting this presentation, if any.
+    pub(crate) fn export_profile(&self) -> Option<&ExportProfile> {
+        self.export_profile.as_ref()
+    }
+
+    /// Set the smallest terminal this presentation can be presented in.
+    pub(crate) fn with_minimum_size(mut self, size: Option<MinimumSize>) -> Self {
+        self.minimum_size = size;
+        self
+    }
+
+    /// Get the smallest terminal this presentation can be presented in, if it declares one.
+    pub(crate) fn minimum_size(&self) -> Option<&MinimumSize> {
+        self.minimum_size.as_ref()
     }
 
     /// Iterate the slides in this presentation.
@@ -40,6 +108,11 @@ impl Presentation {
         self.current_slide_index
     }
 
+    /// Find the index of the slide with the given id.
+    pub(crate) fn find_slide(&self, id: &SlideId) -> Option<usize> {
+        self.slides.iter().position(|slide| slide.metadata.id.as_ref() == Some(id))
+    }
+
     /// Jump to the next slide.
     pub(crate) fn jump_next_slide(&mut self) -> bool {
         let current_slide = self.current_slide_mut();
@@ -105,6 +178,39 @@ impl Presentation {
         self.current_slide().current_chunk_index()
     }
 
+    /// Get the exact position the presentation is at.
+    pub(crate) fn position(&self) -> PresentationPosition {
+        let slide = self.current_slide();
+        PresentationPosition {
+            slide: self.current_slide_index,
+            chunk: slide.current_chunk_index(),
+            step: slide.current_chunk().current_step(),
+        }
+    }
+
+    /// Jump to a position previously returned by [Presentation::position].
+    pub(crate) fn jump_position(&mut self, position: PresentationPosition) -> bool {
+        if !self.jump_slide(position.slide) {
+            return false;
+        }
+        let slide = self.current_slide_mut();
+        slide.jump_chunk(position.chunk);
+        slide.current_chunk().jump_step(position.step);
+        true
+    }
+
+    /// Check whether the current slide is showing its last step.
+    ///
+    /// When this is true, moving forward will jump into the next slide.
+    pub(crate) fn is_current_slide_last_step(&self) -> bool {
+        self.current_slide().is_last_step()
+    }
+
+    /// Check whether we're in the last slide.
+    pub(crate) fn is_last_slide(&self) -> bool {
+        self.current_slide_index == self.slides.len().saturating_sub(1)
+    }
+
     /// Render all widgets in this slide.
     pub(crate) fn render_slide_widgets(&mut self) -> bool {
         let slide = self.current_slide_mut();
@@ -117,6 +223,77 @@ impl Presentation {
         any_rendered
     }
 
+    /// Replace the current slide with the one in the same position in another presentation, staying
+    /// at the same step within it.
+    ///
+    /// Returns the numbers of the code blocks that had already run in the current slide, which are
+    /// expected to run again in the new one, or `None` if the other presentation doesn't have this slide.
+    pub(crate) fn replace_current_slide(&mut self, other: Presentation) -> Option<Vec<usize>> {
+        let position = self.position();
+        let slide = other.slides.into_iter().nth(self.current_slide_index)?;
+        let ran: Vec<_> = self
+            .current_slide()
+            .metadata
+            .code_blocks
+            .iter()
+            .map(|block| {
+                let state = block.execution.as_ref().map(|execution| execution.poll_state());
+                !matches!(state, None | Some(RenderOnDemandState::NotStarted))
+            })
+            .collect();
+        self.slides[self.current_slide_index] = slide;
+        self.jump_position(position);
+        Some(ran.into_iter().enumerate().filter(|(_, ran)| *ran).map(|(index, _)| index + 1).collect())
+    }
+
+    /// Run one of the code blocks in the current slide, by its 1 based number.
+    ///
+    /// Code blocks that already ran are run again.
+    pub(crate) fn run_code_block(&self, number: usize) -> bool {
+        let code_blocks = &self.current_slide().metadata.code_blocks;
+        let execution = number.checked_sub(1).and_then(|index| code_blocks.get(index)?.execution.as_ref());
+        execution.is_some_and(|execution| execution.restart_render())
+    }
+
+    /// Scroll the output of one of the code blocks in the current slide, by its 1 based number.
+    ///
+    /// When no number is given, this is the last executable code block among the visible chunks.
+    pub(crate) fn scroll_code_output(&self, number: Option<usize>, rows: isize) -> bool {
+        let slide = self.current_slide();
+        let execution = match number {
+            Some(number) => {
+                number.checked_sub(1).and_then(|index| slide.metadata.code_blocks.get(index)?.execution.as_ref())
+            }
+            None => slide.visible_code_blocks().filter_map(|block| block.execution.as_ref()).last(),
+        };
+        execution.is_some_and(|execution| execution.scroll(rows))
+    }
+
+    /// Stop every widget in a slide that's still rendering, like code that's still running.
+    pub(crate) fn cancel_slide_widgets(&self, slide_index: usize) -> bool {
+        let Some(slide) = self.slides.get(slide_index) else {
+            return false;
+        };
+        let mut any_cancelled = false;
+        for operation in slide.iter_operations() {
+            if let RenderOperation::RenderOnDemand(operation) = operation {
+                any_cancelled |= operation.cancel_render();
+            }
+        }
+        any_cancelled
+    }
+
+    /// Move every animated image in the current slide to its next frame if it's time to.
+    ///
+    /// Returns whether any of them changed, which means the slide needs to be drawn again.
+    pub(crate) fn advance_animations(&self, now: Instant) -> bool {
+        let mut advanced = false;
+        for image in self.current_slide().iter_images() {
+            advanced |= image.advance(now);
+        }
+        advanced
+    }
+
     /// Poll every widget in the current slide and check whether they're rendered.
     pub(crate) fn widgets_rendered(&mut self) -> bool {
         let slide = self.current_slide_mut();
@@ -142,18 +319,54 @@ impl Presentation {
 pub(crate) struct Slide {
     chunks: Vec<SlideChunk>,
     footer: Vec<RenderOperation>,
+    metadata: SlideMetadata,
     visible_chunks: usize,
 }
 
 impl Slide {
     pub(crate) fn new(chunks: Vec<SlideChunk>, footer: Vec<RenderOperation>) -> Self {
-        Self { chunks, footer, visible_chunks: 1 }
+        Self { chunks, footer, metadata: Default::default(), visible_chunks: 1 }
+    }
+
+    /// Set the metadata for this slide.
+    pub(crate) fn with_metadata(mut self, metadata: SlideMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Get this slide's metadata.
+    pub(crate) fn metadata(&self) -> &SlideMetadata {
+        &self.metadata
     }
 
     pub(crate) fn iter_operations(&self) -> impl Iterator<Item = &RenderOperation> + Clone {
         self.chunks.iter().take(self.visible_chunks).flat_map(|chunk| chunk.operations.iter()).chain(self.footer.iter())
     }
 
+    /// Iterate the visible operations, with the given ones placed after every chunk that's followed by a pause.
+    pub(crate) fn iter_operations_with_pause_markers<'a>(
+        &'a self,
+        marker: &'a [RenderOperation],
+    ) -> impl Iterator<Item = &'a RenderOperation> {
+        let last_chunk = self.chunks.len().saturating_sub(1);
+        self.chunks
+            .iter()
+            .take(self.visible_chunks)
+            .enumerate()
+            .flat_map(move |(index, chunk)| {
+                let marker = if index < last_chunk { marker } else { &[] };
+                chunk.operations.iter().chain(marker)
+            })
+            .chain(self.footer.iter())
+    }
+
+    /// Iterate the operations in every chunk, whether they're visible or not.
+    ///
+    /// Unlike [Slide::iter_operations], this doesn't include the footer.
+    pub(crate) fn iter_chunk_operations(&self) -> impl Iterator<Item = &RenderOperation> {
+        self.chunks.iter().flat_map(|chunk| chunk.operations.iter())
+    }
+
     pub(crate) fn iter_operations_mut(&mut self) -> impl Iterator<Item = &mut RenderOperation> {
         self.chunks
             .iter_mut()
@@ -168,7 +381,7 @@ impl Slide {
 
     #[cfg(test)]
     pub(crate) fn into_operations(self) -> Vec<RenderOperation> {
-        self.chunks.into_iter().flat_map(|chunk| chunk.operations.into_iter()).chain(self.footer.into_iter()).collect()
+        self.chunks.into_iter().flat_map(|chunk| chunk.operations.into_iter()).chain(self.footer).collect()
     }
 
     fn jump_chunk(&mut self, chunk_index: usize) {
@@ -178,6 +391,62 @@ impl Slide {
         }
     }
 
+    /// Check whether this slide has more than one step, either via chunks or mutations.
+    pub(crate) fn has_steps(&self) -> bool {
+        self.chunks.len() > 1 || self.chunks.iter().any(|chunk| !chunk.mutators.is_empty())
+    }
+
+    /// Check whether all chunks in this slide are visible and fully mutated.
+    pub(crate) fn is_last_step(&self) -> bool {
+        self.visible_chunks == self.chunks.len() && self.current_chunk().is_fully_mutated()
+    }
+
+    /// Get a code block along with its 1 based number.
+    ///
+    /// When no number is given, this is the last code block among the visible chunks.
+    pub(crate) fn code_block(&self, number: Option<usize>) -> Option<(usize, &str)> {
+        let index = self.code_block_index(number)?;
+        self.metadata.code_blocks.get(index).map(|block| (index + 1, block.contents.as_str()))
+    }
+
+    /// Get the line a code block starts at along with its 1 based number, picked like in [Slide::code_block].
+    pub(crate) fn code_block_line(&self, number: Option<usize>) -> Option<(usize, usize)> {
+        let index = self.code_block_index(number)?;
+        self.metadata.code_blocks.get(index).map(|block| (index + 1, block.line))
+    }
+
+    /// Get the included file a code block is in, picked like in [Slide::code_block], if it's not in the
+    /// presentation's own file.
+    pub(crate) fn code_block_file(&self, number: Option<usize>) -> Option<&Path> {
+        let index = self.code_block_index(number)?;
+        self.metadata.code_blocks.get(index)?.file.as_deref()
+    }
+
+    fn code_block_index(&self, number: Option<usize>) -> Option<usize> {
+        match number {
+            Some(number) => number.checked_sub(1),
+            None => self.metadata.code_blocks.iter().rposition(|block| block.chunk <= self.current_chunk_index()),
+        }
+    }
+
+    /// Get the code blocks in the visible chunks.
+    pub(crate) fn visible_code_blocks(&self) -> impl Iterator<Item = &CodeBlock> {
+        let current_chunk = self.current_chunk_index();
+        self.metadata.code_blocks.iter().take_while(move |block| block.chunk <= current_chunk)
+    }
+
+    /// Check whether any of the images in the visible chunks is animated.
+    pub(crate) fn has_animations(&self) -> bool {
+        self.iter_images().any(Image::is_animated)
+    }
+
+    fn iter_images(&self) -> impl Iterator<Item = &Image> {
+        self.iter_operations().filter_map(|operation| match operation {
+            RenderOperation::RenderImage(image) | RenderOperation::RenderFloatingImage { image, .. } => Some(image),
+            _ => None,
+        })
+    }
+
     fn current_chunk_index(&self) -> usize {
         self.visible_chunks.saturating_sub(1)
     }
@@ -232,6 +501,112 @@ impl From<Vec<RenderOperation>> for Slide {
     }
 }
 
+/// Metadata about a slide that's not rendered but is useful to describe it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SlideMetadata {
+    /// The slide's title, if any.
+    ///
+    /// This is the slide title if one was used, otherwise the first heading in it.
+    pub(crate) title: Option<String>,
+
+    /// The slide's id, if it has one.
+    pub(crate) id: Option<SlideId>,
+
+    /// The notes for whoever is presenting this slide.
+    pub(crate) notes: Vec<String>,
+
+    /// How often this slide is built again while it's displayed, if ever.
+    pub(crate) refresh_every: Option<Duration>,
+
+    /// The code blocks in this slide, in the order they show up in.
+    pub(crate) code_blocks: Vec<CodeBlock>,
+
+    /// The sounds to play when this slide's chunks are shown.
+    pub(crate) sounds: Vec<SlideSound>,
+}
+
+/// A sound played when a chunk in a slide is shown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SlideSound {
+    /// The index of the chunk that plays this sound.
+    pub(crate) chunk: usize,
+
+    /// The path to the sound file.
+    pub(crate) path: PathBuf,
+}
+
+/// A code block in a slide.
+#[derive(Clone, Debug)]
+pub(crate) struct CodeBlock {
+    /// The index of the chunk this code block is in.
+    pub(crate) chunk: usize,
+
+    /// The code itself.
+    pub(crate) contents: String,
+
+    /// The operation that runs this code block, if it's executable.
+    pub(crate) execution: Option<Rc<dyn RenderOnDemand>>,
+
+    /// The commands that run when this code block is executed.
+    pub(crate) commands: Vec<String>,
+
+    /// The line this code block starts at in the file it's in.
+    pub(crate) line: usize,
+
+    /// The included file this code block is in, if it's not in the presentation's own file.
+    pub(crate) file: Option<PathBuf>,
+
+    /// Whether this code block is replaced by its output, which only shows up once it runs.
+    pub(crate) replaced_with_output: bool,
+}
+
+/// An identifier for a slide that doesn't change when other slides are added or removed.
+///
+/// This is either set explicitly via a `slide_id` command or derived from the slide's title the
+/// same way headings are turned into anchors, e.g. `## My Title` becomes `my-title`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct SlideId(String);
+
+impl SlideId {
+    /// Construct an id out of a slide's title.
+    ///
+    /// Returns `None` if the title contains nothing that can be used as part of an id.
+    pub(crate) fn from_title(title: &str) -> Option<Self> {
+        let mut id = String::new();
+        for c in title.trim().chars().flat_map(char::to_lowercase) {
+            if c.is_alphanumeric() || c == '_' {
+                id.push(c);
+            } else if (c.is_whitespace() || c == '-') && !id.is_empty() && !id.ends_with('-') {
+                id.push('-');
+            }
+        }
+        let id = id.trim_end_matches('-');
+        if id.is_empty() { None } else { Some(Self(id.into())) }
+    }
+
+    /// Get a version of this id with a numeric suffix, used to tell apart slides that share a title.
+    pub(crate) fn with_suffix(&self, suffix: usize) -> Self {
+        Self(format!("{}-{suffix}", self.0))
+    }
+}
+
+impl From<String> for SlideId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for SlideId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A piece of a slide that's displayed in a single step.
+///
+/// A chunk can contain any number of mutators. These are stepped through one after the other, in
+/// the order they were added in: moving forward advances the first mutator that isn't done yet, and
+/// moving backwards rewinds the last one that isn't at its start.
 #[derive(Debug, Default)]
 pub(crate) struct SlideChunk {
     operations: Vec<RenderOperation>,
@@ -260,6 +635,20 @@ impl SlideChunk {
         false
     }
 
+    // Mutators are stepped through in order so the step is the sum of every mutator's progress.
+    fn current_step(&self) -> usize {
+        self.mutators.iter().map(|mutator| mutator.mutations().0).sum()
+    }
+
+    fn jump_step(&self, step: usize) {
+        self.reset_mutations();
+        for _ in 0..step {
+            if !self.mutate_next() {
+                break;
+            }
+        }
+    }
+
     fn mutate_previous(&self) -> bool {
         for mutator in self.mutators.iter().rev() {
             if mutator.mutate_previous() {
@@ -275,6 +664,13 @@ impl SlideChunk {
         }
     }
 
+    fn is_fully_mutated(&self) -> bool {
+        self.mutators.iter().all(|mutator| {
+            let (current, total) = mutator.mutations();
+            current + 1 >= total
+        })
+    }
+
     fn apply_all_mutations(&self) {
         for mutator in &self.mutators {
             mutator.apply_all_mutations();
@@ -282,16 +678,94 @@ impl SlideChunk {
     }
 }
 
+/// A position within a presentation, down to the step within the current chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PresentationPosition {
+    slide: usize,
+    chunk: usize,
+    step: usize,
+}
+
+/// The positions a presentation was at before every navigation, so navigations can be undone.
+#[derive(Default)]
+pub(crate) struct NavigationHistory {
+    positions: VecDeque<PresentationPosition>,
+}
+
+impl NavigationHistory {
+    /// The number of navigations that can be undone.
+    const CAPACITY: usize = 100;
+
+    /// Record that a navigation moved the presentation between these two positions.
+    pub(crate) fn record(&mut self, from: PresentationPosition, to: PresentationPosition) {
+        if from == to {
+            return;
+        }
+        if self.positions.len() == Self::CAPACITY {
+            self.positions.pop_front();
+        }
+        self.positions.push_back(from);
+    }
+
+    /// Take the position the presentation was at before the last navigation.
+    pub(crate) fn pop(&mut self) -> Option<PresentationPosition> {
+        self.positions.pop_back()
+    }
+
+    /// Update every position after the presentation is reloaded.
+    ///
+    /// `slide_mapping` contains the index each slide in the original presentation has in the
+    /// updated one. Positions in slides that no longer exist are dropped.
+    pub(crate) fn remap_slides(&mut self, slide_mapping: &[Option<usize>]) {
+        self.positions.retain_mut(|position| match slide_mapping.get(position.slide).copied().flatten() {
+            Some(slide) => {
+                position.slide = slide;
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+/// Mutates a chunk's contents in steps, e.g. to highlight different lines in a code block.
+///
+/// Every mutator holds a current mutation, starting at the first one. Mutators typically share
+/// state with the render operations they affect, which is why none of these functions take a
+/// mutable reference.
 pub(crate) trait ChunkMutator: Debug {
+    /// Move into the next mutation.
+    ///
+    /// Returns false if this mutator is already at its last mutation.
     fn mutate_next(&self) -> bool;
+
+    /// Move into the previous mutation.
+    ///
+    /// Returns false if this mutator is already at its first mutation.
     fn mutate_previous(&self) -> bool;
+
+    /// Go back to the first mutation.
     fn reset_mutations(&self);
+
+    /// Jump to the last mutation.
     fn apply_all_mutations(&self);
+
+    /// Get the current mutation index and the total number of mutations.
     fn mutations(&self) -> (usize, usize);
 }
 
+/// A problem found when building a slide that didn't stop it from being built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SlideWarning {
+    /// The slide the problem was found in, starting at 1.
+    pub(crate) slide: usize,
+
+    /// The description of the problem.
+    pub(crate) message: String,
+}
+
 /// The metadata for a presentation.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct PresentationMetadata {
     /// The presentation title.
     pub(crate) title: Option<String>,
@@ -304,13 +778,155 @@ pub(crate) struct PresentationMetadata {
     #[serde(default)]
     pub(crate) author: Option<String>,
 
+    /// The author's social links.
+    #[serde(default)]
+    pub(crate) social: SocialLinks,
+
     /// The presentation's theme metadata.
     #[serde(default)]
     pub(crate) theme: PresentationThemeMetadata,
+
+    /// The profile to use when exporting the presentation.
+    #[serde(default)]
+    pub(crate) export: Option<ExportProfile>,
+
+    /// Whether to generate an introduction slide out of the title, sub-title, and author.
+    #[serde(default)]
+    pub(crate) intro_slide: Option<bool>,
+
+    /// The path to a markdown file to use as the introduction slide.
+    #[serde(default)]
+    pub(crate) intro_slide_template: Option<PathBuf>,
+
+    /// Whether to list the links used in every slide at the bottom of it.
+    #[serde(default)]
+    pub(crate) link_references: bool,
+
+    /// The number of rows a slide can take before it's split into more than one.
+    #[serde(default)]
+    pub(crate) max_slide_height: Option<u16>,
+
+    /// The directory relative resource paths are relative to, itself relative to the presentation's directory.
+    #[serde(default)]
+    pub(crate) resources_root: Option<PathBuf>,
+
+    /// Whether to insert an agenda slide that lists every section before each one of them.
+    #[serde(default)]
+    pub(crate) agenda: bool,
+
+    /// The smallest terminal the presentation can be presented in.
+    #[serde(default)]
+    pub(crate) minimum_size: Option<MinimumSize>,
+
+    /// The container image executable code in each language runs in unless it sets its own.
+    #[serde(default)]
+    pub(crate) containers: BTreeMap<String, String>,
+
+    /// The presentation level options, which command line parameters take precedence over.
+    #[serde(default)]
+    pub(crate) options: PresentationOptions,
+}
+
+impl PresentationMetadata {
+    /// Get the JSON schema for a presentation's front matter.
+    pub(crate) fn json_schema() -> serde_json::Value {
+        schema::schema_for::<Self>("presenterm front matter")
+    }
+}
+
+/// The smallest terminal a presentation can be presented in.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MinimumSize {
+    /// The minimum number of columns.
+    pub(crate) columns: u16,
+
+    /// The minimum number of rows.
+    pub(crate) rows: u16,
+}
+
+impl MinimumSize {
+    /// Check whether a terminal with the given dimensions is at least this big.
+    pub(crate) fn fits(&self, columns: u16, rows: u16) -> bool {
+        columns >= self.columns && rows >= self.rows
+    }
+}
+
+/// The settings used when exporting a presentation.
+///
+/// These are pinned so exporting a presentation generates the same output no matter which machine
+/// or terminal it's exported in.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExportProfile {
+    /// The number of columns in the terminal the presentation is rendered in.
+    pub(crate) columns: u16,
+
+    /// The number of rows in the terminal the presentation is rendered in.
+    pub(crate) rows: u16,
+
+    /// Hints on the font to use for whoever is capturing the terminal.
+    #[serde(default)]
+    pub(crate) font: ExportFont,
+
+    /// The name of the theme to use, overriding the presentation's theme.
+    #[serde(default)]
+    pub(crate) theme: Option<String>,
+}
+
+/// The font used when exporting a presentation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExportFont {
+    /// The font family.
+    #[serde(default)]
+    pub(crate) family: Option<String>,
+
+    /// The font size, in points.
+    #[serde(default)]
+    pub(crate) size: Option<u16>,
+}
+
+/// The ways to reach a presentation's author.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct SocialLinks {
+    /// A github username.
+    #[serde(default)]
+    pub(crate) github: Option<String>,
+
+    /// A mastodon handle.
+    #[serde(default)]
+    pub(crate) mastodon: Option<String>,
+
+    /// An email address.
+    #[serde(default)]
+    pub(crate) email: Option<String>,
+}
+
+impl SocialLinks {
+    /// Get every link that's set, in a fixed order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (SocialNetwork, &str)> {
+        let links = [
+            (SocialNetwork::Github, &self.github),
+            (SocialNetwork::Mastodon, &self.mastodon),
+            (SocialNetwork::Email, &self.email),
+        ];
+        links.into_iter().filter_map(|(network, value)| Some((network, value.as_deref()?)))
+    }
+}
+
+/// A network an author can be reached at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SocialNetwork {
+    Github,
+    Mastodon,
+    Email,
 }
 
 /// A presentation's theme metadata.
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct PresentationThemeMetadata {
     /// The theme name.
     #[serde(default)]
@@ -326,7 +942,7 @@ pub(crate) struct PresentationThemeMetadata {
 }
 
 /// A line of preformatted text to be rendered.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub(crate) struct PreformattedLine {
     pub(crate) text: String,
     pub(crate) unformatted_length: usize,
@@ -343,6 +959,9 @@ pub(crate) enum RenderOperation {
     /// Clear the entire screen.
     ClearScreen,
 
+    /// Paint a part of the screen using a different background color, leaving the cursor as is.
+    RenderSplitBackground(SplitBackground),
+
     /// Set the colors to be used for any subsequent operations.
     SetColors(Colors),
 
@@ -363,6 +982,9 @@ pub(crate) enum RenderOperation {
     /// Render an image.
     RenderImage(Image),
 
+    /// Render an image on one side of the slide and let any following content flow next to it.
+    RenderFloatingImage { image: Image, float: ImageFloat },
+
     /// Render a preformatted line.
     ///
     /// The line will usually already have terminal escape codes that include colors and formatting
@@ -379,6 +1001,12 @@ pub(crate) enum RenderOperation {
     /// An operation that is rendered on demand.
     RenderOnDemand(Rc<dyn RenderOnDemand>),
 
+    /// An operation that paints itself.
+    ///
+    /// This allows adding new kinds of visual elements without having to teach every renderer
+    /// about them. See [CustomRender] for how these are laid out.
+    RenderCustom(Rc<dyn CustomRender>),
+
     /// Initialize a column layout.
     ///
     /// The value for each column is the width of the column in column-unit units, where the entire
@@ -401,7 +1029,7 @@ pub(crate) enum RenderOperation {
 }
 
 /// Slide properties, set on initialization.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub(crate) struct MarginProperties {
     /// The horizontal margin.
     pub(crate) horizontal_margin: Margin,
@@ -410,6 +1038,56 @@ pub(crate) struct MarginProperties {
     pub(crate) bottom_slide_margin: u16,
 }
 
+/// Where an image that content flows around is placed, e.g. `right 40%`.
+#[derive(Clone, Debug, PartialEq, Eq, DeserializeFromStr, SerializeDisplay)]
+pub(crate) struct ImageFloat {
+    /// The side of the slide the image is placed on.
+    pub(crate) side: FloatSide,
+
+    /// The percentage of the slide's width taken by the image.
+    pub(crate) width: u8,
+}
+
+impl FromStr for ImageFloat {
+    type Err = ParseImageFloatError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || ParseImageFloatError(input.into());
+        let (side, width) = input.trim().split_once(' ').ok_or_else(error)?;
+        let side = match side {
+            "left" => FloatSide::Left,
+            "right" => FloatSide::Right,
+            _ => return Err(error()),
+        };
+        let width = width.trim().strip_suffix('%').and_then(|width| width.parse().ok()).ok_or_else(error)?;
+        if !(1..100).contains(&width) {
+            return Err(error());
+        }
+        Ok(Self { side, width })
+    }
+}
+
+impl fmt::Display for ImageFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = match self.side {
+            FloatSide::Left => "left",
+            FloatSide::Right => "right",
+        };
+        write!(f, "{side} {}%", self.width)
+    }
+}
+
+/// The side of the slide a floating image is placed on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FloatSide {
+    Left,
+    Right,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("invalid image float '{0}', expected a side and a width like `right 40%`")]
+pub(crate) struct ParseImageFloatError(String);
+
 /// A type that can generate render operations.
 pub(crate) trait AsRenderOperations: Debug + 'static {
     /// Generate render operations.
@@ -420,12 +1098,75 @@ pub(crate) trait AsRenderOperations: Debug + 'static {
 }
 
 /// A type that can be rendered on demand.
+///
+/// These are rendered using the rows left between where they start and the bottom of the slide,
+/// rather than the whole screen's, so they can keep themselves from growing past it.
 pub(crate) trait RenderOnDemand: AsRenderOperations {
     /// Start the on demand render for this operation.
     fn start_render(&self) -> bool;
 
     /// Poll and update the internal on demand state and return the latest.
     fn poll_state(&self) -> RenderOnDemandState;
+
+    /// Start the on demand render for this operation again, even if it already finished.
+    ///
+    /// Operations that can only be rendered once don't start again.
+    fn restart_render(&self) -> bool {
+        false
+    }
+
+    /// Whether the on demand render for this operation finished unsuccessfully.
+    fn failed(&self) -> bool {
+        false
+    }
+
+    /// Whether there's an expectation for what this operation produces, see [RenderOnDemand::output_mismatch].
+    fn has_expected_output(&self) -> bool {
+        false
+    }
+
+    /// Describe how what this operation produced differs from what it's expected to, if there's an expectation.
+    fn output_mismatch(&self) -> Option<String> {
+        None
+    }
+
+    /// Get the text this operation produced so far, without any colors.
+    fn output(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Scroll whatever this operation displays by the given number of rows, up if it's negative.
+    ///
+    /// Returns whether it moved.
+    fn scroll(&self, _rows: isize) -> bool {
+        false
+    }
+
+    /// Stop the on demand render for this operation if it's in progress.
+    ///
+    /// Returns whether there was anything to stop.
+    fn cancel_render(&self) -> bool {
+        false
+    }
+}
+
+/// A type that paints itself into a rectangle of the screen.
+///
+/// Custom operations are laid out as a block starting at the row the cursor is at and taking the
+/// entire width of the current layout: they're first asked how many rows they need given that
+/// width, and then asked to paint themselves into a canvas of that size. The canvas is shrunk if
+/// there aren't enough rows left in the screen, and anything painted outside of it is clipped, so
+/// custom operations never draw over anything else. After painting, the cursor is moved to the
+/// row right after the block.
+pub(crate) trait CustomRender: Debug + 'static {
+    /// Get the number of rows needed to paint this operation using the given number of columns.
+    fn measure(&self, columns: u16) -> u16;
+
+    /// Paint this operation.
+    fn paint(&self, canvas: &mut Canvas);
+
+    /// Get the content in this type to diff it against another `CustomRender`.
+    fn diffable_content(&self) -> Option<&str>;
 }
 
 /// The state of a [RenderOnDemand].
@@ -472,13 +1213,13 @@ mod test {
 
     #[derive(Debug)]
     struct DummyMutator {
-        current: RefCell<usize>,
+        current: Rc<RefCell<usize>>,
         limit: usize,
     }
 
     impl DummyMutator {
         fn new(limit: usize) -> Self {
-            Self { current: 0.into(), limit }
+            Self { current: Default::default(), limit }
         }
     }
 
@@ -512,10 +1253,54 @@ mod test {
         }
 
         fn mutations(&self) -> (usize, usize) {
-            (*self.current.borrow(), self.limit)
+            // `limit` is the last valid index so there's one more mutation than that.
+            (*self.current.borrow(), self.limit + 1)
         }
     }
 
+    #[rstest]
+    #[case::first_chunk(0, &["a", "|", "footer"])]
+    #[case::middle_chunk(1, &["a", "|", "b", "|", "footer"])]
+    #[case::last_chunk(2, &["a", "|", "b", "|", "c", "footer"])]
+    fn pause_markers(#[case] chunk: usize, #[case] expected: &[&str]) {
+        let text = |text: &str| RenderOperation::RenderText {
+            line: WeightedLine::from(text.to_string()),
+            alignment: Default::default(),
+        };
+        let chunks = ["a", "b", "c"].map(|contents| SlideChunk::new(vec![text(contents)], vec![])).into();
+        let mut slide = Slide::new(chunks, vec![text("footer")]);
+        slide.jump_chunk(chunk);
+        let marker = [text("|")];
+        let texts: Vec<_> = slide
+            .iter_operations_with_pause_markers(&marker)
+            .map(|operation| match operation {
+                RenderOperation::RenderText { line, .. } => line.iter_texts().map(|t| t.text.text.as_str()).collect(),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(texts, expected);
+    }
+
+    #[test]
+    fn mutator_order() {
+        let mutators = [DummyMutator::new(1), DummyMutator::new(1), DummyMutator::new(1)];
+        let states = mutators.each_ref().map(|mutator| mutator.current.clone());
+        let chunk = SlideChunk::new(vec![], mutators.into_iter().map(|mutator| Box::new(mutator) as _).collect());
+        let current = || states.clone().map(|state| *state.borrow());
+
+        assert!(chunk.mutate_next());
+        assert_eq!(current(), [1, 0, 0]);
+        assert!(chunk.mutate_next());
+        assert_eq!(current(), [1, 1, 0]);
+        assert!(chunk.mutate_next());
+        assert_eq!(current(), [1, 1, 1]);
+        assert!(!chunk.mutate_next());
+        assert!(chunk.is_fully_mutated());
+
+        assert!(chunk.mutate_previous());
+        assert_eq!(current(), [1, 1, 0]);
+    }
+
     #[rstest]
     #[case::previous_from_first(0, &[Jump::Previous], 0, 0)]
     #[case::next_from_first(0, &[Jump::Next], 0, 1)]
@@ -533,9 +1318,9 @@ mod test {
         #[case] expected_chunk: usize,
     ) {
         let mut presentation = Presentation::new(vec![
-            Slide::new(vec![SlideChunk::from(SlideChunk::default()), SlideChunk::default()], vec![]),
-            Slide::new(vec![SlideChunk::from(SlideChunk::default()), SlideChunk::default()], vec![]),
-            Slide::new(vec![SlideChunk::from(SlideChunk::default()), SlideChunk::default()], vec![]),
+            Slide::new(vec![SlideChunk::default(), SlideChunk::default()], vec![]),
+            Slide::new(vec![SlideChunk::default(), SlideChunk::default()], vec![]),
+            Slide::new(vec![SlideChunk::default(), SlideChunk::default()], vec![]),
         ]);
         presentation.jump_slide(from);
 
@@ -572,17 +1357,17 @@ mod test {
         let mut presentation = Presentation::new(vec![
             Slide::new(
                 vec![
-                    SlideChunk::from(SlideChunk::new(
+                    SlideChunk::new(
                         vec![],
                         vec![Box::new(DummyMutator::new(1)), Box::new(DummyMutator::new(2))],
-                    )),
+                    ),
                     SlideChunk::default(),
                 ],
                 vec![],
             ),
             Slide::new(
                 vec![
-                    SlideChunk::from(SlideChunk::new(vec![], vec![Box::new(DummyMutator::new(2))])),
+                    SlideChunk::new(vec![], vec![Box::new(DummyMutator::new(2))]),
                     SlideChunk::default(),
                 ],
                 vec![],
@@ -605,4 +1390,78 @@ mod test {
         assert_eq!(presentation.current_slide_index(), expected_slide, "slide differs");
         assert_eq!(presentation.current_slide().visible_chunks - 1, expected_chunk, "chunk differs");
     }
+
+    #[test]
+    fn undo_navigation() {
+        let mut presentation = Presentation::new(vec![
+            Slide::new(vec![SlideChunk::new(vec![], vec![Box::new(DummyMutator::new(2))])], vec![]),
+            Slide::new(vec![SlideChunk::default(), SlideChunk::default()], vec![]),
+        ]);
+        let mut history = NavigationHistory::default();
+        let mut positions = Vec::new();
+        for jump in Jump::Next.repeat(5).into_iter().chain([Jump::First]) {
+            let before = presentation.position();
+            positions.push(before);
+            jump.apply(&mut presentation);
+            history.record(before, presentation.position());
+        }
+        // The last jump to the next slide didn't go anywhere so there's nothing to undo for it.
+        positions.remove(4);
+        while let Some(position) = history.pop() {
+            assert!(presentation.jump_position(position));
+            assert_eq!(presentation.position(), positions.pop().unwrap());
+        }
+        assert!(positions.is_empty());
+        assert_eq!(presentation.current_slide().chunks[0].current_step(), 0);
+    }
+
+    #[rstest]
+    #[case::first_chunk(&[], false)]
+    #[case::first_mutation(&[Jump::Next], false)]
+    #[case::last_chunk_not_mutated(&Jump::Next.repeat(3), false)]
+    #[case::last_chunk_mutated(&Jump::Next.repeat(4), true)]
+    fn last_step(#[case] jumps: &[Jump], #[case] expected: bool) {
+        let mut presentation = Presentation::new(vec![Slide::new(
+            vec![
+                SlideChunk::new(vec![], vec![Box::new(DummyMutator::new(2))]),
+                SlideChunk::new(vec![], vec![Box::new(DummyMutator::new(1))]),
+            ],
+            vec![],
+        )]);
+        for jump in jumps {
+            jump.apply(&mut presentation);
+        }
+        assert!(presentation.current_slide().has_steps());
+        assert_eq!(presentation.is_current_slide_last_step(), expected);
+    }
+
+    #[rstest]
+    #[case::simple("Introduction", Some("introduction"))]
+    #[case::spaces("  My   Great Title ", Some("my-great-title"))]
+    #[case::punctuation("What's next? (part 2)", Some("whats-next-part-2"))]
+    #[case::unicode("Über Größe", Some("über-größe"))]
+    #[case::nothing_usable("???", None)]
+    fn slide_id_from_title(#[case] title: &str, #[case] expected: Option<&str>) {
+        let id = SlideId::from_title(title);
+        assert_eq!(id, expected.map(|id| SlideId::from(id.to_string())));
+    }
+
+    #[rstest]
+    #[case::right("right 40%", FloatSide::Right, 40)]
+    #[case::left(" left  25% ", FloatSide::Left, 25)]
+    fn parse_image_float(#[case] input: &str, #[case] side: FloatSide, #[case] width: u8) {
+        let float: ImageFloat = input.parse().expect("parse failed");
+        assert_eq!(float, ImageFloat { side, width });
+        assert_eq!(float.to_string().parse::<ImageFloat>().expect("parse failed"), float);
+    }
+
+    #[rstest]
+    #[case::no_width("right")]
+    #[case::no_percent("right 40")]
+    #[case::unknown_side("up 40%")]
+    #[case::too_wide("left 100%")]
+    #[case::empty("left 0%")]
+    fn invalid_image_float(#[case] input: &str) {
+        assert!(input.parse::<ImageFloat>().is_err());
+    }
 }
@@ -1,24 +1,59 @@
 use crate::{
-    builder::{BuildError, PresentationBuilder, PresentationBuilderOptions},
+    audience::{AudienceServer, AudienceSlide},
+    builder::{BuildError, CommandOutputPolicy, PresentationBuilder, PresentationBuilderOptions},
+    clipboard::Clipboard,
+    clock::SystemClock,
+    compile::{CompiledPresentation, LoadCompiledError},
+    config::{
+        ExecutionConfig, HookConfig, MultiplexerConfig, PlaceholderConfig, SafeAreaConfig, SoundConfig,
+        UnknownCommandPolicy,
+    },
+    control::{self, ControlClient, ControlServer, SlideSummary},
     diff::PresentationDiffer,
+    events::{EventBus, PresentationEvent},
+    execute::SessionManager,
+    hooks::HookRunner,
     input::source::{Command, CommandSource},
     markdown::parse::{MarkdownParser, ParseError},
-    presentation::Presentation,
+    multiplexer::MultiplexerStatus,
+    options::{ExternalOptions, PresentationOptions},
+    poll::PollRegistry,
+    presentation::{CodeBlock, NavigationHistory, Presentation, RenderOnDemandState},
     render::{
+        console::PresenterConsole,
         draw::{RenderError, RenderResult, TerminalDrawer},
         highlighting::CodeHighlighter,
+        html::HtmlRenderer,
+        overview::{SlideOverview, ThumbnailCache},
+        picker::CodePicker,
+        prompt::ExecutionPrompt,
+        properties::WindowSize,
+        terminal::TerminalMode,
     },
     resource::Resources,
+    rpc::StdioControl,
+    sound::SoundPlayer,
     theme::PresentationTheme,
+    timing::SlideTimer,
+    transcript::ExecutionTranscript,
 };
 use std::{
     collections::HashSet,
-    fs,
-    io::{self, Stdout},
+    env,
+    fs::{File, OpenOptions},
+    io::{self, Write},
     mem,
-    path::Path,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+// Commands are waited for for less time while animated images are displayed so their frames show up on time.
+const COMMAND_POLL_TIMEOUT: Duration = Duration::from_millis(250);
+const ANIMATION_POLL_TIMEOUT: Duration = Duration::from_millis(20);
+
 /// A slideshow presenter.
 ///
 /// This type puts everything else together.
@@ -28,9 +63,38 @@ pub struct Presenter<'a> {
     commands: CommandSource,
     parser: MarkdownParser<'a>,
     resources: Resources,
-    mode: PresentMode,
+    options: PresenterOptions,
     state: PresenterState,
     slides_with_pending_widgets: HashSet<usize>,
+    pending_cue: Option<String>,
+    // The code block to copy once the slide is drawn again, see [Command::CopyCode].
+    pending_copy: Option<Option<usize>>,
+    // The code block to open in an editor once the slide is drawn again, see [Command::EditCode].
+    pending_edit: Option<Option<usize>>,
+    events: EventBus,
+    published_slide: Option<usize>,
+    window_title: Option<String>,
+    audience: Option<AudienceServer>,
+    polls: PollRegistry,
+    sessions: SessionManager,
+    control: Option<ControlServer>,
+    // The slide the control server's slide descriptions were last built at, see [Self::update_control_position].
+    control_slide: Option<usize>,
+    started_at: Instant,
+    refreshed_at: Instant,
+    // When the current step was moved into, for moving into the next one automatically.
+    stepped_at: Instant,
+    console: Option<PresenterConsole>,
+    thumbnails: ThumbnailCache,
+    focus: Focus,
+    // The slide, and how many code blocks were visible in it, the last time running the code blocks that are
+    // replaced by their output was asked for in it, see [Self::confirm_command_output].
+    command_output_prompted: Option<(usize, usize)>,
+    showing_pause_markers: bool,
+    timer: SlideTimer,
+    history: NavigationHistory,
+    pending_force_reload: bool,
+    reloadable: bool,
 }
 
 impl<'a> Presenter<'a> {
@@ -41,38 +105,163 @@ impl<'a> Presenter<'a> {
         commands: CommandSource,
         parser: MarkdownParser<'a>,
         resources: Resources,
-        mode: PresentMode,
+        options: PresenterOptions,
     ) -> Self {
+        let mut events = EventBus::default();
+        if !options.hooks.is_empty() {
+            events.subscribe(Box::new(HookRunner::new(options.hooks.clone())));
+        }
+        if matches!(options.control, Some(ControlChannel::Stdio)) {
+            events.subscribe(Box::new(StdioControl));
+        }
         Self {
             default_theme,
             default_highlighter,
             commands,
             parser,
             resources,
-            mode,
+            options,
             state: PresenterState::Empty,
             slides_with_pending_widgets: HashSet::new(),
+            pending_cue: None,
+            pending_copy: None,
+            pending_edit: None,
+            events,
+            published_slide: None,
+            control_slide: None,
+            window_title: None,
+            audience: None,
+            polls: PollRegistry::default(),
+            sessions: SessionManager::default(),
+            control: None,
+            started_at: Instant::now(),
+            refreshed_at: Instant::now(),
+            stepped_at: Instant::now(),
+            console: None,
+            thumbnails: ThumbnailCache::default(),
+            focus: Focus::default(),
+            command_output_prompted: None,
+            showing_pause_markers: false,
+            timer: SlideTimer::default(),
+            history: NavigationHistory::default(),
+            pending_force_reload: false,
+            reloadable: true,
         }
     }
 
     /// Run a presentation.
     pub fn present(mut self, path: &Path) -> Result<(), PresentationError> {
-        self.state = PresenterState::Presenting(self.load_presentation(path)?);
+        let presentation = self.load_presentation(path)?;
+        self.run(presentation, path)
+    }
+
+    /// Run a presentation compiled via [crate::PresentationCompiler].
+    pub fn play(mut self, path: &Path) -> Result<(), PresentationError> {
+        let presentation = CompiledPresentation::load(path)?.into_presentation()?;
+        // There's no markdown to build compiled presentations from again so they can't be reloaded.
+        self.options.mode = PresentMode::Presentation;
+        self.reloadable = false;
+        self.run(presentation, path)
+    }
 
-        let mut drawer = TerminalDrawer::new(io::stdout())?;
+    fn run(mut self, presentation: Presentation, path: &Path) -> Result<(), PresentationError> {
+        self.state = PresenterState::Presenting(presentation);
+        self.update_mode();
+        self.thumbnails.refresh(self.state.presentation());
+        let total_slides = self.state.presentation().iter_slides().count();
+        self.events.publish(PresentationEvent::PresentationStarted { total_slides });
+        if let Some(address) = self.options.audience_view {
+            self.audience = Some(AudienceServer::start(address, self.polls.clone())?);
+        }
+        let control_address = self.options.control_address;
+        match self.options.role {
+            Some(PresenterRole::Presenter) => self.control = Some(ControlServer::bind(control_address)?),
+            Some(PresenterRole::Audience) => self.commands.attach_remote(ControlClient::connect(control_address)),
+            None => (),
+        };
+        // Stdout is taken by the control API so draw straight into the terminal instead.
+        let output: Box<dyn Write> = match self.options.control {
+            Some(ControlChannel::Stdio) => {
+                self.commands.attach_remote(StdioControl::start());
+                Box::new(Self::open_terminal()?)
+            }
+            None => Box::new(io::stdout()),
+        };
+        self.started_at = Instant::now();
+
+        let mode = if self.options.low_bandwidth { TerminalMode::LowBandwidth } else { TerminalMode::Default };
+        let mut drawer = TerminalDrawer::new(output, mode)?;
+        if self.options.control.is_some() {
+            drawer.avoid_stdout();
+        }
+        // Exports always use the whole window, and crop marks are only there to help while rehearsing.
+        if !matches!(self.options.mode, PresentMode::Export) {
+            let crop_marks = self.options.safe_area.crop_marks && matches!(self.options.mode, PresentMode::Development);
+            drawer.set_safe_area(SafeAreaConfig { crop_marks, ..self.options.safe_area.clone() });
+        }
+        let mut multiplexer = MultiplexerStatus::new(&self.options.multiplexer);
+        // Only one of the instances presenting over two terminals plays sounds.
+        let sound = match self.options.role {
+            Some(PresenterRole::Presenter) => SoundConfig::default(),
+            _ => self.options.sound.clone(),
+        };
+        let mut sounds = SoundPlayer::new(&sound);
         loop {
+            drawer.set_image_protocol(self.presentation_options().image_protocol.unwrap_or_default());
+            self.confirm_command_output();
             self.render(&mut drawer)?;
+            self.emit_pending_cue(&mut drawer)?;
+            self.copy_pending_code(&mut drawer)?;
+            if self.edit_pending_code(&mut drawer, path)? {
+                // Whatever was changed is shown right away, even in presentation mode.
+                self.try_reload(path, true);
+                continue;
+            }
+            self.update_window_title(&mut drawer)?;
+            multiplexer.update(self.state.presentation());
+            sounds.update(self.state.presentation());
+            self.update_audience_view()?;
+            self.update_control_position();
+            self.publish_slide_change();
             self.update_widgets(&mut drawer)?;
 
             loop {
                 self.update_widgets(&mut drawer)?;
-                let Some(command) = self.commands.try_next_command()? else {
+                self.refresh_console(&mut drawer)?;
+                self.animate_images(&mut drawer)?;
+                if self.is_refresh_due() {
+                    self.refresh_slide(path);
+                    break;
+                }
+                if self.is_auto_advance_due() {
+                    let command = Command::JumpNextSlide;
+                    self.relay_command(&command);
+                    self.apply_command(command);
+                    // There's nowhere to go after the last step so don't try again right away.
+                    self.stepped_at = Instant::now();
+                    break;
+                }
+                let animated = self.animations_enabled() && self.state.presentation().current_slide().has_animations();
+                let timeout = match animated {
+                    true => ANIMATION_POLL_TIMEOUT,
+                    false => COMMAND_POLL_TIMEOUT,
+                };
+                let Some(command) = self.commands.try_next_command(timeout)? else {
                     continue;
                 };
+                self.relay_command(&command);
                 match self.apply_command(command) {
-                    CommandSideEffect::Exit => return Ok(()),
+                    CommandSideEffect::Exit => {
+                        self.events.publish(PresentationEvent::PresentationEnded);
+                        self.write_timing_report()?;
+                        return Ok(());
+                    }
                     CommandSideEffect::Reload => {
-                        self.try_reload(path);
+                        self.try_reload(path, false);
+                        break;
+                    }
+                    CommandSideEffect::ForceReload => {
+                        self.try_reload(path, true);
                         break;
                     }
                     CommandSideEffect::Redraw => {
@@ -87,38 +276,313 @@ impl<'a> Presenter<'a> {
         }
     }
 
-    fn update_widgets(&mut self, drawer: &mut TerminalDrawer<Stdout>) -> RenderResult {
+    fn update_widgets(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
         let current_index = self.state.presentation().current_slide_index();
         if self.slides_with_pending_widgets.contains(&current_index) {
             self.render(drawer)?;
+            self.update_audience_view()?;
             if self.state.presentation_mut().widgets_rendered() {
                 // Render one last time just in case it _just_ rendered
                 self.render(drawer)?;
+                self.update_audience_view()?;
                 self.slides_with_pending_widgets.remove(&current_index);
+                let event = self.slide_event(|current_slide, total_slides| PresentationEvent::ExecutionFinished {
+                    current_slide,
+                    total_slides,
+                });
+                self.events.publish(event);
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_slide_change(&mut self) {
+        let current_index = self.state.presentation().current_slide_index();
+        if self.published_slide == Some(current_index) {
+            return;
+        }
+        self.published_slide = Some(current_index);
+        self.refreshed_at = Instant::now();
+        self.timer.enter_slide(current_index, Instant::now());
+        let event = self
+            .slide_event(|current_slide, total_slides| PresentationEvent::SlideChanged { current_slide, total_slides });
+        self.events.publish(event);
+    }
+
+    fn write_timing_report(&mut self) -> io::Result<()> {
+        let Some(path) = &self.options.timing_report else {
+            return Ok(());
+        };
+        let titles = self.state.presentation().iter_slides().map(|slide| slide.metadata().title.clone()).collect();
+        self.timer.finish(Instant::now(), titles).write(path)
+    }
+
+    fn update_window_title(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
+        let Some(template) = &self.options.window_title else {
+            return Ok(());
+        };
+        let title = Self::render_window_title(template, self.state.presentation());
+        if self.window_title.as_ref() != Some(&title) {
+            drawer.set_title(&title)?;
+            self.window_title = Some(title);
+        }
+        Ok(())
+    }
+
+    fn render_window_title(template: &str, presentation: &Presentation) -> String {
+        let current_slide = presentation.current_slide_index() + 1;
+        let total_slides = presentation.iter_slides().count();
+        let title = presentation.current_slide().metadata().title.as_deref().unwrap_or_default();
+        template
+            .replace("{title}", title)
+            .replace("{current_slide}", &current_slide.to_string())
+            .replace("{total_slides}", &total_slides.to_string())
+    }
+
+    fn update_audience_view(&self) -> io::Result<()> {
+        let Some(audience) = &self.audience else {
+            return Ok(());
+        };
+        let presentation = self.state.presentation();
+        let html = HtmlRenderer::new(WindowSize::current()?).render_slide(presentation.current_slide());
+        let polls = self.polls.open_polls(presentation.current_slide_index());
+        let current_slide = presentation.current_slide_index() + 1;
+        let total_slides = presentation.iter_slides().count();
+        audience.update(AudienceSlide { html, current_slide, total_slides, polls });
+        Ok(())
+    }
+
+    fn update_control_position(&mut self) {
+        if let Some(control) = &self.control {
+            let presentation = self.state.presentation();
+            let current_index = presentation.current_slide_index();
+            control.set_position(current_index, presentation.current_chunk());
+            // Describing every slide means cloning all of their notes so it's only done when moving to
+            // another slide or after the presentation is reloaded.
+            if self.control_slide == Some(current_index) {
+                return;
+            }
+            self.control_slide = Some(current_index);
+            let slides = presentation
+                .iter_slides()
+                .enumerate()
+                .map(|(index, slide)| SlideSummary {
+                    slide: index + 1,
+                    title: slide.metadata().title.clone(),
+                    notes: slide.metadata().notes.clone(),
+                    total_chunks: slide.iter_chunks().count(),
+                })
+                .collect();
+            control.set_slides(slides);
+        }
+    }
+
+    fn relay_command(&self, command: &Command) {
+        // Code that has to be confirmed before it runs is only relayed once it is, see [Command::RunConfirmedCode].
+        if self.options.execution.confirm && matches!(command, Command::RenderWidgets) {
+            return;
+        }
+        if let Some(control) = &self.control {
+            if control::is_relayed(command) {
+                control.broadcast(command);
             }
         }
+    }
+
+    fn refresh_console(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
+        let PresenterState::Presenting(presentation) = &self.state else {
+            return Ok(());
+        };
+        // The console only changes every second because of the timer so only redraw it if it
+        // actually changed.
+        if self.console.is_some()
+            && self.console != Some(PresenterConsole::new(presentation, self.started_at.elapsed()))
+        {
+            self.render(drawer)?;
+        }
+        Ok(())
+    }
+
+    // Every frame means redrawing the image, which is too much to send over a slow link.
+    fn animations_enabled(&self) -> bool {
+        !self.options.low_bandwidth
+    }
+
+    fn animate_images(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
+        let PresenterState::Presenting(presentation) = &self.state else {
+            return Ok(());
+        };
+        if self.animations_enabled() && presentation.advance_animations(Instant::now()) {
+            self.render(drawer)?;
+        }
         Ok(())
     }
 
-    fn render(&mut self, drawer: &mut TerminalDrawer<Stdout>) -> RenderResult {
+    fn slide_event<F>(&self, builder: F) -> PresentationEvent
+    where
+        F: FnOnce(usize, usize) -> PresentationEvent,
+    {
+        let presentation = self.state.presentation();
+        builder(presentation.current_slide_index() + 1, presentation.iter_slides().count())
+    }
+
+    fn emit_pending_cue(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
+        let (Some(cue), Some(message)) = (self.presentation_options().boundary_cue, self.pending_cue.take()) else {
+            return Ok(());
+        };
+        drawer.emit_cue(&cue, &message)
+    }
+
+    fn copy_pending_code(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
+        let Some(index) = self.pending_copy.take() else {
+            return Ok(());
+        };
+        let message = match self.state.presentation().current_slide().code_block(index) {
+            Some((number, code)) => {
+                // Native tools don't work over SSH so rely on the terminal in that case.
+                if self.options.low_bandwidth || !Clipboard::copy_native(code) {
+                    drawer.copy_to_clipboard(code)?;
+                }
+                format!("Copied code block {number} into the clipboard")
+            }
+            None => "There's no code block to copy".to_string(),
+        };
+        drawer.render_notice(&message)
+    }
+
+    // Returns whether the editor was run, in which case the presentation may have changed.
+    fn edit_pending_code(
+        &mut self,
+        drawer: &mut TerminalDrawer<Box<dyn Write>>,
+        path: &Path,
+    ) -> Result<bool, RenderError> {
+        let Some(index) = self.pending_edit.take() else {
+            return Ok(false);
+        };
+        let slide = self.state.presentation().current_slide();
+        let line = slide.code_block_line(index);
+        // Code blocks in included files have to be edited there rather than in the presentation.
+        let path = slide.code_block_file(index).unwrap_or(path).to_path_buf();
+        let message = match line {
+            // Compiled presentations don't have a source to edit.
+            _ if !self.reloadable => "This presentation can't be edited".to_string(),
+            // The files in the working tree aren't the ones being presented.
+            _ if self.resources.git_revision().is_some() => {
+                "Presentations loaded from a git revision can't be edited".to_string()
+            }
+            None => "There's no code block to edit".to_string(),
+            Some((_, line)) => match drawer.suspend(|| Self::run_editor(&path, line))? {
+                Ok(()) => return Ok(true),
+                Err(e) => format!("Failed to run editor: {e}"),
+            },
+        };
+        drawer.render_notice(&message)?;
+        Ok(false)
+    }
+
+    // The editor is the one in `$VISUAL` or `$EDITOR`, like git does, and is told which line to open the file at.
+    fn run_editor(path: &Path, line: usize) -> io::Result<()> {
+        let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| "vi".into());
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or("vi");
+        let status = process::Command::new(program).args(parts).arg(format!("+{line}")).arg(path).status()?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(io::Error::other(format!("{program} exited with {status}"))),
+        }
+    }
+
+    fn render(&mut self, drawer: &mut TerminalDrawer<Box<dyn Write>>) -> RenderResult {
+        if matches!(self.options.mode, PresentMode::Export) {
+            // Exports must look the same no matter where they're generated so honor the profile.
+            let profile = self.state.presentation().export_profile();
+            drawer.pin_dimensions(profile.map(|profile| (profile.columns, profile.rows)));
+        } else if let PresenterState::Presenting(presentation) = &self.state {
+            // Terminals are resized while this is shown so it's redrawn with the new size until it's big enough.
+            if let Some(minimum) = presentation.minimum_size() {
+                if !drawer.fits(minimum)? {
+                    return drawer.render_too_small(Some(minimum));
+                }
+            }
+        }
         let result = match &self.state {
-            PresenterState::Presenting(presentation) => drawer.render_slide(presentation),
+            PresenterState::Presenting(presentation) if self.focus == Focus::Overview => {
+                drawer.render_overview(&SlideOverview::new(&self.thumbnails, presentation.current_slide_index()))
+            }
+            // Running code is confirmed in the presenter console, as the instances following it run it.
+            PresenterState::Presenting(presentation) if matches!(self.options.role, Some(PresenterRole::Presenter)) => {
+                let console = PresenterConsole::new(presentation, self.started_at.elapsed());
+                let result = drawer.render_console(&console).and_then(|_| match &self.focus {
+                    Focus::ConfirmExecution { blocks } => {
+                        let prompt = ExecutionPrompt::new(presentation.current_slide(), blocks.as_deref());
+                        drawer.render_execution_prompt(&prompt)
+                    }
+                    _ => Ok(()),
+                });
+                self.console = Some(console);
+                result
+            }
+            PresenterState::Presenting(presentation) => {
+                drawer.render_slide(presentation, self.showing_pause_markers).and_then(|_| match &self.focus {
+                    Focus::CodePicker { selected } => {
+                        drawer.render_code_picker(&CodePicker::new(presentation.current_slide(), *selected))
+                    }
+                    Focus::ConfirmExecution { blocks } => {
+                        let prompt = ExecutionPrompt::new(presentation.current_slide(), blocks.as_deref());
+                        drawer.render_execution_prompt(&prompt)
+                    }
+                    Focus::Slide | Focus::Overview => Ok(()),
+                })
+            }
             PresenterState::Failure { error, .. } => drawer.render_error(error),
             PresenterState::Empty => panic!("cannot render without state"),
         };
-        // If the screen is too small, simply ignore this. Eventually the user will resize the
-        // screen.
-        if matches!(result, Err(RenderError::TerminalTooSmall)) { Ok(()) } else { result }
+        if self.pending_force_reload && result.is_ok() {
+            return drawer.render_notice("Press <ctrl>f again to reload the presentation");
+        }
+        // Warnings are shown in the bottom row of the slides they were found in.
+        let showing_slide = result.is_ok() && self.focus == Focus::Slide;
+        if let (PresenterState::Presenting(presentation), true) = (&self.state, showing_slide) {
+            let warnings: Vec<_> = presentation.current_slide_warnings().collect();
+            if let Some(warning) = warnings.first() {
+                let message = match warnings.len() {
+                    1 => format!("warning: {}", warning.message),
+                    count => format!("warning: {} (and {} more)", warning.message, count - 1),
+                };
+                return drawer.render_notice(&message);
+            }
+        }
+        match result {
+            Err(RenderError::TerminalTooSmall) => drawer.render_too_small(None),
+            result => result,
+        }
     }
 
     fn apply_command(&mut self, command: Command) -> CommandSideEffect {
+        // Force reloads have to be confirmed by asking for one twice in a row.
+        let confirming_reload = mem::take(&mut self.pending_force_reload);
+        if matches!(command, Command::ForceReload) {
+            if confirming_reload {
+                return CommandSideEffect::ForceReload;
+            }
+            self.pending_force_reload = self.reloadable;
+            return CommandSideEffect::Redraw;
+        }
+        match self.apply_presentation_command(command) {
+            // Any other command cancels the reload so make sure its notice goes away.
+            CommandSideEffect::None if confirming_reload => CommandSideEffect::Redraw,
+            side_effect => side_effect,
+        }
+    }
+
+    fn apply_presentation_command(&mut self, command: Command) -> CommandSideEffect {
         // These ones always happens no matter our state.
         match command {
             Command::Reload => {
                 return CommandSideEffect::Reload;
             }
             Command::HardReload => {
-                if matches!(self.mode, PresentMode::Development) {
+                if matches!(self.options.mode, PresentMode::Development) {
                     self.resources.clear();
                 }
                 return CommandSideEffect::Reload;
@@ -126,19 +590,54 @@ impl<'a> Presenter<'a> {
             Command::Exit => return CommandSideEffect::Exit,
             _ => (),
         };
+        if let Some(side_effect) = self.apply_execution_command(&command) {
+            return side_effect;
+        }
 
         // Now apply the commands that require a presentation.
         let PresenterState::Presenting(presentation) = &mut self.state else {
             return CommandSideEffect::None;
         };
-        let needs_redraw = match command {
-            Command::Redraw => true,
-            Command::JumpNextSlide => presentation.jump_next_slide(),
-            Command::JumpPreviousSlide => presentation.jump_previous_slide(),
-            Command::JumpFirstSlide => presentation.jump_first_slide(),
-            Command::JumpLastSlide => presentation.jump_last_slide(),
-            Command::JumpSlide(number) => presentation.jump_slide(number.saturating_sub(1) as usize),
-            Command::RenderWidgets => {
+        let undoing = matches!(command, Command::UndoNavigation);
+        let position = presentation.position();
+        let slide_index = presentation.current_slide_index();
+        let needs_redraw = match (command, self.focus.clone()) {
+            // The presenter console doesn't display widgets, the audience instances run them.
+            (Command::RenderWidgets, _) if matches!(self.options.role, Some(PresenterRole::Presenter)) => false,
+            (Command::JumpNextSlide, Focus::CodePicker { selected }) => {
+                let count = presentation.current_slide().visible_code_blocks().count();
+                self.focus = Focus::CodePicker { selected: (selected + 1).min(count.saturating_sub(1)) };
+                true
+            }
+            (Command::JumpPreviousSlide, Focus::CodePicker { selected }) => {
+                self.focus = Focus::CodePicker { selected: selected.saturating_sub(1) };
+                true
+            }
+            (Command::CopyCode { index }, Focus::CodePicker { selected }) => {
+                self.pending_copy = Some(index.or(Some(selected + 1)));
+                self.focus = Focus::Slide;
+                true
+            }
+            (Command::EditCode { index }, Focus::CodePicker { selected }) => {
+                self.pending_edit = Some(index.or(Some(selected + 1)));
+                self.focus = Focus::Slide;
+                true
+            }
+            // The output of the code block that's selected is scrolled rather than the last one.
+            (Command::ScrollOutputUp, Focus::CodePicker { selected }) => {
+                presentation.scroll_code_output(Some(selected + 1), -1)
+            }
+            (Command::ScrollOutputDown, Focus::CodePicker { selected }) => {
+                presentation.scroll_code_output(Some(selected + 1), 1)
+            }
+            (Command::RenderWidgets, Focus::CodePicker { selected }) => {
+                self.focus = Focus::Slide;
+                if presentation.run_code_block(selected + 1) {
+                    self.slides_with_pending_widgets.insert(presentation.current_slide_index());
+                }
+                true
+            }
+            (Command::RenderWidgets, _) => {
                 if presentation.render_slide_widgets() {
                     self.slides_with_pending_widgets.insert(self.state.presentation().current_slide_index());
                     return CommandSideEffect::PollWidgets;
@@ -146,28 +645,322 @@ impl<'a> Presenter<'a> {
                     return CommandSideEffect::None;
                 }
             }
-            // These are handled above as they don't require the presentation
-            Command::Reload | Command::HardReload | Command::Exit => panic!("unreachable commands"),
+            (command, _) => self.apply_slide_command(command),
         };
+        // Nobody sees the output of code running in a slide that was left so it is stopped.
+        let presentation = self.state.presentation();
+        if slide_index != presentation.current_slide_index() {
+            presentation.cancel_slide_widgets(slide_index);
+        }
+        // The picker and the prompt list the code blocks in the current slide so they make no sense in any other one.
+        let lists_code_blocks = matches!(self.focus, Focus::CodePicker { .. } | Focus::ConfirmExecution { .. });
+        if lists_code_blocks && slide_index != presentation.current_slide_index() {
+            self.focus = Focus::Slide;
+        }
+        if !undoing {
+            self.history.record(position, presentation.position());
+        }
+        if position != presentation.position() {
+            self.stepped_at = Instant::now();
+        }
         if needs_redraw { CommandSideEffect::Redraw } else { CommandSideEffect::None }
     }
 
-    fn try_reload(&mut self, path: &Path) {
-        if matches!(self.mode, PresentMode::Presentation) {
+    fn apply_slide_command(&mut self, command: Command) -> bool {
+        let PresenterState::Presenting(presentation) = &mut self.state else {
+            return false;
+        };
+        match command {
+            Command::Redraw => true,
+            Command::CancelExecution => presentation.cancel_slide_widgets(presentation.current_slide_index()),
+            Command::ScrollOutputUp => presentation.scroll_code_output(None, -1),
+            Command::ScrollOutputDown => presentation.scroll_code_output(None, 1),
+            Command::ToggleOverview => {
+                self.focus = if self.focus == Focus::Overview { Focus::Slide } else { Focus::Overview };
+                true
+            }
+            Command::ToggleCodePicker => {
+                self.focus = match self.focus {
+                    Focus::CodePicker { .. } => Focus::Slide,
+                    Focus::Slide | Focus::Overview | Focus::ConfirmExecution { .. } => {
+                        Focus::CodePicker { selected: 0 }
+                    }
+                };
+                true
+            }
+            Command::TogglePauseMarkers if matches!(self.options.mode, PresentMode::Development) => {
+                self.showing_pause_markers = !self.showing_pause_markers;
+                true
+            }
+            Command::TogglePauseMarkers => false,
+            // The overview moves between whole slides rather than through every step in them.
+            Command::JumpNextSlide if self.focus == Focus::Overview => {
+                presentation.jump_slide(presentation.current_slide_index() + 1)
+            }
+            Command::JumpPreviousSlide if self.focus == Focus::Overview => match presentation.current_slide_index() {
+                0 => false,
+                index => presentation.jump_slide(index - 1),
+            },
+            Command::JumpNextSlide => {
+                let moved = presentation.jump_next_slide();
+                if moved {
+                    self.pending_cue = Self::boundary_cue_message(presentation);
+                }
+                moved
+            }
+            Command::JumpPreviousSlide => presentation.jump_previous_slide(),
+            Command::JumpFirstSlide => presentation.jump_first_slide(),
+            Command::JumpLastSlide => presentation.jump_last_slide(),
+            Command::JumpSlide(number) => presentation.jump_slide(number.saturating_sub(1) as usize),
+            Command::JumpPosition { slide, chunk } => {
+                let moved = presentation.jump_slide(slide);
+                if moved {
+                    presentation.jump_chunk(chunk);
+                }
+                moved
+            }
+            Command::CopyCode { index } => {
+                self.pending_copy = Some(index);
+                true
+            }
+            Command::EditCode { index } => {
+                self.pending_edit = Some(index);
+                true
+            }
+            Command::UndoNavigation => match self.history.pop() {
+                Some(position) => presentation.jump_position(position),
+                None => false,
+            },
+            // These are handled above as they don't require the presentation
+            Command::Reload
+            | Command::HardReload
+            | Command::ForceReload
+            | Command::Exit
+            | Command::RenderWidgets
+            | Command::RunConfirmedCode { .. } => {
+                panic!("unreachable commands")
+            }
+        }
+    }
+
+    // Code only runs once what it's going to run was shown and confirmed, if it has to be. While that's being
+    // asked only confirming or cancelling it does anything, along with the commands that don't come from the
+    // keyboard.
+    fn apply_execution_command(&mut self, command: &Command) -> Option<CommandSideEffect> {
+        let PresenterState::Presenting(presentation) = &self.state else {
+            return None;
+        };
+        match (command, &self.focus) {
+            (Command::RenderWidgets, Focus::ConfirmExecution { blocks }) => {
+                let blocks = blocks.clone();
+                self.focus = Focus::Slide;
+                Some(self.run_confirmed_code(blocks))
+            }
+            (Command::CancelExecution, Focus::ConfirmExecution { .. }) => {
+                self.focus = Focus::Slide;
+                Some(CommandSideEffect::Redraw)
+            }
+            (Command::RunConfirmedCode { blocks }, _) => Some(self.run_code(blocks.clone())),
+            (Command::Redraw | Command::JumpPosition { .. }, _) => None,
+            (_, Focus::ConfirmExecution { .. }) => Some(CommandSideEffect::None),
+            (Command::RenderWidgets, focus) if self.options.execution.confirm => {
+                let blocks = match focus {
+                    Focus::CodePicker { selected } => Some(vec![selected + 1]),
+                    _ => None,
+                };
+                // Only widgets that don't run code are rendered, so there's nothing to confirm.
+                if ExecutionPrompt::new(presentation.current_slide(), blocks.as_deref()).is_empty() {
+                    self.focus = Focus::Slide;
+                    return Some(self.run_confirmed_code(blocks));
+                }
+                self.focus = Focus::ConfirmExecution { blocks };
+                Some(CommandSideEffect::Redraw)
+            }
+            _ => None,
+        }
+    }
+
+    // Runs code that was confirmed to run here and in the instances following this one.
+    fn run_confirmed_code(&mut self, blocks: Option<Vec<usize>>) -> CommandSideEffect {
+        self.relay_command(&Command::RunConfirmedCode { blocks: blocks.clone() });
+        self.run_code(blocks)
+    }
+
+    // Runs code blocks in the current slide by number, or every widget in it if there's none. The presenter console
+    // doesn't display widgets so it leaves this up to the instances following it.
+    fn run_code(&mut self, blocks: Option<Vec<usize>>) -> CommandSideEffect {
+        if matches!(self.options.role, Some(PresenterRole::Presenter)) {
+            return CommandSideEffect::Redraw;
+        }
+        let PresenterState::Presenting(presentation) = &mut self.state else {
+            return CommandSideEffect::None;
+        };
+        let started = match blocks {
+            Some(blocks) => blocks.into_iter().filter(|number| presentation.run_code_block(*number)).count() > 0,
+            None => presentation.render_slide_widgets(),
+        };
+        if started {
+            self.slides_with_pending_widgets.insert(presentation.current_slide_index());
+        }
+        CommandSideEffect::Redraw
+    }
+
+    // Code blocks that are replaced by their output only run once that's confirmed, which is asked for as soon as
+    // they show up. The instances following a presenter console leave this up to it.
+    fn confirm_command_output(&mut self) {
+        let PresenterState::Presenting(presentation) = &self.state else {
+            return;
+        };
+        if self.focus != Focus::Slide || matches!(self.options.role, Some(PresenterRole::Audience)) {
+            return;
+        }
+        let slide = presentation.current_slide();
+        let prompted = (presentation.current_slide_index(), slide.visible_code_blocks().count());
+        if self.command_output_prompted == Some(prompted) {
+            return;
+        }
+        self.command_output_prompted = Some(prompted);
+        let not_started = |block: &CodeBlock| {
+            let state = block.execution.as_ref().map(|execution| execution.poll_state());
+            matches!(state, Some(RenderOnDemandState::NotStarted))
+        };
+        let blocks: Vec<_> = slide
+            .visible_code_blocks()
+            .enumerate()
+            .filter(|(_, block)| block.replaced_with_output && not_started(block))
+            .map(|(index, _)| index + 1)
+            .collect();
+        if !blocks.is_empty() {
+            self.focus = Focus::ConfirmExecution { blocks: Some(blocks) };
+        }
+    }
+
+    fn boundary_cue_message(presentation: &Presentation) -> Option<String> {
+        let slide = presentation.current_slide();
+        if !presentation.is_current_slide_last_step() {
+            return None;
+        }
+        let slide_number = presentation.current_slide_index() + 1;
+        if presentation.is_last_slide() {
+            Some(format!("slide {slide_number}: final slide"))
+        } else if slide.has_steps() {
+            Some(format!("slide {slide_number}: last step"))
+        } else {
+            None
+        }
+    }
+
+    fn try_reload(&mut self, path: &Path, force: bool) {
+        if !self.reloadable || (!force && matches!(self.options.mode, PresentMode::Presentation)) {
             return;
         }
         self.slides_with_pending_widgets.clear();
         match self.load_presentation(path) {
             Ok(mut presentation) => {
                 let current = self.state.presentation();
+                let slide_mapping = PresentationDiffer::slide_mapping(current, &presentation);
                 if let Some(modification) = PresentationDiffer::find_first_modification(current, &presentation) {
+                    self.thumbnails.invalidate_from(modification.slide_index);
                     presentation.jump_slide(modification.slide_index);
                     presentation.jump_chunk(modification.chunk_index);
                 } else {
-                    presentation.jump_slide(current.current_slide_index());
+                    let current_slide = current.current_slide_index();
+                    presentation.jump_slide(slide_mapping[current_slide].unwrap_or(current_slide));
                     presentation.jump_chunk(current.current_chunk());
                 }
-                self.state = PresenterState::Presenting(presentation)
+                self.history.remap_slides(&slide_mapping);
+                self.thumbnails.refresh(&presentation);
+                self.control_slide = None;
+                // The code being confirmed may not be there anymore, and whatever code is replaced by its output
+                // has to be confirmed again.
+                if let Focus::ConfirmExecution { blocks } = &self.focus {
+                    if ExecutionPrompt::new(presentation.current_slide(), blocks.as_deref()).is_empty() {
+                        self.focus = Focus::Slide;
+                    }
+                }
+                self.command_output_prompted = None;
+                self.state = PresenterState::Presenting(presentation);
+                self.update_mode();
+            }
+            Err(e) => {
+                let presentation = mem::take(&mut self.state).into_presentation();
+                self.state = PresenterState::Failure { error: e.to_string(), presentation }
+            }
+        };
+    }
+
+    // The options set outside of the presentation, resolved against the ones in its front matter.
+    fn presentation_options(&self) -> PresentationOptions {
+        self.options.presentation_options.resolve(self.state.presentation().options())
+    }
+
+    // Presentation mode can be asked for in the front matter too, which is only known once the presentation is built
+    // and can change every time it's reloaded.
+    fn update_mode(&mut self) {
+        if !self.reloadable || matches!(self.options.mode, PresentMode::Export) {
+            return;
+        }
+        self.options.mode = match self.presentation_options().present {
+            Some(true) => PresentMode::Presentation,
+            _ => PresentMode::Development,
+        };
+    }
+
+    // The instances following a presenter console move along with it, and nothing moves while the picker or the
+    // prompt are shown.
+    fn is_auto_advance_due(&self) -> bool {
+        let Some(interval) = self.presentation_options().auto_advance else {
+            return false;
+        };
+        let follows = matches!(self.options.role, Some(PresenterRole::Audience));
+        let exporting = matches!(self.options.mode, PresentMode::Export);
+        !follows
+            && !exporting
+            && self.focus == Focus::Slide
+            && self.stepped_at.elapsed() >= Duration::from_secs(interval.get())
+    }
+
+    fn is_refresh_due(&self) -> bool {
+        let PresenterState::Presenting(presentation) = &self.state else {
+            return false;
+        };
+        match presentation.current_slide().metadata().refresh_every {
+            Some(interval) => self.reloadable && self.refreshed_at.elapsed() >= interval,
+            None => false,
+        }
+    }
+
+    // Refreshing builds the presentation again but only the current slide's commands run and only that
+    // slide is replaced, so nothing else that's going on in the presentation is lost.
+    fn refresh_slide(&mut self, path: &Path) {
+        self.refreshed_at = Instant::now();
+        let current_index = self.state.presentation().current_slide_index();
+        match self.load_presentation_with(path, Some(current_index)) {
+            Ok(refreshed) => {
+                let PresenterState::Presenting(presentation) = &mut self.state else {
+                    return;
+                };
+                let Some(ran) = presentation.replace_current_slide(refreshed) else {
+                    return;
+                };
+                self.thumbnails.invalidate_from(current_index);
+                self.thumbnails.refresh(presentation);
+                self.control_slide = None;
+                // Only code that was already run is run again, once that's confirmed if it has to be, which the
+                // instances following a presenter console leave up to it. The console doesn't know what ran in
+                // them though, so it asks about the code blocks that are replaced by their output again instead.
+                match (&self.options.role, self.options.execution.confirm) {
+                    (Some(PresenterRole::Presenter), _) => self.command_output_prompted = None,
+                    (_, false) => {
+                        self.run_code(Some(ran));
+                    }
+                    (Some(PresenterRole::Audience), true) => (),
+                    (None, true) => {
+                        if !ran.is_empty() && self.focus == Focus::Slide {
+                            self.focus = Focus::ConfirmExecution { blocks: Some(ran) };
+                        }
+                    }
+                }
             }
             Err(e) => {
                 let presentation = mem::take(&mut self.state).into_presentation();
@@ -176,12 +969,51 @@ impl<'a> Presenter<'a> {
         };
     }
 
+    // Open the terminal itself, regardless of where stdout goes.
+    fn open_terminal() -> io::Result<File> {
+        #[cfg(windows)]
+        const PATH: &str = "CONOUT$";
+        #[cfg(not(windows))]
+        const PATH: &str = "/dev/tty";
+        OpenOptions::new().write(true).open(PATH)
+    }
+
     fn load_presentation(&mut self, path: &Path) -> Result<Presentation, LoadPresentationError> {
-        let content = fs::read_to_string(path).map_err(LoadPresentationError::Reading)?;
+        self.load_presentation_with(path, None)
+    }
+
+    fn load_presentation_with(
+        &mut self,
+        path: &Path,
+        command_output_slide: Option<usize>,
+    ) -> Result<Presentation, LoadPresentationError> {
+        let content = self.resources.presentation(path).map_err(LoadPresentationError::Reading)?;
         let elements = self.parser.parse(&content)?;
-        let mut options = PresentationBuilderOptions::default();
-        if matches!(self.mode, PresentMode::Export) {
+        // Commands run when their output shows up instead if running code has to be confirmed, unless exporting
+        // where nothing runs while presenting.
+        let confirm = self.options.execution.confirm && !matches!(self.options.mode, PresentMode::Export);
+        let command_output = match CommandOutputPolicy::from_allowed(self.options.allow_command_output) {
+            CommandOutputPolicy::Run if confirm => CommandOutputPolicy::Confirm,
+            policy => policy,
+        };
+        let execution_transcript =
+            self.options.execution_transcript.clone().map(|path| ExecutionTranscript::new(path, Arc::new(SystemClock)));
+        let mut options = PresentationBuilderOptions {
+            polls: self.polls.clone(),
+            presentation_options: self.options.presentation_options.clone(),
+            accumulate_errors: true,
+            unknown_commands: self.options.unknown_commands,
+            command_output,
+            command_output_slide,
+            placeholders: self.options.placeholders.clone(),
+            execution: self.options.execution.clone(),
+            execution_transcript,
+            sessions: self.sessions.clone(),
+            ..Default::default()
+        };
+        if matches!(self.options.mode, PresentMode::Export) {
             options.allow_mutations = false;
+            options.use_export_profile = true;
         }
         let presentation = PresentationBuilder::new(
             self.default_highlighter.clone(),
@@ -189,16 +1021,36 @@ impl<'a> Presenter<'a> {
             &mut self.resources,
             options,
         )
-        .build(elements)?;
-        Ok(presentation)
+        .build(elements);
+        // Watch these even if the build failed so fixing an included file fixes the presentation.
+        self.commands.watch_included_files(self.resources.included_files());
+        Ok(presentation?)
     }
 }
 
+/// What commands apply to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum Focus {
+    /// The current slide.
+    #[default]
+    Slide,
+
+    /// The overview of every slide, where navigating selects a slide.
+    Overview,
+
+    /// The picker for the code blocks in the current slide, where navigating selects a code block.
+    CodePicker { selected: usize },
+
+    /// The prompt that asks whether to run code blocks by number, or every visible one when there are none.
+    ConfirmExecution { blocks: Option<Vec<usize>> },
+}
+
 enum CommandSideEffect {
     Exit,
     Redraw,
     PollWidgets,
     Reload,
+    ForceReload,
     None,
 }
 
@@ -239,6 +1091,110 @@ impl PresenterState {
     }
 }
 
+/// The options used when presenting.
+pub struct PresenterOptions {
+    /// The presentation mode.
+    pub mode: PresentMode,
+
+    /// The presentation level options set outside of the presentation, which are resolved against its front matter
+    /// every time it's loaded.
+    pub presentation_options: ExternalOptions,
+
+    /// The hooks to run when presentation events happen.
+    pub hooks: Vec<HookConfig>,
+
+    /// The template used to set the terminal's window title, if any.
+    ///
+    /// This can contain `{title}`, `{current_slide}`, and `{total_slides}` placeholders.
+    pub window_title: Option<String>,
+
+    /// The address to serve the audience web view on, if any.
+    pub audience_view: Option<SocketAddr>,
+
+    /// The role this instance plays when the presentation is driven from more than one terminal.
+    pub role: Option<PresenterRole>,
+
+    /// The address of the control socket used to keep instances with different roles in sync.
+    pub control_address: SocketAddr,
+
+    /// Whether to minimize the amount of data written to the terminal, e.g. over slow SSH links.
+    pub low_bandwidth: bool,
+
+    /// The path to write the time spent on every slide to when the presentation ends, if any.
+    pub timing_report: Option<PathBuf>,
+
+    /// What to do with comments that contain unknown commands.
+    pub unknown_commands: UnknownCommandPolicy,
+
+    /// Whether to run the commands in `command_output` comments.
+    pub allow_command_output: bool,
+
+    /// The placeholders that can be used on top of the built in ones.
+    pub placeholders: PlaceholderConfig,
+
+    /// Another channel the presentation can be controlled through, if any.
+    pub control: Option<ControlChannel>,
+
+    /// What to show in the terminal multiplexer the presentation runs in.
+    pub multiplexer: MultiplexerConfig,
+
+    /// How to play the sounds in the presentation.
+    pub sound: SoundConfig,
+
+    /// The commands used to compile code blocks before executing them.
+    pub execution: ExecutionConfig,
+
+    /// The path to append a transcript of every run of an executable code block to, if any.
+    pub execution_transcript: Option<PathBuf>,
+
+    /// The area around the edges of the terminal that's left empty.
+    pub safe_area: SafeAreaConfig,
+}
+
+/// A channel other programs can use to control a presentation.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ControlChannel {
+    /// Read JSON-RPC commands from stdin and write events into stdout.
+    Stdio,
+}
+
+/// The role an instance plays when presenting over more than one terminal.
+#[derive(Clone, Debug, clap::ValueEnum)]
+pub enum PresenterRole {
+    /// Display the slides, following the instance with the presenter role.
+    Audience,
+
+    /// Display the presenter console and drive every instance with the audience role.
+    Presenter,
+}
+
+/// A non visual cue that indicates the next keypress will change slides.
+#[derive(Clone, Debug, PartialEq, Eq, clap::ValueEnum, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryCue {
+    /// Ring the terminal bell.
+    Bell,
+
+    /// Emit an OSC 9 desktop notification.
+    Notification,
+}
+
+/// The way images are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageProtocol {
+    /// Use the best protocol the terminal supports.
+    #[default]
+    Auto,
+
+    /// Draw images as sixel.
+    #[cfg(feature = "sixel")]
+    Sixel,
+
+    /// Approximate images using colored half block characters, which works on any terminal.
+    HalfBlocks,
+}
+
 /// This presentation mode.
 pub enum PresentMode {
     /// We are developing the presentation so we want live reloads when the input changes.
@@ -276,6 +1232,9 @@ pub enum PresentationError {
     #[error("io: {0}")]
     Io(#[from] io::Error),
 
+    #[error("loading compiled presentation: {0}")]
+    LoadCompiled(#[from] LoadCompiledError),
+
     #[error("fatal error: {0}")]
     Fatal(String),
 }
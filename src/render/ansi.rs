@@ -0,0 +1,270 @@
+//! Parses the ANSI escape sequences programs use to style their output.
+//!
+//! Only the sequences that set the style text is drawn with are kept. Everything else, like the
+//! ones that move the cursor around, would break the layout of the slide so it's dropped.
+
+use crate::style::{Color, Colors, TextStyle};
+use itertools::Itertools;
+use std::{iter::Peekable, mem};
+
+// The colors xterm uses for the 16 basic colors.
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// The style set by the escape sequences seen so far.
+///
+/// Programs don't necessarily reset the style at the end of a line, so this carries over from one
+/// line to the next.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AnsiStyle {
+    bold: bool,
+    italics: bool,
+    underlined: bool,
+    strikethrough: bool,
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+impl AnsiStyle {
+    /// Split a line into pieces of text and the style they're drawn with.
+    pub(crate) fn parse(&mut self, line: &str) -> Vec<(String, TextStyle)> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => {
+                    let sequence = match chars.next() {
+                        Some('[') => Self::take_csi(&mut chars),
+                        Some(']') => {
+                            Self::skip_osc(&mut chars);
+                            None
+                        }
+                        _ => None,
+                    };
+                    let Some(parameters) = sequence else {
+                        continue;
+                    };
+                    if !text.is_empty() {
+                        pieces.push((mem::take(&mut text), self.text_style()));
+                    }
+                    self.apply(&parameters);
+                }
+                '\t' => text.push_str("    "),
+                c if c.is_control() => (),
+                c => text.push(c),
+            };
+        }
+        if !text.is_empty() {
+            pieces.push((text, self.text_style()));
+        }
+        pieces
+    }
+
+    // Takes a control sequence, returning its parameters if it sets the style.
+    fn take_csi(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+        let mut parameters = String::new();
+        for c in chars {
+            match c {
+                '@'..='~' => return (c == 'm').then_some(parameters),
+                c => parameters.push(c),
+            }
+        }
+        None
+    }
+
+    // Operating system commands, like the ones that set the window's title, end in BEL or ESC \.
+    fn skip_osc(chars: &mut Peekable<impl Iterator<Item = char>>) {
+        while let Some(c) = chars.next() {
+            match c {
+                '\x07' => return,
+                '\x1b' if chars.peek() == Some(&'\\') => {
+                    chars.next();
+                    return;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Apply the parameters of a select graphic rendition sequence, e.g. `1;31` in `\x1b[1;31m`.
+    pub(crate) fn apply(&mut self, parameters: &str) {
+        let mut codes = parameters.split([';', ':']).map(|code| code.parse::<u8>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                3 => self.italics = true,
+                4 => self.underlined = true,
+                9 => self.strikethrough = true,
+                22 => self.bold = false,
+                23 => self.italics = false,
+                24 => self.underlined = false,
+                29 => self.strikethrough = false,
+                30..=37 => self.foreground = Some(Self::indexed_color(code - 30)),
+                38 => self.foreground = Self::extended_color(&mut codes),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(Self::indexed_color(code - 40)),
+                48 => self.background = Self::extended_color(&mut codes),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(Self::indexed_color(code - 90 + 8)),
+                100..=107 => self.background = Some(Self::indexed_color(code - 100 + 8)),
+                _ => (),
+            };
+        }
+    }
+
+    // Either `5;index` or `2;r;g;b`.
+    fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+        match codes.next()? {
+            5 => Some(Self::indexed_color(codes.next()?)),
+            2 => Some(Color::new(codes.next()?, codes.next()?, codes.next()?)),
+            _ => None,
+        }
+    }
+
+    // Colors in the 256 color palette: the basic colors, a 6x6x6 color cube, and a grayscale ramp.
+    fn indexed_color(index: u8) -> Color {
+        match index {
+            0..=15 => {
+                let (r, g, b) = BASIC_COLORS[usize::from(index)];
+                Color::new(r, g, b)
+            }
+            16..=231 => {
+                let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+                let index = index - 16;
+                Color::new(level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            _ => {
+                let level = 8 + (index - 232) * 10;
+                Color::new(level, level, level)
+            }
+        }
+    }
+
+    /// Get the foreground color, if one was set.
+    pub(crate) fn foreground(&self) -> Option<Color> {
+        self.foreground
+    }
+
+    /// Get the background color, if one was set.
+    pub(crate) fn background(&self) -> Option<Color> {
+        self.background
+    }
+
+    /// Check whether text is bold.
+    pub(crate) fn is_bold(&self) -> bool {
+        self.bold
+    }
+
+    /// Check whether text is underlined.
+    ///
+    /// Text styles have no underline so this is only seen by whoever looks at the style directly.
+    pub(crate) fn is_underlined(&self) -> bool {
+        self.underlined
+    }
+
+    fn text_style(&self) -> TextStyle {
+        let colors = Colors { foreground: self.foreground, background: self.background };
+        let mut style = TextStyle::default().colors(colors);
+        if self.bold {
+            style = style.bold();
+        }
+        if self.italics {
+            style = style.italics();
+        }
+        if self.strikethrough {
+            style = style.strikethrough();
+        }
+        style
+    }
+}
+
+/// Turn characters and the style they're drawn with back into text that can be printed.
+///
+/// The given colors are used wherever the style doesn't set its own so text with no colors is
+/// drawn like the text around it.
+pub(crate) fn styled_text(chars: &[(char, &TextStyle)], fallback: &Colors) -> String {
+    let fallback = TextStyle::default().colors(fallback.clone());
+    let mut text = String::new();
+    for (style, chars) in &chars.iter().group_by(|(_, style)| *style) {
+        let mut style = style.clone();
+        style.merge(&fallback);
+        text.push_str(&style.apply(chars.map(|(c, _)| c).collect::<String>()).to_string());
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn colored(foreground: Color) -> TextStyle {
+        TextStyle::default().colors(Colors { foreground: Some(foreground), background: None })
+    }
+
+    #[rstest]
+    #[case::plain("hello", vec![("hello", TextStyle::default())])]
+    #[case::basic(
+        "\x1b[31merror\x1b[0m: oops",
+        vec![("error", colored(Color::new(0xcd, 0, 0))), (": oops", TextStyle::default())]
+    )]
+    #[case::bold_bright("\x1b[1;92mok", vec![("ok", colored(Color::new(0, 0xff, 0)).bold())])]
+    #[case::indexed("\x1b[38;5;196mred", vec![("red", colored(Color::new(0xff, 0, 0)))])]
+    #[case::cube("\x1b[38;5;37mcube", vec![("cube", colored(Color::new(0, 175, 175)))])]
+    #[case::grayscale("\x1b[38;5;232mgray", vec![("gray", colored(Color::new(8, 8, 8)))])]
+    #[case::rgb("\x1b[38;2;1;2;3mrgb", vec![("rgb", colored(Color::new(1, 2, 3)))])]
+    #[case::cursor_movement("\x1b[2K\x1b[1Gdone\r", vec![("done", TextStyle::default())])]
+    #[case::title("\x1b]0;title\x07hi", vec![("hi", TextStyle::default())])]
+    #[case::unterminated("hi\x1b[31", vec![("hi", TextStyle::default())])]
+    fn parse(#[case] line: &str, #[case] expected: Vec<(&str, TextStyle)>) {
+        let pieces = AnsiStyle::default().parse(line);
+        let expected: Vec<_> = expected.into_iter().map(|(text, style)| (text.to_string(), style)).collect();
+        assert_eq!(pieces, expected);
+    }
+
+    #[test]
+    fn fallback_colors() {
+        let red = colored(Color::new(0xff, 0, 0));
+        let plain = TextStyle::default();
+        let fallback = Colors { foreground: Some(Color::new(0, 0, 0xff)), background: None };
+        let text = styled_text(&[('a', &red), ('b', &red), ('c', &plain)], &fallback);
+        let expected = format!("{}{}", red.apply("ab"), TextStyle::default().colors(fallback.clone()).apply("c"));
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn style_carries_over() {
+        let mut style = AnsiStyle::default();
+        style.parse("\x1b[3mstarts here");
+        assert_eq!(style.parse("and goes on"), &[("and goes on".to_string(), TextStyle::default().italics())]);
+        style.parse("\x1b[23m");
+        assert_eq!(style.parse("done"), &[("done".to_string(), TextStyle::default())]);
+    }
+
+    #[test]
+    fn underlined() {
+        let mut style = AnsiStyle::default();
+        style.apply("4");
+        assert!(style.is_underlined());
+        style.apply("24");
+        assert!(!style.is_underlined());
+    }
+}
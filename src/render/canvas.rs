@@ -0,0 +1,123 @@
+use crate::{
+    markdown::{
+        elements::StyledText,
+        text::{WeightedLine, WeightedText},
+    },
+    style::TextStyle,
+};
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CanvasCell {
+    // `None` means this cell is taken by the wide character to its left.
+    character: Option<char>,
+    style: TextStyle,
+}
+
+impl Default for CanvasCell {
+    fn default() -> Self {
+        Self { character: Some(' '), style: Default::default() }
+    }
+}
+
+/// A fixed size grid of styled characters.
+///
+/// This is what [crate::presentation::CustomRender] operations paint into. Anything painted
+/// outside of the canvas is clipped.
+#[derive(Clone, Debug)]
+pub(crate) struct Canvas {
+    columns: u16,
+    rows: u16,
+    cells: Vec<CanvasCell>,
+}
+
+impl Canvas {
+    /// Construct an empty canvas of the given size.
+    pub(crate) fn new(columns: u16, rows: u16) -> Self {
+        Self { columns, rows, cells: vec![CanvasCell::default(); columns as usize * rows as usize] }
+    }
+
+    /// The number of columns in this canvas.
+    pub(crate) fn columns(&self) -> u16 {
+        self.columns
+    }
+
+    /// The number of rows in this canvas.
+    pub(crate) fn rows(&self) -> u16 {
+        self.rows
+    }
+
+    /// Print a piece of text starting at the given row and column.
+    ///
+    /// Text doesn't wrap: whatever doesn't fit in the row is discarded.
+    pub(crate) fn print(&mut self, row: u16, column: u16, text: &str, style: &TextStyle) {
+        if row >= self.rows {
+            return;
+        }
+        let mut column = column as usize;
+        for character in text.chars() {
+            let width = character.width().unwrap_or(0);
+            if width == 0 {
+                continue;
+            }
+            if column + width > self.columns as usize {
+                break;
+            }
+            let index = row as usize * self.columns as usize + column;
+            self.cells[index] = CanvasCell { character: Some(character), style: style.clone() };
+            for cell in &mut self.cells[index + 1..index + width] {
+                *cell = CanvasCell { character: None, style: style.clone() };
+            }
+            column += width;
+        }
+    }
+
+    /// Convert this canvas into lines of text, one per row.
+    pub(crate) fn into_lines(self) -> Vec<WeightedLine> {
+        let mut lines = Vec::new();
+        for row in self.cells.chunks(self.columns.max(1) as usize) {
+            let mut texts: Vec<StyledText> = Vec::new();
+            for cell in row {
+                let Some(character) = cell.character else {
+                    continue;
+                };
+                match texts.last_mut() {
+                    Some(text) if text.style == cell.style => text.text.push(character),
+                    _ => texts.push(StyledText::new(character.to_string(), cell.style.clone())),
+                };
+            }
+            lines.push(WeightedLine::from(texts.into_iter().map(WeightedText::from).collect::<Vec<_>>()));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line_texts(line: &WeightedLine) -> Vec<String> {
+        line.iter_texts().map(|text| text.text.text.clone()).collect()
+    }
+
+    #[test]
+    fn print() {
+        let mut canvas = Canvas::new(5, 2);
+        canvas.print(0, 1, "hi", &TextStyle::default());
+        canvas.print(1, 3, "bye", &TextStyle::default().bold());
+        canvas.print(2, 0, "nope", &TextStyle::default());
+        let lines = canvas.into_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(line_texts(&lines[0]), &[" hi  "]);
+        assert_eq!(line_texts(&lines[1]), &["   ", "by"]);
+    }
+
+    #[test]
+    fn wide_characters() {
+        let mut canvas = Canvas::new(5, 1);
+        canvas.print(0, 0, "日本語", &TextStyle::default());
+        let lines = canvas.into_lines();
+        assert_eq!(line_texts(&lines[0]), &["日本 "]);
+        assert_eq!(lines[0].width(), 5);
+    }
+}
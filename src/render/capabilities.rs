@@ -0,0 +1,123 @@
+use super::{properties::WindowSize, terminal::TerminalMode};
+use crate::presenter::ImageProtocol;
+
+/// The way images are drawn in the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum GraphicsProtocol {
+    /// Let viuer choose between the kitty and iterm2 protocols, falling back to unicode blocks.
+    Viuer,
+
+    /// Let viuer draw images as sixel.
+    #[cfg(feature = "sixel")]
+    Sixel,
+
+    /// Approximate images using colored half block characters, which works on any terminal.
+    HalfBlocks,
+}
+
+/// What the terminal we're drawing on is able to do.
+///
+/// This is the only place that decides how images are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TerminalCapabilities {
+    /// The protocol used to draw images, if they can be drawn at all.
+    pub(crate) graphics: Option<GraphicsProtocol>,
+}
+
+impl TerminalCapabilities {
+    /// Find out what the terminal supports.
+    ///
+    /// Graphics protocols send lots of data so images are approximated in low bandwidth mode. The
+    /// same goes for headless mode, as nothing can interpret graphics protocols there.
+    pub(crate) fn detect(mode: TerminalMode) -> Self {
+        let graphics = match mode {
+            TerminalMode::Default => Some(Self::graphics_protocol()),
+            TerminalMode::LowBandwidth | TerminalMode::Headless => Some(GraphicsProtocol::HalfBlocks),
+        };
+        Self { graphics }
+    }
+
+    /// Stop using protocols that can only draw into stdout, for when the terminal is written to
+    /// some other way.
+    ///
+    /// viuer always writes into stdout so only half blocks are left.
+    pub(crate) fn without_stdout(self) -> Self {
+        let graphics = self.graphics.map(|_| GraphicsProtocol::HalfBlocks);
+        Self { graphics }
+    }
+
+    /// Draw images using the given protocol rather than the detected one, if images can be drawn at all.
+    pub(crate) fn with_image_protocol(self, protocol: ImageProtocol) -> Self {
+        let graphics = match protocol {
+            ImageProtocol::Auto => self.graphics,
+            #[cfg(feature = "sixel")]
+            ImageProtocol::Sixel => self.graphics.map(|_| GraphicsProtocol::Sixel),
+            ImageProtocol::HalfBlocks => self.graphics.map(|_| GraphicsProtocol::HalfBlocks),
+        };
+        Self { graphics }
+    }
+
+    /// Get the protocol to draw an image with in a window, if images can be drawn at all.
+    pub(crate) fn image_protocol(&self, dimensions: &WindowSize) -> Option<GraphicsProtocol> {
+        match self.graphics? {
+            // Images can't be sized in pixels if we don't know how many pixels the window has.
+            _ if !dimensions.has_pixels => Some(GraphicsProtocol::HalfBlocks),
+            protocol => Some(protocol),
+        }
+    }
+
+    #[cfg(not(feature = "sixel"))]
+    fn graphics_protocol() -> GraphicsProtocol {
+        GraphicsProtocol::Viuer
+    }
+
+    #[cfg(feature = "sixel")]
+    fn graphics_protocol() -> GraphicsProtocol {
+        // viuer prefers sixel but terminals that support kitty or iterm2 get those as they're much faster.
+        let native = viuer::is_iterm_supported() || viuer::get_kitty_support() != viuer::KittySupport::None;
+        if !native && viuer::is_sixel_supported() {
+            GraphicsProtocol::Sixel
+        } else {
+            GraphicsProtocol::Viuer
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::low_bandwidth(TerminalMode::LowBandwidth, Some(GraphicsProtocol::HalfBlocks))]
+    #[case::headless(TerminalMode::Headless, Some(GraphicsProtocol::HalfBlocks))]
+    fn detect(#[case] mode: TerminalMode, #[case] expected: Option<GraphicsProtocol>) {
+        assert_eq!(TerminalCapabilities::detect(mode).graphics, expected);
+    }
+
+    #[rstest]
+    #[case::pixels(true, Some(GraphicsProtocol::Viuer))]
+    #[case::no_pixels(false, Some(GraphicsProtocol::HalfBlocks))]
+    fn image_protocol(#[case] has_pixels: bool, #[case] expected: Option<GraphicsProtocol>) {
+        let capabilities = TerminalCapabilities { graphics: Some(GraphicsProtocol::Viuer) };
+        let dimensions = WindowSize { rows: 10, columns: 10, width: 100, height: 200, has_pixels };
+        assert_eq!(capabilities.image_protocol(&dimensions), expected);
+        assert_eq!(TerminalCapabilities::default().image_protocol(&dimensions), None);
+    }
+
+    #[rstest]
+    #[case::auto(ImageProtocol::Auto, Some(GraphicsProtocol::Viuer))]
+    #[case::half_blocks(ImageProtocol::HalfBlocks, Some(GraphicsProtocol::HalfBlocks))]
+    fn with_image_protocol(#[case] protocol: ImageProtocol, #[case] expected: Option<GraphicsProtocol>) {
+        let capabilities = TerminalCapabilities { graphics: Some(GraphicsProtocol::Viuer) };
+        assert_eq!(capabilities.with_image_protocol(protocol).graphics, expected);
+        assert_eq!(TerminalCapabilities::default().with_image_protocol(protocol).graphics, None);
+    }
+
+    #[test]
+    fn without_stdout() {
+        let capabilities = TerminalCapabilities { graphics: Some(GraphicsProtocol::Viuer) }.without_stdout();
+        assert_eq!(capabilities.graphics, Some(GraphicsProtocol::HalfBlocks));
+        assert_eq!(TerminalCapabilities::default().without_stdout().graphics, None);
+    }
+}
@@ -0,0 +1,76 @@
+use super::canvas::Canvas;
+use crate::{presentation::CustomRender, style::TextStyle};
+use unicode_width::UnicodeWidthStr;
+
+/// A bar in a [BarChart].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Bar {
+    /// The label displayed next to the bar.
+    pub(crate) label: String,
+
+    /// The bar's value.
+    pub(crate) value: u64,
+}
+
+/// A horizontal bar chart, one bar per row.
+///
+/// Bars are scaled so the largest one takes up to half of the available columns.
+#[derive(Clone, Debug)]
+pub(crate) struct BarChart {
+    bars: Vec<Bar>,
+}
+
+impl BarChart {
+    /// Construct a new chart.
+    pub(crate) fn new(bars: Vec<Bar>) -> Self {
+        Self { bars }
+    }
+}
+
+impl CustomRender for BarChart {
+    fn measure(&self, _columns: u16) -> u16 {
+        self.bars.len() as u16
+    }
+
+    fn paint(&self, canvas: &mut Canvas) {
+        let label_width = self.bars.iter().map(|bar| bar.label.width()).max().unwrap_or_default();
+        let max_value = self.bars.iter().map(|bar| bar.value).max().unwrap_or_default().max(1);
+        let bar_width = (canvas.columns() as usize / 2).saturating_sub(label_width).max(1) as u64;
+        let style = TextStyle::default();
+        for (row, bar) in self.bars.iter().enumerate() {
+            let row = row as u16;
+            let length = (bar.value * bar_width / max_value) as usize;
+            canvas.print(row, 0, &bar.label, &style);
+            canvas.print(row, label_width as u16 + 2, &format!("{} {}", "█".repeat(length), bar.value), &style);
+        }
+    }
+
+    fn diffable_content(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paint() {
+        let chart = BarChart::new(vec![
+            Bar { label: "yes".into(), value: 4 },
+            Bar { label: "no".into(), value: 2 },
+            Bar { label: "maybe".into(), value: 0 },
+        ]);
+        assert_eq!(chart.measure(20), 3);
+
+        let mut canvas = Canvas::new(20, 3);
+        chart.paint(&mut canvas);
+        let lines: Vec<String> = canvas
+            .into_lines()
+            .iter()
+            .map(|line| line.iter_texts().map(|text| text.text.text.as_str()).collect())
+            .collect();
+        // Half of the columns minus the widest label leaves 5 columns for the bars.
+        assert_eq!(lines, &["yes    █████ 4      ", "no     ██ 2         ", "maybe   0           "]);
+    }
+}
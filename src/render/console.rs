@@ -0,0 +1,127 @@
+use crate::{
+    markdown::{
+        elements::StyledText,
+        text::{WeightedLine, WeightedText},
+    },
+    presentation::{Presentation, RenderOperation},
+    style::{Colors, TextStyle},
+    theme::{Alignment, Margin},
+};
+use std::time::Duration;
+
+/// The presenter console.
+///
+/// This is what the presenter sees in their own terminal when the audience is looking at a
+/// different one: where they are in the presentation, what comes next, and how long they've been
+/// talking for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PresenterConsole {
+    current_slide: usize,
+    total_slides: usize,
+    current_step: usize,
+    total_steps: usize,
+    current_title: Option<String>,
+    next_title: Option<Option<String>>,
+    notes: Vec<String>,
+    elapsed: Duration,
+}
+
+impl PresenterConsole {
+    /// Construct the console for the current state of a presentation.
+    pub(crate) fn new(presentation: &Presentation, elapsed: Duration) -> Self {
+        let current_index = presentation.current_slide_index();
+        let slide = presentation.current_slide();
+        let next_title = presentation.iter_slides().nth(current_index + 1).map(|slide| slide.metadata().title.clone());
+        Self {
+            current_slide: current_index + 1,
+            total_slides: presentation.iter_slides().count(),
+            current_step: presentation.current_chunk() + 1,
+            total_steps: slide.iter_chunks().count(),
+            current_title: slide.metadata().title.clone(),
+            next_title,
+            notes: slide.metadata().notes.clone(),
+            // Only whole seconds are displayed so don't keep anything else around.
+            elapsed: Duration::from_secs(elapsed.as_secs()),
+        }
+    }
+
+    /// Generate the render operations that display this console.
+    pub(crate) fn render_operations(&self) -> Vec<RenderOperation> {
+        let seconds = self.elapsed.as_secs();
+        let elapsed = format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+        let current = format!(
+            "Slide {}/{}, step {}/{}",
+            self.current_slide, self.total_slides, self.current_step, self.total_steps
+        );
+        let next = match &self.next_title {
+            Some(title) => title.clone().unwrap_or_else(|| "(untitled)".into()),
+            None => "(end of presentation)".into(),
+        };
+        let mut lines = vec![
+            Self::entry("Elapsed", elapsed),
+            Vec::new(),
+            Self::entry("Current", current),
+            Self::entry("Title", self.current_title.clone().unwrap_or_else(|| "(untitled)".into())),
+            Vec::new(),
+            Self::entry("Next", next),
+        ];
+        if !self.notes.is_empty() {
+            let header = WeightedText::from(StyledText::new("Notes:", TextStyle::default().bold()));
+            lines.extend([Vec::new(), vec![header]]);
+            lines.extend(self.notes.iter().map(|note| vec![WeightedText::from(StyledText::from(format!("• {note}")))]));
+        }
+
+        let alignment = Alignment::Left { margin: Margin::Fixed(2) };
+        let mut operations = vec![
+            RenderOperation::ClearScreen,
+            RenderOperation::SetColors(Colors::default()),
+            RenderOperation::RenderLineBreak,
+        ];
+        for line in lines {
+            operations.extend([
+                RenderOperation::RenderText { line: WeightedLine::from(line), alignment: alignment.clone() },
+                RenderOperation::RenderLineBreak,
+            ]);
+        }
+        operations
+    }
+
+    fn entry(label: &str, value: String) -> Vec<WeightedText> {
+        vec![
+            WeightedText::from(StyledText::new(format!("{label}: "), TextStyle::default().bold())),
+            WeightedText::from(StyledText::from(value)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::presentation::{Slide, SlideChunk, SlideMetadata};
+
+    #[test]
+    fn console() {
+        let slides = vec![
+            Slide::new(vec![SlideChunk::default(), SlideChunk::default()], vec![])
+                .with_metadata(SlideMetadata {
+                    title: Some("intro".into()),
+                    notes: vec!["say hi".into()],
+                    ..Default::default()
+                }),
+            Slide::new(vec![SlideChunk::default()], vec![]),
+        ];
+        let mut presentation = Presentation::new(slides);
+        let console = PresenterConsole::new(&presentation, Duration::from_millis(3500));
+        assert_eq!(console.current_slide, 1);
+        assert_eq!(console.total_slides, 2);
+        assert_eq!((console.current_step, console.total_steps), (1, 2));
+        assert_eq!(console.current_title.as_deref(), Some("intro"));
+        assert_eq!(console.next_title, Some(None));
+        assert_eq!(console.elapsed, Duration::from_secs(3));
+        assert_eq!(console.notes, &["say hi"]);
+
+        presentation.jump_last_slide();
+        let console = PresenterConsole::new(&presentation, Duration::ZERO);
+        assert_eq!(console.next_title, None);
+    }
+}
@@ -1,10 +1,19 @@
-use super::{engine::RenderEngine, terminal::Terminal};
+use super::{
+    console::PresenterConsole,
+    engine::RenderEngine,
+    overview::SlideOverview,
+    picker::CodePicker,
+    prompt::ExecutionPrompt,
+    terminal::{Terminal, TerminalMode},
+};
 use crate::{
+    config::SafeAreaConfig,
     markdown::{
         elements::StyledText,
         text::{WeightedLine, WeightedText},
     },
-    presentation::{Presentation, RenderOperation},
+    presentation::{MinimumSize, Presentation, RenderOperation},
+    presenter::{BoundaryCue, ImageProtocol},
     render::properties::WindowSize,
     style::{Color, Colors, TextStyle},
     theme::{Alignment, Margin},
@@ -17,6 +26,8 @@ pub(crate) type RenderResult = Result<(), RenderError>;
 /// Allows drawing elements in the terminal.
 pub(crate) struct TerminalDrawer<W: io::Write> {
     terminal: Terminal<W>,
+    pinned_dimensions: Option<(u16, u16)>,
+    safe_area: SafeAreaConfig,
 }
 
 impl<W> TerminalDrawer<W>
@@ -24,31 +35,213 @@ where
     W: io::Write,
 {
     /// Construct a drawer over a [std::io::Write].
-    pub(crate) fn new(handle: W) -> io::Result<Self> {
-        let terminal = Terminal::new(handle)?;
-        Ok(Self { terminal })
+    pub(crate) fn new(handle: W, mode: TerminalMode) -> io::Result<Self> {
+        let terminal = Terminal::new(handle, mode)?;
+        Ok(Self { terminal, pinned_dimensions: None, safe_area: Default::default() })
+    }
+
+    /// Render using the given number of columns and rows rather than the terminal's size.
+    pub(crate) fn pin_dimensions(&mut self, dimensions: Option<(u16, u16)>) {
+        self.pinned_dimensions = dimensions;
+    }
+
+    /// Never write into stdout, as the terminal is written to some other way.
+    pub(crate) fn avoid_stdout(&mut self) {
+        self.terminal.avoid_stdout();
+    }
+
+    /// Draw images using the given protocol rather than the detected one.
+    pub(crate) fn set_image_protocol(&mut self, protocol: ImageProtocol) {
+        self.terminal.set_image_protocol(protocol);
+    }
+
+    /// Leave the given area around the edges of the terminal empty.
+    pub(crate) fn set_safe_area(&mut self, safe_area: SafeAreaConfig) {
+        self.safe_area = safe_area;
+    }
+
+    // The dimensions of the safe area, which is all of the window unless one is set.
+    fn window_dimensions(&self) -> io::Result<WindowSize> {
+        let dimensions = WindowSize::current()?;
+        let dimensions = match self.pinned_dimensions {
+            Some((columns, rows)) => dimensions.with_dimensions(columns, rows),
+            None => dimensions,
+        };
+        let SafeAreaConfig { rows, columns, .. } = self.safe_area;
+        Ok(dimensions.shrink_rows(rows.saturating_mul(2)).shrink_columns(columns.saturating_mul(2)))
+    }
+
+    fn engine(&mut self, dimensions: WindowSize) -> RenderEngine<'_, W> {
+        let SafeAreaConfig { rows, columns, .. } = self.safe_area;
+        RenderEngine::new(&mut self.terminal, dimensions).with_origin(columns, rows)
     }
 
     /// Render a slide.
-    pub(crate) fn render_slide(&mut self, presentation: &Presentation) -> RenderResult {
-        let window_dimensions = WindowSize::current()?;
+    ///
+    /// When `pause_markers` is set, a faint marker is rendered in every place the slide pauses at.
+    pub(crate) fn render_slide(&mut self, presentation: &Presentation, pause_markers: bool) -> RenderResult {
+        let window_dimensions = self.window_dimensions()?;
         let slide = presentation.current_slide();
-        let engine = RenderEngine::new(&mut self.terminal, window_dimensions);
-        engine.render(slide.iter_operations())?;
+        let engine = self.engine(window_dimensions.clone());
+        if pause_markers {
+            engine.render(slide.iter_operations_with_pause_markers(&Self::pause_marker()))?;
+        } else {
+            engine.render(slide.iter_operations())?;
+        }
+        if self.safe_area.crop_marks {
+            self.render_crop_marks(&window_dimensions)?;
+        }
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    // The marks go right outside the safe area's corners, or on the window's edges if there's no room for them.
+    fn render_crop_marks(&mut self, dimensions: &WindowSize) -> RenderResult {
+        let SafeAreaConfig { rows, columns, .. } = self.safe_area;
+        let last_column = dimensions.columns.saturating_add(columns.saturating_mul(2)).saturating_sub(1);
+        let last_row = dimensions.rows.saturating_add(rows.saturating_mul(2)).saturating_sub(1);
+        let (left, top) = (columns.saturating_sub(1), rows.saturating_sub(1));
+        let right = columns.saturating_add(dimensions.columns).min(last_column);
+        let bottom = rows.saturating_add(dimensions.rows).min(last_row);
+        let colors = Colors { foreground: Some(Color::new(128, 128, 128)), background: None };
+        self.terminal.set_colors(colors)?;
+        for (column, row, mark) in [(left, top, "┌"), (right, top, "┐"), (left, bottom, "└"), (right, bottom, "┘")] {
+            self.terminal.move_to(column, row)?;
+            self.terminal.print_line(mark)?;
+        }
+        Ok(())
+    }
+
+    fn pause_marker() -> [RenderOperation; 2] {
+        let colors = Colors { foreground: Some(Color::new(128, 128, 128)), background: None };
+        let style = TextStyle::default().colors(colors);
+        let text = vec![WeightedText::from(StyledText::new("· · · pause · · ·", style))];
+        let alignment = Alignment::Center { minimum_size: 0, minimum_margin: Margin::Fixed(0) };
+        [RenderOperation::RenderText { line: WeightedLine::from(text), alignment }, RenderOperation::RenderLineBreak]
+    }
+
+    /// Render the presenter console.
+    pub(crate) fn render_console(&mut self, console: &PresenterConsole) -> RenderResult {
+        let window_dimensions = self.window_dimensions()?;
+        let operations = console.render_operations();
+        let engine = self.engine(window_dimensions);
+        engine.render(operations.iter())?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Render the overview of every slide.
+    pub(crate) fn render_overview(&mut self, overview: &SlideOverview) -> RenderResult {
+        let window_dimensions = self.window_dimensions()?;
+        let operations = overview.render_operations(&window_dimensions);
+        let engine = self.engine(window_dimensions);
+        engine.render(operations.iter())?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Render the code block picker on top of whatever was rendered before.
+    pub(crate) fn render_code_picker(&mut self, picker: &CodePicker) -> RenderResult {
+        let window_dimensions = self.window_dimensions()?;
+        let operations = picker.render_operations(&window_dimensions);
+        let engine = self.engine(window_dimensions);
+        engine.render(operations.iter())?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Render the prompt that asks for confirmation before running code on top of whatever was rendered before.
+    pub(crate) fn render_execution_prompt(&mut self, prompt: &ExecutionPrompt) -> RenderResult {
+        let window_dimensions = self.window_dimensions()?;
+        let operations = prompt.render_operations(&window_dimensions);
+        let engine = self.engine(window_dimensions);
+        engine.render(operations.iter())?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Give the terminal back while running something that takes it over, like an editor.
+    pub(crate) fn suspend<T>(&mut self, run: impl FnOnce() -> T) -> Result<T, RenderError> {
+        Ok(self.terminal.suspend(run)?)
+    }
+
+    /// Emit a non visual cue to let the presenter know they've reached a boundary.
+    pub(crate) fn emit_cue(&mut self, cue: &BoundaryCue, message: &str) -> RenderResult {
+        match cue {
+            BoundaryCue::Bell => self.terminal.ring_bell()?,
+            BoundaryCue::Notification => self.terminal.notify(message)?,
+        };
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Set the terminal's window title.
+    pub(crate) fn set_title(&mut self, title: &str) -> RenderResult {
+        self.terminal.set_title(title)?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Ask the terminal to copy some text into the clipboard.
+    pub(crate) fn copy_to_clipboard(&mut self, text: &str) -> RenderResult {
+        self.terminal.copy_to_clipboard(text)?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Render a notice in the bottom row, on top of whatever was rendered before.
+    pub(crate) fn render_notice(&mut self, message: &str) -> RenderResult {
+        let dimensions = self.window_dimensions()?;
+        let text = vec![WeightedText::from(StyledText::new(message, TextStyle::default().bold()))];
+        let alignment = Alignment::Center { minimum_size: 0, minimum_margin: Margin::Fixed(0) };
+        let operations = [
+            RenderOperation::SetColors(Colors {
+                foreground: Some(Color::new(255, 0, 0)),
+                background: Some(Color::new(0, 0, 0)),
+            }),
+            RenderOperation::JumpToBottomRow { index: 0 },
+            RenderOperation::RenderText { line: WeightedLine::from(text), alignment },
+        ];
+        let engine = self.engine(dimensions);
+        engine.render(operations.iter())?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+
+    /// Check whether the terminal is at least as big as the given size.
+    pub(crate) fn fits(&self, size: &MinimumSize) -> io::Result<bool> {
+        let dimensions = self.window_dimensions()?;
+        Ok(size.fits(dimensions.columns, dimensions.rows))
+    }
+
+    /// Render a screen asking to make the terminal bigger, showing its current size.
+    ///
+    /// The size the presentation needs is shown too, if it declares one.
+    pub(crate) fn render_too_small(&mut self, minimum: Option<&MinimumSize>) -> RenderResult {
+        let dimensions = self.window_dimensions()?;
+        for message in too_small_messages(&dimensions, minimum) {
+            let operations = too_small_operations(message);
+            match self.engine(dimensions.clone()).render(operations.iter()) {
+                Err(RenderError::TerminalTooSmall) => continue,
+                result => {
+                    result?;
+                    break;
+                }
+            }
+        }
         self.terminal.flush()?;
         Ok(())
     }
 
     /// Render an error.
     pub(crate) fn render_error(&mut self, message: &str) -> RenderResult {
-        let dimensions = WindowSize::current()?;
+        let dimensions = self.window_dimensions()?;
         let heading = vec![
             WeightedText::from(StyledText::new("Error loading presentation", TextStyle::default().bold())),
             WeightedText::from(StyledText::from(": ")),
         ];
-        let error = vec![WeightedText::from(StyledText::from(message))];
         let alignment = Alignment::Center { minimum_size: 0, minimum_margin: Margin::Percent(8) };
-        let operations = [
+        let mut operations = vec![
             RenderOperation::ClearScreen,
             RenderOperation::SetColors(Colors {
                 foreground: Some(Color::new(255, 0, 0)),
@@ -57,16 +250,45 @@ where
             RenderOperation::JumpToVerticalCenter,
             RenderOperation::RenderText { line: WeightedLine::from(heading), alignment: alignment.clone() },
             RenderOperation::RenderLineBreak,
-            RenderOperation::RenderLineBreak,
-            RenderOperation::RenderText { line: WeightedLine::from(error), alignment: alignment.clone() },
         ];
-        let engine = RenderEngine::new(&mut self.terminal, dimensions);
+        // Errors can span multiple lines, e.g. when there's an error in more than one slide.
+        for line in message.lines() {
+            let error = vec![WeightedText::from(StyledText::from(line))];
+            operations.extend([
+                RenderOperation::RenderLineBreak,
+                RenderOperation::RenderText { line: WeightedLine::from(error), alignment: alignment.clone() },
+            ]);
+        }
+        let engine = self.engine(dimensions);
         engine.render(operations.iter())?;
         self.terminal.flush()?;
         Ok(())
     }
 }
 
+// The messages shown when the terminal is too small, from the most to the least detailed one, as
+// the terminal may be too small for them too. The last one is empty so it always fits.
+fn too_small_messages(dimensions: &WindowSize, minimum: Option<&MinimumSize>) -> [String; 3] {
+    let size = format!("{}×{}", dimensions.columns, dimensions.rows);
+    let message = match minimum {
+        Some(MinimumSize { columns, rows }) => {
+            format!("Please resize the terminal to at least {columns}×{rows} (currently {size})")
+        }
+        None => format!("The terminal is too small to show this slide (currently {size})"),
+    };
+    [message, size, String::new()]
+}
+
+fn too_small_operations(message: String) -> [RenderOperation; 3] {
+    let text = vec![WeightedText::from(StyledText::new(message, TextStyle::default().bold()))];
+    let alignment = Alignment::Center { minimum_size: 0, minimum_margin: Margin::Fixed(0) };
+    [
+        RenderOperation::ClearScreen,
+        RenderOperation::JumpToVerticalCenter,
+        RenderOperation::RenderText { line: WeightedLine::from(text), alignment },
+    ]
+}
+
 /// A rendering error.
 #[derive(thiserror::Error, Debug)]
 pub enum RenderError {
@@ -88,3 +310,25 @@ pub enum RenderError {
     #[error(transparent)]
     Other(Box<dyn std::error::Error>),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::raster::SlideRasterizer;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::wide(80, "The terminal is too small to show this slide (currently 80×5)")]
+    #[case::narrow(8, "8×5")]
+    #[case::tiny(2, "")]
+    fn too_small(#[case] columns: u16, #[case] expected: &str) {
+        let rasterizer = SlideRasterizer::new(columns, 5);
+        let dimensions = WindowSize::from((columns, 5));
+        // The first message that fits is the one shown.
+        let lines = too_small_messages(&dimensions, None)
+            .into_iter()
+            .find_map(|message| rasterizer.render_text(too_small_operations(message).iter()).ok())
+            .expect("nothing fits");
+        assert_eq!(lines.join("").trim(), expected);
+    }
+}
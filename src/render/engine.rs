@@ -1,6 +1,7 @@
 use super::{
+    canvas::Canvas,
     draw::{RenderError, RenderResult},
-    layout::Layout,
+    layout::{Layout, WindowRect},
     media::{Image, MediaRender},
     properties::CursorPosition,
     terminal::Terminal,
@@ -8,10 +9,13 @@ use super::{
 };
 use crate::{
     markdown::text::WeightedLine,
-    presentation::{AsRenderOperations, MarginProperties, PreformattedLine, RenderOnDemand, RenderOperation},
+    presentation::{
+        AsRenderOperations, CustomRender, ImageFloat, MarginProperties, PreformattedLine, RenderOnDemand,
+        RenderOperation,
+    },
     render::{layout::Positioning, properties::WindowSize},
     style::Colors,
-    theme::Alignment,
+    theme::{Alignment, Margin, SplitBackground, SplitRegion},
 };
 use std::{io, mem};
 
@@ -24,6 +28,8 @@ where
     colors: Colors,
     max_modified_row: u16,
     layout: LayoutState,
+    float: Option<FloatState>,
+    top_row: u16,
 }
 
 impl<'a, W> RenderEngine<'a, W>
@@ -32,9 +38,28 @@ where
 {
     pub(crate) fn new(terminal: &'a mut Terminal<W>, window_dimensions: WindowSize) -> Self {
         let max_modified_row = terminal.cursor_row;
-        let current_rect = WindowRect { dimensions: window_dimensions, start_column: 0 };
-        let window_rects = vec![current_rect.clone()];
-        Self { terminal, window_rects, colors: Default::default(), max_modified_row, layout: Default::default() }
+        let window_rects = vec![WindowRect::new(window_dimensions)];
+        Self {
+            terminal,
+            window_rects,
+            colors: Default::default(),
+            max_modified_row,
+            layout: Default::default(),
+            float: None,
+            top_row: 0,
+        }
+    }
+
+    /// Render starting at the given column and row rather than at the terminal's top left corner.
+    ///
+    /// Rows are absolute everywhere else so the window still spans the same number of rows, just
+    /// further down.
+    pub(crate) fn with_origin(mut self, column: u16, row: u16) -> Self {
+        let rect = &mut self.window_rects[0];
+        rect.dimensions = rect.dimensions.with_dimensions(rect.dimensions.columns, rect.dimensions.rows + row);
+        rect.start_column = column;
+        self.top_row = row;
+        self
     }
 
     pub(crate) fn render<'b>(mut self, operations: impl Iterator<Item = &'b RenderOperation>) -> RenderResult {
@@ -45,8 +70,13 @@ where
     }
 
     fn render_one(&mut self, operation: &RenderOperation) -> RenderResult {
+        // Once we're past the bottom of a floating image, content can use the whole width again.
+        if self.float.as_ref().is_some_and(|float| self.terminal.cursor_row >= float.bottom_row) {
+            self.end_float();
+        }
         match operation {
             RenderOperation::ClearScreen => self.clear_screen(),
+            RenderOperation::RenderSplitBackground(split) => self.render_split_background(split),
             RenderOperation::ApplyMargin(properties) => self.apply_margin(properties),
             RenderOperation::PopMargin => self.pop_margin(),
             RenderOperation::SetColors(colors) => self.set_colors(colors),
@@ -55,9 +85,11 @@ where
             RenderOperation::RenderText { line: texts, alignment } => self.render_text(texts, alignment),
             RenderOperation::RenderLineBreak => self.render_line_break(),
             RenderOperation::RenderImage(image) => self.render_image(image),
+            RenderOperation::RenderFloatingImage { image, float } => self.render_floating_image(image, float),
             RenderOperation::RenderPreformattedLine(operation) => self.render_preformatted_line(operation),
             RenderOperation::RenderDynamic(generator) => self.render_dynamic(generator.as_ref()),
             RenderOperation::RenderOnDemand(generator) => self.render_on_demand(generator.as_ref()),
+            RenderOperation::RenderCustom(operation) => self.render_custom(operation.as_ref()),
             RenderOperation::InitColumnLayout { columns } => self.init_column_layout(columns),
             RenderOperation::EnterColumn { column } => self.enter_column(*column),
             RenderOperation::ExitLayout => self.exit_layout(),
@@ -77,8 +109,31 @@ where
 
     fn clear_screen(&mut self) -> RenderResult {
         self.terminal.clear_screen()?;
-        self.terminal.move_to(0, 0)?;
-        self.max_modified_row = 0;
+        self.terminal.move_to(0, self.top_row)?;
+        self.max_modified_row = self.top_row;
+        self.end_float();
+        Ok(())
+    }
+
+    fn render_split_background(&mut self, split: &SplitBackground) -> RenderResult {
+        let WindowRect { dimensions, start_column } = self.current_rect().clone();
+        let percent = u32::from(split.percent.min(100));
+        // This can't overflow as the result is at most the size it's a percentage of.
+        let scale = |size: u16| (u32::from(size) * percent / 100) as u16;
+        let height = dimensions.rows.saturating_sub(self.top_row);
+        let (rows, columns) = match split.region {
+            SplitRegion::Left => (height, scale(dimensions.columns)),
+            SplitRegion::Top => (scale(height), dimensions.columns),
+        };
+        let start_row = self.terminal.cursor_row;
+        self.terminal.set_colors(Colors { background: Some(split.color), ..self.colors.clone() })?;
+        let line = " ".repeat(columns as usize);
+        for row in self.top_row..self.top_row + rows {
+            self.terminal.move_to(start_column, row)?;
+            self.terminal.print_line(&line)?;
+        }
+        self.apply_colors()?;
+        self.terminal.move_to(start_column, start_row)?;
         Ok(())
     }
 
@@ -92,6 +147,10 @@ where
     }
 
     fn pop_margin(&mut self) -> RenderResult {
+        // The float's rect sits on top of the margin being popped so the float is over too.
+        if self.float.as_ref().is_some_and(|float| float.rect_index + 1 == self.window_rects.len()) {
+            self.end_float();
+        }
         if self.window_rects.len() == 1 {
             return Err(RenderError::PopDefaultScreen);
         }
@@ -110,7 +169,8 @@ where
     }
 
     fn jump_to_vertical_center(&mut self) -> RenderResult {
-        let center_row = self.current_dimensions().rows / 2;
+        let rows = self.current_dimensions().rows;
+        let center_row = self.top_row + rows.saturating_sub(self.top_row) / 2;
         self.terminal.move_to_row(center_row)?;
         Ok(())
     }
@@ -123,7 +183,9 @@ where
 
     fn render_text(&mut self, text: &WeightedLine, alignment: &Alignment) -> RenderResult {
         let layout = self.build_layout(alignment.clone());
-        let text_drawer = TextDrawer::new(&layout, text, self.current_dimensions(), &self.colors)?;
+        let dimensions = self.current_dimensions().clone();
+        let protocol = self.terminal.capabilities().image_protocol(&dimensions);
+        let text_drawer = TextDrawer::new(&layout, text, &dimensions, &self.colors)?.with_image_protocol(protocol);
         text_drawer.draw(self.terminal)
     }
 
@@ -133,15 +195,48 @@ where
     }
 
     fn render_image(&mut self, image: &Image) -> RenderResult {
+        let Some(protocol) = self.terminal.capabilities().image_protocol(self.current_dimensions()) else {
+            let line = WeightedLine::from("[image]".to_string());
+            return self.render_text(&line, &Alignment::Center { minimum_margin: Default::default(), minimum_size: 0 });
+        };
         let position = CursorPosition { row: self.terminal.cursor_row, column: self.current_rect().start_column };
+        let dimensions = self.current_dimensions().clone();
         MediaRender
-            .draw_image(image, position, self.current_dimensions())
+            .draw_image(image, position, &dimensions, protocol, self.colors.background, self.terminal)
             .map_err(|e| RenderError::Other(Box::new(e)))?;
+        // Approximated images change colors as they're drawn.
+        self.apply_colors()?;
         // TODO try to avoid
         self.terminal.sync_cursor_row()?;
         Ok(())
     }
 
+    fn render_floating_image(&mut self, image: &Image, float: &ImageFloat) -> RenderResult {
+        self.end_float();
+        let (image_rect, text_rect) = self.current_rect().split_float(float);
+        let start_row = self.terminal.cursor_row;
+        self.window_rects.push(image_rect);
+        let result = self.render_image(image);
+        self.window_rects.pop();
+        result?;
+
+        // Everything that follows is drawn next to the image until the cursor goes past it.
+        let bottom_row = self.terminal.cursor_row.max(start_row + 1);
+        self.max_modified_row = self.max_modified_row.max(bottom_row);
+        self.terminal.move_to_row(start_row)?;
+        self.float = Some(FloatState { rect_index: self.window_rects.len(), bottom_row });
+        self.window_rects.push(text_rect);
+        Ok(())
+    }
+
+    fn end_float(&mut self) {
+        if let Some(float) = self.float.take() {
+            if float.rect_index < self.window_rects.len() {
+                self.window_rects.remove(float.rect_index);
+            }
+        }
+    }
+
     fn render_preformatted_line(&mut self, operation: &PreformattedLine) -> RenderResult {
         let PreformattedLine { text, unformatted_length, block_length, alignment } = operation;
         let layout = self.build_layout(alignment.clone());
@@ -170,20 +265,39 @@ where
     }
 
     fn render_on_demand(&mut self, generator: &dyn RenderOnDemand) -> RenderResult {
-        let operations = generator.as_render_operations(self.current_dimensions());
+        let mut dimensions = self.current_dimensions().clone();
+        dimensions.rows = dimensions.rows.saturating_sub(self.terminal.cursor_row);
+        let operations = generator.as_render_operations(&dimensions);
         for operation in operations {
             self.render_one(&operation)?;
         }
         Ok(())
     }
 
+    fn render_custom(&mut self, operation: &dyn CustomRender) -> RenderResult {
+        let start_row = self.terminal.cursor_row;
+        let columns = self.current_dimensions().columns;
+        let available_rows = self.current_dimensions().rows.saturating_sub(start_row);
+        let mut canvas = Canvas::new(columns, operation.measure(columns).min(available_rows));
+        operation.paint(&mut canvas);
+
+        let rows = canvas.rows();
+        let alignment = Alignment::Left { margin: Margin::Fixed(0) };
+        for (offset, line) in canvas.into_lines().into_iter().enumerate() {
+            self.terminal.move_to_row(start_row + offset as u16)?;
+            self.render_text(&line, &alignment)?;
+        }
+        self.terminal.move_to_row(start_row + rows)?;
+        Ok(())
+    }
+
     fn init_column_layout(&mut self, columns: &[u8]) -> RenderResult {
         if !matches!(self.layout, LayoutState::Default) {
             self.exit_layout()?;
         }
         let columns = columns.iter().copied().map(u16::from).collect();
-        let current_position = CursorPosition::current()?;
-        self.layout = LayoutState::InitializedColumn { columns, start_row: current_position.row };
+        let start_row = self.terminal.current_row()?;
+        self.layout = LayoutState::InitializedColumn { columns, start_row };
         Ok(())
     }
 
@@ -202,26 +316,8 @@ where
                 (columns, start_row)
             }
         };
-        let total_column_units: u16 = columns.iter().sum();
-        let column_units_before: u16 = columns.iter().take(column_index).sum();
-        let current_rect = self.current_rect();
-        let unit_width = current_rect.dimensions.columns as f64 / total_column_units as f64;
-        let start_column = current_rect.start_column + (unit_width * column_units_before as f64) as u16;
-        let new_column_count = (total_column_units - columns[column_index]) * unit_width as u16;
-        let new_size = current_rect.dimensions.shrink_columns(new_column_count);
-        let mut dimensions = WindowRect { dimensions: new_size, start_column };
-        if columns.len() != 1 {
-            // Shrink every column's right edge except for last
-            if column_index < columns.len() - 1 {
-                dimensions = dimensions.shrink_right(4);
-            }
-            // Shrink every column's left edge except for first
-            if column_index > 0 {
-                dimensions = dimensions.shrink_left(4);
-            }
-        }
-
-        self.window_rects.push(dimensions);
+        let column = self.current_rect().column(&columns, column_index).ok_or(RenderError::InvalidLayoutEnter)?;
+        self.window_rects.push(column);
         self.layout = LayoutState::EnteredColumn { columns, start_row };
         self.terminal.move_to_row(start_row)?;
         Ok(())
@@ -244,6 +340,15 @@ where
     }
 }
 
+/// An image content is flowing around.
+struct FloatState {
+    /// The index of the rect for the content next to the image in the rect stack.
+    rect_index: usize,
+
+    /// The first row below the image.
+    bottom_row: u16,
+}
+
 #[derive(Default)]
 enum LayoutState {
     #[default]
@@ -257,33 +362,3 @@ enum LayoutState {
         start_row: u16,
     },
 }
-
-#[derive(Clone, Debug)]
-struct WindowRect {
-    dimensions: WindowSize,
-    start_column: u16,
-}
-
-impl WindowRect {
-    fn apply_margin(&self, margin: u16) -> Self {
-        let dimensions = self.dimensions.shrink_columns(margin.saturating_mul(2));
-        let start_column = self.start_column + margin;
-        Self { dimensions, start_column }
-    }
-
-    fn shrink_left(&self, size: u16) -> Self {
-        let dimensions = self.dimensions.clone();
-        let start_column = self.start_column.saturating_add(size);
-        Self { dimensions, start_column }
-    }
-
-    fn shrink_right(&self, size: u16) -> Self {
-        let dimensions = self.dimensions.shrink_columns(size);
-        Self { dimensions, start_column: self.start_column }
-    }
-
-    fn shrink_rows(&self, rows: u16) -> Self {
-        let dimensions = self.dimensions.shrink_rows(rows);
-        Self { dimensions, start_column: self.start_column }
-    }
-}
@@ -0,0 +1,196 @@
+//! Reads the orientation images taken with cameras, like phones, are meant to be displayed in.
+//!
+//! Cameras store pictures the way the sensor captured them and use the EXIF orientation tag to say
+//! how they need to be rotated and flipped, which the image decoders don't do on their own.
+
+use image::DynamicImage;
+
+const ORIENTATION_TAG: u16 = 0x0112;
+const SHORT_TYPE: u16 = 3;
+
+/// Rotate and flip an image the way the EXIF metadata in its encoded contents says, if it has any.
+pub(crate) fn apply_orientation(image: DynamicImage, contents: &[u8]) -> DynamicImage {
+    match orientation(contents) {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Find the EXIF orientation in JPEG, PNG, or WebP image contents.
+fn orientation(contents: &[u8]) -> Option<u16> {
+    let exif = if contents.starts_with(&[0xff, 0xd8]) {
+        jpeg_exif(contents)?
+    } else if contents.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_exif(contents)?
+    } else if contents.starts_with(b"RIFF") && contents.get(8..12) == Some(b"WEBP") {
+        webp_exif(contents)?
+    } else {
+        return None;
+    };
+    tiff_orientation(exif)
+}
+
+// The EXIF data is in an APP1 segment somewhere before the image data begins.
+fn jpeg_exif(contents: &[u8]) -> Option<&[u8]> {
+    let mut position = 2;
+    loop {
+        let [0xff, marker] = contents.get(position..position + 2)? else {
+            return None;
+        };
+        // Start of scan, the image data follows.
+        if *marker == 0xda {
+            return None;
+        }
+        let length = read_u16(contents.get(position + 2..position + 4)?, false);
+        let segment = contents.get(position + 4..position + 2 + usize::from(length))?;
+        if *marker == 0xe1 {
+            if let Some(exif) = segment.strip_prefix(b"Exif\0\0") {
+                return Some(exif);
+            }
+        }
+        position += 2 + usize::from(length);
+    }
+}
+
+fn png_exif(contents: &[u8]) -> Option<&[u8]> {
+    let mut position = 8;
+    loop {
+        let length = read_u32(contents.get(position..position + 4)?, false) as usize;
+        let kind = contents.get(position + 4..position + 8)?;
+        let data = contents.get(position + 8..position + 8 + length)?;
+        match kind {
+            b"eXIf" => return Some(data),
+            b"IEND" => return None,
+            _ => position += 12 + length,
+        };
+    }
+}
+
+fn webp_exif(contents: &[u8]) -> Option<&[u8]> {
+    let mut position = 12;
+    loop {
+        let kind = contents.get(position..position + 4)?;
+        let length = read_u32(contents.get(position + 4..position + 8)?, true) as usize;
+        let data = contents.get(position + 8..position + 8 + length)?;
+        if kind == b"EXIF" {
+            // Some writers keep the prefix JPEG uses.
+            return Some(data.strip_prefix(b"Exif\0\0").unwrap_or(data));
+        }
+        // Chunks are padded to an even size.
+        position += 8 + length + length % 2;
+    }
+}
+
+// EXIF data is a TIFF header followed by directories of tags, the first of which has the orientation.
+fn tiff_orientation(exif: &[u8]) -> Option<u16> {
+    let little_endian = match exif.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let offset = read_u32(exif.get(4..8)?, little_endian) as usize;
+    let entries = read_u16(exif.get(offset..offset + 2)?, little_endian);
+    for index in 0..usize::from(entries) {
+        let entry = exif.get(offset + 2 + index * 12..offset + 14 + index * 12)?;
+        let tag = read_u16(&entry[0..2], little_endian);
+        let kind = read_u16(&entry[2..4], little_endian);
+        if tag == ORIENTATION_TAG && kind == SHORT_TYPE {
+            return Some(read_u16(&entry[8..10], little_endian));
+        }
+    }
+    None
+}
+
+fn read_u16(bytes: &[u8], little_endian: bool) -> u16 {
+    let bytes = [bytes[0], bytes[1]];
+    if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) }
+}
+
+fn read_u32(bytes: &[u8], little_endian: bool) -> u32 {
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    // A TIFF header followed by a directory with only an orientation entry.
+    fn exif(orientation: u16, little_endian: bool) -> Vec<u8> {
+        let u16_bytes = |value: u16| if little_endian { value.to_le_bytes() } else { value.to_be_bytes() };
+        let u32_bytes = |value: u32| if little_endian { value.to_le_bytes() } else { value.to_be_bytes() };
+        let mut exif = if little_endian { b"II".to_vec() } else { b"MM".to_vec() };
+        exif.extend(u16_bytes(42));
+        exif.extend(u32_bytes(8));
+        exif.extend(u16_bytes(1));
+        exif.extend(u16_bytes(ORIENTATION_TAG));
+        exif.extend(u16_bytes(SHORT_TYPE));
+        exif.extend(u32_bytes(1));
+        exif.extend(u16_bytes(orientation));
+        exif.extend([0, 0]);
+        exif
+    }
+
+    fn jpeg(exif: &[u8]) -> Vec<u8> {
+        let mut contents = vec![0xff, 0xd8];
+        // Some other segment that comes first.
+        contents.extend([0xff, 0xe0, 0, 4, 0, 0]);
+        contents.extend([0xff, 0xe1]);
+        contents.extend((exif.len() as u16 + 8).to_be_bytes());
+        contents.extend(b"Exif\0\0");
+        contents.extend(exif);
+        contents.extend([0xff, 0xda]);
+        contents
+    }
+
+    fn png(exif: &[u8]) -> Vec<u8> {
+        let mut contents = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, data) in [(&b"IHDR"[..], &[0; 13][..]), (b"eXIf", exif), (b"IEND", &[])] {
+            contents.extend((data.len() as u32).to_be_bytes());
+            contents.extend(kind);
+            contents.extend(data);
+            // The checksum isn't checked.
+            contents.extend([0; 4]);
+        }
+        contents
+    }
+
+    fn webp(exif: &[u8]) -> Vec<u8> {
+        let mut contents = b"RIFF\0\0\0\0WEBP".to_vec();
+        for (kind, data) in [(&b"VP8X"[..], &[0; 9][..]), (b"EXIF", exif)] {
+            contents.extend(kind);
+            contents.extend((data.len() as u32).to_le_bytes());
+            contents.extend(data);
+            if data.len() % 2 == 1 {
+                contents.push(0);
+            }
+        }
+        contents
+    }
+
+    #[rstest]
+    #[case::jpeg_big_endian(jpeg(&exif(6, false)), Some(6))]
+    #[case::jpeg_little_endian(jpeg(&exif(3, true)), Some(3))]
+    #[case::png(png(&exif(8, false)), Some(8))]
+    #[case::webp(webp(&exif(2, true)), Some(2))]
+    #[case::no_exif(jpeg(b"potato"), None)]
+    #[case::truncated(jpeg(&exif(6, false))[..20].to_vec(), None)]
+    #[case::other_format(b"GIF89a".to_vec(), None)]
+    fn find_orientation(#[case] contents: Vec<u8>, #[case] expected: Option<u16>) {
+        assert_eq!(orientation(&contents), expected);
+    }
+
+    #[test]
+    fn rotate() {
+        let image = DynamicImage::new_rgb8(4, 2);
+        let rotated = apply_orientation(image, &jpeg(&exif(6, false)));
+        assert_eq!((rotated.width(), rotated.height()), (2, 4));
+    }
+}
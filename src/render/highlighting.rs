@@ -1,5 +1,6 @@
 use crate::markdown::elements::CodeLanguage;
 use once_cell::sync::Lazy;
+use std::{fs, io, path::Path, sync::Arc};
 use syntect::{
     easy::HighlightLines,
     highlighting::{Style, Theme, ThemeSet},
@@ -16,22 +17,76 @@ static THEMES: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
 /// A code highlighter.
 #[derive(Clone)]
 pub struct CodeHighlighter {
-    theme: &'static Theme,
+    theme: Arc<Theme>,
+    custom_syntaxes: Option<&'static SyntaxSet>,
 }
 
 impl CodeHighlighter {
     /// Construct a new highlighted using the given [syntect] theme name.
-    pub fn new(theme: &str) -> Result<Self, ThemeNotFound> {
-        let theme = THEMES.themes.get(theme).ok_or(ThemeNotFound)?;
-        Ok(Self { theme })
+    ///
+    /// This can also be the path to a `.tmTheme` file.
+    pub fn new(theme: &str) -> Result<Self, InvalidCodeTheme> {
+        Ok(Self { theme: Self::load_theme(theme)?, custom_syntaxes: None })
+    }
+
+    /// Get a copy of this highlighter that uses the given [syntect] theme name or `.tmTheme` file.
+    pub(crate) fn with_theme(&self, theme: &str) -> Result<Self, InvalidCodeTheme> {
+        Ok(Self { theme: Self::load_theme(theme)?, custom_syntaxes: self.custom_syntaxes })
+    }
+
+    /// Check whether a theme name is actually the path to a `.tmTheme` file.
+    pub(crate) fn is_theme_file(theme: &str) -> bool {
+        Path::new(theme).extension().is_some_and(|extension| extension == "tmTheme")
+    }
+
+    fn load_theme(theme: &str) -> Result<Arc<Theme>, InvalidCodeTheme> {
+        if !Self::is_theme_file(theme) {
+            return THEMES.themes.get(theme).cloned().map(Arc::new).ok_or(InvalidCodeTheme::NotFound);
+        }
+        let contents = fs::read(theme)?;
+        let theme = ThemeSet::load_from_reader(&mut io::Cursor::new(&contents))?;
+        Ok(Arc::new(theme))
+    }
+
+    /// Get a copy of this highlighter that also knows the `.sublime-syntax` definitions in the given directory.
+    ///
+    /// The definitions are added on top of the built in ones, so they can include or embed any of them. The loaded
+    /// definitions are kept around until the program exits so this is meant to be done only once.
+    pub fn with_syntaxes_from(self, path: &Path) -> Result<Self, LoadSyntaxesError> {
+        let mut builder = SYNTAX_SET.clone().into_builder();
+        builder.add_from_folder(path, true)?;
+        let syntaxes = Box::leak(Box::new(builder.build()));
+        Ok(Self { custom_syntaxes: Some(syntaxes), ..self })
+    }
+
+    /// Create a highlighter for code in a specific language, written as the given token in its code block.
+    ///
+    /// The token is first looked up by name or file extension among the custom syntax definitions, so these
+    /// take precedence over the built in ones.
+    pub(crate) fn code_highlighter(&self, language: &CodeLanguage, token: &str) -> LanguageHighlighter<'_> {
+        let Some(syntaxes) = self.custom_syntaxes else {
+            return self.language_highlighter(language);
+        };
+        // Custom definitions go after the built in ones, and the last one that matches wins, same as in syntect.
+        let custom = &syntaxes.syntaxes()[SYNTAX_SET.syntaxes().len()..];
+        let syntax = custom.iter().rev().find(|syntax| {
+            syntax.name.eq_ignore_ascii_case(token)
+                || syntax.file_extensions.iter().any(|extension| extension.eq_ignore_ascii_case(token))
+        });
+        match syntax {
+            Some(syntax) if !token.is_empty() => {
+                LanguageHighlighter { highlighter: HighlightLines::new(syntax, &self.theme), syntaxes }
+            }
+            _ => self.language_highlighter(language),
+        }
     }
 
     /// Create a highlighter for a specific language.
-    pub(crate) fn language_highlighter(&self, language: &CodeLanguage) -> LanguageHighlighter {
+    pub(crate) fn language_highlighter(&self, language: &CodeLanguage) -> LanguageHighlighter<'_> {
         let extension = Self::language_extension(language);
         let syntax = SYNTAX_SET.find_syntax_by_extension(extension).unwrap();
-        let highlighter = HighlightLines::new(syntax, self.theme);
-        LanguageHighlighter { highlighter }
+        let highlighter = HighlightLines::new(syntax, &self.theme);
+        LanguageHighlighter { highlighter, syntaxes: &SYNTAX_SET }
     }
 
     fn language_extension(language: &CodeLanguage) -> &'static str {
@@ -49,8 +104,10 @@ impl CodeHighlighter {
             Cpp => "cpp",
             Crontab => "crontab",
             Css => "css",
+            D2 => "txt",
             DLang => "d",
             Docker => "Dockerfile",
+            Dot => "dot",
             Dotenv => "env",
             Elixir => "ex",
             Elm => "elm",
@@ -66,6 +123,8 @@ impl CodeHighlighter {
             Lua => "lua",
             Makefile => "make",
             Markdown => "md",
+            // There's no mermaid syntax so these look like plain text.
+            Mermaid => "txt",
             OCaml => "ml",
             Perl => "pl",
             Php => "php",
@@ -81,8 +140,9 @@ impl CodeHighlighter {
             Svelte => "svelte",
             Terraform => "tf",
             TypeScript => "ts",
+            Typst => "txt",
             // default to plain text so we get the same look&feel
-            Unknown => "txt",
+            Unknown(_) => "txt",
             Vue => "vue",
             Xml => "xml",
             Yaml => "yaml",
@@ -90,19 +150,20 @@ impl CodeHighlighter {
         }
     }
 }
-pub(crate) struct LanguageHighlighter {
-    highlighter: HighlightLines<'static>,
+pub(crate) struct LanguageHighlighter<'a> {
+    highlighter: HighlightLines<'a>,
+    syntaxes: &'static SyntaxSet,
 }
 
-impl LanguageHighlighter {
+impl LanguageHighlighter<'_> {
     pub(crate) fn highlight_line(&mut self, line: &str) -> String {
-        let ranges = self.highlighter.highlight_line(line, &SYNTAX_SET).unwrap();
+        let ranges = self.highlighter.highlight_line(line, self.syntaxes).unwrap();
         as_24_bit_terminal_escaped(&ranges, true)
     }
 
     pub(crate) fn style_line<'a>(&mut self, line: &'a str) -> Vec<StyledTokens<'a>> {
         self.highlighter
-            .highlight_line(line, &SYNTAX_SET)
+            .highlight_line(line, self.syntaxes)
             .unwrap()
             .into_iter()
             .map(|(style, tokens)| StyledTokens { style, tokens })
@@ -121,14 +182,28 @@ impl<'a> StyledTokens<'a> {
     }
 }
 
-/// A theme could not be found.
+/// A code highlighting theme could not be loaded.
 #[derive(Debug, thiserror::Error)]
-#[error("theme not found")]
-pub struct ThemeNotFound;
+pub enum InvalidCodeTheme {
+    #[error("theme not found")]
+    NotFound,
+
+    #[error("reading theme file: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid theme file: {0}")]
+    Invalid(#[from] syntect::LoadingError),
+}
+
+/// The syntax definitions in a directory could not be loaded.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid syntax definitions: {0}")]
+pub struct LoadSyntaxesError(#[from] syntect::LoadingError);
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use rstest::rstest;
     use strum::IntoEnumIterator;
 
     #[test]
@@ -139,4 +214,73 @@ mod test {
             assert!(syntax.is_some(), "extension {extension} for {language:?} not found");
         }
     }
+
+    #[rstest]
+    #[case::unknown_language(CodeLanguage::Unknown("potato".into()), "potato", "mash it")]
+    #[case::known_language(CodeLanguage::Typst, "typst", "mash it")]
+    #[case::included_syntax(CodeLanguage::Unknown("potato".into()), "potato", "fn main")]
+    fn custom_syntaxes(#[case] language: CodeLanguage, #[case] token: &str, #[case] line: &str) {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let syntax = r"%YAML 1.2
+---
+name: Potato
+file_extensions: [potato, typst]
+scope: source.potato
+contexts:
+  main:
+    - match: '\bmash\b'
+      scope: keyword.control.potato
+    - include: scope:source.rust
+";
+        std::fs::write(directory.path().join("potato.sublime-syntax"), syntax).expect("failed to write syntax");
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let plain = highlighter.code_highlighter(&language, token).highlight_line(line);
+
+        let highlighter = highlighter.with_syntaxes_from(directory.path()).expect("failed to load syntaxes");
+        let highlighted = highlighter.code_highlighter(&language, token).highlight_line(line);
+        assert_ne!(plain, highlighted);
+    }
+
+    #[test]
+    fn invalid_custom_syntaxes() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        std::fs::write(directory.path().join("broken.sublime-syntax"), "name: [").expect("failed to write syntax");
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        assert!(highlighter.with_syntaxes_from(directory.path()).is_err());
+    }
+
+    #[test]
+    fn theme_file() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let theme = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>Potato</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#123456</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>
+"#;
+        let path = directory.path().join("potato.tmTheme");
+        std::fs::write(&path, theme).expect("failed to write theme");
+        let highlighter = CodeHighlighter::new(path.to_str().unwrap()).expect("failed to load theme");
+        let line = highlighter.language_highlighter(&CodeLanguage::Rust).highlight_line("potato");
+        assert!(line.contains("\x1b[38;2;18;52;86m"), "unexpected line: {line:?}");
+    }
+
+    #[test]
+    fn missing_theme_file() {
+        let result = CodeHighlighter::new("/does/not/exist.tmTheme");
+        assert!(matches!(result, Err(InvalidCodeTheme::Io(_))));
+    }
 }
@@ -0,0 +1,358 @@
+use crate::{
+    encoding::encode_base64,
+    markdown::text::WeightedLine,
+    presentation::{FloatSide, ImageFloat, PreformattedLine, RenderOperation, Slide},
+    render::{ansi::AnsiStyle, canvas::Canvas, media::Image, properties::WindowSize},
+    style::{Colors, TextStyle},
+    theme::{Alignment, SplitBackground, SplitRegion},
+};
+use std::fmt::Write;
+
+const DOCUMENT_STYLE: &str = "\
+  body { margin: 0; font-family: monospace; }
+  .slide { display: flow-root; min-height: 100vh; padding: 2em; box-sizing: border-box; white-space: pre-wrap; }
+  .slide { break-after: page; }
+  .line { min-height: 1.2em; }
+";
+
+/// Renders slides as HTML.
+///
+/// The output is a best effort approximation of what the slide looks like in the terminal: text
+/// keeps its styling and alignment, code blocks keep their colors, and column layouts become flex
+/// containers. Anything that only makes sense in a terminal, like cursor jumps, is ignored.
+pub(crate) struct HtmlRenderer {
+    dimensions: WindowSize,
+    output: String,
+    line: String,
+    line_alignment: Option<&'static str>,
+    colors: Option<Colors>,
+    split_background: Option<SplitBackground>,
+    layout: Option<ColumnLayout>,
+}
+
+impl HtmlRenderer {
+    /// Construct a new renderer.
+    ///
+    /// The dimensions are used to generate any operations that depend on the screen size.
+    pub(crate) fn new(dimensions: WindowSize) -> Self {
+        Self {
+            dimensions,
+            output: String::new(),
+            line: String::new(),
+            line_alignment: None,
+            colors: None,
+            split_background: None,
+            layout: None,
+        }
+    }
+
+    /// Render the visible parts of a slide.
+    pub(crate) fn render_slide(mut self, slide: &Slide) -> String {
+        for operation in slide.iter_operations() {
+            self.render_one(operation);
+        }
+        self.flush_line();
+        self.exit_layout();
+
+        let mut style = String::new();
+        if let Some(colors) = &self.colors {
+            push_colors_style(&mut style, colors);
+        }
+        if let Some(SplitBackground { region, percent, color }) = &self.split_background {
+            let direction = match region {
+                SplitRegion::Left => "right",
+                SplitRegion::Top => "bottom",
+            };
+            let _ = write!(
+                style,
+                "background-image: linear-gradient(to {direction}, #{color} {percent}%, transparent {percent}%);"
+            );
+        }
+        format!("<div class=\"slide\" style=\"{style}\">{}</div>", self.output)
+    }
+
+    /// Put rendered slides together into a standalone HTML document.
+    pub(crate) fn render_document(title: &str, slides: &[String]) -> String {
+        let mut output = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>");
+        push_escaped(&mut output, title);
+        let _ = write!(output, "</title>\n<style>\n{DOCUMENT_STYLE}</style>\n</head>\n<body>\n");
+        for slide in slides {
+            output.push_str(slide);
+            output.push('\n');
+        }
+        output.push_str("</body>\n</html>\n");
+        output
+    }
+
+    fn render_one(&mut self, operation: &RenderOperation) {
+        match operation {
+            RenderOperation::ClearScreen
+            | RenderOperation::JumpToVerticalCenter
+            | RenderOperation::JumpToBottomRow { .. }
+            | RenderOperation::ApplyMargin(_)
+            | RenderOperation::PopMargin => (),
+            RenderOperation::RenderSplitBackground(split) => self.split_background = Some(split.clone()),
+            RenderOperation::SetColors(colors) => {
+                // The first colors set are the slide's colors.
+                if self.colors.is_none() {
+                    self.colors = Some(colors.clone());
+                }
+            }
+            RenderOperation::RenderText { line, alignment } => self.render_text(line, alignment),
+            RenderOperation::RenderLineBreak => self.flush_line(),
+            RenderOperation::RenderImage(image) => self.render_image(image),
+            RenderOperation::RenderFloatingImage { image, float } => self.render_floating_image(image, float),
+            RenderOperation::RenderPreformattedLine(line) => self.render_preformatted_line(line),
+            RenderOperation::RenderDynamic(generator) => {
+                for operation in generator.as_render_operations(&self.dimensions) {
+                    self.render_one(&operation);
+                }
+            }
+            RenderOperation::RenderOnDemand(generator) => {
+                for operation in generator.as_render_operations(&self.dimensions) {
+                    self.render_one(&operation);
+                }
+            }
+            RenderOperation::RenderCustom(operation) => {
+                self.flush_line();
+                let columns = self.dimensions.columns;
+                let mut canvas = Canvas::new(columns, operation.measure(columns));
+                operation.paint(&mut canvas);
+                for line in canvas.into_lines() {
+                    self.render_text(&line, &Alignment::Left { margin: Default::default() });
+                    self.flush_line();
+                }
+            }
+            RenderOperation::InitColumnLayout { columns } => {
+                self.flush_line();
+                self.exit_layout();
+                self.output.push_str("<div class=\"columns\" style=\"display: flex;\">");
+                self.layout = Some(ColumnLayout { columns: columns.clone(), in_column: false });
+            }
+            RenderOperation::EnterColumn { column } => self.enter_column(*column),
+            RenderOperation::ExitLayout => {
+                self.flush_line();
+                self.exit_layout();
+            }
+        };
+    }
+
+    fn render_text(&mut self, line: &WeightedLine, alignment: &Alignment) {
+        self.line_alignment.get_or_insert(alignment_name(alignment));
+        for text in line.iter_texts() {
+            push_styled_text(&mut self.line, &text.text.text, &text.text.style);
+        }
+    }
+
+    fn render_preformatted_line(&mut self, line: &PreformattedLine) {
+        self.line_alignment.get_or_insert(alignment_name(&line.alignment));
+        push_ansi_text(&mut self.line, &line.text);
+    }
+
+    fn render_image(&mut self, image: &Image) {
+        self.flush_line();
+        // An image that can't be encoded is simply not shown.
+        let Ok(contents) = image.to_png() else {
+            return;
+        };
+        let contents = encode_base64(&contents);
+        let _ = write!(
+            self.output,
+            "<div class=\"line\" style=\"text-align: center;\">\
+            <img style=\"max-width: 100%;\" src=\"data:image/png;base64,{contents}\"></div>"
+        );
+    }
+
+    fn render_floating_image(&mut self, image: &Image, float: &ImageFloat) {
+        self.flush_line();
+        let Ok(contents) = image.to_png() else {
+            return;
+        };
+        let contents = encode_base64(&contents);
+        let (side, margin) = match float.side {
+            FloatSide::Left => ("left", "right"),
+            FloatSide::Right => ("right", "left"),
+        };
+        let _ = write!(
+            self.output,
+            "<img style=\"float: {side}; width: {}%; margin-{margin}: 1em;\" src=\"data:image/png;base64,{contents}\">",
+            float.width
+        );
+    }
+
+    fn enter_column(&mut self, column: usize) {
+        self.flush_line();
+        let Some(layout) = &mut self.layout else {
+            return;
+        };
+        if layout.in_column {
+            self.output.push_str("</div>");
+        }
+        let width = layout.columns.get(column).copied().unwrap_or(1);
+        let _ = write!(self.output, "<div class=\"column\" style=\"flex: {width}; min-width: 0;\">");
+        layout.in_column = true;
+    }
+
+    fn exit_layout(&mut self) {
+        if let Some(layout) = self.layout.take() {
+            if layout.in_column {
+                self.output.push_str("</div>");
+            }
+            self.output.push_str("</div>");
+        }
+    }
+
+    fn flush_line(&mut self) {
+        let alignment = self.line_alignment.take().unwrap_or("left");
+        let contents = if self.line.is_empty() { "&nbsp;".to_string() } else { std::mem::take(&mut self.line) };
+        let _ = write!(self.output, "<div class=\"line\" style=\"text-align: {alignment};\">{contents}</div>");
+    }
+}
+
+struct ColumnLayout {
+    columns: Vec<u8>,
+    in_column: bool,
+}
+
+fn alignment_name(alignment: &Alignment) -> &'static str {
+    match alignment {
+        Alignment::Left { .. } => "left",
+        Alignment::Right { .. } => "right",
+        Alignment::Center { .. } => "center",
+    }
+}
+
+fn push_colors_style(output: &mut String, colors: &Colors) {
+    if let Some(color) = &colors.foreground {
+        let _ = write!(output, "color: #{color};");
+    }
+    if let Some(color) = &colors.background {
+        let _ = write!(output, "background-color: #{color};");
+    }
+}
+
+fn push_styled_text(output: &mut String, text: &str, style: &TextStyle) {
+    let mut css = String::new();
+    if style.is_bold() {
+        css.push_str("font-weight: bold;");
+    }
+    if style.is_italics() || style.is_link() {
+        css.push_str("font-style: italic;");
+    }
+    // Both decorations need to go in a single property or the last one would override the first.
+    let decorations: Vec<_> = [(style.is_strikethrough(), "line-through"), (style.is_link(), "underline")]
+        .into_iter()
+        .filter_map(|(enabled, decoration)| enabled.then_some(decoration))
+        .collect();
+    if !decorations.is_empty() {
+        let _ = write!(css, "text-decoration: {};", decorations.join(" "));
+    }
+    push_colors_style(&mut css, &style.colors);
+    push_span(output, text, &css);
+}
+
+fn push_span(output: &mut String, text: &str, css: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if css.is_empty() {
+        push_escaped(output, text);
+    } else {
+        let _ = write!(output, "<span style=\"{css}\">");
+        push_escaped(output, text);
+        output.push_str("</span>");
+    }
+}
+
+fn push_escaped(output: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+}
+
+/// Converts text that contains ANSI escape codes, like the ones generated when highlighting code,
+/// into HTML.
+fn push_ansi_text(output: &mut String, text: &str) {
+    for (text, style) in AnsiStyle::default().parse(text) {
+        push_styled_text(output, &text, &style);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        markdown::{elements::StyledText, text::WeightedText},
+        presentation::SlideChunk,
+    };
+    use rstest::rstest;
+
+    fn render(operations: Vec<RenderOperation>) -> String {
+        let slide = Slide::new(vec![SlideChunk::new(operations, Vec::new())], Vec::new());
+        let dimensions = WindowSize { rows: 10, columns: 10, width: 0, height: 0, has_pixels: false };
+        HtmlRenderer::new(dimensions).render_slide(&slide)
+    }
+
+    #[test]
+    fn text() {
+        let text = WeightedText::from(StyledText::new("<hi>", TextStyle::default().bold()));
+        let alignment = Alignment::Center { minimum_margin: Default::default(), minimum_size: 0 };
+        let operations = vec![
+            RenderOperation::RenderText { line: vec![text].into(), alignment },
+            RenderOperation::RenderLineBreak,
+        ];
+        let expected = "<div class=\"slide\" style=\"\"><div class=\"line\" style=\"text-align: center;\">\
+            <span style=\"font-weight: bold;\">&lt;hi&gt;</span></div><div class=\"line\" style=\"text-align: left;\">\
+            &nbsp;</div></div>";
+        assert_eq!(render(operations), expected);
+    }
+
+    #[test]
+    fn text_decorations() {
+        let mut output = String::new();
+        push_styled_text(&mut output, "a", &TextStyle::default().strikethrough().link());
+        assert_eq!(output, "<span style=\"font-style: italic;text-decoration: line-through underline;\">a</span>");
+    }
+
+    #[test]
+    fn columns() {
+        let operations = vec![
+            RenderOperation::InitColumnLayout { columns: vec![1, 2] },
+            RenderOperation::EnterColumn { column: 0 },
+            RenderOperation::EnterColumn { column: 1 },
+            RenderOperation::ExitLayout,
+        ];
+        let rendered = render(operations);
+        assert!(rendered.contains("<div class=\"column\" style=\"flex: 1; min-width: 0;\">"));
+        assert!(rendered.contains("<div class=\"column\" style=\"flex: 2; min-width: 0;\">"));
+        assert_eq!(rendered.matches("<div").count(), rendered.matches("</div>").count());
+    }
+
+    #[test]
+    fn document() {
+        let slides = ["<div class=\"slide\">a</div>".to_string(), "<div class=\"slide\">b</div>".to_string()];
+        let document = HtmlRenderer::render_document("<deck>", &slides);
+        assert!(document.starts_with("<!DOCTYPE html>"));
+        assert!(document.contains("<title>&lt;deck&gt;</title>"));
+        assert!(document.contains("<body>\n<div class=\"slide\">a</div>\n<div class=\"slide\">b</div>\n</body>"));
+    }
+
+    #[rstest]
+    #[case::plain("hello", "hello")]
+    #[case::colored("\x1b[38;2;255;0;0mred\x1b[0m", "<span style=\"color: #ff0000;\">red</span>")]
+    #[case::basic_color("\x1b[31mred", "<span style=\"color: #cd0000;\">red</span>")]
+    #[case::bold_reset("\x1b[1ma\x1b[22mb", "<span style=\"font-weight: bold;\">a</span>b")]
+    #[case::unknown_sequence("\x1b[2Ka", "a")]
+    fn ansi_text(#[case] input: &str, #[case] expected: &str) {
+        let mut output = String::new();
+        push_ansi_text(&mut output, input);
+        assert_eq!(output, expected);
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{render::properties::WindowSize, theme::Alignment};
+use crate::{
+    presentation::{FloatSide, ImageFloat},
+    render::properties::WindowSize,
+    theme::Alignment,
+};
 
 #[derive(Debug)]
 pub(crate) struct Layout {
@@ -68,6 +72,86 @@ pub(crate) struct Positioning {
     pub(crate) start_column: u16,
 }
 
+/// The number of columns left empty on each side of the boundary between two layout columns.
+const COLUMN_GUTTER: u16 = 4;
+
+/// A rectangle in the screen that spans every row in its dimensions starting at a specific column.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct WindowRect {
+    pub(crate) dimensions: WindowSize,
+    pub(crate) start_column: u16,
+}
+
+impl WindowRect {
+    pub(crate) fn new(dimensions: WindowSize) -> Self {
+        Self { dimensions, start_column: 0 }
+    }
+
+    /// Shrink this rect by the given margin on both the left and right sides.
+    pub(crate) fn apply_margin(&self, margin: u16) -> Self {
+        let margin = margin.min(self.dimensions.columns / 2);
+        self.shrink_left(margin).shrink_right(margin)
+    }
+
+    pub(crate) fn shrink_left(&self, size: u16) -> Self {
+        let size = size.min(self.dimensions.columns);
+        let dimensions = self.dimensions.shrink_columns(size);
+        Self { dimensions, start_column: self.start_column + size }
+    }
+
+    pub(crate) fn shrink_right(&self, size: u16) -> Self {
+        let dimensions = self.dimensions.shrink_columns(size);
+        Self { dimensions, start_column: self.start_column }
+    }
+
+    pub(crate) fn shrink_rows(&self, rows: u16) -> Self {
+        let dimensions = self.dimensions.shrink_rows(rows);
+        Self { dimensions, start_column: self.start_column }
+    }
+
+    /// Split this rect into columns, where each column takes as many units of its width as the
+    /// value at its position in `units`, and get the one at the given index.
+    ///
+    /// Columns are separated by a gutter so their contents don't touch each other. Returns `None` if
+    /// the index is out of bounds or there are no units to split into.
+    pub(crate) fn column(&self, units: &[u16], index: usize) -> Option<Self> {
+        let total_units: u32 = units.iter().copied().map(u32::from).sum();
+        if index >= units.len() || total_units == 0 {
+            return None;
+        }
+        // Boundaries are computed from the accumulated units so columns are contiguous regardless
+        // of how the width is rounded.
+        let boundary = |index: usize| {
+            let units_before: u32 = units[..index].iter().copied().map(u32::from).sum();
+            (u32::from(self.dimensions.columns) * units_before / total_units) as u16
+        };
+        let start = boundary(index);
+        let end = boundary(index + 1);
+        let rect = self.shrink_left(start).shrink_right(self.dimensions.columns - end);
+        let rect = match index + 1 < units.len() {
+            true => rect.shrink_right(COLUMN_GUTTER),
+            false => rect,
+        };
+        let rect = match index > 0 {
+            true => rect.shrink_left(COLUMN_GUTTER),
+            false => rect,
+        };
+        Some(rect)
+    }
+
+    /// Split this rect into the one a floating image is drawn in and the one left next to it for
+    /// everything else.
+    pub(crate) fn split_float(&self, float: &ImageFloat) -> (Self, Self) {
+        let width = u16::from(float.width);
+        let (units, image_index) = match float.side {
+            FloatSide::Left => ([width, 100 - width], 0),
+            FloatSide::Right => ([100 - width, width], 1),
+        };
+        let column = |index| self.column(&units, index).expect("invalid float column");
+        (column(image_index), column(1 - image_index))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -165,4 +249,93 @@ mod test {
         let positioning = Layout::new(alignment).compute(&dimensions, length);
         assert_eq!(positioning, expected);
     }
+
+    fn rect(start_column: u16, columns: u16) -> WindowRect {
+        let dimensions = WindowSize { rows: 10, columns, width: columns * 10, height: 100, has_pixels: true };
+        WindowRect { dimensions, start_column }
+    }
+
+    fn end_column(rect: &WindowRect) -> u16 {
+        rect.start_column + rect.dimensions.columns
+    }
+
+    #[rstest]
+    #[case::single(&[1], 0, rect(5, 100))]
+    #[case::halves_left(&[1, 1], 0, rect(5, 46))]
+    #[case::halves_right(&[1, 1], 1, rect(59, 46))]
+    #[case::thirds_middle(&[1, 1, 1], 1, rect(42, 25))]
+    #[case::uneven_right(&[3, 1], 1, rect(84, 21))]
+    fn column(#[case] units: &[u16], #[case] index: usize, #[case] expected: WindowRect) {
+        let column = rect(5, 100).column(units, index).expect("no column");
+        assert_eq!(column.start_column, expected.start_column);
+        assert_eq!(column.dimensions.columns, expected.dimensions.columns);
+    }
+
+    #[rstest]
+    #[case::right(FloatSide::Right, (69, 36), (5, 56))]
+    #[case::left(FloatSide::Left, (5, 36), (49, 56))]
+    fn split_float(#[case] side: FloatSide, #[case] image: (u16, u16), #[case] text: (u16, u16)) {
+        let span = |rect: WindowRect| (rect.start_column, rect.dimensions.columns);
+        let (image_rect, text_rect) = rect(5, 100).split_float(&ImageFloat { side, width: 40 });
+        assert_eq!(span(image_rect), image);
+        assert_eq!(span(text_rect), text);
+    }
+
+    #[rstest]
+    #[case::out_of_bounds(&[1, 1], 2)]
+    #[case::no_units(&[], 0)]
+    #[case::zero_units(&[0, 0], 0)]
+    fn invalid_column(#[case] units: &[u16], #[case] index: usize) {
+        assert_eq!(rect(0, 100).column(units, index), None);
+    }
+
+    #[rstest]
+    #[case::single(&[1])]
+    #[case::halves(&[1, 1])]
+    #[case::uneven(&[2, 1, 3])]
+    #[case::many(&[1, 1, 1, 1, 1, 1, 1])]
+    #[case::large(&[255, 1])]
+    fn columns_fit_in_parent(#[case] units: &[u16]) {
+        for start_column in [0, 7] {
+            for columns in 0..=300 {
+                let parent = rect(start_column, columns);
+                let mut previous_end = parent.start_column;
+                for index in 0..units.len() {
+                    let column = parent.column(units, index).expect("no column");
+                    assert!(column.start_column >= previous_end, "{columns} columns, index {index}: overlaps");
+                    assert!(end_column(&column) <= end_column(&parent), "{columns} columns, index {index}: overflows");
+                    previous_end = end_column(&column);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn margins_fit_in_parent() {
+        for columns in 0..=200 {
+            for margin in 0..=columns + 10 {
+                let parent = rect(3, columns);
+                let inner = parent.apply_margin(margin);
+                assert!(inner.start_column >= parent.start_column);
+                assert!(end_column(&inner) <= end_column(&parent), "{columns} columns, margin {margin}");
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::left(Alignment::Left { margin: Margin::Fixed(5) })]
+    #[case::left_percent(Alignment::Left { margin: Margin::Percent(20) })]
+    #[case::right(Alignment::Right { margin: Margin::Fixed(5) })]
+    #[case::center(Alignment::Center { minimum_margin: Margin::Fixed(5), minimum_size: 0 })]
+    #[case::center_minimum_size(Alignment::Center { minimum_margin: Margin::Fixed(5), minimum_size: 40 })]
+    fn positioning_fits_in_window(#[case] alignment: Alignment) {
+        let layout = Layout::new(alignment);
+        for columns in 1..=150 {
+            let dimensions = WindowSize::from((columns, 10));
+            for length in (0..=200).step_by(7) {
+                let Positioning { max_line_length, start_column } = layout.compute(&dimensions, length);
+                assert!(start_column + max_line_length <= columns, "{columns} columns, length {length}");
+            }
+        }
+    }
 }
@@ -1,28 +1,285 @@
-use crate::render::properties::WindowSize;
-use image::{DynamicImage, ImageError};
-use std::{fmt::Debug, io, rc::Rc};
+use crate::{
+    markdown::elements::{ImageAttributes, ImageFit},
+    render::properties::WindowSize,
+    style::Color,
+};
+use crossterm::style::{self, Stylize};
+use image::{
+    codecs::gif::GifDecoder, imageops::FilterType, AnimationDecoder, DynamicImage, Frame, ImageError, ImageFormat, Rgba,
+};
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Debug,
+    io,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 use viuer::ViuError;
 
-use super::properties::CursorPosition;
+use super::{
+    capabilities::GraphicsProtocol,
+    exif,
+    properties::CursorPosition,
+    svg::{Rasterizations, VectorImage},
+    terminal::Terminal,
+};
 
 /// An image.
 ///
 /// This stores the image in an [std::rc::Rc] so it's cheap to clone.
 #[derive(Clone, PartialEq)]
-pub(crate) struct Image(Rc<DynamicImage>);
+pub(crate) struct Image {
+    contents: Rc<ImageContents>,
+    attributes: ImageAttributes,
+    scaled: Rc<ScaledImages>,
+}
+
+#[derive(PartialEq)]
+enum ImageContents {
+    Raster(Rc<DynamicImage>),
+    Vector(Box<VectorImage>),
+    Animated(AnimatedImage),
+}
 
 impl Debug for Image {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Image<{}x{}>", self.0.width(), self.0.height())
+        let image = self.natural();
+        write!(f, "Image<{}x{}>", image.width(), image.height())
     }
 }
 
 impl Image {
     /// Construct a new image from a byte sequence.
+    ///
+    /// SVG documents are rasterized, GIFs have every one of their frames decoded, and everything
+    /// else is decoded based on its contents and rotated as its EXIF metadata says, if it has any.
     pub(crate) fn new(contents: &[u8]) -> Result<Self, InvalidImage> {
-        let contents = image::load_from_memory(contents)?;
-        let contents = Rc::new(contents);
-        Ok(Self(contents))
+        Self::with_rasterizations(contents, &Default::default())
+    }
+
+    /// Construct a new image like [Image::new] does, keeping its rasterizations in the given cache if it's an SVG.
+    pub(crate) fn with_rasterizations(contents: &[u8], rasterizations: &Rasterizations) -> Result<Self, InvalidImage> {
+        if VectorImage::is_svg(contents) {
+            let image = VectorImage::new(contents.to_vec(), rasterizations)?;
+            return Ok(Self::with_contents(ImageContents::Vector(Box::new(image))));
+        }
+        let format = image::guess_format(contents).ok();
+        let decode_error = |error| match format {
+            Some(format) => InvalidImage::DecodeFormat(format!("{format:?}").to_lowercase(), error),
+            None => InvalidImage::Decode(error),
+        };
+        match format {
+            // Decoding these needs libdav1d, which is only used when built with the avif feature.
+            #[cfg(not(feature = "avif"))]
+            Some(ImageFormat::Avif) => return Err(InvalidImage::UnsupportedFormat("avif")),
+            Some(ImageFormat::Gif) => {
+                let frames = GifDecoder::new(contents).and_then(|decoder| decoder.into_frames().collect_frames());
+                let frames = frames.map_err(decode_error)?;
+                if frames.len() > 1 {
+                    return Ok(Self::with_contents(ImageContents::Animated(AnimatedImage::new(frames))));
+                }
+            }
+            _ => (),
+        };
+        let image = image::load_from_memory(contents).map_err(decode_error)?;
+        Ok(Self::from(exif::apply_orientation(image, contents)))
+    }
+
+    fn with_contents(contents: ImageContents) -> Self {
+        Self { contents: Rc::new(contents), attributes: Default::default(), scaled: Default::default() }
+    }
+
+    /// Get a copy of this image that always displays its first frame if it's animated.
+    pub(crate) fn without_animation(&self) -> Self {
+        match self.contents.as_ref() {
+            ImageContents::Animated(image) => Self {
+                contents: Rc::new(ImageContents::Raster(image.frames[0].image.clone())),
+                attributes: self.attributes,
+                scaled: Default::default(),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Get the attributes that say how this image is scaled when drawn.
+    pub(crate) fn attributes(&self) -> ImageAttributes {
+        self.attributes
+    }
+
+    /// Get a copy of this image that's scaled as the given attributes say when drawn.
+    pub(crate) fn with_attributes(&self, attributes: ImageAttributes) -> Self {
+        Self { contents: self.contents.clone(), attributes, scaled: self.scaled.clone() }
+    }
+
+    /// Get the width and height of this image in pixels.
+    pub(crate) fn size(&self) -> (u32, u32) {
+        let image = self.natural();
+        (image.width(), image.height())
+    }
+
+    /// Check whether this is an animated image.
+    pub(crate) fn is_animated(&self) -> bool {
+        matches!(self.contents.as_ref(), ImageContents::Animated(_))
+    }
+
+    /// Move this image to its next frame if the current one has been displayed for long enough.
+    ///
+    /// Returns whether the frame changed, which means the image needs to be drawn again.
+    pub(crate) fn advance(&self, now: Instant) -> bool {
+        match self.contents.as_ref() {
+            ImageContents::Animated(image) => image.advance(now),
+            _ => false,
+        }
+    }
+
+    /// Encode this image as PNG.
+    pub(crate) fn to_png(&self) -> Result<Vec<u8>, ImageError> {
+        let mut output = io::Cursor::new(Vec::new());
+        self.natural().write_to(&mut output, image::ImageOutputFormat::Png)?;
+        Ok(output.into_inner())
+    }
+
+    fn natural(&self) -> &DynamicImage {
+        match self.contents.as_ref() {
+            ImageContents::Raster(image) => image,
+            ImageContents::Vector(image) => image.natural(),
+            ImageContents::Animated(image) => &image.frames[image.current.get().0].image,
+        }
+    }
+
+    // Raster images are always scaled when drawn but vector ones can be rasterized at the right size instead.
+    fn at_width(&self, width: u32) -> Result<Rc<DynamicImage>, InvalidImage> {
+        match self.contents.as_ref() {
+            ImageContents::Raster(image) => Ok(image.clone()),
+            ImageContents::Vector(image) => image.rasterize(width.max(1)),
+            ImageContents::Animated(image) => Ok(image.frames[image.current.get().0].image.clone()),
+        }
+    }
+
+    // Scales an image like [MediaRender::scale] does, reusing the result if it was scaled the same way before.
+    fn scaled(
+        &self,
+        image: Rc<DynamicImage>,
+        size: (f64, f64),
+        visible: (f64, f64),
+        filter: FilterType,
+    ) -> Rc<DynamicImage> {
+        let (size, visible) = MediaRender::pixels(size, visible);
+        let key = ScaleKey { source: image, size, visible, filter };
+        if let Some(image) = self.scaled.get(&key) {
+            return image;
+        }
+        let image = MediaRender::scale(key.source.clone(), size, visible, filter);
+        self.scaled.insert(key, image.clone());
+        image
+    }
+}
+
+/// The number of scaled copies kept around for every image.
+const MAX_SCALED_IMAGES: usize = 4;
+
+/// The copies of an image scaled to the sizes it was drawn at, from least to most recently used.
+///
+/// Images are drawn again every time the slide they're in is, almost always at the same size, and
+/// scaling them is by far the slowest part of it.
+#[derive(Default)]
+struct ScaledImages(RefCell<Vec<(ScaleKey, Rc<DynamicImage>)>>);
+
+impl ScaledImages {
+    fn get(&self, key: &ScaleKey) -> Option<Rc<DynamicImage>> {
+        let mut images = self.0.borrow_mut();
+        let index = images.iter().position(|(other, _)| other == key)?;
+        let entry = images.remove(index);
+        let image = entry.1.clone();
+        images.push(entry);
+        Some(image)
+    }
+
+    fn insert(&self, key: ScaleKey, image: Rc<DynamicImage>) {
+        let mut images = self.0.borrow_mut();
+        if images.len() == MAX_SCALED_IMAGES {
+            images.remove(0);
+        }
+        images.push((key, image));
+    }
+}
+
+// Copies are only a cache so they don't make images any different.
+impl PartialEq for ScaledImages {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+// The image that was scaled, which is different for every rasterization and animation frame, and how.
+struct ScaleKey {
+    source: Rc<DynamicImage>,
+    size: (u32, u32),
+    visible: (u32, u32),
+    filter: FilterType,
+}
+
+impl PartialEq for ScaleKey {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.source, &other.source)
+            && (self.size, self.visible, self.filter) == (other.size, other.visible, other.filter)
+    }
+}
+
+impl From<DynamicImage> for Image {
+    fn from(image: DynamicImage) -> Self {
+        Self::with_contents(ImageContents::Raster(Rc::new(image)))
+    }
+}
+
+#[derive(PartialEq)]
+struct AnimationFrame {
+    image: Rc<DynamicImage>,
+    delay: Duration,
+}
+
+struct AnimatedImage {
+    frames: Vec<AnimationFrame>,
+    // The frame being displayed and when it started being displayed, which is only known once it's drawn.
+    current: Cell<(usize, Option<Instant>)>,
+}
+
+impl AnimatedImage {
+    // Browsers display frames without a delay, or with a really short one, for 100ms so do the same.
+    const MINIMUM_DELAY: Duration = Duration::from_millis(20);
+    const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+    fn new(frames: Vec<Frame>) -> Self {
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let (numerator, denominator) = frame.delay().numer_denom_ms();
+                let delay = Duration::from_millis(u64::from(numerator) / u64::from(denominator.max(1)));
+                let delay = if delay < Self::MINIMUM_DELAY { Self::DEFAULT_DELAY } else { delay };
+                AnimationFrame { image: Rc::new(DynamicImage::ImageRgba8(frame.into_buffer())), delay }
+            })
+            .collect();
+        Self { frames, current: Cell::new((0, None)) }
+    }
+
+    fn advance(&self, now: Instant) -> bool {
+        match self.current.get() {
+            (index, None) => {
+                self.current.set((index, Some(now)));
+                false
+            }
+            (index, Some(shown_at)) if now.saturating_duration_since(shown_at) >= self.frames[index].delay => {
+                self.current.set(((index + 1) % self.frames.len(), Some(now)));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for AnimatedImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.frames == other.frames
     }
 }
 
@@ -37,56 +294,177 @@ impl MediaRender {
     /// and that fits in the screen at the current cursor positioned, it will be drawn as-is.
     ///
     /// In case the image does not fit, it will be resized to fit the screen, preserving the aspect
-    /// ratio.
-    pub(crate) fn draw_image(
+    /// ratio. Images that have a fit set are instead scaled to the available space as it says.
+    ///
+    /// Images are resized to exactly the number of pixels the cells they take up have so the
+    /// terminal doesn't need to scale them again.
+    /// Transparent pixels are drawn using the given background color when the image is
+    /// approximated using characters.
+    pub(crate) fn draw_image<W: io::Write>(
         &self,
         image: &Image,
         position: CursorPosition,
         dimensions: &WindowSize,
+        protocol: GraphicsProtocol,
+        background: Option<Color>,
+        terminal: &mut Terminal<W>,
     ) -> Result<(), RenderImageError> {
+        if protocol == GraphicsProtocol::HalfBlocks {
+            return Self::draw_half_blocks(image, position, dimensions, background, terminal);
+        }
         if !dimensions.has_pixels {
             return Err(RenderImageError::NoWindowSize);
         }
-        let image = &image.0;
+        let source = image;
+        let natural = source.natural();
+        // viuer draws straight into stdout so make sure everything before the image is written.
+        terminal.flush()?;
 
-        // Compute the image's width in columns by translating pixels -> columns.
+        // Work out how large the image is in pixels and how many of them we have to draw it in.
         let column_in_pixels = dimensions.pixels_per_column();
-        let column_margin = (dimensions.columns as f64 * 0.95) as u32;
-        let mut width_in_columns = (image.width() as f64 / column_in_pixels) as u32;
-
-        // Do the same for its height.
         let row_in_pixels = dimensions.pixels_per_row();
-        let height_in_rows = (image.height() as f64 / row_in_pixels) as u32;
-
-        // If the image doesn't fit vertically, shrink it.
-        let available_height = dimensions.rows.saturating_sub(position.row) as u32;
-        if height_in_rows > available_height {
-            // Because we only use the width to draw, here we scale the width based on how much we
-            // need to shrink the height.
-            let shrink_ratio = available_height as f64 / height_in_rows as f64;
-            width_in_columns = (width_in_columns as f64 * shrink_ratio) as u32;
-        }
-        // Don't go too far wide.
-        let width_in_columns = width_in_columns.min(column_margin);
+        let available_columns = Self::available_columns(source, dimensions);
+        let available_rows = dimensions.rows.saturating_sub(position.row) as f64;
+        let available = (available_columns * column_in_pixels, available_rows * row_in_pixels);
+        let size = Self::fit((natural.width() as f64, natural.height() as f64), available, source.attributes.fit);
+        let visible = (size.0.min(available.0), size.1.min(available.1));
+        let width_in_columns = ((visible.0 / column_in_pixels) as u32).max(1);
+
+        // Snap the width to the cells the image is drawn in.
+        let ratio = width_in_columns as f64 * column_in_pixels / visible.0;
+        let size = (size.0 * ratio, size.1 * ratio);
+        let visible = (visible.0 * ratio, visible.1 * ratio);
 
         // Draw it in the middle
         let start_column = dimensions.columns / 2 - (width_in_columns / 2) as u16;
         let start_column = start_column + position.column;
+        // This is derived from the size of a column so vector images are rasterized again after resizes.
+        let image = source.at_width(size.0 as u32)?;
+        let image = source.scaled(image, size, visible, FilterType::Lanczos3);
         let config = viuer::Config {
             width: Some(width_in_columns),
             x: start_column,
             y: position.row as i16,
             ..Default::default()
         };
-        viuer::print(image, &config)?;
+        match protocol {
+            GraphicsProtocol::Viuer => {
+                // viuer would otherwise pick sixel over the protocols detected for this terminal.
+                #[cfg(feature = "sixel")]
+                let config = viuer::Config { use_sixel: false, ..config };
+                viuer::print(&image, &config)?;
+            }
+            #[cfg(feature = "sixel")]
+            GraphicsProtocol::Sixel => {
+                let config = viuer::Config { use_kitty: false, use_iterm: false, ..config };
+                viuer::print(&image, &config)?;
+            }
+            GraphicsProtocol::HalfBlocks => unreachable!("half blocks drawn above"),
+        }
         Ok(())
     }
+
+    // Every character is split into a top and a bottom pixel using the upper half block character,
+    // whose foreground is the top pixel and whose background is the bottom one.
+    fn draw_half_blocks<W: io::Write>(
+        image: &Image,
+        position: CursorPosition,
+        dimensions: &WindowSize,
+        background: Option<Color>,
+        terminal: &mut Terminal<W>,
+    ) -> Result<(), RenderImageError> {
+        // Without knowing the size of a column assume it's a common one.
+        let column_in_pixels =
+            if dimensions.has_pixels { dimensions.pixels_per_column() } else { DEFAULT_PIXELS_PER_COLUMN };
+        let source = image;
+        let natural = source.natural();
+        // A column is about as wide as half a row is tall, so both halves are square.
+        let natural = (natural.width() as f64 / column_in_pixels, natural.height() as f64 / column_in_pixels);
+        let available_height = dimensions.rows.saturating_sub(position.row) as f64 * 2.0;
+        let available = (Self::available_columns(source, dimensions), available_height);
+        let size = Self::fit(natural, available, source.attributes.fit);
+        let visible = ((size.0.min(available.0) as u32).max(1), (size.1.min(available.1) as u32).max(1));
+        let (width, height) = visible;
+        let image = source.at_width((size.0 * column_in_pixels) as u32)?;
+        let image = source.scaled(image, size, (width as f64, height as f64), FilterType::Triangle).to_rgba8();
+
+        let background: style::Color = background.map(Into::into).unwrap_or(style::Color::Reset);
+        let color = |pixel: &Rgba<u8>| match pixel.0 {
+            [_, _, _, alpha] if alpha < 128 => background,
+            [r, g, b, _] => style::Color::Rgb { r, g, b },
+        };
+        let start_column = position.column + dimensions.columns / 2 - (width / 2) as u16;
+        for (index, row) in (0..height).step_by(2).enumerate() {
+            terminal.move_to(start_column, position.row + index as u16)?;
+            for column in 0..width {
+                let top = color(image.get_pixel(column, row));
+                let bottom = if row + 1 < height { color(image.get_pixel(column, row + 1)) } else { background };
+                terminal.print_styled_line("▀".to_string().with(top).on(bottom))?;
+            }
+        }
+        terminal.move_to_next_line(1)?;
+        Ok(())
+    }
+
+    fn available_columns(image: &Image, dimensions: &WindowSize) -> f64 {
+        match image.attributes.columns {
+            Some(columns) => columns.min(dimensions.columns) as f64,
+            // Don't go too far wide.
+            None => (dimensions.columns as f64 * 0.95).floor(),
+        }
+    }
+
+    // The size an image that's `natural` big is scaled to when drawn into `available`, in the same units.
+    fn fit(natural: (f64, f64), available: (f64, f64), fit: Option<ImageFit>) -> (f64, f64) {
+        let (width, height) = natural;
+        let scales = (available.0 / width, available.1 / height);
+        let scale = match fit {
+            None => scales.0.min(scales.1).min(1.0),
+            Some(ImageFit::Contain) => scales.0.min(scales.1),
+            Some(ImageFit::Cover) => scales.0.max(scales.1),
+            Some(ImageFit::Fill) => return available,
+        };
+        (width * scale, height * scale)
+    }
+
+    // The size an image is scaled to and the part of it that's visible, in whole pixels.
+    fn pixels(size: (f64, f64), visible: (f64, f64)) -> ((u32, u32), (u32, u32)) {
+        let size = ((size.0 as u32).max(1), (size.1 as u32).max(1));
+        let visible = ((visible.0 as u32).clamp(1, size.0), (visible.1 as u32).clamp(1, size.1));
+        (size, visible)
+    }
+
+    // Scales an image to the given size and crops whatever of it isn't visible around its center.
+    fn scale(image: Rc<DynamicImage>, size: (u32, u32), visible: (u32, u32), filter: FilterType) -> Rc<DynamicImage> {
+        let image = match (image.width(), image.height()) == size {
+            true => image,
+            false => Rc::new(image.resize_exact(size.0, size.1, filter)),
+        };
+        match visible == size {
+            true => image,
+            false => Rc::new(image.crop_imm((size.0 - visible.0) / 2, (size.1 - visible.1) / 2, visible.0, visible.1)),
+        }
+    }
 }
 
+/// The width of a column in pixels used when the terminal doesn't tell us.
+const DEFAULT_PIXELS_PER_COLUMN: f64 = 8.0;
+
 /// An invalid image.
 #[derive(thiserror::Error, Debug)]
-#[error("invalid image: {0}")]
-pub struct InvalidImage(#[from] ImageError);
+pub enum InvalidImage {
+    #[error("invalid image: {0}")]
+    Decode(#[from] ImageError),
+
+    #[error("invalid {0} image: {1}")]
+    DecodeFormat(String, ImageError),
+
+    #[error("{0} images are not supported")]
+    UnsupportedFormat(&'static str),
+
+    #[error("invalid svg image: {0}")]
+    Rasterize(String),
+}
 
 /// An image render error.
 #[derive(thiserror::Error, Debug)]
@@ -104,3 +482,127 @@ pub enum RenderImageError {
     #[error("no window size support in terminal")]
     NoWindowSize,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::terminal::TerminalMode;
+    use image::RgbaImage;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::natural_size(10, 40, 5, (2, 3))]
+    #[case::shrunk_to_fit(10, 4, 2, (1, 2))]
+    fn half_blocks(
+        #[case] columns: u16,
+        #[case] rows: u16,
+        #[case] start_row: u16,
+        #[case] expected_size: (usize, usize),
+    ) {
+        // 16x40 pixels is 2 columns by 5 half rows at 8 pixels per column.
+        let image = Image::from(DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 40, Rgba([255, 0, 0, 255]))));
+        let dimensions = WindowSize { rows, columns, width: 0, height: 0, has_pixels: false };
+        let position = CursorPosition { row: start_row, column: 0 };
+        let mut output = Vec::new();
+        let mut terminal = Terminal::new(&mut output, TerminalMode::Headless).unwrap();
+        MediaRender
+            .draw_image(&image, position, &dimensions, GraphicsProtocol::HalfBlocks, None, &mut terminal)
+            .expect("draw failed");
+        let (width, height) = expected_size;
+        assert_eq!(terminal.cursor_row, start_row + height as u16);
+        terminal.flush().unwrap();
+        drop(terminal);
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches('▀').count(), width * height);
+    }
+
+    #[rstest]
+    #[case::natural(None, (20.0, 10.0))]
+    #[case::contain(Some(ImageFit::Contain), (80.0, 40.0))]
+    #[case::cover(Some(ImageFit::Cover), (100.0, 50.0))]
+    #[case::fill(Some(ImageFit::Fill), (100.0, 40.0))]
+    fn fit_size(#[case] fit: Option<ImageFit>, #[case] expected: (f64, f64)) {
+        assert_eq!(MediaRender::fit((20.0, 10.0), (100.0, 40.0), fit), expected);
+    }
+
+    #[test]
+    fn cover_crops_center() {
+        let mut image = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]));
+        image.put_pixel(0, 0, Rgba([0, 0, 255, 255]));
+        image.put_pixel(3, 0, Rgba([0, 0, 255, 255]));
+        let image = Rc::new(DynamicImage::ImageRgba8(image));
+        let scaled = MediaRender::scale(image, (4, 2), (2, 2), FilterType::Nearest);
+        assert_eq!((scaled.width(), scaled.height()), (2, 2));
+        assert!(scaled.to_rgba8().pixels().all(|pixel| pixel == &Rgba([255, 0, 0, 255])));
+    }
+
+    #[test]
+    fn scaled_copies() {
+        let image = Image::from(DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]))));
+        let natural = image.at_width(4).expect("invalid image");
+        let scale = |size| image.scaled(natural.clone(), size, size, FilterType::Nearest);
+        let first = scale((2.0, 2.0));
+        assert!(Rc::ptr_eq(&first, &scale((2.0, 2.0))));
+        assert!(!Rc::ptr_eq(&first, &scale((3.0, 3.0))));
+
+        // Once there's too many of them the least recently used one is dropped.
+        for size in 4..4 + MAX_SCALED_IMAGES {
+            scale((size as f64, size as f64));
+        }
+        assert!(!Rc::ptr_eq(&first, &scale((2.0, 2.0))));
+    }
+
+    fn gif(delays: &[u32]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let frames = delays.iter().enumerate().map(|(index, delay)| {
+            let pixel = Rgba([index as u8 * 100, 0, 0, 255]);
+            Frame::from_parts(RgbaImage::from_pixel(2, 2, pixel), 0, 0, image::Delay::from_numer_denom_ms(*delay, 1))
+        });
+        image::codecs::gif::GifEncoder::new(&mut output).encode_frames(frames).expect("encoding failed");
+        output
+    }
+
+    #[test]
+    fn animation() {
+        let image = Image::new(&gif(&[50, 0])).expect("invalid image");
+        assert!(image.is_animated());
+        let start = Instant::now();
+        let first_frame = image.natural().clone();
+        assert!(!image.advance(start));
+        assert!(!image.advance(start + Duration::from_millis(40)));
+        assert!(image.advance(start + Duration::from_millis(50)));
+        assert_ne!(image.natural(), &first_frame);
+
+        // The second frame has no delay so it's shown for the default one.
+        let start = start + Duration::from_millis(50);
+        assert!(!image.advance(start + Duration::from_millis(50)));
+        assert!(image.advance(start + Duration::from_millis(100)));
+        assert_eq!(image.natural(), &first_frame);
+
+        let still = image.without_animation();
+        assert!(!still.is_animated());
+        assert!(!still.advance(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn single_frame_gif() {
+        let image = Image::new(&gif(&[50])).expect("invalid image");
+        assert!(!image.is_animated());
+    }
+
+    #[rstest]
+    #[case::truncated_png(b"\x89PNG\r\n\x1a\n", "invalid png image")]
+    #[case::truncated_webp(b"RIFF\x04\0\0\0WEBP", "invalid webp image")]
+    fn decode_errors(#[case] contents: &[u8], #[case] expected: &str) {
+        let Err(error) = Image::new(contents) else { panic!("image decoded") };
+        assert!(error.to_string().starts_with(expected), "{error}");
+    }
+
+    #[cfg(not(feature = "avif"))]
+    #[test]
+    fn avif_unsupported() {
+        let Err(error) = Image::new(b"\0\0\0\x1cftypavif") else { panic!("image decoded") };
+        assert_eq!(error.to_string(), "avif images are not supported");
+    }
+}
@@ -1,8 +1,20 @@
+pub(crate) mod ansi;
+pub(crate) mod canvas;
+pub(crate) mod capabilities;
+pub(crate) mod chart;
+pub(crate) mod console;
 pub(crate) mod draw;
 pub(crate) mod engine;
+pub(crate) mod exif;
 pub(crate) mod highlighting;
+pub(crate) mod html;
 pub(crate) mod layout;
 pub(crate) mod media;
+pub(crate) mod overview;
+pub(crate) mod picker;
+pub(crate) mod prompt;
 pub(crate) mod properties;
+pub(crate) mod raster;
+pub(crate) mod svg;
 pub(crate) mod terminal;
 pub(crate) mod text;
@@ -0,0 +1,212 @@
+use super::{properties::WindowSize, raster::SlideRasterizer};
+use crate::{
+    markdown::{
+        elements::StyledText,
+        text::{WeightedLine, WeightedText},
+    },
+    presentation::{Presentation, RenderOperation},
+    style::{Color, Colors, TextStyle},
+    theme::{Alignment, Margin},
+};
+use unicode_width::UnicodeWidthChar;
+
+/// The number of columns slides are rendered into when generating their thumbnails.
+const THUMBNAIL_COLUMNS: u16 = 36;
+
+/// The number of rows slides are rendered into when generating their thumbnails.
+const THUMBNAIL_ROWS: u16 = 10;
+
+/// The horizontal space between thumbnails in the overview.
+const THUMBNAIL_GAP: u16 = 2;
+
+/// The rows every thumbnail takes in the overview: the thumbnail itself, its borders, its caption, and a gap.
+const THUMBNAIL_HEIGHT: u16 = THUMBNAIL_ROWS + 4;
+
+const OVERVIEW_MARGIN: u16 = 2;
+
+/// A miniature text rendering of a slide.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Thumbnail {
+    title: Option<String>,
+    lines: Vec<String>,
+}
+
+/// Keeps a thumbnail for every slide in a presentation so the overview doesn't need to render
+/// every slide whenever it's opened.
+#[derive(Debug, Default)]
+pub(crate) struct ThumbnailCache {
+    thumbnails: Vec<Thumbnail>,
+}
+
+impl ThumbnailCache {
+    /// Discard the thumbnails for every slide starting at the given one.
+    ///
+    /// This is meant to be used with the first modification found when diffing a presentation
+    /// against the one it's replacing.
+    pub(crate) fn invalidate_from(&mut self, slide_index: usize) {
+        self.thumbnails.truncate(slide_index);
+    }
+
+    /// Make sure there's a thumbnail for every slide in the presentation.
+    ///
+    /// Only the thumbnails that are missing are generated.
+    pub(crate) fn refresh(&mut self, presentation: &Presentation) {
+        let rasterizer = SlideRasterizer::new(THUMBNAIL_COLUMNS, THUMBNAIL_ROWS);
+        let slides: Vec<_> = presentation.iter_slides().collect();
+        self.thumbnails.truncate(slides.len());
+        for slide in slides.into_iter().skip(self.thumbnails.len()) {
+            // A slide that can't be rendered this small is better shown as blank than not at all.
+            let lines = rasterizer.render_text(slide.iter_chunk_operations()).unwrap_or_default();
+            self.thumbnails.push(Thumbnail { title: slide.metadata().title.clone(), lines });
+        }
+    }
+}
+
+/// A grid of all the slides in a presentation.
+pub(crate) struct SlideOverview<'a> {
+    thumbnails: &'a ThumbnailCache,
+    selected: usize,
+}
+
+impl<'a> SlideOverview<'a> {
+    /// Construct an overview that has the given slide selected.
+    pub(crate) fn new(thumbnails: &'a ThumbnailCache, selected: usize) -> Self {
+        Self { thumbnails, selected }
+    }
+
+    /// Generate the render operations that display this overview.
+    ///
+    /// As many rows of thumbnails as fit in the screen are displayed, scrolling so the selected
+    /// slide is always visible.
+    pub(crate) fn render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
+        let box_width = THUMBNAIL_COLUMNS + 2;
+        let available_columns = dimensions.columns.saturating_sub(OVERVIEW_MARGIN * 2) + THUMBNAIL_GAP;
+        let per_row = (available_columns / (box_width + THUMBNAIL_GAP)).max(1) as usize;
+        let visible_rows = (dimensions.rows.saturating_sub(1) / THUMBNAIL_HEIGHT).max(1) as usize;
+        let selected_row = self.selected / per_row;
+        let first_row = selected_row.saturating_sub(visible_rows - 1);
+
+        let alignment = Alignment::Left { margin: Margin::Fixed(OVERVIEW_MARGIN) };
+        let mut operations = vec![
+            RenderOperation::ClearScreen,
+            RenderOperation::SetColors(Colors::default()),
+            RenderOperation::RenderLineBreak,
+        ];
+        let rows = self.thumbnails.thumbnails.chunks(per_row).enumerate().skip(first_row).take(visible_rows);
+        for (row_index, row) in rows {
+            for line in self.render_row(row_index * per_row, row) {
+                operations.extend([
+                    RenderOperation::RenderText { line, alignment: alignment.clone() },
+                    RenderOperation::RenderLineBreak,
+                ]);
+            }
+        }
+        operations
+    }
+
+    fn render_row(&self, first_index: usize, thumbnails: &[Thumbnail]) -> Vec<WeightedLine> {
+        let width = THUMBNAIL_COLUMNS as usize;
+        let mut lines: Vec<Vec<WeightedText>> = vec![Vec::new(); THUMBNAIL_HEIGHT as usize - 1];
+        for (offset, thumbnail) in thumbnails.iter().enumerate() {
+            let index = first_index + offset;
+            let style = match index == self.selected {
+                true => TextStyle::default()
+                    .bold()
+                    .colors(Colors { foreground: Some(Color::new(255, 200, 0)), background: None }),
+                false => TextStyle::default(),
+            };
+            let border = |text: String| WeightedText::from(StyledText::new(text, style.clone()));
+            let plain = |text: String| WeightedText::from(StyledText::from(text));
+            let gap = || plain(" ".repeat(THUMBNAIL_GAP as usize));
+            lines[0].extend([border(format!("┌{}┐", "─".repeat(width))), gap()]);
+            for row in 0..THUMBNAIL_ROWS as usize {
+                let text = thumbnail.lines.get(row).map(String::as_str).unwrap_or_default();
+                lines[row + 1].extend([border("│".into()), plain(fit(text, width)), border("│".into()), gap()]);
+            }
+            lines[THUMBNAIL_ROWS as usize + 1].extend([border(format!("└{}┘", "─".repeat(width))), gap()]);
+            let caption = match &thumbnail.title {
+                Some(title) => format!("{}. {title}", index + 1),
+                None => format!("{}.", index + 1),
+            };
+            lines[THUMBNAIL_ROWS as usize + 2].extend([border(fit(&caption, width + 2)), gap()]);
+        }
+        lines.into_iter().map(WeightedLine::from).collect()
+    }
+}
+
+/// Truncate or pad a piece of text so it's exactly the given width.
+pub(crate) fn fit(text: &str, width: usize) -> String {
+    let mut output = String::new();
+    let mut output_width = 0;
+    for character in text.chars() {
+        let character_width = character.width().unwrap_or(0);
+        if output_width + character_width > width {
+            break;
+        }
+        output.push(character);
+        output_width += character_width;
+    }
+    output.push_str(&" ".repeat(width - output_width));
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::presentation::{Slide, SlideChunk, SlideMetadata};
+    use rstest::rstest;
+
+    fn slide(text: &str) -> Slide {
+        let line = WeightedLine::from(text.to_string());
+        let operations = vec![RenderOperation::RenderText { line, alignment: Default::default() }];
+        Slide::new(vec![SlideChunk::new(operations, vec![])], vec![])
+            .with_metadata(SlideMetadata { title: Some(text.into()), ..Default::default() })
+    }
+
+    #[test]
+    fn refresh() {
+        let mut cache = ThumbnailCache::default();
+        cache.refresh(&Presentation::new(vec![slide("one"), slide("two")]));
+        assert_eq!(cache.thumbnails.len(), 2);
+        assert_eq!(cache.thumbnails[1].title.as_deref(), Some("two"));
+        assert_eq!(cache.thumbnails[1].lines[0], "two");
+
+        // Thumbnails that weren't invalidated are kept as is.
+        cache.invalidate_from(1);
+        cache.refresh(&Presentation::new(vec![slide("uno"), slide("dos"), slide("tres")]));
+        let lines: Vec<_> = cache.thumbnails.iter().map(|thumbnail| thumbnail.lines[0].as_str()).collect();
+        assert_eq!(lines, &["one", "dos", "tres"]);
+
+        cache.refresh(&Presentation::new(vec![slide("uno")]));
+        assert_eq!(cache.thumbnails.len(), 1);
+    }
+
+    #[test]
+    fn scrolls_to_selected() {
+        let mut cache = ThumbnailCache::default();
+        cache.refresh(&Presentation::new((0..10).map(|index| slide(&index.to_string())).collect()));
+        // One thumbnail per row and one row per screen.
+        let dimensions = WindowSize::from((THUMBNAIL_COLUMNS + 10, THUMBNAIL_HEIGHT + 1));
+        let operations = SlideOverview::new(&cache, 7).render_operations(&dimensions);
+        let texts: Vec<String> = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderText { line, .. } => {
+                    Some(line.iter_texts().map(|text| text.text.text.clone()).collect())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts.len(), THUMBNAIL_HEIGHT as usize - 1);
+        assert!(texts[1].starts_with("│7 "));
+        assert!(texts.last().unwrap().starts_with("8. 7"));
+    }
+
+    #[rstest]
+    #[case::pad("hi", 4, "hi  ")]
+    #[case::truncate("hello", 3, "hel")]
+    #[case::wide("日本", 3, "日 ")]
+    fn fit_text(#[case] text: &str, #[case] width: usize, #[case] expected: &str) {
+        assert_eq!(fit(text, width), expected);
+    }
+}
@@ -0,0 +1,115 @@
+use super::{overview::fit, properties::WindowSize};
+use crate::{
+    markdown::{
+        elements::StyledText,
+        text::{WeightedLine, WeightedText},
+    },
+    presentation::{RenderOperation, Slide},
+    style::{Color, Colors, TextStyle},
+    theme::{Alignment, Margin},
+};
+
+/// The most code blocks listed at once, scrolling so the selected one is always listed.
+const MAX_VISIBLE_BLOCKS: usize = 9;
+
+const HELP: &str = "↑/↓ select · y copy · e edit · <ctrl>e run · c close";
+
+/// A list of the visible code blocks in a slide drawn over its bottom rows, used to pick one to act on.
+pub(crate) struct CodePicker {
+    blocks: Vec<String>,
+    selected: usize,
+}
+
+impl CodePicker {
+    /// Construct a picker for a slide that has the given code block selected.
+    ///
+    /// Every block is identified by its 1 based number followed by its first line.
+    pub(crate) fn new(slide: &Slide, selected: usize) -> Self {
+        let blocks = slide
+            .visible_code_blocks()
+            .enumerate()
+            .map(|(index, block)| {
+                let first_line = block.contents.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+                format!("{}. {}", index + 1, first_line.trim())
+            })
+            .collect();
+        Self { blocks, selected }
+    }
+
+    /// Generate the render operations that display this picker.
+    pub(crate) fn render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
+        let width = (dimensions.columns as usize).saturating_sub(4);
+        let first = self.selected.saturating_sub(MAX_VISIBLE_BLOCKS - 1);
+        let mut lines = vec![(fit("Code blocks", width), false)];
+        if self.blocks.is_empty() {
+            lines.push((fit("There are no code blocks in this slide", width), false));
+        }
+        for (index, block) in self.blocks.iter().enumerate().skip(first).take(MAX_VISIBLE_BLOCKS) {
+            lines.push((fit(block, width), index == self.selected));
+        }
+        lines.push((fit(HELP, width), false));
+
+        let colors = Colors { foreground: Some(Color::new(255, 255, 255)), background: Some(Color::new(40, 40, 40)) };
+        let selected_colors =
+            Colors { foreground: Some(Color::new(0, 0, 0)), background: Some(Color::new(255, 200, 0)) };
+        let alignment = Alignment::Left { margin: Margin::Fixed(2) };
+        let mut operations = vec![RenderOperation::SetColors(colors.clone())];
+        let last_row = lines.len() - 1;
+        for (row, (line, selected)) in lines.into_iter().enumerate() {
+            let style = match selected {
+                true => TextStyle::default().bold().colors(selected_colors.clone()),
+                false => TextStyle::default().colors(colors.clone()),
+            };
+            let line = WeightedLine::from(vec![WeightedText::from(StyledText::new(line, style))]);
+            operations.extend([
+                RenderOperation::JumpToBottomRow { index: (last_row - row) as u16 },
+                RenderOperation::RenderText { line, alignment: alignment.clone() },
+            ]);
+        }
+        operations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::presentation::{CodeBlock, SlideChunk, SlideMetadata};
+
+    fn texts(operations: &[RenderOperation]) -> Vec<(String, bool)> {
+        operations
+            .iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderText { line, .. } => {
+                    let text: String = line.iter_texts().map(|text| text.text.text.clone()).collect();
+                    let selected = line.iter_texts().any(|text| text.text.style.is_bold());
+                    Some((text.trim_end().to_string(), selected))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lists_visible_blocks() {
+        // The last block is in a chunk that isn't visible yet.
+        let code_blocks = [(0, "\nfn main() {}\n"), (0, "echo hi"), (1, "hidden")]
+            .into_iter()
+            .map(|(chunk, contents)| CodeBlock {
+                chunk,
+                contents: contents.into(),
+                execution: None,
+                commands: vec![],
+                line: 1,
+                file: None,
+                replaced_with_output: false,
+            })
+            .collect();
+        let chunks = (0..2).map(|_| SlideChunk::new(vec![], vec![])).collect();
+        let slide = Slide::new(chunks, vec![]).with_metadata(SlideMetadata { code_blocks, ..Default::default() });
+
+        let operations = CodePicker::new(&slide, 1).render_operations(&WindowSize::from((80, 24)));
+        let expected = [("Code blocks", false), ("1. fn main() {}", false), ("2. echo hi", true), (HELP, false)];
+        let expected: Vec<_> = expected.into_iter().map(|(text, selected)| (text.to_string(), selected)).collect();
+        assert_eq!(texts(&operations), expected);
+    }
+}
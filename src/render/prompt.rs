@@ -0,0 +1,151 @@
+use super::{overview::fit, properties::WindowSize};
+use crate::{
+    markdown::{
+        elements::StyledText,
+        text::{WeightedLine, WeightedText},
+    },
+    presentation::{RenderOperation, Slide},
+    style::{Color, Colors, TextStyle},
+    theme::{Alignment, Margin},
+};
+
+/// The most command lines listed at once, the rest are summarized in a single line.
+const MAX_VISIBLE_LINES: usize = 12;
+
+const HELP: &str = "<ctrl>e run · <ctrl>x cancel";
+
+/// A prompt drawn over the bottom rows of a slide that shows what running its code does before it runs.
+pub(crate) struct ExecutionPrompt {
+    lines: Vec<String>,
+}
+
+impl ExecutionPrompt {
+    /// Construct a prompt for the executable code blocks in a slide.
+    ///
+    /// When block numbers are given only those code blocks are included, otherwise every visible one is.
+    pub(crate) fn new(slide: &Slide, blocks: Option<&[usize]>) -> Self {
+        let mut lines = Vec::new();
+        for (index, code_block) in slide.visible_code_blocks().enumerate() {
+            if code_block.execution.is_none() || blocks.is_some_and(|blocks| !blocks.contains(&(index + 1))) {
+                continue;
+            }
+            lines.push(format!("{}.", index + 1));
+            match code_block.commands.is_empty() {
+                true => lines.push("  (unknown command)".into()),
+                false => lines.extend(code_block.commands.iter().map(|command| format!("  {command}"))),
+            };
+        }
+        Self { lines }
+    }
+
+    /// Whether there's anything to run.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Generate the render operations that display this prompt.
+    pub(crate) fn render_operations(&self, dimensions: &WindowSize) -> Vec<RenderOperation> {
+        let width = (dimensions.columns as usize).saturating_sub(4);
+        let mut lines = vec![(fit("Run this code?", width), true)];
+        for line in self.lines.iter().take(MAX_VISIBLE_LINES) {
+            lines.push((fit(line, width), false));
+        }
+        if self.lines.len() > MAX_VISIBLE_LINES {
+            let hidden = format!("  ... and {} more lines", self.lines.len() - MAX_VISIBLE_LINES);
+            lines.push((fit(&hidden, width), false));
+        }
+        lines.push((fit(HELP, width), false));
+
+        let colors = Colors { foreground: Some(Color::new(255, 255, 255)), background: Some(Color::new(40, 40, 40)) };
+        let alignment = Alignment::Left { margin: Margin::Fixed(2) };
+        let mut operations = vec![RenderOperation::SetColors(colors.clone())];
+        let last_row = lines.len() - 1;
+        for (row, (line, bold)) in lines.into_iter().enumerate() {
+            let style = match bold {
+                true => TextStyle::default().bold().colors(colors.clone()),
+                false => TextStyle::default().colors(colors.clone()),
+            };
+            let line = WeightedLine::from(vec![WeightedText::from(StyledText::new(line, style))]);
+            operations.extend([
+                RenderOperation::JumpToBottomRow { index: (last_row - row) as u16 },
+                RenderOperation::RenderText { line, alignment: alignment.clone() },
+            ]);
+        }
+        operations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::presentation::{
+        AsRenderOperations, CodeBlock, RenderOnDemand, RenderOnDemandState, SlideChunk, SlideMetadata,
+    };
+    use rstest::rstest;
+    use std::rc::Rc;
+
+    #[derive(Debug)]
+    struct Noop;
+
+    impl AsRenderOperations for Noop {
+        fn as_render_operations(&self, _: &WindowSize) -> Vec<RenderOperation> {
+            vec![]
+        }
+
+        fn diffable_content(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    impl RenderOnDemand for Noop {
+        fn poll_state(&self) -> RenderOnDemandState {
+            RenderOnDemandState::NotStarted
+        }
+
+        fn start_render(&self) -> bool {
+            false
+        }
+    }
+
+    fn texts(operations: &[RenderOperation]) -> Vec<String> {
+        operations
+            .iter()
+            .filter_map(|operation| match operation {
+                RenderOperation::RenderText { line, .. } => {
+                    let text: String = line.iter_texts().map(|text| text.text.text.clone()).collect();
+                    Some(text.trim_end().to_string())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::all(None, &["Run this code?", "1.", "  $ echo hi", "  $ ls", "3.", "  $ rustc main.rs", HELP])]
+    #[case::selected(Some(&[3][..]), &["Run this code?", "3.", "  $ rustc main.rs", HELP])]
+    fn lists_commands(#[case] blocks: Option<&[usize]>, #[case] expected: &[&str]) {
+        // The second block isn't executable and the last one is in a chunk that isn't visible yet.
+        let code_blocks = [
+            (0, true, vec!["$ echo hi", "$ ls"]),
+            (0, false, vec![]),
+            (0, true, vec!["$ rustc main.rs"]),
+            (1, true, vec!["$ hidden"]),
+        ]
+        .into_iter()
+        .map(|(chunk, executable, commands)| CodeBlock {
+            chunk,
+            contents: String::new(),
+            execution: executable.then(|| Rc::new(Noop) as Rc<dyn RenderOnDemand>),
+            commands: commands.into_iter().map(String::from).collect(),
+            replaced_with_output: false,
+            line: 1,
+            file: None,
+        })
+        .collect();
+        let chunks = (0..2).map(|_| SlideChunk::new(vec![], vec![])).collect();
+        let slide = Slide::new(chunks, vec![]).with_metadata(SlideMetadata { code_blocks, ..Default::default() });
+
+        let operations = ExecutionPrompt::new(&slide, blocks).render_operations(&WindowSize::from((80, 24)));
+        assert_eq!(texts(&operations), expected);
+    }
+}
@@ -5,7 +5,7 @@ use std::io::{self, ErrorKind};
 ///
 /// This is the same as [crossterm::terminal::window_size] except with some added functionality,
 /// like implementing `Clone`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct WindowSize {
     pub(crate) rows: u16,
     pub(crate) columns: u16,
@@ -58,6 +58,15 @@ impl WindowSize {
         }
     }
 
+    /// Get a window with the given dimensions.
+    ///
+    /// This preserves the relationship between rows/columns and pixels.
+    pub(crate) fn with_dimensions(&self, columns: u16, rows: u16) -> WindowSize {
+        let width = (self.pixels_per_column() * columns as f64) as u16;
+        let height = (self.pixels_per_row() * rows as f64) as u16;
+        WindowSize { rows, columns, width, height, has_pixels: self.has_pixels }
+    }
+
     /// The number of pixels per column.
     pub(crate) fn pixels_per_column(&self) -> f64 {
         self.width as f64 / self.columns as f64
@@ -116,4 +125,11 @@ mod test {
 
         assert!(new_dimensions.has_pixels);
     }
+
+    #[test]
+    fn with_dimensions() {
+        let dimensions = WindowSize { rows: 10, columns: 10, width: 200, height: 100, has_pixels: true };
+        let new_dimensions = dimensions.with_dimensions(20, 5);
+        assert_eq!(new_dimensions, WindowSize { rows: 5, columns: 20, width: 400, height: 50, has_pixels: true });
+    }
 }
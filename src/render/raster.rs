@@ -0,0 +1,464 @@
+use super::{
+    ansi::AnsiStyle,
+    draw::RenderError,
+    engine::RenderEngine,
+    properties::WindowSize,
+    terminal::{Terminal, TerminalMode},
+};
+use crate::presentation::{RenderOperation, Slide};
+use font8x8::{UnicodeFonts, BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, LATIN_FONTS, MISC_FONTS};
+use image::{Rgb, RgbImage};
+use std::fmt;
+use unicode_width::UnicodeWidthChar;
+
+/// The width of a glyph in the font, in pixels.
+const GLYPH_WIDTH: u32 = 8;
+
+/// The height of a glyph in the font, in pixels.
+const GLYPH_HEIGHT: u32 = 8;
+
+/// How many pixels every glyph pixel takes horizontally.
+///
+/// Glyphs are stretched twice as much vertically so cells end up with the usual terminal aspect ratio.
+const SCALE: u32 = 2;
+
+const CELL_WIDTH: u32 = GLYPH_WIDTH * SCALE;
+const CELL_HEIGHT: u32 = GLYPH_HEIGHT * SCALE * 2;
+
+/// The character stored in the cells that are taken by the wide character to their left.
+const CONTINUATION: char = '\0';
+
+const DEFAULT_FOREGROUND: Rgb<u8> = Rgb([229, 229, 229]);
+const DEFAULT_BACKGROUND: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Renders slides into images without going through a real terminal.
+pub(crate) struct SlideRasterizer {
+    dimensions: WindowSize,
+}
+
+impl SlideRasterizer {
+    /// Construct a rasterizer that renders slides using the given number of columns and rows.
+    pub(crate) fn new(columns: u16, rows: u16) -> Self {
+        Self { dimensions: WindowSize::from((columns, rows)) }
+    }
+
+    /// Render the visible part of a slide into an image.
+    pub(crate) fn rasterize(&self, slide: &Slide) -> Result<RgbImage, RenderError> {
+        Ok(self.render_grid(slide.iter_operations())?.rasterize())
+    }
+
+    /// Render a set of operations into plain text, one string per row.
+    ///
+    /// Trailing whitespace is removed from every row.
+    pub(crate) fn render_text<'a>(
+        &self,
+        operations: impl Iterator<Item = &'a RenderOperation>,
+    ) -> Result<Vec<String>, RenderError> {
+        Ok(self.render_grid(operations)?.lines())
+    }
+
+    /// Render a set of operations into plain text along with the styles used in it, one row at a time.
+    ///
+    /// See [SlideRasterizer::render_text] for how the text in every row looks.
+    pub(crate) fn render_styled_text<'a>(
+        &self,
+        operations: impl Iterator<Item = &'a RenderOperation>,
+    ) -> Result<Vec<StyledRow>, RenderError> {
+        let grid = self.render_grid(operations)?;
+        let rows = grid.lines().into_iter().zip(grid.styles());
+        Ok(rows.map(|(text, styles)| StyledRow { text, styles }).collect())
+    }
+
+    fn render_grid<'a>(
+        &self,
+        operations: impl Iterator<Item = &'a RenderOperation>,
+    ) -> Result<TerminalGrid, RenderError> {
+        let mut output = Vec::new();
+        {
+            let mut terminal = Terminal::new(&mut output, TerminalMode::Headless)?;
+            RenderEngine::new(&mut terminal, self.dimensions.clone()).render(operations)?;
+            terminal.flush()?;
+        }
+        let mut grid = TerminalGrid::new(self.dimensions.columns, self.dimensions.rows);
+        grid.feed(&String::from_utf8_lossy(&output));
+        Ok(grid)
+    }
+}
+
+/// A row of text and the styles used in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct StyledRow {
+    pub(crate) text: String,
+
+    /// The columns that don't use the default style and the style they use, e.g. `1-4 fg=#ff0000 bold`.
+    pub(crate) styles: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct CellStyle {
+    foreground: Option<Rgb<u8>>,
+    background: Option<Rgb<u8>>,
+    bold: bool,
+    underlined: bool,
+}
+
+impl From<&AnsiStyle> for CellStyle {
+    fn from(style: &AnsiStyle) -> Self {
+        Self {
+            foreground: style.foreground().map(|color| Rgb(color.as_rgb())),
+            background: style.background().map(|color| Rgb(color.as_rgb())),
+            bold: style.is_bold(),
+            underlined: style.is_underlined(),
+        }
+    }
+}
+
+impl fmt::Display for CellStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let color = |Rgb([r, g, b]): Rgb<u8>| format!("#{r:02x}{g:02x}{b:02x}");
+        let mut attributes = Vec::new();
+        attributes.extend(self.foreground.map(|foreground| format!("fg={}", color(foreground))));
+        attributes.extend(self.background.map(|background| format!("bg={}", color(background))));
+        attributes.extend(self.bold.then(|| "bold".to_string()));
+        attributes.extend(self.underlined.then(|| "underlined".to_string()));
+        write!(f, "{}", attributes.join(" "))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cell {
+    character: char,
+    style: CellStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { character: ' ', style: Default::default() }
+    }
+}
+
+/// A virtual terminal screen that interprets the escape codes we emit.
+///
+/// This only understands the handful of sequences [Terminal] writes, which is all we need to
+/// reconstruct what a slide looks like.
+struct TerminalGrid {
+    columns: u16,
+    rows: u16,
+    cells: Vec<Cell>,
+    row: u16,
+    column: u16,
+    style: CellStyle,
+    sgr: AnsiStyle,
+}
+
+impl TerminalGrid {
+    fn new(columns: u16, rows: u16) -> Self {
+        let cells = vec![Cell::default(); columns as usize * rows as usize];
+        Self { columns, rows, cells, row: 0, column: 0, style: Default::default(), sgr: Default::default() }
+    }
+
+    fn feed(&mut self, input: &str) {
+        let mut chars = input.chars();
+        while let Some(character) = chars.next() {
+            match character {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut parameters = String::new();
+                        for character in chars.by_ref() {
+                            if ('\x40'..='\x7e').contains(&character) {
+                                self.apply_csi(&parameters, character);
+                                break;
+                            }
+                            parameters.push(character);
+                        }
+                    }
+                    // Operating system commands, like setting the title, end in a bell.
+                    Some(']') => for _ in chars.by_ref().take_while(|c| *c != '\x07') {},
+                    _ => (),
+                },
+                '\r' => self.column = 0,
+                '\n' => self.row = self.row.saturating_add(1),
+                character if character.is_control() => (),
+                character => self.print(character),
+            };
+        }
+    }
+
+    fn apply_csi(&mut self, parameters: &str, command: char) {
+        // Private sequences, like hiding the cursor, don't affect what's on screen.
+        if parameters.starts_with('?') {
+            return;
+        }
+        let values: Vec<u16> = parameters.split(';').map(|value| value.parse().unwrap_or(0)).collect();
+        let first = values[0];
+        let amount = first.max(1);
+        match command {
+            'H' => {
+                self.row = first.saturating_sub(1);
+                self.column = values.get(1).copied().unwrap_or(1).saturating_sub(1);
+            }
+            'd' => self.row = first.saturating_sub(1),
+            'G' => self.column = first.saturating_sub(1),
+            'A' => self.row = self.row.saturating_sub(amount),
+            'B' => self.row = self.row.saturating_add(amount),
+            'C' => self.column = self.column.saturating_add(amount),
+            'D' => self.column = self.column.saturating_sub(amount),
+            'E' => {
+                self.row = self.row.saturating_add(amount);
+                self.column = 0;
+            }
+            'J' if first >= 2 => self.clear(0..self.cells.len()),
+            'K' => {
+                let start = self.index(self.row, self.column.min(self.columns));
+                let end = self.index(self.row, self.columns);
+                if self.row < self.rows {
+                    self.clear(start..end);
+                }
+            }
+            'm' => {
+                self.sgr.apply(parameters);
+                self.style = CellStyle::from(&self.sgr);
+            }
+            _ => (),
+        }
+    }
+
+    fn print(&mut self, character: char) {
+        let width = character.width().unwrap_or(0) as u16;
+        if width == 0 {
+            return;
+        }
+        for offset in 0..width {
+            let column = self.column.saturating_add(offset);
+            if self.row < self.rows && column < self.columns {
+                let index = self.index(self.row, column);
+                // Wide characters are drawn in the first cell, the rest are marked as taken by it.
+                let character = if offset == 0 { character } else { CONTINUATION };
+                self.cells[index] = Cell { character, style: self.style };
+            }
+        }
+        self.column = self.column.saturating_add(width);
+    }
+
+    fn clear(&mut self, range: std::ops::Range<usize>) {
+        // Like most terminals, cleared cells take the current background color.
+        let style = CellStyle { background: self.style.background, ..Default::default() };
+        for cell in &mut self.cells[range] {
+            *cell = Cell { character: ' ', style };
+        }
+    }
+
+    fn index(&self, row: u16, column: u16) -> usize {
+        row as usize * self.columns as usize + column as usize
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.columns as usize)
+            .map(|row| {
+                let line: String = row.iter().map(|cell| cell.character).filter(|c| *c != CONTINUATION).collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    // The runs of cells in every row that use the same style, other than the default one.
+    fn styles(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.columns as usize)
+            .map(|row| {
+                let mut runs = Vec::new();
+                let mut start = 0;
+                for end in 1..=row.len() {
+                    if end < row.len() && row[end].style == row[start].style {
+                        continue;
+                    }
+                    let style = row[start].style;
+                    if style != CellStyle::default() {
+                        runs.push(format!("{}-{end} {style}", start + 1));
+                    }
+                    start = end;
+                }
+                runs.join(", ")
+            })
+            .collect()
+    }
+
+    fn rasterize(&self) -> RgbImage {
+        let mut image = RgbImage::new(self.columns as u32 * CELL_WIDTH, self.rows as u32 * CELL_HEIGHT);
+        for (index, cell) in self.cells.iter().enumerate() {
+            let x = (index % self.columns as usize) as u32 * CELL_WIDTH;
+            let y = (index / self.columns as usize) as u32 * CELL_HEIGHT;
+            let foreground = cell.style.foreground.unwrap_or(DEFAULT_FOREGROUND);
+            let background = cell.style.background.unwrap_or(DEFAULT_BACKGROUND);
+            fill(&mut image, x, y, CELL_WIDTH, CELL_HEIGHT, background);
+
+            let glyph = glyph(cell.character).unwrap_or_default();
+            for (glyph_y, bits) in glyph.iter().enumerate() {
+                for glyph_x in 0..GLYPH_WIDTH {
+                    // Bold text is drawn by smearing every pixel one pixel to the right.
+                    let is_set = bits & (1 << glyph_x) != 0;
+                    let is_smeared = cell.style.bold && glyph_x > 0 && bits & (1 << (glyph_x - 1)) != 0;
+                    if is_set || is_smeared {
+                        let pixel_x = x + glyph_x * SCALE;
+                        let pixel_y = y + glyph_y as u32 * SCALE * 2;
+                        fill(&mut image, pixel_x, pixel_y, SCALE, SCALE * 2, foreground);
+                    }
+                }
+            }
+            if cell.style.underlined {
+                fill(&mut image, x, y + CELL_HEIGHT - SCALE, CELL_WIDTH, SCALE, foreground);
+            }
+        }
+        image
+    }
+}
+
+fn fill(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for pixel_y in y..y + height {
+        for pixel_x in x..x + width {
+            image.put_pixel(pixel_x, pixel_y, color);
+        }
+    }
+}
+
+fn glyph(character: char) -> Option<[u8; 8]> {
+    BASIC_FONTS
+        .get(character)
+        .or_else(|| LATIN_FONTS.get(character))
+        .or_else(|| BOX_FONTS.get(character))
+        .or_else(|| BLOCK_FONTS.get(character))
+        .or_else(|| GREEK_FONTS.get(character))
+        .or_else(|| MISC_FONTS.get(character))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        markdown::text::WeightedLine,
+        presentation::{RenderOperation, SlideChunk},
+        render::{
+            chart::{Bar, BarChart},
+            media::Image,
+        },
+        style::{Color, Colors},
+        theme::{SplitBackground, SplitRegion},
+    };
+    use image::{DynamicImage, Rgba, RgbaImage};
+    use std::rc::Rc;
+
+    fn cell(grid: &TerminalGrid, row: u16, column: u16) -> Cell {
+        grid.cells[grid.index(row, column)]
+    }
+
+    #[test]
+    fn print_and_move() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.feed("hi\x1b[2;4Hx\x1b[1Ey\x1b[5Gz");
+        assert_eq!(cell(&grid, 0, 0).character, 'h');
+        assert_eq!(cell(&grid, 0, 1).character, 'i');
+        assert_eq!(cell(&grid, 1, 3).character, 'x');
+        assert_eq!(cell(&grid, 2, 0).character, 'y');
+        assert_eq!(cell(&grid, 2, 4).character, 'z');
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut grid = TerminalGrid::new(2, 1);
+        grid.feed("hello\x1b[5;1Hbye");
+        assert_eq!(cell(&grid, 0, 1).character, 'e');
+    }
+
+    #[test]
+    fn colors() {
+        let mut grid = TerminalGrid::new(4, 1);
+        grid.feed("\x1b[38;2;1;2;3m\x1b[48;5;196m\x1b[1ma\x1b[0mb");
+        let (foreground, background) = (Some(Rgb([1, 2, 3])), Some(Rgb([255, 0, 0])));
+        assert_eq!(cell(&grid, 0, 0).style, CellStyle { foreground, background, bold: true, underlined: false });
+        assert_eq!(cell(&grid, 0, 1).style, CellStyle::default());
+    }
+
+    #[test]
+    fn clear_uses_background() {
+        let mut grid = TerminalGrid::new(2, 2);
+        grid.feed("\x1b[48;2;10;20;30m\x1b[2J");
+        let background = Some(Rgb([10, 20, 30]));
+        assert!(grid.cells.iter().all(|cell| cell.style.background == background));
+    }
+
+    #[test]
+    fn lines() {
+        let mut grid = TerminalGrid::new(4, 2);
+        grid.feed("日a\x1b[2;2Hb");
+        assert_eq!(grid.lines(), &["日a", " b"]);
+    }
+
+    #[test]
+    fn styles() {
+        let mut grid = TerminalGrid::new(6, 2);
+        grid.feed("\x1b[38;2;255;0;0mab\x1b[1mc\x1b[0md\x1b[4;48;5;21me");
+        assert_eq!(grid.styles(), &["1-2 fg=#ff0000, 3-3 fg=#ff0000 bold, 5-5 bg=#0000ff underlined", ""]);
+    }
+
+    #[test]
+    fn ignored_sequences() {
+        let mut grid = TerminalGrid::new(4, 1);
+        grid.feed("\x1b[?25l\x1b]0;title\x07\x1b[22;0ta");
+        assert_eq!(cell(&grid, 0, 0).character, 'a');
+    }
+
+    #[test]
+    fn rasterize_slide() {
+        let colors = Colors { background: Some(Color::new(0, 0, 255)), foreground: Some(Color::new(255, 255, 255)) };
+        let operations = vec![
+            RenderOperation::SetColors(colors),
+            RenderOperation::ClearScreen,
+            RenderOperation::RenderText { line: WeightedLine::from("█".to_string()), alignment: Default::default() },
+        ];
+        let slide = Slide::new(vec![SlideChunk::new(operations, vec![])], vec![]);
+        let image = SlideRasterizer::new(4, 2).rasterize(&slide).expect("rasterizing failed");
+        assert_eq!(image.dimensions(), (4 * CELL_WIDTH, 2 * CELL_HEIGHT));
+        assert_eq!(image.get_pixel(0, 0), &Rgb([255, 255, 255]));
+        assert_eq!(image.get_pixel(4 * CELL_WIDTH - 1, 2 * CELL_HEIGHT - 1), &Rgb([0, 0, 255]));
+    }
+
+    #[test]
+    fn wide_split_background() {
+        let split = SplitBackground { region: SplitRegion::Left, percent: 70, color: Color::new(255, 0, 0) };
+        let operations = [RenderOperation::RenderSplitBackground(split)];
+        let grid = SlideRasterizer::new(1000, 1).render_grid(operations.iter()).expect("rendering failed");
+        assert_eq!(cell(&grid, 0, 699).style.background, Some(Rgb([255, 0, 0])));
+        assert_eq!(cell(&grid, 0, 700).style.background, None);
+    }
+
+    #[test]
+    fn rasterize_image() {
+        let image = Image::from(DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255]))));
+        let slide = Slide::new(vec![SlideChunk::new(vec![RenderOperation::RenderImage(image)], vec![])], vec![]);
+        let image = SlideRasterizer::new(4, 2).rasterize(&slide).expect("rasterizing failed");
+        // The image is drawn in the middle of the first row.
+        assert_eq!(image.get_pixel(2 * CELL_WIDTH, 0), &Rgb([255, 0, 0]));
+        assert_eq!(image.get_pixel(2 * CELL_WIDTH, CELL_HEIGHT - 1), &Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn custom_operations_are_clipped() {
+        let bars = (0..5).map(|value| Bar { label: value.to_string(), value }).collect();
+        let after = WeightedLine::from("after".to_string());
+        let operations = [
+            RenderOperation::RenderLineBreak,
+            RenderOperation::RenderCustom(Rc::new(BarChart::new(bars))),
+            RenderOperation::RenderText { line: after, alignment: Default::default() },
+        ];
+        let lines = SlideRasterizer::new(12, 3).render_text(operations.iter()).expect("rendering failed");
+        assert_eq!(lines, &["", "0   0", "1  █ 1"]);
+    }
+
+    #[test]
+    fn image_size() {
+        let grid = TerminalGrid::new(3, 2);
+        let image = grid.rasterize();
+        assert_eq!(image.dimensions(), (3 * CELL_WIDTH, 2 * CELL_HEIGHT));
+    }
+}
@@ -0,0 +1,209 @@
+//! Rasterizes SVG images using resvg.
+
+use super::media::InvalidImage;
+use image::{DynamicImage, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, OnceLock},
+};
+
+/// The number of rasterizations kept around, for every image together.
+const MAX_RASTERIZATIONS: usize = 16;
+
+/// An SVG image.
+///
+/// These are parsed once when loaded and rasterized again when drawn at a different width so
+/// they stay sharp at any size.
+pub(crate) struct VectorImage {
+    source: Vec<u8>,
+    tree: usvg::Tree,
+    natural: Rc<DynamicImage>,
+    // This image's key in the rasterizations.
+    id: usize,
+    rasterizations: Rasterizations,
+}
+
+impl VectorImage {
+    /// Construct a vector image out of an SVG document, rasterizing it at its natural size.
+    ///
+    /// Rasterizations at any other size are kept in the given cache.
+    pub(crate) fn new(source: Vec<u8>, rasterizations: &Rasterizations) -> Result<Self, InvalidImage> {
+        let options = usvg::Options { fontdb: fonts(), ..Default::default() };
+        let tree = usvg::Tree::from_data(&source, &options).map_err(|e| InvalidImage::Rasterize(e.to_string()))?;
+        let natural = Rc::new(rasterize(&tree, tree.size().width().ceil() as u32)?);
+        let id = rasterizations.next_id();
+        Ok(Self { source, tree, natural, id, rasterizations: rasterizations.clone() })
+    }
+
+    /// Check whether some contents look like an SVG document.
+    pub(crate) fn is_svg(contents: &[u8]) -> bool {
+        let Ok(contents) = std::str::from_utf8(contents) else {
+            return false;
+        };
+        let contents = contents.trim_start_matches('\u{feff}').trim_start();
+        contents.starts_with("<svg") || (contents.starts_with("<?xml") && contents.contains("<svg"))
+    }
+
+    /// Get this image rasterized at its natural size.
+    pub(crate) fn natural(&self) -> &DynamicImage {
+        &self.natural
+    }
+
+    /// Get this image rasterized so it's the given number of pixels wide.
+    pub(crate) fn rasterize(&self, width: u32) -> Result<Rc<DynamicImage>, InvalidImage> {
+        if width == self.natural.width() {
+            return Ok(self.natural.clone());
+        }
+        if let Some(image) = self.rasterizations.get(self.id, width) {
+            return Ok(image);
+        }
+        let image = Rc::new(rasterize(&self.tree, width)?);
+        self.rasterizations.insert(self.id, image.clone());
+        Ok(image)
+    }
+}
+
+/// The vector images rasterized at the sizes they were drawn at, from least to most recently used.
+///
+/// Images are drawn over and over again at the same few sizes, e.g. in the presentation and in the
+/// presenter console, so the latest rasterizations are kept around. This is a handle that's cheap
+/// to clone, and every clone shares the same rasterizations.
+#[derive(Clone, Default)]
+pub(crate) struct Rasterizations(Rc<RefCell<RasterizationEntries>>);
+
+#[derive(Default)]
+struct RasterizationEntries {
+    next_id: usize,
+    images: Vec<(usize, Rc<DynamicImage>)>,
+}
+
+impl Rasterizations {
+    /// Forget about every rasterization.
+    pub(crate) fn clear(&self) {
+        self.0.borrow_mut().images.clear();
+    }
+
+    fn next_id(&self) -> usize {
+        let mut entries = self.0.borrow_mut();
+        entries.next_id += 1;
+        entries.next_id
+    }
+
+    fn get(&self, id: usize, width: u32) -> Option<Rc<DynamicImage>> {
+        let images = &mut self.0.borrow_mut().images;
+        let index = images.iter().position(|(other, image)| *other == id && image.width() == width)?;
+        let entry = images.remove(index);
+        let image = entry.1.clone();
+        images.push(entry);
+        Some(image)
+    }
+
+    fn insert(&self, id: usize, image: Rc<DynamicImage>) {
+        let images = &mut self.0.borrow_mut().images;
+        if images.len() == MAX_RASTERIZATIONS {
+            images.remove(0);
+        }
+        images.push((id, image));
+    }
+}
+
+impl PartialEq for VectorImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+// Text in images is drawn using the system's fonts, which take a while to load so it's only done once.
+fn fonts() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    let fonts = FONTS.get_or_init(|| {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        Arc::new(fonts)
+    });
+    fonts.clone()
+}
+
+// Rasterizes an image so it's the given number of pixels wide, keeping its aspect ratio.
+fn rasterize(tree: &usvg::Tree, width: u32) -> Result<DynamicImage, InvalidImage> {
+    let size = tree.size();
+    let width = width.max(1);
+    let scale = width as f32 / size.width();
+    let height = ((size.height() * scale).round() as u32).max(1);
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| InvalidImage::Rasterize(format!("can't be rasterized at {width}x{height}")))?;
+    resvg::render(tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    // tiny-skia uses premultiplied alpha while the image crate doesn't.
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let image = RgbaImage::from_raw(width, height, pixels).expect("pixmap has the wrong number of pixels");
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::svg("<svg xmlns=\"http://www.w3.org/2000/svg\"/>", true)]
+    #[case::xml_declaration("<?xml version=\"1.0\"?>\n<svg/>", true)]
+    #[case::leading_whitespace("\n  <svg/>", true)]
+    #[case::other_xml("<?xml version=\"1.0\"?>\n<html/>", false)]
+    #[case::png("\u{89}PNG", false)]
+    fn is_svg(#[case] contents: &str, #[case] expected: bool) {
+        assert_eq!(VectorImage::is_svg(contents.as_bytes()), expected);
+    }
+
+    #[test]
+    fn rasterize() {
+        let source = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+            <rect width="10" height="10" fill="red"/>
+        </svg>"#;
+        let rasterizations = Rasterizations::default();
+        let image = VectorImage::new(source.as_bytes().to_vec(), &rasterizations).expect("invalid image");
+        assert_eq!((image.natural().width(), image.natural().height()), (20, 10));
+
+        let rasterized = image.rasterize(40).expect("rasterizing failed");
+        assert!(Rc::ptr_eq(&rasterized, &image.rasterize(40).expect("rasterizing failed")));
+
+        // The rasterizations are shared with other images but they each get their own ones.
+        let other = VectorImage::new(source.as_bytes().to_vec(), &rasterizations).expect("invalid image");
+        assert!(!Rc::ptr_eq(&rasterized, &other.rasterize(40).expect("rasterizing failed")));
+
+        let image = rasterized.to_rgba8();
+        assert_eq!(image.dimensions(), (40, 20));
+        assert_eq!(image.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(35, 5).0[3], 0);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(VectorImage::new(b"<svg".to_vec(), &Default::default()).is_err());
+    }
+
+    #[test]
+    fn rasterizations() {
+        let rasterizations = Rasterizations::default();
+        for width in 1..=MAX_RASTERIZATIONS as u32 {
+            rasterizations.insert(0, Rc::new(DynamicImage::new_rgba8(width, 1)));
+        }
+        // Using the oldest one makes the second oldest one the next one to go.
+        assert!(rasterizations.get(0, 1).is_some());
+        rasterizations.insert(1, Rc::new(DynamicImage::new_rgba8(1, 1)));
+        assert!(rasterizations.get(0, 1).is_some());
+        assert!(rasterizations.get(0, 2).is_none());
+        assert!(rasterizations.get(1, 1).is_some());
+
+        rasterizations.clear();
+        assert!(rasterizations.get(1, 1).is_none());
+    }
+}
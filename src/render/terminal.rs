@@ -1,29 +1,102 @@
-use super::properties::CursorPosition;
-use crate::style::Colors;
+use super::{capabilities::TerminalCapabilities, properties::CursorPosition};
+use crate::{clipboard::Clipboard, presenter::ImageProtocol, style::Colors};
 use crossterm::{
     cursor,
     style::{self, StyledContent},
     terminal::{self},
     QueueableCommand,
 };
-use std::io;
+use std::io::{self, BufWriter, Write};
+
+/// The size of the buffer used to batch writes in low bandwidth and headless modes.
+const LOW_BANDWIDTH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The way in which the terminal is written to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum TerminalMode {
+    /// Use every feature available.
+    #[default]
+    Default,
+
+    /// Minimize the amount of data written, e.g. when presenting over a slow SSH connection.
+    ///
+    /// In this mode writes are batched until the terminal is flushed, colors are downgraded to the
+    /// 256 color palette, and images are approximated using colored characters.
+    LowBandwidth,
+
+    /// Write into a buffer rather than a real terminal, e.g. to rasterize slides into images.
+    ///
+    /// The terminal's state is never touched in this mode, and images are approximated using colored
+    /// characters.
+    Headless,
+}
 
 /// A wrapper over the terminal write handle.
 pub(crate) struct Terminal<W>
 where
     W: io::Write,
 {
-    writer: W,
+    writer: BufWriter<W>,
+    mode: TerminalMode,
+    detected_capabilities: TerminalCapabilities,
+    capabilities: TerminalCapabilities,
+    image_protocol: ImageProtocol,
+    avoiding_stdout: bool,
     pub(crate) cursor_row: u16,
+    title_saved: bool,
 }
 
 impl<W: io::Write> Terminal<W> {
-    pub(crate) fn new(mut writer: W) -> io::Result<Self> {
-        terminal::enable_raw_mode()?;
-        writer.queue(cursor::Hide)?;
-        writer.queue(terminal::EnterAlternateScreen)?;
+    pub(crate) fn new(writer: W, mode: TerminalMode) -> io::Result<Self> {
+        // A zero sized buffer means every write goes straight into the underlying writer.
+        let buffer_size = match mode {
+            TerminalMode::Default => 0,
+            TerminalMode::LowBandwidth | TerminalMode::Headless => LOW_BANDWIDTH_BUFFER_SIZE,
+        };
+        let mut writer = BufWriter::with_capacity(buffer_size, writer);
+        if mode != TerminalMode::Headless {
+            terminal::enable_raw_mode()?;
+            writer.queue(cursor::Hide)?;
+            writer.queue(terminal::EnterAlternateScreen)?;
+        }
 
-        Ok(Self { writer, cursor_row: 0 })
+        let capabilities = TerminalCapabilities::detect(mode);
+        Ok(Self {
+            writer,
+            mode,
+            detected_capabilities: capabilities,
+            capabilities,
+            image_protocol: ImageProtocol::Auto,
+            avoiding_stdout: false,
+            cursor_row: 0,
+            title_saved: false,
+        })
+    }
+
+    /// Get what this terminal is able to do.
+    pub(crate) fn capabilities(&self) -> &TerminalCapabilities {
+        &self.capabilities
+    }
+
+    /// Never write into stdout, as this terminal is written to some other way.
+    pub(crate) fn avoid_stdout(&mut self) {
+        self.avoiding_stdout = true;
+        self.update_capabilities();
+    }
+
+    /// Draw images using the given protocol rather than the detected one.
+    pub(crate) fn set_image_protocol(&mut self, protocol: ImageProtocol) {
+        self.image_protocol = protocol;
+        self.update_capabilities();
+    }
+
+    // Not writing into stdout always wins over the protocol that was asked for.
+    fn update_capabilities(&mut self) {
+        let capabilities = self.detected_capabilities.with_image_protocol(self.image_protocol);
+        self.capabilities = match self.avoiding_stdout {
+            true => capabilities.without_stdout(),
+            false => capabilities,
+        };
     }
 
     pub(crate) fn move_to(&mut self, column: u16, row: u16) -> io::Result<()> {
@@ -56,11 +129,19 @@ impl<W: io::Write> Terminal<W> {
     }
 
     pub(crate) fn print_line(&mut self, text: &str) -> io::Result<()> {
-        self.writer.queue(style::Print(text))?;
+        match self.mode {
+            TerminalMode::Default | TerminalMode::Headless => self.writer.queue(style::Print(text))?,
+            TerminalMode::LowBandwidth => self.writer.queue(style::Print(downgrade_escape_codes(text)))?,
+        };
         Ok(())
     }
 
-    pub(crate) fn print_styled_line(&mut self, content: StyledContent<String>) -> io::Result<()> {
+    pub(crate) fn print_styled_line(&mut self, mut content: StyledContent<String>) -> io::Result<()> {
+        if self.mode == TerminalMode::LowBandwidth {
+            let style = content.style_mut();
+            style.foreground_color = style.foreground_color.map(downgrade_color);
+            style.background_color = style.background_color.map(downgrade_color);
+        }
         self.writer.queue(style::PrintStyledContent(content))?;
         Ok(())
     }
@@ -72,17 +153,75 @@ impl<W: io::Write> Terminal<W> {
     }
 
     pub(crate) fn set_colors(&mut self, colors: Colors) -> io::Result<()> {
-        self.writer.queue(style::SetColors(colors.into()))?;
+        let mut colors: style::Colors = colors.into();
+        if self.mode == TerminalMode::LowBandwidth {
+            colors.foreground = colors.foreground.map(downgrade_color);
+            colors.background = colors.background.map(downgrade_color);
+        }
+        self.writer.queue(style::SetColors(colors))?;
+        Ok(())
+    }
+
+    pub(crate) fn ring_bell(&mut self) -> io::Result<()> {
+        self.writer.write_all(b"\x07")?;
+        Ok(())
+    }
+
+    pub(crate) fn notify(&mut self, message: &str) -> io::Result<()> {
+        // OSC 9 desktop notification.
+        write!(self.writer, "\x1b]9;{message}\x07")?;
+        Ok(())
+    }
+
+    pub(crate) fn copy_to_clipboard(&mut self, text: &str) -> io::Result<()> {
+        self.writer.write_all(Clipboard::osc52_sequence(text).as_bytes())?;
         Ok(())
     }
 
+    pub(crate) fn set_title(&mut self, title: &str) -> io::Result<()> {
+        if !self.title_saved {
+            // Push the current title onto the terminal's title stack so we can restore it on exit.
+            self.writer.write_all(b"\x1b[22;0t")?;
+            self.title_saved = true;
+        }
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        self.writer.queue(terminal::SetTitle(title))?;
+        Ok(())
+    }
+
+    /// Give the terminal back while running something that takes it over, like an editor.
+    pub(crate) fn suspend<T>(&mut self, run: impl FnOnce() -> T) -> io::Result<T> {
+        self.writer.queue(terminal::LeaveAlternateScreen)?;
+        self.writer.queue(cursor::Show)?;
+        self.writer.flush()?;
+        terminal::disable_raw_mode()?;
+        let output = run();
+        terminal::enable_raw_mode()?;
+        self.writer.queue(cursor::Hide)?;
+        self.writer.queue(terminal::EnterAlternateScreen)?;
+        self.writer.flush()?;
+        Ok(output)
+    }
+
     pub(crate) fn flush(&mut self) -> io::Result<()> {
         self.writer.flush()?;
         Ok(())
     }
 
+    /// Get the row the cursor is currently at.
+    ///
+    /// This asks the terminal for it unless we're headless, in which case there's nobody to ask.
+    pub(crate) fn current_row(&mut self) -> io::Result<u16> {
+        if self.mode == TerminalMode::Headless {
+            return Ok(self.cursor_row);
+        }
+        // Anything still buffered could move the cursor so it needs to be written first.
+        self.writer.flush()?;
+        Ok(CursorPosition::current()?.row)
+    }
+
     pub(crate) fn sync_cursor_row(&mut self) -> io::Result<()> {
-        self.cursor_row = CursorPosition::current()?.row;
+        self.cursor_row = self.current_row()?;
         Ok(())
     }
 }
@@ -92,9 +231,110 @@ where
     W: io::Write,
 {
     fn drop(&mut self) {
+        if self.mode == TerminalMode::Headless {
+            let _ = self.writer.flush();
+            return;
+        }
         let _ = self.writer.queue(terminal::LeaveAlternateScreen);
         let _ = self.writer.queue(cursor::Show);
+        if self.title_saved {
+            let _ = self.writer.write_all(b"\x1b[23;0t");
+        }
         let _ = self.writer.flush();
         let _ = terminal::disable_raw_mode();
     }
 }
+
+/// Convert a color into the closest one in the 256 color palette.
+fn downgrade_color(color: style::Color) -> style::Color {
+    let style::Color::Rgb { r, g, b } = color else {
+        return color;
+    };
+    // Grays get mapped into the grayscale ramp, which has more levels than the color cube.
+    if r == g && g == b {
+        return match r {
+            0..=3 => style::Color::AnsiValue(16),
+            248..=255 => style::Color::AnsiValue(231),
+            _ => style::Color::AnsiValue(232 + ((r as u16 - 8) * 24 / 240) as u8),
+        };
+    }
+    let scale = |value: u8| ((value as u16 * 5 + 127) / 255) as u8;
+    style::Color::AnsiValue(16 + 36 * scale(r) + 6 * scale(g) + scale(b))
+}
+
+/// Rewrite any true color escape codes in a piece of text into their 256 color equivalents.
+fn downgrade_escape_codes(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find(|c: char| ('\x40'..='\x7e').contains(&c)) else {
+            output.push_str("\x1b[");
+            break;
+        };
+        let (parameters, command) = (&rest[..end], &rest[end..end + 1]);
+        rest = &rest[end + 1..];
+        output.push_str("\x1b[");
+        if command == "m" {
+            output.push_str(&downgrade_sgr_parameters(parameters));
+        } else {
+            output.push_str(parameters);
+        }
+        output.push_str(command);
+    }
+    output.push_str(rest);
+    output
+}
+
+fn downgrade_sgr_parameters(parameters: &str) -> String {
+    let values: Vec<&str> = parameters.split(';').collect();
+    let mut output = Vec::new();
+    let mut index = 0;
+    while index < values.len() {
+        let value = values[index];
+        let is_true_color = matches!(value, "38" | "48") && values.get(index + 1) == Some(&"2");
+        let components = values.get(index + 2..index + 5).map(|c| [c[0], c[1], c[2]].map(|c| c.parse::<u8>()));
+        match components {
+            Some([Ok(r), Ok(g), Ok(b)]) if is_true_color => {
+                let style::Color::AnsiValue(color) = downgrade_color(style::Color::Rgb { r, g, b }) else {
+                    unreachable!("rgb colors are always downgraded to ansi values");
+                };
+                output.push(format!("{value};5;{color}"));
+                index += 5;
+            }
+            _ => {
+                output.push(value.to_string());
+                index += 1;
+            }
+        }
+    }
+    output.join(";")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::black(0, 0, 0, 16)]
+    #[case::white(255, 255, 255, 231)]
+    #[case::gray(128, 128, 128, 244)]
+    #[case::red(255, 0, 0, 196)]
+    #[case::teal(0, 128, 128, 37)]
+    fn downgrade(#[case] r: u8, #[case] g: u8, #[case] b: u8, #[case] expected: u8) {
+        assert_eq!(downgrade_color(style::Color::Rgb { r, g, b }), style::Color::AnsiValue(expected));
+    }
+
+    #[rstest]
+    #[case::plain("hello", "hello")]
+    #[case::foreground("\x1b[38;2;255;0;0mhi", "\x1b[38;5;196mhi")]
+    #[case::both("\x1b[48;2;0;0;0m\x1b[38;2;255;255;255mhi", "\x1b[48;5;16m\x1b[38;5;231mhi")]
+    #[case::mixed("\x1b[1;38;2;255;0;0;4mhi", "\x1b[1;38;5;196;4mhi")]
+    #[case::other_sequence("\x1b[2Khi", "\x1b[2Khi")]
+    #[case::truncated("hi\x1b[38;2", "hi\x1b[38;2")]
+    fn downgrade_escapes(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(downgrade_escape_codes(input), expected);
+    }
+}
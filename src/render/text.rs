@@ -2,9 +2,11 @@ use super::terminal::Terminal;
 use crate::{
     markdown::text::WeightedLine,
     render::{
+        capabilities::GraphicsProtocol,
         draw::{RenderError, RenderResult},
         layout::{Layout, Positioning},
-        properties::WindowSize,
+        media::{Image, MediaRender},
+        properties::{CursorPosition, WindowSize},
     },
     style::{Colors, TextStyle},
 };
@@ -19,13 +21,15 @@ pub(crate) struct TextDrawer<'a> {
     line: &'a WeightedLine,
     positioning: Positioning,
     default_colors: &'a Colors,
+    dimensions: &'a WindowSize,
+    image_protocol: Option<GraphicsProtocol>,
 }
 
 impl<'a> TextDrawer<'a> {
     pub(crate) fn new(
         layout: &Layout,
         line: &'a WeightedLine,
-        dimensions: &WindowSize,
+        dimensions: &'a WindowSize,
         default_colors: &'a Colors,
     ) -> Result<Self, RenderError> {
         let text_length = line.width() as u16;
@@ -34,10 +38,16 @@ impl<'a> TextDrawer<'a> {
         if text_length > positioning.max_line_length && positioning.max_line_length <= MINIMUM_LINE_LENGTH {
             Err(RenderError::TerminalTooSmall)
         } else {
-            Ok(Self { line, positioning, default_colors })
+            Ok(Self { line, positioning, default_colors, dimensions, image_protocol: None })
         }
     }
 
+    /// Draw the images within the text using the given protocol instead of leaving their space blank.
+    pub(crate) fn with_image_protocol(mut self, protocol: Option<GraphicsProtocol>) -> Self {
+        self.image_protocol = protocol;
+        self
+    }
+
     /// Draw text on the given handle.
     ///
     /// This performs word splitting and word wrapping.
@@ -52,7 +62,10 @@ impl<'a> TextDrawer<'a> {
             if line_index > 0 {
                 terminal.move_down(1)?;
             }
+            let mut column = start_column;
             for chunk in line {
+                let image = chunk.image();
+                let width = chunk.width() as u16;
                 let (text, style) = chunk.into_parts();
                 let text = style.apply(text);
                 terminal.print_styled_line(text)?;
@@ -62,8 +75,43 @@ impl<'a> TextDrawer<'a> {
                 if style != TextStyle::default() {
                     terminal.set_colors(self.default_colors.clone())?;
                 }
+                if let (Some(image), Some(protocol)) = (image, self.image_protocol) {
+                    self.draw_image(image, protocol, column, width, terminal)?;
+                }
+                column += width;
             }
         }
         Ok(())
     }
+
+    // Images are drawn over the text that was printed in their place, as if the window ended right where that text
+    // does so they're fit into those cells.
+    fn draw_image<W>(
+        &self,
+        image: &Image,
+        protocol: GraphicsProtocol,
+        column: u16,
+        columns: u16,
+        terminal: &mut Terminal<W>,
+    ) -> RenderResult
+    where
+        W: io::Write,
+    {
+        let row = terminal.cursor_row;
+        let dimensions = WindowSize {
+            rows: row + 1,
+            columns,
+            width: (self.dimensions.pixels_per_column() * columns as f64) as u16,
+            height: (self.dimensions.pixels_per_row() * (row + 1) as f64) as u16,
+            has_pixels: self.dimensions.has_pixels,
+        };
+        let position = CursorPosition { column, row };
+        MediaRender
+            .draw_image(image, position, &dimensions, protocol, self.default_colors.background, terminal)
+            .map_err(|e| RenderError::Other(Box::new(e)))?;
+        // Approximated images change colors as they're drawn.
+        terminal.set_colors(self.default_colors.clone())?;
+        terminal.move_to(column + columns, row)?;
+        Ok(())
+    }
 }
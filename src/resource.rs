@@ -1,21 +1,48 @@
 use crate::{
-    render::media::{Image, InvalidImage},
+    config::{ExecutionConfig, MathConfig},
+    execute::{CodeExecuteError, CodeExecuter},
+    markdown::elements::Code,
+    render::{
+        media::{Image, InvalidImage},
+        svg::Rasterizations,
+    },
+    style::Color,
     theme::{LoadThemeError, PresentationTheme},
+    transcript::ExecutionTranscript,
 };
 use std::{
-    collections::HashMap,
-    fs, io,
+    collections::{BTreeSet, HashMap},
+    env, fs, io,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
 };
 
+/// How long to wait for a remote image to be downloaded.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MERMAID_COMMAND: &str = "mmdc --quiet --backgroundColor transparent --input diagram.mmd --output diagram.png";
+const GRAPHVIZ_COMMAND: &str = "dot -Tpng -Gbgcolor=transparent -o diagram.png diagram.dot";
+const D2_COMMAND: &str = "d2 diagram.d2 diagram.png";
+const DEFAULT_LATEX_COMMAND: &str = "latex -interaction=nonstopmode -halt-on-error formula.tex \
+    && dvipng -D 300 -T tight -bg Transparent -o formula.png formula.dvi";
+const DEFAULT_TYPST_COMMAND: &str = "typst compile --format png --ppi 300 formula.typ formula.png";
+
 /// Manages resources pulled from the filesystem such as images.
 ///
 /// All resources are cached so once a specific resource is loaded, looking it up with the same
 /// path will involve an in-memory lookup.
 pub struct Resources {
     base_path: PathBuf,
+    root: Option<PathBuf>,
+    provider: ResourceProvider,
+    remote_images: RemoteImages,
+    diagrams: Diagrams,
     images: HashMap<PathBuf, Image>,
+    rasterizations: Rasterizations,
     themes: HashMap<PathBuf, PresentationTheme>,
+    code_outputs: HashMap<String, Vec<String>>,
+    included_files: BTreeSet<PathBuf>,
 }
 
 impl Resources {
@@ -23,30 +50,179 @@ impl Resources {
     ///
     /// Any relative paths will be assumed to be relative to the given base.
     pub fn new<P: Into<PathBuf>>(base_path: P) -> Self {
-        Self { base_path: base_path.into(), images: Default::default(), themes: Default::default() }
+        Self {
+            base_path: base_path.into(),
+            root: None,
+            provider: Default::default(),
+            remote_images: Default::default(),
+            diagrams: Default::default(),
+            images: Default::default(),
+            rasterizations: Default::default(),
+            themes: Default::default(),
+            code_outputs: Default::default(),
+            included_files: Default::default(),
+        }
+    }
+
+    /// Read every resource, including the presentation itself, from a git revision rather than the
+    /// working tree.
+    pub fn with_git_revision<S: Into<String>>(mut self, revision: S) -> Self {
+        self.provider = ResourceProvider::GitRevision(revision.into());
+        self
+    }
+
+    /// Get the git revision resources are read from, if they're not read from the working tree.
+    pub(crate) fn git_revision(&self) -> Option<&str> {
+        match &self.provider {
+            ResourceProvider::Filesystem => None,
+            ResourceProvider::GitRevision(revision) => Some(revision),
+        }
+    }
+
+    /// Use the given commands to render math blocks.
+    pub fn with_math_config(mut self, config: MathConfig) -> Self {
+        self.diagrams.math = config;
+        self
+    }
+
+    /// Get the base path, which is usually the presentation's directory.
+    pub(crate) fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
+    /// Set the directory resources are looked up in, relative to the base path.
+    ///
+    /// Resources are looked up in the base path itself if this isn't set.
+    pub(crate) fn set_root(&mut self, root: Option<PathBuf>) {
+        self.root = root;
+    }
+
+    /// Get the path a resource is at.
+    ///
+    /// Relative paths are relative to the root set via [Resources::set_root], if any, or the base
+    /// path otherwise. Absolute paths are used as is.
+    pub(crate) fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        match &self.root {
+            Some(root) => self.base_path.join(root).join(path),
+            None => self.base_path.join(path),
+        }
+    }
+
+    /// Check whether there's a file or directory in the given path, which is resolved like in
+    /// [Resources::resolve].
+    pub(crate) fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.provider.exists(&self.resolve(path))
+    }
+
+    /// Read the presentation in the given path.
+    pub(crate) fn presentation(&self, path: &Path) -> io::Result<String> {
+        let contents = self.provider.read(path)?;
+        String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read a markdown file that's used as part of the presentation.
+    pub(crate) fn markdown<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
+        self.presentation(&self.resolve(path))
+    }
+
+    /// Read a file whose contents are included in the presentation, like a code snippet.
+    ///
+    /// These are never cached, and every file read this way is kept track of so it can be watched
+    /// for changes, see [Resources::included_files].
+    pub(crate) fn included_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<String> {
+        let path = self.resolve(path);
+        self.included_files.insert(path.clone());
+        self.presentation(&path)
+    }
+
+    /// Get the files read via [Resources::included_file] since the last call to
+    /// [Resources::clear_included_files].
+    pub(crate) fn included_files(&self) -> impl Iterator<Item = &Path> {
+        self.included_files.iter().map(PathBuf::as_path)
+    }
+
+    /// Forget about the files included so far.
+    pub(crate) fn clear_included_files(&mut self) {
+        self.included_files.clear();
     }
 
     /// Get the image at the given path.
+    ///
+    /// Paths that are `http://` or `https://` URLs are downloaded, unless they were downloaded before.
     pub(crate) fn image<P: AsRef<Path>>(&mut self, path: P) -> Result<Image, LoadImageError> {
-        let path = self.base_path.join(path);
+        let url = RemoteImages::url(path.as_ref()).map(ToString::to_string);
+        let path = match &url {
+            Some(url) => self.remote_images.cache_path(url),
+            None => self.resolve(path),
+        };
         if let Some(image) = self.images.get(&path) {
             return Ok(image.clone());
         }
 
-        let contents = fs::read(&path).map_err(|e| LoadImageError::Io(path.clone(), e))?;
-        let image = Image::new(&contents)?;
+        let contents = match url {
+            Some(url) => self.remote_images.fetch(&url)?,
+            None => self.provider.read(&path).map_err(|e| LoadImageError::Io(path.clone(), e))?,
+        };
+        let image = Image::with_rasterizations(&contents, &self.rasterizations)?;
         self.images.insert(path, image.clone());
         Ok(image)
     }
 
+    /// Get the image a diagram renders into.
+    ///
+    /// Diagrams are only rendered if a diagram with the same contents wasn't rendered before. Math
+    /// is drawn using the given color, as the images are transparent and would otherwise be black.
+    pub(crate) fn diagram(
+        &mut self,
+        kind: DiagramKind,
+        contents: &str,
+        foreground: Option<Color>,
+    ) -> Result<Image, LoadImageError> {
+        let source = Diagrams::source(kind, contents, foreground);
+        let path = self.diagrams.cache_path(kind, &source);
+        if let Some(image) = self.images.get(&path) {
+            return Ok(image.clone());
+        }
+        let image = Image::new(&self.diagrams.render(kind, &source)?)?;
+        self.images.insert(path, image.clone());
+        Ok(image)
+    }
+
+    /// Get the output of running a piece of code.
+    ///
+    /// Code is only run if code in the same language with the same contents, running in the same
+    /// container or host if any, didn't run successfully before, so reloading the presentation
+    /// doesn't run every code block again. Code that does run is appended to the given transcript, if any.
+    pub(crate) fn code_output(
+        &mut self,
+        code: &Code,
+        config: &ExecutionConfig,
+        transcript: Option<&ExecutionTranscript>,
+    ) -> Result<Vec<String>, CodeExecuteError> {
+        let container = code.attributes.container.as_deref().unwrap_or_default();
+        let host = code.attributes.host.as_deref().unwrap_or_default();
+        let key = format!("{}\n{container}\n{host}\n{}", code.language.name(), code.contents);
+        if let Some(output) = self.code_outputs.get(&key) {
+            return Ok(output.clone());
+        }
+        let output = CodeExecuter::execute_sync(code, config, transcript)?;
+        self.code_outputs.insert(key, output.clone());
+        Ok(output)
+    }
+
+    /// Get the path an image that's a URL is cached at once it's downloaded.
+    pub(crate) fn remote_image_path<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        RemoteImages::url(path.as_ref()).map(|url| self.remote_images.cache_path(url))
+    }
+
     /// Get the theme at the given path.
     pub(crate) fn theme<P: AsRef<Path>>(&mut self, path: P) -> Result<PresentationTheme, LoadThemeError> {
-        let path = self.base_path.join(path);
+        let path = self.resolve(path);
         if let Some(theme) = self.themes.get(&path) {
             return Ok(theme.clone());
         }
 
-        let theme = PresentationTheme::from_path(&path)?;
+        let theme = PresentationTheme::from_contents(&self.provider.read(&path)?)?;
         self.themes.insert(path, theme.clone());
         Ok(theme)
     }
@@ -54,7 +230,276 @@ impl Resources {
     /// Clears all resources.
     pub(crate) fn clear(&mut self) {
         self.images.clear();
+        self.rasterizations.clear();
         self.themes.clear();
+        self.code_outputs.clear();
+    }
+}
+
+/// Downloads images from URLs.
+///
+/// Images are kept in a cache directory so they're only downloaded again if they changed since,
+/// which is decided by the modification time the server reports. Downloads use `curl` so there's
+/// no need to bundle an HTTP client and a TLS stack.
+struct RemoteImages {
+    cache_directory: PathBuf,
+}
+
+impl Default for RemoteImages {
+    fn default() -> Self {
+        Self { cache_directory: cache_directory("images") }
+    }
+}
+
+impl RemoteImages {
+    /// Get the URL in a path, if it is one.
+    fn url(path: &Path) -> Option<&str> {
+        let path = path.to_str()?;
+        (path.starts_with("http://") || path.starts_with("https://")).then_some(path)
+    }
+
+    /// Get the path an image is cached at.
+    fn cache_path(&self, url: &str) -> PathBuf {
+        self.cache_directory.join(format!("{:016x}", stable_hash(url)))
+    }
+
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchImageError> {
+        let path = self.cache_path(url);
+        let error = |reason: String| FetchImageError { url: url.to_string(), reason };
+        fs::create_dir_all(&self.cache_directory).map_err(|e| error(format!("creating cache directory: {e}")))?;
+        let cached = path.exists();
+        // Download into a temporary file so an interrupted download is never mistaken for a cached image.
+        let download_path = path.with_extension("download");
+        let mut command = Command::new("curl");
+        command
+            .args(["--fail", "--silent", "--show-error", "--location", "--proto", "=http,https", "--max-time"])
+            .arg(FETCH_TIMEOUT.as_secs().to_string())
+            // The cached image gets the server's modification time so it can be compared against it later.
+            .arg("--remote-time");
+        if cached {
+            command.arg("--time-cond").arg(&path);
+        }
+        let output = command.arg("--output").arg(&download_path).arg(url).stdin(Stdio::null()).output();
+        // Nothing is written if the image didn't change since it was cached.
+        let downloaded = matches!(&output, Ok(output) if output.status.success())
+            && fs::metadata(&download_path).is_ok_and(|metadata| metadata.len() > 0);
+        if downloaded {
+            fs::rename(&download_path, &path).map_err(|e| error(format!("caching image: {e}")))?;
+        } else {
+            let _ = fs::remove_file(&download_path);
+            // The cached image is still good if it can't be checked, e.g. while offline.
+            if !cached {
+                let reason = match output {
+                    Ok(output) if !output.status.success() => {
+                        String::from_utf8_lossy(&output.stderr).trim().to_string()
+                    }
+                    Ok(_) => "empty response".into(),
+                    Err(e) => format!("running curl: {e}"),
+                };
+                return Err(error(reason));
+            }
+        }
+        fs::read(&path).map_err(|e| error(format!("reading cached image: {e}")))
+    }
+}
+
+/// A language diagrams can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DiagramKind {
+    /// A mermaid diagram, rendered using `mmdc`, mermaid's command line tool.
+    Mermaid,
+
+    /// A graphviz diagram written in dot, rendered using `dot`.
+    Graphviz,
+
+    /// A d2 diagram, rendered using `d2`.
+    D2,
+
+    /// A LaTeX formula, rendered using the configured command.
+    Latex,
+
+    /// A typst formula, rendered using the configured command.
+    Typst,
+}
+
+/// Renders diagrams into images using the tool for the language they're written in.
+///
+/// Rendering a diagram takes a while so every rendered diagram is kept in a cache directory, keyed
+/// by its contents, the same way remote images are.
+struct Diagrams {
+    cache_directory: PathBuf,
+    math: MathConfig,
+}
+
+impl Default for Diagrams {
+    fn default() -> Self {
+        Self { cache_directory: cache_directory("diagrams"), math: Default::default() }
+    }
+}
+
+impl Diagrams {
+    /// Get the source that's rendered for a diagram.
+    ///
+    /// Math is only the formula itself so this wraps it into a document that fits tightly around it.
+    fn source(kind: DiagramKind, contents: &str, foreground: Option<Color>) -> String {
+        let contents = contents.trim();
+        match kind {
+            DiagramKind::Mermaid | DiagramKind::Graphviz | DiagramKind::D2 => contents.into(),
+            DiagramKind::Latex => {
+                let color = foreground.map(|color| format!("\\color[HTML]{{{color}}}\n")).unwrap_or_default();
+                format!(
+                    "\\documentclass[preview,border=1pt]{{standalone}}\n\\usepackage{{amsmath,amssymb,xcolor}}\n\
+                     \\begin{{document}}\n{color}$\\displaystyle\n{contents}\n$\n\\end{{document}}\n"
+                )
+            }
+            DiagramKind::Typst => {
+                let color = foreground.map(|color| format!("#set text(fill: rgb(\"#{color}\"))\n"));
+                let color = color.unwrap_or_default();
+                format!("#set page(width: auto, height: auto, margin: 2pt, fill: none)\n{color}$ {contents} $\n")
+            }
+        }
+    }
+
+    /// Get the producer that renders the given kind of diagram.
+    fn producer(&self, kind: DiagramKind) -> ImageProducer<'_> {
+        match kind {
+            DiagramKind::Mermaid => {
+                ImageProducer { name: "mermaid", input_file: "diagram.mmd", command: MERMAID_COMMAND }
+            }
+            DiagramKind::Graphviz => {
+                ImageProducer { name: "dot", input_file: "diagram.dot", command: GRAPHVIZ_COMMAND }
+            }
+            DiagramKind::D2 => ImageProducer { name: "d2", input_file: "diagram.d2", command: D2_COMMAND },
+            DiagramKind::Latex => ImageProducer {
+                name: "latex",
+                input_file: "formula.tex",
+                command: self.math.latex.as_deref().unwrap_or(DEFAULT_LATEX_COMMAND),
+            },
+            DiagramKind::Typst => ImageProducer {
+                name: "typst",
+                input_file: "formula.typ",
+                command: self.math.typst.as_deref().unwrap_or(DEFAULT_TYPST_COMMAND),
+            },
+        }
+    }
+
+    /// Get the path a diagram's image is cached at.
+    fn cache_path(&self, kind: DiagramKind, source: &str) -> PathBuf {
+        let producer = self.producer(kind);
+        // Changing the command used to render a diagram can change what it looks like.
+        let key = format!("{}\n{source}", producer.command);
+        self.cache_directory.join(format!("{}-{:016x}.png", producer.name, stable_hash(&key)))
+    }
+
+    fn render(&self, kind: DiagramKind, source: &str) -> Result<Vec<u8>, RenderDiagramError> {
+        let path = self.cache_path(kind, source);
+        if let Ok(image) = fs::read(&path) {
+            return Ok(image);
+        }
+        let error = |reason: String| RenderDiagramError { reason };
+        fs::create_dir_all(&self.cache_directory).map_err(|e| error(format!("creating cache directory: {e}")))?;
+        let image = self.producer(kind).produce(source)?;
+        // Write into a temporary file so an interrupted write is never mistaken for a cached diagram.
+        let download_path = path.with_extension("download");
+        fs::write(&download_path, &image).map_err(|e| error(format!("caching diagram: {e}")))?;
+        fs::rename(&download_path, &path).map_err(|e| error(format!("caching diagram: {e}")))?;
+        Ok(image)
+    }
+}
+
+/// A tool that turns a diagram's source into an image.
+///
+/// Producers run a shell command in a directory that contains the source in `input_file`, which
+/// has to write the image next to it in a file with the same name and a `png` extension. Supporting
+/// a new tool only takes describing how it's run.
+struct ImageProducer<'a> {
+    /// The name images rendered by this producer are cached under.
+    name: &'static str,
+
+    /// The file the source is written into.
+    input_file: &'static str,
+
+    /// The command that renders the image.
+    command: &'a str,
+}
+
+impl ImageProducer<'_> {
+    fn produce(&self, source: &str) -> Result<Vec<u8>, RenderDiagramError> {
+        let error = |reason: String| RenderDiagramError { reason };
+        // Tools like to leave auxiliary files around so run them in a directory of their own.
+        let directory = tempfile::tempdir().map_err(|e| error(format!("creating directory: {e}")))?;
+        let input_path = directory.path().join(self.input_file);
+        fs::write(&input_path, source).map_err(|e| error(format!("writing diagram: {e}")))?;
+        CodeExecuter::run_command(self.command, directory.path(), None).map_err(|e| error(e.to_string()))?;
+        fs::read(input_path.with_extension("png")).map_err(|e| error(format!("reading diagram: {e}")))
+    }
+}
+
+/// Get the directory the given kind of resource is cached in.
+fn cache_directory(name: &str) -> PathBuf {
+    let cache_directory = match env::var_os("XDG_CACHE_HOME") {
+        Some(path) => PathBuf::from(path),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")).unwrap_or_else(env::temp_dir),
+    };
+    cache_directory.join("presenterm").join(name)
+}
+
+// Cached resources need to be found across builds, which the std hashers don't guarantee, so use FNV-1a.
+fn stable_hash(input: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in input.bytes() {
+        hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Where resources are read from.
+#[derive(Default)]
+enum ResourceProvider {
+    /// The filesystem.
+    #[default]
+    Filesystem,
+
+    /// The git object the path points to in a revision, e.g. a tag or commit.
+    GitRevision(String),
+}
+
+impl ResourceProvider {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Filesystem => fs::read(path),
+            Self::GitRevision(revision) => Self::read_git_object(revision, path),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        match self {
+            Self::Filesystem => path.exists(),
+            Self::GitRevision(revision) => Self::read_git_object(revision, path).is_ok(),
+        }
+    }
+
+    fn read_git_object(revision: &str, path: &Path) -> io::Result<Vec<u8>> {
+        let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+        // Running git in the file's directory lets `./` resolve the path relative to it, wherever the
+        // repository's root is.
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .arg("show")
+            .arg(format!("{revision}:./{}", file_name.to_string_lossy()))
+            .stdin(Stdio::null())
+            .output()?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            let error = String::from_utf8_lossy(&output.stderr);
+            Err(io::Error::new(io::ErrorKind::NotFound, format!("git revision {revision}: {}", error.trim())))
+        }
     }
 }
 
@@ -66,4 +511,176 @@ pub enum LoadImageError {
 
     #[error("processing image: {0}")]
     InvalidImage(#[from] InvalidImage),
+
+    #[error(transparent)]
+    Fetch(#[from] FetchImageError),
+
+    #[error(transparent)]
+    RenderDiagram(#[from] RenderDiagramError),
+}
+
+/// An error downloading an image.
+#[derive(thiserror::Error, Debug)]
+#[error("{url}: {reason}")]
+pub struct FetchImageError {
+    url: String,
+    reason: String,
+}
+
+/// An error rendering a diagram.
+#[derive(thiserror::Error, Debug)]
+#[error("{reason}")]
+pub struct RenderDiagramError {
+    reason: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+    use tempfile::TempDir;
+
+    fn git(directory: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .arg("-C")
+            .arg(directory)
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .expect("failed to run git");
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[rstest]
+    #[case::relative(None, "img.png", "/talks/img.png")]
+    #[case::absolute(None, "/tmp/img.png", "/tmp/img.png")]
+    #[case::root(Some("assets"), "img.png", "/talks/assets/img.png")]
+    #[case::parent_root(Some("../shared"), "img.png", "/talks/../shared/img.png")]
+    #[case::absolute_with_root(Some("assets"), "/tmp/img.png", "/tmp/img.png")]
+    fn resolve(#[case] root: Option<&str>, #[case] path: &str, #[case] expected: &str) {
+        let mut resources = Resources::new("/talks");
+        resources.set_root(root.map(PathBuf::from));
+        assert_eq!(resources.resolve(path), Path::new(expected));
+    }
+
+    fn remote_resources(directory: &TempDir) -> Resources {
+        let mut resources = Resources::new("/talks");
+        resources.remote_images = RemoteImages { cache_directory: directory.path().join("images") };
+        resources.diagrams = Diagrams { cache_directory: directory.path().join("diagrams"), math: Default::default() };
+        resources
+    }
+
+    fn write_png(path: &Path) {
+        let mut contents = io::Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(2, 1).write_to(&mut contents, image::ImageOutputFormat::Png).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents.into_inner()).unwrap();
+    }
+
+    #[test]
+    fn cached_remote_image() {
+        let directory = TempDir::new().expect("failed to create directory");
+        let mut resources = remote_resources(&directory);
+        let url = "http://127.0.0.1:1/diagram.png";
+        let path = resources.remote_image_path(url).expect("not a url");
+        assert!(path.starts_with(directory.path()));
+        assert_eq!(resources.remote_image_path("diagram.png"), None);
+
+        // The cached image is used if it can't be checked for changes.
+        write_png(&path);
+        assert!(resources.image(url).is_ok());
+    }
+
+    #[test]
+    fn changed_remote_image() {
+        let directory = TempDir::new().expect("failed to create directory");
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let url = format!("http://{}/diagram.png", listener.local_addr().unwrap());
+        let image_path = directory.path().join("new.png");
+        write_png(&image_path);
+        let body = fs::read(&image_path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("failed to accept");
+            let mut request = [0; 4096];
+            let length = stream.read(&mut request).expect("failed to read");
+            let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+            stream.write_all(header.as_bytes()).and_then(|_| stream.write_all(&body)).expect("failed to write");
+            String::from_utf8_lossy(&request[..length]).to_lowercase()
+        });
+
+        let mut resources = remote_resources(&directory);
+        let path = resources.remote_image_path(&url).expect("not a url");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "stale").unwrap();
+        assert!(resources.image(&url).is_ok());
+        assert!(server.join().unwrap().contains("if-modified-since"));
+        assert_eq!(fs::read(&path).unwrap(), fs::read(&image_path).unwrap());
+    }
+
+    #[test]
+    fn cached_diagram() {
+        let directory = TempDir::new().expect("failed to create directory");
+        let mut resources = remote_resources(&directory);
+        let diagram = "graph LR\n  a --> b";
+        let path = resources.diagrams.cache_path(DiagramKind::Mermaid, diagram);
+        assert!(path.starts_with(directory.path()));
+        assert_ne!(path, resources.diagrams.cache_path(DiagramKind::Mermaid, "graph LR\n  a --> c"));
+        assert_ne!(path, resources.diagrams.cache_path(DiagramKind::Graphviz, diagram));
+        assert_ne!(path, resources.diagrams.cache_path(DiagramKind::D2, diagram));
+
+        // Diagrams are only rendered if they're not in the cache.
+        write_png(&path);
+        assert!(resources.diagram(DiagramKind::Mermaid, diagram, None).is_ok());
+    }
+
+    #[test]
+    fn math_command() {
+        let directory = TempDir::new().expect("failed to create directory");
+        let image_path = directory.path().join("formula.png");
+        write_png(&image_path);
+        let command = format!("grep -q 'x^2' formula.typ && cp {} formula.png", image_path.display());
+        let config = MathConfig { typst: Some(command), ..Default::default() };
+        let mut resources = remote_resources(&directory).with_math_config(config);
+        assert!(resources.diagram(DiagramKind::Typst, "x^2", None).is_ok());
+
+        let source = Diagrams::source(DiagramKind::Typst, "x^2", None);
+        assert!(resources.diagrams.cache_path(DiagramKind::Typst, &source).exists());
+        let result = resources.diagram(DiagramKind::Typst, "y^2", None);
+        assert!(matches!(result, Err(LoadImageError::RenderDiagram(_))), "{result:?}");
+    }
+
+    #[test]
+    fn failed_download() {
+        let directory = TempDir::new().expect("failed to create directory");
+        let mut resources = remote_resources(&directory);
+        let url = "http://127.0.0.1:1/diagram.png";
+        let result = resources.image(url);
+        assert!(matches!(result, Err(LoadImageError::Fetch(_))), "{result:?}");
+        assert!(!resources.remote_image_path(url).unwrap().exists());
+    }
+
+    #[test]
+    fn git_revision() {
+        let directory = TempDir::new().expect("failed to create directory");
+        let path = directory.path().join("slides").join("presentation.md");
+        fs::create_dir(path.parent().unwrap()).unwrap();
+        fs::write(&path, "# old").unwrap();
+        git(directory.path(), &["init", "-q"]);
+        git(directory.path(), &["add", "."]);
+        git(directory.path(), &["commit", "-q", "-m", "initial"]);
+        fs::write(&path, "# new").unwrap();
+
+        let resources = Resources::new(directory.path()).with_git_revision("HEAD");
+        assert_eq!(resources.presentation(&path).expect("reading failed"), "# old");
+        assert_eq!(Resources::new(directory.path()).presentation(&path).expect("reading failed"), "# new");
+
+        let missing = resources.presentation(&directory.path().join("missing.md"));
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
 }
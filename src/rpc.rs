@@ -0,0 +1,167 @@
+//! A JSON-RPC API over stdin and stdout, used to drive presentations from other programs.
+//!
+//! Every line in stdin is a JSON-RPC 2.0 request whose method is the name of a command, e.g.
+//! `jump_next_slide`, and whose params are that command's arguments, if it takes any. Requests
+//! that have an id get a response, and every presentation event is written as a notification.
+
+use crate::{
+    events::{EventListener, PresentationEvent},
+    input::source::Command,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, Write},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+use strum::VariantNames;
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Reads commands from stdin and writes events into stdout.
+pub(crate) struct StdioControl;
+
+impl StdioControl {
+    /// Start reading commands from stdin in the background.
+    ///
+    /// Responses are written into stdout as requests come in.
+    pub(crate) fn start() -> Receiver<Command> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let (command, response) = Self::handle_line(&line);
+                if let Some(response) = response {
+                    Self::write(&response);
+                }
+                if let Some(command) = command {
+                    if sender.send(command).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Parse a request, returning the command in it and the response to send, if any.
+    fn handle_line(line: &str) -> (Option<Command>, Option<Value>) {
+        #[derive(Deserialize)]
+        struct Request {
+            jsonrpc: String,
+            method: String,
+            #[serde(default)]
+            params: Option<Value>,
+            #[serde(default)]
+            id: Option<Value>,
+        }
+
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return (None, Some(Self::error(Value::Null, PARSE_ERROR, &e.to_string()))),
+        };
+        let id = request.id.clone();
+        let respond = |response: Value| id.as_ref().map(|_| response);
+        if request.jsonrpc != "2.0" {
+            let response = Self::error(id.clone().unwrap_or_default(), INVALID_REQUEST, "expected jsonrpc 2.0");
+            return (None, Some(response));
+        }
+        if !Command::VARIANTS.contains(&request.method.as_str()) {
+            let message = format!("unknown method: {}", request.method);
+            return (None, respond(Self::error(id.clone().unwrap_or_default(), METHOD_NOT_FOUND, &message)));
+        }
+        // Commands are externally tagged so the method is either the whole command or its tag.
+        let command = match request.params {
+            None | Some(Value::Null) => Value::String(request.method),
+            Some(params) => json!({ request.method: params }),
+        };
+        match serde_json::from_value::<Command>(command) {
+            Ok(command) => {
+                let response = json!({ "jsonrpc": "2.0", "id": id, "result": null });
+                (Some(command), respond(response))
+            }
+            Err(e) => (None, respond(Self::error(id.clone().unwrap_or_default(), INVALID_PARAMS, &e.to_string()))),
+        }
+    }
+
+    fn error(id: Value, code: i32, message: &str) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+    }
+
+    fn notification(event: &PresentationEvent) -> Value {
+        json!({ "jsonrpc": "2.0", "method": event.name(), "params": event })
+    }
+
+    fn write(message: &Value) {
+        let mut stdout = io::stdout().lock();
+        // There's nobody to tell if whoever's reading stdout went away.
+        let _ = writeln!(stdout, "{message}").and_then(|_| stdout.flush());
+    }
+}
+
+impl EventListener for StdioControl {
+    fn on_event(&mut self, event: &PresentationEvent) {
+        Self::write(&Self::notification(event));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::unit(r#"{"jsonrpc": "2.0", "method": "jump_next_slide"}"#, Command::JumpNextSlide)]
+    #[case::null_params(r#"{"jsonrpc": "2.0", "method": "exit", "params": null}"#, Command::Exit)]
+    #[case::params(r#"{"jsonrpc": "2.0", "method": "jump_slide", "params": 3}"#, Command::JumpSlide(3))]
+    #[case::object_params(
+        r#"{"jsonrpc": "2.0", "method": "jump_position", "params": {"slide": 1, "chunk": 2}}"#,
+        Command::JumpPosition { slide: 1, chunk: 2 }
+    )]
+    fn notification_requests(#[case] line: &str, #[case] expected: Command) {
+        let (command, response) = StdioControl::handle_line(line);
+        assert_eq!(command, Some(expected));
+        assert_eq!(response, None);
+    }
+
+    #[test]
+    fn response() {
+        let (command, response) =
+            StdioControl::handle_line(r#"{"jsonrpc": "2.0", "method": "jump_first_slide", "id": 7}"#);
+        assert_eq!(command, Some(Command::JumpFirstSlide));
+        assert_eq!(response, Some(json!({"jsonrpc": "2.0", "id": 7, "result": null})));
+    }
+
+    #[rstest]
+    #[case::invalid_json("{", PARSE_ERROR)]
+    #[case::wrong_version(r#"{"jsonrpc": "1.0", "method": "exit", "id": 1}"#, INVALID_REQUEST)]
+    #[case::unknown_method(r#"{"jsonrpc": "2.0", "method": "potato", "id": 1}"#, METHOD_NOT_FOUND)]
+    #[case::invalid_params(r#"{"jsonrpc": "2.0", "method": "jump_slide", "params": "a", "id": 1}"#, INVALID_PARAMS)]
+    #[case::missing_params(r#"{"jsonrpc": "2.0", "method": "jump_slide", "id": 1}"#, INVALID_PARAMS)]
+    #[case::unexpected_params(r#"{"jsonrpc": "2.0", "method": "exit", "params": 1, "id": 1}"#, INVALID_PARAMS)]
+    fn errors(#[case] line: &str, #[case] code: i32) {
+        let (command, response) = StdioControl::handle_line(line);
+        assert_eq!(command, None);
+        assert_eq!(response.expect("no response")["error"]["code"], code);
+    }
+
+    #[test]
+    fn event_notification() {
+        let event = PresentationEvent::SlideChanged { current_slide: 2, total_slides: 5 };
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "method": "slide_changed",
+            "params": {"event": "slide_changed", "current_slide": 2, "total_slides": 5}
+        });
+        assert_eq!(StdioControl::notification(&event), expected);
+    }
+}
@@ -0,0 +1,276 @@
+//! The JSON schemas of the files users write by hand, like themes.
+//!
+//! These schemas help editors autocomplete and validate those files, and let presentations be
+//! validated without building them. They're derived from the same types those files are loaded
+//! into, and tests make sure they accept everything those types produce.
+
+use schemars::{generate::SchemaSettings, JsonSchema};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+/// The pattern for a color in `rrggbb` format.
+pub(crate) const HEX_COLOR_PATTERN: &str = "^[0-9a-fA-F]{6}$";
+
+/// Get the JSON schema for a type, as the top level one in a document.
+pub(crate) fn schema_for<T: JsonSchema>(title: &str) -> Value {
+    let mut schema = generator().into_root_schema_for::<T>().to_value();
+    schema["title"] = json!(title);
+    schema
+}
+
+// Subschemas are inlined rather than referenced so [validate] doesn't need to resolve them.
+fn generator() -> schemars::SchemaGenerator {
+    let mut settings = SchemaSettings::draft2020_12();
+    settings.inline_subschemas = true;
+    settings.into_generator()
+}
+
+/// A value that doesn't match a schema.
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("{path}: {message}")]
+pub(crate) struct SchemaViolation {
+    /// The path to the value, e.g. `$.theme.name`.
+    pub(crate) path: String,
+
+    /// What's wrong with it.
+    pub(crate) message: String,
+}
+
+/// Validate a value against a schema generated by this module.
+///
+/// This only understands the keywords schemars emits for the types in this crate.
+pub(crate) fn validate(schema: &Value, value: &Value) -> Result<(), SchemaViolation> {
+    validate_at(schema, value, "$").map(|_| ())
+}
+
+// Returns the properties in the value the schema evaluated, which `unevaluatedProperties` in the
+// schemas that contain it needs to know about.
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<BTreeSet<String>, SchemaViolation> {
+    let violation = |message: String| Err(SchemaViolation { path: path.into(), message });
+    let mut evaluated = BTreeSet::new();
+    if let Some(schemas) = schema.get("oneOf").and_then(Value::as_array) {
+        let results: Vec<_> = schemas.iter().map(|schema| validate_at(schema, value, path)).collect();
+        let matches = results.iter().filter(|result| result.is_ok()).count();
+        if matches > 1 {
+            return violation(format!("{value} is ambiguous"));
+        }
+        evaluated.extend(pick_alternative(schema, results, value, path)?);
+    }
+    if let Some(schemas) = schema.get("anyOf").and_then(Value::as_array) {
+        let results: Vec<_> = schemas.iter().map(|schema| validate_at(schema, value, path)).collect();
+        evaluated.extend(pick_alternative(schema, results, value, path)?);
+    }
+    let types: Vec<_> = match schema.get("type") {
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        Some(Value::String(name)) => vec![name.as_str()],
+        _ => vec![],
+    };
+    let mut type_matches = types.is_empty();
+    for name in types {
+        type_matches |= match name {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "null" => value.is_null(),
+            other => return violation(format!("unknown type {other}")),
+        };
+    }
+    if !type_matches {
+        return violation(format!("expected {}, found {value}", schema["type"]));
+    }
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            return violation(format!("expected {expected}, found {value}"));
+        }
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            return violation(format!("{value} is not one of {}", schema["enum"]));
+        }
+    }
+    if let Some(number) = value.as_i64() {
+        let minimum = schema.get("minimum").and_then(Value::as_i64).unwrap_or(i64::MIN);
+        if number < minimum {
+            return violation(format!("{number} is smaller than {minimum}"));
+        }
+    }
+    if let Some(number) = value.as_u64() {
+        let maximum = schema.get("maximum").and_then(Value::as_u64).unwrap_or(u64::MAX);
+        if number > maximum {
+            return violation(format!("{number} is larger than {maximum}"));
+        }
+    }
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        let min_length = schema.get("minLength").and_then(Value::as_u64).unwrap_or(0);
+        let max_length = schema.get("maxLength").and_then(Value::as_u64).unwrap_or(u64::MAX);
+        if length < min_length || length > max_length {
+            return violation(format!("{value} has an invalid length"));
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            assert_eq!(pattern, HEX_COLOR_PATTERN, "unsupported pattern");
+            if text.len() != 6 || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+                return violation(format!("{value} is not a color"));
+            }
+        }
+    }
+    if let (Some(values), Some(items)) = (value.as_array(), schema.get("items")) {
+        for (index, value) in values.iter().enumerate() {
+            validate_at(items, value, &format!("{path}[{index}]"))?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, value) in object {
+            let property_path = format!("{path}.{name}");
+            match (properties.and_then(|properties| properties.get(name)), &schema["additionalProperties"]) {
+                (Some(schema), _) => validate_at(schema, value, &property_path)?,
+                (None, Value::Bool(false)) => return violation(format!("unknown property {name}")),
+                (None, additional @ Value::Object(_)) => validate_at(additional, value, &property_path)?,
+                (None, _) => continue,
+            };
+            evaluated.insert(name.clone());
+        }
+        if schema.get("unevaluatedProperties") == Some(&Value::Bool(false)) {
+            if let Some(name) = object.keys().find(|name| !evaluated.contains(*name)) {
+                return violation(format!("unknown property {name}"));
+            }
+        }
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+            let name = name.as_str().expect("non string property name");
+            if !object.contains_key(name) {
+                return violation(format!("missing property {name}"));
+            }
+        }
+    }
+    Ok(evaluated)
+}
+
+// Get what the alternatives that matched evaluated, or the violation that most likely explains why
+// none of them did.
+fn pick_alternative(
+    schema: &Value,
+    results: Vec<Result<BTreeSet<String>, SchemaViolation>>,
+    value: &Value,
+    path: &str,
+) -> Result<BTreeSet<String>, SchemaViolation> {
+    let (matches, violations): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    if !matches.is_empty() {
+        return Ok(matches.into_iter().flat_map(Result::unwrap_or_default).collect());
+    }
+    // If it only failed deep inside one of the alternatives, that's likely the intended one.
+    let mut deeper = violations.into_iter().filter_map(Result::err).filter(|violation| violation.path != path);
+    match (deeper.next(), deeper.next()) {
+        (Some(violation), None) => Err(violation),
+        _ => Err(SchemaViolation {
+            path: path.into(),
+            message: format!("invalid value {value}, expected: {}", describe(schema)),
+        }),
+    }
+}
+
+fn describe(schema: &Value) -> &str {
+    schema.get("description").and_then(Value::as_str).unwrap_or("value").trim_end_matches('.')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::style::Color;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    #[schemars(deny_unknown_fields)]
+    struct Test {
+        name: String,
+        size: Option<u8>,
+        letter: Option<char>,
+        kind: Option<Kind>,
+        tags: Option<Vec<String>>,
+        color: Option<Color>,
+        sizes: Option<BTreeMap<String, u8>>,
+        margin: Option<Margin>,
+        #[serde(flatten)]
+        mode: Option<Mode>,
+        #[serde(default)]
+        enabled: bool,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum Kind {
+        A,
+        B,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum Margin {
+        Fixed(u8),
+        Percent(u8),
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    #[serde(tag = "mode", rename_all = "snake_case")]
+    enum Mode {
+        Fast { speed: u8 },
+        Slow,
+    }
+
+    fn schema() -> Value {
+        schema_for::<Test>("Test")
+    }
+
+    #[rstest]
+    #[case::minimal(json!({"name": "potato"}))]
+    #[case::nulls(json!({"name": "potato", "size": null, "margin": null}))]
+    #[case::everything(json!({
+        "name": "potato",
+        "size": 42,
+        "letter": "x",
+        "kind": "b",
+        "color": "ff00aa",
+        "margin": {"percent": 50},
+        "tags": ["a", "b"],
+        "sizes": {"small": 1, "large": 9},
+        "mode": "fast",
+        "speed": 3,
+        "enabled": true
+    }))]
+    fn valid(#[case] value: Value) {
+        assert_eq!(validate(&schema(), &value), Ok(()));
+    }
+
+    #[test]
+    fn violation_path() {
+        let value = json!({"name": "potato", "margin": {"fixed": 500}});
+        let violation = validate(&schema(), &value).expect_err("no violation");
+        assert_eq!(violation.path, "$.margin.fixed");
+    }
+
+    #[rstest]
+    #[case::missing_required(json!({}))]
+    #[case::unknown_property(json!({"name": "potato", "nope": 1}))]
+    #[case::wrong_type(json!({"name": 1}))]
+    #[case::too_large(json!({"name": "potato", "size": 256}))]
+    #[case::long_character(json!({"name": "potato", "letter": "xy"}))]
+    #[case::invalid_enum(json!({"name": "potato", "kind": "c"}))]
+    #[case::invalid_color(json!({"name": "potato", "color": "red"}))]
+    #[case::invalid_item(json!({"name": "potato", "tags": ["a", 1]}))]
+    #[case::invalid_map_value(json!({"name": "potato", "sizes": {"small": 256}}))]
+    #[case::negative(json!({"name": "potato", "size": -1}))]
+    #[case::no_alternative(json!({"name": "potato", "margin": {"fixed": 1, "percent": 1}}))]
+    #[case::flattened_without_tag(json!({"name": "potato", "speed": 1}))]
+    #[case::flattened_wrong_variant(json!({"name": "potato", "mode": "slow", "speed": 1}))]
+    fn invalid(#[case] value: Value) {
+        assert!(validate(&schema(), &value).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+//! Text snapshots of how every slide in a presentation looks.
+//!
+//! Snapshots are stored as one text file per slide, along with one that describes the colors and
+//! styles used in every row, and are meant to be checked in alongside the presentation, so that
+//! changes to it, or to the theme it uses, can be compared against how it looked before to find out
+//! which slides they affect.
+
+use crate::{
+    builder::{BuildError, CommandOutputPolicy, PresentationBuilder, PresentationBuilderOptions},
+    config::ExecutionConfig,
+    markdown::parse::ParseError,
+    options::ExternalOptions,
+    render::{
+        draw::RenderError,
+        raster::{SlideRasterizer, StyledRow},
+    },
+    CodeHighlighter, MarkdownParser, PresentationTheme, Resources,
+};
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The dimensions slides are rendered in if the presentation doesn't have an export profile.
+const DEFAULT_DIMENSIONS: (u16, u16) = (100, 30);
+
+/// The prefix and extension of the file every slide's snapshot is written into, e.g. `slide-01.txt`.
+const FILE_PREFIX: &str = "slide-";
+const FILE_EXTENSION: &str = "txt";
+
+/// The extension of the file the styles in every slide's snapshot are written into, e.g. `slide-01.styles`.
+const STYLES_EXTENSION: &str = "styles";
+
+/// Takes snapshots of presentations and compares them against previous ones.
+pub struct SnapshotRecorder<'a> {
+    parser: MarkdownParser<'a>,
+    default_theme: &'a PresentationTheme,
+    default_highlighter: CodeHighlighter,
+    resources: Resources,
+    options: ExternalOptions,
+    command_output: CommandOutputPolicy,
+    execution: ExecutionConfig,
+}
+
+impl<'a> SnapshotRecorder<'a> {
+    /// Construct a new recorder.
+    pub fn new(
+        parser: MarkdownParser<'a>,
+        default_theme: &'a PresentationTheme,
+        default_highlighter: CodeHighlighter,
+        resources: Resources,
+    ) -> Self {
+        Self {
+            parser,
+            default_theme,
+            default_highlighter,
+            resources,
+            options: Default::default(),
+            command_output: Default::default(),
+            execution: Default::default(),
+        }
+    }
+
+    /// Set the presentation options given via command line parameters and the config file.
+    pub fn with_options(mut self, options: ExternalOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set whether to run the commands in `command_output` comments.
+    pub fn with_command_output(mut self, allowed: bool) -> Self {
+        self.command_output = CommandOutputPolicy::from_allowed(allowed);
+        self
+    }
+
+    /// Set the configuration used to run code blocks while taking snapshots, like the ones that are
+    /// replaced with their output.
+    pub fn with_execution(mut self, config: ExecutionConfig) -> Self {
+        self.execution = config;
+        self
+    }
+
+    /// Take a snapshot of every slide in the given presentation and write them into the given directory.
+    ///
+    /// Any snapshots already in the directory are replaced.
+    pub fn update(&mut self, presentation_path: &Path, directory: &Path) -> Result<(), SnapshotError> {
+        let snapshots = self.take(presentation_path)?;
+        write_snapshots(directory, &snapshots).map_err(SnapshotError::WriteSnapshots)
+    }
+
+    /// Take a snapshot of every slide in the given presentation and compare them against the ones in
+    /// the given directory.
+    pub fn compare(
+        &mut self,
+        presentation_path: &Path,
+        directory: &Path,
+    ) -> Result<Vec<SnapshotChange>, SnapshotError> {
+        if !directory.is_dir() {
+            return Err(SnapshotError::NoSnapshots(directory.into()));
+        }
+        let previous = read_snapshots(directory).map_err(SnapshotError::ReadSnapshots)?;
+        let current = self.take(presentation_path)?;
+        Ok(diff_snapshots(&previous, &current))
+    }
+
+    fn take(&mut self, presentation_path: &Path) -> Result<Vec<SlideSnapshot>, SnapshotError> {
+        let content = self.resources.presentation(presentation_path).map_err(SnapshotError::ReadPresentation)?;
+        self.take_contents(&content)
+    }
+
+    fn take_contents(&mut self, content: &str) -> Result<Vec<SlideSnapshot>, SnapshotError> {
+        let elements = self.parser.parse(content)?;
+        let options = PresentationBuilderOptions {
+            allow_mutations: false,
+            use_export_profile: true,
+            presentation_options: self.options.clone(),
+            command_output: self.command_output,
+            execution: self.execution.clone(),
+            ..Default::default()
+        };
+        let mut presentation = PresentationBuilder::new(
+            self.default_highlighter.clone(),
+            self.default_theme,
+            &mut self.resources,
+            options,
+        )
+        .build(elements)?;
+        let (columns, rows) = match presentation.export_profile() {
+            Some(profile) => (profile.columns, profile.rows),
+            None => DEFAULT_DIMENSIONS,
+        };
+        let rasterizer = SlideRasterizer::new(columns, rows);
+        let mut snapshots = Vec::new();
+        loop {
+            // Slides are captured once all of their steps are shown.
+            if presentation.is_current_slide_last_step() {
+                let rows = rasterizer.render_styled_text(presentation.current_slide().iter_operations())?;
+                snapshots.push(SlideSnapshot { rows });
+            }
+            if !presentation.jump_next_slide() {
+                break;
+            }
+        }
+        Ok(snapshots)
+    }
+}
+
+/// The text a slide is rendered into, and the styles used in it, one row at a time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SlideSnapshot {
+    rows: Vec<StyledRow>,
+}
+
+fn snapshot_path(directory: &Path, slide: usize, extension: &str) -> PathBuf {
+    directory.join(format!("{FILE_PREFIX}{slide:02}.{extension}"))
+}
+
+// Get the slide number a snapshot file, or the file with its styles, belongs to, if it's one.
+fn snapshot_slide(path: &Path, extension: &str) -> Option<usize> {
+    if path.extension()? != extension {
+        return None;
+    }
+    path.file_stem()?.to_str()?.strip_prefix(FILE_PREFIX)?.parse().ok()
+}
+
+fn write_lines<'a>(path: &Path, lines: impl Iterator<Item = &'a str>) -> io::Result<()> {
+    let mut contents = lines.collect::<Vec<_>>().join("\n");
+    contents.push('\n');
+    fs::write(path, contents)
+}
+
+fn write_snapshots(directory: &Path, snapshots: &[SlideSnapshot]) -> io::Result<()> {
+    fs::create_dir_all(directory)?;
+    // Remove the snapshots for slides that no longer exist.
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if [FILE_EXTENSION, STYLES_EXTENSION].iter().any(|extension| snapshot_slide(&path, extension).is_some()) {
+            fs::remove_file(path)?;
+        }
+    }
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        let rows = &snapshot.rows;
+        write_lines(&snapshot_path(directory, index + 1, FILE_EXTENSION), rows.iter().map(|row| row.text.as_str()))?;
+        let styles = rows.iter().map(|row| row.styles.as_str());
+        write_lines(&snapshot_path(directory, index + 1, STYLES_EXTENSION), styles)?;
+    }
+    Ok(())
+}
+
+fn read_snapshots(directory: &Path) -> io::Result<Vec<SlideSnapshot>> {
+    let mut slides = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if let Some(slide) = snapshot_slide(&path, FILE_EXTENSION) {
+            slides.push(slide);
+        }
+    }
+    slides.sort();
+    let mut snapshots = Vec::new();
+    for slide in slides {
+        let text = fs::read_to_string(snapshot_path(directory, slide, FILE_EXTENSION))?;
+        // Snapshots taken before styles were recorded don't have them, which shows up as every styled row changing.
+        let styles = match fs::read_to_string(snapshot_path(directory, slide, STYLES_EXTENSION)) {
+            Ok(styles) => styles,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut styles = styles.lines();
+        let rows = text
+            .lines()
+            .map(|text| StyledRow { text: text.into(), styles: styles.next().unwrap_or_default().into() })
+            .collect();
+        snapshots.push(SlideSnapshot { rows });
+    }
+    Ok(snapshots)
+}
+
+fn diff_snapshots(previous: &[SlideSnapshot], current: &[SlideSnapshot]) -> Vec<SnapshotChange> {
+    let mut changes = Vec::new();
+    for slide in 0..previous.len().max(current.len()) {
+        let kind = match (previous.get(slide), current.get(slide)) {
+            (Some(previous), Some(current)) => {
+                let rows = previous.rows.len().max(current.rows.len());
+                let line = |snapshot: &SlideSnapshot, row| {
+                    let empty = || StyledRow { text: String::new(), styles: String::new() };
+                    snapshot.rows.get(row).cloned().unwrap_or_else(empty)
+                };
+                let lines: Vec<_> = (0..rows)
+                    .map(|row| LineChange { row: row + 1, before: line(previous, row), after: line(current, row) })
+                    .filter(|change| change.before != change.after)
+                    .collect();
+                if lines.is_empty() {
+                    continue;
+                }
+                SnapshotChangeKind::Modified(lines)
+            }
+            (None, Some(_)) => SnapshotChangeKind::Added,
+            (Some(_), None) => SnapshotChangeKind::Removed,
+            (None, None) => continue,
+        };
+        changes.push(SnapshotChange { slide: slide + 1, kind });
+    }
+    changes
+}
+
+/// A slide that looks different than it did in the last snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotChange {
+    /// The slide's number, starting at 1.
+    slide: usize,
+    kind: SnapshotChangeKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SnapshotChangeKind {
+    Added,
+    Removed,
+    Modified(Vec<LineChange>),
+}
+
+/// A row in a slide that changed, either its text or the styles used in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LineChange {
+    /// The row's number, starting at 1.
+    row: usize,
+    before: StyledRow,
+    after: StyledRow,
+}
+
+impl Display for SnapshotChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let slide = self.slide;
+        match &self.kind {
+            SnapshotChangeKind::Added => write!(f, "slide {slide}: new slide"),
+            SnapshotChangeKind::Removed => write!(f, "slide {slide}: removed"),
+            SnapshotChangeKind::Modified(lines) => {
+                write!(f, "slide {slide}: {} row(s) changed", lines.len())?;
+                for LineChange { row, before, after } in lines {
+                    if before.text != after.text {
+                        write!(f, "\n  {row:>3} - {}\n  {row:>3} + {}", before.text, after.text)?;
+                    }
+                    if before.styles != after.styles {
+                        write!(f, "\n  {row:>3} - styles: {}\n  {row:>3} + styles: {}", before.styles, after.styles)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An error taking or comparing snapshots.
+#[derive(thiserror::Error, Debug)]
+pub enum SnapshotError {
+    #[error("failed to read presentation: {0}")]
+    ReadPresentation(io::Error),
+
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    #[error(transparent)]
+    Build(#[from] BuildError),
+
+    #[error("failed to render slide: {0}")]
+    RenderSlide(#[from] RenderError),
+
+    #[error("no snapshots found in {0}, take them using --update first")]
+    NoSnapshots(PathBuf),
+
+    #[error("failed to read snapshots: {0}")]
+    ReadSnapshots(io::Error),
+
+    #[error("failed to write snapshots: {0}")]
+    WriteSnapshots(io::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use comrak::Arena;
+
+    fn take(content: &str) -> Vec<SlideSnapshot> {
+        let arena = Arena::new();
+        let parser = MarkdownParser::new(&arena);
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples");
+        let mut recorder = SnapshotRecorder::new(parser, &theme, highlighter, resources);
+        recorder.take_contents(content).expect("snapshot failed")
+    }
+
+    fn row(text: &str, styles: &str) -> StyledRow {
+        StyledRow { text: text.into(), styles: styles.into() }
+    }
+
+    fn snapshot(lines: &[&str]) -> SlideSnapshot {
+        SlideSnapshot { rows: lines.iter().map(|line| row(line, "")).collect() }
+    }
+
+    #[test]
+    fn one_per_slide() {
+        let snapshots = take("hello\n\n<!-- pause -->\n\nworld\n\n<!-- end_slide -->\n\nbye");
+        assert_eq!(snapshots.len(), 2);
+        let text = |snapshot: &SlideSnapshot| snapshot.rows.iter().map(|row| row.text.clone()).collect::<String>();
+        // Slides are captured with every step shown.
+        assert!(text(&snapshots[0]).contains("hello"));
+        assert!(text(&snapshots[0]).contains("world"));
+        assert!(text(&snapshots[1]).contains("bye"));
+    }
+
+    #[test]
+    fn styles() {
+        let snapshots = take("# hello");
+        let row = snapshots[0].rows.iter().find(|row| row.text.contains("hello")).expect("no title");
+        assert!(row.styles.contains("bold"), "{row:?}");
+    }
+
+    #[test]
+    fn write_and_read() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let snapshots = vec![snapshot(&["a", "", "b"]), SlideSnapshot { rows: vec![row("c", "1-1 bold")] }];
+        write_snapshots(directory.path(), &snapshots).expect("failed to write");
+        assert!(directory.path().join("slide-02.txt").exists());
+        assert!(directory.path().join("slide-02.styles").exists());
+        assert_eq!(read_snapshots(directory.path()).expect("failed to read"), snapshots);
+
+        // Snapshots for slides that are gone are removed.
+        write_snapshots(directory.path(), &snapshots[..1]).expect("failed to write");
+        assert!(!directory.path().join("slide-02.txt").exists());
+        assert!(!directory.path().join("slide-02.styles").exists());
+        assert_eq!(read_snapshots(directory.path()).expect("failed to read"), &snapshots[..1]);
+    }
+
+    #[test]
+    fn diff() {
+        let previous = vec![snapshot(&["a", "b"]), snapshot(&["c"]), snapshot(&["d"])];
+        let current = vec![snapshot(&["a", "b"]), snapshot(&["c", "e"])];
+        let changes = diff_snapshots(&previous, &current);
+        let change = LineChange { row: 2, before: row("", ""), after: row("e", "") };
+        let expected = vec![
+            SnapshotChange { slide: 2, kind: SnapshotChangeKind::Modified(vec![change]) },
+            SnapshotChange { slide: 3, kind: SnapshotChangeKind::Removed },
+        ];
+        assert_eq!(changes, expected);
+        assert_eq!(changes[0].to_string(), "slide 2: 1 row(s) changed\n    2 - \n    2 + e");
+
+        let changes = diff_snapshots(&current, &previous);
+        assert_eq!(changes[1], SnapshotChange { slide: 3, kind: SnapshotChangeKind::Added });
+    }
+
+    #[test]
+    fn diff_styles() {
+        let previous = vec![SlideSnapshot { rows: vec![row("a", "1-1 fg=#ff0000")] }];
+        let current = vec![SlideSnapshot { rows: vec![row("a", "1-1 fg=#00ff00")] }];
+        let changes = diff_snapshots(&previous, &current);
+        let expected = "slide 1: 1 row(s) changed\n    1 - styles: 1-1 fg=#ff0000\n    1 + styles: 1-1 fg=#00ff00";
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), expected);
+    }
+}
@@ -0,0 +1,84 @@
+//! Sounds played when slides are shown, set via the `sound` command.
+
+use crate::{config::SoundConfig, presentation::Presentation};
+use std::{
+    path::Path,
+    process::{self, Stdio},
+    thread,
+};
+
+/// Plays the sounds in the slide or chunk that was just shown.
+///
+/// Sounds are only ever played if a player is set in the configuration, and the player runs in the
+/// background so it never blocks the presentation.
+pub(crate) struct SoundPlayer {
+    player: Option<Vec<String>>,
+    shown: Option<(usize, usize)>,
+}
+
+impl SoundPlayer {
+    /// Construct a sound player that uses the player in the given configuration, if any.
+    pub(crate) fn new(config: &SoundConfig) -> Self {
+        Self { player: config.player.clone(), shown: None }
+    }
+
+    /// Play the sounds in the current chunk, if it wasn't shown the last time this was called.
+    pub(crate) fn update(&mut self, presentation: &Presentation) {
+        let slide = presentation.current_slide_index();
+        let chunk = presentation.current_chunk();
+        if !self.enter(slide, chunk) {
+            return;
+        }
+        let Some(player) = &self.player else {
+            return;
+        };
+        let sounds = presentation.current_slide().metadata().sounds.iter().filter(|sound| sound.chunk == chunk);
+        for sound in sounds {
+            Self::play(player, &sound.path);
+        }
+    }
+
+    // Going back to an earlier chunk in the same slide doesn't show anything new so it stays quiet.
+    fn enter(&mut self, slide: usize, chunk: usize) -> bool {
+        let shown = match self.shown {
+            Some((shown_slide, shown_chunk)) => shown_slide != slide || shown_chunk < chunk,
+            None => true,
+        };
+        self.shown = Some((slide, chunk));
+        shown
+    }
+
+    fn play(player: &[String], path: &Path) {
+        let path = path.display().to_string();
+        let mut args = player.iter().map(|arg| arg.replace("{path}", &path));
+        let Some(program) = args.next() else {
+            return;
+        };
+        let child = process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = child {
+            // Reap the process in the background so we don't leave zombies around.
+            thread::spawn(move || child.wait());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn enter() {
+        let mut player = SoundPlayer::new(&Default::default());
+        assert!(player.enter(0, 0));
+        assert!(!player.enter(0, 0));
+        assert!(player.enter(0, 1));
+        assert!(!player.enter(0, 0));
+        assert!(player.enter(1, 0));
+        assert!(player.enter(0, 2));
+    }
+}
@@ -1,14 +1,17 @@
+use crate::schema::HEX_COLOR_PATTERN;
 use crossterm::style::Stylize;
 use hex::{FromHex, FromHexError};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use std::{
+    borrow::Cow,
     fmt::{self, Display},
     str::FromStr,
 };
 
 /// The style of a piece of text.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub(crate) struct TextStyle {
     flags: u8,
     pub(crate) colors: Colors,
@@ -125,6 +128,14 @@ impl Color {
     pub(crate) fn new(r: u8, g: u8, b: u8) -> Self {
         Self(crossterm::style::Color::Rgb { r, g, b })
     }
+
+    /// Get the red, green, and blue components of this color.
+    pub(crate) fn as_rgb(&self) -> [u8; 3] {
+        match self.0 {
+            crossterm::style::Color::Rgb { r, g, b } => [r, g, b],
+            _ => panic!("not rgb"),
+        }
+    }
 }
 
 impl FromStr for Color {
@@ -138,11 +149,18 @@ impl FromStr for Color {
 
 impl Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let rgb = match self.0 {
-            crossterm::style::Color::Rgb { r, g, b } => [r, g, b],
-            _ => panic!("not rgb"),
-        };
-        write!(f, "{}", hex::encode(rgb))
+        write!(f, "{}", hex::encode(self.as_rgb()))
+    }
+}
+
+// Colors are (de)serialized from strings so their schema can't be derived.
+impl JsonSchema for Color {
+    fn schema_name() -> Cow<'static, str> {
+        "Color".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({ "type": "string", "pattern": HEX_COLOR_PATTERN })
     }
 }
 
@@ -153,7 +171,8 @@ impl From<Color> for crossterm::style::Color {
 }
 
 /// Text colors.
-#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct Colors {
     /// The background color.
     pub(crate) background: Option<Color>,
@@ -1,11 +1,17 @@
-use crate::style::Colors;
+use crate::{
+    presentation::SocialNetwork,
+    schema,
+    style::{Color, Colors},
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{fs, io, path::Path};
+use std::{collections::BTreeMap, io};
 
 include!(concat!(env!("OUT_DIR"), "/themes.rs"));
 
 /// A presentation theme.
-#[derive(Default, Clone, Debug, Deserialize, Serialize)]
+#[derive(Default, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct PresentationTheme {
     /// The style for a slide's title.
     #[serde(default)]
@@ -46,6 +52,14 @@ pub struct PresentationTheme {
     /// The style of the presentation footer.
     #[serde(default)]
     pub(crate) footer: FooterStyle,
+
+    /// The layouts slides can use via the `slide_layout` command, by name.
+    #[serde(default)]
+    pub(crate) slide_layouts: BTreeMap<String, SlideLayoutStyle>,
+
+    /// The blank lines between elements.
+    #[serde(default)]
+    pub(crate) spacing: SpacingStyle,
 }
 
 impl PresentationTheme {
@@ -63,10 +77,9 @@ impl PresentationTheme {
         THEMES.keys().copied()
     }
 
-    /// Construct a presentation from a path.
-    pub(crate) fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoadThemeError> {
-        let contents = fs::read_to_string(path)?;
-        let theme = serde_yaml::from_str(&contents)?;
+    /// Construct a presentation theme from the contents of a theme file.
+    pub(crate) fn from_contents(contents: &[u8]) -> Result<Self, LoadThemeError> {
+        let theme = serde_yaml::from_slice(contents)?;
         Ok(theme)
     }
 
@@ -89,15 +102,22 @@ impl PresentationTheme {
             PresentationTitle => &self.intro_slide.title.alignment,
             PresentationSubTitle => &self.intro_slide.subtitle.alignment,
             PresentationAuthor => &self.intro_slide.author.alignment,
+            PresentationSocial => &self.intro_slide.social.alignment,
             Table => &self.table,
             BlockQuote => &self.block_quote.alignment,
         };
         alignment.clone().unwrap_or_default()
     }
+
+    /// Get the JSON schema for a presentation theme.
+    pub fn json_schema() -> serde_json::Value {
+        schema::schema_for::<Self>("presenterm theme")
+    }
 }
 
 /// The style of a slide title.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct SlideTitleStyle {
     /// The alignment.
     #[serde(flatten, default)]
@@ -121,7 +141,8 @@ pub(crate) struct SlideTitleStyle {
 }
 
 /// The style for all headings.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct HeadingStyles {
     /// H1 style.
     #[serde(default)]
@@ -149,7 +170,8 @@ pub(crate) struct HeadingStyles {
 }
 
 /// The style for a heading.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct HeadingStyle {
     /// The alignment.
     #[serde(flatten, default)]
@@ -164,10 +186,34 @@ pub(crate) struct HeadingStyle {
     /// The colors to be used.
     #[serde(default)]
     pub(crate) colors: Colors,
+
+    /// The way the heading's text is transformed before being displayed.
+    #[serde(default)]
+    pub(crate) text_transform: Option<TextTransform>,
+
+    /// The character used to draw a line under the heading, spanning its width.
+    #[serde(default)]
+    pub(crate) underline: Option<char>,
+
+    /// The character used to draw a bar on the left of the heading.
+    #[serde(default)]
+    pub(crate) border: Option<char>,
+}
+
+/// A way to transform a heading's text.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TextTransform {
+    /// Make every letter uppercase.
+    Uppercase,
+
+    /// Make the first letter in every word uppercase.
+    Title,
 }
 
 /// The style of a block quote.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct BlockQuoteStyle {
     /// The alignment.
     #[serde(flatten, default)]
@@ -185,7 +231,8 @@ pub(crate) struct BlockQuoteStyle {
 }
 
 /// The style for the presentation introduction slide.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct IntroSlideStyle {
     /// The style of the title line.
     #[serde(default)]
@@ -198,13 +245,19 @@ pub(crate) struct IntroSlideStyle {
     /// The style of the author line.
     #[serde(default)]
     pub(crate) author: AuthorStyle,
+
+    /// The style of the author's social links.
+    #[serde(default)]
+    pub(crate) social: SocialStyle,
 }
 
 /// A simple style.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct DefaultStyle {
     /// The margin on the left/right of the screen.
     #[serde(default, with = "serde_yaml::with::singleton_map")]
+    #[schemars(with = "Option<Margin>")]
     pub(crate) margin: Option<Margin>,
 
     /// The colors to be used.
@@ -213,7 +266,8 @@ pub(crate) struct DefaultStyle {
 }
 
 /// A simple style.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct BasicStyle {
     /// The alignment.
     #[serde(flatten, default)]
@@ -227,7 +281,7 @@ pub(crate) struct BasicStyle {
 /// Text alignment.
 ///
 /// This allows anchoring presentation elements to the left, center, or right of the screen.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, JsonSchema)]
 #[serde(tag = "alignment", rename_all = "snake_case")]
 pub(crate) enum Alignment {
     /// Left alignment.
@@ -262,8 +316,62 @@ impl Default for Alignment {
     }
 }
 
+/// A layout a slide can use.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct SlideLayoutStyle {
+    /// A part of the slide that's painted using a different background color.
+    #[serde(default)]
+    pub(crate) split_background: Option<SplitBackground>,
+}
+
+/// The blank lines between elements.
+///
+/// Any element that's not set here is followed by a single blank line.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct SpacingStyle {
+    /// The blank lines after a paragraph.
+    #[serde(default)]
+    pub(crate) paragraph: Option<u8>,
+
+    /// The blank lines after a heading.
+    #[serde(default)]
+    pub(crate) heading: Option<u8>,
+
+    /// The blank lines before and after a code block.
+    #[serde(default)]
+    pub(crate) code: Option<u8>,
+}
+
+/// A part of a slide that's painted using a different background color before any content.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct SplitBackground {
+    /// The part of the slide that's painted.
+    pub(crate) region: SplitRegion,
+
+    /// The percentage of the slide's width or height that's painted.
+    pub(crate) percent: u8,
+
+    /// The background color.
+    pub(crate) color: Color,
+}
+
+/// The part of a slide painted by a [SplitBackground].
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SplitRegion {
+    /// A column on the left of the slide.
+    Left,
+
+    /// A band on the top of the slide.
+    Top,
+}
+
 /// The style for the author line in the presentation intro slide.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct AuthorStyle {
     /// The alignment.
     #[serde(flatten, default)]
@@ -278,8 +386,67 @@ pub(crate) struct AuthorStyle {
     pub(crate) positioning: AuthorPositioning,
 }
 
+/// The style for the author's social links in the presentation intro slide.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub(crate) struct SocialStyle {
+    /// The alignment.
+    #[serde(flatten, default)]
+    pub(crate) alignment: Option<Alignment>,
+
+    /// The colors to be used.
+    #[serde(default)]
+    pub(crate) colors: Colors,
+
+    /// How each link is labeled.
+    #[serde(default)]
+    pub(crate) icons: SocialIcons,
+
+    /// How links are laid out.
+    #[serde(default)]
+    pub(crate) layout: SocialLayout,
+}
+
+/// How each social link is labeled.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SocialIcons {
+    /// Use nerd font icons, which requires a nerd font in the terminal.
+    NerdFont,
+
+    /// Use the name of the network.
+    #[default]
+    Text,
+}
+
+impl SocialIcons {
+    /// Get the label to put before a link to the given network.
+    pub(crate) fn label(&self, network: SocialNetwork) -> &'static str {
+        match (self, network) {
+            (Self::NerdFont, SocialNetwork::Github) => "\u{f09b} ",
+            (Self::NerdFont, SocialNetwork::Mastodon) => "\u{f0ad1} ",
+            (Self::NerdFont, SocialNetwork::Email) => "\u{f0e0} ",
+            (Self::Text, SocialNetwork::Github) => "github: ",
+            (Self::Text, SocialNetwork::Mastodon) => "mastodon: ",
+            (Self::Text, SocialNetwork::Email) => "email: ",
+        }
+    }
+}
+
+/// How social links are laid out.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SocialLayout {
+    /// All in the same line.
+    #[default]
+    Inline,
+
+    /// One per line.
+    Stacked,
+}
+
 /// The style of the footer that's shown in every slide.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "style", rename_all = "snake_case")]
 pub(crate) enum FooterStyle {
     /// Use a template to generate the footer.
@@ -324,7 +491,8 @@ impl Default for FooterStyle {
 }
 
 /// The style for a piece of code.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct CodeBlockStyle {
     /// The alignment.
     #[serde(flatten)]
@@ -334,21 +502,27 @@ pub(crate) struct CodeBlockStyle {
     #[serde(default)]
     pub(crate) padding: PaddingRect,
 
-    /// The syntect theme name to use.
+    /// The syntect theme name to use, or the path to a `.tmTheme` file.
     #[serde(default)]
     pub(crate) theme_name: Option<String>,
 }
 
 /// The style for the output of a code execution block.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct ExecutionOutputBlockStyle {
     /// The colors to be used.
     #[serde(default)]
     pub(crate) colors: Colors,
+
+    /// The most lines of output shown at once, scrolling through the rest.
+    #[serde(default)]
+    pub(crate) max_lines: Option<u16>,
 }
 
 /// The style for inline code.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct InlineCodeStyle {
     /// The colors to be used.
     #[serde(default)]
@@ -356,7 +530,8 @@ pub(crate) struct InlineCodeStyle {
 }
 
 /// Vertical/horizontal padding.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct PaddingRect {
     /// The number of columns to use as horizontal padding.
     #[serde(default)]
@@ -368,7 +543,7 @@ pub(crate) struct PaddingRect {
 }
 
 /// A margin.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum Margin {
     /// A fixed number of characters.
@@ -397,7 +572,7 @@ impl Default for Margin {
 }
 
 /// An element type.
-#[derive(Clone, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[derive(Clone, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum ElementType {
     SlideTitle,
@@ -413,12 +588,13 @@ pub(crate) enum ElementType {
     PresentationTitle,
     PresentationSubTitle,
     PresentationAuthor,
+    PresentationSocial,
     Table,
     BlockQuote,
 }
 
 /// Where to position the author's name in the intro slide.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum AuthorPositioning {
     /// Right below the title.
@@ -442,6 +618,8 @@ pub enum LoadThemeError {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::schema::validate;
+    use serde_json::Value;
 
     #[test]
     fn validate_themes() {
@@ -454,4 +632,55 @@ mod test {
             assert!(merged.is_ok(), "theme '{theme_name}' can't be merged: {}", merged.unwrap_err());
         }
     }
+
+    #[test]
+    fn schema_accepts_themes() {
+        let schema = PresentationTheme::json_schema();
+        for (theme_name, contents) in THEMES.iter() {
+            let value: Value = serde_yaml::from_slice(contents).expect("invalid yaml");
+            if let Err(e) = validate(&schema, &value) {
+                panic!("theme '{theme_name}' doesn't match schema: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn schema_rejects_typos() {
+        let schema = PresentationTheme::json_schema();
+        let value = serde_json::json!({"slide_title": {"alignment": "center", "separatr": true}});
+        let violation = validate(&schema, &value).expect_err("typo accepted");
+        assert_eq!(violation.to_string(), "$.slide_title: unknown property separatr");
+    }
+
+    #[test]
+    fn schema_accepts_serialized_themes() {
+        let schema = PresentationTheme::json_schema();
+        let mut themes = vec![PresentationTheme::default()];
+        themes.extend(THEMES.keys().map(|name| PresentationTheme::from_name(name).unwrap()));
+        let mut populated = PresentationTheme {
+            table: Some(Alignment::Center { minimum_margin: Margin::Percent(8), minimum_size: 10 }),
+            footer: FooterStyle::ProgressBar { character: Some('#'), colors: Default::default() },
+            ..Default::default()
+        };
+        populated.slide_title.padding_top = Some(1);
+        populated.code.padding.horizontal = Some(2);
+        populated.code.theme_name = Some("potato".into());
+        populated.default_style.margin = Some(Margin::Fixed(5));
+        populated.headings.h1.prefix = Some("#".into());
+        populated.headings.h2.text_transform = Some(TextTransform::Title);
+        populated.headings.h2.underline = Some('─');
+        populated.intro_slide.author.positioning = AuthorPositioning::BelowTitle;
+        populated.intro_slide.social.icons = SocialIcons::NerdFont;
+        populated.intro_slide.social.layout = SocialLayout::Stacked;
+        let split_background = SplitBackground { region: SplitRegion::Top, percent: 30, color: Color::new(1, 2, 3) };
+        populated.spacing.code = Some(2);
+        populated.slide_layouts.insert("title".into(), SlideLayoutStyle { split_background: Some(split_background) });
+        themes.push(populated);
+        for theme in themes {
+            let value = serde_json::to_value(&theme).expect("serialization failed");
+            if let Err(e) = validate(&schema, &value) {
+                panic!("serialized theme doesn't match schema: {e}");
+            }
+        }
+    }
 }
@@ -0,0 +1,143 @@
+//! Tracking of the time spent on every slide while presenting.
+
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Keeps track of how long each slide was displayed for.
+#[derive(Default)]
+pub(crate) struct SlideTimer {
+    current: Option<(usize, Instant)>,
+    slides: Vec<SlideTime>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct SlideTime {
+    elapsed: Duration,
+    visits: u32,
+}
+
+impl SlideTimer {
+    /// Record that the given slide started being displayed at `now`.
+    pub(crate) fn enter_slide(&mut self, index: usize, now: Instant) {
+        self.stop(now);
+        if self.slides.len() <= index {
+            self.slides.resize(index + 1, SlideTime::default());
+        }
+        self.slides[index].visits += 1;
+        self.current = Some((index, now));
+    }
+
+    /// Stop the clock at `now` and build a report using the given slide titles.
+    pub(crate) fn finish(&mut self, now: Instant, titles: Vec<Option<String>>) -> TimingReport {
+        self.stop(now);
+        let total_slides = titles.len().max(self.slides.len());
+        let mut titles = titles.into_iter();
+        let slides: Vec<_> = (0..total_slides)
+            .map(|index| {
+                let time = self.slides.get(index).copied().unwrap_or_default();
+                SlideTiming {
+                    slide: index + 1,
+                    title: titles.next().flatten(),
+                    seconds: time.elapsed.as_secs_f64(),
+                    visits: time.visits,
+                }
+            })
+            .collect();
+        let total_seconds = slides.iter().map(|slide| slide.seconds).sum();
+        TimingReport { total_seconds, slides }
+    }
+
+    fn stop(&mut self, now: Instant) {
+        if let Some((index, started_at)) = self.current.take() {
+            self.slides[index].elapsed += now.saturating_duration_since(started_at);
+        }
+    }
+}
+
+/// The time spent on every slide during a presentation.
+#[derive(Debug, Serialize)]
+pub(crate) struct TimingReport {
+    total_seconds: f64,
+    slides: Vec<SlideTiming>,
+}
+
+#[derive(Debug, Serialize)]
+struct SlideTiming {
+    slide: usize,
+    title: Option<String>,
+    seconds: f64,
+    visits: u32,
+}
+
+impl TimingReport {
+    /// Write this report into the given path.
+    ///
+    /// The report is written as JSON if the path has a `.json` extension and as CSV otherwise.
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        let contents = match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        fs::write(path, contents)
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serialization failed")
+    }
+
+    fn to_csv(&self) -> String {
+        let mut output = String::from("slide,title,seconds,visits\n");
+        for SlideTiming { slide, title, seconds, visits } in &self.slides {
+            let title = Self::escape_csv(title.as_deref().unwrap_or_default());
+            output.push_str(&format!("{slide},{title},{seconds:.1},{visits}\n"));
+        }
+        output
+    }
+
+    fn escape_csv(field: &str) -> String {
+        if field.contains([',', '"', '\n']) { format!("\"{}\"", field.replace('"', "\"\"")) } else { field.into() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build_report() -> TimingReport {
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        let mut timer = SlideTimer::default();
+        timer.enter_slide(0, at(0));
+        timer.enter_slide(1, at(10));
+        timer.enter_slide(0, at(15));
+        timer.enter_slide(1, at(20));
+        let titles = vec![Some("Intro, kind of".into()), None, Some("Unvisited".into())];
+        timer.finish(at(50), titles)
+    }
+
+    #[test]
+    fn accumulates_visits() {
+        let report = build_report();
+        let times: Vec<_> = report.slides.iter().map(|slide| (slide.seconds, slide.visits)).collect();
+        assert_eq!(times, &[(15.0, 2), (35.0, 2), (0.0, 0)]);
+        assert_eq!(report.total_seconds, 50.0);
+    }
+
+    #[test]
+    fn csv() {
+        let expected = "slide,title,seconds,visits\n1,\"Intro, kind of\",15.0,2\n2,,35.0,2\n3,Unvisited,0.0,0\n";
+        assert_eq!(build_report().to_csv(), expected);
+    }
+
+    #[test]
+    fn json() {
+        let report: serde_json::Value = serde_json::from_str(&build_report().to_json()).expect("invalid json");
+        assert_eq!(report["total_seconds"], 50.0);
+        assert_eq!(report["slides"][1]["title"], serde_json::Value::Null);
+        assert_eq!(report["slides"][2]["visits"], 0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Transcripts of the code executed while presenting.
+
+use crate::{
+    clock::Clock,
+    execute::ProcessStatus,
+    markdown::elements::{Code, CodeLanguage},
+};
+use std::{
+    fmt::{self, Display},
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+/// A log file every run of an executable code block is appended to.
+#[derive(Clone, Debug)]
+pub(crate) struct ExecutionTranscript {
+    path: PathBuf,
+    clock: Arc<dyn Clock>,
+}
+
+impl ExecutionTranscript {
+    /// Construct a transcript that's written into the given path, with entries timestamped using the given clock.
+    pub(crate) fn new(path: PathBuf, clock: Arc<dyn Clock>) -> Self {
+        Self { path, clock }
+    }
+
+    /// Append an entry to this transcript, stamped with the time it's appended at, creating its file if it doesn't
+    /// exist.
+    pub(crate) fn record(&self, entry: &TranscriptEntry) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(format!("## {}\n{entry}", self.clock.now()).as_bytes())
+    }
+}
+
+/// A single run of a code block.
+#[derive(Debug)]
+pub(crate) struct TranscriptEntry<'a> {
+    pub(crate) commands: &'a [String],
+
+    /// The code the commands ran, if it isn't the commands themselves, see [TranscriptEntry::source].
+    pub(crate) source: &'a str,
+    pub(crate) output: &'a [String],
+    pub(crate) status: &'a ProcessStatus,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) duration: Duration,
+}
+
+impl TranscriptEntry<'_> {
+    /// Get the code that's recorded along with the commands that ran it.
+    ///
+    /// Shell code is run line by line so it's already recorded as the commands, whereas anything else is written
+    /// into a file that's compiled or given to an interpreter.
+    pub(crate) fn source(code: &Code) -> String {
+        match &code.language {
+            CodeLanguage::Shell(_) => String::new(),
+            _ => code.executable_contents(),
+        }
+    }
+}
+
+impl Display for TranscriptEntry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "command:")?;
+        for command in self.commands {
+            writeln!(f, "  {command}")?;
+        }
+        if !self.source.is_empty() {
+            writeln!(f, "source:")?;
+            for line in self.source.lines() {
+                writeln!(f, "  {line}")?;
+            }
+        }
+        let status = match self.status {
+            ProcessStatus::Running => "running",
+            ProcessStatus::Success => "success",
+            ProcessStatus::Failure => "failure",
+            ProcessStatus::TimedOut => "timed out",
+            ProcessStatus::Cancelled => "cancelled",
+        };
+        match self.exit_code {
+            Some(code) => writeln!(f, "status: {status} (exit code {code})")?,
+            None => writeln!(f, "status: {status}")?,
+        };
+        writeln!(f, "duration: {:.3}s", self.duration.as_secs_f64())?;
+        writeln!(f, "output:")?;
+        for line in self.output {
+            writeln!(f, "  {line}")?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{FixedClock, UtcTime};
+    use std::fs;
+
+    #[test]
+    fn appends_entries() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        let clock = Arc::new(FixedClock(UtcTime(951825600)));
+        let transcript = ExecutionTranscript::new(directory.path().join("transcript.log"), clock);
+        let commands = ["$ python main.py".to_string()];
+        let output = ["hi".to_string()];
+        let entry = TranscriptEntry {
+            commands: &commands,
+            source: "print('hi')\n",
+            output: &output,
+            status: &ProcessStatus::Success,
+            exit_code: Some(0),
+            duration: Duration::from_millis(1500),
+        };
+        transcript.record(&entry).expect("failed to write");
+        let entry =
+            TranscriptEntry { status: &ProcessStatus::Cancelled, exit_code: None, source: "", output: &[], ..entry };
+        transcript.record(&entry).expect("failed to write");
+
+        let contents = fs::read_to_string(directory.path().join("transcript.log")).expect("failed to read");
+        let expected = "\
+## 2000-02-29T12:00:00Z
+command:
+  $ python main.py
+source:
+  print('hi')
+status: success (exit code 0)
+duration: 1.500s
+output:
+  hi
+
+## 2000-02-29T12:00:00Z
+command:
+  $ python main.py
+status: cancelled
+duration: 1.500s
+output:
+
+";
+        assert_eq!(contents, expected);
+    }
+}
@@ -0,0 +1,741 @@
+use crate::{
+    builder::{
+        BuildError, CommandOutputPolicy, CommandParseError, CommentCommand, PresentationBuilder,
+        PresentationBuilderOptions,
+    },
+    config::{ExecutionConfig, UnknownCommandPolicy},
+    markdown::{elements::MarkdownElement, parse::ParseError},
+    presentation::{Presentation, PresentationMetadata, RenderOnDemandState, RenderOperation},
+    schema, CodeHighlighter, MarkdownParser, PresentationTheme, Resources,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    fmt, io,
+    path::Path,
+    process::{Command, Stdio},
+    thread::sleep,
+    time::Duration,
+};
+
+/// How often a running code block is checked for completion when validating snippets.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long code blocks can run for when validating snippets, unless a timeout is configured for them.
+pub(crate) const DEFAULT_SNIPPET_TIMEOUT_MS: u64 = 60_000;
+
+/// How long to wait for a link to be fetched before considering it broken.
+const LINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks a presentation's front matter and comment commands, and then every slide in it.
+///
+/// This reports every problem found rather than just the first one. Slides are only built once
+/// the front matter and commands are valid, which finds the problems schemas can't, like missing
+/// images.
+pub struct PresentationValidator<'a> {
+    parser: MarkdownParser<'a>,
+    default_theme: &'a PresentationTheme,
+    default_highlighter: CodeHighlighter,
+    resources: Resources,
+    unknown_commands: UnknownCommandPolicy,
+    execution: ExecutionConfig,
+}
+
+impl<'a> PresentationValidator<'a> {
+    /// Construct a new validator.
+    pub fn new(
+        parser: MarkdownParser<'a>,
+        default_theme: &'a PresentationTheme,
+        default_highlighter: CodeHighlighter,
+        resources: Resources,
+    ) -> Self {
+        Self {
+            parser,
+            default_theme,
+            default_highlighter,
+            resources,
+            unknown_commands: Default::default(),
+            execution: Default::default(),
+        }
+    }
+
+    /// Set what to do with comments that contain unknown commands.
+    ///
+    /// Unknown commands are reported as warnings rather than errors when using
+    /// [UnknownCommandPolicy::Warn].
+    pub fn with_unknown_commands(mut self, policy: UnknownCommandPolicy) -> Self {
+        self.unknown_commands = policy;
+        self
+    }
+
+    /// Set the configuration used to run executable code blocks when validating snippets.
+    ///
+    /// This also says which languages code blocks can be executed in, which is checked when validating.
+    pub fn with_execution(mut self, config: ExecutionConfig) -> Self {
+        self.execution = config;
+        self
+    }
+
+    /// Get the JSON schema for a presentation's front matter.
+    pub fn front_matter_schema() -> Value {
+        PresentationMetadata::json_schema()
+    }
+
+    /// Get the JSON schema for the commands that can be used in comments.
+    pub fn comment_command_schema() -> Value {
+        CommentCommand::json_schema()
+    }
+
+    /// Validate the presentation in the given path.
+    pub fn validate(&mut self, presentation_path: &Path) -> Result<Vec<ValidationIssue>, ValidateError> {
+        let contents = self.resources.presentation(presentation_path).map_err(ValidateError::ReadPresentation)?;
+        self.validate_contents(&contents)
+    }
+
+    /// Run every executable code block in the presentation in the given path.
+    ///
+    /// Every code block that fails, or whose output doesn't match the `expected_output` block that
+    /// follows it, is reported along with the line it starts at and the slide it's in. Code blocks
+    /// run one at a time, in the order they show up in the presentation, and are stopped after a
+    /// minute unless a timeout is configured for them.
+    pub fn validate_snippets(&mut self, presentation_path: &Path) -> Result<Vec<ValidationIssue>, ValidateError> {
+        let contents = self.resources.presentation(presentation_path).map_err(ValidateError::ReadPresentation)?;
+        self.validate_snippets_contents(&contents)
+    }
+
+    fn validate_snippets_contents(&mut self, contents: &str) -> Result<Vec<ValidationIssue>, ValidateError> {
+        let elements = self.parser.parse(contents)?;
+        // Nobody's around to stop code that never finishes, like in CI pipelines.
+        let mut execution = self.execution.clone();
+        execution.timeout_ms = execution.timeout_ms.or(Some(DEFAULT_SNIPPET_TIMEOUT_MS));
+        let options = PresentationBuilderOptions {
+            unknown_commands: UnknownCommandPolicy::Ignore,
+            command_output: CommandOutputPolicy::Skip,
+            execution,
+            ..Default::default()
+        };
+        let presentation = match self.build(elements, options) {
+            Ok(presentation) => presentation,
+            Err(issues) => return Ok(issues),
+        };
+        let mut issues = Vec::new();
+        for (slide_index, slide) in presentation.iter_slides().enumerate() {
+            for (block_index, code_block) in slide.metadata().code_blocks.iter().enumerate() {
+                let Some(execution) = &code_block.execution else { continue };
+                // Blocks that already ran as another one's dependency don't start again.
+                execution.start_render();
+                while !matches!(execution.poll_state(), RenderOnDemandState::Rendered) {
+                    sleep(POLL_INTERVAL);
+                }
+                let name = format!("code block {} in slide {}", block_index + 1, slide_index + 1);
+                let message = match execution.output_mismatch() {
+                    _ if execution.failed() => match execution.output() {
+                        output if output.is_empty() => format!("{name} failed"),
+                        output => format!("{name} failed with output:\n{}", output.join("\n")),
+                    },
+                    Some(differences) => format!("{name} didn't produce its expected output: {differences}"),
+                    None => continue,
+                };
+                let issue = ValidationIssue::error(IssueLocation::Line(code_block.line), message);
+                issues.push(issue.in_file(code_block.file.as_deref()));
+            }
+        }
+        Ok(issues)
+    }
+
+    /// Check that every link in the presentation in the given path points somewhere.
+    ///
+    /// Links to files are looked up relative to the presentation, and `http` and `https` links are
+    /// fetched using `curl`. Every link that's broken is reported along with the slide it's in.
+    pub fn check_links(&mut self, presentation_path: &Path) -> Result<Vec<ValidationIssue>, ValidateError> {
+        let contents = self.resources.presentation(presentation_path).map_err(ValidateError::ReadPresentation)?;
+        self.check_links_contents(&contents)
+    }
+
+    fn check_links_contents(&mut self, contents: &str) -> Result<Vec<ValidationIssue>, ValidateError> {
+        let elements = self.parser.parse(contents)?;
+        let options = PresentationBuilderOptions {
+            unknown_commands: UnknownCommandPolicy::Ignore,
+            command_output: CommandOutputPolicy::Skip,
+            execution: self.execution.clone(),
+            ..Default::default()
+        };
+        let presentation = match self.build(elements, options) {
+            Ok(presentation) => presentation,
+            Err(issues) => return Ok(issues),
+        };
+        let mut issues = Vec::new();
+        for (slide_index, slide) in presentation.iter_slides().enumerate() {
+            let mut links = Vec::new();
+            for operation in slide.iter_operations() {
+                let RenderOperation::RenderText { line, .. } = operation else { continue };
+                for text in line.iter_texts().map(|text| &text.text) {
+                    // Link references repeat the links in the slide so every one is only checked once.
+                    if text.style.is_link() && !links.contains(&text.text) {
+                        links.push(text.text.clone());
+                    }
+                }
+            }
+            let location = IssueLocation::Slide(slide_index + 1);
+            for link in links {
+                let issue = match self.check_link(&link) {
+                    LinkStatus::Valid => continue,
+                    LinkStatus::Broken(reason) => {
+                        ValidationIssue::error(location.clone(), format!("broken link {link}: {reason}"))
+                    }
+                    LinkStatus::Unknown(reason) => {
+                        ValidationIssue::warning(location.clone(), format!("could not check link {link}: {reason}"))
+                    }
+                };
+                issues.push(issue);
+            }
+        }
+        Ok(issues)
+    }
+
+    fn check_link(&self, link: &str) -> LinkStatus {
+        if link.starts_with("http://") || link.starts_with("https://") {
+            return Self::fetch_link(link);
+        }
+        // Anchors and links with any other scheme, like `mailto:`, can't be checked.
+        if link.starts_with('#') || link.contains(':') {
+            return LinkStatus::Valid;
+        }
+        let path = link.split(['#', '?']).next().unwrap_or(link);
+        match self.resources.exists(path) {
+            true => LinkStatus::Valid,
+            false => LinkStatus::Broken("file does not exist".into()),
+        }
+    }
+
+    fn fetch_link(url: &str) -> LinkStatus {
+        let output = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--location", "--max-time"])
+            .arg(LINK_TIMEOUT.as_secs().to_string())
+            .arg("--output")
+            .arg(if cfg!(windows) { "NUL" } else { "/dev/null" })
+            .arg(url)
+            .stdin(Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => LinkStatus::Valid,
+            Ok(output) => LinkStatus::Broken(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            Err(e) => LinkStatus::Unknown(format!("failed to run curl: {e}")),
+        }
+    }
+
+    fn validate_contents(&mut self, contents: &str) -> Result<Vec<ValidationIssue>, ValidateError> {
+        let elements = self.parser.parse(contents)?;
+        let front_matter_schema = Self::front_matter_schema();
+        let command_schema = Self::comment_command_schema();
+        let mut issues = Vec::new();
+        for element in &elements {
+            let (line, result) = match element {
+                MarkdownElement::FrontMatter(contents) => (1, Self::validate_yaml(&front_matter_schema, contents)),
+                MarkdownElement::Comment { comment, source_position } => {
+                    if PresentationBuilder::should_ignore_comment(comment) {
+                        continue;
+                    }
+                    let line = source_position.start.line;
+                    match (PresentationBuilder::parse_commands(comment), self.unknown_commands) {
+                        (Err(error @ CommandParseError::UnknownCommand(_)), UnknownCommandPolicy::Warn) => {
+                            issues.push(ValidationIssue::warning(IssueLocation::Line(line), error.to_string()));
+                            continue;
+                        }
+                        (Err(CommandParseError::UnknownCommand(_)), UnknownCommandPolicy::Ignore) => continue,
+                        _ => (),
+                    };
+                    let result = PresentationBuilder::comment_lines(comment)
+                        .try_for_each(|line| Self::validate_commands(&command_schema, line));
+                    (line, result)
+                }
+                _ => continue,
+            };
+            if let Err(message) = result {
+                issues.push(ValidationIssue::error(IssueLocation::Line(line), message));
+            }
+        }
+        if issues.iter().all(|issue| issue.severity == IssueSeverity::Warning) {
+            // Unknown commands were already reported above, and commands are never run while validating.
+            let options = PresentationBuilderOptions {
+                unknown_commands: UnknownCommandPolicy::Ignore,
+                command_output: CommandOutputPolicy::Skip,
+                execution: self.execution.clone(),
+                ..Default::default()
+            };
+            if let Err(build_issues) = self.build(elements, options) {
+                issues.extend(build_issues);
+            }
+        }
+        Ok(issues)
+    }
+
+    fn build(
+        &mut self,
+        elements: Vec<MarkdownElement>,
+        options: PresentationBuilderOptions,
+    ) -> Result<Presentation, Vec<ValidationIssue>> {
+        let options = PresentationBuilderOptions { accumulate_errors: true, ..options };
+        let builder = PresentationBuilder::new(
+            self.default_highlighter.clone(),
+            self.default_theme,
+            &mut self.resources,
+            options,
+        );
+        builder.build(elements).map_err(|error| match error {
+            BuildError::Multiple(errors) => errors
+                .into_iter()
+                .map(|error| ValidationIssue::error(IssueLocation::Slide(error.slide), error.error.to_string()))
+                .collect(),
+            error => vec![ValidationIssue::error(IssueLocation::Presentation, error.to_string())],
+        })
+    }
+
+    fn validate_yaml(schema: &Value, contents: &str) -> Result<(), String> {
+        let value = Self::parse_yaml(contents)?;
+        schema::validate(schema, &value).map_err(|e| e.to_string())
+    }
+
+    // A line can contain either a single command or a list of them.
+    fn validate_commands(schema: &Value, contents: &str) -> Result<(), String> {
+        // Lines with placeholders in them can only be checked once their template is used.
+        if contents.contains("{{") {
+            return Ok(());
+        }
+        // Template uses aren't valid YAML so they can't be checked against the schema.
+        if contents.starts_with("use_template:") {
+            return contents.parse::<CommentCommand>().map(|_| ()).map_err(|e| e.to_string());
+        }
+        match Self::parse_yaml(contents)? {
+            Value::Array(commands) => {
+                let list_schema = json!({ "type": "array", "items": schema });
+                schema::validate(&list_schema, &Value::Array(commands)).map_err(|e| e.to_string())
+            }
+            command => schema::validate(schema, &command).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn parse_yaml(contents: &str) -> Result<Value, String> {
+        serde_yaml::from_str(contents).map_err(|e| format!("invalid yaml: {e}"))
+    }
+}
+
+enum LinkStatus {
+    Valid,
+    Broken(String),
+    Unknown(String),
+}
+
+/// A problem found when validating a presentation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    /// Where the problem was found.
+    pub location: IssueLocation,
+
+    /// The description of the problem.
+    pub message: String,
+
+    /// How serious the problem is.
+    pub severity: IssueSeverity,
+
+    /// The file the location is in, if it's not the presentation's own file but one it includes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl ValidationIssue {
+    fn error(location: IssueLocation, message: String) -> Self {
+        Self { location, message, severity: IssueSeverity::Error, file: None }
+    }
+
+    fn warning(location: IssueLocation, message: String) -> Self {
+        Self { location, message, severity: IssueSeverity::Warning, file: None }
+    }
+
+    fn in_file(self, file: Option<&Path>) -> Self {
+        Self { file: file.map(|file| file.to_string_lossy().into()), ..self }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.location)?;
+        if let Some(file) = &self.file {
+            write!(f, " of {file}")?;
+        }
+        match self.severity {
+            IssueSeverity::Error => write!(f, ": {}", self.message),
+            IssueSeverity::Warning => write!(f, ": warning: {}", self.message),
+        }
+    }
+}
+
+/// How serious a problem is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// The presentation can't be presented.
+    Error,
+
+    /// The presentation can be presented but likely doesn't look as intended.
+    Warning,
+}
+
+/// Where a problem was found.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "number", rename_all = "snake_case")]
+pub enum IssueLocation {
+    /// A line in the presentation's file.
+    Line(usize),
+
+    /// A slide, starting at 1.
+    Slide(usize),
+
+    /// The presentation as a whole.
+    Presentation,
+}
+
+impl fmt::Display for IssueLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Line(line) => write!(f, "line {line}"),
+            Self::Slide(slide) => write!(f, "slide {slide}"),
+            Self::Presentation => write!(f, "presentation"),
+        }
+    }
+}
+
+/// The problems found when validating a presentation, for programs to consume.
+#[derive(Debug, Serialize)]
+pub struct ValidationReport<'a> {
+    // Paths aren't always valid UTF-8, which JSON strings have to be.
+    path: String,
+    errors: usize,
+    warnings: usize,
+    issues: &'a [ValidationIssue],
+}
+
+impl<'a> ValidationReport<'a> {
+    /// Construct a report for the problems found in the presentation in the given path.
+    pub fn new(path: &'a Path, issues: &'a [ValidationIssue]) -> Self {
+        let errors = issues.iter().filter(|issue| issue.severity == IssueSeverity::Error).count();
+        let warnings = issues.len() - errors;
+        Self { path: path.to_string_lossy().into(), errors, warnings, issues }
+    }
+
+    /// Serialize this report as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("serialization failed")
+    }
+}
+
+/// An error validating a presentation.
+#[derive(thiserror::Error, Debug)]
+pub enum ValidateError {
+    #[error("failed to read presentation: {0}")]
+    ReadPresentation(io::Error),
+
+    #[error("failed to parse presentation: {0}")]
+    ParsePresentation(#[from] ParseError),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use comrak::Arena;
+    use rstest::rstest;
+    use std::fs;
+
+    fn validate(contents: &str) -> Vec<ValidationIssue> {
+        validate_with_policy(contents, UnknownCommandPolicy::Error)
+    }
+
+    fn validate_snippets(contents: &str) -> Vec<ValidationIssue> {
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples");
+        let mut validator = PresentationValidator::new(MarkdownParser::new(&arena), &theme, highlighter, resources);
+        validator.validate_snippets_contents(contents).expect("validation failed")
+    }
+
+    fn check_links(contents: &str) -> Vec<ValidationIssue> {
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples");
+        let mut validator = PresentationValidator::new(MarkdownParser::new(&arena), &theme, highlighter, resources);
+        validator.check_links_contents(contents).expect("validation failed")
+    }
+
+    fn validate_with_policy(contents: &str, policy: UnknownCommandPolicy) -> Vec<ValidationIssue> {
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new("examples");
+        let mut validator = PresentationValidator::new(MarkdownParser::new(&arena), &theme, highlighter, resources)
+            .with_unknown_commands(policy);
+        validator.validate_contents(contents).expect("validation failed")
+    }
+
+    #[test]
+    fn valid_presentation() {
+        let contents = r#"---
+title: hi
+theme:
+  name: dark
+  override:
+    default:
+      colors:
+        foreground: "beeeff"
+export:
+  columns: 80
+  rows: 24
+---
+
+<!-- column_layout: [1, 2] -->
+
+<!-- [column: 0, pause] -->
+
+<!--
+column: 1
+pause
+-->
+
+<!--
+a comment for humans
+-->
+
+<!-- pause -->
+
+<!-- template: comparison -->
+
+<!-- column: {{column}} -->
+
+<!-- end_template -->
+
+<!-- use_template: comparison with: {column: 0} -->
+"#;
+        assert_eq!(validate(contents), &[]);
+    }
+
+    #[test]
+    fn every_issue_is_reported() {
+        let contents = r#"---
+titel: hi
+---
+
+<!-- paus -->
+
+<!-- column: 1 -->
+
+<!-- column_layout: [1, potato] -->
+
+<!-- [pause, potato] -->
+
+<!-- use_template: a with: potato -->
+"#;
+        let issues = validate(contents);
+        let lines: Vec<_> = issues.iter().map(|issue| issue.location.clone()).collect();
+        let expected: Vec<_> = [1, 5, 9, 11, 13].into_iter().map(IssueLocation::Line).collect();
+        assert_eq!(lines, expected);
+        assert!(issues[3].message.starts_with("$[1]"), "{}", issues[3].message);
+    }
+
+    #[test]
+    fn build_issues() {
+        let contents = r#"
+![](missing.png)
+
+<!-- end_slide -->
+
+hi
+
+<!-- end_slide -->
+
+<!-- column: 0 -->
+"#;
+        let issues = validate(contents);
+        let locations: Vec<_> = issues.iter().map(|issue| issue.location.clone()).collect();
+        assert_eq!(locations, &[IssueLocation::Slide(1), IssueLocation::Slide(3)]);
+        assert!(issues[0].message.contains("missing.png"), "{}", issues[0].message);
+    }
+
+    #[test]
+    fn snippet_issues() {
+        let contents = r#"---
+title: hi
+---
+
+```bash +exec
+true
+```
+
+<!-- end_slide -->
+
+```rust
+fn main() {}
+```
+
+```bash +exec
+echo hi
+false
+```
+"#;
+        // The title in the front matter makes for the first slide.
+        let message = "code block 2 in slide 3 failed with output:\nhi\n[finished with error]";
+        let expected = [ValidationIssue::error(IssueLocation::Line(15), message.into())];
+        assert_eq!(validate_snippets(contents), expected);
+    }
+
+    #[test]
+    fn included_snippet_issues() {
+        let directory = tempfile::tempdir().expect("failed to create tempdir");
+        fs::write(directory.path().join("part.md"), "hi
+
+```bash +exec
+false
+```
+").expect("failed to write");
+        let arena = Arena::new();
+        let theme = Default::default();
+        let highlighter = CodeHighlighter::new("base16-ocean.dark").unwrap();
+        let resources = Resources::new(directory.path());
+        let mut validator = PresentationValidator::new(MarkdownParser::new(&arena), &theme, highlighter, resources);
+        let issues = validator.validate_snippets_contents("intro\n\n<!-- include: part.md -->\n").expect("failed");
+
+        let file = directory.path().join("part.md").to_string_lossy().to_string();
+        let expected = ValidationIssue {
+            location: IssueLocation::Line(3),
+            message: "code block 1 in slide 1 failed with output:\n[finished with error]".into(),
+            severity: IssueSeverity::Error,
+            file: Some(file.clone()),
+        };
+        assert_eq!(issues, &[expected]);
+        assert!(issues[0].to_string().starts_with(&format!("line 3 of {file}: ")), "{}", issues[0]);
+    }
+
+    #[test]
+    fn expected_output_issues() {
+        let contents = r#"---
+theme:
+  name: dark
+---
+
+```bash +exec
+printf 'hi\n\033[1mmom\033[0m  \n\n'
+```
+
+```expected_output
+hi
+mom
+```
+
+```bash +exec
+echo hi
+echo bye
+```
+
+```expected_output
+hi
+mom
+dad
+```
+"#;
+        let message = concat!(
+            r#"code block 2 in slide 1 didn't produce its expected output: "#,
+            r#"line 2: expected "mom", got "bye"; line 3: expected "dad", got nothing"#
+        );
+        assert_eq!(validate_snippets(contents), &[ValidationIssue::error(IssueLocation::Line(15), message.into())]);
+    }
+
+    #[test]
+    fn link_issues() {
+        let contents = r#"
+[demo](demo.md), [missing](missing.md), [heading](#heading), [mail](mailto:bob@example.com)
+
+<!-- end_slide -->
+
+[section](demo.md#section) and [closed port](http://127.0.0.1:1/)
+"#;
+        let issues = check_links(contents);
+        let issues: Vec<_> = issues.iter().map(|issue| (issue.location.clone(), issue.message.as_str())).collect();
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(issues[0], (IssueLocation::Slide(1), "broken link missing.md: file does not exist"));
+        assert_eq!(issues[1].0, IssueLocation::Slide(2));
+        assert!(issues[1].1.starts_with("broken link http://127.0.0.1:1/: "), "{}", issues[1].1);
+    }
+
+    #[rstest]
+    #[case::error(UnknownCommandPolicy::Error, &[(IssueLocation::Line(8), IssueSeverity::Error)])]
+    #[case::warn(UnknownCommandPolicy::Warn, &[(IssueLocation::Line(8), IssueSeverity::Warning)])]
+    #[case::ignore(UnknownCommandPolicy::Ignore, &[])]
+    fn unknown_commands(#[case] policy: UnknownCommandPolicy, #[case] expected: &[(IssueLocation, IssueSeverity)]) {
+        let contents = r#"---
+theme:
+  name: dark
+---
+
+hi
+
+<!-- end_slid -->
+
+<!-- column_layout: [0] -->
+
+<!-- column: 0 -->
+"#;
+        // The build issues show up only if the unknown command doesn't stop the presentation from being built.
+        let issues: Vec<_> = validate_with_policy(contents, policy)
+            .into_iter()
+            .filter(|issue| issue.location != IssueLocation::Slide(1))
+            .map(|issue| (issue.location, issue.severity))
+            .collect();
+        assert_eq!(issues, expected);
+    }
+
+    #[rstest]
+    #[case::unknown_property("potato: 1", "$: unknown property potato")]
+    #[case::nested("theme: { override: { default: { colors: { foreground: red } } } }", "$.theme.override.default")]
+    #[case::missing_rows("export: { columns: 80 }", "$.export: missing property rows")]
+    fn front_matter_issues(#[case] front_matter: &str, #[case] expected: &str) {
+        let issues = validate(&format!("---\n{front_matter}\n---\n"));
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.starts_with(expected), "{}", issues[0].message);
+    }
+
+    #[test]
+    fn json_report() {
+        let issues = [
+            ValidationIssue::error(IssueLocation::Line(3), "unknown command".into()),
+            ValidationIssue::warning(IssueLocation::Slide(2), "image is too large".into()),
+            ValidationIssue::error(IssueLocation::Presentation, "invalid theme".into()),
+        ];
+        let report: Value = serde_json::from_str(&ValidationReport::new(Path::new("slides.md"), &issues).to_json())
+            .expect("invalid json");
+        let expected = serde_json::json!({
+            "path": "slides.md",
+            "errors": 2,
+            "warnings": 1,
+            "issues": [
+                { "location": { "kind": "line", "number": 3 }, "message": "unknown command", "severity": "error" },
+                {
+                    "location": { "kind": "slide", "number": 2 },
+                    "message": "image is too large",
+                    "severity": "warning",
+                },
+                { "location": { "kind": "presentation" }, "message": "invalid theme", "severity": "error" },
+            ],
+        });
+        assert_eq!(report, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn json_report_non_utf8_path() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let path = Path::new(OsStr::from_bytes(b"slides\xff.md"));
+        let report: Value = serde_json::from_str(&ValidationReport::new(path, &[]).to_json()).expect("invalid json");
+        assert_eq!(report["path"], "slides\u{fffd}.md");
+    }
+}